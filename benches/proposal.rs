@@ -0,0 +1,78 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks of the anchoring proposal building on the anchoring heights.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use exonum::helpers::Height;
+use exonum_btc_anchoring::test_helpers::{get_anchoring_schema, AnchoringTestKit};
+
+/// Creates a testkit with the anchoring proposal for the second anchoring height.
+fn testkit_at_anchoring_height(nodes_num: u16) -> AnchoringTestKit {
+    let mut anchoring_testkit = AnchoringTestKit::new(nodes_num, 5);
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(700_000).0,
+    );
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit.inner.create_blocks_until(Height(5));
+    anchoring_testkit
+}
+
+fn bench_proposal(c: &mut Criterion) {
+    let anchoring_testkit = testkit_at_anchoring_height(4);
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+
+    let mut group = c.benchmark_group("proposal");
+    group.bench_function("build", |b| {
+        b.iter(|| {
+            let state = schema.actual_state();
+            schema.proposed_anchoring_transaction(snapshot.for_core(), &state)
+        })
+    });
+    group.bench_function("cached", |b| {
+        b.iter(|| schema.recover_runtime_state(snapshot.for_core()).proposal)
+    });
+    group.finish();
+}
+
+fn bench_anchoring_block(c: &mut Criterion) {
+    let mut group = c.benchmark_group("anchoring_block");
+    for &nodes_num in &[4, 8, 16] {
+        let mut anchoring_testkit = testkit_at_anchoring_height(nodes_num);
+        let signatures = anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        group.bench_function(format!("sign_and_finalize/{}", nodes_num), |b| {
+            b.iter(|| {
+                anchoring_testkit
+                    .inner
+                    .create_block_with_transactions(signatures.clone());
+                anchoring_testkit.inner.rollback();
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_proposal, bench_anchoring_block);
+criterion_main!(benches);
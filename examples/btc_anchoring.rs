@@ -17,6 +17,8 @@ use exonum_cli::{NodeBuilder, Spec};
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     exonum::helpers::init_logger()?;
+    // Reject the invalid API settings before the node is started.
+    exonum_btc_anchoring::api::check_api_env()?;
     NodeBuilder::new()
         .with(Spec::new(exonum_btc_anchoring::BtcAnchoringService))
         .run()
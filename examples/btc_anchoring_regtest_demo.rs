@@ -0,0 +1,209 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Demo of the whole anchoring pipeline on top of the local `bitcoind` in the regtest mode.
+//!
+//! The demo boots a single-node Exonum chain with the anchoring service, funds the anchoring
+//! wallet from the `bitcoind` wallet and runs the sync tasks, which sign the anchoring
+//! proposals and broadcast the anchoring transactions. Bitcoin blocks are mined by the demo
+//! itself, and each anchoring transaction is printed as soon as it gets confirmed.
+//!
+//! Start `bitcoind` with the following options and run the demo:
+//!
+//! ```text
+//! bitcoind -regtest -txindex -rpcuser=user -rpcpassword=password
+//! cargo run --example btc_anchoring_regtest_demo run
+//! ```
+
+use anyhow::anyhow;
+use bitcoincore_rpc::{
+    bitcoin::{Address as RpcAddress, Amount},
+    Auth as BitcoinRpcAuth, Client as BitcoinRpcClient, RpcApi,
+};
+use exonum_btc_anchoring::{
+    api::PrivateApi,
+    btc,
+    sync::{
+        AnchoringChainUpdateTask, BitcoinRelay, ChainUpdateError, KeyPool, SyncWithBitcoinTask,
+        TransactionStatus,
+    },
+    test_helpers::AnchoringTestKit,
+};
+use structopt::StructOpt;
+use tokio::time::delay_for;
+
+use std::time::Duration;
+
+/// Runs the anchoring demo.
+#[derive(Debug, StructOpt)]
+struct RunCommand {
+    /// Bitcoin RPC url.
+    #[structopt(long, default_value = "http://localhost:18443")]
+    bitcoin_rpc_host: String,
+    /// Bitcoin RPC username.
+    #[structopt(long, default_value = "user")]
+    bitcoin_rpc_user: String,
+    /// Bitcoin RPC password.
+    #[structopt(long, default_value = "password")]
+    bitcoin_rpc_password: String,
+    /// Interval between anchored Exonum blocks.
+    #[structopt(long, default_value = "5")]
+    anchoring_interval: u64,
+    /// Number of confirmed anchoring transactions after which the demo stops.
+    #[structopt(long, default_value = "5")]
+    anchors: u64,
+    /// Amount of the initial funding transaction in satoshis.
+    #[structopt(long, default_value = "1000000")]
+    funds: u64,
+    /// Delay between Exonum blocks in milliseconds.
+    #[structopt(long, default_value = "200")]
+    block_delay: u64,
+}
+
+#[derive(Debug, StructOpt)]
+enum Commands {
+    /// Run the anchoring demo with the local regtest Bitcoin node.
+    Run(RunCommand),
+}
+
+impl RunCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let bitcoin_client = BitcoinRpcClient::new(
+            self.bitcoin_rpc_host.clone(),
+            BitcoinRpcAuth::UserPass(
+                self.bitcoin_rpc_user.clone(),
+                self.bitcoin_rpc_password.clone(),
+            ),
+        )?;
+        let miner_address = bitcoin_client.get_new_address(None, None)?;
+
+        // Coinbase outputs become spendable only after 100 confirmations.
+        let funds = Amount::from_sat(self.funds);
+        if bitcoin_client.get_balance(None, None)? < funds {
+            println!("Mining blocks to obtain balance for the anchoring wallet...");
+            bitcoin_client.generate_to_address(101, &miner_address)?;
+        }
+
+        let mut testkit =
+            AnchoringTestKit::with_network(1, self.anchoring_interval, bitcoin::Network::Regtest);
+        let api = testkit.inner.api();
+        let client = api.client().clone();
+
+        // Fund the anchoring wallet and confirm the funding transaction.
+        let anchoring_address = testkit.actual_anchoring_config().anchoring_address();
+        println!("Anchoring address: {}", anchoring_address);
+        let funding_txid = bitcoin_client.send_to_address(
+            &anchoring_address.to_string().parse::<RpcAddress>()?,
+            funds,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )?;
+        bitcoin_client.generate_to_address(1, &miner_address)?;
+        let funding_tx: btc::Transaction = bitcoin_client
+            .get_raw_transaction_hex(&funding_txid, None)?
+            .parse()?;
+        println!("Funding transaction: {}", funding_tx.id());
+        testkit.inner.create_block_with_transactions(
+            testkit.create_funding_confirmation_txs_with(funding_tx),
+        );
+
+        let key_pool = KeyPool::new(testkit.anchoring_keypairs())?;
+        let chain_updater = AnchoringChainUpdateTask::new(key_pool, client.clone());
+        let bitcoin_relay = SyncWithBitcoinTask::new(
+            BitcoinRpcClient::new(
+                self.bitcoin_rpc_host.clone(),
+                BitcoinRpcAuth::UserPass(self.bitcoin_rpc_user, self.bitcoin_rpc_password),
+            )?,
+            client.clone(),
+        );
+
+        let mut latest_synced_tx_index = None;
+        let mut confirmed_anchors = 0;
+        while confirmed_anchors < self.anchors {
+            // Commit the signatures of the anchoring proposal, if any.
+            testkit.inner.create_block();
+
+            match chain_updater.process().await {
+                Ok(_) => {}
+                Err(ChainUpdateError::Internal(e)) => return Err(e),
+                Err(e) => log::warn!("Unable to sign the anchoring proposal: {:?}", e),
+            }
+            latest_synced_tx_index = bitcoin_relay
+                .process(latest_synced_tx_index)
+                .await
+                .map_err(|e| anyhow!("Unable to sync with the Bitcoin network: {:?}", e))?;
+            bitcoin_client.generate_to_address(1, &miner_address)?;
+
+            // Report newly confirmed anchoring transactions.
+            let chain_len = client
+                .transactions_count()
+                .await
+                .map_err(|e| anyhow!("{}", e))?
+                .value;
+            while confirmed_anchors < chain_len {
+                let tx = client
+                    .transaction_with_index(confirmed_anchors)
+                    .await
+                    .map_err(|e| anyhow!("{}", e))?
+                    .ok_or_else(|| anyhow!("Anchoring transaction is absent"))?;
+                let confirmations = match bitcoin_client.transaction_status(tx.id()).await? {
+                    TransactionStatus::Committed(confirmations) => confirmations,
+                    _ => break,
+                };
+
+                let payload = tx.anchoring_payload().unwrap();
+                println!(
+                    "Anchor #{} for the block {} is confirmed: txid {}, confirmations {}",
+                    confirmed_anchors,
+                    payload.block_height,
+                    tx.id(),
+                    confirmations
+                );
+                // Record the Bitcoin block of the anchor in the service schema.
+                bitcoin_relay
+                    .confirm_anchor(confirmed_anchors)
+                    .await
+                    .map_err(|e| anyhow!("Unable to confirm anchor: {:?}", e))?;
+                confirmed_anchors += 1;
+            }
+
+            delay_for(Duration::from_millis(self.block_delay)).await;
+        }
+
+        println!(
+            "Demo is finished at the Exonum height {}, {} anchors are confirmed",
+            testkit.inner.height(),
+            confirmed_anchors
+        );
+        Ok(())
+    }
+}
+
+impl Commands {
+    async fn run(self) -> anyhow::Result<()> {
+        match self {
+            Commands::Run(cmd) => cmd.run().await,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    exonum::helpers::init_logger()?;
+    Commands::from_args().run().await
+}
@@ -14,14 +14,42 @@
 
 use anyhow::{anyhow, bail};
 use async_trait::async_trait;
-use bitcoincore_rpc::{Auth as BitcoinRpcAuth, Client as BitcoinRpcClient};
-use exonum::crypto::Hash;
+use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, Fingerprint};
+use bitcoincore_rpc::Auth as BitcoinRpcAuth;
+use exonum::crypto::{Hash, PublicKey};
+#[cfg(feature = "redis")]
+use exonum_btc_anchoring::sync::RedisCheckpointStore;
+#[cfg(feature = "sled")]
+use exonum_btc_anchoring::sync::SledCheckpointStore;
+#[cfg(feature = "zmq")]
+use exonum_btc_anchoring::sync::{BitcoinEvent, ZmqNotifications};
 use exonum_btc_anchoring::{
-    api::{AnchoringChainLength, AnchoringProposalState, IndexQuery, PrivateApi},
-    blockchain::SignInput,
+    api::{
+        AnchorConfirmationState, AnchorStatusQuery, AnchoringChainLength, AnchoringProposalState,
+        FundingTransactionQuery, FundingTransactionState, IndexQuery, ManagementApi, PrivateApi,
+        RecoveryBundleQuery, ServiceVersion, SpecialTxApprovalState, SpecialTxQuery,
+    },
+    blockchain::{
+        AnchorInclusion, AttestKey, ConfirmAnchor, ReportFeeEstimate, RequestCpfp, SignInput,
+        SigningProgress,
+    },
     btc,
-    config::Config as AnchoringConfig,
-    sync::{AnchoringChainUpdateTask, ChainUpdateError, SyncWithBitcoinError, SyncWithBitcoinTask},
+    config::{Config as AnchoringConfig, KeyDerivation},
+    psbt::ProposalPsbt,
+    recovery::{EncryptedRecoveryBundle, RecoveryKey},
+    sync::{
+        self, check_bootstrap, AnchoringChainUpdateTask, BitcoinRelay, BitcoinRpcClient,
+        BroadcastPolicy, ChainUpdateError, CheckpointStore, ElectrumError, ElectrumRelay,
+        FallbackRelay, FeeHistogram, FileCheckpointStore, HwiSigner, KeyPool, Keystore,
+        MultiSyncRunner, OfflineSignatures, PrivateApiAuth, RemoteSigner, ReviewPolicy,
+        ReviewQueue, SighashExport, SyncRunner, SyncWithBitcoinError, SyncWithBitcoinTask,
+        TransactionStatus, UnlockedKeystore, VerificationPolicy,
+    },
+};
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
 };
 use serde::{de::DeserializeOwned, ser::Serialize};
 use serde_derive::{Deserialize, Serialize};
@@ -29,11 +57,12 @@ use structopt::StructOpt;
 use tokio::time::delay_for;
 
 use std::{
-    collections::HashMap,
-    convert::TryFrom,
+    convert::{Infallible, TryFrom},
     fs::{self, File},
     io::prelude::*,
+    net::SocketAddr,
     path::{Path, PathBuf},
+    sync::Arc,
     time::Duration,
 };
 
@@ -44,6 +73,8 @@ pub struct ApiClient {
     prefix: String,
     /// Underlying HTTP client.
     client: reqwest::Client,
+    /// Authentication of the requests, if it is required by the private API.
+    auth: Option<PrivateApiAuth>,
 }
 
 impl ApiClient {
@@ -57,70 +88,93 @@ impl ApiClient {
                 instance_name.as_ref()
             ),
             client: reqwest::Client::new(),
+            auth: None,
         }
     }
 
+    /// Authenticates the requests of the client with the given credentials.
+    pub fn with_auth(mut self, auth: PrivateApiAuth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
     fn endpoint(&self, name: impl AsRef<str>) -> String {
         format!("{}/{}", self.prefix, name.as_ref())
     }
 
-    async fn get<R>(&self, endpoint: &str) -> Result<R, reqwest::Error>
+    async fn send<R>(&self, request: reqwest::RequestBuilder) -> anyhow::Result<R>
     where
         R: DeserializeOwned + Send + 'static,
     {
-        self.client
-            .get(&self.endpoint(endpoint))
-            .send()
-            .await?
-            .json()
-            .await
+        let mut request = request.build()?;
+        if let Some(auth) = self.auth.as_ref() {
+            auth.authenticate(&mut request)?;
+        }
+        Ok(self.client.execute(request).await?.json().await?)
     }
 
-    async fn get_query<Q, R>(&self, endpoint: &str, query: &Q) -> Result<R, reqwest::Error>
+    async fn get<R>(&self, endpoint: &str) -> anyhow::Result<R>
+    where
+        R: DeserializeOwned + Send + 'static,
+    {
+        self.send(self.client.get(&self.endpoint(endpoint))).await
+    }
+
+    async fn get_query<Q, R>(&self, endpoint: &str, query: &Q) -> anyhow::Result<R>
     where
         Q: Serialize,
         R: DeserializeOwned + Send + 'static,
     {
-        self.client
-            .get(&self.endpoint(endpoint))
-            .query(query)
-            .send()
-            .await?
-            .json()
+        self.send(self.client.get(&self.endpoint(endpoint)).query(query))
             .await
     }
 
-    async fn post<Q, R>(&self, endpoint: &str, body: &Q) -> Result<R, reqwest::Error>
+    async fn post<Q, R>(&self, endpoint: &str, body: &Q) -> anyhow::Result<R>
     where
         Q: Serialize,
         R: DeserializeOwned + Send + 'static,
     {
-        self.client
-            .post(&self.endpoint(endpoint))
-            .json(&body)
-            .send()
-            .await?
-            .json()
+        self.send(self.client.post(&self.endpoint(endpoint)).json(&body))
             .await
     }
 }
 
 #[async_trait]
 impl PrivateApi for ApiClient {
-    type Error = reqwest::Error;
+    type Error = anyhow::Error;
 
     async fn sign_input(&self, sign_input: SignInput) -> Result<Hash, Self::Error> {
-        self.post("sign-input", &sign_input).await
+        self.post("signing/sign-input", &sign_input).await
     }
 
-    async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error> {
-        self.post("add-funds", &transaction).await
+    async fn sign_inputs(&self, sign_inputs: Vec<SignInput>) -> Result<Vec<Hash>, Self::Error> {
+        self.post("signing/sign-inputs", &sign_inputs).await
+    }
+
+    async fn confirm_anchor(&self, confirmation: ConfirmAnchor) -> Result<Hash, Self::Error> {
+        self.post("signing/confirm-anchor", &confirmation).await
+    }
+
+    async fn report_fee_estimate(&self, estimate: ReportFeeEstimate) -> Result<Hash, Self::Error> {
+        self.post("signing/report-fee-estimate", &estimate).await
+    }
+
+    async fn request_cpfp(&self, request: RequestCpfp) -> Result<Hash, Self::Error> {
+        self.post("signing/request-cpfp", &request).await
     }
 
     async fn anchoring_proposal(&self) -> Result<AnchoringProposalState, Self::Error> {
         self.get("anchoring-proposal").await
     }
 
+    async fn anchoring_proposal_psbt(&self) -> Result<Option<ProposalPsbt>, Self::Error> {
+        self.get("anchoring-proposal/psbt").await
+    }
+
+    async fn submit_psbt(&self, psbt: ProposalPsbt) -> Result<Vec<Hash>, Self::Error> {
+        self.post("signing/psbt", &psbt).await
+    }
+
     async fn config(&self) -> Result<AnchoringConfig, Self::Error> {
         self.get("config").await
     }
@@ -135,6 +189,67 @@ impl PrivateApi for ApiClient {
     async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error> {
         self.get("transactions-count").await
     }
+
+    async fn unspent_funding_transaction(&self) -> Result<Option<btc::Transaction>, Self::Error> {
+        self.get("funds/unspent").await
+    }
+
+    async fn funding_transaction_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<FundingTransactionState, Self::Error> {
+        self.get_query("funds/status", &FundingTransactionQuery { txid })
+            .await
+    }
+
+    async fn anchor_confirmation_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<AnchorConfirmationState, Self::Error> {
+        self.get_query("anchor/status", &AnchorStatusQuery { txid })
+            .await
+    }
+
+    async fn version(&self) -> Result<ServiceVersion, Self::Error> {
+        self.get("version").await
+    }
+
+    async fn signing_progress(&self) -> Result<Option<SigningProgress>, Self::Error> {
+        self.get("signing-progress").await
+    }
+
+    async fn special_tx_approval_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<SpecialTxApprovalState, Self::Error> {
+        self.get_query("special-tx/status", &SpecialTxQuery { txid })
+            .await
+    }
+}
+
+#[async_trait]
+impl ManagementApi for ApiClient {
+    type Error = anyhow::Error;
+
+    async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error> {
+        self.post("management/add-funds", &transaction).await
+    }
+
+    async fn attest_key(&self, attestation: AttestKey) -> Result<Hash, Self::Error> {
+        self.post("management/attest-key", &attestation).await
+    }
+
+    async fn recovery_bundle(
+        &self,
+        query: RecoveryBundleQuery,
+    ) -> Result<EncryptedRecoveryBundle, Self::Error> {
+        self.post("management/recovery-bundle", &query).await
+    }
+
+    async fn approve_special_tx(&self, txid: btc::Sha256d) -> Result<Hash, Self::Error> {
+        self.post("management/approve-special-tx", &SpecialTxQuery { txid })
+            .await
+    }
 }
 
 /// Generate initial configuration for the btc anchoring sync utility.
@@ -147,6 +262,10 @@ struct GenerateConfigCommand {
     /// Anchoring node private API url address.
     #[structopt(long, short = "e", default_value = "http://localhost:8081")]
     exonum_private_api: String,
+    /// Anchoring node private API url address for the funds and configuration
+    /// management requests. By default, the `exonum_private_api` address is used.
+    #[structopt(long)]
+    exonum_management_api: Option<String>,
     /// Bitcoin network type.
     #[structopt(long, short = "n", default_value = "testnet")]
     bitcoin_network: bitcoin::Network,
@@ -162,6 +281,22 @@ struct GenerateConfigCommand {
     /// Bitcoin RPC password.
     #[structopt(long)]
     bitcoin_rpc_password: Option<String>,
+    /// Address of the Electrum server in the `host:port` format, which is used
+    /// instead of the Bitcoin RPC.
+    #[structopt(long, conflicts_with = "bitcoin-rpc-host")]
+    electrum_address: Option<String>,
+    /// BIP-32 extended private key from which the Bitcoin keys are derived.
+    /// If it is not specified, a random Bitcoin key is generated.
+    #[structopt(long)]
+    bitcoin_xprv: Option<String>,
+    /// Derivation path of the account from which the Bitcoin keys are derived.
+    /// By default, the first BIP-84 account of the Bitcoin network is used.
+    #[structopt(long)]
+    account_path: Option<String>,
+    /// Path to the encrypted keystore in which the Bitcoin keys are stored instead of
+    /// the configuration file.
+    #[structopt(long, short = "k")]
+    keystore: Option<PathBuf>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -169,10 +304,45 @@ struct RunCommand {
     /// Path to a sync utility configuration file.
     #[structopt(long, short = "c")]
     config: PathBuf,
+    /// Never sign or broadcast anything, only verify anchoring proposals and
+    /// the anchoring chain and report found inconsistencies.
+    #[structopt(long)]
+    verify_only: bool,
+    /// Maximum total fee of the anchoring transaction in satoshis that is considered
+    /// correct in the verify-only mode.
+    #[structopt(long)]
+    max_fee: Option<u64>,
+    /// Fee rate in satoshis per virtual byte. If specified, broadcasting of the anchoring
+    /// transactions is delayed while the estimated fee rate required to get into the next
+    /// Bitcoin block exceeds this value.
+    #[structopt(long)]
+    target_fee_rate: Option<u64>,
+    /// Maximum delay of broadcasting of the anchoring transaction in seconds.
+    #[structopt(long, default_value = "3600")]
+    max_broadcast_delay: u64,
+    /// Amount of satoshis. If specified, anchoring proposals which spend more than this
+    /// amount from the anchoring wallets are not signed until they are approved via
+    /// the local review endpoint or the review window expires.
+    #[structopt(long)]
+    review_threshold: Option<u64>,
+    /// Duration of the review window in seconds.
+    #[structopt(long, default_value = "3600")]
+    review_window: u64,
+    /// Listen address of the local review endpoint.
+    #[structopt(long, default_value = "127.0.0.1:8095")]
+    review_listen_address: SocketAddr,
 }
 
 /// Generates a new Bitcoin key pair and add them to the key pool of the specified
-/// configuration file.
+/// configuration file. If the configuration file contains an extended private key,
+/// the key pair is derived from it by the next index.
+#[derive(Debug, StructOpt)]
+struct BootstrapCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+}
+
 #[derive(Debug, StructOpt)]
 struct GenerateKeypairCommand {
     /// Path to a sync utility configuration file.
@@ -180,42 +350,371 @@ struct GenerateKeypairCommand {
     config: PathBuf,
 }
 
+/// Attests a Bitcoin key from the key pool of the specified configuration file
+/// for the service key of the anchoring node.
+#[derive(Debug, StructOpt)]
+struct AttestKeyCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Service key of the anchoring node.
+    #[structopt(long)]
+    service_key: PublicKey,
+    /// Bitcoin public key to attest.
+    #[structopt(long)]
+    bitcoin_key: btc::PublicKey,
+}
+
+/// Prints the BIP-32 derivation of a Bitcoin key derived from the extended private key
+/// of the specified configuration file, which is specified together with the key in
+/// the anchoring configuration.
+#[derive(Debug, StructOpt)]
+struct KeyDerivationCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Derived Bitcoin public key.
+    #[structopt(long)]
+    bitcoin_key: btc::PublicKey,
+}
+
+/// Approves broadcasting of the anchoring transaction held by the emergency brake
+/// on behalf of the anchoring node.
+#[derive(Debug, StructOpt)]
+struct ApproveSpecialTxCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Identifier of the anchoring transaction to approve.
+    #[structopt(long)]
+    txid: btc::Sha256d,
+}
+
+/// Derives the anchoring address from the given list of Bitcoin keys, so anyone can
+/// verify the destination of the anchoring transactions.
+#[derive(Debug, StructOpt)]
+struct DeriveAddressCommand {
+    /// Bitcoin network type.
+    #[structopt(long, short = "n", default_value = "testnet")]
+    bitcoin_network: bitcoin::Network,
+    /// Bitcoin public keys of the anchoring nodes in the same order as in
+    /// the anchoring configuration.
+    #[structopt(long = "bitcoin-key", short = "k", required = true)]
+    bitcoin_keys: Vec<btc::PublicKey>,
+    /// Number of signatures required to spend anchoring outputs. By default, it is
+    /// equal to the byzantine quorum of the given keys.
+    #[structopt(long, short = "q")]
+    quorum: Option<usize>,
+}
+
+/// Encrypts the memo to use it in the `encrypted_memo` field of the anchoring
+/// configuration.
+#[derive(Debug, StructOpt)]
+struct EncryptMemoCommand {
+    /// Hex-encoded 32-byte memo key shared among auditors.
+    #[structopt(long, short = "k")]
+    key: btc::MemoKey,
+    /// Memo content.
+    #[structopt(long, short = "m")]
+    memo: String,
+}
+
+/// Computes the hash of the recovery key to use it in the `recovery_export` field of
+/// the anchoring configuration.
+#[derive(Debug, StructOpt)]
+struct HashRecoveryKeyCommand {
+    /// Hex-encoded 32-byte recovery key kept by the escrow agents.
+    #[structopt(long, short = "k")]
+    key: RecoveryKey,
+}
+
+/// Downloads the disaster recovery bundle encrypted by the recovery key.
+#[derive(Debug, StructOpt)]
+struct ExportRecoveryBundleCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Index of the export authorized by the supervisor.
+    #[structopt(long, short = "i")]
+    export_index: u64,
+    /// Hex-encoded 32-byte recovery key kept by the escrow agents.
+    #[structopt(long, short = "k")]
+    key: RecoveryKey,
+    /// Path to the file in which the encrypted bundle will be saved.
+    #[structopt(long, short = "o", default_value = "recovery_bundle.json")]
+    output: PathBuf,
+}
+
+/// Exports the signature hashes of the anchoring proposal to a file, which is carried
+/// to the air-gapped machine for the offline signing.
+#[derive(Debug, StructOpt)]
+struct ExportSighashesCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Path to the file in which the signature hashes will be saved.
+    #[structopt(long, short = "o", default_value = "sighashes.json")]
+    output: PathBuf,
+}
+
+/// Signs the exported signature hashes by the Bitcoin keys of the specified configuration
+/// file. This command doesn't connect to the network, so it can be run on the air-gapped
+/// machine.
+#[derive(Debug, StructOpt)]
+struct SignOfflineCommand {
+    /// Path to a sync utility configuration file with the Bitcoin keys.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Path to the file with the exported signature hashes.
+    #[structopt(long, short = "i", default_value = "sighashes.json")]
+    input: PathBuf,
+    /// Path to the file in which the signatures will be saved.
+    #[structopt(long, short = "o", default_value = "signatures.json")]
+    output: PathBuf,
+}
+
+/// Moves the plaintext Bitcoin keys of the configuration file to the encrypted keystore.
+#[derive(Debug, StructOpt)]
+struct MigrateKeystoreCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Path to the encrypted keystore which will be created.
+    #[structopt(long, short = "k", default_value = "btc_anchoring_keystore.json")]
+    keystore: PathBuf,
+}
+
+/// Verifies the signatures made offline against the actual anchoring proposal and
+/// submits them to the anchoring service.
+#[derive(Debug, StructOpt)]
+struct ImportSignaturesCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Path to the file with the signatures.
+    #[structopt(long, short = "i", default_value = "signatures.json")]
+    input: PathBuf,
+}
+
+/// Runs the authenticating proxy in front of the private API of the anchoring node,
+/// so the sync utility can run on a different host than the node with an older service
+/// version, which doesn't verify the requests itself. The proxy verifies the requests
+/// by the shared secret and forwards them to the private API.
+#[derive(Debug, StructOpt)]
+struct AuthProxyCommand {
+    /// Path to the file with the authentication settings, which has the same format as
+    /// the `private_api_auth` section of the sync utility configuration.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Anchoring node private API url address to which the requests are forwarded.
+    #[structopt(long, short = "e", default_value = "http://127.0.0.1:8081")]
+    exonum_private_api: String,
+    /// Listen address of the proxy.
+    #[structopt(long, default_value = "0.0.0.0:8091")]
+    listen_address: SocketAddr,
+}
+
 #[derive(Debug, StructOpt)]
 enum Commands {
     /// Generate initial configuration for the btc anchoring sync utility.
     GenerateConfig(GenerateConfigCommand),
     /// Run btc anchoring sync utility.
     Run(RunCommand),
+    /// Check the preconditions of the first anchoring transaction and print
+    /// the checklist.
+    Bootstrap(BootstrapCommand),
     /// Generate a new Bitcoin key pair and add them to the key pool of the specified
     /// configuration file.
     GenerateKeypair(GenerateKeypairCommand),
+    /// Attest a Bitcoin key from the key pool of the specified configuration file
+    /// for the service key of the anchoring node.
+    AttestKey(AttestKeyCommand),
+    /// Print the BIP-32 derivation of a Bitcoin key for the anchoring configuration.
+    KeyDerivation(KeyDerivationCommand),
+    /// Derive the anchoring address from the given list of Bitcoin keys.
+    DeriveAddress(DeriveAddressCommand),
+    /// Encrypt the memo for the anchoring configuration.
+    EncryptMemo(EncryptMemoCommand),
+    /// Compute the hash of the recovery key for the recovery bundle export authorization.
+    HashRecoveryKey(HashRecoveryKeyCommand),
+    /// Download the encrypted disaster recovery bundle authorized by the supervisor.
+    ExportRecoveryBundle(ExportRecoveryBundleCommand),
+    /// Approve broadcasting of the anchoring transaction held by the emergency brake.
+    ApproveSpecialTx(ApproveSpecialTxCommand),
+    /// Export the signature hashes of the anchoring proposal for the offline signing.
+    ExportSighashes(ExportSighashesCommand),
+    /// Sign the exported signature hashes on the air-gapped machine.
+    SignOffline(SignOfflineCommand),
+    /// Verify and submit the signatures made offline.
+    ImportSignatures(ImportSignaturesCommand),
+    /// Move the plaintext Bitcoin keys of the configuration file to the encrypted keystore.
+    MigrateKeystore(MigrateKeystoreCommand),
+    /// Run the authenticating proxy in front of the private API of the anchoring node.
+    AuthProxy(AuthProxyCommand),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct SyncConfig {
     exonum_private_api: String,
+    #[serde(default)]
+    exonum_management_api: Option<String>,
+    /// Authentication of the private API requests, if it is required by the node
+    /// or by the authenticating proxy in front of it.
+    #[serde(default)]
+    private_api_auth: Option<PrivateApiAuthConfig>,
+    /// Authentication of the funds and configuration management requests, which has
+    /// its own credentials on the node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    management_api_auth: Option<PrivateApiAuthConfig>,
     instance_name: String,
-    #[serde(with = "flatten_keypairs")]
-    bitcoin_key_pool: HashMap<btc::PublicKey, btc::PrivateKey>,
+    /// Address of the ZeroMQ notifications of the Bitcoin node, requires the `zmq` feature.
+    #[serde(default)]
+    bitcoin_zmq_address: Option<String>,
+    /// Bitcoin keypairs in order of their priority. If the keystore is specified,
+    /// the keypairs stored in it are added after the ones from the configuration file.
+    #[serde(default, with = "flatten_keypairs")]
+    bitcoin_key_pool: Vec<(btc::PublicKey, btc::PrivateKey)>,
+    #[serde(default)]
+    bitcoin_hd_keys: Option<HdKeysConfig>,
+    /// Path to the encrypted keystore with the Bitcoin keys.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keystore: Option<PathBuf>,
+    /// Keystore unlocked on loading the configuration.
+    #[serde(skip)]
+    unlocked_keystore: Option<UnlockedKeystore>,
+    /// Hardware wallet which signs the anchoring proposals instead of the key pool.
+    #[serde(default)]
+    hardware_wallet: Option<HardwareWalletConfig>,
+    /// Remote signing service which signs the anchoring proposals instead of the key pool.
+    #[serde(default)]
+    remote_signer: Option<RemoteSignerConfig>,
     bitcoin_rpc_config: Option<BitcoinRpcConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    fallback_bitcoin_rpc_configs: Vec<BitcoinRpcConfig>,
+    #[serde(default)]
+    electrum: Option<ElectrumConfig>,
+    #[serde(default)]
+    checkpoint: Option<CheckpointConfig>,
+    /// Additional anchoring instances synced by the `run` subcommand alongside the main one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    instances: Vec<InstanceConfig>,
 }
 
 impl SyncConfig {
     /// Extracts Bitcoin network type from the one of Bitcoin private keys in this config.
     fn bitcoin_network(&self) -> Option<bitcoin::Network> {
         self.bitcoin_key_pool
-            .values()
-            .next()
-            .map(|key| key.0.network)
+            .first()
+            .map(|(_, key)| key.0.network)
+            .or_else(|| {
+                let hd_keys = self.bitcoin_hd_keys.as_ref()?;
+                hd_keys.xprv().ok().map(|xprv| xprv.network)
+            })
+    }
+
+    /// Returns the Bitcoin keys from the key pool together with the keys derived
+    /// from the extended private key. The keys from the key pool have higher priority.
+    fn key_pool(&self) -> anyhow::Result<KeyPool> {
+        let mut keypairs = self.bitcoin_key_pool.clone();
+        if let Some(hd_keys) = self.bitcoin_hd_keys.as_ref() {
+            for index in &hd_keys.key_indexes {
+                keypairs.push(hd_keys.derive_keypair(*index)?);
+            }
+        }
+        KeyPool::new(keypairs).map_err(From::from)
+    }
+
+    /// Returns the Bitcoin relay specified by this config. The relay fails over from
+    /// the main Bitcoin node to the fallback ones and then to the Electrum server.
+    fn bitcoin_relay(&self) -> anyhow::Result<Option<FallbackRelay<Relay>>> {
+        let mut backends = self
+            .bitcoin_rpc_config
+            .iter()
+            .chain(&self.fallback_bitcoin_rpc_configs)
+            .cloned()
+            .map(|rpc_config| BitcoinRpcClient::try_from(rpc_config).map(Relay::Bitcoind))
+            .collect::<Result<Vec<_>, _>>()?;
+        backends.extend(
+            self.electrum
+                .as_ref()
+                .map(|electrum| Relay::Electrum(electrum.relay())),
+        );
+
+        if backends.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(FallbackRelay::new(backends)))
+        }
+    }
+
+    /// Returns the client for the private API requests.
+    fn private_client(&self) -> anyhow::Result<ApiClient> {
+        self.api_client(&self.exonum_private_api, self.private_api_auth.as_ref())
+    }
+
+    /// Returns the client for the funds and configuration management requests.
+    fn management_client(&self) -> anyhow::Result<ApiClient> {
+        let hostname = self
+            .exonum_management_api
+            .as_ref()
+            .unwrap_or(&self.exonum_private_api);
+        self.api_client(hostname, self.management_api_auth.as_ref())
+    }
+
+    /// Returns the main anchoring instance followed by the additional ones.
+    fn instance_configs(&self) -> Vec<InstanceConfig> {
+        let main_instance = InstanceConfig {
+            instance_name: self.instance_name.clone(),
+            exonum_private_api: self.exonum_private_api.clone(),
+            private_api_auth: self.private_api_auth.clone(),
+            checkpoint: self.checkpoint.clone(),
+        };
+        let mut instances = vec![main_instance];
+        instances.extend(self.instances.iter().cloned());
+        instances
+    }
+
+    fn api_client(
+        &self,
+        hostname: &str,
+        auth: Option<&PrivateApiAuthConfig>,
+    ) -> anyhow::Result<ApiClient> {
+        let client = ApiClient::new(hostname, &self.instance_name);
+        Ok(match auth {
+            Some(auth) => client.with_auth(auth.auth()?),
+            None => client,
+        })
     }
 
     fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
         let mut file = File::open(path)?;
         let mut toml = String::new();
         file.read_to_string(&mut toml)?;
-        toml::de::from_str(&toml).map_err(From::from)
+        let mut config: Self = toml::de::from_str(&toml)?;
+        if let Some(keystore_path) = config.keystore.clone() {
+            let passphrase = sync::read_passphrase(&keystore_path)?;
+            config.unlock_keystore(UnlockedKeystore::open(keystore_path, passphrase)?);
+        }
+        // Reject duplicate Bitcoin keys as early as possible.
+        config.key_pool()?;
+        Ok(config)
+    }
+
+    /// Adds the secrets of the unlocked keystore to this config.
+    fn unlock_keystore(&mut self, unlocked: UnlockedKeystore) {
+        let keystore = &unlocked.keystore;
+        self.bitcoin_key_pool
+            .extend(keystore.bitcoin_keys.iter().cloned());
+        if let Some(hd_keys) = self.bitcoin_hd_keys.as_mut() {
+            if hd_keys.xprv.is_none() {
+                hd_keys.xprv = keystore.xprv.clone();
+            }
+        }
+        self.unlocked_keystore = Some(unlocked);
     }
 
+    /// Saves the config. If the keystore is unlocked, the Bitcoin keys are encrypted
+    /// and saved to the keystore instead of the config file.
     fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
         let path = path.as_ref();
 
@@ -223,13 +722,218 @@ impl SyncConfig {
             fs::create_dir_all(dir)?;
         }
 
+        let mut value_toml = toml::Value::try_from(&self)?;
+        if let Some(unlocked) = self.unlocked_keystore.as_ref() {
+            let mut unlocked = unlocked.clone();
+            unlocked.keystore = Keystore {
+                bitcoin_keys: self.bitcoin_key_pool.clone(),
+                xprv: self
+                    .bitcoin_hd_keys
+                    .as_ref()
+                    .and_then(|hd_keys| hd_keys.xprv.clone()),
+            };
+            unlocked.save()?;
+
+            let table = value_toml
+                .as_table_mut()
+                .expect("Sync config is serialized into the table");
+            table.remove("bitcoin_key_pool");
+            if let Some(hd_keys) = table
+                .get_mut("bitcoin_hd_keys")
+                .and_then(toml::Value::as_table_mut)
+            {
+                hd_keys.remove("xprv");
+            }
+        }
+
         let mut file = File::create(path)?;
-        let value_toml = toml::Value::try_from(&self)?;
         file.write_all(value_toml.to_string().as_bytes())?;
         Ok(())
     }
 }
 
+/// Anchoring instance synced alongside the main one, for example, the anchoring service
+/// of another Exonum chain. The Bitcoin keys, the signer and the Bitcoin relay are shared
+/// with the main instance.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct InstanceConfig {
+    /// Name of the anchoring instance, which must be unique among the synced instances.
+    instance_name: String,
+    exonum_private_api: String,
+    #[serde(default)]
+    private_api_auth: Option<PrivateApiAuthConfig>,
+    /// Checkpoint of the sync with Bitcoin task of this instance.
+    #[serde(default)]
+    checkpoint: Option<CheckpointConfig>,
+}
+
+impl InstanceConfig {
+    /// Returns the client for the private API requests to this instance.
+    fn private_client(&self) -> anyhow::Result<ApiClient> {
+        let client = ApiClient::new(&self.exonum_private_api, &self.instance_name);
+        Ok(match self.private_api_auth.as_ref() {
+            Some(auth) => client.with_auth(auth.auth()?),
+            None => client,
+        })
+    }
+}
+
+/// BIP-32 extended private key from which the Bitcoin keys are derived.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct HdKeysConfig {
+    /// Extended private key in the Base58 encoding, which is stored in the keystore
+    /// if the latter is specified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    xprv: Option<String>,
+    /// Derivation path of the account, e.g. `m/84'/1'/0'`. The Bitcoin keys are derived
+    /// as the non-hardened children of the account.
+    account_path: String,
+    /// Indexes of the derived Bitcoin keys.
+    key_indexes: Vec<u32>,
+}
+
+impl HdKeysConfig {
+    fn xprv(&self) -> anyhow::Result<ExtendedPrivKey> {
+        self.xprv
+            .as_ref()
+            .ok_or_else(|| anyhow!("Extended private key is absent in the config and keystore"))?
+            .parse()
+            .map_err(From::from)
+    }
+
+    fn derive_keypair(&self, index: u32) -> anyhow::Result<(btc::PublicKey, btc::PrivateKey)> {
+        let account = self.account_path.parse::<DerivationPath>()?;
+        let path = btc::anchoring_key_path(&account, index)?;
+        btc::derive_keypair(&self.xprv()?, &path).map_err(From::from)
+    }
+
+    /// Returns the index of the next key, which is used to rotate the Bitcoin key.
+    /// Returns the derivation of the given Bitcoin key, if it is one of the derived keys.
+    fn key_derivation(&self, public_key: btc::PublicKey) -> anyhow::Result<Option<KeyDerivation>> {
+        let account = self.account_path.parse::<DerivationPath>()?;
+        let account_xpub = btc::account_xpub(&self.xprv()?, &account)?;
+        for &index in &self.key_indexes {
+            if btc::derive_public_key(&account_xpub, index)? == public_key {
+                return Ok(Some(KeyDerivation {
+                    account_xpub,
+                    index,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_index(&self) -> u32 {
+        self.key_indexes.iter().max().map_or(0, |index| index + 1)
+    }
+}
+
+/// Hardware wallet accessed via the HWI tool.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct HardwareWalletConfig {
+    /// Type of the device, e.g. `ledger` or `trezor`.
+    device_type: String,
+    /// Hex-encoded fingerprint of the master key of the device.
+    fingerprint: String,
+    /// Bitcoin network of the device keys.
+    network: bitcoin::Network,
+    /// Path to the HWI executable, `hwi` from the `PATH` is used if not specified.
+    #[serde(default)]
+    hwi_path: Option<PathBuf>,
+    /// Bitcoin keys of the device in order of their priority.
+    keys: Vec<HardwareWalletKey>,
+}
+
+/// Bitcoin key stored on the hardware wallet.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct HardwareWalletKey {
+    public_key: btc::PublicKey,
+    /// Derivation path of the key, e.g. `m/84'/1'/0'/0`.
+    derivation_path: String,
+}
+
+impl HardwareWalletConfig {
+    fn signer(&self) -> anyhow::Result<HwiSigner> {
+        let fingerprint = hex::decode(&self.fingerprint)?;
+        if fingerprint.len() != 4 {
+            bail!("Fingerprint of the master key should be 4 bytes long");
+        }
+        let mut signer = HwiSigner::new(
+            &self.device_type,
+            Fingerprint::from(fingerprint.as_slice()),
+            self.network,
+        );
+        if let Some(hwi_path) = self.hwi_path.as_ref() {
+            signer = signer.with_executable(hwi_path);
+        }
+        for key in &self.keys {
+            signer = signer.with_key(key.public_key, key.derivation_path.parse()?);
+        }
+        Ok(signer)
+    }
+}
+
+/// Remote signing service, for example, a daemon in front of the HSM.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct RemoteSignerConfig {
+    /// URL to which the signing requests are sent.
+    url: String,
+    /// Bearer token of the requests.
+    #[serde(default)]
+    auth_token: Option<String>,
+    /// Hex-encoded key of the HMAC-SHA256 signatures of the requests.
+    #[serde(default)]
+    request_key: Option<String>,
+    /// Bitcoin keys of the service in order of their priority.
+    keys: Vec<btc::PublicKey>,
+}
+
+/// Authentication of the private API requests.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct PrivateApiAuthConfig {
+    /// Bearer token of the requests.
+    #[serde(default)]
+    auth_token: Option<String>,
+    /// Hex-encoded key of the HMAC-SHA256 signatures of the requests.
+    #[serde(default)]
+    request_key: Option<String>,
+    /// Maximum difference between the request timestamp and the proxy clock in seconds.
+    #[serde(default)]
+    max_clock_skew_secs: Option<u64>,
+}
+
+impl PrivateApiAuthConfig {
+    fn auth(&self) -> anyhow::Result<PrivateApiAuth> {
+        if self.auth_token.is_none() && self.request_key.is_none() {
+            bail!("Neither the bearer token nor the request key of the private API is specified");
+        }
+        let mut auth = PrivateApiAuth::new();
+        if let Some(auth_token) = self.auth_token.as_ref() {
+            auth = auth.with_auth_token(auth_token);
+        }
+        if let Some(request_key) = self.request_key.as_ref() {
+            auth = auth.with_request_key(hex::decode(request_key)?);
+        }
+        if let Some(max_clock_skew) = self.max_clock_skew_secs {
+            auth = auth.with_max_clock_skew(Duration::from_secs(max_clock_skew));
+        }
+        Ok(auth)
+    }
+}
+
+impl RemoteSignerConfig {
+    fn signer(&self) -> anyhow::Result<RemoteSigner> {
+        let mut signer = RemoteSigner::new(&self.url, self.keys.iter().copied());
+        if let Some(auth_token) = self.auth_token.as_ref() {
+            signer = signer.with_auth_token(auth_token);
+        }
+        if let Some(request_key) = self.request_key.as_ref() {
+            signer = signer.with_request_key(hex::decode(request_key)?);
+        }
+        Ok(signer)
+    }
+}
+
 /// `Bitcoind` rpc configuration.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 struct BitcoinRpcConfig {
@@ -239,6 +943,259 @@ struct BitcoinRpcConfig {
     user: Option<String>,
     /// Bitcoin RPC password.
     password: Option<String>,
+    /// Number of connections to the Bitcoin node.
+    #[serde(default)]
+    pool_size: Option<usize>,
+    /// Timeout of the connection establishment in seconds.
+    #[serde(default)]
+    connect_timeout_secs: Option<u64>,
+    /// Timeout of the whole request in seconds.
+    #[serde(default)]
+    timeout_secs: Option<u64>,
+    /// Paths to the trusted root certificates in the PEM format, for example,
+    /// the CA certificate of the TLS proxy in front of the Bitcoin node.
+    #[serde(default)]
+    root_certificates: Vec<PathBuf>,
+    /// Disables the validation of the TLS certificates. Use only in test environments.
+    #[serde(default)]
+    accept_invalid_certs: bool,
+    /// URL of the proxy for the requests to the Bitcoin node, for example,
+    /// `socks5://127.0.0.1:9050`.
+    #[serde(default)]
+    proxy: Option<String>,
+}
+
+/// Electrum server configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct ElectrumConfig {
+    /// Address of the Electrum server in the `host:port` format.
+    address: String,
+    /// Timeout of the requests to the Electrum server in seconds.
+    #[serde(default)]
+    timeout_secs: Option<u64>,
+}
+
+impl ElectrumConfig {
+    fn relay(&self) -> ElectrumRelay {
+        let relay = ElectrumRelay::new(self.address.clone());
+        match self.timeout_secs {
+            Some(secs) => relay.with_timeout(Duration::from_secs(secs)),
+            None => relay,
+        }
+    }
+}
+
+/// Bitcoin relay selected in the sync utility configuration.
+#[derive(Debug, Clone)]
+enum Relay {
+    /// Bitcoin Core node.
+    Bitcoind(BitcoinRpcClient),
+    /// Electrum server.
+    Electrum(ElectrumRelay),
+}
+
+#[async_trait]
+impl BitcoinRelay for Relay {
+    type Error = anyhow::Error;
+
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        Ok(match self {
+            Relay::Bitcoind(relay) => relay.send_transaction(transaction).await?,
+            Relay::Electrum(relay) => relay.send_transaction(transaction).await?,
+        })
+    }
+
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        Ok(match self {
+            Relay::Bitcoind(relay) => relay.transaction_status(id).await?,
+            Relay::Electrum(relay) => relay.transaction_status(id).await?,
+        })
+    }
+
+    fn is_mempool_conflict(error: &Self::Error) -> bool {
+        if let Some(e) = error.downcast_ref::<bitcoincore_rpc::Error>() {
+            BitcoinRpcClient::is_mempool_conflict(e)
+        } else if let Some(e) = error.downcast_ref::<ElectrumError>() {
+            ElectrumRelay::is_mempool_conflict(e)
+        } else {
+            false
+        }
+    }
+
+    fn is_unavailable(error: &Self::Error) -> bool {
+        if let Some(e) = error.downcast_ref::<bitcoincore_rpc::Error>() {
+            BitcoinRpcClient::is_unavailable(e)
+        } else if let Some(e) = error.downcast_ref::<ElectrumError>() {
+            ElectrumRelay::is_unavailable(e)
+        } else {
+            false
+        }
+    }
+
+    async fn find_conflicting_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        Ok(match self {
+            Relay::Bitcoind(relay) => relay.find_conflicting_transaction(transaction).await?,
+            Relay::Electrum(relay) => relay.find_conflicting_transaction(transaction).await?,
+        })
+    }
+
+    async fn transaction_inclusion(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<AnchorInclusion>, Self::Error> {
+        Ok(match self {
+            Relay::Bitcoind(relay) => relay.transaction_inclusion(id).await?,
+            Relay::Electrum(relay) => relay.transaction_inclusion(id).await?,
+        })
+    }
+
+    async fn fee_histogram(&self) -> Result<Option<FeeHistogram>, Self::Error> {
+        Ok(match self {
+            Relay::Bitcoind(relay) => relay.fee_histogram().await?,
+            Relay::Electrum(relay) => relay.fee_histogram().await?,
+        })
+    }
+
+    async fn relay_fee_policy(&self) -> Result<Option<btc::RelayFeePolicy>, Self::Error> {
+        Ok(match self {
+            Relay::Bitcoind(relay) => relay.relay_fee_policy().await?,
+            Relay::Electrum(relay) => relay.relay_fee_policy().await?,
+        })
+    }
+}
+
+/// Interval between the rounds of the verification loop.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Runner of the sync tasks driven by the `run` subcommand.
+type Runner = MultiSyncRunner<ApiClient, FallbackRelay<Relay>>;
+
+/// Wakes up the sync loop for the next round.
+///
+/// If the ZeroMQ notifications of the Bitcoin node are enabled, the round starts as soon
+/// as a new Bitcoin block is connected or the latest broadcast anchoring transaction is
+/// seen by the node, but no later than after the polling interval.
+struct SyncWaker {
+    #[cfg(feature = "zmq")]
+    notifications: Option<ZmqNotifications>,
+}
+
+#[cfg(feature = "zmq")]
+impl SyncWaker {
+    fn new(sync_config: &SyncConfig) -> anyhow::Result<Self> {
+        let notifications = sync_config
+            .bitcoin_zmq_address
+            .as_deref()
+            .map(ZmqNotifications::subscribe)
+            .transpose()?;
+        Ok(Self { notifications })
+    }
+
+    async fn wait(&mut self, last_broadcast: Option<btc::Sha256d>) {
+        let notifications = match self.notifications.as_mut() {
+            Some(notifications) => notifications,
+            None => return delay_for(POLL_INTERVAL).await,
+        };
+
+        let event = notifications
+            .wait_for(POLL_INTERVAL, |event| match event {
+                BitcoinEvent::Block(_) => true,
+                BitcoinEvent::Transaction(txid) => Some(*txid) == last_broadcast,
+            })
+            .await;
+        if let Some(event) = event {
+            log::trace!("Sync round is triggered by the Bitcoin node: {:?}", event);
+        }
+    }
+
+    /// Wakes up the sync with Bitcoin tasks of the runner on the notifications of
+    /// the Bitcoin node until the tasks are shut down.
+    async fn wake_up_runner(mut self, runner: Arc<Runner>) {
+        let mut notifications = match self.notifications.take() {
+            Some(notifications) => notifications,
+            None => return,
+        };
+        let tasks = runner
+            .instances()
+            .filter_map(|(_, runner)| runner.bitcoin_task())
+            .collect::<Vec<_>>();
+        while !tasks.iter().all(|task| task.is_shut_down()) {
+            let last_broadcasts = tasks
+                .iter()
+                .filter_map(|task| task.last_broadcast())
+                .map(|(_, txid)| txid)
+                .collect::<Vec<_>>();
+            let event = notifications
+                .wait_for(POLL_INTERVAL, |event| match event {
+                    BitcoinEvent::Block(_) => true,
+                    BitcoinEvent::Transaction(txid) => last_broadcasts.contains(txid),
+                })
+                .await;
+            if let Some(event) = event {
+                log::trace!("Sync round is triggered by the Bitcoin node: {:?}", event);
+                runner.wake_up_bitcoin_sync();
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "zmq"))]
+impl SyncWaker {
+    fn new(sync_config: &SyncConfig) -> anyhow::Result<Self> {
+        if sync_config.bitcoin_zmq_address.is_some() {
+            bail!("ZeroMQ notifications of the Bitcoin node require the `zmq` feature.");
+        }
+        Ok(Self {})
+    }
+
+    async fn wait(&mut self, _last_broadcast: Option<btc::Sha256d>) {
+        delay_for(POLL_INTERVAL).await
+    }
+
+    async fn wake_up_runner(self, _runner: Arc<Runner>) {}
+}
+
+/// Storage of the sync utility checkpoint, which keeps the index of the latest synced
+/// anchoring transaction and the history of broadcasts between restarts.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum CheckpointConfig {
+    /// Local JSON file.
+    File { path: PathBuf },
+    /// Embedded `sled` database, requires the `sled` feature.
+    Sled { path: PathBuf },
+    /// Redis server, requires the `redis` feature.
+    Redis {
+        url: String,
+        #[serde(default)]
+        key: Option<String>,
+    },
+}
+
+impl CheckpointConfig {
+    fn open(&self) -> anyhow::Result<Box<dyn CheckpointStore + Send + Sync>> {
+        match self {
+            CheckpointConfig::File { path } => Ok(Box::new(FileCheckpointStore::new(path))),
+            #[cfg(feature = "sled")]
+            CheckpointConfig::Sled { path } => Ok(Box::new(SledCheckpointStore::open(path)?)),
+            #[cfg(feature = "redis")]
+            CheckpointConfig::Redis { url, key } => {
+                Ok(Box::new(RedisCheckpointStore::new(url, key.clone())?))
+            }
+            #[allow(unreachable_patterns)]
+            other => bail!(
+                "Checkpoint store {:?} is not supported, rebuild the utility with \
+                 the corresponding feature.",
+                other
+            ),
+        }
+    }
 }
 
 impl TryFrom<BitcoinRpcConfig> for BitcoinRpcClient {
@@ -249,96 +1206,236 @@ impl TryFrom<BitcoinRpcConfig> for BitcoinRpcClient {
             value.user.unwrap_or_default(),
             value.password.unwrap_or_default(),
         );
-        Self::new(value.host, auth)
+        let mut builder =
+            Self::builder(value.host, auth).danger_accept_invalid_certs(value.accept_invalid_certs);
+        if let Some(pool_size) = value.pool_size {
+            builder = builder.pool_size(pool_size);
+        }
+        if let Some(timeout_secs) = value.connect_timeout_secs {
+            builder = builder.connect_timeout(Duration::from_secs(timeout_secs));
+        }
+        if let Some(timeout_secs) = value.timeout_secs {
+            builder = builder.request_timeout(Duration::from_secs(timeout_secs));
+        }
+        for path in &value.root_certificates {
+            builder = builder.add_root_certificate(fs::read(path)?);
+        }
+        if let Some(proxy) = value.proxy {
+            builder = builder.proxy(proxy);
+        }
+        builder.build()
     }
 }
 
 impl GenerateConfigCommand {
     fn run(self) -> anyhow::Result<()> {
-        let bitcoin_keypair = btc::gen_keypair(self.bitcoin_network);
+        let bitcoin_hd_keys = self.bitcoin_hd_keys();
+        let (bitcoin_keypair, bitcoin_key_pool) = match bitcoin_hd_keys.as_ref() {
+            Some(hd_keys) => (hd_keys.derive_keypair(0)?, Vec::new()),
+            None => {
+                let bitcoin_keypair = btc::gen_keypair(self.bitcoin_network);
+                (bitcoin_keypair.clone(), vec![bitcoin_keypair])
+            }
+        };
 
         let bitcoin_rpc_config = self.bitcoin_rpc_config();
+        let unlocked_keystore = match self.keystore.as_ref() {
+            Some(path) => {
+                let passphrase = sync::read_new_passphrase(path)?;
+                Some(UnlockedKeystore::create(path, passphrase))
+            }
+            None => None,
+        };
         let sync_config = SyncConfig {
             exonum_private_api: self.exonum_private_api,
-            bitcoin_key_pool: std::iter::once(bitcoin_keypair.clone()).collect(),
+            exonum_management_api: self.exonum_management_api,
+            private_api_auth: None,
+            management_api_auth: None,
+            bitcoin_key_pool,
+            bitcoin_hd_keys,
+            keystore: self.keystore,
+            unlocked_keystore,
+            hardware_wallet: None,
+            remote_signer: None,
             instance_name: self.instance_name,
+            bitcoin_zmq_address: None,
             bitcoin_rpc_config,
+            fallback_bitcoin_rpc_configs: Vec::new(),
+            electrum: self.electrum_config(),
+            checkpoint: None,
+            instances: Vec::new(),
         };
 
         sync_config.save(self.output)?;
         log::info!("Generated initial configuration for the btc anchoring sync util.");
         log::trace!(
             "Available Bitcoin keys in key pool: {:?}",
-            sync_config.bitcoin_key_pool
+            sync_config.key_pool()?.public_keys().collect::<Vec<_>>()
         );
         // Print the received Bitcoin public key to use it in scripts.
         println!("{}", bitcoin_keypair.0);
         Ok(())
     }
 
+    fn bitcoin_hd_keys(&self) -> Option<HdKeysConfig> {
+        let xprv = self.bitcoin_xprv.clone()?;
+        let account_path = self.account_path.clone().unwrap_or_else(|| {
+            let coin_type = match self.bitcoin_network {
+                bitcoin::Network::Bitcoin => 0,
+                _ => 1,
+            };
+            format!("m/84'/{}'/0'", coin_type)
+        });
+        Some(HdKeysConfig {
+            xprv: Some(xprv),
+            account_path,
+            key_indexes: vec![0],
+        })
+    }
+
     fn bitcoin_rpc_config(&self) -> Option<BitcoinRpcConfig> {
         self.bitcoin_rpc_host.clone().map(|host| BitcoinRpcConfig {
             host,
             user: self.bitcoin_rpc_user.clone(),
             password: self.bitcoin_rpc_password.clone(),
+            ..BitcoinRpcConfig::default()
+        })
+    }
+
+    fn electrum_config(&self) -> Option<ElectrumConfig> {
+        self.electrum_address.clone().map(|address| ElectrumConfig {
+            address,
+            timeout_secs: None,
         })
     }
 }
 
 impl RunCommand {
     async fn run(self) -> anyhow::Result<()> {
+        if self.verify_only {
+            return self.verify().await;
+        }
+
+        let sync_config = SyncConfig::load(&self.config)?;
+        let waker = SyncWaker::new(&sync_config)?;
+        let review_queue = self.review_queue();
+        if let Some(review_queue) = review_queue.as_ref() {
+            let address = self.review_listen_address;
+            log::info!("Review endpoint listens at http://{}", address);
+            tokio::spawn(serve_review_endpoint(review_queue.clone(), address));
+        }
+
+        let mut runner = MultiSyncRunner::new();
+        for instance in sync_config.instance_configs() {
+            let instance_runner = self
+                .sync_runner(&sync_config, &instance, review_queue.clone())
+                .await?;
+            runner = runner.with_instance(instance.instance_name, instance_runner)?;
+        }
+        let runner = Arc::new(runner);
+        tokio::spawn(waker.wake_up_runner(runner.clone()));
+
+        let shutdown = tokio::spawn({
+            let runner = runner.clone();
+            async move {
+                termination_signal().await?;
+                log::info!("Shutting down the sync utility");
+                runner.shutdown().await
+            }
+        });
+        runner.run().await?;
+        // The runner stops only after the shutdown, so wait until the checkpoints are saved.
+        shutdown.await?
+    }
+
+    /// Creates the runner of the sync tasks of the given anchoring instance.
+    async fn sync_runner(
+        &self,
+        sync_config: &SyncConfig,
+        instance: &InstanceConfig,
+        review_queue: Option<ReviewQueue>,
+    ) -> anyhow::Result<SyncRunner<ApiClient, FallbackRelay<Relay>>> {
+        let client = instance.private_client()?;
+        let mut chain_updater = match (
+            sync_config.hardware_wallet.as_ref(),
+            sync_config.remote_signer.as_ref(),
+        ) {
+            (Some(_), Some(_)) => {
+                bail!("Only one of the hardware wallet and the remote signer can be specified")
+            }
+            (Some(hardware_wallet), None) => {
+                AnchoringChainUpdateTask::with_signer(hardware_wallet.signer()?, client.clone())
+            }
+            (None, Some(remote_signer)) => {
+                AnchoringChainUpdateTask::with_signer(remote_signer.signer()?, client.clone())
+            }
+            (None, None) => AnchoringChainUpdateTask::new(sync_config.key_pool()?, client.clone()),
+        };
+        if let Some(review_queue) = review_queue {
+            chain_updater = chain_updater.with_review_queue(review_queue);
+        }
+        check_compatibility(&chain_updater).await?;
+
+        let mut runner = SyncRunner::new(chain_updater);
+        if let Some(relay) = sync_config.bitcoin_relay()? {
+            let mut task = SyncWithBitcoinTask::new(relay, client);
+            if let Some(policy) = self.broadcast_policy() {
+                task = task.with_broadcast_policy(policy);
+            }
+            if let Some(checkpoint) = instance.checkpoint.as_ref() {
+                task = task.with_restored_checkpoint(checkpoint.open()?).await?;
+            }
+            runner = runner.with_bitcoin_task(Arc::new(task));
+        }
+        Ok(runner)
+    }
+
+    async fn verify(self) -> anyhow::Result<()> {
         let sync_config = SyncConfig::load(self.config)?;
-        let client = ApiClient::new(sync_config.exonum_private_api, sync_config.instance_name);
-        let chain_updater =
-            AnchoringChainUpdateTask::new(sync_config.bitcoin_key_pool, client.clone());
-        let bitcoin_relay = sync_config
-            .bitcoin_rpc_config
-            .map(BitcoinRpcClient::try_from)
-            .transpose()?
-            .map(|relay| SyncWithBitcoinTask::new(relay, client.clone()));
+        let relay = sync_config.bitcoin_relay()?;
+        let mut waker = SyncWaker::new(&sync_config)?;
+        let client = sync_config.private_client()?;
+        // Bitcoin keys are not needed to verify proposals.
+        let chain_updater = AnchoringChainUpdateTask::new(KeyPool::default(), client.clone());
+        check_compatibility(&chain_updater).await?;
+        let bitcoin_relay = relay.map(|relay| SyncWithBitcoinTask::new(relay, client.clone()));
+        let policy = VerificationPolicy {
+            max_fee: self.max_fee,
+        };
 
-        let mut latest_synced_tx_index: Option<u64> = None;
+        log::info!("Running in the verify-only mode.");
+        let mut next_tx_index = 0;
         loop {
-            match chain_updater.process().await {
-                Ok(_) => {}
-                // Client problems most often occurs due to network problems.
+            match chain_updater.verify_proposal(&policy).await {
+                Ok(alerts) => alerts
+                    .iter()
+                    .for_each(|alert| log::error!("Verification alert: {}", alert)),
                 Err(ChainUpdateError::Client(e)) => {
                     log::error!("An error in the anchoring API client occurred. {}", e)
                 }
-                // Sometimes Bitcoin end in the anchoring wallet.
                 Err(ChainUpdateError::InsufficientFunds { total_fee, balance }) => log::warn!(
                     "Insufficient funds to construct a new anchoring transaction, \
                      total fee is {}, total balance is {}",
                     total_fee,
                     balance
                 ),
-                // For the work of anchoring you need to replenish anchoring wallet.
                 Err(ChainUpdateError::NoInitialFunds) => {
-                    let address = match chain_updater.anchoring_config().await {
-                        Ok(config) => config.anchoring_address(),
-                        Err(e) => {
-                            log::error!("An error in the anchoring API client occurred. {}", e);
-                            continue;
-                        }
-                    };
-
-                    log::warn!(
-                        "Initial funding transaction is absent, you should send some \
-                         Bitcoins to the address {}",
-                        address
-                    );
-                    log::warn!(
-                        "And then confirm this transaction using the private \
-                         `add-funds` API method."
-                    )
+                    log::warn!("Initial funding transaction is absent.")
                 }
-                // Stop execution if an internal error occurred.
+                // Proposals are not signed, thus not reviewed in the verify-only mode.
+                Err(ChainUpdateError::AwaitingReview { .. })
+                | Err(ChainUpdateError::ReviewDenied { .. }) => {}
                 Err(ChainUpdateError::Internal(e)) => return Err(e),
             }
 
             if let Some(relay) = bitcoin_relay.as_ref() {
-                match relay.process(latest_synced_tx_index).await {
-                    Ok(index) => latest_synced_tx_index = index,
+                match relay.verify_chain(next_tx_index).await {
+                    Ok((index, alerts)) => {
+                        next_tx_index = index;
+                        alerts
+                            .iter()
+                            .for_each(|alert| log::error!("Verification alert: {}", alert));
+                    }
 
                     Err(SyncWithBitcoinError::Client(e)) => {
                         log::error!("An error in the anchoring API client occurred. {}", e)
@@ -348,21 +1445,67 @@ impl RunCommand {
                         log::error!("An error in the Bitcoin relay occurred. {}", e)
                     }
 
-                    Err(SyncWithBitcoinError::UnconfirmedFundingTransaction(id)) => bail!(
-                        "Funding transaction with id {} is unconfirmed by Bitcoin network. \
-                         This is a serious mistake that can break anchoring process.",
+                    Err(SyncWithBitcoinError::UnconfirmedFundingTransaction(id)) => log::error!(
+                        "Funding transaction with id {} is unconfirmed by Bitcoin network.",
                         id
                     ),
 
-                    // Stop execution if an internal error occurred.
+                    Err(SyncWithBitcoinError::MempoolConflict {
+                        txid,
+                        conflicting_txid,
+                    }) => log_mempool_conflict(txid, conflicting_txid),
+
+                    Err(SyncWithBitcoinError::RelayFeeTooLow {
+                        txid,
+                        min_relay_fee,
+                        node_min_relay_fee,
+                    }) => log_relay_fee_too_low(txid, min_relay_fee, node_min_relay_fee),
+
+                    Err(SyncWithBitcoinError::AwaitingApprovals {
+                        txid,
+                        approvals,
+                        required,
+                    }) => log_awaiting_approvals(txid, approvals, required),
+
                     Err(SyncWithBitcoinError::Internal(e)) => return Err(e),
                 }
             }
 
-            // Don't perform this actions too frequent to avoid DOS attack.
-            delay_for(Duration::from_secs(5)).await
+            waker.wait(None).await
         }
     }
+
+    fn broadcast_policy(&self) -> Option<BroadcastPolicy> {
+        self.target_fee_rate.map(|target_fee_rate| BroadcastPolicy {
+            target_fee_rate,
+            max_delay: Duration::from_secs(self.max_broadcast_delay),
+        })
+    }
+
+    fn review_queue(&self) -> Option<ReviewQueue> {
+        self.review_threshold.map(|spend_threshold| {
+            ReviewQueue::new(ReviewPolicy {
+                spend_threshold,
+                review_window: Duration::from_secs(self.review_window),
+            })
+        })
+    }
+}
+
+impl BootstrapCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let sync_config = SyncConfig::load(self.config)?;
+        let key_pool = sync_config.key_pool()?;
+        let relay = sync_config.bitcoin_relay()?;
+        let client = sync_config.private_client()?;
+
+        let report = check_bootstrap(&client, relay.as_ref(), &key_pool).await?;
+        print!("{}", report);
+        if !report.is_ready() {
+            bail!("The first anchoring transaction can't be created, see the failed checks.");
+        }
+        Ok(())
+    }
 }
 
 impl GenerateKeypairCommand {
@@ -375,12 +1518,20 @@ impl GenerateKeypairCommand {
                  Perhaps pool of keys in config is empty."
             )
         })?;
-        let bitcoin_keypair = btc::gen_keypair(network);
-        let bitcoin_pub_key = bitcoin_keypair.0;
-
-        sync_config
-            .bitcoin_key_pool
-            .extend(std::iter::once(bitcoin_keypair));
+        let bitcoin_pub_key = match sync_config.bitcoin_hd_keys.as_mut() {
+            Some(hd_keys) => {
+                let index = hd_keys.next_index();
+                let bitcoin_keypair = hd_keys.derive_keypair(index)?;
+                hd_keys.key_indexes.push(index);
+                bitcoin_keypair.0
+            }
+            None => {
+                let bitcoin_keypair = btc::gen_keypair(network);
+                let bitcoin_pub_key = bitcoin_keypair.0;
+                sync_config.bitcoin_key_pool.push(bitcoin_keypair);
+                bitcoin_pub_key
+            }
+        };
         sync_config.save(self.config)?;
         // Print the received Bitcoin public key to use it in scripts.
         println!("{}", bitcoin_pub_key);
@@ -388,12 +1539,447 @@ impl GenerateKeypairCommand {
     }
 }
 
+impl AttestKeyCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let sync_config = SyncConfig::load(&self.config)?;
+        let key_pool = sync_config.key_pool()?;
+        let private_key = key_pool
+            .get(&self.bitcoin_key)
+            .ok_or_else(|| anyhow!("Bitcoin key {} is absent in the key pool", self.bitcoin_key))?;
+
+        let attestation = AttestKey::new(&self.service_key, self.bitcoin_key, private_key);
+        let hash = sync_config
+            .management_client()?
+            .attest_key(attestation)
+            .await?;
+        // Print the hash of the sent transaction to use it in scripts.
+        println!("{}", hash.to_hex());
+        Ok(())
+    }
+}
+
+impl KeyDerivationCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let sync_config = SyncConfig::load(&self.config)?;
+        let hd_keys = sync_config
+            .bitcoin_hd_keys
+            .as_ref()
+            .ok_or_else(|| anyhow!("Extended private key is not specified in the config"))?;
+        let derivation = hd_keys.key_derivation(self.bitcoin_key)?.ok_or_else(|| {
+            anyhow!(
+                "Bitcoin key {} is not derived from the extended private key",
+                self.bitcoin_key
+            )
+        })?;
+        // Print the derivation to use it in the anchoring configuration.
+        println!("{}", serde_json::to_string_pretty(&derivation)?);
+        Ok(())
+    }
+}
+
+impl ApproveSpecialTxCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let sync_config = SyncConfig::load(&self.config)?;
+        let hash = sync_config
+            .management_client()?
+            .approve_special_tx(self.txid)
+            .await?;
+        // Print the hash of the sent transaction to use it in scripts.
+        println!("{}", hash.to_hex());
+        Ok(())
+    }
+}
+
+impl MigrateKeystoreCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let mut sync_config = SyncConfig::load(&self.config)?;
+        if let Some(keystore) = sync_config.keystore.as_ref() {
+            bail!(
+                "Bitcoin keys are already stored in the keystore {}",
+                keystore.display()
+            );
+        }
+
+        let passphrase = sync::read_new_passphrase(&self.keystore)?;
+        sync_config.keystore = Some(self.keystore.clone());
+        sync_config.unlocked_keystore = Some(UnlockedKeystore::create(&self.keystore, passphrase));
+        sync_config.save(&self.config)?;
+        log::info!(
+            "Bitcoin keys are moved to the encrypted keystore {}, remove the backups \
+             of the plaintext config.",
+            self.keystore.display()
+        );
+        Ok(())
+    }
+}
+
+impl DeriveAddressCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let quorum = self
+            .quorum
+            .unwrap_or_else(|| exonum::helpers::byzantine_quorum(self.bitcoin_keys.len()));
+        let address =
+            AnchoringConfig::derive_address_with(self.bitcoin_network, self.bitcoin_keys, quorum)?;
+        // Print the derived address to use it in scripts.
+        println!("{}", address);
+        Ok(())
+    }
+}
+
+impl EncryptMemoCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let encrypted_memo = self.key.encrypt(self.memo.as_bytes())?;
+        // Print the encrypted memo to use it in scripts.
+        println!("{}", hex::encode(encrypted_memo));
+        Ok(())
+    }
+}
+
+impl HashRecoveryKeyCommand {
+    fn run(self) -> anyhow::Result<()> {
+        // Print the key hash to use it in scripts.
+        println!("{}", self.key.hash().to_hex());
+        Ok(())
+    }
+}
+
+impl ExportRecoveryBundleCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let sync_config = SyncConfig::load(&self.config)?;
+        let query = RecoveryBundleQuery {
+            export_index: self.export_index,
+            key: self.key.clone(),
+        };
+        let encrypted_bundle = sync_config
+            .management_client()?
+            .recovery_bundle(query)
+            .await?;
+        // Make sure that the bundle can be decrypted by the escrow agents.
+        self.key.decrypt(&encrypted_bundle)?;
+        fs::write(&self.output, serde_json::to_vec_pretty(&encrypted_bundle)?)?;
+        Ok(())
+    }
+}
+
+impl ExportSighashesCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let sync_config = SyncConfig::load(&self.config)?;
+        let client = sync_config.private_client()?;
+        // Bitcoin keys are not needed to export the signature hashes.
+        let chain_updater = AnchoringChainUpdateTask::new(KeyPool::default(), client);
+        check_compatibility(&chain_updater).await?;
+
+        let export = match chain_updater.export_sighashes().await {
+            Ok(export) => export,
+            Err(ChainUpdateError::Client(e)) => {
+                bail!("Unable to get the anchoring proposal. {}", e)
+            }
+            Err(ChainUpdateError::Internal(e)) => return Err(e),
+            Err(e) => bail!("Unable to export the signature hashes: {:?}", e),
+        };
+        fs::write(&self.output, serde_json::to_vec_pretty(&export)?)?;
+        // Print the identifier of the proposal to compare it on the air-gapped machine.
+        println!("{}", export.txid);
+        Ok(())
+    }
+}
+
+impl SignOfflineCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let key_pool = SyncConfig::load(&self.config)?.key_pool()?;
+        let export: SighashExport = serde_json::from_slice(&fs::read(&self.input)?)?;
+        let signatures = export.sign(&key_pool);
+        if signatures.signatures.is_empty() {
+            bail!("Key pool has no keys to sign the proposal {}", export.txid);
+        }
+
+        fs::write(&self.output, serde_json::to_vec_pretty(&signatures)?)?;
+        println!(
+            "Signed {} of {} inputs of the proposal {} anchoring the block at height {}",
+            signatures.signatures.len(),
+            export.inputs.len(),
+            export.txid,
+            export.anchored_height
+        );
+        Ok(())
+    }
+}
+
+impl ImportSignaturesCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let sync_config = SyncConfig::load(&self.config)?;
+        let client = sync_config.private_client()?;
+        let chain_updater = AnchoringChainUpdateTask::new(KeyPool::default(), client);
+        check_compatibility(&chain_updater).await?;
+
+        let signatures: OfflineSignatures = serde_json::from_slice(&fs::read(&self.input)?)?;
+        match chain_updater.import_signatures(&signatures).await {
+            Ok(count) => {
+                println!("Submitted {} signatures", count);
+                Ok(())
+            }
+            Err(ChainUpdateError::Client(e)) => bail!("Unable to submit the signatures. {}", e),
+            Err(ChainUpdateError::Internal(e)) => Err(e),
+            Err(e) => bail!("Unable to import the signatures: {:?}", e),
+        }
+    }
+}
+
+/// Waits for the termination signal: `SIGTERM` sent by the container orchestration
+/// or Ctrl+C.
+impl AuthProxyCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let toml = fs::read_to_string(&self.config)?;
+        let auth = toml::de::from_str::<PrivateApiAuthConfig>(&toml)?.auth()?;
+        log::info!(
+            "Authenticating proxy listens at http://{} and forwards requests to {}",
+            self.listen_address,
+            self.exonum_private_api
+        );
+        serve_auth_proxy(auth, self.exonum_private_api, self.listen_address).await
+    }
+}
+
+async fn termination_signal() -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigterm = signal(SignalKind::terminate())?;
+        tokio::select! {
+            _ = sigterm.recv() => Ok(()),
+            result = tokio::signal::ctrl_c() => result.map_err(From::from),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await.map_err(From::from)
+    }
+}
+
+/// Serves the local endpoint for the review of the anchoring proposals:
+///
+/// - `GET /proposals` returns the proposals queued for review;
+/// - `POST /proposals/{txid}/approve` approves the proposal;
+/// - `POST /proposals/{txid}/deny` denies the proposal.
+///
+/// The endpoint has no authentication, so it should listen on the loopback interface only.
+async fn serve_review_endpoint(review_queue: ReviewQueue, address: SocketAddr) {
+    let make_service = make_service_fn(move |_| {
+        let review_queue = review_queue.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let response = handle_review_request(&review_queue, &request);
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+    if let Err(e) = Server::bind(&address).serve(make_service).await {
+        log::error!("Review endpoint has stopped. {}", e);
+    }
+}
+
+fn handle_review_request(review_queue: &ReviewQueue, request: &Request<Body>) -> Response<Body> {
+    let path = request.uri().path().trim_matches('/');
+    let segments = path.split('/').collect::<Vec<_>>();
+    match (request.method(), segments.as_slice()) {
+        (&Method::GET, ["proposals"]) => {
+            let proposals = serde_json::to_string_pretty(&review_queue.proposals())
+                .expect("Unable to serialize queued proposals");
+            Response::new(Body::from(proposals))
+        }
+        (&Method::POST, ["proposals", txid, action]) => {
+            let txid = match txid.parse::<btc::Sha256d>() {
+                Ok(txid) => txid,
+                Err(e) => return text_response(StatusCode::BAD_REQUEST, e.to_string()),
+            };
+            let is_queued = match *action {
+                "approve" => review_queue.approve(txid),
+                "deny" => review_queue.deny(txid),
+                _ => return text_response(StatusCode::NOT_FOUND, "Unknown action"),
+            };
+            if is_queued {
+                text_response(
+                    StatusCode::OK,
+                    format!("Proposal {} has been {}d", txid, action),
+                )
+            } else {
+                text_response(StatusCode::NOT_FOUND, "Proposal is not queued for review")
+            }
+        }
+        _ => text_response(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+/// Serves the authenticating proxy of the private API. The requests which fail
+/// the authentication are rejected with the `401 Unauthorized` status, and the other ones
+/// are forwarded to the private API as is.
+async fn serve_auth_proxy(
+    auth: PrivateApiAuth,
+    exonum_private_api: String,
+    address: SocketAddr,
+) -> anyhow::Result<()> {
+    let client = reqwest::Client::new();
+    let make_service = make_service_fn(move |_| {
+        let auth = auth.clone();
+        let exonum_private_api = exonum_private_api.clone();
+        let client = client.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let auth = auth.clone();
+                let exonum_private_api = exonum_private_api.clone();
+                let client = client.clone();
+                async move {
+                    let response =
+                        proxy_private_request(&auth, &exonum_private_api, &client, request).await;
+                    Ok::<_, Infallible>(response)
+                }
+            }))
+        }
+    });
+    Server::bind(&address).serve(make_service).await?;
+    Ok(())
+}
+
+async fn proxy_private_request(
+    auth: &PrivateApiAuth,
+    exonum_private_api: &str,
+    client: &reqwest::Client,
+    request: Request<Body>,
+) -> Response<Body> {
+    let (parts, body) = request.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => return text_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map_or("/", |path_and_query| path_and_query.as_str());
+    if let Err(e) = auth.verify(&parts.method, path_and_query, &parts.headers, &body) {
+        log::warn!(
+            "Rejected private API request {} {}. {}",
+            parts.method,
+            path_and_query,
+            e
+        );
+        return text_response(StatusCode::UNAUTHORIZED, e.to_string());
+    }
+
+    let mut forwarded = client
+        .request(
+            parts.method.clone(),
+            &format!("{}{}", exonum_private_api, path_and_query),
+        )
+        .body(body);
+    if let Some(content_type) = parts.headers.get(CONTENT_TYPE) {
+        forwarded = forwarded.header(CONTENT_TYPE, content_type.clone());
+    }
+    let response = match forwarded.send().await {
+        Ok(response) => response,
+        Err(e) => return text_response(StatusCode::BAD_GATEWAY, e.to_string()),
+    };
+    let status = response.status();
+    let content_type = response.headers().get(CONTENT_TYPE).cloned();
+    let body = match response.bytes().await {
+        Ok(body) => body,
+        Err(e) => return text_response(StatusCode::BAD_GATEWAY, e.to_string()),
+    };
+
+    let mut proxied = Response::new(Body::from(body));
+    *proxied.status_mut() = status;
+    if let Some(content_type) = content_type {
+        proxied.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+    proxied
+}
+
+fn text_response(status: StatusCode, text: impl Into<String>) -> Response<Body> {
+    let mut response = Response::new(Body::from(text.into()));
+    *response.status_mut() = status;
+    response
+}
+
+/// Checks that the anchoring service of the node is compatible with this sync utility.
+async fn check_compatibility(
+    chain_updater: &AnchoringChainUpdateTask<ApiClient>,
+) -> anyhow::Result<()> {
+    match chain_updater.check_compatibility().await {
+        Ok(version) => {
+            log::info!(
+                "Anchoring service version {} is compatible",
+                version.crate_version
+            );
+            Ok(())
+        }
+        Err(ChainUpdateError::Client(e)) => {
+            bail!("Unable to get the version of the anchoring service. {}", e)
+        }
+        Err(ChainUpdateError::Internal(e)) => Err(e),
+        Err(e) => bail!("Unexpected compatibility check error: {:?}", e),
+    }
+}
+
+/// Reports the anchoring transaction rejected by the Bitcoin node due to the conflicting
+/// transaction in the memory pool.
+fn log_mempool_conflict(txid: btc::Sha256d, conflicting_txid: Option<btc::Sha256d>) {
+    match conflicting_txid {
+        Some(conflicting_txid) => log::error!(
+            "Anchoring transaction {} conflicts with the transaction {} in the Bitcoin \
+             memory pool, which spends the same outputs. Probably, another variant of \
+             the anchoring transaction has been broadcast.",
+            txid,
+            conflicting_txid
+        ),
+        None => log::error!(
+            "Anchoring transaction {} conflicts with an unknown transaction in the Bitcoin \
+             memory pool.",
+            txid
+        ),
+    }
+}
+
+fn log_relay_fee_too_low(txid: btc::Sha256d, min_relay_fee: u64, node_min_relay_fee: u64) {
+    log::error!(
+        "Anchoring transaction {} has been rejected by the Bitcoin node. The minimal relay \
+         fee rate of the node is {} sat/kvB, while the anchoring configuration allows {} \
+         sat/kvB. Raise the `min_relay_fee` value of the anchoring configuration.",
+        txid,
+        node_min_relay_fee,
+        min_relay_fee
+    );
+}
+
+fn log_awaiting_approvals(txid: btc::Sha256d, approvals: u64, required: u64) {
+    log::warn!(
+        "Broadcasting of the anchoring transaction {} is held by the emergency brake, \
+         {} of {} required approvals have been recorded. Approve this transaction using \
+         the `approve-special-tx` command.",
+        txid,
+        approvals,
+        required
+    );
+}
+
 impl Commands {
     async fn run(self) -> anyhow::Result<()> {
         match self {
             Commands::GenerateConfig(cmd) => cmd.run(),
             Commands::GenerateKeypair(cmd) => cmd.run(),
+            Commands::AttestKey(cmd) => cmd.run().await,
+            Commands::KeyDerivation(cmd) => cmd.run(),
+            Commands::DeriveAddress(cmd) => cmd.run(),
+            Commands::EncryptMemo(cmd) => cmd.run(),
+            Commands::HashRecoveryKey(cmd) => cmd.run(),
+            Commands::ExportRecoveryBundle(cmd) => cmd.run().await,
+            Commands::ApproveSpecialTx(cmd) => cmd.run().await,
+            Commands::ExportSighashes(cmd) => cmd.run().await,
+            Commands::SignOffline(cmd) => cmd.run(),
+            Commands::ImportSignatures(cmd) => cmd.run().await,
+            Commands::MigrateKeystore(cmd) => cmd.run(),
+            Commands::AuthProxy(cmd) => cmd.run().await,
             Commands::Run(cmd) => cmd.run().await,
+            Commands::Bootstrap(cmd) => cmd.run().await,
         }
     }
 }
@@ -409,8 +1995,6 @@ mod flatten_keypairs {
 
     use serde_derive::{Deserialize, Serialize};
 
-    use std::collections::HashMap;
-
     /// The structure for storing the bitcoin keypair.
     /// It is required for reading data from the .toml file into memory.
     #[derive(Deserialize, Serialize)]
@@ -421,7 +2005,7 @@ mod flatten_keypairs {
         private_key: PrivateKey,
     }
 
-    pub fn serialize<S>(keys: &HashMap<PublicKey, PrivateKey>, ser: S) -> Result<S::Ok, S::Error>
+    pub fn serialize<S>(keys: &[(PublicKey, PrivateKey)], ser: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
@@ -429,15 +2013,15 @@ mod flatten_keypairs {
 
         let keypairs = keys
             .iter()
-            .map(|(&public_key, private_key)| BitcoinKeypair {
-                public_key,
+            .map(|(public_key, private_key)| BitcoinKeypair {
+                public_key: *public_key,
                 private_key: private_key.clone(),
             })
             .collect::<Vec<_>>();
         keypairs.serialize(ser)
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<HashMap<PublicKey, PrivateKey>, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<(PublicKey, PrivateKey)>, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
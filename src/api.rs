@@ -16,30 +16,59 @@
 //!
 //! Anchoring API is divided into public and private parts, with public part intended for
 //! unauthorized use, and private part intended to be used by [sync][sync] module.
-//! Private part is implementation detail and should not be used directly.
+//! Private part is implementation detail and should not be used directly. Its requests
+//! are authenticated by the shared secret if it is configured for the node, see
+//! [`PrivateApiAuth`][auth] for details.
 //!
 //! [sync]: ../sync/index.html
+//! [auth]: ../sync/struct.PrivateApiAuth.html
 
+use actix_web::{
+    http::{header, HeaderValue, Method, StatusCode},
+    web::{Bytes, Payload, Query},
+    FromRequest, HttpRequest, HttpResponse,
+};
 use anyhow::{anyhow, ensure};
 use async_trait::async_trait;
 use btc_transaction_utils::{p2wsh, TxInRef};
-use exonum::{blockchain::IndexProof, crypto::Hash, helpers::Height};
-use exonum_merkledb::ListProof;
+use exonum::{
+    blockchain::{ApiSender, BlockProof, Blockchain, IndexProof, SendError},
+    crypto::{self, Hash, KeyPair, PublicKey},
+    helpers::Height,
+    merkledb::{access::Prefixed, Snapshot},
+    runtime::{
+        BlockchainData, ExecutionError, InstanceDescriptor, InstanceId, InstanceStatus, MethodId,
+        SnapshotExt,
+    },
+};
+use exonum_explorer::{BlockchainExplorer, CommittedTransaction};
+use exonum_merkledb::{access::Access, BinaryValue, ListProof, MapProof, ObjectHash};
 use exonum_rust_runtime::{
-    api::{self, ServiceApiBuilder, ServiceApiState},
-    Broadcaster,
+    api::{
+        self,
+        backends::actix::{RawHandler, RequestHandler},
+        ServiceApiBuilder, ServiceApiState,
+    },
+    Broadcaster, GenericCall, MethodDescriptor,
 };
+use futures::future::{BoxFuture, Future, FutureExt, LocalBoxFuture};
+use log::error;
 use serde_derive::{Deserialize, Serialize};
 
-use std::cmp::{
-    self,
-    Ordering::{self, Equal, Greater, Less},
-};
+use std::{borrow::Borrow, cmp, iter, sync::Arc};
 
 use crate::{
-    blockchain::{AddFunds, BtcAnchoringInterface, Schema, SignInput},
+    blockchain::{
+        is_fee_payer_input, AddFunds, AnchorInclusion, AnchoringLatency, ApproveSpecialTx,
+        AttestKey, BtcAnchoringInterface, ConfirmAnchor, FinalizationIncident, NoProposalReason,
+        ReportFeeEstimate, RequestCpfp, Schema, SignInput, SignatureConflict, SigningProgress,
+        TransitionStatus, MAX_ANCHORING_INPUTS,
+    },
     btc,
-    config::Config,
+    config::{AnchoringKeys, Config},
+    psbt::ProposalPsbt,
+    recovery::{EncryptedRecoveryBundle, RecoveryBundle, RecoveryKey},
+    sync::{PrivateApiAuth, PrivateApiScope},
 };
 
 /// A proof of existence for an anchoring transaction at the given height.
@@ -49,13 +78,323 @@ pub struct TransactionProof {
     pub index_proof: IndexProof,
     /// Proof for the specific transaction in this table.
     pub transaction_proof: ListProof<btc::Transaction>,
+    /// Height of the block in which the anchoring transaction has been finalized.
+    /// This value is not covered by the proof.
+    #[serde(default)]
+    pub finalized_at: Option<Height>,
+    /// Location of the anchoring transaction in the Bitcoin blockchain agreed by a quorum
+    /// of the anchoring nodes. This value is not covered by the proof.
+    #[serde(default)]
+    pub inclusion: Option<AnchorInclusion>,
+    /// Height of the block in which the anchoring transaction has been marked as finally
+    /// confirmed by a quorum of the anchoring nodes. This value is not covered by the proof.
+    #[serde(default)]
+    pub finally_confirmed_at: Option<Height>,
+}
+
+impl TransactionProof {
+    /// Verifies that no anchoring transaction covers the block with the given height,
+    /// i.e. the proof is given for the latest anchoring transaction, which anchors
+    /// an earlier block, or for the empty anchoring chain.
+    ///
+    /// The proof of the anchoring transaction commits to the length of the anchoring chain,
+    /// so the node cannot hide the later anchoring transactions. Since the `find-transaction`
+    /// endpoint returns the latest anchoring transaction for the heights which are not
+    /// anchored yet, its response for such a height passes this verification.
+    pub fn verify_absence(
+        &self,
+        instance_name: &str,
+        validator_keys: &[PublicKey],
+        height: Height,
+    ) -> anyhow::Result<AnchoringAbsence> {
+        let (index_name, index_hash) = self.index_proof.verify(validator_keys)?;
+        let expected_name = format!("{}.transactions_chain", instance_name);
+        ensure!(
+            index_name == expected_name,
+            "Proof is given for the index {}, expected {}",
+            index_name,
+            expected_name
+        );
+        let checked_proof = self.transaction_proof.check_against_hash(index_hash)?;
+        let chain_length = checked_proof.length();
+
+        let latest_anchored_height = match checked_proof.entries().first() {
+            Some((index, tx)) => {
+                ensure!(
+                    index + 1 == chain_length,
+                    "Anchoring transaction {} is not the latest one in the chain of {}",
+                    tx.id(),
+                    chain_length
+                );
+                let payload = tx
+                    .anchoring_payload()
+                    .ok_or_else(|| anyhow!("Anchoring transaction {} has no payload", tx.id()))?;
+                ensure!(
+                    payload.block_height < height,
+                    "Block {} is anchored by the transaction {}",
+                    height,
+                    tx.id()
+                );
+                Some(payload.block_height)
+            }
+            None => {
+                ensure!(
+                    chain_length == 0,
+                    "Proof doesn't contain the latest anchoring transaction"
+                );
+                None
+            }
+        };
+        Ok(AnchoringAbsence {
+            block_height: self.index_proof.block_proof.block.height,
+            chain_length,
+            latest_anchored_height,
+        })
+    }
+}
+
+/// Verified absence of the anchoring transactions covering the block,
+/// see [`TransactionProof::verify_absence`].
+///
+/// [`TransactionProof::verify_absence`]: struct.TransactionProof.html#method.verify_absence
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringAbsence {
+    /// Height of the Exonum block authenticated by the proof.
+    pub block_height: Height,
+    /// Length of the anchoring chain at the authenticated block.
+    pub chain_length: u64,
+    /// Height of the block anchored by the latest anchoring transaction, if any.
+    pub latest_anchored_height: Option<Height>,
+}
+
+/// A proof of the final confirmation of the anchoring transaction in the Bitcoin blockchain
+/// agreed by a quorum of the anchoring nodes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnchorFinalityProof {
+    /// Proof of authenticity for the anchor finality heights index within the database.
+    pub index_proof: IndexProof,
+    /// Proof of the height of the block in which the anchoring transaction has been marked
+    /// as finally confirmed, or of its absence if it has not been finally confirmed yet.
+    pub finality_proof: MapProof<btc::Sha256d, u64>,
+}
+
+/// Identifier of the `sign_input` method of the `BtcAnchoringInterface`.
+const SIGN_INPUT_METHOD_ID: MethodId = 0;
+
+/// Input signature of the anchoring proposal rejected by the anchoring service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedSignature {
+    /// Height of the block with the rejected transaction.
+    pub height: Height,
+    /// Hash of the rejected transaction.
+    pub tx_hash: Hash,
+    /// Service key of the transaction author.
+    pub author: PublicKey,
+    /// Identifier of the signed anchoring transaction.
+    pub txid: btc::Sha256d,
+    /// Index of the signed input.
+    pub input: u32,
+    /// Reason of the rejection.
+    pub error: ExecutionError,
+}
+
+impl RejectedSignature {
+    /// Extracts the rejected signature from the committed transaction, if it is a failed
+    /// `sign_input` call of the anchoring service instance with the given identifier.
+    fn from_committed(instance_id: InstanceId, tx: &CommittedTransaction) -> Option<Self> {
+        let error = tx.status().err()?;
+        let payload = tx.content().payload();
+        if payload.call_info.instance_id != instance_id
+            || payload.call_info.method_id != SIGN_INPUT_METHOD_ID
+        {
+            return None;
+        }
+        let sign_input = SignInput::from_bytes(payload.arguments.as_slice().into()).ok()?;
+        Some(Self {
+            height: tx.location().block_height(),
+            tx_hash: tx.content().object_hash(),
+            author: tx.content().author(),
+            txid: sign_input.txid,
+            input: sign_input.input,
+            error: error.clone(),
+        })
+    }
+}
+
+/// A page of the anchoring transactions chain along with the proof of its authenticity.
+///
+/// The transactions are returned in the form they are stored in the anchoring chain,
+/// i.e. without witnesses if the compact storage is enabled.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionsPage {
+    /// Proof of authenticity for the transactions index within the database.
+    pub index_proof: IndexProof,
+    /// Proof for the transactions of the page in this index.
+    pub transactions_proof: ListProof<btc::Transaction>,
+    /// Index of the first transaction of the next page, if any. Pass it as the `from`
+    /// parameter to get the next page.
+    pub next: Option<u64>,
+}
+
+/// Proof supplied for the verification by the anchoring node.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofToVerify {
+    /// Proof of the anchoring transaction returned by the `find-transaction` endpoint.
+    Transaction(TransactionProof),
+    /// Proof of the Exonum block, i.e. the block header with the precommits
+    /// of the validators.
+    Block(BlockProof),
+}
+
+/// Verdict of the proof verification against the actual validator set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ProofVerdict {
+    /// The proof is valid.
+    Valid {
+        /// Height of the Exonum block authenticated by the proof.
+        block_height: Height,
+        /// Hash of the Exonum block authenticated by the proof.
+        block_hash: Hash,
+        /// Index of the anchoring transaction proven by the proof, if any.
+        anchor_index: Option<u64>,
+        /// Identifier of the anchoring transaction proven by the proof, if any.
+        txid: Option<btc::Sha256d>,
+    },
+    /// The proof is invalid.
+    Invalid {
+        /// Description of the verification failure.
+        reason: String,
+    },
+}
+
+/// Anchor covering the Exonum block, i.e. the first anchoring transaction which anchors
+/// this block or a later one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockCoverage {
+    /// Index of the anchoring transaction in the anchoring chain.
+    pub anchor_index: u64,
+    /// Identifier of the anchoring transaction.
+    pub txid: btc::Sha256d,
+    /// The first block height covered by the anchoring transaction.
+    pub first_height: Height,
+    /// The last block height covered by the anchoring transaction, i.e. the height
+    /// of the anchored block.
+    pub last_height: Height,
+    /// Confirmation state of the anchoring transaction.
+    pub state: AnchorConfirmationState,
+    /// Number of the Bitcoin confirmations the block effectively has, i.e. the number
+    /// of confirmations of the anchoring transaction at the Bitcoin height given
+    /// in the request. Unknown until the anchoring nodes agree on the location
+    /// of the anchoring transaction.
+    pub confirmations: Option<u64>,
+}
+
+/// Anchoring transaction found by its Bitcoin identifier together with the anchored block.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnchoredPayload {
+    /// Index of the anchoring transaction in the anchoring chain.
+    pub anchor_index: u64,
+    /// Payload of the anchoring transaction, i.e. the height and the hash of the anchored
+    /// Exonum block.
+    pub payload: btc::Payload,
+    /// Proof of existence of the anchoring transaction.
+    pub proof: TransactionProof,
+}
+
+/// Anchoring configuration which has been actual together with the height from which
+/// it became actual.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoricalConfig {
+    /// Anchoring configuration.
+    pub config: Config,
+    /// Height of the block in which the configuration became actual. Unknown for the
+    /// configurations activated before the `1.1` artifact.
+    pub actual_from: Option<Height>,
+}
+
+/// Kind of the actual anchoring state.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AnchoringStateKind {
+    /// The usual anchoring workflow.
+    Regular,
+    /// The transition of the anchoring chain to the address of the following configuration.
+    Transition,
+}
+
+/// Summary of the anchoring service status.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringStatus {
+    /// Kind of the actual anchoring state.
+    pub state: AnchoringStateKind,
+    /// Height of the next block to be anchored.
+    pub next_anchoring_height: Height,
+    /// Height of the latest anchored block, if any.
+    pub latest_anchored_height: Option<Height>,
+    /// Progress of collecting signatures for the actual anchoring proposal, including
+    /// the number of signatures for each input, if there is a proposal.
+    pub signing_progress: Option<SigningProgress>,
+    /// Amount of satoshis available to the anchoring wallet.
+    pub available_funds: u64,
+}
+
+/// Schedule of the next anchoring transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringSchedule {
+    /// Kind of the actual anchoring state.
+    pub state: AnchoringStateKind,
+    /// Height of the next block to be anchored.
+    pub next_anchoring_height: Height,
+    /// Height of the latest committed block.
+    pub latest_height: Height,
+    /// Number of blocks to be committed until the next block to be anchored. It is zero
+    /// if this block has been already committed and its anchoring proposal is being signed.
+    pub blocks_remaining: u64,
+}
+
+/// Range of the Exonum block heights available in the core schema of the node.
+///
+/// Requests for the heights outside of this range are rejected, and the JSON-encoded
+/// range is returned in the `detail` field of the error body, so the clients can adjust
+/// their requests.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HeightRange {
+    /// The first available height.
+    pub from: Height,
+    /// The last available height, i.e. the height of the latest committed block.
+    pub to: Height,
+}
+
+impl HeightRange {
+    /// Checks that the range contains the given height.
+    pub fn contains(&self, height: Height) -> bool {
+        self.from <= height && height <= self.to
+    }
+
+    /// Parses the range from the error returned for the out-of-range height, if the error
+    /// contains it.
+    pub fn from_error(error: &api::Error) -> Option<Self> {
+        serde_json::from_str(&error.body.detail).ok()
+    }
+
+    fn out_of_range_error(self, height: Height) -> api::Error {
+        api::Error::new(StatusCode::UNPROCESSABLE_ENTITY)
+            .title(format!(
+                "Block height {} is out of the available range [{}, {}]",
+                height, self.from, self.to
+            ))
+            .detail(serde_json::to_string(&self).unwrap())
+    }
 }
 
 /// State of the next anchoring transaction proposal.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum AnchoringProposalState {
     /// There is no anchoring transaction proposal at the time.
-    None,
+    None {
+        /// Reason why there is no proposal.
+        reason: NoProposalReason,
+    },
     /// There is a non-finalized anchoring transaction.
     Available {
         /// Proposal content.
@@ -79,9 +418,12 @@ pub enum AnchoringProposalState {
 impl AnchoringProposalState {
     fn try_from_proposal(
         proposal: Option<Result<(btc::Transaction, Vec<btc::Transaction>), btc::BuilderError>>,
+        no_proposal_reason: impl FnOnce() -> NoProposalReason,
     ) -> Result<Self, api::Error> {
         match proposal {
-            None => Ok(AnchoringProposalState::None),
+            None => Ok(AnchoringProposalState::None {
+                reason: no_proposal_reason(),
+            }),
             Some(Ok((transaction, inputs))) => Ok(AnchoringProposalState::Available {
                 transaction,
                 inputs,
@@ -108,6 +450,503 @@ impl From<u64> for AnchoringChainLength {
     }
 }
 
+/// Rolling digest of the anchoring transactions chain, which allows to compare
+/// the anchoring histories of two deployments in one request.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringChainDigest {
+    /// Number of the anchoring transactions covered by the digest.
+    pub length: u64,
+    /// Digest of the transactions: `d(0) = 0`, `d(i + 1) = sha256(d(i) || txid(i))`,
+    /// where `txid(i)` is the identifier of the transaction with the index `i`.
+    pub digest: Hash,
+}
+
+impl AnchoringChainDigest {
+    /// Computes the digest of the given anchoring transactions in order of their indices.
+    pub fn new<I>(transactions: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<btc::Transaction>,
+    {
+        transactions
+            .into_iter()
+            .fold(Self::default(), |digest, transaction| {
+                digest.append(transaction.borrow())
+            })
+    }
+
+    /// Returns the digest of the chain with the given transaction appended.
+    pub fn append(self, transaction: &btc::Transaction) -> Self {
+        let txid = transaction.id();
+        let mut bytes = self.digest.as_ref().to_vec();
+        bytes.extend_from_slice(&txid.0[..]);
+        Self {
+            length: self.length + 1,
+            digest: crypto::hash(&bytes),
+        }
+    }
+}
+
+impl Default for AnchoringChainDigest {
+    fn default() -> Self {
+        Self {
+            length: 0,
+            digest: Hash::zero(),
+        }
+    }
+}
+
+/// Change of the anchoring configuration parameter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValueChange<T> {
+    /// Value in the actual configuration.
+    pub actual: T,
+    /// Value in the following configuration.
+    pub following: T,
+}
+
+impl<T: PartialEq> ValueChange<T> {
+    fn new(actual: T, following: T) -> Option<Self> {
+        if actual == following {
+            None
+        } else {
+            Some(Self { actual, following })
+        }
+    }
+}
+
+/// Differences of the following anchoring configuration from the actual one, which take
+/// effect once the following configuration becomes actual.
+///
+/// The unchanged parameters are omitted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigDiff {
+    /// Anchoring keys which are present only in the following configuration.
+    /// Keys with the changed weight are both removed and added.
+    pub added_keys: Vec<AnchoringKeys>,
+    /// Anchoring keys which are present only in the actual configuration.
+    pub removed_keys: Vec<AnchoringKeys>,
+    /// Change of the total weight of the signatures required to spend the anchoring output.
+    pub signature_quorum: Option<ValueChange<usize>>,
+    /// Change of the interval in blocks between the anchored blocks.
+    pub anchoring_interval: Option<ValueChange<u64>>,
+    /// Change of the fee per byte in satoshis.
+    pub transaction_fee: Option<ValueChange<u64>>,
+    /// Change of the address of the fee wallet.
+    pub fee_payer_address: Option<ValueChange<Option<btc::Address>>>,
+    /// Change of the anchoring address.
+    pub anchoring_address: Option<ValueChange<btc::Address>>,
+}
+
+impl ConfigDiff {
+    /// Computes the differences of the following configuration from the actual one.
+    pub fn new(actual: &Config, following: &Config) -> Self {
+        let difference = |from: &Config, to: &Config| {
+            from.anchoring_keys
+                .iter()
+                .filter(|keys| !to.anchoring_keys.contains(keys))
+                .cloned()
+                .collect()
+        };
+        Self {
+            added_keys: difference(following, actual),
+            removed_keys: difference(actual, following),
+            signature_quorum: ValueChange::new(
+                actual.signature_quorum(),
+                following.signature_quorum(),
+            ),
+            anchoring_interval: ValueChange::new(
+                actual.anchoring_interval,
+                following.anchoring_interval,
+            ),
+            transaction_fee: ValueChange::new(actual.transaction_fee, following.transaction_fee),
+            fee_payer_address: ValueChange::new(
+                actual.fee_payer_address(),
+                following.fee_payer_address(),
+            ),
+            anchoring_address: ValueChange::new(
+                actual.anchoring_address(),
+                following.anchoring_address(),
+            ),
+        }
+    }
+}
+
+/// State of the funding transaction in the anchoring service.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FundingTransactionState {
+    /// Funding transaction is unknown to the anchoring service.
+    Unknown,
+    /// Funding transaction is waiting for the confirmations from the anchoring nodes.
+    Pending {
+        /// Number of the received confirmations.
+        confirmations: u64,
+        /// Number of the confirmations required to use this transaction.
+        required: u64,
+    },
+    /// Funding transaction has received enough confirmations, but it has been rejected
+    /// together with the other funding transactions confirmed in the same block.
+    /// It is applied once any anchoring node confirms it again.
+    Rejected {
+        /// Height of the block in which the funding transaction has been rejected.
+        height: u64,
+    },
+    /// Funding transaction is confirmed and will be spent by the next anchoring transaction.
+    Spendable,
+    /// Funding transaction has been already spent by one of the anchoring transactions.
+    Spent,
+}
+
+/// State of the anchoring transaction confirmation agreed by the anchoring nodes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AnchorConfirmationState {
+    /// Anchoring transaction is unknown to the anchoring service.
+    Unknown,
+    /// Anchoring transaction is waiting for the anchoring nodes to report enough
+    /// confirmations.
+    Pending {
+        /// Location of the anchoring transaction agreed by the anchoring nodes, if any.
+        inclusion: Option<AnchorInclusion>,
+        /// Number of the anchoring nodes which have reported enough confirmations.
+        votes: u64,
+        /// Number of the votes required to consider the anchoring transaction
+        /// finally confirmed.
+        required: u64,
+    },
+    /// Anchoring transaction is finally confirmed by a quorum of the anchoring nodes.
+    Final {
+        /// Location of the anchoring transaction.
+        inclusion: AnchorInclusion,
+        /// Height of the block in which the anchoring transaction has been marked as
+        /// finally confirmed.
+        confirmed_at: Height,
+    },
+}
+
+impl AnchorConfirmationState {
+    /// Returns the confirmation state of the anchoring transaction with the given identifier.
+    pub(crate) fn from_schema<T: Access>(schema: &Schema<T>, txid: btc::Sha256d) -> Self {
+        if schema.finalization_height(&txid).is_none() {
+            return AnchorConfirmationState::Unknown;
+        }
+
+        let inclusion = schema.anchor_inclusion(&txid);
+        match (inclusion, schema.anchor_finality_height(&txid)) {
+            (Some(inclusion), Some(confirmed_at)) => AnchorConfirmationState::Final {
+                inclusion,
+                confirmed_at,
+            },
+            (inclusion, _) => AnchorConfirmationState::Pending {
+                inclusion,
+                votes: schema
+                    .anchor_finality_votes
+                    .get(&txid)
+                    .map_or(0, |votes| votes.0.len() as u64),
+                required: schema.actual_config().byzantine_quorum() as u64,
+            },
+        }
+    }
+}
+
+/// State of the validator approvals for the finalized anchoring transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SpecialTxApprovalState {
+    /// The anchoring transaction is not special, so it doesn't require approvals.
+    NotRequired,
+    /// The special anchoring transaction is waiting for the approvals of the validators.
+    Pending {
+        /// Service keys of the validators which have approved the transaction.
+        approvers: Vec<PublicKey>,
+        /// Number of the approvals required by the emergency brake.
+        required: u64,
+    },
+    /// The special anchoring transaction has been approved by enough validators.
+    Approved {
+        /// Service keys of the validators which have approved the transaction.
+        approvers: Vec<PublicKey>,
+    },
+}
+
+impl SpecialTxApprovalState {
+    /// Checks that the anchoring transaction can be broadcast to the Bitcoin network.
+    pub fn is_broadcast_allowed(&self) -> bool {
+        match self {
+            SpecialTxApprovalState::Pending { .. } => false,
+            _ => true,
+        }
+    }
+
+    /// Returns the approval state of the anchoring transaction with the given identifier.
+    pub(crate) fn from_schema<T: Access>(schema: &Schema<T>, txid: btc::Sha256d) -> Self {
+        let approvers = match schema.special_tx_approvals(&txid) {
+            Some(approvers) => approvers,
+            None => return SpecialTxApprovalState::NotRequired,
+        };
+        // The supervisor may release the brake by removing it from the configuration.
+        let required = schema
+            .actual_config()
+            .emergency_brake
+            .map_or(0, |brake| u64::from(brake.required_approvals));
+        if approvers.len() as u64 >= required {
+            SpecialTxApprovalState::Approved { approvers }
+        } else {
+            SpecialTxApprovalState::Pending {
+                approvers,
+                required,
+            }
+        }
+    }
+}
+
+/// Limits of the anchoring service.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceLimits {
+    /// Maximum number of inputs in the anchoring transaction.
+    pub max_inputs_per_anchor: usize,
+    /// Maximum number of anchoring nodes.
+    pub max_anchoring_nodes: usize,
+    /// Version of the anchoring payload format.
+    pub payload_version: u8,
+    /// Maximum length of the encrypted memo in bytes.
+    pub max_memo_len: usize,
+    /// Minimal value of the anchoring output in satoshis.
+    pub dust_threshold: u64,
+    /// Minimal fee per byte in satoshis.
+    pub min_transaction_fee: u64,
+    /// Bitcoin networks supported by the anchoring service.
+    pub supported_networks: Vec<bitcoin::Network>,
+    /// Number of anchoring nodes in the actual configuration.
+    pub anchoring_nodes: usize,
+    /// Total weight of the signatures required to spend the anchoring output
+    /// in the actual configuration.
+    pub quorum: usize,
+    /// Maximum weight of the funding transaction in the actual configuration.
+    pub max_funding_tx_weight: u64,
+    /// Maximum number of outputs of the funding transaction in the actual configuration.
+    pub max_funding_tx_outputs: u32,
+}
+
+/// Version and compatibility metadata of the anchoring service build.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceVersion {
+    /// Version of the `exonum-btc-anchoring` crate.
+    pub crate_version: String,
+    /// Version of the anchoring payload format.
+    pub payload_version: u8,
+    /// Hash of the protobuf sources of the service data types.
+    pub schema_hash: Hash,
+    /// Features supported by the service.
+    pub features: Vec<String>,
+}
+
+impl ServiceVersion {
+    /// Features supported by this build of the anchoring service.
+    pub const FEATURES: &'static [&'static str] = &[
+        "fee-payer",
+        "encrypted-memo",
+        "key-attestation",
+        "anchor-confirmation",
+        "final-anchor",
+        "finalization-incidents",
+    ];
+
+    /// Returns the metadata of this build.
+    pub fn current() -> Self {
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION").to_owned(),
+            payload_version: btc::Payload::VERSION,
+            schema_hash: crate::proto::schema_hash(),
+            features: Self::FEATURES.iter().map(|&s| s.to_owned()).collect(),
+        }
+    }
+
+    /// Checks that the anchoring service with the given metadata is compatible with
+    /// this build, i.e. it uses the same payload and data formats and supports all
+    /// features of this build.
+    pub fn check_compatibility(&self, other: &Self) -> anyhow::Result<()> {
+        ensure!(
+            self.payload_version == other.payload_version,
+            "Payload format version mismatch: expected {}, got {} (crate version {})",
+            self.payload_version,
+            other.payload_version,
+            other.crate_version
+        );
+        let missing_features = self
+            .features
+            .iter()
+            .filter(|feature| !other.features.contains(feature))
+            .cloned()
+            .collect::<Vec<_>>();
+        ensure!(
+            missing_features.is_empty(),
+            "Features are not supported (crate version {}): {}",
+            other.crate_version,
+            missing_features.join(", ")
+        );
+        ensure!(
+            self.schema_hash == other.schema_hash,
+            "Protobuf schema mismatch: expected {}, got {} (crate version {})",
+            self.schema_hash.to_hex(),
+            other.schema_hash.to_hex(),
+            other.crate_version
+        );
+        Ok(())
+    }
+}
+
+/// Object hashes of the anchoring schema indexes.
+///
+/// If the service state of two nodes diverges, comparing these hashes shows which
+/// index differs. The entries are not Merkelized, so their hashes are calculated
+/// from their content.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaHashes {
+    /// Object hash of the anchoring transactions chain.
+    pub transactions_chain: Hash,
+    /// Object hash of the anchor coverage list.
+    pub anchor_coverage: Hash,
+    /// Object hash of the index of the anchoring transaction positions by their identifiers.
+    pub transaction_indexes: Hash,
+    /// Object hash of the index of the anchoring transaction positions by the anchored heights.
+    pub anchored_heights: Hash,
+    /// Object hash of the spent funding transactions index.
+    pub spent_funding_transactions: Hash,
+    /// Object hash of the index of the anchoring transactions by the outputs spent by them.
+    pub spent_outputs: Hash,
+    /// Object hash of the input signatures index.
+    pub transaction_signatures: Hash,
+    /// Object hash of the unconfirmed funding transactions index.
+    pub unconfirmed_funding_transactions: Hash,
+    /// Object hash of the pending funding transactions index.
+    pub pending_funding_transactions: Hash,
+    /// Object hash of the rejected funding transactions index.
+    pub rejected_funding_transactions: Hash,
+    /// Object hash of the key attestations index.
+    pub key_attestations: Hash,
+    /// Object hash of the finalization heights index.
+    pub finalization_heights: Hash,
+    /// Object hash of the finalization incidents list.
+    pub finalization_incidents: Hash,
+    /// Object hash of the signature conflicts list.
+    pub signature_conflicts: Hash,
+    /// Object hash of the anchor inclusion votes index.
+    pub anchor_inclusion_votes: Hash,
+    /// Object hash of the anchor inclusions index.
+    pub anchor_inclusions: Hash,
+    /// Object hash of the anchor finality votes index.
+    pub anchor_finality_votes: Hash,
+    /// Object hash of the anchor finality heights index.
+    pub anchor_finality_heights: Hash,
+    /// Object hash of the finality latencies list.
+    pub finality_latencies: Hash,
+    /// Object hash of the index of the anchoring transaction witnesses stored separately.
+    pub transaction_witnesses: Hash,
+    /// Object hash of the witness scripts index.
+    pub witness_scripts: Hash,
+    /// Object hash of the configuration history list.
+    pub config_history: Hash,
+    /// Object hash of the configuration activation heights list.
+    pub config_activation_heights: Hash,
+    /// Object hash of the recovery exports list.
+    pub recovery_exports: Hash,
+    /// Object hash of the special anchoring transaction approvals index.
+    pub special_tx_approvals: Hash,
+    /// Hash of the actual configuration.
+    pub actual_config: Option<Hash>,
+    /// Hash of the following configuration, if any.
+    pub following_config: Option<Hash>,
+    /// Hash of the unspent funding transaction, if any.
+    pub unspent_funding_transaction: Option<Hash>,
+    /// Hash of the unspent fee wallet funding transaction, if any.
+    pub unspent_fee_payer_transaction: Option<Hash>,
+}
+
+/// A page of the list items ordered by their position in the list.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page<T> {
+    /// Items of the page.
+    pub items: Vec<T>,
+    /// Position of the first item of the next page, if any. Pass it as the `start`
+    /// parameter to get the next page.
+    pub next: Option<u64>,
+}
+
+/// Query parameters for the paginated list requests.
+///
+/// Items are returned in the order of their position in the list, which coincides with
+/// the order of the block heights, so the pages remain stable while the list grows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PageQuery {
+    /// Position of the first item to return.
+    pub start: Option<u64>,
+    /// Returns only the items recorded at the given height or after it.
+    pub from_height: Option<Height>,
+    /// Returns only the items recorded at the given height or before it.
+    pub to_height: Option<Height>,
+    /// Maximum number of items on the page, `PageQuery::DEFAULT_LIMIT` by default.
+    pub limit: Option<u64>,
+}
+
+impl PageQuery {
+    /// Default number of items on the page.
+    pub const DEFAULT_LIMIT: u64 = 100;
+    /// Maximum number of items on the page.
+    pub const MAX_LIMIT: u64 = 1_000;
+
+    /// Returns a page of the list with the given length, whose items are ordered by
+    /// the height returned by the `height` closure.
+    pub(crate) fn paginate<T>(
+        &self,
+        len: u64,
+        item: impl Fn(u64) -> T,
+        height: impl Fn(&T) -> Height,
+    ) -> api::Result<Page<T>> {
+        let limit = self.limit.unwrap_or(Self::DEFAULT_LIMIT);
+        if limit == 0 || limit > Self::MAX_LIMIT {
+            return Err(api::Error::bad_request()
+                .title("Invalid page request")
+                .detail(format!(
+                    "Page limit should be in range from 1 to {}",
+                    Self::MAX_LIMIT
+                )));
+        }
+
+        // Items are ordered by height, so the first suitable one can be found by
+        // the binary search.
+        let mut start = cmp::min(self.start.unwrap_or(0), len);
+        if let Some(from_height) = self.from_height {
+            let mut end = len;
+            while start < end {
+                let mid = start + (end - start) / 2;
+                if height(&item(mid)) < from_height {
+                    start = mid + 1;
+                } else {
+                    end = mid;
+                }
+            }
+        }
+
+        let is_suitable = |value: &T| self.to_height.map_or(true, |to| height(value) <= to);
+        let mut items = Vec::new();
+        let mut position = start;
+        while position < len {
+            let value = item(position);
+            if !is_suitable(&value) {
+                return Ok(Page { items, next: None });
+            }
+            if items.len() as u64 == limit {
+                return Ok(Page {
+                    items,
+                    next: Some(position),
+                });
+            }
+            items.push(value);
+            position += 1;
+        }
+        Ok(Page { items, next: None })
+    }
+}
+
 /// Public API client for the Exonum Bitcoin anchoring service.
 #[async_trait]
 pub trait PublicApi {
@@ -139,6 +978,15 @@ pub trait PublicApi {
     /// otherwise, return the anchoring transaction with the height that is greater or equal
     /// to the given one.
     ///
+    /// If the given height is not anchored yet, the latest anchoring transaction is returned,
+    /// and its proof is the proof of absence of the later anchors, which can be checked by
+    /// [`TransactionProof::verify_absence`].
+    ///
+    /// The height above the latest committed block is rejected with the
+    /// `422 Unprocessable Entity` error, which contains the available [`HeightRange`].
+    /// The `404 Not Found` error is returned if the node lacks the block history
+    /// to build the proof.
+    ///
     /// | Property    | Value |
     /// |-------------|-------|
     /// | Path        | `/api/services/{btc_anchoring}/find-transaction` |
@@ -148,6 +996,8 @@ pub trait PublicApi {
     ///
     /// [`FindTransactionQuery`]: struct.FindTransactionQuery.html
     /// [`TransactionProof`]: struct.TransactionProof.html
+    /// [`HeightRange`]: struct.HeightRange.html
+    /// [`TransactionProof::verify_absence`]: struct.TransactionProof.html#method.verify_absence
     async fn find_transaction(
         &self,
         height: Option<Height>,
@@ -163,102 +1013,759 @@ pub trait PublicApi {
     ///
     /// [`config`]: ../config/struct.Config.html
     async fn config(&self) -> Result<Config, Self::Error>;
-}
-
-/// Private API client for the Exonum Bitcoin anchoring service.
-#[async_trait]
-pub trait PrivateApi {
-    /// Error type for the current API client implementation.
-    type Error;
-    /// Creates and broadcasts the `TxSignature` transaction, which is signed
-    /// by the current node, and returns its hash.
+    /// Returns the state of the funding transaction with the given identifier.
     ///
     /// | Property    | Value |
     /// |-------------|-------|
-    /// | Path        | `/api/services/{btc_anchoring}/sign-input` |
-    /// | Method      | POST   |
-    /// | Query type  | [`SignInput`] |
-    /// | Return type | [`Hash`] |
+    /// | Path        | `/api/services/{btc_anchoring}/funds/status` |
+    /// | Method      | GET   |
+    /// | Query type  | [`FundingTransactionQuery`] |
+    /// | Return type | [`FundingTransactionState`] |
     ///
-    /// [`SignInput`]: ../blockchain/struct.SignInput.html
-    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
-    async fn sign_input(&self, sign_input: SignInput) -> Result<Hash, Self::Error>;
-    /// Adds funds via suitable funding transaction.
+    /// [`FundingTransactionQuery`]: struct.FundingTransactionQuery.html
+    /// [`FundingTransactionState`]: enum.FundingTransactionState.html
+    async fn funding_transaction_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<FundingTransactionState, Self::Error>;
+    /// Returns the state of the anchoring transaction confirmation in the Bitcoin blockchain
+    /// agreed by the anchoring nodes.
     ///
-    /// Bitcoin transaction should have output with value to the current anchoring address.
-    /// The transaction will be applied if 2/3+1 anchoring nodes sent it.
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/anchor/status` |
+    /// | Method      | GET   |
+    /// | Query type  | [`AnchorStatusQuery`] |
+    /// | Return type | [`AnchorConfirmationState`] |
+    ///
+    /// [`AnchorStatusQuery`]: struct.AnchorStatusQuery.html
+    /// [`AnchorConfirmationState`]: enum.AnchorConfirmationState.html
+    async fn anchor_confirmation_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<AnchorConfirmationState, Self::Error>;
+    /// Returns the proof of the final confirmation of the anchoring transaction with
+    /// the given identifier, so third parties can check which anchors are final without
+    /// trusting the node.
     ///
     /// | Property    | Value |
     /// |-------------|-------|
-    /// | Path        | `/api/services/{btc_anchoring}/add-funds` |
-    /// | Method      | POST   |
-    /// | Query type  | [`AddFunds`] |
-    /// | Return type | [`Hash`] |
+    /// | Path        | `/api/services/{btc_anchoring}/anchor/finality-proof` |
+    /// | Method      | GET   |
+    /// | Query type  | [`AnchorStatusQuery`] |
+    /// | Return type | [`AnchorFinalityProof`] |
     ///
-    /// [`AddFunds`]: ../blockchain/struct.AddFunds.html
-    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
-    async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error>;
-    /// Returns a proposal for the next anchoring transaction, if it makes sense.
-    /// If there is not enough satoshis to create a proposal an error is returned.
+    /// [`AnchorStatusQuery`]: struct.AnchorStatusQuery.html
+    /// [`AnchorFinalityProof`]: struct.AnchorFinalityProof.html
+    async fn anchor_finality_proof(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<AnchorFinalityProof, Self::Error>;
+    /// Returns limits of the anchoring service, both compile-time and configured ones.
     ///
     /// | Property    | Value |
     /// |-------------|-------|
-    /// | Path        | `/api/services/{btc_anchoring}/anchoring-proposal` |
+    /// | Path        | `/api/services/{btc_anchoring}/limits` |
     /// | Method      | GET   |
     /// | Query type  | - |
-    /// | Return type | [`AnchoringProposalState`] |
+    /// | Return type | [`ServiceLimits`] |
     ///
-    /// [`AnchoringProposalState`]: enum.AnchoringProposalState.html
-    async fn anchoring_proposal(&self) -> Result<AnchoringProposalState, Self::Error>;
-    /// Returns an actual anchoring configuration.
+    /// [`ServiceLimits`]: struct.ServiceLimits.html
+    async fn limits(&self) -> Result<ServiceLimits, Self::Error>;
+    /// Returns object hashes of the anchoring schema indexes. This endpoint is intended
+    /// for debugging of the service state divergence between nodes.
     ///
     /// | Property    | Value |
     /// |-------------|-------|
-    /// | Path        | `/api/services/{btc_anchoring}/config` |
+    /// | Path        | `/api/services/{btc_anchoring}/debug/state-hashes` |
     /// | Method      | GET   |
     /// | Query type  | - |
-    /// | Return type | [`Config`] |
+    /// | Return type | [`SchemaHashes`] |
     ///
-    /// [`config`]: ../config/struct.Config.html
-    async fn config(&self) -> Result<Config, Self::Error>;
-    /// Returns an anchoring transaction with the specified index in anchoring transactions chain.
+    /// [`SchemaHashes`]: struct.SchemaHashes.html
+    async fn state_hashes(&self) -> Result<SchemaHashes, Self::Error>;
+    /// Returns a page of the divergences detected during the finalization of
+    /// the anchoring proposals.
     ///
     /// | Property    | Value |
     /// |-------------|-------|
-    /// | Path        | `/api/services/{btc_anchoring}/transaction` |
+    /// | Path        | `/api/services/{btc_anchoring}/finalization-incidents` |
     /// | Method      | GET   |
-    /// | Query type  | [`IndexQuery`] |
-    /// | Return type | [`Option<btc::Transaction>`] |
+    /// | Query type  | [`PageQuery`] |
+    /// | Return type | [`Page<FinalizationIncident>`] |
     ///
-    /// ['IndexQuery']: struct.IndexQuery.html
-    /// [`Option<btc::Transaction>`]: ../btc/struct.Transaction.html
-    async fn transaction_with_index(
+    /// [`PageQuery`]: struct.PageQuery.html
+    /// [`Page<FinalizationIncident>`]: ../blockchain/struct.FinalizationIncident.html
+    async fn finalization_incidents(
         &self,
-        index: u64,
-    ) -> Result<Option<btc::Transaction>, Self::Error>;
-    /// Returns a total number of anchoring transactions in the chain.
+        query: PageQuery,
+    ) -> Result<Page<FinalizationIncident>, Self::Error>;
+    /// Returns a page of the conflicting signatures of the anchoring proposal inputs
+    /// made by the same Bitcoin keys. Each conflict is an evidence of the possible
+    /// compromise of the key.
     ///
     /// | Property    | Value |
     /// |-------------|-------|
-    /// | Path        | `/api/services/{btc_anchoring}/transactions-count` |
+    /// | Path        | `/api/services/{btc_anchoring}/signature-conflicts` |
+    /// | Method      | GET   |
+    /// | Query type  | [`PageQuery`] |
+    /// | Return type | [`Page<SignatureConflict>`] |
+    ///
+    /// [`PageQuery`]: struct.PageQuery.html
+    /// [`Page<SignatureConflict>`]: ../blockchain/struct.SignatureConflict.html
+    async fn signature_conflicts(
+        &self,
+        query: PageQuery,
+    ) -> Result<Page<SignatureConflict>, Self::Error>;
+    /// Returns the input signatures of the anchoring proposals rejected by the service
+    /// in the latest blocks, together with the authors and the reasons of the rejection.
+    ///
+    /// The rejected transactions don't change the service data, so the signatures are
+    /// collected from the committed blocks, and the number of the scanned blocks is bounded.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/rejected-signatures` |
+    /// | Method      | GET   |
+    /// | Query type  | [`RejectedSignaturesQuery`] |
+    /// | Return type | [`Vec<RejectedSignature>`] |
+    ///
+    /// [`RejectedSignaturesQuery`]: struct.RejectedSignaturesQuery.html
+    /// [`Vec<RejectedSignature>`]: struct.RejectedSignature.html
+    async fn rejected_signatures(
+        &self,
+        query: RejectedSignaturesQuery,
+    ) -> Result<Vec<RejectedSignature>, Self::Error>;
+    /// Returns the version and compatibility metadata of the anchoring service.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/version` |
     /// | Method      | GET   |
     /// | Query type  | - |
-    /// | Return type | [`AnchoringChainLength`] |
+    /// | Return type | [`ServiceVersion`] |
     ///
-    /// [`AnchoringChainLength`]: struct.AnchoringChainLength.html
-    async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error>;
-}
-
-struct ApiImpl(ServiceApiState);
-
-impl ApiImpl {
-    fn broadcaster(&self) -> api::Result<Broadcaster> {
-        self.0.broadcaster().ok_or_else(|| {
-            api::Error::bad_request()
-                .title("Invalid broadcast request")
-                .detail("Node is not a validator")
-        })
-    }
+    /// [`ServiceVersion`]: struct.ServiceVersion.html
+    async fn version(&self) -> Result<ServiceVersion, Self::Error>;
+    /// Returns the distribution of the latencies of the latest finally confirmed anchors,
+    /// i.e. the number of blocks between the anchored block and the final confirmation
+    /// of its anchoring transaction, if any anchor has been finally confirmed.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/anchoring-latency` |
+    /// | Method      | GET   |
+    /// | Query type  | [`LatencyQuery`] |
+    /// | Return type | [`Option<AnchoringLatency>`] |
+    ///
+    /// [`LatencyQuery`]: struct.LatencyQuery.html
+    /// [`Option<AnchoringLatency>`]: ../blockchain/struct.AnchoringLatency.html
+    async fn anchoring_latency(
+        &self,
+        query: LatencyQuery,
+    ) -> Result<Option<AnchoringLatency>, Self::Error>;
+    /// Returns the diagnostics of the transition of the anchoring chain to the address
+    /// of the following configuration, i.e. the pending step and the estimated number
+    /// of blocks remaining, if the transition is in progress.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/transition-status` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Option<TransitionStatus>`] |
+    ///
+    /// [`Option<TransitionStatus>`]: ../blockchain/struct.TransitionStatus.html
+    async fn transition_status(&self) -> Result<Option<TransitionStatus>, Self::Error>;
+    /// Returns the differences of the following anchoring configuration from the actual
+    /// one if the following configuration exists, i.e. the anchoring nodes and the other
+    /// parameters which change once the transition is over.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/config/diff` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Option<ConfigDiff>`] |
+    ///
+    /// [`Option<ConfigDiff>`]: struct.ConfigDiff.html
+    async fn config_diff(&self) -> Result<Option<ConfigDiff>, Self::Error>;
+    /// Returns the anchoring configurations which have been actual in order of their
+    /// activation together with the heights from which they became actual, so the old
+    /// anchoring transactions can be validated against the configurations of their time.
+    /// The following configuration is not included.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/config/history` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Vec<HistoricalConfig>`] |
+    ///
+    /// [`Vec<HistoricalConfig>`]: struct.HistoricalConfig.html
+    async fn config_history(&self) -> Result<Vec<HistoricalConfig>, Self::Error>;
+    /// Returns the rolling digest of the whole anchoring transactions chain if the index
+    /// is not specified, otherwise, the digest of the chain up to the transaction with
+    /// the given index inclusive. The index out of the chain is rejected with the
+    /// `404 Not Found` error.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/chain/digest` |
+    /// | Method      | GET   |
+    /// | Query type  | [`ChainDigestQuery`] |
+    /// | Return type | [`AnchoringChainDigest`] |
+    ///
+    /// [`ChainDigestQuery`]: struct.ChainDigestQuery.html
+    /// [`AnchoringChainDigest`]: struct.AnchoringChainDigest.html
+    async fn chain_digest(
+        &self,
+        query: ChainDigestQuery,
+    ) -> Result<AnchoringChainDigest, Self::Error>;
+    /// Returns a page of the anchoring transactions chain starting from the given index
+    /// along with the Merkle proof for it. The page is empty if the index is beyond
+    /// the end of the chain.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/transactions` |
+    /// | Method      | GET   |
+    /// | Query type  | [`TransactionsQuery`] |
+    /// | Return type | [`TransactionsPage`] |
+    ///
+    /// [`TransactionsQuery`]: struct.TransactionsQuery.html
+    /// [`TransactionsPage`]: struct.TransactionsPage.html
+    async fn transactions(&self, query: TransactionsQuery)
+        -> Result<TransactionsPage, Self::Error>;
+    /// Returns the anchor covering the block with the given height, i.e. the first
+    /// anchoring transaction which anchors this block or a later one, if the block
+    /// has been anchored.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/coverage` |
+    /// | Method      | GET   |
+    /// | Query type  | [`CoverageQuery`] |
+    /// | Return type | [`Option<BlockCoverage>`] |
+    ///
+    /// [`CoverageQuery`]: struct.CoverageQuery.html
+    /// [`Option<BlockCoverage>`]: struct.BlockCoverage.html
+    async fn coverage(&self, query: CoverageQuery) -> Result<Option<BlockCoverage>, Self::Error>;
+    /// Returns the summary of the anchoring service status, i.e. the actual anchoring state,
+    /// the anchoring heights, the signing progress of the actual proposal and the available
+    /// funds.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/status` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`AnchoringStatus`] |
+    ///
+    /// [`AnchoringStatus`]: struct.AnchoringStatus.html
+    async fn status(&self) -> Result<AnchoringStatus, Self::Error>;
+    /// Returns the height of the next block to be anchored and the number of blocks
+    /// remaining until then, taking the transition state into account. Returns `None`
+    /// if the anchoring chain has been shut down.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/schedule` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Option<AnchoringSchedule>`] |
+    ///
+    /// [`Option<AnchoringSchedule>`]: struct.AnchoringSchedule.html
+    async fn schedule(&self) -> Result<Option<AnchoringSchedule>, Self::Error>;
+    /// Verifies the supplied proof of the anchoring transaction or the Exonum block against
+    /// the actual validator set. The invalid proofs are reported in the verdict rather than
+    /// as errors.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/verify-proof` |
+    /// | Method      | POST  |
+    /// | Query type  | [`ProofToVerify`] |
+    /// | Return type | [`ProofVerdict`] |
+    ///
+    /// [`ProofToVerify`]: enum.ProofToVerify.html
+    /// [`ProofVerdict`]: enum.ProofVerdict.html
+    async fn verify_proof(&self, proof: ProofToVerify) -> Result<ProofVerdict, Self::Error>;
+    /// Returns the anchoring transaction with the given Bitcoin identifier together with
+    /// the Exonum block anchored by it, if the transaction belongs to the anchoring chain.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/transaction-by-txid` |
+    /// | Method      | GET   |
+    /// | Query type  | [`TxidQuery`] |
+    /// | Return type | [`Option<AnchoredPayload>`] |
+    ///
+    /// [`TxidQuery`]: struct.TxidQuery.html
+    /// [`Option<AnchoredPayload>`]: struct.AnchoredPayload.html
+    async fn transaction_by_txid(
+        &self,
+        query: TxidQuery,
+    ) -> Result<Option<AnchoredPayload>, Self::Error>;
+}
+
+/// Private API client for the Exonum Bitcoin anchoring service.
+///
+/// This API contains the signing operations and the read-only requests needed
+/// by the anchoring nodes. The mutating signing endpoints are placed under the `signing/`
+/// path prefix.
+#[async_trait]
+pub trait PrivateApi {
+    /// Error type for the current API client implementation.
+    type Error;
+    /// Creates and broadcasts the `TxSignature` transaction, which is signed
+    /// by the current node, and returns its hash.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/signing/sign-input` |
+    /// | Method      | POST   |
+    /// | Query type  | [`SignInput`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`SignInput`]: ../blockchain/struct.SignInput.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn sign_input(&self, sign_input: SignInput) -> Result<Hash, Self::Error>;
+    /// Creates and broadcasts the `TxSignature` transactions for all the given inputs
+    /// at once, which are signed by the current node, and returns their hashes.
+    /// The transactions are broadcast only if all the signatures are correct.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/signing/sign-inputs` |
+    /// | Method      | POST   |
+    /// | Query type  | [`Vec<SignInput>`] |
+    /// | Return type | [`Vec<Hash>`] |
+    ///
+    /// [`Vec<SignInput>`]: ../blockchain/struct.SignInput.html
+    /// [`Vec<Hash>`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn sign_inputs(&self, sign_inputs: Vec<SignInput>) -> Result<Vec<Hash>, Self::Error>;
+    /// Creates and broadcasts the `ConfirmAnchor` transaction, which is signed
+    /// by the current node, and returns its hash.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/signing/confirm-anchor` |
+    /// | Method      | POST   |
+    /// | Query type  | [`ConfirmAnchor`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`ConfirmAnchor`]: ../blockchain/struct.ConfirmAnchor.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn confirm_anchor(&self, confirmation: ConfirmAnchor) -> Result<Hash, Self::Error>;
+    /// Creates and broadcasts the `ReportFeeEstimate` transaction, which is signed
+    /// by the current node, and returns its hash.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/signing/report-fee-estimate` |
+    /// | Method      | POST   |
+    /// | Query type  | [`ReportFeeEstimate`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`ReportFeeEstimate`]: ../blockchain/struct.ReportFeeEstimate.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn report_fee_estimate(&self, estimate: ReportFeeEstimate) -> Result<Hash, Self::Error>;
+    /// Creates and broadcasts the `RequestCpfp` transaction, which is signed
+    /// by the current node, and returns its hash.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/signing/request-cpfp` |
+    /// | Method      | POST   |
+    /// | Query type  | [`RequestCpfp`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`RequestCpfp`]: ../blockchain/struct.RequestCpfp.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn request_cpfp(&self, request: RequestCpfp) -> Result<Hash, Self::Error>;
+    /// Returns a proposal for the next anchoring transaction, if it makes sense.
+    /// If there is not enough satoshis to create a proposal an error is returned.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/anchoring-proposal` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`AnchoringProposalState`] |
+    ///
+    /// [`AnchoringProposalState`]: enum.AnchoringProposalState.html
+    async fn anchoring_proposal(&self) -> Result<AnchoringProposalState, Self::Error>;
+    /// Returns the actual anchoring proposal in the PSBT format for the external signers,
+    /// if there is a proposal.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/anchoring-proposal/psbt` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Option<ProposalPsbt>`] |
+    ///
+    /// [`Option<ProposalPsbt>`]: ../psbt/struct.ProposalPsbt.html
+    async fn anchoring_proposal_psbt(&self) -> Result<Option<ProposalPsbt>, Self::Error>;
+    /// Converts the signatures of the given PSBT made by the Bitcoin keys of the current
+    /// node into the `SignInput` transactions, broadcasts them and returns their hashes.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/signing/psbt` |
+    /// | Method      | POST   |
+    /// | Query type  | [`ProposalPsbt`] |
+    /// | Return type | [`Vec<Hash>`] |
+    ///
+    /// [`ProposalPsbt`]: ../psbt/struct.ProposalPsbt.html
+    /// [`Vec<Hash>`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn submit_psbt(&self, psbt: ProposalPsbt) -> Result<Vec<Hash>, Self::Error>;
+    /// Returns an actual anchoring configuration.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/config` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Config`] |
+    ///
+    /// [`config`]: ../config/struct.Config.html
+    async fn config(&self) -> Result<Config, Self::Error>;
+    /// Returns an anchoring transaction with the specified index in anchoring transactions chain.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/transaction` |
+    /// | Method      | GET   |
+    /// | Query type  | [`IndexQuery`] |
+    /// | Return type | [`Option<btc::Transaction>`] |
+    ///
+    /// ['IndexQuery']: struct.IndexQuery.html
+    /// [`Option<btc::Transaction>`]: ../btc/struct.Transaction.html
+    async fn transaction_with_index(
+        &self,
+        index: u64,
+    ) -> Result<Option<btc::Transaction>, Self::Error>;
+    /// Returns a total number of anchoring transactions in the chain.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/transactions-count` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`AnchoringChainLength`] |
+    ///
+    /// [`AnchoringChainLength`]: struct.AnchoringChainLength.html
+    async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error>;
+    /// Returns the unspent funding transaction of the actual configuration, which
+    /// will be spent by the next anchoring transaction, if any.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/funds/unspent` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Option<btc::Transaction>`] |
+    ///
+    /// [`Option<btc::Transaction>`]: ../btc/struct.Transaction.html
+    async fn unspent_funding_transaction(&self) -> Result<Option<btc::Transaction>, Self::Error>;
+    /// Returns the state of the funding transaction with the given identifier.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/funds/status` |
+    /// | Method      | GET   |
+    /// | Query type  | [`FundingTransactionQuery`] |
+    /// | Return type | [`FundingTransactionState`] |
+    ///
+    /// [`FundingTransactionQuery`]: struct.FundingTransactionQuery.html
+    /// [`FundingTransactionState`]: enum.FundingTransactionState.html
+    async fn funding_transaction_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<FundingTransactionState, Self::Error>;
+    /// Returns the state of the anchoring transaction confirmation in the Bitcoin blockchain
+    /// agreed by the anchoring nodes.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/anchor/status` |
+    /// | Method      | GET   |
+    /// | Query type  | [`AnchorStatusQuery`] |
+    /// | Return type | [`AnchorConfirmationState`] |
+    ///
+    /// [`AnchorStatusQuery`]: struct.AnchorStatusQuery.html
+    /// [`AnchorConfirmationState`]: enum.AnchorConfirmationState.html
+    async fn anchor_confirmation_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<AnchorConfirmationState, Self::Error>;
+    /// Returns the version and compatibility metadata of the anchoring service.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/version` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`ServiceVersion`] |
+    ///
+    /// [`ServiceVersion`]: struct.ServiceVersion.html
+    async fn version(&self) -> Result<ServiceVersion, Self::Error>;
+    /// Returns the progress of collecting signatures for the actual anchoring proposal,
+    /// if any. The sync utility uses it to re-submit signatures for stalled proposals.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/signing-progress` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Option<SigningProgress>`] |
+    ///
+    /// [`Option<SigningProgress>`]: ../blockchain/struct.SigningProgress.html
+    async fn signing_progress(&self) -> Result<Option<SigningProgress>, Self::Error>;
+    /// Returns the state of the validator approvals for the finalized anchoring transaction
+    /// with the specified identifier. The sync utility broadcasts the special anchoring
+    /// transactions only after they have been approved.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/special-tx/status` |
+    /// | Method      | GET   |
+    /// | Query type  | [`SpecialTxQuery`] |
+    /// | Return type | [`SpecialTxApprovalState`] |
+    ///
+    /// [`SpecialTxQuery`]: struct.SpecialTxQuery.html
+    /// [`SpecialTxApprovalState`]: enum.SpecialTxApprovalState.html
+    async fn special_tx_approval_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<SpecialTxApprovalState, Self::Error>;
+}
+
+/// Private API client for the funds and configuration management of the Exonum Bitcoin
+/// anchoring service.
+///
+/// Endpoints of this API are placed under the `management/` path prefix, so the access
+/// to them can be restricted independently of the signing endpoints. Thus the machine
+/// that holds the Bitcoin keys doesn't have to be authorized to register new funding
+/// transactions.
+#[async_trait]
+pub trait ManagementApi {
+    /// Error type for the current API client implementation.
+    type Error;
+    /// Adds funds via suitable funding transaction.
+    ///
+    /// Bitcoin transaction should have output with value to the current anchoring address.
+    /// The transaction will be applied if 2/3+1 anchoring nodes sent it.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/management/add-funds` |
+    /// | Method      | POST   |
+    /// | Query type  | [`AddFunds`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`AddFunds`]: ../blockchain/struct.AddFunds.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error>;
+    /// Creates and broadcasts the `AttestKey` transaction, which is signed
+    /// by the current node, and returns its hash.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/management/attest-key` |
+    /// | Method      | POST   |
+    /// | Query type  | [`AttestKey`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`AttestKey`]: ../blockchain/struct.AttestKey.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn attest_key(&self, attestation: AttestKey) -> Result<Hash, Self::Error>;
+    /// Returns the disaster recovery bundle encrypted by the escrow key.
+    ///
+    /// The export should be authorized by the supervisor with the hash of the given
+    /// escrow key. The key is sent in the request body, thus it is a POST request.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/management/recovery-bundle` |
+    /// | Method      | POST   |
+    /// | Query type  | [`RecoveryBundleQuery`] |
+    /// | Return type | [`EncryptedRecoveryBundle`] |
+    ///
+    /// [`RecoveryBundleQuery`]: struct.RecoveryBundleQuery.html
+    /// [`EncryptedRecoveryBundle`]: ../recovery/struct.EncryptedRecoveryBundle.html
+    async fn recovery_bundle(
+        &self,
+        query: RecoveryBundleQuery,
+    ) -> Result<EncryptedRecoveryBundle, Self::Error>;
+    /// Creates and broadcasts the `ApproveSpecialTx` transaction, which is signed
+    /// by the current node, and returns its hash. The node should be a validator.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/management/approve-special-tx` |
+    /// | Method      | POST   |
+    /// | Query type  | [`SpecialTxQuery`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`SpecialTxQuery`]: struct.SpecialTxQuery.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn approve_special_tx(&self, txid: btc::Sha256d) -> Result<Hash, Self::Error>;
+}
+
+/// Blockchain state available to the API handlers.
+///
+/// Besides the state of the regular endpoints, it is implemented by the state of
+/// the endpoints wired as the raw handlers, which authenticate the requests or return
+/// the caching headers.
+trait ApiState {
+    /// Broadcaster of the transactions signed by the service key of the node.
+    type Broadcaster: GenericCall<(), Output = BoxFuture<'static, Result<Hash, SendError>>>;
+
+    fn data(&self) -> BlockchainData<&dyn Snapshot>;
+
+    fn service_data(&self) -> Prefixed<&dyn Snapshot>;
+
+    fn snapshot(&self) -> &dyn Snapshot;
+
+    fn instance(&self) -> &InstanceDescriptor;
+
+    fn service_key(&self) -> PublicKey;
+
+    /// Returns the broadcaster if the node is a validator and the service is active.
+    fn broadcaster(&self) -> Option<Self::Broadcaster>;
+}
+
+impl ApiState for ServiceApiState {
+    type Broadcaster = Broadcaster;
+
+    fn data(&self) -> BlockchainData<&dyn Snapshot> {
+        ServiceApiState::data(self)
+    }
+
+    fn service_data(&self) -> Prefixed<&dyn Snapshot> {
+        ServiceApiState::service_data(self)
+    }
+
+    fn snapshot(&self) -> &dyn Snapshot {
+        ServiceApiState::snapshot(self)
+    }
+
+    fn instance(&self) -> &InstanceDescriptor {
+        ServiceApiState::instance(self)
+    }
+
+    fn service_key(&self) -> PublicKey {
+        ServiceApiState::service_key(self)
+    }
+
+    fn broadcaster(&self) -> Option<Broadcaster> {
+        ServiceApiState::broadcaster(self)
+    }
+}
+
+/// State of the endpoints implemented on top of the raw actix handlers.
+struct RawApiState {
+    snapshot: Box<dyn Snapshot>,
+    is_active: bool,
+    broadcaster: NodeBroadcaster,
+}
+
+impl RawApiState {
+    fn new(blockchain: &Blockchain, request: &HttpRequest) -> api::Result<Self> {
+        let snapshot = blockchain.snapshot();
+        let instance = instance_name(request)
+            .and_then(|name| snapshot.for_dispatcher().get_instance(name))
+            .filter(|instance| instance.status.is_some())
+            .ok_or_else(|| {
+                api::Error::new(StatusCode::INTERNAL_SERVER_ERROR).title("Service is gone")
+            })?;
+
+        Ok(Self {
+            is_active: instance
+                .status
+                .as_ref()
+                .map_or(false, InstanceStatus::is_active),
+            broadcaster: NodeBroadcaster {
+                instance: instance.spec.as_descriptor(),
+                keypair: blockchain.service_keypair().clone(),
+                sender: blockchain.sender().clone(),
+            },
+            snapshot,
+        })
+    }
+}
+
+impl ApiState for RawApiState {
+    type Broadcaster = NodeBroadcaster;
+
+    fn data(&self) -> BlockchainData<&dyn Snapshot> {
+        BlockchainData::new(&self.snapshot, &self.instance().name)
+    }
+
+    fn service_data(&self) -> Prefixed<&dyn Snapshot> {
+        self.data().for_executing_service()
+    }
+
+    fn snapshot(&self) -> &dyn Snapshot {
+        &self.snapshot
+    }
+
+    fn instance(&self) -> &InstanceDescriptor {
+        &self.broadcaster.instance
+    }
+
+    fn service_key(&self) -> PublicKey {
+        self.broadcaster.keypair.public_key()
+    }
+
+    fn broadcaster(&self) -> Option<NodeBroadcaster> {
+        if self.is_active {
+            self.snapshot.for_core().validator_id(self.service_key())?;
+            Some(self.broadcaster.clone())
+        } else {
+            None
+        }
+    }
+}
+
+/// Signs the transactions by the service key of the node and broadcasts them in the same way
+/// as the broadcaster of the regular endpoints, which cannot be created outside of them.
+#[derive(Clone)]
+struct NodeBroadcaster {
+    instance: InstanceDescriptor,
+    keypair: KeyPair,
+    sender: ApiSender,
+}
+
+impl GenericCall<()> for NodeBroadcaster {
+    type Output = BoxFuture<'static, Result<Hash, SendError>>;
+
+    fn generic_call(&self, _ctx: (), method: MethodDescriptor<'_>, args: Vec<u8>) -> Self::Output {
+        let tx = self.keypair.generic_call(self.instance.id, method, args);
+        let tx_hash = tx.object_hash();
+
+        let sender = self.sender.clone();
+        async move {
+            sender.broadcast_transaction(tx).await?;
+            Ok(tx_hash)
+        }
+        .boxed()
+    }
+}
+
+struct ApiImpl<S>(S);
+
+impl<S: ApiState> ApiImpl<S> {
+    fn broadcaster(&self) -> api::Result<S::Broadcaster> {
+        self.0.broadcaster().ok_or_else(|| {
+            api::Error::bad_request()
+                .title("Invalid broadcast request")
+                .detail("Node is not a validator")
+        })
+    }
 
     fn actual_config(self) -> api::Result<Config> {
         Ok(Schema::new(self.0.service_data()).actual_config())
@@ -283,14 +1790,63 @@ impl ApiImpl {
             .1;
 
         // Verify input signature.
-        p2wsh::InputSigner::new(config.redeem_script())
-            .verify_input(
-                TxInRef::new(proposal.as_ref(), sign_input.input as usize),
-                input.as_ref(),
-                &bitcoin_key.0,
-                sign_input.input_signature.as_ref(),
-            )
-            .map_err(|e| anyhow!("Input signature verification failed: {}", e))
+        sign_input
+            .input_signature
+            .check_canonical(sign_input.sighash_type)
+            .map_err(|e| anyhow!("Input signature is not canonical: {}", e))?;
+        let txin = TxInRef::new(proposal.as_ref(), sign_input.input as usize);
+        let signature = sign_input.input_signature.as_ref();
+        let verification_result = match config.fee_payer_key {
+            Some(fee_payer_key)
+                if is_fee_payer_input(&config, &proposal, &inputs, sign_input.input as usize) =>
+            {
+                btc::verify_p2wpk_input(
+                    &proposal,
+                    sign_input.input as usize,
+                    input,
+                    &fee_payer_key,
+                    config.network,
+                    &sign_input.input_signature,
+                    sign_input.sighash_type,
+                )
+                .map_err(|e| e.to_string())
+            }
+            _ if sign_input.sighash_type != btc::SigHashType::All => {
+                Err("Multisig inputs should be signed with SIGHASH_ALL.".to_owned())
+            }
+            _ => p2wsh::InputSigner::new(config.redeem_script())
+                .verify_input(txin, input.as_ref(), &bitcoin_key.0, signature)
+                .map_err(|e| e.to_string()),
+        };
+        verification_result.map_err(|e| anyhow!("Input signature verification failed: {}", e))
+    }
+
+    /// Extracts the signatures made by the Bitcoin keys of this node from the given PSBT.
+    fn psbt_signatures(&self, psbt: &ProposalPsbt) -> anyhow::Result<Vec<SignInput>> {
+        let schema = Schema::new(self.0.service_data());
+        let (proposal, _) = schema
+            .actual_proposed_anchoring_transaction(self.0.data().for_core())
+            .ok_or_else(|| anyhow!("Anchoring transaction proposal is absent."))??;
+
+        let config = schema.actual_config();
+        let bitcoin_key = config
+            .find_bitcoin_key(&self.0.service_key())
+            .ok_or_else(|| anyhow!("This node is not an anchoring node."))?
+            .1;
+        // The node may also hold the key of the fee wallet.
+        let bitcoin_keys = iter::once(bitcoin_key)
+            .chain(config.fee_payer_key)
+            .collect::<Vec<_>>();
+        let sign_inputs = psbt.signatures(&proposal, &bitcoin_keys)?;
+        ensure!(
+            !sign_inputs.is_empty(),
+            "PSBT has no signatures made by the Bitcoin keys of this node."
+        );
+        // All signatures are verified before any of them is broadcast.
+        for sign_input in &sign_inputs {
+            self.verify_sign_input(sign_input)?;
+        }
+        Ok(sign_inputs)
     }
 
     fn verify_funding_tx(&self, tx: &btc::Transaction) -> anyhow::Result<()> {
@@ -299,37 +1855,61 @@ impl ApiImpl {
         let schema = Schema::new(self.0.service_data());
         let config = schema.actual_config();
         ensure!(
-            !schema.spent_funding_transactions.contains(&txid),
+            !schema.is_spent_funding_transaction(tx),
             "Funding transaction {} has been already used.",
             txid
         );
+        let has_fee_payer_out = config
+            .fee_payer_out_script()
+            .map_or(false, |script| tx.find_out(&script).is_some());
         ensure!(
-            tx.find_out(&config.anchoring_out_script()).is_some(),
+            tx.find_out(&config.anchoring_out_script()).is_some() || has_fee_payer_out,
             "Funding transaction {} is not suitable.",
             txid
         );
-        Ok(())
+        config.check_funding_tx_limits(tx)
     }
 
-    fn transaction_proof(&self, tx_index: u64) -> TransactionProof {
+    /// Returns the range of the block heights available in the core schema.
+    fn height_range(&self) -> api::Result<HeightRange> {
+        let blocks_count = self.0.data().for_core().block_hashes_by_height().len();
+        match blocks_count.checked_sub(1) {
+            Some(to) => Ok(HeightRange {
+                from: Height(0),
+                to: Height(to),
+            }),
+            None => Err(api::Error::not_found().title("The node has no committed blocks")),
+        }
+    }
+
+    fn transaction_proof(&self, tx_index: u64) -> api::Result<TransactionProof> {
         let index_proof = self
             .0
             .data()
             .proof_for_service_index("transactions_chain")
-            .unwrap();
-        let transaction_proof = Schema::new(self.0.service_data())
-            .transactions_chain
-            .get_proof(tx_index);
+            .ok_or_else(|| {
+                api::Error::not_found()
+                    .title("Proof for the anchoring chain is unavailable at the latest block")
+            })?;
+        let schema = Schema::new(self.0.service_data());
+        let transaction_proof = schema.transactions_chain.get_proof(tx_index);
+        let txid = schema.transactions_chain.get(tx_index).map(|tx| tx.id());
+        let finalized_at = txid.and_then(|txid| schema.finalization_height(&txid));
+        let inclusion = txid.and_then(|txid| schema.anchor_inclusion(&txid));
+        let finally_confirmed_at = txid.and_then(|txid| schema.anchor_finality_height(&txid));
 
-        TransactionProof {
+        Ok(TransactionProof {
             index_proof,
             transaction_proof,
-        }
+            finalized_at,
+            inclusion,
+            finally_confirmed_at,
+        })
     }
 }
 
 // Public API implementation
-impl ApiImpl {
+impl<S: ApiState> ApiImpl<S> {
     async fn actual_address(self) -> api::Result<btc::Address> {
         Ok(Schema::new(self.0.service_data())
             .actual_config()
@@ -343,58 +1923,500 @@ impl ApiImpl {
     }
 
     async fn find_transaction(self, height: Option<Height>) -> api::Result<TransactionProof> {
-        let anchoring_schema = Schema::new(self.0.service_data());
-        let tx_chain = anchoring_schema.transactions_chain;
+        if let Some(height) = height {
+            let range = self.height_range()?;
+            if !range.contains(height) {
+                return Err(range.out_of_range_error(height));
+            }
+        }
 
-        if tx_chain.is_empty() {
-            return Ok(self.transaction_proof(0));
+        let schema = Schema::new(self.0.service_data());
+        let tx_index = match height {
+            Some(height) => schema.nearest_anchor_index(height),
+            None => schema.transactions_chain.len().checked_sub(1),
+        };
+        // The proof of absence is returned for the empty anchoring chain.
+        let tx_index = tx_index.unwrap_or(0);
+        self.transaction_proof(tx_index)
+    }
+
+    async fn config(self) -> api::Result<Config> {
+        self.actual_config().map_err(api::Error::internal)
+    }
+
+    async fn limits(self) -> api::Result<ServiceLimits> {
+        let config = self.actual_config()?;
+        Ok(ServiceLimits {
+            max_inputs_per_anchor: MAX_ANCHORING_INPUTS,
+            max_anchoring_nodes: Config::MAX_NODES_COUNT,
+            payload_version: btc::Payload::VERSION,
+            max_memo_len: btc::MAX_MEMO_LEN,
+            dust_threshold: btc::DUST_THRESHOLD,
+            min_transaction_fee: Config::MIN_TX_FEE,
+            supported_networks: Config::SUPPORTED_NETWORKS.to_vec(),
+            anchoring_nodes: config.anchoring_keys.len(),
+            quorum: config.signature_quorum(),
+            max_funding_tx_weight: config.max_funding_tx_weight,
+            max_funding_tx_outputs: config.max_funding_tx_outputs,
+        })
+    }
+
+    async fn finalization_incidents(
+        self,
+        query: PageQuery,
+    ) -> api::Result<Page<FinalizationIncident>> {
+        let incidents = Schema::new(self.0.service_data()).finalization_incidents;
+        query.paginate(
+            incidents.len(),
+            |index| incidents.get(index).unwrap(),
+            |incident| Height(incident.height),
+        )
+    }
+
+    async fn signature_conflicts(self, query: PageQuery) -> api::Result<Page<SignatureConflict>> {
+        let conflicts = Schema::new(self.0.service_data()).signature_conflicts;
+        query.paginate(
+            conflicts.len(),
+            |index| conflicts.get(index).unwrap(),
+            |conflict| Height(conflict.height),
+        )
+    }
+
+    async fn rejected_signatures(
+        self,
+        query: RejectedSignaturesQuery,
+    ) -> api::Result<Vec<RejectedSignature>> {
+        let blocks = query
+            .blocks
+            .unwrap_or(RejectedSignaturesQuery::DEFAULT_BLOCKS);
+        if blocks == 0 || blocks > RejectedSignaturesQuery::MAX_BLOCKS {
+            return Err(api::Error::bad_request().title(format!(
+                "Number of blocks should be in range from 1 to {}.",
+                RejectedSignaturesQuery::MAX_BLOCKS
+            )));
         }
 
-        let tx_index = if let Some(height) = height {
-            // Handmade binary search.
-            let f = |index| -> Ordering {
-                // index is always in [0, size), that means index is >= 0 and < size.
-                // index >= 0: by definition
-                // index < size: index = size / 2 + size / 4 + size / 8 ...
-                let other = tx_chain
-                    .get(index)
-                    .unwrap()
-                    .anchoring_payload()
-                    .unwrap()
-                    .block_height;
-                other.cmp(&height)
-            };
+        let explorer = BlockchainExplorer::new(self.0.snapshot());
+        let latest_height = explorer.height();
+        let to_height = query
+            .to_height
+            .map_or(latest_height, |height| cmp::min(height, latest_height));
+        let from_height = (to_height.0 + 1).saturating_sub(blocks);
+        let instance_id = self.0.instance().id;
+        let signatures = (from_height..=to_height.0)
+            .filter_map(|height| explorer.block_with_txs(Height(height)))
+            .flat_map(|block| block.transactions)
+            .filter_map(|tx| RejectedSignature::from_committed(instance_id, &tx))
+            .collect();
+        Ok(signatures)
+    }
 
-            let mut base = 0;
-            let mut size = tx_chain.len();
-            while size > 1 {
-                let half = size / 2;
-                let mid = base + half;
-                let cmp = f(mid);
-                base = if cmp == Greater { base } else { mid };
-                size -= half;
-            }
-            // Don't forget to check base value.
-            let cmp = f(base);
-            if cmp == Equal {
-                base
-            } else {
-                cmp::min(base + (cmp == Less) as u64, tx_chain.len() - 1)
+    async fn version(self) -> api::Result<ServiceVersion> {
+        Ok(ServiceVersion::current())
+    }
+
+    async fn anchoring_latency(self, query: LatencyQuery) -> api::Result<Option<AnchoringLatency>> {
+        let window = query.window.unwrap_or(LatencyQuery::DEFAULT_WINDOW);
+        if window == 0 || window > LatencyQuery::MAX_WINDOW {
+            return Err(api::Error::bad_request().title(format!(
+                "Window should be in range from 1 to {}.",
+                LatencyQuery::MAX_WINDOW
+            )));
+        }
+        Ok(Schema::new(self.0.service_data()).anchoring_latency(window))
+    }
+
+    async fn transition_status(self) -> api::Result<Option<TransitionStatus>> {
+        Ok(Schema::new(self.0.service_data()).transition_status(self.0.data().for_core()))
+    }
+
+    async fn config_diff(self) -> api::Result<Option<ConfigDiff>> {
+        let schema = Schema::new(self.0.service_data());
+        Ok(schema
+            .following_config()
+            .map(|following| ConfigDiff::new(&schema.actual_config(), &following)))
+    }
+
+    async fn config_history(self) -> api::Result<Vec<HistoricalConfig>> {
+        let schema = Schema::new(self.0.service_data());
+        Ok(schema
+            .config_activations()
+            .into_iter()
+            .map(|(config, actual_from)| HistoricalConfig {
+                config,
+                actual_from,
+            })
+            .collect())
+    }
+
+    async fn chain_digest(self, query: ChainDigestQuery) -> api::Result<AnchoringChainDigest> {
+        let transactions = Schema::new(self.0.service_data()).transactions_chain;
+        let length = match query.index {
+            Some(index) if index >= transactions.len() => {
+                return Err(api::Error::not_found()
+                    .title("Anchoring transaction not found")
+                    .detail(format!(
+                        "Anchoring chain has {} transactions, the index {} is out of range.",
+                        transactions.len(),
+                        index
+                    )))
             }
+            Some(index) => index + 1,
+            None => transactions.len(),
+        };
+        Ok(AnchoringChainDigest::new(
+            transactions.iter().take(length as usize),
+        ))
+    }
+
+    async fn coverage(self, query: CoverageQuery) -> api::Result<Option<BlockCoverage>> {
+        let schema = Schema::new(self.0.service_data());
+        let anchor_index = match schema.covering_anchor_index(query.height) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let coverage = schema
+            .anchor_coverage(anchor_index)
+            .expect("Anchor coverage should exist for each anchoring transaction");
+        let txid = schema
+            .transactions_chain
+            .get(anchor_index)
+            .expect("Anchoring transaction should exist for each anchor coverage")
+            .id();
+
+        let confirmations = query.bitcoin_height.and_then(|bitcoin_height| {
+            let inclusion = schema.anchor_inclusion(&txid)?;
+            Some(
+                bitcoin_height
+                    .saturating_add(1)
+                    .saturating_sub(inclusion.block_height),
+            )
+        });
+        Ok(Some(BlockCoverage {
+            anchor_index,
+            txid,
+            first_height: Height(coverage.first_height),
+            last_height: Height(coverage.last_height),
+            state: AnchorConfirmationState::from_schema(&schema, txid),
+            confirmations,
+        }))
+    }
+
+    async fn status(self) -> api::Result<AnchoringStatus> {
+        let schema = Schema::new(self.0.service_data());
+        let anchoring_state = schema.actual_state();
+        let state = if anchoring_state.is_transition() {
+            AnchoringStateKind::Transition
+        } else {
+            AnchoringStateKind::Regular
+        };
+        Ok(AnchoringStatus {
+            state,
+            next_anchoring_height: schema.following_anchoring_height(&anchoring_state),
+            latest_anchored_height: schema.latest_anchored_height(),
+            signing_progress: schema.signing_progress(self.0.data().for_core()),
+            available_funds: schema.available_funds(),
+        })
+    }
+
+    async fn schedule(self) -> api::Result<Option<AnchoringSchedule>> {
+        let schema = Schema::new(self.0.service_data());
+        if schema.completion_height().is_some() {
+            return Ok(None);
+        }
+        let anchoring_state = schema.actual_state();
+        let state = if anchoring_state.is_transition() {
+            AnchoringStateKind::Transition
         } else {
-            tx_chain.len() - 1
+            AnchoringStateKind::Regular
+        };
+        let next_anchoring_height = schema.following_anchoring_height(&anchoring_state);
+        let latest_height = self.0.data().for_core().height();
+        Ok(Some(AnchoringSchedule {
+            state,
+            next_anchoring_height,
+            latest_height,
+            blocks_remaining: next_anchoring_height.0.saturating_sub(latest_height.0),
+        }))
+    }
+
+    async fn verify_proof(self, proof: ProofToVerify) -> api::Result<ProofVerdict> {
+        let validator_keys = self
+            .0
+            .data()
+            .for_core()
+            .consensus_config()
+            .validator_keys
+            .iter()
+            .map(|keys| keys.consensus_key)
+            .collect::<Vec<_>>();
+        let verdict = match self.verify_proof_with_keys(&proof, &validator_keys) {
+            Ok(verdict) => verdict,
+            Err(e) => ProofVerdict::Invalid {
+                reason: e.to_string(),
+            },
+        };
+        Ok(verdict)
+    }
+
+    fn verify_proof_with_keys(
+        &self,
+        proof: &ProofToVerify,
+        validator_keys: &[PublicKey],
+    ) -> anyhow::Result<ProofVerdict> {
+        let (block_proof, transaction) = match proof {
+            ProofToVerify::Block(block_proof) => {
+                block_proof.verify(validator_keys)?;
+                (block_proof, None)
+            }
+            ProofToVerify::Transaction(proof) => {
+                let (index_name, index_hash) = proof.index_proof.verify(validator_keys)?;
+                let expected_name = format!("{}.transactions_chain", self.0.instance().name);
+                ensure!(
+                    index_name == expected_name,
+                    "Proof is given for the index {}, expected {}",
+                    index_name,
+                    expected_name
+                );
+                let checked_proof = proof.transaction_proof.check_against_hash(index_hash)?;
+                let transaction = checked_proof
+                    .entries()
+                    .first()
+                    .map(|(index, tx)| (*index, tx.id()));
+                (&proof.index_proof.block_proof, transaction)
+            }
         };
+        Ok(ProofVerdict::Valid {
+            block_height: block_proof.block.height,
+            block_hash: block_proof.block.object_hash(),
+            anchor_index: transaction.map(|(index, _)| index),
+            txid: transaction.map(|(_, txid)| txid),
+        })
+    }
+
+    async fn transaction_by_txid(self, query: TxidQuery) -> api::Result<Option<AnchoredPayload>> {
+        let schema = Schema::new(self.0.service_data());
+        let anchor_index = match schema.anchoring_transaction_index(&query.txid) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let payload = schema
+            .transactions_chain
+            .get(anchor_index)
+            .and_then(|tx| tx.anchoring_payload())
+            .expect("Anchoring transaction should have a payload");
+        Ok(Some(AnchoredPayload {
+            anchor_index,
+            payload,
+            proof: self.transaction_proof(anchor_index)?,
+        }))
+    }
+
+    async fn transactions(self, query: TransactionsQuery) -> api::Result<TransactionsPage> {
+        let count = query.count.unwrap_or(PageQuery::DEFAULT_LIMIT);
+        if count == 0 || count > PageQuery::MAX_LIMIT {
+            return Err(api::Error::bad_request()
+                .title("Invalid page request")
+                .detail(format!(
+                    "Number of transactions should be in range from 1 to {}",
+                    PageQuery::MAX_LIMIT
+                )));
+        }
+
+        let index_proof = self
+            .0
+            .data()
+            .proof_for_service_index("transactions_chain")
+            .ok_or_else(|| {
+                api::Error::not_found()
+                    .title("Proof for the anchoring chain is unavailable at the latest block")
+            })?;
+        let transactions = Schema::new(self.0.service_data()).transactions_chain;
+        let len = transactions.len();
+        let start = cmp::min(query.from.unwrap_or(0), len);
+        let end = cmp::min(start.saturating_add(count), len);
+        Ok(TransactionsPage {
+            index_proof,
+            transactions_proof: transactions.get_range_proof(start..end),
+            next: Some(end).filter(|&end| end < len),
+        })
+    }
+
+    async fn signing_progress(self) -> api::Result<Option<SigningProgress>> {
+        Ok(Schema::new(self.0.service_data()).signing_progress(self.0.data().for_core()))
+    }
+
+    async fn special_tx_approval_state(
+        self,
+        txid: btc::Sha256d,
+    ) -> api::Result<SpecialTxApprovalState> {
+        let schema = Schema::new(self.0.service_data());
+        Ok(SpecialTxApprovalState::from_schema(&schema, txid))
+    }
+
+    async fn state_hashes(self) -> api::Result<SchemaHashes> {
+        let schema = Schema::new(self.0.service_data());
+        Ok(SchemaHashes {
+            transactions_chain: schema.transactions_chain.object_hash(),
+            anchor_coverage: schema.anchor_coverage.object_hash(),
+            transaction_indexes: schema.transaction_indexes.object_hash(),
+            anchored_heights: schema.anchored_heights.object_hash(),
+            spent_funding_transactions: schema.spent_funding_transactions.object_hash(),
+            spent_outputs: schema.spent_outputs.object_hash(),
+            transaction_signatures: schema.transaction_signatures.object_hash(),
+            unconfirmed_funding_transactions: schema.unconfirmed_funding_transactions.object_hash(),
+            pending_funding_transactions: schema.pending_funding_transactions.object_hash(),
+            rejected_funding_transactions: schema.rejected_funding_transactions.object_hash(),
+            key_attestations: schema.key_attestations.object_hash(),
+            finalization_heights: schema.finalization_heights.object_hash(),
+            finalization_incidents: schema.finalization_incidents.object_hash(),
+            signature_conflicts: schema.signature_conflicts.object_hash(),
+            anchor_inclusion_votes: schema.anchor_inclusion_votes.object_hash(),
+            anchor_inclusions: schema.anchor_inclusions.object_hash(),
+            anchor_finality_votes: schema.anchor_finality_votes.object_hash(),
+            anchor_finality_heights: schema.anchor_finality_heights.object_hash(),
+            finality_latencies: schema.finality_latencies.object_hash(),
+            transaction_witnesses: schema.transaction_witnesses.object_hash(),
+            witness_scripts: schema.witness_scripts.object_hash(),
+            config_history: schema.config_history.object_hash(),
+            config_activation_heights: schema.config_activation_heights.object_hash(),
+            recovery_exports: schema.recovery_exports.object_hash(),
+            special_tx_approvals: schema.special_tx_approvals.object_hash(),
+            actual_config: schema.actual_config.get().map(|x| x.object_hash()),
+            following_config: schema.following_config.get().map(|x| x.object_hash()),
+            unspent_funding_transaction: schema
+                .unspent_funding_transaction
+                .get()
+                .map(|x| x.object_hash()),
+            unspent_fee_payer_transaction: schema
+                .unspent_fee_payer_transaction
+                .get()
+                .map(|x| x.object_hash()),
+        })
+    }
+
+    async fn funding_transaction_state(
+        self,
+        txid: btc::Sha256d,
+    ) -> api::Result<FundingTransactionState> {
+        let schema = Schema::new(self.0.service_data());
+
+        if schema.spent_funding_transactions.contains(&txid) {
+            return Ok(FundingTransactionState::Spent);
+        }
+
+        let is_spendable = schema
+            .unspent_funding_transaction()
+            .into_iter()
+            .chain(schema.unspent_fee_payer_transaction())
+            .any(|tx| tx.id() == txid);
+        if is_spendable {
+            return Ok(FundingTransactionState::Spendable);
+        }
+
+        if let Some(height) = schema.rejected_funding_transactions.get(&txid) {
+            return Ok(FundingTransactionState::Rejected { height });
+        }
+
+        let state = match schema.unconfirmed_funding_transactions.get(&txid) {
+            Some(confirmations) => FundingTransactionState::Pending {
+                confirmations: confirmations.0.len() as u64,
+                required: schema.actual_config().byzantine_quorum() as u64,
+            },
+            None => FundingTransactionState::Unknown,
+        };
+        Ok(state)
+    }
+
+    async fn anchor_confirmation_state(
+        self,
+        txid: btc::Sha256d,
+    ) -> api::Result<AnchorConfirmationState> {
+        let schema = Schema::new(self.0.service_data());
+        Ok(AnchorConfirmationState::from_schema(&schema, txid))
+    }
+
+    async fn anchor_finality_proof(self, txid: btc::Sha256d) -> api::Result<AnchorFinalityProof> {
+        let index_proof = self
+            .0
+            .data()
+            .proof_for_service_index("anchor_finality_heights")
+            .ok_or_else(|| {
+                api::Error::not_found().title(
+                    "Proof for the anchor finality heights is unavailable at the latest block",
+                )
+            })?;
+        let schema = Schema::new(self.0.service_data());
+        Ok(AnchorFinalityProof {
+            index_proof,
+            finality_proof: schema.anchor_finality_heights.get_proof(txid),
+        })
+    }
+}
+
+// Cacheable API implementation
+impl<S: ApiState> ApiImpl<S> {
+    /// The transaction proof is final if the transaction anchors the block with the given
+    /// height or covers it and has been finally confirmed. The index proof at the later
+    /// blocks differs, but the cached one remains valid.
+    async fn cacheable_find_transaction(
+        self,
+        height: Option<Height>,
+    ) -> api::Result<Cacheable<TransactionProof>> {
+        let is_anchored = height.map_or(false, |height| {
+            let schema = Schema::new(self.0.service_data());
+            schema.anchor_index_by_height(height).is_some()
+                || schema.covering_anchor_index(height).is_some()
+        });
+        let proof = self.find_transaction(height).await?;
+        Ok(Cacheable {
+            is_final: is_anchored && proof.finally_confirmed_at.is_some(),
+            value: proof,
+        })
+    }
 
-        Ok(self.transaction_proof(tx_index))
+    async fn cacheable_anchor_finality_proof(
+        self,
+        txid: btc::Sha256d,
+    ) -> api::Result<Cacheable<AnchorFinalityProof>> {
+        let is_final = Schema::new(self.0.service_data())
+            .anchor_finality_height(&txid)
+            .is_some();
+        let proof = self.anchor_finality_proof(txid).await?;
+        Ok(Cacheable {
+            value: proof,
+            is_final,
+        })
     }
 
-    async fn config(self) -> api::Result<Config> {
-        self.actual_config().map_err(api::Error::internal)
+    async fn cacheable_transaction_with_index(
+        self,
+        index: u64,
+    ) -> api::Result<Cacheable<Option<btc::Transaction>>> {
+        let transaction = self.transaction_with_index(index).await?;
+        Ok(Cacheable {
+            // Anchoring transactions are never removed or replaced once they are added
+            // to the anchoring chain.
+            is_final: transaction.is_some(),
+            value: transaction,
+        })
+    }
+
+    async fn immutable_transaction(self, index: u64) -> api::Result<Cacheable<btc::Transaction>> {
+        let transaction = Schema::new(self.0.service_data())
+            .anchoring_transaction(index)
+            .ok_or_else(|| {
+                api::Error::not_found().title("Anchoring transaction with this index is absent")
+            })?;
+        Ok(Cacheable {
+            value: transaction,
+            is_final: true,
+        })
     }
 }
 
 /// Private API implementation
-impl ApiImpl {
+impl<S: ApiState> ApiImpl<S> {
     async fn sign_input(self, sign_input: SignInput) -> Result<Hash, api::Error> {
         // Verify Bitcoin signature.
         self.verify_sign_input(&sign_input).map_err(|e| {
@@ -409,6 +2431,114 @@ impl ApiImpl {
             .map_err(|e| api::Error::internal(e).title("Sign input request failed"))
     }
 
+    async fn sign_inputs(self, sign_inputs: Vec<SignInput>) -> Result<Vec<Hash>, api::Error> {
+        if sign_inputs.is_empty() || sign_inputs.len() > MAX_ANCHORING_INPUTS {
+            return Err(api::Error::bad_request()
+                .title("Invalid sign inputs request")
+                .detail(format!(
+                    "Number of inputs should be in range from 1 to {}",
+                    MAX_ANCHORING_INPUTS
+                )));
+        }
+        // Verify all the Bitcoin signatures before broadcasting any of them.
+        for sign_input in &sign_inputs {
+            self.verify_sign_input(sign_input).map_err(|e| {
+                api::Error::bad_request()
+                    .title("Sign input request verification has failed")
+                    .detail(format!("Input {}: {}", sign_input.input, e))
+            })?;
+        }
+
+        let mut hashes = Vec::with_capacity(sign_inputs.len());
+        for sign_input in sign_inputs {
+            let hash = self
+                .broadcaster()?
+                .sign_input((), sign_input)
+                .await
+                .map_err(|e| api::Error::internal(e).title("Sign input request failed"))?;
+            hashes.push(hash);
+        }
+        Ok(hashes)
+    }
+
+    async fn confirm_anchor(self, confirmation: ConfirmAnchor) -> Result<Hash, api::Error> {
+        let schema = Schema::new(self.0.service_data());
+        if schema.finalization_height(&confirmation.txid).is_none() {
+            return Err(api::Error::bad_request()
+                .title("Anchor confirmation verification has failed")
+                .detail(format!(
+                    "Anchoring transaction {} has not been finalized.",
+                    confirmation.txid
+                )));
+        }
+
+        self.broadcaster()?
+            .confirm_anchor((), confirmation)
+            .await
+            .map_err(|e| api::Error::internal(e).title("Confirm anchor request failed"))
+    }
+
+    async fn report_fee_estimate(self, estimate: ReportFeeEstimate) -> Result<Hash, api::Error> {
+        let config = Schema::new(self.0.service_data()).actual_config();
+        let error = if config.fee_estimation.is_none() {
+            Some("Fee estimation is disabled in the actual configuration.")
+        } else if estimate.fee == 0 {
+            Some("Fee estimate should be greater than zero.")
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            return Err(api::Error::bad_request()
+                .title("Fee estimate verification has failed")
+                .detail(error));
+        }
+
+        self.broadcaster()?
+            .report_fee_estimate((), estimate)
+            .await
+            .map_err(|e| api::Error::internal(e).title("Report fee estimate request failed"))
+    }
+
+    async fn request_cpfp(self, request: RequestCpfp) -> Result<Hash, api::Error> {
+        let config = Schema::new(self.0.service_data()).actual_config();
+        let error = if config.max_cpfp_fee == 0 {
+            Some("CPFP fee bumping is disabled in the actual configuration.")
+        } else if request.fee == 0 {
+            Some("CPFP fee should be greater than zero.")
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            return Err(api::Error::bad_request()
+                .title("CPFP request verification has failed")
+                .detail(error));
+        }
+
+        self.broadcaster()?
+            .request_cpfp((), request)
+            .await
+            .map_err(|e| api::Error::internal(e).title("CPFP request failed"))
+    }
+
+    async fn submit_psbt(self, psbt: ProposalPsbt) -> Result<Vec<Hash>, api::Error> {
+        let sign_inputs = self.psbt_signatures(&psbt).map_err(|e| {
+            api::Error::bad_request()
+                .title("PSBT verification has failed")
+                .detail(e.to_string())
+        })?;
+
+        let mut hashes = Vec::with_capacity(sign_inputs.len());
+        for sign_input in sign_inputs {
+            let hash = self
+                .broadcaster()?
+                .sign_input((), sign_input)
+                .await
+                .map_err(|e| api::Error::internal(e).title("Sign input request failed"))?;
+            hashes.push(hash);
+        }
+        Ok(hashes)
+    }
+
     async fn add_funds(self, transaction: btc::Transaction) -> Result<Hash, api::Error> {
         self.verify_funding_tx(&transaction).map_err(|e| {
             api::Error::bad_request()
@@ -422,19 +2552,85 @@ impl ApiImpl {
             .map_err(|e| api::Error::internal(e).title("Add funds request failed"))
     }
 
+    async fn attest_key(self, attestation: AttestKey) -> Result<Hash, api::Error> {
+        self.broadcaster()?
+            .attest_key((), attestation)
+            .await
+            .map_err(|e| api::Error::internal(e).title("Attest key request failed"))
+    }
+
+    async fn approve_special_tx(self, txid: btc::Sha256d) -> Result<Hash, api::Error> {
+        let schema = Schema::new(self.0.service_data());
+        if schema.special_tx_approvals(&txid).is_none() {
+            return Err(api::Error::bad_request()
+                .title("Invalid approve special transaction request")
+                .detail(format!(
+                    "Anchoring transaction {} doesn't require approvals",
+                    txid
+                )));
+        }
+
+        self.broadcaster()?
+            .approve_special_tx((), ApproveSpecialTx { txid })
+            .await
+            .map_err(|e| {
+                api::Error::internal(e).title("Approve special transaction request failed")
+            })
+    }
+
+    async fn recovery_bundle(
+        self,
+        query: RecoveryBundleQuery,
+    ) -> Result<EncryptedRecoveryBundle, api::Error> {
+        let schema = Schema::new(self.0.service_data());
+        let export = schema
+            .recovery_exports
+            .get(query.export_index)
+            .ok_or_else(|| {
+                api::Error::not_found()
+                    .title("Unknown recovery export")
+                    .detail(format!(
+                        "Recovery export {} has not been authorized.",
+                        query.export_index
+                    ))
+            })?;
+        if query.key.hash() != export.escrow_key_hash {
+            return Err(api::Error::bad_request()
+                .title("Recovery bundle request verification has failed")
+                .detail("Escrow key does not match the authorized one."));
+        }
+
+        let height = self.0.data().for_core().height();
+        let bundle = RecoveryBundle::new(&schema, query.export_index, export, height);
+        Ok(query.key.encrypt(&bundle))
+    }
+
     async fn anchoring_proposal(self) -> Result<AnchoringProposalState, api::Error> {
         let core_schema = self.0.data().for_core();
         let anchoring_schema = Schema::new(self.0.service_data());
 
         AnchoringProposalState::try_from_proposal(
             anchoring_schema.actual_proposed_anchoring_transaction(core_schema),
+            || anchoring_schema.no_proposal_reason(self.0.data().for_core()),
         )
     }
 
+    async fn anchoring_proposal_psbt(self) -> Result<Option<ProposalPsbt>, api::Error> {
+        let schema = Schema::new(self.0.service_data());
+        let (proposal, inputs) =
+            match schema.actual_proposed_anchoring_transaction(self.0.data().for_core()) {
+                Some(Ok(proposal)) => proposal,
+                Some(Err(e)) => return Err(api::Error::internal(e)),
+                None => return Ok(None),
+            };
+
+        ProposalPsbt::new(&schema.actual_config(), &proposal, &inputs)
+            .map(Some)
+            .map_err(|e| api::Error::internal(e).title("Unable to create PSBT"))
+    }
+
     async fn transaction_with_index(self, index: u64) -> api::Result<Option<btc::Transaction>> {
-        Ok(Schema::new(self.0.service_data())
-            .transactions_chain
-            .get(index))
+        Ok(Schema::new(self.0.service_data()).anchoring_transaction(index))
     }
 
     async fn transactions_count(self) -> api::Result<AnchoringChainLength> {
@@ -443,6 +2639,10 @@ impl ApiImpl {
             .len()
             .into())
     }
+
+    async fn unspent_funding_transaction(self) -> api::Result<Option<btc::Transaction>> {
+        Ok(Schema::new(self.0.service_data()).unspent_funding_transaction())
+    }
 }
 
 /// Query parameters for the find transaction request.
@@ -452,6 +2652,109 @@ pub struct FindTransactionQuery {
     pub height: Option<Height>,
 }
 
+/// Query parameters for the funding transaction status request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FundingTransactionQuery {
+    /// Identifier of the funding transaction.
+    pub txid: btc::Sha256d,
+}
+
+/// Query parameters for the anchoring latency request.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct LatencyQuery {
+    /// Number of the latest finally confirmed anchors to take into account,
+    /// `DEFAULT_WINDOW` if not specified.
+    #[serde(default)]
+    pub window: Option<u64>,
+}
+
+impl LatencyQuery {
+    /// Default number of the anchors taken into account.
+    pub const DEFAULT_WINDOW: u64 = 100;
+    /// Maximum number of the anchors taken into account.
+    pub const MAX_WINDOW: u64 = 10_000;
+}
+
+/// Query parameters for the rejected signatures request.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct RejectedSignaturesQuery {
+    /// Height of the latest scanned block, the latest committed block if not specified.
+    #[serde(default)]
+    pub to_height: Option<Height>,
+    /// Number of the scanned blocks, `DEFAULT_BLOCKS` if not specified.
+    #[serde(default)]
+    pub blocks: Option<u64>,
+}
+
+impl RejectedSignaturesQuery {
+    /// Default number of the scanned blocks.
+    pub const DEFAULT_BLOCKS: u64 = 100;
+    /// Maximum number of the scanned blocks.
+    pub const MAX_BLOCKS: u64 = 1_000;
+}
+
+/// Query parameters for the anchoring chain digest request.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ChainDigestQuery {
+    /// Index of the latest transaction covered by the digest, the whole chain is
+    /// covered if not specified.
+    #[serde(default)]
+    pub index: Option<u64>,
+}
+
+/// Query parameters for the anchoring transactions chain request.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TransactionsQuery {
+    /// Index of the first transaction of the page, zero if not specified.
+    #[serde(default)]
+    pub from: Option<u64>,
+    /// Maximum number of the transactions on the page, `PageQuery::DEFAULT_LIMIT`
+    /// if not specified.
+    #[serde(default)]
+    pub count: Option<u64>,
+}
+
+/// Query parameters for the block coverage request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CoverageQuery {
+    /// Height of the Exonum block.
+    pub height: Height,
+    /// Height of the latest Bitcoin block known to the client. If specified, the number
+    /// of the Bitcoin confirmations of the covering anchor is calculated.
+    #[serde(default)]
+    pub bitcoin_height: Option<u64>,
+}
+
+/// Query parameters for the anchoring transaction lookup by its Bitcoin identifier.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TxidQuery {
+    /// Identifier of the anchoring transaction.
+    pub txid: btc::Sha256d,
+}
+
+/// Query parameters for the anchoring transaction confirmation status request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AnchorStatusQuery {
+    /// Identifier of the anchoring transaction.
+    pub txid: btc::Sha256d,
+}
+
+/// Query parameters for the special anchoring transaction requests.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SpecialTxQuery {
+    /// Identifier of the special anchoring transaction.
+    pub txid: btc::Sha256d,
+}
+
+/// Query parameters for the recovery bundle request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryBundleQuery {
+    /// Index of the export in the `Schema::recovery_exports` list.
+    pub export_index: u64,
+    /// Escrow key which encrypts the bundle.
+    pub key: RecoveryKey,
+}
+
 /// Query parameters for the anchoring transaction request.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct IndexQuery {
@@ -459,7 +2762,25 @@ pub struct IndexQuery {
     pub index: u64,
 }
 
+/// Checks the environment variables which configure the API of the anchoring service.
+///
+/// The API is wired even if they are invalid: the default `max-age` of the cacheable
+/// responses is used, and the private API scopes with the invalid credentials reject
+/// all requests. Call this function on the node start to fail early instead.
+pub fn check_api_env() -> anyhow::Result<()> {
+    cache_max_age_from_env()?;
+    for &scope in &[PrivateApiScope::Signing, PrivateApiScope::Management] {
+        PrivateApiAuth::from_env(scope)
+            .map_err(|e| anyhow!("Invalid authentication of the {} API: {}", scope, e))?;
+    }
+    Ok(())
+}
+
 pub(crate) fn wire(builder: &mut ServiceApiBuilder) {
+    let cache_max_age = cache_max_age_from_env().unwrap_or_else(|e| {
+        error!("{}, the default value is used", e);
+        DEFAULT_CACHE_MAX_AGE
+    });
     builder
         .public_scope()
         .endpoint("address/actual", |state, _query: ()| {
@@ -468,28 +2789,506 @@ pub(crate) fn wire(builder: &mut ServiceApiBuilder) {
         .endpoint("address/following", |state, _query: ()| {
             ApiImpl(state).following_address()
         })
-        .endpoint("find-transaction", |state, query: FindTransactionQuery| {
-            ApiImpl(state).find_transaction(query.height)
+        .endpoint("config", |state, _query: ()| ApiImpl(state).config())
+        .endpoint("funds/unspent", |state, _query: ()| {
+            ApiImpl(state).unspent_funding_transaction()
+        })
+        .endpoint("funds/status", |state, query: FundingTransactionQuery| {
+            ApiImpl(state).funding_transaction_state(query.txid)
         })
-        .endpoint("config", |state, _query: ()| ApiImpl(state).config());
-    builder
-        .private_scope()
-        .endpoint_mut("sign-input", |state, query: SignInput| {
+        .endpoint("anchor/status", |state, query: AnchorStatusQuery| {
+            ApiImpl(state).anchor_confirmation_state(query.txid)
+        })
+        .endpoint("limits", |state, _query: ()| ApiImpl(state).limits())
+        .endpoint("debug/state-hashes", |state, _query: ()| {
+            ApiImpl(state).state_hashes()
+        })
+        .endpoint("finalization-incidents", |state, query: PageQuery| {
+            ApiImpl(state).finalization_incidents(query)
+        })
+        .endpoint("signature-conflicts", |state, query: PageQuery| {
+            ApiImpl(state).signature_conflicts(query)
+        })
+        .endpoint(
+            "rejected-signatures",
+            |state, query: RejectedSignaturesQuery| ApiImpl(state).rejected_signatures(query),
+        )
+        .endpoint("version", |state, _query: ()| ApiImpl(state).version())
+        .endpoint("anchoring-latency", |state, query: LatencyQuery| {
+            ApiImpl(state).anchoring_latency(query)
+        })
+        .endpoint("transition-status", |state, _query: ()| {
+            ApiImpl(state).transition_status()
+        })
+        .endpoint("config/diff", |state, _query: ()| {
+            ApiImpl(state).config_diff()
+        })
+        .endpoint("config/history", |state, _query: ()| {
+            ApiImpl(state).config_history()
+        })
+        .endpoint("chain/digest", |state, query: ChainDigestQuery| {
+            ApiImpl(state).chain_digest(query)
+        })
+        .endpoint("transactions", |state, query: TransactionsQuery| {
+            ApiImpl(state).transactions(query)
+        })
+        .endpoint("coverage", |state, query: CoverageQuery| {
+            ApiImpl(state).coverage(query)
+        })
+        .endpoint("status", |state, _query: ()| ApiImpl(state).status())
+        .endpoint("schedule", |state, _query: ()| ApiImpl(state).schedule())
+        .endpoint_mut("verify-proof", |state, proof: ProofToVerify| {
+            ApiImpl(state).verify_proof(proof)
+        })
+        .endpoint("transaction-by-txid", |state, query: TxidQuery| {
+            ApiImpl(state).transaction_by_txid(query)
+        });
+    RawScope::public(builder, cache_max_age)
+        .cacheable_endpoint("find-transaction", |state, query: FindTransactionQuery| {
+            ApiImpl(state).cacheable_find_transaction(query.height)
+        })
+        .cacheable_endpoint(
+            "anchor/finality-proof",
+            |state, query: AnchorStatusQuery| {
+                ApiImpl(state).cacheable_anchor_finality_proof(query.txid)
+            },
+        )
+        .cacheable_endpoint("transactions/immutable", |state, query: IndexQuery| {
+            ApiImpl(state).immutable_transaction(query.index)
+        });
+    crate::events::wire(builder);
+    #[cfg(feature = "juniper")]
+    crate::graphql::wire(builder);
+    RawScope::private(builder, PrivateApiScope::Signing, cache_max_age)
+        .endpoint_mut("signing/sign-input", |state, query: SignInput| {
             ApiImpl(state).sign_input(query)
         })
-        .endpoint_mut("add-funds", |state, query: btc::Transaction| {
-            ApiImpl(state).add_funds(query)
+        .deprecated_endpoint_mut(
+            "sign-input",
+            "signing/sign-input",
+            |state, query: SignInput| ApiImpl(state).sign_input(query),
+        )
+        .endpoint_mut("signing/sign-inputs", |state, query: Vec<SignInput>| {
+            ApiImpl(state).sign_inputs(query)
+        })
+        .endpoint_mut("signing/confirm-anchor", |state, query: ConfirmAnchor| {
+            ApiImpl(state).confirm_anchor(query)
+        })
+        .endpoint_mut(
+            "signing/report-fee-estimate",
+            |state, query: ReportFeeEstimate| ApiImpl(state).report_fee_estimate(query),
+        )
+        .endpoint_mut("signing/request-cpfp", |state, query: RequestCpfp| {
+            ApiImpl(state).request_cpfp(query)
+        })
+        .endpoint_mut("signing/psbt", |state, query: ProposalPsbt| {
+            ApiImpl(state).submit_psbt(query)
         })
         .endpoint("anchoring-proposal", |state, _query: ()| {
             ApiImpl(state).anchoring_proposal()
         })
+        .endpoint("anchoring-proposal/psbt", |state, _query: ()| {
+            ApiImpl(state).anchoring_proposal_psbt()
+        })
         .endpoint("config", |state, _query: ()| ApiImpl(state).config())
-        .endpoint("transaction", |state, query: IndexQuery| {
-            ApiImpl(state).transaction_with_index(query.index)
+        .cacheable_endpoint("transaction", |state, query: IndexQuery| {
+            ApiImpl(state).cacheable_transaction_with_index(query.index)
         })
         .endpoint("transactions-count", |state, _query: ()| {
             ApiImpl(state).transactions_count()
+        })
+        .endpoint("funds/status", |state, query: FundingTransactionQuery| {
+            ApiImpl(state).funding_transaction_state(query.txid)
+        })
+        .endpoint("anchor/status", |state, query: AnchorStatusQuery| {
+            ApiImpl(state).anchor_confirmation_state(query.txid)
+        })
+        .endpoint("version", |state, _query: ()| ApiImpl(state).version())
+        .endpoint("signing-progress", |state, _query: ()| {
+            ApiImpl(state).signing_progress()
+        })
+        .endpoint("special-tx/status", |state, query: SpecialTxQuery| {
+            ApiImpl(state).special_tx_approval_state(query.txid)
+        });
+    RawScope::private(builder, PrivateApiScope::Management, cache_max_age)
+        .endpoint_mut("management/add-funds", |state, query: btc::Transaction| {
+            ApiImpl(state).add_funds(query)
+        })
+        .deprecated_endpoint_mut(
+            "add-funds",
+            "management/add-funds",
+            |state, query: btc::Transaction| ApiImpl(state).add_funds(query),
+        )
+        .endpoint_mut("management/attest-key", |state, query: AttestKey| {
+            ApiImpl(state).attest_key(query)
+        })
+        .endpoint_mut(
+            "management/recovery-bundle",
+            |state, query: RecoveryBundleQuery| ApiImpl(state).recovery_bundle(query),
+        )
+        .endpoint_mut(
+            "management/approve-special-tx",
+            |state, query: SpecialTxQuery| ApiImpl(state).approve_special_tx(query.txid),
+        );
+}
+
+/// Environment variable with the `max-age` of the cacheable API responses in seconds,
+/// which never change once they are returned.
+pub const API_CACHE_MAX_AGE_ENV: &str = "BTC_ANCHORING_API_CACHE_MAX_AGE";
+/// Default `max-age` of the final cacheable API responses, which is one year.
+const DEFAULT_CACHE_MAX_AGE: u64 = 31_536_000;
+/// `Cache-Control` header value for the responses which may change in the next block.
+const MUTABLE_CACHE_CONTROL: &str = "no-cache";
+
+/// Reads the `max-age` of the final cacheable API responses from the environment variable.
+fn cache_max_age_from_env() -> anyhow::Result<u64> {
+    match std::env::var(API_CACHE_MAX_AGE_ENV) {
+        Ok(value) => value
+            .parse()
+            .map_err(|e| anyhow!("Invalid `{}` value: {}", API_CACHE_MAX_AGE_ENV, e)),
+        Err(std::env::VarError::NotPresent) => Ok(DEFAULT_CACHE_MAX_AGE),
+        Err(e) => Err(anyhow!("Invalid `{}` value: {}", API_CACHE_MAX_AGE_ENV, e)),
+    }
+}
+
+/// Response of the cacheable endpoint.
+struct Cacheable<T> {
+    value: T,
+    /// Whether the response never changes once it is returned. Other responses can be
+    /// cached only with the revalidation.
+    is_final: bool,
+}
+
+/// Endpoints implemented on top of the raw actix handlers, since the regular endpoints have
+/// no access to the request headers and body.
+///
+/// The private endpoints verify the authentication of the requests if it is configured for
+/// their scope by the environment variables of the node (see [`PrivateApiScope`]).
+/// The cacheable endpoints return the `Cache-Control` and `ETag` headers, so the responses
+/// can be cached by the reverse proxies and CDNs.
+///
+/// [`PrivateApiScope`]: ../sync/enum.PrivateApiScope.html
+struct RawScope<'a> {
+    builder: &'a mut ServiceApiBuilder,
+    is_private: bool,
+    auth: ScopeAuth,
+    /// `Cache-Control` header value for the final responses of the cacheable endpoints.
+    final_cache_control: String,
+}
+
+impl<'a> RawScope<'a> {
+    fn public(builder: &'a mut ServiceApiBuilder, cache_max_age: u64) -> Self {
+        Self {
+            builder,
+            is_private: false,
+            auth: ScopeAuth::Disabled,
+            final_cache_control: format!("public, max-age={}, immutable", cache_max_age),
+        }
+    }
+
+    fn private(
+        builder: &'a mut ServiceApiBuilder,
+        scope: PrivateApiScope,
+        cache_max_age: u64,
+    ) -> Self {
+        let auth = match PrivateApiAuth::from_env(scope) {
+            Ok(Some(auth)) => ScopeAuth::Required(auth),
+            Ok(None) => ScopeAuth::Disabled,
+            Err(e) => {
+                error!(
+                    "Invalid authentication of the {} API, its requests are rejected: {}",
+                    scope, e
+                );
+                ScopeAuth::Invalid(scope)
+            }
+        };
+        Self {
+            builder,
+            is_private: true,
+            auth,
+            final_cache_control: format!("private, max-age={}, immutable", cache_max_age),
+        }
+    }
+
+    fn endpoint<Q, I, F, R>(&mut self, name: &'static str, handler: F) -> &mut Self
+    where
+        Q: serde::de::DeserializeOwned + 'static,
+        I: serde::Serialize + 'static,
+        F: Fn(ApiImpl<RawApiState>, Q) -> R + Clone + Send + Sync + 'static,
+        R: Future<Output = api::Result<I>> + 'static,
+    {
+        self.raw_endpoint(name, Method::GET, handler, json_response)
+    }
+
+    fn endpoint_mut<Q, I, F, R>(&mut self, name: &'static str, handler: F) -> &mut Self
+    where
+        Q: serde::de::DeserializeOwned + 'static,
+        I: serde::Serialize + 'static,
+        F: Fn(ApiImpl<RawApiState>, Q) -> R + Clone + Send + Sync + 'static,
+        R: Future<Output = api::Result<I>> + 'static,
+    {
+        self.raw_endpoint(name, Method::POST, handler, json_response)
+    }
+
+    /// Adds the endpoint which has been moved to the new path. Its responses have
+    /// the `Warning` header, which refers to the new path.
+    fn deprecated_endpoint_mut<Q, I, F, R>(
+        &mut self,
+        name: &'static str,
+        new_name: &'static str,
+        handler: F,
+    ) -> &mut Self
+    where
+        Q: serde::de::DeserializeOwned + 'static,
+        I: serde::Serialize + 'static,
+        F: Fn(ApiImpl<RawApiState>, Q) -> R + Clone + Send + Sync + 'static,
+        R: Future<Output = api::Result<I>> + 'static,
+    {
+        let warning = deprecation_warning(new_name);
+        let respond = move |_request: &HttpRequest, response: api::Result<I>| {
+            response.map(|value| {
+                HttpResponse::Ok()
+                    .header(header::WARNING, warning.as_str())
+                    .json(value)
+            })
+        };
+        self.raw_endpoint(name, Method::POST, handler, respond)
+    }
+
+    fn cacheable_endpoint<Q, I, F, R>(&mut self, name: &'static str, handler: F) -> &mut Self
+    where
+        Q: serde::de::DeserializeOwned + 'static,
+        I: serde::Serialize + 'static,
+        F: Fn(ApiImpl<RawApiState>, Q) -> R + Clone + Send + Sync + 'static,
+        R: Future<Output = api::Result<Cacheable<I>>> + 'static,
+    {
+        let final_cache_control = self.final_cache_control.clone();
+        let respond = move |request: &HttpRequest, response: api::Result<Cacheable<I>>| {
+            cacheable_response(request, response, &final_cache_control)
+        };
+        self.raw_endpoint(name, Method::GET, handler, respond)
+    }
+
+    fn raw_endpoint<Q, I, F, R, P>(
+        &mut self,
+        name: &'static str,
+        method: Method,
+        handler: F,
+        respond: P,
+    ) -> &mut Self
+    where
+        Q: serde::de::DeserializeOwned + 'static,
+        F: Fn(ApiImpl<RawApiState>, Q) -> R + Clone + Send + Sync + 'static,
+        R: Future<Output = api::Result<I>> + 'static,
+        P: Fn(&HttpRequest, api::Result<I>) -> api::Result<HttpResponse>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        let blockchain = self.builder.blockchain().clone();
+        let auth = self.auth.clone();
+        let is_mutable = method == Method::POST;
+        let inner: Arc<RawHandler> = Arc::new(move |request: HttpRequest, payload: Payload| {
+            let blockchain = blockchain.clone();
+            let auth = auth.clone();
+            let handler = handler.clone();
+            let respond = respond.clone();
+            async move {
+                let body = Bytes::from_request(&request, &mut payload.into_inner()).await?;
+                match &auth {
+                    ScopeAuth::Disabled => {}
+                    ScopeAuth::Required(auth) => authenticate_request(auth, &request, &body)?,
+                    ScopeAuth::Invalid(scope) => {
+                        return Err(api::Error::internal(format!(
+                            "Authentication of the {} API is misconfigured, \
+                             see the node logs for details",
+                            scope
+                        ))
+                        .into())
+                    }
+                }
+                let query = if is_mutable {
+                    serde_json::from_slice(&body).map_err(|e| {
+                        api::Error::bad_request()
+                            .title("Cannot read JSON from request body")
+                            .detail(e.to_string())
+                    })?
+                } else {
+                    Query::<Q>::from_query(request.query_string())
+                        .map_err(|e| {
+                            api::Error::bad_request()
+                                .title("Invalid query")
+                                .detail(e.to_string())
+                        })?
+                        .into_inner()
+                };
+
+                let state = RawApiState::new(&blockchain, &request)?;
+                let source = state.instance().to_string();
+                let response = handler(ApiImpl(state), query)
+                    .await
+                    .map_err(|e| e.source(source));
+                respond(&request, response).map_err(actix_web::Error::from)
+            }
+            .boxed_local()
+                as LocalBoxFuture<'static, Result<HttpResponse, actix_web::Error>>
+        });
+
+        let scope = if self.is_private {
+            self.builder.private_scope()
+        } else {
+            self.builder.public_scope()
+        };
+        scope.web_backend().raw_handler(RequestHandler {
+            name: name.to_owned(),
+            method,
+            inner,
         });
+        self
+    }
+}
+
+/// Authentication of the API scope.
+#[derive(Debug, Clone)]
+enum ScopeAuth {
+    /// The requests are not authenticated.
+    Disabled,
+    /// The requests are verified with the given credentials.
+    Required(PrivateApiAuth),
+    /// The credentials of the scope are invalid, so all its requests are rejected.
+    Invalid(PrivateApiScope),
+}
+
+/// Returns the `Warning` header value for the deprecated endpoint, which has been moved
+/// to the given path.
+fn deprecation_warning(new_name: &str) -> String {
+    format!(
+        "299 - \"Deprecated API: This endpoint is deprecated, use the `{}` endpoint instead.\"",
+        new_name
+    )
+}
+
+/// Verifies the authentication headers of the private API request.
+fn authenticate_request(
+    auth: &PrivateApiAuth,
+    request: &HttpRequest,
+    body: &[u8],
+) -> api::Result<()> {
+    let path_and_query = request
+        .uri()
+        .path_and_query()
+        .map_or_else(|| request.path(), |path_and_query| path_and_query.as_str());
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    auth.verify_with(request.method(), path_and_query, header, body)
+        .map_err(|e| {
+            api::Error::new(StatusCode::UNAUTHORIZED)
+                .title("Private API request is not authenticated")
+                .detail(e.to_string())
+        })
+}
+
+/// Extracts the name of the service instance from the request path, which has the
+/// `/api/services/{instance_name}/{endpoint}` form.
+pub(crate) fn instance_name(request: &HttpRequest) -> Option<&str> {
+    let mut segments = request.path().split('/').skip_while(|s| *s != "services");
+    segments.next()?;
+    segments.next().filter(|name| !name.is_empty())
+}
+
+fn json_response<T: serde::Serialize>(
+    _request: &HttpRequest,
+    response: api::Result<T>,
+) -> api::Result<HttpResponse> {
+    response.map(|value| HttpResponse::Ok().json(value))
+}
+
+/// Returns the response of the cacheable endpoint along with the caching headers.
+///
+/// The hash of the response body is used as the entity tag, so the clients can revalidate
+/// the cached responses by the `If-None-Match` header. Errors must not be cached, since
+/// the missing data may appear in the next block.
+fn cacheable_response<T: serde::Serialize>(
+    request: &HttpRequest,
+    response: api::Result<Cacheable<T>>,
+    final_cache_control: &str,
+) -> api::Result<HttpResponse> {
+    let response = response.map_err(|mut e| {
+        e.headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(MUTABLE_CACHE_CONTROL),
+        );
+        e
+    })?;
+    let body = serde_json::to_vec(&response.value).map_err(api::Error::internal)?;
+    let etag = format!("\"{}\"", crypto::hash(&body).to_hex());
+    let cache_control = if response.is_final {
+        final_cache_control
+    } else {
+        MUTABLE_CACHE_CONTROL
+    };
+
+    let is_not_modified = request
+        .headers()
+        .get_all(header::IF_NONE_MATCH)
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| matches_entity_tag(value, &etag));
+    let mut builder = if is_not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    builder
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(header::ETAG, etag);
+    Ok(if is_not_modified {
+        builder.finish()
+    } else {
+        builder.content_type("application/json").body(body)
+    })
+}
+
+/// Checks whether the value of the `If-None-Match` header matches the given strong entity tag.
+///
+/// The header contains either `*` or a comma-separated list of the entity tags, which are
+/// compared by the weak comparison as RFC 7232 requires, i.e. the `W/` prefix is ignored.
+/// Malformed entity tags never match.
+fn matches_entity_tag(if_none_match: &str, etag: &str) -> bool {
+    if if_none_match.trim() == "*" {
+        return true;
+    }
+
+    let mut rest = if_none_match;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        if rest.is_empty() {
+            return false;
+        }
+
+        let tag = rest.strip_prefix("W/").unwrap_or(rest);
+        let tag_len = tag
+            .strip_prefix('"')
+            .and_then(|quoted| quoted.find('"'))
+            .map(|end| end + 2);
+        match tag_len {
+            Some(tag_len) => {
+                if &tag[..tag_len] == etag {
+                    return true;
+                }
+                rest = &tag[tag_len..];
+            }
+            // Skip the malformed entity tag up to the next comma.
+            None => match rest.find(',') {
+                Some(pos) => rest = &rest[pos..],
+                None => return false,
+            },
+        }
+    }
 }
 
 impl<T> std::fmt::Debug for dyn PublicApi<Error = T> {
@@ -503,3 +3302,233 @@ impl<T> std::fmt::Debug for dyn PrivateApi<Error = T> {
         f.debug_struct("PrivateApi").finish()
     }
 }
+
+impl<T> std::fmt::Debug for dyn ManagementApi<Error = T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ManagementApi").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::{header, Method, StatusCode},
+        test::TestRequest,
+        HttpRequest,
+    };
+    use exonum::helpers::Height;
+    use exonum_rust_runtime::api;
+    use reqwest::{Request, Url};
+
+    use super::{
+        authenticate_request, cacheable_response, matches_entity_tag, Cacheable, Page, PageQuery,
+    };
+    use crate::sync::PrivateApiAuth;
+
+    const SIGN_INPUT_PATH: &str = "/api/services/anchoring/signing/sign-input";
+
+    fn paginate(query: PageQuery, heights: &[u64]) -> Page<u64> {
+        query
+            .paginate(
+                heights.len() as u64,
+                |i| heights[i as usize],
+                |h| Height(*h),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn paginate_by_position() {
+        let heights = (0..10).collect::<Vec<_>>();
+        let query = PageQuery {
+            limit: Some(4),
+            ..PageQuery::default()
+        };
+
+        let page = paginate(query, &heights);
+        assert_eq!(page.items, vec![0, 1, 2, 3]);
+        assert_eq!(page.next, Some(4));
+
+        let page = paginate(
+            PageQuery {
+                start: Some(8),
+                ..query
+            },
+            &heights,
+        );
+        assert_eq!(page.items, vec![8, 9]);
+        assert_eq!(page.next, None);
+
+        let page = paginate(
+            PageQuery {
+                start: Some(20),
+                ..query
+            },
+            &heights,
+        );
+        assert!(page.items.is_empty());
+        assert_eq!(page.next, None);
+    }
+
+    #[test]
+    fn paginate_by_height() {
+        let heights = vec![1, 3, 3, 5, 8, 13, 21];
+        let query = PageQuery {
+            from_height: Some(Height(3)),
+            to_height: Some(Height(13)),
+            limit: Some(3),
+            ..PageQuery::default()
+        };
+
+        let page = paginate(query, &heights);
+        assert_eq!(page.items, vec![3, 3, 5]);
+        assert_eq!(page.next, Some(3));
+
+        let page = paginate(
+            PageQuery {
+                start: page.next,
+                ..query
+            },
+            &heights,
+        );
+        assert_eq!(page.items, vec![8, 13]);
+        assert_eq!(page.next, None);
+    }
+
+    #[test]
+    fn paginate_err_limit() {
+        for &limit in &[0, PageQuery::MAX_LIMIT + 1] {
+            let query = PageQuery {
+                limit: Some(limit),
+                ..PageQuery::default()
+            };
+            assert!(query.paginate(0, |_| 0, |_| Height(0)).is_err());
+        }
+    }
+
+    fn authenticated_request(auth: &PrivateApiAuth, body: &[u8]) -> HttpRequest {
+        let url = Url::parse(&format!("http://127.0.0.1:8081{}", SIGN_INPUT_PATH)).unwrap();
+        let mut request = Request::new(Method::POST, url);
+        *request.body_mut() = Some(body.to_vec().into());
+        auth.authenticate(&mut request).unwrap();
+
+        request
+            .headers()
+            .iter()
+            .fold(
+                TestRequest::post().uri(SIGN_INPUT_PATH),
+                |test_request, (name, value)| {
+                    test_request.header(name.as_str(), value.to_str().unwrap())
+                },
+            )
+            .to_http_request()
+    }
+
+    #[test]
+    fn private_api_requests_authentication() {
+        let auth = PrivateApiAuth::new()
+            .with_auth_token("token")
+            .with_request_key(b"secret".to_vec());
+
+        let request = authenticated_request(&auth, b"{}");
+        authenticate_request(&auth, &request, b"{}").unwrap();
+        // The same request cannot be replayed.
+        let e = authenticate_request(&auth, &request, b"{}").unwrap_err();
+        assert_eq!(e.http_code.as_u16(), 401);
+
+        // The request body is covered by the signature.
+        let request = authenticated_request(&auth, b"{}");
+        let e = authenticate_request(&auth, &request, b"[]").unwrap_err();
+        assert_eq!(e.http_code.as_u16(), 401);
+
+        let request = TestRequest::post().uri(SIGN_INPUT_PATH).to_http_request();
+        let e = authenticate_request(&auth, &request, b"{}").unwrap_err();
+        assert_eq!(e.http_code.as_u16(), 401);
+
+        // The credentials of the signing scope are not accepted by the management one.
+        let management_auth = PrivateApiAuth::new()
+            .with_auth_token("management-token")
+            .with_request_key(b"management-secret".to_vec());
+        let request = authenticated_request(&auth, b"{}");
+        let e = authenticate_request(&management_auth, &request, b"{}").unwrap_err();
+        assert_eq!(e.http_code.as_u16(), 401);
+    }
+
+    #[test]
+    fn if_none_match_parsing() {
+        let etag = "\"abc\"";
+        assert!(matches_entity_tag("\"abc\"", etag));
+        assert!(matches_entity_tag("W/\"abc\"", etag));
+        assert!(matches_entity_tag("\"def\", W/\"abc\"", etag));
+        assert!(matches_entity_tag("\"d,e\",\"abc\"", etag));
+        assert!(matches_entity_tag(" * ", etag));
+        assert!(matches_entity_tag("abc, \"abc\"", etag));
+
+        assert!(!matches_entity_tag("", etag));
+        assert!(!matches_entity_tag("\"abcd\"", etag));
+        assert!(!matches_entity_tag("abc", etag));
+        assert!(!matches_entity_tag("\"d,\"abc\"", etag));
+        assert!(!matches_entity_tag("w/\"abc\"", etag));
+    }
+
+    #[test]
+    fn cacheable_responses() {
+        const FINAL_CACHE_CONTROL: &str = "public, max-age=60, immutable";
+        let header_value = |response: &actix_web::HttpResponse, name: header::HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned()
+        };
+
+        let request = TestRequest::get().to_http_request();
+        let value = Cacheable {
+            value: 42,
+            is_final: true,
+        };
+        let response = cacheable_response(&request, Ok(value), FINAL_CACHE_CONTROL).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            header_value(&response, header::CACHE_CONTROL),
+            FINAL_CACHE_CONTROL
+        );
+        let etag = header_value(&response, header::ETAG);
+
+        // Conditional requests are answered with `304 Not Modified`.
+        let request = TestRequest::get()
+            .header(header::IF_NONE_MATCH, format!("\"other\", W/{}", etag))
+            .to_http_request();
+        let value = Cacheable {
+            value: 42,
+            is_final: false,
+        };
+        let response = cacheable_response(&request, Ok(value), FINAL_CACHE_CONTROL).unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(header_value(&response, header::CACHE_CONTROL), "no-cache");
+        assert_eq!(header_value(&response, header::ETAG), etag);
+
+        let value = Cacheable {
+            value: 43,
+            is_final: true,
+        };
+        let response = cacheable_response(&request, Ok(value), FINAL_CACHE_CONTROL).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(header_value(&response, header::ETAG), etag);
+
+        // Errors are never cached.
+        let e =
+            cacheable_response::<u64>(&request, Err(api::Error::not_found()), FINAL_CACHE_CONTROL)
+                .unwrap_err();
+        assert_eq!(
+            e.headers
+                .get(header::CACHE_CONTROL)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            "no-cache"
+        );
+    }
+}
@@ -72,6 +72,47 @@ impl ObjectHash for TxInputId {
     }
 }
 
+/// Unique transaction output identifier composed of a transaction identifier
+/// and an output index, i.e. the outpoint of the Bitcoin transaction.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TxOutputId {
+    /// Transaction identifier.
+    pub txid: Sha256d,
+    /// Transaction output index.
+    pub output: u32,
+}
+
+impl TxOutputId {
+    /// Creates a new identifier.
+    pub fn new(txid: Sha256d, output: u32) -> Self {
+        Self { txid, output }
+    }
+}
+
+impl BinaryKey for TxOutputId {
+    fn size(&self) -> usize {
+        self.txid.size() + self.output.size()
+    }
+
+    fn read(inp: &[u8]) -> Self {
+        let TxInputId { txid, input } = TxInputId::read(inp);
+        Self {
+            txid,
+            output: input,
+        }
+    }
+
+    fn write(&self, out: &mut [u8]) -> usize {
+        TxInputId::new(self.txid, self.output).write(out)
+    }
+}
+
+impl ObjectHash for TxOutputId {
+    fn object_hash(&self) -> Hash {
+        TxInputId::new(self.txid, self.output).object_hash()
+    }
+}
+
 #[test]
 fn test_tx_input_id_binary_key() {
     let txout = TxInputId {
@@ -88,3 +129,18 @@ fn test_tx_input_id_binary_key() {
     let buf_hash = crypto::hash(&buf);
     assert_eq!(txout2.object_hash(), buf_hash);
 }
+
+#[test]
+fn test_tx_output_id_binary_key() {
+    let outpoint = TxOutputId {
+        txid: Sha256d::from_slice(crypto::hash(&[1, 2, 3]).as_ref()).unwrap(),
+        output: 1,
+    };
+
+    let mut buf = vec![0_u8; outpoint.size()];
+    outpoint.write(&mut buf);
+
+    let outpoint2 = TxOutputId::read(&buf);
+    assert_eq!(outpoint, outpoint2);
+    assert_eq!(outpoint2.object_hash(), crypto::hash(&buf));
+}
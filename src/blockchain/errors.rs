@@ -30,12 +30,47 @@ pub enum Error {
     InputVerificationFailed = 2,
     /// An error occurred while creating of the anchoring transaction proposal.
     AnchoringBuilderError = 3,
-    /// Unexpected anchoring proposal transaction ID.
+    /// Signature is made neither for the actual anchoring proposal nor for the latest
+    /// anchoring transaction.
     UnexpectedProposalTxId = 4,
     /// Funding transaction has been already used.
     AlreadyUsedFundingTx = 5,
     /// Funding transaction is unsuitable.
     UnsuitableFundingTx = 6,
+    /// Bitcoin key attestation is invalid.
+    InvalidKeyAttestation = 7,
+    /// Bitcoin key is not attested by its owner.
+    MissingKeyAttestation = 8,
+    /// There is no anchoring proposal at the time, and the signature is not made
+    /// for the latest anchoring transaction.
+    NoAnchoringProposal = 9,
+    /// The transaction input signature is not a canonical low-S DER signature
+    /// with the declared sighash type.
+    NonCanonicalSignature = 10,
+    /// The declared sighash type is not allowed for the signed input.
+    UnsupportedSigHashType = 11,
+    /// The anchoring transaction with the specified identifier has not been finalized.
+    UnknownAnchoringTransaction = 12,
+    /// The anchoring chain has been shut down or its final anchor has been already requested.
+    AnchoringChainShutDown = 13,
+    /// Funding transaction exceeds the weight or output count limits of the actual
+    /// configuration.
+    OversizedFundingTx = 14,
+    /// Transaction author is not a validator, so it can't approve special anchoring
+    /// transactions.
+    UnauthorizedApprover = 15,
+    /// The anchoring transaction with the specified identifier doesn't require approvals.
+    UnknownSpecialTransaction = 16,
+    /// The fee estimation is disabled in the actual configuration.
+    FeeEstimationDisabled = 17,
+    /// The reported fee estimate is zero.
+    InvalidFeeEstimate = 18,
+    /// The fee bumping by the child-pays-for-parent transaction is disabled in the actual
+    /// configuration.
+    CpfpDisabled = 19,
+    /// The fee bumping is requested for the anchoring transaction which is not the latest one
+    /// or has been already confirmed in the Bitcoin blockchain.
+    UnexpectedCpfpParent = 20,
 }
 
 impl Error {
@@ -0,0 +1,286 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Data migrations between the artifact versions of the service.
+//!
+//! A migration writes only the indexes it changes to the new data, the rest of the indexes,
+//! including the anchoring transactions chain and the funding transactions, are kept as is.
+//! Thus the migration scripts must never create the whole `Schema` over the new data, since
+//! it would replace all the indexes with the empty ones.
+
+use exonum::{
+    crypto::PublicKey,
+    merkledb::access::AccessExt,
+    runtime::migrations::{MigrationContext, MigrationError},
+};
+
+use super::{data_layout::TxOutputId, Schema};
+use crate::{btc, config::Config, proto::AnchorCoverage};
+
+/// Migrates the data of the `1.0` artifact to the `1.1` one.
+pub(crate) fn migrate_from_v1_0(ctx: &mut MigrationContext) -> Result<(), MigrationError> {
+    restore_config_history(ctx)?;
+    restore_anchor_coverage(ctx)?;
+    restore_transaction_indexes(ctx)?;
+    restore_anchored_heights(ctx)?;
+    restore_spent_outputs(ctx)
+}
+
+/// The configuration history and the Bitcoin key attestations were not recorded
+/// by the `1.0` artifact, so they are restored from the actual and the following
+/// configurations. The keys of these configurations have been accepted by the supervisor,
+/// thus they are considered attested by their owners.
+fn restore_config_history(ctx: &mut MigrationContext) -> Result<(), MigrationError> {
+    let old_schema = Schema::new(ctx.helper.old_data());
+    let actual_config = old_schema.actual_config();
+    let following_config = old_schema.following_config();
+
+    if old_schema.config_history.is_empty() {
+        let mut config_history = ctx
+            .helper
+            .new_data()
+            .get_proof_list::<_, Config>("config_history");
+        config_history.push(actual_config.clone());
+        config_history.extend(following_config.clone());
+    }
+
+    if old_schema.key_attestations.is_empty() {
+        let mut key_attestations = ctx
+            .helper
+            .new_data()
+            .get_proof_map::<_, PublicKey, btc::PublicKey>("key_attestations");
+        for keys in actual_config.anchoring_keys.iter().chain(
+            following_config
+                .iter()
+                .flat_map(|config| &config.anchoring_keys),
+        ) {
+            key_attestations.put(&keys.service_key, keys.bitcoin_key);
+        }
+    }
+    Ok(())
+}
+
+/// The ranges of the block heights covered by the anchoring transactions were not recorded
+/// by the `1.0` artifact, so they are restored from the anchoring chain.
+fn restore_anchor_coverage(ctx: &mut MigrationContext) -> Result<(), MigrationError> {
+    let old_schema = Schema::new(ctx.helper.old_data());
+    if !old_schema.anchor_coverage.is_empty() {
+        return Ok(());
+    }
+
+    let mut anchor_coverage = ctx
+        .helper
+        .new_data()
+        .get_proof_list::<_, AnchorCoverage>("anchor_coverage");
+    for tx in old_schema.transactions_chain.iter() {
+        let payload = tx.anchoring_payload().ok_or_else(|| {
+            MigrationError::new(format!(
+                "Anchoring transaction {} has no anchoring payload",
+                tx.id()
+            ))
+        })?;
+        let coverage =
+            AnchorCoverage::following(anchor_coverage.last().as_ref(), payload.block_height);
+        anchor_coverage.push(coverage);
+    }
+    Ok(())
+}
+
+/// The index of the anchoring transactions by their identifiers was not recorded
+/// by the `1.0` artifact, so it is restored from the anchoring chain.
+fn restore_transaction_indexes(ctx: &mut MigrationContext) -> Result<(), MigrationError> {
+    let old_schema = Schema::new(ctx.helper.old_data());
+    let mut transaction_indexes = ctx
+        .helper
+        .new_data()
+        .get_proof_map::<_, btc::Sha256d, u64>("transaction_indexes");
+    for (index, tx) in old_schema.transactions_chain.iter().enumerate() {
+        transaction_indexes.put(&tx.id(), index as u64);
+    }
+    Ok(())
+}
+
+/// The index of the anchoring transactions by the anchored heights was not recorded
+/// by the `1.0` artifact, so it is restored from the anchoring chain.
+fn restore_anchored_heights(ctx: &mut MigrationContext) -> Result<(), MigrationError> {
+    let old_schema = Schema::new(ctx.helper.old_data());
+    let mut anchored_heights = ctx
+        .helper
+        .new_data()
+        .get_proof_map::<_, u64, u64>("anchored_heights");
+    for (index, tx) in old_schema.transactions_chain.iter().enumerate() {
+        let payload = tx.anchoring_payload().ok_or_else(|| {
+            MigrationError::new(format!(
+                "Anchoring transaction {} has no anchoring payload",
+                tx.id()
+            ))
+        })?;
+        if !anchored_heights.contains(&payload.block_height.0) {
+            anchored_heights.put(&payload.block_height.0, index as u64);
+        }
+    }
+    Ok(())
+}
+
+/// The index of the anchoring transactions by the outputs spent by them was not recorded
+/// by the `1.0` artifact, so it is restored from the inputs of the anchoring chain.
+fn restore_spent_outputs(ctx: &mut MigrationContext) -> Result<(), MigrationError> {
+    let old_schema = Schema::new(ctx.helper.old_data());
+    let mut spent_outputs = ctx
+        .helper
+        .new_data()
+        .get_proof_map::<_, TxOutputId, btc::Sha256d>("spent_outputs");
+    for tx in old_schema.transactions_chain.iter() {
+        for input in &tx.0.input {
+            let output = TxOutputId::new(
+                input.previous_output.txid.into(),
+                input.previous_output.vout,
+            );
+            spent_outputs.put(&output, tx.id());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::{
+        helpers::Height,
+        runtime::{migrations::MigrateData, versioning::Version},
+    };
+    use exonum_rust_runtime::ServiceFactory;
+    use exonum_testkit::migrations::MigrationTest;
+
+    use crate::{
+        blockchain::{data_layout::TxOutputId, AnchorCoverage, Schema},
+        test_helpers::{create_fake_funding_transaction, get_anchoring_schema, AnchoringTestKit},
+        BtcAnchoringService,
+    };
+
+    #[test]
+    fn migration_from_v1_0_keeps_anchoring_chain() {
+        // Produce the data of a running anchoring chain.
+        let mut testkit = AnchoringTestKit::default();
+        let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+        testkit
+            .inner
+            .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+        testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval));
+        testkit
+            .inner
+            .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+
+        let snapshot = testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        let chain = schema.transactions_chain.iter().collect::<Vec<_>>();
+        let spent_funding = schema
+            .spent_funding_transactions
+            .values()
+            .collect::<Vec<_>>();
+        let config = schema.actual_config();
+        let unspent_funding = create_fake_funding_transaction(&config.anchoring_address(), 150_000);
+        assert_eq!(chain.len(), 2);
+        assert!(!spent_funding.is_empty());
+
+        // Lay out the same data as the `1.0` artifact does.
+        let mut test = MigrationTest::new(BtcAnchoringService, Version::new(1, 0, 0));
+        test.setup(|access| {
+            let mut schema = Schema::new(access);
+            schema.actual_config.set(config.clone());
+            for tx in &chain {
+                schema.transactions_chain.push(tx.clone());
+            }
+            for tx in &spent_funding {
+                schema.spent_funding_transactions.put(&tx.id(), tx.clone());
+            }
+            schema
+                .unspent_funding_transaction
+                .set(unspent_funding.clone());
+        })
+        .migrate();
+
+        let schema = Schema::new(test.end_snapshot());
+        assert_eq!(schema.transactions_chain.iter().collect::<Vec<_>>(), chain);
+        assert_eq!(
+            schema
+                .spent_funding_transactions
+                .values()
+                .collect::<Vec<_>>(),
+            spent_funding
+        );
+        assert_eq!(schema.unspent_funding_transaction(), Some(unspent_funding));
+        assert_eq!(schema.actual_config(), config);
+        assert_eq!(schema.config_history(), vec![config.clone()]);
+        // The activation height of the configuration has not been recorded.
+        assert_eq!(schema.config_activations(), vec![(config.clone(), None)]);
+        assert_eq!(
+            schema.anchor_coverage(0),
+            Some(AnchorCoverage {
+                first_height: 0,
+                last_height: 0
+            })
+        );
+        assert_eq!(
+            schema.anchor_coverage(1),
+            Some(AnchorCoverage {
+                first_height: 1,
+                last_height: anchoring_interval
+            })
+        );
+        assert_eq!(schema.covering_anchor_index(Height(1)), Some(1));
+        for (index, tx) in chain.iter().enumerate() {
+            assert_eq!(
+                schema.anchoring_transaction_index(&tx.id()),
+                Some(index as u64)
+            );
+            let anchored_height = tx.anchoring_payload().unwrap().block_height;
+            assert_eq!(
+                schema.anchor_index_by_height(anchored_height),
+                Some(index as u64)
+            );
+        }
+        for tx in &chain {
+            for input in &tx.0.input {
+                let output = TxOutputId::new(
+                    input.previous_output.txid.into(),
+                    input.previous_output.vout,
+                );
+                assert_eq!(schema.spending_transaction(&output), Some(tx.id()));
+            }
+        }
+        assert!(spent_funding
+            .iter()
+            .all(|tx| schema.is_spent_funding_transaction(tx)));
+        for keys in &config.anchoring_keys {
+            assert_eq!(
+                schema.attested_bitcoin_key(&keys.service_key),
+                Some(keys.bitcoin_key)
+            );
+        }
+    }
+
+    #[test]
+    fn migration_from_unsupported_version() {
+        let scripts = BtcAnchoringService.migration_scripts(&Version::new(0, 13, 0));
+        assert!(scripts.is_err());
+        // No data migration is needed for the actual artifact.
+        let actual_version = BtcAnchoringService.artifact_id().version;
+        assert!(BtcAnchoringService
+            .migration_scripts(&actual_version)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -15,19 +15,37 @@
 //! Blockchain implementation details for the BTC anchoring service.
 
 pub use self::{schema::Schema, transactions::BtcAnchoringInterface};
-pub use crate::proto::{AddFunds, SignInput};
+pub use crate::proto::{
+    AddFunds, AnchorCoverage, AnchorInclusion, ApproveSpecialTx, AttestKey,
+    AuthorizedRecoveryExport, ConfirmAnchor, CpfpAgreement, FeeBudgetBreach, FeeSpending,
+    FinalizationIncident, ReportFeeEstimate, RequestCpfp, SignInput, SignatureConflict,
+};
 
 use bitcoin::blockdata::script::Script;
 use btc_transaction_utils::{multisig::RedeemScript, p2wsh};
 use exonum::helpers::Height;
+use serde_derive::{Deserialize, Serialize};
 
-use crate::{btc::Address, config::Config};
+use std::fmt;
+
+use crate::{
+    btc::{self, Address, BuilderError, Transaction},
+    config::Config,
+};
 
 pub mod data_layout;
 pub mod errors;
+pub(crate) mod migrations;
+mod proposal_cache;
 pub mod schema;
 pub mod transactions;
 
+/// Maximum number of inputs in the anchoring transaction.
+///
+/// The anchoring transaction spends the previous anchoring transaction output,
+/// an unspent funding transaction and up to two outputs of the fee wallet.
+pub const MAX_ANCHORING_INPUTS: usize = 4;
+
 /// Current state of the BTC anchoring service.
 #[derive(Debug, Clone)]
 pub enum BtcAnchoringState {
@@ -114,11 +132,302 @@ impl BtcAnchoringState {
 
     /// Returns the nearest following anchoring height for the given height.
     pub fn following_anchoring_height(&self, latest_anchored_height: Option<Height>) -> Height {
+        self.following_anchoring_height_in_epoch(latest_anchored_height, Height::zero())
+    }
+
+    /// Returns the nearest following anchoring height for the given height within
+    /// the anchoring epoch started at the given height.
+    pub fn following_anchoring_height_in_epoch(
+        &self,
+        latest_anchored_height: Option<Height>,
+        epoch_start: Height,
+    ) -> Height {
         latest_anchored_height.map_or_else(Height::zero, |height| match self {
             BtcAnchoringState::Regular {
                 ref actual_configuration,
-            } => actual_configuration.following_anchoring_height(height),
+            } => actual_configuration.following_anchoring_height_in_epoch(epoch_start, height),
             BtcAnchoringState::Transition { .. } => height,
         })
     }
 }
+
+/// Runtime state of the BTC anchoring service.
+///
+/// The state is derived solely from the service schema, thus it remains valid after
+/// the node restart or restoring the node from a storage snapshot.
+#[derive(Debug, Clone)]
+pub struct RuntimeState {
+    /// Current state of the anchoring.
+    pub anchoring_state: BtcAnchoringState,
+    /// Height of the latest anchored block.
+    pub latest_anchored_height: Option<Height>,
+    /// Height of the next block to be anchored.
+    pub next_anchoring_height: Height,
+    /// Proposal of the next anchoring transaction with its inputs.
+    pub proposal: Option<Result<(Transaction, Vec<Transaction>), BuilderError>>,
+}
+
+/// Reason why there is no anchoring transaction proposal at the moment.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum NoProposalReason {
+    /// The block to be anchored has not been committed yet.
+    AwaitingAnchoringHeight {
+        /// Height of the next block to be anchored.
+        next_anchoring_height: Height,
+        /// Height of the latest committed block.
+        latest_height: Height,
+    },
+    /// The anchoring chain has been transferred to the address of the following
+    /// configuration, which has not become actual yet.
+    AwaitingTransition,
+    /// The fee budget of the actual configuration has been exceeded, so the anchoring
+    /// is paused until the supervisor applies a new configuration.
+    FeeBudgetExceeded {
+        /// Height of the block in which the budget has been exceeded.
+        breach_height: Height,
+        /// Total fee in satoshis spent within the rolling window of the budget.
+        spent_fees: u64,
+        /// Maximal total fee in satoshis allowed by the budget.
+        max_fees: u64,
+    },
+    /// The anchoring chain has been shut down by the final anchoring transaction.
+    Completed {
+        /// Height of the block in which the final anchoring transaction has been finalized.
+        completion_height: Height,
+    },
+}
+
+impl fmt::Display for NoProposalReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoProposalReason::AwaitingAnchoringHeight {
+                next_anchoring_height,
+                latest_height,
+            } => write!(
+                f,
+                "waiting for the block at height {} to be anchored, the latest height is {}",
+                next_anchoring_height, latest_height
+            ),
+            NoProposalReason::AwaitingTransition => f.write_str(
+                "waiting for the following configuration to become actual after the transition",
+            ),
+            NoProposalReason::FeeBudgetExceeded {
+                breach_height,
+                spent_fees,
+                max_fees,
+            } => write!(
+                f,
+                "fee budget has been exceeded at height {}: spent {} satoshis of {}, \
+                 waiting for the supervisor to lift the pause",
+                breach_height, spent_fees, max_fees
+            ),
+            NoProposalReason::Completed { completion_height } => write!(
+                f,
+                "anchoring chain has been completed at height {}",
+                completion_height
+            ),
+        }
+    }
+}
+
+/// Pending step of the transition of the anchoring chain to the address of
+/// the following configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TransitionStep {
+    /// The transition transaction is waiting for the signatures of the anchoring nodes
+    /// of the outgoing configuration.
+    AwaitingSignatures {
+        /// Identifier of the transition transaction proposal.
+        txid: btc::Sha256d,
+        /// Total weight of the signatures collected for the least signed input.
+        signatures: u64,
+        /// Total weight of the signatures required for each input.
+        required: u64,
+    },
+    /// The transition transaction cannot be created, since the funds of the outgoing
+    /// configuration don't cover the transaction fee.
+    AwaitingFunds {
+        /// Total transaction fee.
+        total_fee: u64,
+        /// Available balance.
+        balance: u64,
+    },
+    /// The transition transaction has been finalized and is waiting for a quorum of
+    /// the anchoring nodes to report it finally confirmed in the Bitcoin blockchain.
+    AwaitingConfirmations {
+        /// Identifier of the transition transaction.
+        txid: btc::Sha256d,
+        /// Number of the anchoring nodes which have reported enough confirmations.
+        votes: u64,
+        /// Number of the votes required to consider the transaction finally confirmed.
+        required: u64,
+    },
+    /// The anchoring chain has been transferred to the following address, which
+    /// is waiting for the following configuration to become actual.
+    AwaitingConfigActivation,
+}
+
+impl fmt::Display for TransitionStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransitionStep::AwaitingSignatures {
+                txid,
+                signatures,
+                required,
+            } => write!(
+                f,
+                "waiting for the signatures of the outgoing anchoring nodes for transaction {}, \
+                 {} of {} collected",
+                txid, signatures, required
+            ),
+            TransitionStep::AwaitingFunds { total_fee, balance } => write!(
+                f,
+                "waiting for funds, the balance of {} satoshis doesn't cover the fee of {} satoshis",
+                balance, total_fee
+            ),
+            TransitionStep::AwaitingConfirmations {
+                txid,
+                votes,
+                required,
+            } => write!(
+                f,
+                "waiting for the final confirmation of transaction {}, {} of {} votes collected",
+                txid, votes, required
+            ),
+            TransitionStep::AwaitingConfigActivation => {
+                f.write_str("waiting for the following configuration to become actual")
+            }
+        }
+    }
+}
+
+/// Diagnostics of the transition of the anchoring chain to the address of the following
+/// configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransitionStatus {
+    /// Anchoring address to which the anchoring chain is transferred.
+    pub following_address: btc::Address,
+    /// Height of the block in which the following configuration has been applied.
+    pub started_at: Height,
+    /// Step of the transition which is pending at the moment.
+    pub step: TransitionStep,
+    /// Estimated number of blocks until the transition transaction is finally confirmed.
+    ///
+    /// The estimate is based on the median latency of the latest finally confirmed
+    /// anchors. It is `None` if there are no finally confirmed anchors yet or the step
+    /// depends on the actions of the administrators, like adding funds.
+    pub estimated_blocks_remaining: Option<u64>,
+}
+
+/// Progress of collecting signatures for the anchoring transaction proposal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SigningProgress {
+    /// Identifier of the anchoring transaction proposal.
+    pub txid: btc::Sha256d,
+    /// Height of the block anchored by the proposal.
+    pub anchored_height: Height,
+    /// Number of blocks the proposal has been waiting for a quorum of signatures
+    /// since the anchored block.
+    pub waiting_blocks: u64,
+    /// Number of the signatures collected for each input of the proposal.
+    pub input_signatures: Vec<u64>,
+    /// Whether the proposal has been waiting longer than the signature timeout
+    /// of the anchoring configuration.
+    pub is_stalled: bool,
+}
+
+/// Distribution of the anchoring latencies, i.e. the number of Exonum blocks between
+/// the anchored block and the block in which its anchoring transaction has been marked
+/// as finally confirmed in the Bitcoin blockchain.
+///
+/// Latencies are measured in blocks, since the blocks do not contain the time of
+/// their commit agreed by the validators.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringLatency {
+    /// Number of the latest finally confirmed anchors, which latencies are taken
+    /// into account.
+    pub samples: u64,
+    /// Median latency.
+    pub p50: u64,
+    /// 90th percentile of the latencies.
+    pub p90: u64,
+    /// 99th percentile of the latencies.
+    pub p99: u64,
+    /// Maximal latency.
+    pub max: u64,
+}
+
+impl AnchoringLatency {
+    /// Computes the distribution of the given latencies, returns `None` if there
+    /// are no latencies at all.
+    pub fn from_samples(mut latencies: Vec<u64>) -> Option<Self> {
+        latencies.sort_unstable();
+        let max = *latencies.last()?;
+        // Uses the nearest-rank method.
+        let percentile = |p: usize| {
+            let rank = (p * latencies.len() + 99) / 100;
+            latencies[rank.max(1) - 1]
+        };
+        Some(Self {
+            samples: latencies.len() as u64,
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max,
+        })
+    }
+}
+
+/// Checks that the input of the anchoring transaction proposal with the given index spends
+/// funds of the fee wallet specified in the given configuration.
+pub(crate) fn is_fee_payer_input(
+    config: &Config,
+    proposal: &Transaction,
+    inputs: &[Transaction],
+    index: usize,
+) -> bool {
+    let fee_payer_script = if let Some(script) = config.fee_payer_out_script() {
+        script
+    } else {
+        return false;
+    };
+
+    proposal
+        .0
+        .input
+        .get(index)
+        .and_then(|txin| {
+            let out_index = txin.previous_output.vout as usize;
+            inputs.get(index)?.0.output.get(out_index)
+        })
+        .map_or(false, |out| out.script_pubkey == fee_payer_script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnchoringLatency;
+
+    #[test]
+    fn anchoring_latency_percentiles() {
+        assert_eq!(AnchoringLatency::from_samples(vec![]), None);
+
+        let latency = AnchoringLatency::from_samples(vec![7]).unwrap();
+        assert_eq!((latency.p50, latency.p99, latency.max), (7, 7, 7));
+
+        // Latencies from 1 to 100 in reverse order.
+        let latency = AnchoringLatency::from_samples((1..=100).rev().collect()).unwrap();
+        assert_eq!(
+            latency,
+            AnchoringLatency {
+                samples: 100,
+                p50: 50,
+                p90: 90,
+                p99: 99,
+                max: 100,
+            }
+        );
+
+        let latency = AnchoringLatency::from_samples(vec![10, 20, 30]).unwrap();
+        assert_eq!((latency.p50, latency.p90, latency.p99), (20, 30, 30));
+    }
+}
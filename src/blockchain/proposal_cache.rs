@@ -0,0 +1,85 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cache of the anchoring transaction proposals.
+//!
+//! The proposal is needed by every `sign_input` transaction and by the block finalization
+//! hooks, while it can only change when one of its inputs changes. The proposal is a pure
+//! function of the [`ProposalKey`], so the proposal built for the same key is reused
+//! instead of being rebuilt. The cache doesn't affect the results of the execution, thus
+//! it is safe for the consensus: each node builds the proposal lazily at the first request
+//! and gets exactly the same result as without the cache.
+
+use exonum::{crypto::Hash, helpers::Height};
+
+use std::cell::RefCell;
+
+use crate::btc::{self, BuilderError, Transaction};
+
+/// Proposal of the anchoring transaction with the spent transactions.
+pub(crate) type Proposal = Option<Result<(Transaction, Vec<Transaction>), BuilderError>>;
+
+/// Inputs of the anchoring proposal builder.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ProposalKey {
+    /// Hash of the anchoring chain.
+    pub transactions_chain: Hash,
+    /// Hash of the actual anchoring configuration.
+    pub actual_config: Hash,
+    /// Hash of the following anchoring configuration.
+    pub following_config: Option<Hash>,
+    /// Identifier of the unspent funding transaction.
+    pub unspent_funding_transaction: Option<btc::Sha256d>,
+    /// Identifier of the unspent funding transaction of the fee wallet.
+    pub unspent_fee_payer_transaction: Option<btc::Sha256d>,
+    /// Whether the final anchoring transaction has been already finalized.
+    pub is_completed: bool,
+    /// Whether the anchoring is paused because of the excess of the fee budget.
+    pub is_paused: bool,
+    /// Whether the proposal is the final anchoring transaction.
+    pub is_final_anchoring: bool,
+    /// Height of the block to be anchored.
+    pub anchoring_height: Height,
+    /// Hash of the block to be anchored, if it exists.
+    pub anchoring_block_hash: Option<Hash>,
+    /// Fee per byte of the anchoring proposal.
+    pub transaction_fee: u64,
+    /// Fee per byte of the child-pays-for-parent package, if the proposal bumps the fee
+    /// of the latest anchoring transaction.
+    pub cpfp_fee: Option<u64>,
+}
+
+thread_local! {
+    /// Blocks are executed by a single thread, so the latest proposal is enough to serve
+    /// all the requests during the block execution.
+    static LATEST_PROPOSAL: RefCell<Option<(ProposalKey, Proposal)>> = RefCell::new(None);
+}
+
+/// Returns the proposal cached for the given key, or builds and caches a new one.
+pub(crate) fn get_or_build(key: ProposalKey, build: impl FnOnce() -> Proposal) -> Proposal {
+    let cached = LATEST_PROPOSAL.with(|latest| {
+        latest
+            .borrow()
+            .as_ref()
+            .filter(|(latest_key, _)| *latest_key == key)
+            .map(|(_, proposal)| proposal.clone())
+    });
+    if let Some(proposal) = cached {
+        return proposal;
+    }
+
+    let proposal = build();
+    LATEST_PROPOSAL.with(|latest| *latest.borrow_mut() = Some((key, proposal.clone())));
+    proposal
+}
@@ -14,34 +14,71 @@
 
 //! Information schema for the btc anchoring service.
 
-use exonum::{blockchain::Schema as CoreSchema, helpers::Height};
+use exonum::{
+    blockchain::Schema as CoreSchema,
+    crypto::{self, Hash, PublicKey},
+    helpers::Height,
+};
 use exonum_derive::FromAccess;
 use exonum_merkledb::{
     access::{Access, FromAccess, RawAccessMut},
-    Entry, ProofListIndex, ProofMapIndex,
+    Entry, ObjectHash, ProofListIndex, ProofMapIndex,
 };
-use log::{error, trace};
+use log::{error, info, trace, warn};
+
+use std::iter;
 
 use crate::{
     btc::{self, BtcAnchoringTransactionBuilder, BuilderError, Sha256d, Transaction},
-    config::Config,
-    proto::BinaryMap,
+    config::{Config, RecoveryExport},
+    proto::{BinaryMap, CpfpAgreement, InputWitness, TransactionWitnesses},
 };
 
-use super::{data_layout::*, BtcAnchoringState};
+use super::{
+    data_layout::*,
+    is_fee_payer_input,
+    proposal_cache::{self, ProposalKey},
+    AnchorCoverage, AnchorInclusion, AnchoringLatency, AuthorizedRecoveryExport, BtcAnchoringState,
+    FeeBudgetBreach, FeeSpending, FinalizationIncident, NoProposalReason, RuntimeState,
+    SignatureConflict, SigningProgress, TransitionStatus, TransitionStep,
+};
 
 /// A set of signatures for a transaction input ordered by the anchoring node identifiers.
 pub type InputSignatures = BinaryMap<u16, btc::InputSignature>;
 /// A set of funding transaction confirmations.
 pub type TransactionConfirmations = BinaryMap<btc::PublicKey, ()>;
+/// A set of the anchoring transaction locations reported by the anchoring nodes.
+pub type AnchorInclusionVotes = BinaryMap<btc::PublicKey, AnchorInclusion>;
+/// A set of the service keys of the validators which have approved the special
+/// anchoring transaction.
+pub type SpecialTxApprovals = BinaryMap<PublicKey, ()>;
+/// A set of the fee rates estimated by the Bitcoin nodes of the anchoring nodes.
+pub type FeeEstimates = BinaryMap<btc::PublicKey, u64>;
+/// A set of the fee rates of the child-pays-for-parent package requested by the anchoring
+/// nodes for the latest anchoring transaction.
+pub type CpfpRequests = BinaryMap<btc::PublicKey, u64>;
+
+/// Number of the latest finally confirmed anchors which latencies are used to estimate
+/// the remaining duration of the transition.
+const TRANSITION_LATENCY_WINDOW: u64 = 100;
 
 /// Information schema for `exonum-btc-anchoring`.
 #[derive(Debug, FromAccess)]
 pub struct Schema<T: Access> {
     /// Complete chain of the anchoring transactions.
     pub transactions_chain: ProofListIndex<T::Base, Transaction>,
+    /// Ranges of the block heights covered by the anchoring transactions with the same
+    /// indexes in the anchoring chain.
+    pub(crate) anchor_coverage: ProofListIndex<T::Base, AnchorCoverage>,
+    /// Indexes of the anchoring transactions in the anchoring chain by their identifiers.
+    pub(crate) transaction_indexes: ProofMapIndex<T::Base, Sha256d, u64>,
+    /// Indexes of the first anchoring transactions in the anchoring chain which anchor
+    /// the blocks with the given heights.
+    pub(crate) anchored_heights: ProofMapIndex<T::Base, u64, u64>,
     /// Already spent funding transactions.
     pub(crate) spent_funding_transactions: ProofMapIndex<T::Base, Sha256d, Transaction>,
+    /// Identifiers of the anchoring transactions by the outputs spent by them.
+    pub(crate) spent_outputs: ProofMapIndex<T::Base, TxOutputId, Sha256d>,
     /// Signatures for the given transaction input.
     pub(crate) transaction_signatures: ProofMapIndex<T::Base, TxInputId, InputSignatures>,
     /// Actual anchoring configuration entry.
@@ -54,6 +91,88 @@ pub struct Schema<T: Access> {
     /// Entry that may contain an unspent funding transaction for the
     /// actual configuration.
     pub(crate) unspent_funding_transaction: Entry<T::Base, Transaction>,
+    /// Entry that may contain an unspent funding transaction for the fee wallet
+    /// of the actual configuration.
+    pub(crate) unspent_fee_payer_transaction: Entry<T::Base, Transaction>,
+    /// Funding transactions that have received enough confirmations in the current block.
+    /// They are applied as a single batch after all transactions of the block.
+    pub(crate) pending_funding_transactions: ProofMapIndex<T::Base, Sha256d, Transaction>,
+    /// Heights of the blocks in which the batches of the confirmed funding transactions
+    /// have been rejected. The confirmations of the rejected transactions are kept, so they
+    /// are applied once any anchoring node confirms them again.
+    pub(crate) rejected_funding_transactions: ProofMapIndex<T::Base, Sha256d, u64>,
+    /// Bitcoin keys attested by the owners of the corresponding service keys.
+    pub(crate) key_attestations: ProofMapIndex<T::Base, PublicKey, btc::PublicKey>,
+    /// Heights of the blocks in which the anchoring transactions have been finalized.
+    pub(crate) finalization_heights: ProofMapIndex<T::Base, Sha256d, u64>,
+    /// Divergences detected during the finalization of the anchoring proposals.
+    pub finalization_incidents: ProofListIndex<T::Base, FinalizationIncident>,
+    /// Different signatures of the same anchoring proposal inputs made by the same
+    /// Bitcoin keys, which have been rejected.
+    pub signature_conflicts: ProofListIndex<T::Base, SignatureConflict>,
+    /// Locations of the anchoring transactions reported by the anchoring nodes, which
+    /// have not yet reached a quorum.
+    pub(crate) anchor_inclusion_votes: ProofMapIndex<T::Base, Sha256d, AnchorInclusionVotes>,
+    /// Locations of the anchoring transactions in the Bitcoin blockchain agreed
+    /// by a quorum of the anchoring nodes.
+    pub(crate) anchor_inclusions: ProofMapIndex<T::Base, Sha256d, AnchorInclusion>,
+    /// Locations of the anchoring transactions reported by the anchoring nodes together
+    /// with enough confirmations, which have not yet reached a quorum.
+    pub(crate) anchor_finality_votes: ProofMapIndex<T::Base, Sha256d, AnchorInclusionVotes>,
+    /// Heights of the blocks in which the anchoring transactions have been marked as
+    /// finally confirmed in the Bitcoin blockchain.
+    pub(crate) anchor_finality_heights: ProofMapIndex<T::Base, Sha256d, u64>,
+    /// Latencies of the finally confirmed anchors in blocks, in order of their
+    /// final confirmation.
+    pub(crate) finality_latencies: ProofListIndex<T::Base, u64>,
+    /// Height of the latest block at the moment when the final anchor has been requested.
+    /// This block is anchored by the final anchoring transaction.
+    pub(crate) final_anchoring_height: Entry<T::Base, u64>,
+    /// Height of the block in which the final anchoring transaction has been finalized.
+    pub(crate) completion_height: Entry<T::Base, u64>,
+    /// Height of the latest anchored block at the moment when the actual anchoring
+    /// interval became effective.
+    pub(crate) anchoring_epoch_start: Entry<T::Base, u64>,
+    /// Witnesses of the anchoring transactions stored in the compact form.
+    pub(crate) transaction_witnesses: ProofMapIndex<T::Base, Sha256d, TransactionWitnesses>,
+    /// Witness scripts referenced by the stored witnesses.
+    pub(crate) witness_scripts: ProofMapIndex<T::Base, Hash, Vec<u8>>,
+    /// Anchoring configurations in order of their activation.
+    pub(crate) config_history: ProofListIndex<T::Base, Config>,
+    /// Heights of the blocks in which the configurations of the history became actual.
+    /// The list is aligned with the end of the configuration history, since the heights
+    /// have not been recorded for the configurations activated before the `1.1` artifact.
+    pub(crate) config_activation_heights: ProofListIndex<T::Base, u64>,
+    /// Recovery bundle exports authorized by the supervisor.
+    pub recovery_exports: ProofListIndex<T::Base, AuthorizedRecoveryExport>,
+    /// Approvals of the finalized special anchoring transactions, which are broadcast
+    /// only after the approvals of the validators.
+    pub(crate) special_tx_approvals: ProofMapIndex<T::Base, Sha256d, SpecialTxApprovals>,
+    /// Latest fee rates reported by the anchoring nodes if the fee estimation is enabled.
+    pub(crate) fee_estimates: Entry<T::Base, FeeEstimates>,
+    /// Fee per byte of the anchoring proposals agreed upon the latest finalized anchoring
+    /// transaction if the fee estimation is enabled.
+    pub(crate) anchoring_fee: Entry<T::Base, u64>,
+    /// Height of the block in which the following configuration with a new anchoring
+    /// address has been applied. It is kept until the transition transaction is finally
+    /// confirmed.
+    pub(crate) transition_start_height: Entry<T::Base, u64>,
+    /// Identifier of the finalized transition transaction, which is not finally confirmed yet.
+    pub(crate) transition_transaction: Entry<T::Base, Sha256d>,
+    /// Fee rates of the child-pays-for-parent package requested by the anchoring nodes
+    /// for the latest anchoring transaction, which have not yet reached a quorum.
+    pub(crate) cpfp_requests: Entry<T::Base, CpfpRequests>,
+    /// Fee bumping of the latest anchoring transaction agreed by a quorum of the anchoring
+    /// nodes. It is removed once the next anchoring transaction is finalized.
+    pub(crate) cpfp_agreement: Entry<T::Base, CpfpAgreement>,
+    /// Fees paid by the finalized anchoring transactions in order of their finalization.
+    pub(crate) fee_spendings: ProofListIndex<T::Base, FeeSpending>,
+    /// Excess of the fee budget, which pauses the anchoring proposals until the supervisor
+    /// applies a new configuration.
+    pub(crate) fee_budget_breach: Entry<T::Base, FeeBudgetBreach>,
+    /// Height of the block in which the latest excess of the fee budget has been lifted.
+    /// The fees spent before this height are not counted in the budget.
+    pub(crate) fee_budget_reset_height: Entry<T::Base, u64>,
 }
 
 impl<T: Access> Schema<T> {
@@ -75,6 +194,40 @@ impl<T: Access> Schema<T> {
         self.following_config.get()
     }
 
+    /// Returns the anchoring configurations in order of their activation, including
+    /// the following configuration if it exists.
+    ///
+    /// For the services started before the history has been recorded, it begins with
+    /// the configuration which was actual at the moment of the service update.
+    pub fn config_history(&self) -> Vec<Config> {
+        let mut configs = self.config_history.iter().collect::<Vec<_>>();
+        if configs.is_empty() {
+            configs.push(self.actual_config());
+        }
+        configs.extend(self.following_config());
+        configs
+    }
+
+    /// Returns the anchoring configurations which have been actual in order of their
+    /// activation together with the heights of the blocks in which they became actual.
+    ///
+    /// The heights are unknown for the configurations activated before the `1.1`
+    /// artifact.
+    pub fn config_activations(&self) -> Vec<(Config, Option<Height>)> {
+        let configs = self.config_history.iter().collect::<Vec<_>>();
+        if configs.is_empty() {
+            return vec![(self.actual_config(), None)];
+        }
+
+        let unknown_count = configs.len() - self.config_activation_heights.len() as usize;
+        let heights = iter::repeat(None).take(unknown_count).chain(
+            self.config_activation_heights
+                .iter()
+                .map(|h| Some(Height(h))),
+        );
+        configs.into_iter().zip(heights).collect()
+    }
+
     /// Returns the list of signatures for the given transaction input.
     pub fn input_signatures(&self, input: &TxInputId) -> InputSignatures {
         self.transaction_signatures.get(input).unwrap_or_default()
@@ -85,6 +238,266 @@ impl<T: Access> Schema<T> {
         self.unspent_funding_transaction.get()
     }
 
+    /// Returns the amount of satoshis available to the anchoring wallet, i.e. the change
+    /// of the latest anchoring transaction and the output of the unspent funding transaction.
+    pub fn available_funds(&self) -> u64 {
+        let change = self
+            .transactions_chain
+            .last()
+            .and_then(|tx| tx.0.output.first().map(|out| out.value));
+        let funding = self.unspent_funding_transaction.get().and_then(|tx| {
+            let script_pubkey = self.actual_config().anchoring_out_script();
+            tx.find_out(&script_pubkey).map(|(_, out)| out.value)
+        });
+        change.unwrap_or(0) + funding.unwrap_or(0)
+    }
+
+    /// Returns an unspent funding transaction for the fee wallet if it exists.
+    pub fn unspent_fee_payer_transaction(&self) -> Option<Transaction> {
+        self.unspent_fee_payer_transaction.get()
+    }
+
+    /// Returns the identifier of the anchoring transaction which has spent the given output.
+    pub fn spending_transaction(&self, output: &TxOutputId) -> Option<Sha256d> {
+        self.spent_outputs.get(output)
+    }
+
+    /// Checks whether the given funding transaction has been already spent by one of
+    /// the anchoring transactions, either as a funding transaction or through any of its
+    /// outputs. The anchoring transactions themselves are never suitable as funding ones,
+    /// since their outputs are spent by the anchoring chain.
+    pub fn is_spent_funding_transaction(&self, transaction: &Transaction) -> bool {
+        let txid = transaction.id();
+        self.spent_funding_transactions.contains(&txid)
+            || self.transaction_indexes.contains(&txid)
+            || (0..transaction.0.output.len()).any(|output| {
+                self.spent_outputs
+                    .contains(&TxOutputId::new(txid, output as u32))
+            })
+    }
+
+    /// Returns the Bitcoin key attested by the owner of the given service key.
+    pub fn attested_bitcoin_key(&self, service_key: &PublicKey) -> Option<btc::PublicKey> {
+        self.key_attestations.get(service_key)
+    }
+
+    /// Returns the height of the block in which the anchoring transaction with the given
+    /// identifier has been finalized.
+    pub fn finalization_height(&self, txid: &Sha256d) -> Option<Height> {
+        self.finalization_heights.get(txid).map(Height)
+    }
+
+    /// Returns the location of the anchoring transaction with the given identifier in
+    /// the Bitcoin blockchain if it has been confirmed by a quorum of the anchoring nodes.
+    pub fn anchor_inclusion(&self, txid: &Sha256d) -> Option<AnchorInclusion> {
+        self.anchor_inclusions.get(txid)
+    }
+
+    /// Returns the height of the block in which the anchoring transaction with the given
+    /// identifier has been marked as finally confirmed by a quorum of the anchoring nodes.
+    pub fn anchor_finality_height(&self, txid: &Sha256d) -> Option<Height> {
+        self.anchor_finality_heights.get(txid).map(Height)
+    }
+
+    /// Returns the distribution of the latencies of the given number of the latest
+    /// finally confirmed anchors, if any.
+    pub fn anchoring_latency(&self, window: u64) -> Option<AnchoringLatency> {
+        let len = self.finality_latencies.len();
+        let from = len.saturating_sub(window);
+        AnchoringLatency::from_samples(self.finality_latencies.iter_from(from).collect())
+    }
+
+    /// Returns the service keys of the validators which have approved the special anchoring
+    /// transaction with the given identifier, or `None` if the transaction doesn't require
+    /// approvals.
+    pub fn special_tx_approvals(&self, txid: &Sha256d) -> Option<Vec<PublicKey>> {
+        self.special_tx_approvals
+            .get(txid)
+            .map(|approvals| approvals.0.keys().copied().collect())
+    }
+
+    /// Returns the latest fee rates reported by the anchoring nodes.
+    pub fn fee_estimates(&self) -> FeeEstimates {
+        self.fee_estimates.get().unwrap_or_default()
+    }
+
+    /// Returns the median of the fee rates reported by the anchoring nodes of the given
+    /// configuration, limited by the caps of its fee estimation.
+    ///
+    /// Returns `None` if the fee estimation is disabled or less than a quorum of
+    /// the anchoring nodes have reported their estimates.
+    pub fn estimated_fee(&self, config: &Config) -> Option<u64> {
+        let fee_estimation = config.fee_estimation?;
+        let estimates = self.fee_estimates();
+        let mut fees = config
+            .anchoring_keys
+            .iter()
+            .filter_map(|keys| estimates.0.get(&keys.bitcoin_key).copied())
+            .collect::<Vec<_>>();
+        if fees.len() < config.byzantine_quorum() {
+            return None;
+        }
+
+        // The median is not affected by the outliers reported by the minority
+        // of the anchoring nodes.
+        fees.sort_unstable();
+        Some(fee_estimation.clamp(fees[fees.len() / 2]))
+    }
+
+    /// Returns the fee per byte of the anchoring proposals for the given configuration.
+    ///
+    /// If the fee estimation is enabled, the fee is agreed upon the finalization of each
+    /// anchoring transaction, so it doesn't change while the anchoring proposal is being
+    /// signed. Until the first agreement, the static `transaction_fee` is used.
+    pub fn anchoring_fee(&self, config: &Config) -> u64 {
+        match config.fee_estimation {
+            Some(fee_estimation) => {
+                let fee = self.anchoring_fee.get().unwrap_or(config.transaction_fee);
+                fee_estimation.clamp(fee)
+            }
+            None => config.transaction_fee,
+        }
+    }
+
+    /// Returns the fee rates of the child-pays-for-parent package requested by the anchoring
+    /// nodes for the latest anchoring transaction until a quorum is reached.
+    pub fn cpfp_requests(&self) -> CpfpRequests {
+        self.cpfp_requests.get().unwrap_or_default()
+    }
+
+    /// Returns the median of the fee rates of the child-pays-for-parent package requested
+    /// by the anchoring nodes of the given configuration, limited by its `max_cpfp_fee`.
+    ///
+    /// Returns `None` if less than a quorum of the anchoring nodes have requested
+    /// the fee bumping.
+    pub fn requested_cpfp_fee(&self, config: &Config) -> Option<u64> {
+        let requests = self.cpfp_requests();
+        let mut fees = config
+            .anchoring_keys
+            .iter()
+            .filter_map(|keys| requests.0.get(&keys.bitcoin_key).copied())
+            .collect::<Vec<_>>();
+        if fees.len() < config.byzantine_quorum() {
+            return None;
+        }
+
+        fees.sort_unstable();
+        Some(fees[fees.len() / 2].min(config.max_cpfp_fee))
+    }
+
+    /// Returns the fee bumping of the latest anchoring transaction agreed by a quorum
+    /// of the anchoring nodes, if any. The next anchoring proposal anchors the block
+    /// specified in the agreement and pays for its parent.
+    pub fn cpfp_agreement(&self) -> Option<CpfpAgreement> {
+        self.cpfp_agreement.get()
+    }
+
+    /// Returns the total fee in satoshis of the anchoring transactions finalized within
+    /// the given number of blocks up to the given height.
+    ///
+    /// The fees spent before the latest excess of the fee budget has been lifted
+    /// are not counted.
+    pub fn spent_fees(&self, window: u64, height: Height) -> u64 {
+        let window_start = height
+            .0
+            .saturating_sub(window)
+            .max(self.fee_budget_reset_height.get().unwrap_or_default());
+        let len = self.fee_spendings.len();
+        (0..len)
+            .rev()
+            .filter_map(|index| self.fee_spendings.get(index))
+            .take_while(|spending| spending.height > window_start)
+            .map(|spending| spending.fee)
+            .sum()
+    }
+
+    /// Returns the excess of the fee budget if the anchoring proposals are paused
+    /// because of it.
+    pub fn fee_budget_breach(&self) -> Option<FeeBudgetBreach> {
+        self.fee_budget_breach.get()
+    }
+
+    /// Returns the fee paid by the anchoring transaction, which spends the outputs
+    /// of the previous anchoring transaction and the funding transactions.
+    fn anchoring_transaction_fee(&self, tx: &Transaction) -> Option<u64> {
+        let len = self.transactions_chain.len();
+        let prev_tx = len
+            .checked_sub(2)
+            .and_then(|index| self.transactions_chain.get(index));
+        let inputs = &tx.0.input;
+        let prev_outputs = inputs
+            .iter()
+            .map(|input| {
+                let txid = Sha256d::from(input.previous_output.txid);
+                let prev_tx = prev_tx
+                    .clone()
+                    .filter(|prev_tx| prev_tx.id() == txid)
+                    .or_else(|| self.spent_funding_transactions.get(&txid))?;
+                prev_tx
+                    .0
+                    .output
+                    .get(input.previous_output.vout as usize)
+                    .cloned()
+            })
+            .collect::<Option<Vec<_>>>()?;
+        tx.fee(&prev_outputs)
+    }
+
+    /// Returns the height of the block anchored by the finalized anchoring transaction
+    /// with the given identifier.
+    pub(crate) fn anchored_height(&self, txid: &Sha256d) -> Option<Height> {
+        let index = self.anchoring_transaction_index(txid)?;
+        let tx = self.transactions_chain.get(index)?;
+        tx.anchoring_payload().map(|payload| payload.block_height)
+    }
+
+    /// Returns the height of the block to be anchored by the final anchoring transaction
+    /// if the final anchor has been requested.
+    pub fn final_anchoring_height(&self) -> Option<Height> {
+        self.final_anchoring_height.get().map(Height)
+    }
+
+    /// Returns the height of the block in which the final anchoring transaction has been
+    /// finalized. No anchoring transactions are created after this height.
+    pub fn completion_height(&self) -> Option<Height> {
+        self.completion_height.get().map(Height)
+    }
+
+    /// Returns the height from which the anchoring heights of the actual anchoring
+    /// interval are counted.
+    pub fn anchoring_epoch_start(&self) -> Height {
+        self.anchoring_epoch_start
+            .get()
+            .map_or_else(Height::zero, Height)
+    }
+
+    /// Checks that the next anchoring transaction for the given anchoring state is the final one.
+    pub fn is_final_anchoring(&self, anchoring_state: &BtcAnchoringState) -> bool {
+        // The transition transaction should be finalized before the final one.
+        self.final_anchoring_height.exists()
+            && !self.completion_height.exists()
+            && anchoring_state.is_regular()
+    }
+
+    /// Returns the height of the next block to be anchored for the given anchoring state.
+    ///
+    /// Once the final anchor is requested, the block which has been the latest one
+    /// at the moment of the request is anchored regardless of the anchoring interval.
+    /// Similarly, the child-pays-for-parent proposal anchors the block specified
+    /// in the agreement.
+    pub fn following_anchoring_height(&self, anchoring_state: &BtcAnchoringState) -> Height {
+        match self.final_anchoring_height() {
+            Some(height) if self.is_final_anchoring(anchoring_state) => height,
+            _ => match self.cpfp_agreement.get() {
+                Some(agreement) => Height(agreement.anchoring_height),
+                None => anchoring_state.following_anchoring_height_in_epoch(
+                    self.latest_anchored_height(),
+                    self.anchoring_epoch_start(),
+                ),
+            },
+        }
+    }
+
     /// Returns an actual state of anchoring.
     pub fn actual_state(&self) -> BtcAnchoringState {
         let actual_configuration = self.actual_config();
@@ -103,11 +516,25 @@ impl<T: Access> Schema<T> {
     }
 
     /// Returns the proposal of the next anchoring transaction for the given anchoring state.
+    ///
+    /// The proposal is built from scratch on each call, while [`recover_runtime_state`]
+    /// reuses the proposal built for the same state of the schema.
+    ///
+    /// [`recover_runtime_state`]: #method.recover_runtime_state
     pub fn proposed_anchoring_transaction(
         &self,
         core_schema: CoreSchema<impl Access>,
         actual_state: &BtcAnchoringState,
     ) -> Option<Result<(Transaction, Vec<Transaction>), BuilderError>> {
+        // The anchoring chain has been shut down.
+        if self.completion_height.exists() {
+            return None;
+        }
+        // The anchoring is paused until the supervisor lifts the excess of the fee budget.
+        if self.fee_budget_breach.exists() {
+            return None;
+        }
+
         let config = actual_state.actual_config();
         let unspent_anchoring_transaction = self.transactions_chain.last();
         let unspent_funding_transaction = self.unspent_funding_transaction.get();
@@ -148,30 +575,340 @@ impl<T: Access> Schema<T> {
         }
 
         if let Some(tx) = unspent_funding_transaction {
+            if self.is_spent_funding_transaction(&tx) {
+                return Some(Err(BuilderError::SpentFundingTx));
+            }
             if let Err(e) = builder.additional_funds(tx) {
                 return Some(Err(e));
             }
         }
 
+        // Pay the fee from the fee wallet if it is specified.
+        if let Some(fee_payer_script) = config.fee_payer_out_script() {
+            builder.fee_payer(fee_payer_script.clone());
+            // Spend the rest of the fee wallet funds returned by the latest anchoring
+            // transaction.
+            let fee_payer_change = self
+                .transactions_chain
+                .last()
+                .filter(|tx| tx.find_out(&fee_payer_script).is_some());
+            let fee_payer_funding_transaction = self.unspent_fee_payer_transaction.get();
+            if let Some(tx) = fee_payer_funding_transaction.as_ref() {
+                if self.is_spent_funding_transaction(tx) {
+                    return Some(Err(BuilderError::SpentFundingTx));
+                }
+            }
+            let fee_payer_funds = fee_payer_change
+                .into_iter()
+                .chain(fee_payer_funding_transaction);
+            for tx in fee_payer_funds {
+                if let Err(e) = builder.fee_payer_funds(tx) {
+                    return Some(Err(e));
+                }
+            }
+        }
+
         // Add corresponding payload.
-        let latest_anchored_height = self.latest_anchored_height();
-        let anchoring_height = actual_state.following_anchoring_height(latest_anchored_height);
+        let anchoring_height = self.following_anchoring_height(actual_state);
         let anchoring_block_hash = core_schema.block_hash_by_height(anchoring_height)?;
 
         builder.payload(anchoring_height, anchoring_block_hash);
-        builder.fee(config.transaction_fee);
+        if let Some(memo) = config.encrypted_memo.clone() {
+            builder.memo(memo);
+        }
+        let is_tombstone = config
+            .final_anchor
+            .map_or(false, |request| request.tombstone);
+        if is_tombstone && self.is_final_anchoring(actual_state) {
+            builder.tombstone();
+        }
+        builder.fee(self.anchoring_fee(config));
+        // Bump the fee of the latest anchoring transaction, which is spent by the proposal.
+        if let Some(agreement) = self.cpfp_agreement.get() {
+            builder.fee(agreement.fee.max(self.anchoring_fee(config)));
+            let parent = self.last_anchoring_transaction()?;
+            if let Some(parent_fee) = self.anchoring_transaction_fee(&parent) {
+                builder.pay_for_parent(parent.vsize() as u64, parent_fee);
+            }
+        }
+        builder.relay_fee_policy(btc::RelayFeePolicy {
+            min_relay_fee: config.min_relay_fee,
+            ..btc::RelayFeePolicy::default()
+        });
 
         // Create anchoring proposal.
         Some(builder.create())
     }
 
+    /// Explains why there is no proposal of the next anchoring transaction
+    /// for the actual anchoring state.
+    ///
+    /// The result makes sense only if [`actual_proposed_anchoring_transaction`]
+    /// returns `None`.
+    ///
+    /// [`actual_proposed_anchoring_transaction`]: #method.actual_proposed_anchoring_transaction
+    pub fn no_proposal_reason(&self, core_schema: CoreSchema<impl Access>) -> NoProposalReason {
+        if let Some(completion_height) = self.completion_height() {
+            return NoProposalReason::Completed { completion_height };
+        }
+        if let Some(breach) = self.fee_budget_breach() {
+            return NoProposalReason::FeeBudgetExceeded {
+                breach_height: Height(breach.height),
+                spent_fees: breach.spent_fees,
+                max_fees: breach.max_fees,
+            };
+        }
+
+        let actual_state = self.actual_state();
+        if actual_state.is_transition() {
+            let is_transferred = self.transactions_chain.last().map_or(false, |tx| {
+                tx.0.output[0].script_pubkey == actual_state.script_pubkey()
+            });
+            if is_transferred {
+                return NoProposalReason::AwaitingTransition;
+            }
+        }
+
+        NoProposalReason::AwaitingAnchoringHeight {
+            next_anchoring_height: self.following_anchoring_height(&actual_state),
+            latest_height: core_schema.height(),
+        }
+    }
+
+    /// Returns the diagnostics of the transition of the anchoring chain to the address
+    /// of the following configuration, or `None` if there is no transition in progress.
+    ///
+    /// The transition is considered to be in progress until its transaction is finally
+    /// confirmed in the Bitcoin blockchain.
+    pub fn transition_status(
+        &self,
+        core_schema: CoreSchema<impl Access>,
+    ) -> Option<TransitionStatus> {
+        if self.completion_height.exists() {
+            return None;
+        }
+        let started_at = Height(self.transition_start_height.get()?);
+        let latest_height = core_schema.height();
+
+        let actual_state = self.actual_state();
+        let config = actual_state.actual_config();
+        let required = config.byzantine_quorum() as u64;
+        let step = if let Some(txid) = self.transition_transaction.get() {
+            TransitionStep::AwaitingConfirmations {
+                txid,
+                votes: self
+                    .anchor_finality_votes
+                    .get(&txid)
+                    .map_or(0, |votes| votes.0.len() as u64),
+                required,
+            }
+        } else {
+            match self.actual_proposed_anchoring_transaction(core_schema) {
+                None => TransitionStep::AwaitingConfigActivation,
+                Some(Ok((proposal, inputs))) => {
+                    let txid = proposal.id();
+                    // Inputs of the fee wallet are signed by its own key.
+                    let signatures = (0..proposal.0.input.len())
+                        .filter(|&index| !is_fee_payer_input(config, &proposal, &inputs, index))
+                        .map(|index| {
+                            let input_id = TxInputId::new(txid, index as u32);
+                            self.input_signatures(&input_id).weight(config) as u64
+                        })
+                        .min()
+                        .unwrap_or_default();
+                    TransitionStep::AwaitingSignatures {
+                        txid,
+                        signatures,
+                        required: config.signature_quorum() as u64,
+                    }
+                }
+                Some(Err(BuilderError::InsufficientFunds { total_fee, balance })) => {
+                    TransitionStep::AwaitingFunds { total_fee, balance }
+                }
+                // Other errors mean that the anchoring chain is broken, which is not
+                // specific to the transition.
+                Some(Err(_)) => return None,
+            }
+        };
+
+        // The transition transaction behaves like a regular anchor whose block has been
+        // committed at the start of the transition.
+        let estimated_blocks_remaining = match step {
+            TransitionStep::AwaitingSignatures { .. }
+            | TransitionStep::AwaitingConfirmations { .. } => self
+                .anchoring_latency(TRANSITION_LATENCY_WINDOW)
+                .map(|latency| {
+                    let elapsed = latest_height.0.saturating_sub(started_at.0);
+                    latency.p50.saturating_sub(elapsed)
+                }),
+            TransitionStep::AwaitingFunds { .. } | TransitionStep::AwaitingConfigActivation => None,
+        };
+        Some(TransitionStatus {
+            following_address: actual_state.output_address(),
+            started_at,
+            step,
+            estimated_blocks_remaining,
+        })
+    }
+
     /// Returns the proposal of the next anchoring transaction for the actual anchoring state.
     pub fn actual_proposed_anchoring_transaction(
         &self,
         core_schema: CoreSchema<impl Access>,
     ) -> Option<Result<(Transaction, Vec<Transaction>), BuilderError>> {
-        let actual_state = self.actual_state();
-        self.proposed_anchoring_transaction(core_schema, &actual_state)
+        self.recover_runtime_state(core_schema).proposal
+    }
+
+    /// Returns the progress of collecting signatures for the actual anchoring proposal.
+    ///
+    /// The proposal appears as soon as the block to be anchored is committed, so its waiting
+    /// time is counted from this block.
+    pub fn signing_progress(
+        &self,
+        core_schema: CoreSchema<impl Access>,
+    ) -> Option<SigningProgress> {
+        let latest_height = core_schema.next_height().0.checked_sub(1)?;
+        let (proposal, _) = self
+            .actual_proposed_anchoring_transaction(core_schema)?
+            .ok()?;
+        let anchored_height = proposal.anchoring_payload()?.block_height;
+
+        let txid = proposal.id();
+        let input_signatures = (0..proposal.0.input.len())
+            .map(|index| {
+                let input_id = TxInputId::new(txid, index as u32);
+                self.input_signatures(&input_id).0.len() as u64
+            })
+            .collect();
+        let waiting_blocks = latest_height.saturating_sub(anchored_height.0);
+        Some(SigningProgress {
+            txid,
+            anchored_height,
+            waiting_blocks,
+            input_signatures,
+            is_stalled: waiting_blocks >= self.actual_config().signature_timeout,
+        })
+    }
+
+    /// Recovers the runtime state of the anchoring service from the schema.
+    ///
+    /// The service does not keep any data between blocks in memory, this method is
+    /// the only way to obtain the current anchoring state, including the transition
+    /// flags and the anchoring transaction proposal. The proposal is built lazily
+    /// and reused while its inputs in the schema remain the same, so the repeated
+    /// calls during the block execution are cheap.
+    pub fn recover_runtime_state(&self, core_schema: CoreSchema<impl Access>) -> RuntimeState {
+        let anchoring_state = self.actual_state();
+        let latest_anchored_height = self.latest_anchored_height();
+        let next_anchoring_height = self.following_anchoring_height(&anchoring_state);
+        let transaction_fee = self.anchoring_fee(anchoring_state.actual_config());
+        let key = ProposalKey {
+            transactions_chain: self.transactions_chain.object_hash(),
+            actual_config: anchoring_state.actual_config().object_hash(),
+            following_config: self.following_config().map(|config| config.object_hash()),
+            unspent_funding_transaction: self.unspent_funding_transaction.get().map(|tx| tx.id()),
+            unspent_fee_payer_transaction: self
+                .unspent_fee_payer_transaction
+                .get()
+                .map(|tx| tx.id()),
+            is_completed: self.completion_height.exists(),
+            is_paused: self.fee_budget_breach.exists(),
+            is_final_anchoring: self.is_final_anchoring(&anchoring_state),
+            anchoring_height: next_anchoring_height,
+            anchoring_block_hash: core_schema.block_hash_by_height(next_anchoring_height),
+            transaction_fee,
+            cpfp_fee: self.cpfp_agreement.get().map(|agreement| agreement.fee),
+        };
+        let proposal = proposal_cache::get_or_build(key, || {
+            self.proposed_anchoring_transaction(core_schema, &anchoring_state)
+        });
+        RuntimeState {
+            anchoring_state,
+            latest_anchored_height,
+            next_anchoring_height,
+            proposal,
+        }
+    }
+
+    /// Returns the anchoring transaction with the given index in the anchoring chain.
+    ///
+    /// Transactions stored in the compact form are returned with the restored witnesses.
+    pub fn anchoring_transaction(&self, index: u64) -> Option<Transaction> {
+        self.transactions_chain
+            .get(index)
+            .map(|tx| self.restore_witnesses(tx))
+    }
+
+    /// Returns the latest anchoring transaction with the restored witnesses.
+    pub fn last_anchoring_transaction(&self) -> Option<Transaction> {
+        self.transactions_chain
+            .last()
+            .map(|tx| self.restore_witnesses(tx))
+    }
+
+    fn restore_witnesses(&self, mut tx: Transaction) -> Transaction {
+        let witnesses = match self.transaction_witnesses.get(&tx.id()) {
+            Some(witnesses) => witnesses,
+            None => return tx,
+        };
+        for (input, witness) in tx.0.input.iter_mut().zip(witnesses.inputs) {
+            let mut items = witness.items;
+            if let Some(script_hash) = witness.witness_script_hash {
+                let script = self.witness_scripts.get(&script_hash).expect(
+                    "Witness script of the stored anchoring transaction is absent. \
+                     If this error occurs, inform the service authors about it.",
+                );
+                items.push(script);
+            }
+            input.witness = items;
+        }
+        tx
+    }
+
+    /// Returns the index of the first anchoring transaction which anchors the block with
+    /// the given height or a later one, i.e. the anchor covering this block.
+    pub fn covering_anchor_index(&self, height: Height) -> Option<u64> {
+        // Covered ranges follow each other along the anchoring chain, so the binary search
+        // is used.
+        let len = self.anchor_coverage.len();
+        let (mut low, mut high) = (0, len);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.anchor_coverage.get(mid)?.last_height < height.0 {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        Some(low).filter(|&index| index < len)
+    }
+
+    /// Returns the index of the first anchoring transaction which anchors exactly the block
+    /// with the given height.
+    pub fn anchor_index_by_height(&self, height: Height) -> Option<u64> {
+        self.anchored_heights.get(&height.0)
+    }
+
+    /// Returns the index of the anchoring transaction nearest to the block with the given
+    /// height, i.e. the one which anchors this block, or the covering one, or the latest
+    /// one if the block has not been anchored yet. Returns `None` if the anchoring chain
+    /// is empty.
+    pub fn nearest_anchor_index(&self, height: Height) -> Option<u64> {
+        self.anchor_index_by_height(height)
+            .or_else(|| self.covering_anchor_index(height))
+            .or_else(|| self.transactions_chain.len().checked_sub(1))
+    }
+
+    /// Returns the range of the block heights covered by the anchoring transaction
+    /// with the given index.
+    pub fn anchor_coverage(&self, index: u64) -> Option<AnchorCoverage> {
+        self.anchor_coverage.get(index)
+    }
+
+    /// Returns the index of the anchoring transaction with the given identifier
+    /// in the anchoring chain.
+    pub fn anchoring_transaction_index(&self, txid: &Sha256d) -> Option<u64> {
+        self.transaction_indexes.get(txid)
     }
 
     /// Returns the height of the latest anchored block.
@@ -194,14 +931,20 @@ where
     T: Access,
     T::Base: RawAccessMut,
 {
-    /// Adds a finalized transaction to the tail of the anchoring transactions.
-    pub(crate) fn push_anchoring_transaction(&mut self, tx: Transaction) {
+    /// Adds a transaction finalized in the block with the given height to the tail
+    /// of the anchoring transactions.
+    pub(crate) fn push_anchoring_transaction(&mut self, tx: Transaction, height: Height) {
         // An unspent funding transaction is always unconditionally added to the anchoring
         // transaction proposal, so we can simply move it to the list of spent.
         if let Some(funding_transaction) = self.unspent_funding_transaction.take() {
             self.spent_funding_transactions
                 .put(&funding_transaction.id(), funding_transaction);
         }
+        // The same is true for an unspent funding transaction of the fee wallet.
+        if let Some(funding_transaction) = self.unspent_fee_payer_transaction.take() {
+            self.spent_funding_transactions
+                .put(&funding_transaction.id(), funding_transaction);
+        }
         // Special case if we have an active following configuration.
         if let Some(config) = self.following_config() {
             // Check that the anchoring transaction is correct.
@@ -223,21 +966,237 @@ where
             );
             // If preconditions are correct, just reassign the config as an actual.
             self.following_config.remove();
-            self.actual_config.set(config);
+            self.set_actual_config(config, height);
+            self.transition_transaction.set(tx.id());
+        }
+
+        let anchored_height = tx
+            .anchoring_payload()
+            .expect(
+                "Unable to find payload in the anchoring transaction. \
+                 If this error occurs, inform the service authors about it.",
+            )
+            .block_height;
+        let coverage =
+            AnchorCoverage::following(self.anchor_coverage.last().as_ref(), anchored_height);
+        self.anchor_coverage.push(coverage);
+        self.transaction_indexes
+            .put(&tx.id(), self.transactions_chain.len());
+        for input in &tx.0.input {
+            let output = TxOutputId::new(
+                input.previous_output.txid.into(),
+                input.previous_output.vout,
+            );
+            self.spent_outputs.put(&output, tx.id());
+        }
+        if !self.anchored_heights.contains(&anchored_height.0) {
+            self.anchored_heights
+                .put(&anchored_height.0, self.transactions_chain.len());
         }
+
+        let tx = if self.actual_config().compact_storage {
+            self.strip_witnesses(tx)
+        } else {
+            tx
+        };
         self.transactions_chain.push(tx);
     }
 
+    /// Records the fee paid by the anchoring transaction finalized at the given height
+    /// and pauses the anchoring proposals if the fee budget of the given configuration
+    /// is exceeded.
+    pub(crate) fn record_fee_spending(&mut self, config: &Config, fee: u64, height: Height) {
+        self.fee_spendings.push(FeeSpending {
+            height: height.0,
+            fee,
+        });
+
+        let fee_budget = match config.fee_budget {
+            Some(fee_budget) => fee_budget,
+            None => return,
+        };
+        let spent_fees = self.spent_fees(fee_budget.window, height);
+        if spent_fees > fee_budget.max_fees {
+            warn!(
+                "Fee budget has been exceeded: spent_fees={}, max_fees={}, window={}. \
+                 Anchoring is paused until the supervisor applies a new configuration.",
+                spent_fees, fee_budget.max_fees, fee_budget.window
+            );
+            self.fee_budget_breach.set(FeeBudgetBreach {
+                height: height.0,
+                spent_fees,
+                max_fees: fee_budget.max_fees,
+            });
+        }
+    }
+
+    /// Lifts the excess of the fee budget, so the anchoring proposals are resumed.
+    /// The fees spent before the given height are not counted in the budget anymore.
+    pub(crate) fn lift_fee_budget_breach(&mut self, height: Height) {
+        if self.fee_budget_breach.take().is_some() {
+            info!(
+                "Excess of the fee budget has been lifted at height {}",
+                height
+            );
+            self.fee_budget_reset_height.set(height.0);
+        }
+    }
+
+    /// Moves witnesses of the anchoring transaction to the separate index and returns
+    /// the transaction without them. The transaction identifier remains the same.
+    fn strip_witnesses(&mut self, mut tx: Transaction) -> Transaction {
+        let mut inputs = Vec::with_capacity(tx.0.input.len());
+        for input in &mut tx.0.input {
+            let mut items = std::mem::take(&mut input.witness);
+            // The P2WSH witness consists of an empty item, the signatures and
+            // the witness script, whereas the P2WPKH one of the fee wallet consists of
+            // the signature and the public key only.
+            let witness_script_hash = if items.len() > 2 {
+                let script = items.pop().unwrap();
+                let script_hash = crypto::hash(&script);
+                if !self.witness_scripts.contains(&script_hash) {
+                    self.witness_scripts.put(&script_hash, script);
+                }
+                Some(script_hash)
+            } else {
+                None
+            };
+            inputs.push(InputWitness {
+                items,
+                witness_script_hash,
+            });
+        }
+        self.transaction_witnesses
+            .put(&tx.id(), TransactionWitnesses { inputs });
+        tx
+    }
+
+    /// Sets the given configuration as the actual one starting from the block with
+    /// the given height.
+    ///
+    /// If the anchoring interval is changed, a new anchoring epoch is started at the latest
+    /// anchored height, so the next anchored block is exactly one new interval after it.
+    pub(crate) fn set_actual_config(&mut self, config: Config, height: Height) {
+        let is_interval_changed = self.actual_config.get().map_or(false, |actual| {
+            actual.anchoring_interval != config.anchoring_interval
+        });
+        if is_interval_changed {
+            let epoch_start = self.latest_anchored_height().unwrap_or_else(Height::zero);
+            self.anchoring_epoch_start.set(epoch_start.0);
+        }
+        if self.config_history.is_empty() {
+            // Services started before the history has been recorded.
+            if let Some(actual) = self.actual_config.get() {
+                self.config_history.push(actual);
+            }
+        }
+        self.config_history.push(config.clone());
+        self.config_activation_heights.push(height.0);
+        self.actual_config.set(config);
+    }
+
+    /// Records the recovery bundle export authorized by the supervisor at the given height.
+    pub(crate) fn authorize_recovery_export(&mut self, export: RecoveryExport, height: Height) {
+        self.recovery_exports.push(AuthorizedRecoveryExport {
+            escrow_key_hash: export.escrow_key_hash,
+            height: height.0,
+        });
+    }
+
     /// Sets the given transaction as the current unspent funding transaction.
     pub(crate) fn set_funding_transaction(&mut self, transaction: btc::Transaction) {
         debug_assert!(
-            !self.spent_funding_transactions.contains(&transaction.id()),
+            !self.is_spent_funding_transaction(&transaction),
             "Funding transaction must be unspent."
         );
         // Remove confirmations for this transaction to avoid attack of re-setting
         // this transaction as funding.
         self.unconfirmed_funding_transactions
             .put(&transaction.id(), TransactionConfirmations::default());
+        self.rejected_funding_transactions.remove(&transaction.id());
         self.unspent_funding_transaction.set(transaction);
     }
+
+    /// Applies funding transactions confirmed in the current block as a single batch.
+    ///
+    /// Transactions are applied in order of their identifiers, so the result does not depend
+    /// on the order of `add_funds` transactions in the block. Each wallet can be funded
+    /// by at most one transaction of the batch, since only one unspent funding transaction
+    /// is kept per wallet. If at least one transaction is unsuitable for the actual
+    /// configuration or funds the same wallet as another one, none of them is applied. In this
+    /// case the batch is recorded as rejected at the given height, the confirmations of its
+    /// transactions are kept, and the identifier of the unsuitable transaction is returned.
+    pub(crate) fn apply_pending_funding_transactions(
+        &mut self,
+        height: Height,
+    ) -> Result<(), Sha256d> {
+        let mut batch = self
+            .pending_funding_transactions
+            .values()
+            .collect::<Vec<_>>();
+        self.pending_funding_transactions.clear();
+        batch.sort_by_key(Transaction::id);
+
+        if let Err(txid) = self.check_funding_batch(&batch) {
+            for transaction in &batch {
+                self.rejected_funding_transactions
+                    .put(&transaction.id(), height.0);
+            }
+            return Err(txid);
+        }
+
+        let anchoring_out_script = self.actual_config().anchoring_out_script();
+        for transaction in batch {
+            if transaction.find_out(&anchoring_out_script).is_none() {
+                self.set_fee_payer_transaction(transaction);
+            } else {
+                self.set_funding_transaction(transaction);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the whole batch of funding transactions before applying anything.
+    fn check_funding_batch(&self, batch: &[Transaction]) -> Result<(), Sha256d> {
+        let config = self.actual_config();
+        let anchoring_out_script = config.anchoring_out_script();
+        let fee_payer_out_script = config.fee_payer_out_script();
+        let (mut has_funds, mut has_fee_payer_funds) = (false, false);
+        for transaction in batch {
+            let txid = transaction.id();
+            if self.is_spent_funding_transaction(transaction) {
+                return Err(txid);
+            }
+
+            let is_funded = if transaction.find_out(&anchoring_out_script).is_some() {
+                &mut has_funds
+            } else if fee_payer_out_script
+                .as_ref()
+                .and_then(|script| transaction.find_out(script))
+                .is_some()
+            {
+                &mut has_fee_payer_funds
+            } else {
+                return Err(txid);
+            };
+            // Another transaction of the batch funds the same wallet.
+            if *is_funded {
+                return Err(txid);
+            }
+            *is_funded = true;
+        }
+        Ok(())
+    }
+
+    /// Sets the given transaction as the current unspent funding transaction of the fee wallet.
+    pub(crate) fn set_fee_payer_transaction(&mut self, transaction: btc::Transaction) {
+        debug_assert!(
+            !self.is_spent_funding_transaction(&transaction),
+            "Funding transaction must be unspent."
+        );
+        self.unconfirmed_funding_transactions
+            .put(&transaction.id(), TransactionConfirmations::default());
+        self.rejected_funding_transactions.remove(&transaction.id());
+        self.unspent_fee_payer_transaction.set(transaction);
+    }
 }
@@ -14,20 +14,36 @@
 
 //! BTC anchoring transactions.
 
-pub use crate::proto::{AddFunds, SignInput};
+pub use crate::proto::{
+    AddFunds, ApproveSpecialTx, AttestKey, ConfirmAnchor, ReportFeeEstimate, RequestCpfp, SignInput,
+};
 
-use btc_transaction_utils::{p2wsh::InputSigner, TxInRef};
-use exonum::runtime::{CommonError, ExecutionError, ExecutionFail};
+use bitcoin::{
+    network::constants::Network,
+    secp256k1::{Message, Secp256k1, Signature},
+};
+use bitcoin_hashes::{sha256d, Hash};
+use btc_transaction_utils::{p2wpk, p2wsh::InputSigner, TxInRef};
+use exonum::{
+    crypto::PublicKey,
+    runtime::{CommonError, ExecutionError, ExecutionFail},
+};
 use exonum_derive::{exonum_interface, interface_method};
 use exonum_rust_runtime::ExecutionContext;
-use log::{info, trace};
+use log::{error, info, trace, warn};
+
+use std::iter;
 
 use crate::{btc, config::Config, BtcAnchoringService};
 
 use super::{
     data_layout::TxInputId,
     errors::Error,
-    schema::{InputSignatures, Schema, TransactionConfirmations},
+    is_fee_payer_input,
+    schema::{
+        AnchorInclusionVotes, InputSignatures, Schema, SpecialTxApprovals, TransactionConfirmations,
+    },
+    AnchorInclusion, CpfpAgreement, FinalizationIncident, SignatureConflict,
 };
 
 impl SignInput {
@@ -50,6 +66,65 @@ impl SignInput {
             )
             .map_err(|e| Error::InputVerificationFailed.with_description(e))
     }
+
+    // Check that the fee wallet input signature is correct.
+    fn verify_fee_payer_signature(
+        &self,
+        public_key: &btc::PublicKey,
+        network: Network,
+        proposal: &btc::Transaction,
+        inputs: &[btc::Transaction],
+    ) -> Result<(), ExecutionError> {
+        let input_transaction = inputs.get(self.input as usize).ok_or(Error::NoSuchInput)?;
+        btc::verify_p2wpk_input(
+            proposal,
+            self.input as usize,
+            input_transaction,
+            public_key,
+            network,
+            &self.input_signature,
+            self.sighash_type,
+        )
+        .map_err(|e| Error::InputVerificationFailed.with_description(e))
+    }
+}
+
+impl AttestKey {
+    /// Creates a proof of possession of the given Bitcoin key for the specified service key.
+    pub fn new(
+        service_key: &PublicKey,
+        bitcoin_key: btc::PublicKey,
+        bitcoin_private_key: &btc::PrivateKey,
+    ) -> Self {
+        let message = key_attestation_message(service_key, &bitcoin_key);
+        let signature = Secp256k1::signing_only()
+            .sign(&message, &bitcoin_private_key.0.key)
+            .serialize_der()
+            .to_vec();
+        Self {
+            bitcoin_key,
+            signature,
+        }
+    }
+
+    // Check that the attestation is signed by the attested Bitcoin key.
+    fn verify(&self, service_key: &PublicKey) -> Result<(), ExecutionError> {
+        let signature = Signature::from_der(&self.signature)
+            .map_err(|e| Error::InvalidKeyAttestation.with_description(e))?;
+        let message = key_attestation_message(service_key, &self.bitcoin_key);
+        Secp256k1::verification_only()
+            .verify(&message, &signature, &self.bitcoin_key.0.key)
+            .map_err(|e| Error::InvalidKeyAttestation.with_description(e))
+    }
+}
+
+/// Returns the message which should be signed by the Bitcoin key to attest it
+/// for the given service key.
+fn key_attestation_message(service_key: &PublicKey, bitcoin_key: &btc::PublicKey) -> Message {
+    let mut data = service_key.as_ref().to_vec();
+    data.extend_from_slice(&bitcoin_key.0.to_bytes());
+    let digest = sha256d::Hash::hash(&data);
+    Message::from_slice(&digest[..]).expect("Digest has a correct length")
 }
 
 impl InputSignatures {
@@ -58,6 +133,11 @@ impl InputSignatures {
         self.0.len()
     }
 
+    /// Returns the signature made by the specified anchoring node.
+    fn get(&self, id: u16) -> Option<&btc::InputSignature> {
+        self.0.get(&id)
+    }
+
     /// Inserts a key-value pair into the map.
     fn insert(&mut self, id: u16, signature: btc::InputSignature) {
         self.0.insert(id, signature);
@@ -69,6 +149,22 @@ impl InputSignatures {
     ) -> impl IntoIterator<Item = btc_transaction_utils::InputSignature> + 'a {
         self.0.values().map(|x| x.0.clone())
     }
+
+    /// Returns the total weight of the Bitcoin keys which have made the signatures.
+    pub(crate) fn weight(&self, config: &Config) -> usize {
+        self.0.keys().map(|&id| config.key_weight(id)).sum()
+    }
+
+    /// Gets an iterator over the values of the map, in order by key, each value repeated
+    /// as many times as the weight of the Bitcoin key which has made it.
+    fn weighted_values<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> impl Iterator<Item = btc_transaction_utils::InputSignature> + 'a {
+        self.0.iter().flat_map(move |(&id, signature)| {
+            iter::repeat(signature.0.clone()).take(config.key_weight(id))
+        })
+    }
 }
 
 impl TransactionConfirmations {
@@ -80,7 +176,22 @@ impl TransactionConfirmations {
     /// Checks if there are enough confirmations to mark transaction as funding.
     pub(crate) fn has_enough_confirmations(&self, config: &Config) -> Result<bool, ExecutionError> {
         let confirmations = self.0.len();
-        Ok(confirmations == config.byzantine_quorum())
+        // Confirmations of a rejected funding transaction are kept, so the later ones may
+        // exceed the quorum.
+        Ok(confirmations >= config.byzantine_quorum())
+    }
+}
+
+impl AnchorInclusionVotes {
+    /// Adds the anchoring transaction location reported by the specified anchoring node.
+    fn vote_by_node(&mut self, public_key: btc::PublicKey, inclusion: AnchorInclusion) {
+        self.0.insert(public_key, inclusion);
+    }
+
+    /// Checks if there are enough identical reports to consider the given location agreed.
+    fn has_quorum_for(&self, inclusion: &AnchorInclusion, config: &Config) -> bool {
+        let votes = self.0.values().filter(|vote| *vote == inclusion).count();
+        votes >= config.byzantine_quorum()
     }
 }
 
@@ -99,6 +210,41 @@ pub trait BtcAnchoringInterface<Ctx> {
     /// The transaction will be applied if 2/3+1 anchoring nodes sent it.
     #[interface_method(id = 1)]
     fn add_funds(&self, context: Ctx, arg: AddFunds) -> Self::Output;
+    /// Attests the Bitcoin key of the transaction author.
+    ///
+    /// Bitcoin key should be attested by its owner before it can be used
+    /// in the anchoring configuration.
+    #[interface_method(id = 2)]
+    fn attest_key(&self, context: Ctx, arg: AttestKey) -> Self::Output;
+    /// Reports the location of the confirmed anchoring transaction in the Bitcoin blockchain.
+    ///
+    /// The location will be stored if 2/3+1 anchoring nodes reported the same one.
+    /// The anchoring transaction is marked as finally confirmed if 2/3+1 anchoring nodes
+    /// reported the same location with at least `finality_confirmations` confirmations.
+    #[interface_method(id = 3)]
+    fn confirm_anchor(&self, context: Ctx, arg: ConfirmAnchor) -> Self::Output;
+    /// Approves broadcasting of the finalized special anchoring transaction.
+    ///
+    /// The approvals are made out-of-band by the validators, the special anchoring
+    /// transaction is broadcast by the anchoring nodes only after it has been approved
+    /// by the number of validators required by the emergency brake.
+    #[interface_method(id = 4)]
+    fn approve_special_tx(&self, context: Ctx, arg: ApproveSpecialTx) -> Self::Output;
+    /// Reports the fee rate estimated by the Bitcoin node of the anchoring node.
+    ///
+    /// The fee of the anchoring proposals is set to the median of the estimates once
+    /// the anchoring transaction is finalized, provided that 2/3+1 anchoring nodes
+    /// have reported their estimates.
+    #[interface_method(id = 5)]
+    fn report_fee_estimate(&self, context: Ctx, arg: ReportFeeEstimate) -> Self::Output;
+    /// Requests the fee bumping of the latest anchoring transaction, which is stuck
+    /// in the mempool, by the child-pays-for-parent transaction.
+    ///
+    /// Once 2/3+1 anchoring nodes have requested the fee bumping, the next anchoring
+    /// proposal spends the latest anchoring transaction immediately and pays for it
+    /// with the median of the requested fee rates.
+    #[interface_method(id = 6)]
+    fn request_cpfp(&self, context: Ctx, arg: RequestCpfp) -> Self::Output;
 }
 
 impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
@@ -119,86 +265,96 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
             .ok_or(Error::UnauthorizedAnchoringKey)?;
 
         // Check that there is an anchoring proposal for the actual blockchain state.
-        let (proposal, expected_inputs) = if let Some(proposal) = schema
+        let proposal = schema
             .actual_proposed_anchoring_transaction(context.data().for_core())
             .transpose()
-            .map_err(Error::anchoring_builder_error)?
-        {
-            proposal
-        } else {
-            // There is no anchoring request at the current blockchain state.
-            // Make sure txid is equal to the identifier of the last anchoring transaction.
-            let latest_anchoring_txid = schema
-                .transactions_chain
-                .last()
-                // If the anchoring chain is not established, then the proposal must exist.
-                .unwrap()
-                .id();
-            if latest_anchoring_txid == arg.txid {
-                return Ok(());
-            } else {
-                return Err(Error::UnexpectedProposalTxId.into());
-            }
+            .map_err(Error::anchoring_builder_error)?;
+        let latest_anchoring_txid = schema.transactions_chain.last().map(|tx| tx.id());
+        let (proposal, expected_inputs) = match proposal {
+            Some((proposal, inputs)) if proposal.id() == arg.txid => (proposal, inputs),
+            // The signature is made for the proposal which has been already finalized.
+            // For example, a slow node may sign the transition transaction after it has been
+            // superseded by the proposal spending funds of the following address. Such
+            // signatures are no longer needed, so we just ignore them.
+            _ if latest_anchoring_txid == Some(arg.txid) => return Ok(()),
+            Some(_) => return Err(Error::UnexpectedProposalTxId.into()),
+            None => return Err(Error::NoAnchoringProposal.into()),
         };
 
-        // Make sure txid is equal to the identifier of the anchoring transaction proposal.
-        if proposal.id() != arg.txid {
-            return Err(Error::UnexpectedProposalTxId.into());
-        }
-
         // Check that input signature is correct.
         let redeem_script = actual_config.redeem_script();
         let quorum = redeem_script.content().quorum;
         let input_signer = InputSigner::new(redeem_script);
-        arg.verify_signature(&input_signer, &public_key, &proposal, &expected_inputs)?;
+        // Inputs of the fee wallet require a single signature made by the fee payer key.
+        let is_fee_payer =
+            |index: usize| is_fee_payer_input(&actual_config, &proposal, &expected_inputs, index);
+        let required_signatures = |index: usize| if is_fee_payer(index) { 1 } else { quorum };
+
+        let input_index = arg.input as usize;
+        arg.input_signature
+            .check_canonical(arg.sighash_type)
+            .map_err(|e| Error::NonCanonicalSignature.with_description(e))?;
+        match actual_config.fee_payer_key {
+            Some(fee_payer_key) if is_fee_payer(input_index) => arg.verify_fee_payer_signature(
+                &fee_payer_key,
+                actual_config.network,
+                &proposal,
+                &expected_inputs,
+            )?,
+            // Multisig inputs are always signed by all anchoring nodes together,
+            // so they have to commit to the whole transaction.
+            _ if arg.sighash_type != btc::SigHashType::All => {
+                return Err(Error::UnsupportedSigHashType
+                    .with_description("Multisig inputs should be signed with SIGHASH_ALL."))
+            }
+            _ => arg.verify_signature(&input_signer, &public_key, &proposal, &expected_inputs)?,
+        }
 
         // All preconditions are correct and we can use this signature.
         let input_id = TxInputId::new(proposal.id(), arg.input);
         let mut input_signatures = schema.input_signatures(&input_id);
-        let mut input_signature_len = input_signatures.len();
+        // Signatures are made with deterministic nonces, so the same key never makes different
+        // signatures of the same input. The conflicting signature is recorded as an evidence
+        // instead of overwriting the previous one. The transaction doesn't fail, otherwise
+        // the evidence would be reverted together with its changes.
+        let (signer_key, recorded_signature) = match actual_config.fee_payer_key {
+            // All signatures of the fee wallet inputs are made by the fee payer key.
+            Some(fee_payer_key) if is_fee_payer(input_index) => {
+                (fee_payer_key, input_signatures.0.values().next())
+            }
+            _ => (public_key, input_signatures.get(anchoring_node_id)),
+        };
+        if let Some(recorded_signature) = recorded_signature {
+            if is_conflicting_signature(recorded_signature, &arg.input_signature) {
+                let height = context.data().for_core().next_height();
+                error!(
+                    "Bitcoin key {} has made different signatures of the input {} of the \
+                     anchoring proposal {}, the key may be compromised",
+                    signer_key, arg.input, arg.txid
+                );
+                schema.signature_conflicts.push(SignatureConflict {
+                    height: height.0,
+                    txid: arg.txid,
+                    input: arg.input,
+                    public_key: signer_key,
+                    recorded_signature: recorded_signature.clone(),
+                    conflicting_signature: arg.input_signature,
+                });
+                return Ok(());
+            }
+        }
         // Check that we have not reached the quorum yet, otherwise we should not do anything.
-        if input_signature_len < quorum {
-            // Add signature to schema.
+        // The proposal itself is finalized after all transactions of the block.
+        let collected_signatures = if is_fee_payer(input_index) {
+            input_signatures.len()
+        } else {
+            input_signatures.weight(&actual_config)
+        };
+        if collected_signatures < required_signatures(input_index) {
             input_signatures.insert(anchoring_node_id, arg.input_signature);
             schema
                 .transaction_signatures
                 .put(&input_id, input_signatures);
-            input_signature_len += 1;
-        } else {
-            return Ok(());
-        }
-
-        // If we have enough signatures for specific input we have to check that we also have
-        // sufficient signatures to finalize proposal transaction.
-        if input_signature_len == quorum {
-            let mut finalized_tx: btc::Transaction = proposal.clone();
-            // Make sure we reach a quorum for each input.
-            for index in 0..expected_inputs.len() {
-                let input_id = TxInputId::new(proposal.id(), index as u32);
-                let signatures_for_input = schema.input_signatures(&input_id);
-                // We have not enough signatures for this input, so we can not finalize this
-                // proposal at the moment.
-                if signatures_for_input.len() != quorum {
-                    return Ok(());
-                }
-
-                input_signer.spend_input(
-                    &mut finalized_tx.0.input[index],
-                    signatures_for_input.values(),
-                );
-            }
-
-            let payload = finalized_tx.anchoring_metadata().unwrap().1;
-
-            info!("====== ANCHORING ======");
-            info!("txid: {}", finalized_tx.id().to_string());
-            info!("height: {}", payload.block_height);
-            info!("hash: {}", payload.block_hash.to_hex());
-            info!("balance: {}", finalized_tx.0.output[0].value);
-            trace!("Anchoring txhex: {}", finalized_tx.to_string());
-
-            // Add finalized transaction to the tail of anchoring transactions.
-            schema.push_anchoring_transaction(finalized_tx);
         }
         Ok(())
     }
@@ -220,11 +376,18 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
         let (_, txout) = arg
             .transaction
             .find_out(&actual_config.anchoring_out_script())
+            .or_else(|| {
+                let fee_payer_script = actual_config.fee_payer_out_script()?;
+                arg.transaction.find_out(&fee_payer_script)
+            })
             .ok_or(Error::UnsuitableFundingTx)?;
+        actual_config
+            .check_funding_tx_limits(&arg.transaction)
+            .map_err(|e| Error::OversizedFundingTx.with_description(e))?;
 
         // Check that the transaction has not been used before
         let funding_txid = arg.transaction.id();
-        if schema.spent_funding_transactions.contains(&funding_txid) {
+        if schema.is_spent_funding_transaction(&arg.transaction) {
             return Err(Error::AlreadyUsedFundingTx.into());
         }
 
@@ -235,19 +398,645 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
             .unwrap_or_default();
         confirmations.confirm_by_node(public_key);
 
-        // Set this transaction as unspent funding if there are enough confirmations
-        // otherwise just write confirmation to the schema.
+        // Add this transaction to the batch of funding transactions of the current block
+        // if there are enough confirmations, otherwise just write confirmation to the schema.
+        // The batch will be applied after all transactions in the block.
         if confirmations.has_enough_confirmations(&actual_config)? {
             info!("====== ADD_FUNDS ======");
             info!("txid: {}", arg.transaction.id().to_string());
             info!("balance: {}", txout.value);
 
-            schema.set_funding_transaction(arg.transaction);
-        } else {
+            // Confirmations are removed only once the batch has been applied, so the
+            // transaction of a rejected batch can be applied later.
             schema
-                .unconfirmed_funding_transactions
-                .put(&funding_txid, confirmations);
+                .pending_funding_transactions
+                .put(&funding_txid, arg.transaction);
+        }
+        schema
+            .unconfirmed_funding_transactions
+            .put(&funding_txid, confirmations);
+        Ok(())
+    }
+
+    fn attest_key(&self, context: ExecutionContext<'_>, arg: AttestKey) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+
+        // Check that the author owns the attested Bitcoin key.
+        arg.verify(&author)?;
+
+        info!("====== ATTEST_KEY ======");
+        info!("service_key: {}", author);
+        info!("bitcoin_key: {}", arg.bitcoin_key);
+
+        Schema::new(context.service_data())
+            .key_attestations
+            .put(&author, arg.bitcoin_key);
+        Ok(())
+    }
+
+    fn confirm_anchor(&self, context: ExecutionContext<'_>, arg: ConfirmAnchor) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is authorized to report anchoring transaction locations.
+        let actual_config = schema.actual_config();
+        let (_, public_key) = actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+
+        // Check that the anchoring transaction has been finalized.
+        if !schema.finalization_heights.contains(&arg.txid) {
+            return Err(Error::UnknownAnchoringTransaction.into());
+        }
+        // The anchoring transaction is already finally confirmed, so late reports
+        // are just ignored.
+        if schema.anchor_finality_heights.contains(&arg.txid) {
+            return Ok(());
+        }
+
+        let inclusion = arg.inclusion();
+        if !schema.anchor_inclusions.contains(&arg.txid) {
+            let mut votes = schema
+                .anchor_inclusion_votes
+                .get(&arg.txid)
+                .unwrap_or_default();
+            votes.vote_by_node(public_key, inclusion.clone());
+
+            if votes.has_quorum_for(&inclusion, &actual_config) {
+                info!("====== CONFIRM_ANCHOR ======");
+                info!("txid: {}", arg.txid);
+                info!("block_hash: {}", inclusion.block_hash);
+                info!("block_height: {}", inclusion.block_height);
+
+                schema.anchor_inclusion_votes.remove(&arg.txid);
+                schema.anchor_inclusions.put(&arg.txid, inclusion.clone());
+            } else {
+                schema.anchor_inclusion_votes.put(&arg.txid, votes);
+            }
+        }
+
+        if arg.confirmations < actual_config.finality_confirmations {
+            return Ok(());
+        }
+
+        let mut votes = schema
+            .anchor_finality_votes
+            .get(&arg.txid)
+            .unwrap_or_default();
+        votes.vote_by_node(public_key, inclusion.clone());
+
+        if votes.has_quorum_for(&inclusion, &actual_config) {
+            let height = context.data().for_core().next_height();
+            info!("====== FINAL_ANCHOR ======");
+            info!("txid: {}", arg.txid);
+            info!("block_hash: {}", inclusion.block_hash);
+            info!("confirmed at: {}", height);
+
+            // The final location takes precedence over the location agreed earlier,
+            // since the anchoring transaction may have been moved by a chain reorganization.
+            schema.anchor_inclusion_votes.remove(&arg.txid);
+            schema.anchor_inclusions.put(&arg.txid, inclusion);
+            schema.anchor_finality_votes.remove(&arg.txid);
+            schema.anchor_finality_heights.put(&arg.txid, height.0);
+            if let Some(anchored_height) = schema.anchored_height(&arg.txid) {
+                let latency = height.0.saturating_sub(anchored_height.0);
+                info!("latency: {} blocks", latency);
+                schema.finality_latencies.push(latency);
+            }
+            // The transition is over once its transaction is finally confirmed.
+            if schema.transition_transaction.get() == Some(arg.txid) {
+                schema.transition_transaction.remove();
+                schema.transition_start_height.remove();
+            }
+        } else {
+            schema.anchor_finality_votes.put(&arg.txid, votes);
+        }
+        Ok(())
+    }
+
+    fn approve_special_tx(
+        &self,
+        context: ExecutionContext<'_>,
+        arg: ApproveSpecialTx,
+    ) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+
+        // Check that author is one of the validators.
+        let is_validator = context
+            .data()
+            .for_core()
+            .consensus_config()
+            .validator_keys
+            .iter()
+            .any(|keys| keys.service_key == author);
+        if !is_validator {
+            return Err(Error::UnauthorizedApprover.into());
         }
+
+        let mut schema = Schema::new(context.service_data());
+        let mut approvals = schema
+            .special_tx_approvals
+            .get(&arg.txid)
+            .ok_or(Error::UnknownSpecialTransaction)?;
+
+        info!("====== APPROVE_SPECIAL_TX ======");
+        info!("txid: {}", arg.txid);
+        info!("approved by: {}", author);
+
+        approvals.0.insert(author, ());
+        schema.special_tx_approvals.put(&arg.txid, approvals);
         Ok(())
     }
+
+    fn report_fee_estimate(
+        &self,
+        context: ExecutionContext<'_>,
+        arg: ReportFeeEstimate,
+    ) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is authorized to report fee estimates.
+        let actual_config = schema.actual_config();
+        let (_, public_key) = actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+        if actual_config.fee_estimation.is_none() {
+            return Err(Error::FeeEstimationDisabled.into());
+        }
+        if arg.fee == 0 {
+            return Err(Error::InvalidFeeEstimate.into());
+        }
+
+        trace!("Fee estimate {} reported by {}", arg.fee, public_key);
+        let mut estimates = schema.fee_estimates();
+        estimates.0.insert(public_key, arg.fee);
+        schema.fee_estimates.set(estimates);
+        Ok(())
+    }
+
+    fn request_cpfp(&self, context: ExecutionContext<'_>, arg: RequestCpfp) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let core_schema = context.data().for_core();
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is authorized to request fee bumping.
+        let actual_config = schema.actual_config();
+        let (_, public_key) = actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+        if actual_config.max_cpfp_fee == 0 {
+            return Err(Error::CpfpDisabled.into());
+        }
+        if arg.fee == 0 {
+            return Err(Error::InvalidFeeEstimate.into());
+        }
+        if schema.completion_height.exists() {
+            return Err(Error::AnchoringChainShutDown.into());
+        }
+        // Only the latest anchoring transaction, which has not been confirmed yet,
+        // can be bumped.
+        let latest_txid = schema.transactions_chain.last().map(|tx| tx.id());
+        if latest_txid != Some(arg.txid) || schema.anchor_inclusions.contains(&arg.txid) {
+            return Err(Error::UnexpectedCpfpParent.into());
+        }
+        // The agreed fee doesn't change while the child transaction is being signed.
+        if schema.cpfp_agreement.exists() {
+            return Ok(());
+        }
+
+        trace!("CPFP fee {} requested by {}", arg.fee, public_key);
+        let mut requests = schema.cpfp_requests();
+        requests.0.insert(public_key, arg.fee);
+        schema.cpfp_requests.set(requests);
+
+        if let Some(fee) = schema.requested_cpfp_fee(&actual_config) {
+            // The child transaction anchors the latest committed block, which should
+            // follow the block anchored by its parent.
+            let anchoring_height = schema
+                .latest_anchored_height()
+                .map_or(core_schema.height(), |height| {
+                    height.next().max(core_schema.height())
+                });
+            info!("====== CPFP ======");
+            info!("parent txid: {}", arg.txid);
+            info!("fee: {}", fee);
+            info!("anchoring height: {}", anchoring_height);
+            schema.cpfp_agreement.set(CpfpAgreement {
+                txid: arg.txid,
+                fee: fee.max(actual_config.transaction_fee),
+                anchoring_height: anchoring_height.0,
+            });
+            schema.cpfp_requests.remove();
+        }
+        Ok(())
+    }
+}
+
+/// Finalizes the anchoring proposal if each of its inputs has enough signatures.
+///
+/// This function is invoked after all transactions of the block, thus the finalized
+/// transaction and the height of its finalization do not depend on the order of
+/// the `sign_input` transactions within the block.
+pub(crate) fn finalize_anchoring_proposal(context: &ExecutionContext<'_>) {
+    let core_schema = context.data().for_core();
+    let height = core_schema.next_height();
+    let mut schema = Schema::new(context.service_data());
+
+    // Errors of the proposal creation are reported by the `sign_input` transactions.
+    let (proposal, inputs) = match schema.actual_proposed_anchoring_transaction(core_schema) {
+        Some(Ok(proposal)) => proposal,
+        _ => return,
+    };
+
+    let actual_config = schema.actual_config();
+    let redeem_script = actual_config.redeem_script();
+    let quorum = redeem_script.content().quorum;
+    let input_signer = InputSigner::new(redeem_script);
+    let fee_payer_signer = actual_config
+        .fee_payer_key
+        .map(|key| p2wpk::InputSigner::new(key.0, actual_config.network));
+
+    let mut finalized_tx = proposal.clone();
+    for index in 0..inputs.len() {
+        let input_id = TxInputId::new(proposal.id(), index as u32);
+        let signatures_for_input = schema.input_signatures(&input_id);
+        let input = &mut finalized_tx.0.input[index];
+        // Inputs of the fee wallet require a single signature made by the fee payer key.
+        match fee_payer_signer.as_ref() {
+            Some(signer) if is_fee_payer_input(&actual_config, &proposal, &inputs, index) => {
+                if signatures_for_input.len() != 1 {
+                    return;
+                }
+                let signature = signatures_for_input.values().into_iter().next().unwrap();
+                signer.spend_input(input, signature);
+            }
+            _ => {
+                if signatures_for_input.weight(&actual_config) < quorum {
+                    return;
+                }
+                // The signature of the key repeated in the redeem script matches each of
+                // its occurrences, the excess weight of the last signer is dropped.
+                input_signer.spend_input(
+                    input,
+                    signatures_for_input
+                        .weighted_values(&actual_config)
+                        .take(quorum),
+                );
+            }
+        }
+    }
+
+    // Cross-check the finalized transaction with the proposal rebuilt from the schema
+    // bypassing the proposal cache, the finalization must only add the witness data
+    // to the proposal.
+    let expected_proposal =
+        schema.proposed_anchoring_transaction(context.data().for_core(), &schema.actual_state());
+    let verification = match expected_proposal {
+        Some(Ok((expected_proposal, _))) => {
+            verify_finalized_transaction(&expected_proposal, &finalized_tx)
+                .map_err(|description| (expected_proposal.id(), description))
+        }
+        _ => Err((
+            proposal.id(),
+            "Anchoring proposal can't be rebuilt from the schema".to_owned(),
+        )),
+    };
+    if let Err((expected_txid, description)) = verification {
+        error!(
+            "Finalized anchoring transaction {} diverges from the proposal {}: {}",
+            finalized_tx.id(),
+            expected_txid,
+            description
+        );
+
+        let incident = FinalizationIncident {
+            height: height.0,
+            expected_txid,
+            actual_txid: finalized_tx.id(),
+            description,
+        };
+        // The proposal remains the same until the next anchoring height, so we should
+        // not record the same incident in each block.
+        let is_recorded = schema.finalization_incidents.last().map_or(false, |last| {
+            last.expected_txid == incident.expected_txid && last.actual_txid == incident.actual_txid
+        });
+        if !is_recorded {
+            schema.finalization_incidents.push(incident);
+        }
+        return;
+    }
+
+    let payload = finalized_tx.anchoring_metadata().unwrap().1;
+    let is_final = schema.is_final_anchoring(&schema.actual_state());
+    // Transition transactions sweep the funds to the following anchoring address.
+    let is_sweep = is_final || schema.following_config().is_some();
+    let is_special = is_special_transaction(&actual_config, &finalized_tx, &inputs, is_sweep);
+    let fee = transaction_fee(&finalized_tx, &inputs);
+
+    info!("====== ANCHORING ======");
+    info!("txid: {}", finalized_tx.id().to_string());
+    info!("height: {}", payload.block_height);
+    info!("hash: {}", payload.block_hash.to_hex());
+    info!("balance: {}", finalized_tx.0.output[0].value);
+    info!("finalized at: {}", height);
+    trace!("Anchoring txhex: {}", finalized_tx.to_string());
+
+    // Add finalized transaction to the tail of anchoring transactions.
+    schema
+        .finalization_heights
+        .put(&finalized_tx.id(), height.0);
+    if is_special {
+        info!("Special anchoring transaction requires approvals of the validators.");
+        schema
+            .special_tx_approvals
+            .put(&finalized_tx.id(), SpecialTxApprovals::default());
+    }
+    schema.push_anchoring_transaction(finalized_tx, height);
+    if let Some(fee) = fee {
+        schema.record_fee_spending(&actual_config, fee, height);
+    }
+    // The fee bumping requests relate to the previous anchoring transaction.
+    schema.cpfp_requests.remove();
+    schema.cpfp_agreement.remove();
+    // The fee of the next anchoring proposals is agreed upon the finalization, so it
+    // doesn't change while the anchoring proposal is being signed.
+    if let Some(fee) = schema.estimated_fee(&actual_config) {
+        info!("fee of the next anchoring transactions: {}", fee);
+        schema.anchoring_fee.set(fee);
+    }
+
+    if is_final {
+        info!("====== SHUTDOWN ======");
+        info!("final anchored height: {}", payload.block_height);
+        info!("completed at: {}", height);
+        schema.completion_height.set(height.0);
+    }
+}
+
+/// Checks that the signatures of the same input made by the same key differ, while they
+/// have the same signature hash type. Signatures of the fee wallet inputs with different
+/// signature hash types sign different messages, so they differ legitimately.
+fn is_conflicting_signature(recorded: &btc::InputSignature, other: &btc::InputSignature) -> bool {
+    let recorded = recorded.0.as_ref();
+    let other = other.0.as_ref();
+    recorded != other && recorded.last() == other.last()
+}
+
+/// Checks that the finalized anchoring transaction should be approved by the validators
+/// before broadcasting according to the emergency brake of the given configuration.
+fn is_special_transaction(
+    config: &Config,
+    transaction: &btc::Transaction,
+    inputs: &[btc::Transaction],
+    is_sweep: bool,
+) -> bool {
+    let emergency_brake = match config.emergency_brake {
+        Some(emergency_brake) => emergency_brake,
+        None => return false,
+    };
+    if is_sweep {
+        return true;
+    }
+    if emergency_brake.fee_threshold == 0 {
+        return false;
+    }
+    // The fee can't be computed only for a malformed proposal, which is not
+    // finalized anyway.
+    transaction_fee(transaction, inputs).map_or(true, |fee| fee > emergency_brake.fee_threshold)
+}
+
+/// Computes the fee of the anchoring transaction spending the outputs of the given inputs.
+fn transaction_fee(transaction: &btc::Transaction, inputs: &[btc::Transaction]) -> Option<u64> {
+    let prev_outputs = transaction
+        .0
+        .input
+        .iter()
+        .zip(inputs)
+        .filter_map(|(txin, input)| {
+            input
+                .0
+                .output
+                .get(txin.previous_output.vout as usize)
+                .cloned()
+        })
+        .collect::<Vec<_>>();
+    transaction.fee(&prev_outputs)
+}
+
+/// Warns about the anchoring proposal which has not collected a quorum of signatures
+/// within the signature timeout. The warning is repeated each timeout period until
+/// the proposal is finalized.
+pub(crate) fn check_signing_progress(context: &ExecutionContext<'_>) {
+    let schema = Schema::new(context.service_data());
+    let next_height = context.data().for_core().next_height();
+
+    // Rebuild the proposal only at the end of each timeout period.
+    let anchoring_height = schema.following_anchoring_height(&schema.actual_state());
+    let timeout = schema.actual_config().signature_timeout;
+    match next_height.0.checked_sub(anchoring_height.0 + 1) {
+        Some(blocks) if blocks > 0 && blocks % timeout == 0 => {}
+        _ => return,
+    }
+
+    if let Some(progress) = schema.signing_progress(context.data().for_core()) {
+        warn!(
+            "Stalled anchoring proposal: txid={}, anchored_height={}, waiting_blocks={}, \
+             input_signatures={:?}, signature_timeout={}",
+            progress.txid,
+            progress.anchored_height,
+            progress.waiting_blocks,
+            progress.input_signatures,
+            timeout
+        );
+    }
+}
+
+/// Checks that the finalized anchoring transaction differs from the proposal only
+/// by the witness data of the inputs, i.e. it has the same payload, spends the same
+/// outputs and has the same outputs.
+fn verify_finalized_transaction(
+    proposal: &btc::Transaction,
+    finalized_tx: &btc::Transaction,
+) -> Result<(), String> {
+    if finalized_tx.anchoring_payload() != proposal.anchoring_payload() {
+        return Err("Anchoring payloads differ".to_owned());
+    }
+
+    if finalized_tx.0.input.len() != proposal.0.input.len() {
+        return Err("Numbers of inputs differ".to_owned());
+    }
+    let inputs = finalized_tx.0.input.iter().zip(&proposal.0.input);
+    for (index, (input, expected_input)) in inputs.enumerate() {
+        if input.previous_output != expected_input.previous_output
+            || input.script_sig != expected_input.script_sig
+            || input.sequence != expected_input.sequence
+        {
+            return Err(format!("Input {} differs", index));
+        }
+        if input.witness.is_empty() {
+            return Err(format!("Input {} is not signed", index));
+        }
+    }
+
+    if finalized_tx.0.output.len() != proposal.0.output.len() {
+        return Err("Numbers of outputs differ".to_owned());
+    }
+    if let Some(index) = finalized_tx
+        .0
+        .output
+        .iter()
+        .zip(&proposal.0.output)
+        .position(|(output, expected_output)| output != expected_output)
+    {
+        return Err(format!("Output {} differs", index));
+    }
+
+    // The remaining fields, such as the version and the lock time, are checked
+    // by the transaction identifiers.
+    if finalized_tx.id() != proposal.id() {
+        return Err("Transaction identifiers differ".to_owned());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Network;
+    use bitcoin_hashes::{sha256d, Hash as _};
+    use exonum::{
+        crypto::{Hash, KeyPair},
+        helpers::Height,
+    };
+
+    use crate::{
+        btc::{self, BtcAnchoringTransactionBuilder},
+        config::{AnchoringKeys, Config},
+        test_helpers::create_fake_funding_transaction,
+    };
+
+    use super::{is_conflicting_signature, verify_finalized_transaction};
+
+    fn create_proposal(block_height: Height) -> btc::Transaction {
+        let anchoring_keys = (0..4).map(|_| AnchoringKeys {
+            bitcoin_key: btc::gen_keypair(Network::Testnet).0,
+            service_key: KeyPair::random().public_key(),
+            weight: AnchoringKeys::DEFAULT_WEIGHT,
+            derivation: None,
+        });
+        let config = Config::with_public_keys(Network::Testnet, anchoring_keys).unwrap();
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(block_height, Hash::zero());
+        builder.create().unwrap().0
+    }
+
+    #[test]
+    fn test_verify_finalized_transaction() {
+        let proposal = create_proposal(Height::zero());
+
+        let mut finalized_tx = proposal.clone();
+        assert_eq!(
+            verify_finalized_transaction(&proposal, &finalized_tx),
+            Err("Input 0 is not signed".to_owned())
+        );
+
+        finalized_tx.0.input[0].witness.push(vec![1, 2, 3]);
+        assert_eq!(
+            verify_finalized_transaction(&proposal, &finalized_tx),
+            Ok(())
+        );
+
+        finalized_tx.0.lock_time += 1;
+        assert_eq!(
+            verify_finalized_transaction(&proposal, &finalized_tx),
+            Err("Transaction identifiers differ".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_verify_tampered_finalized_transaction() {
+        let proposal = create_proposal(Height::zero());
+        let mut signed_tx = proposal.clone();
+        signed_tx.0.input[0].witness.push(vec![1, 2, 3]);
+        assert_eq!(verify_finalized_transaction(&proposal, &signed_tx), Ok(()));
+
+        // Another output is spent.
+        let mut tampered_tx = signed_tx.clone();
+        tampered_tx.0.input[0].previous_output.vout += 1;
+        assert_eq!(
+            verify_finalized_transaction(&proposal, &tampered_tx),
+            Err("Input 0 differs".to_owned())
+        );
+
+        // The funds are sent to another address.
+        let mut tampered_tx = signed_tx.clone();
+        tampered_tx.0.output[0].script_pubkey = bitcoin::Script::new();
+        assert_eq!(
+            verify_finalized_transaction(&proposal, &tampered_tx),
+            Err("Output 0 differs".to_owned())
+        );
+
+        // The change value is increased.
+        let mut tampered_tx = signed_tx.clone();
+        tampered_tx.0.output[0].value += 1;
+        assert_eq!(
+            verify_finalized_transaction(&proposal, &tampered_tx),
+            Err("Output 0 differs".to_owned())
+        );
+
+        // Another block is anchored.
+        let mut tampered_tx = signed_tx.clone();
+        tampered_tx.0.output[1] = create_proposal(Height(1)).0.output[1].clone();
+        assert_eq!(
+            verify_finalized_transaction(&proposal, &tampered_tx),
+            Err("Anchoring payloads differ".to_owned())
+        );
+
+        // An extra output is added.
+        let mut tampered_tx = signed_tx;
+        tampered_tx.0.output.push(tampered_tx.0.output[0].clone());
+        assert_eq!(
+            verify_finalized_transaction(&proposal, &tampered_tx),
+            Err("Numbers of outputs differ".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_conflicting_signatures() {
+        let (_, private_key) = btc::gen_keypair(Network::Testnet);
+        let sign = |data: &[u8], sighash_type| {
+            btc::InputSignature::sign(&sha256d::Hash::hash(data), sighash_type, &private_key)
+        };
+
+        let signature = sign(b"first", btc::SigHashType::All);
+        assert!(!is_conflicting_signature(&signature, &signature.clone()));
+        assert!(is_conflicting_signature(
+            &signature,
+            &sign(b"second", btc::SigHashType::All)
+        ));
+        // Signatures with different sighash types sign different messages.
+        assert!(!is_conflicting_signature(
+            &signature,
+            &sign(b"second", btc::SigHashType::AllPlusAnyoneCanPay)
+        ));
+    }
 }
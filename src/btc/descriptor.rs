@@ -0,0 +1,94 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Characters allowed in the output descriptors, in order of their values for the checksum.
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}\
+                             IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~\
+                             ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+/// Characters of the descriptor checksum.
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+/// Generator of the BCH code of the descriptor checksum.
+const GENERATOR: [u64; 5] = [
+    0xf5_dee5_1989,
+    0xa9_fdca_3312,
+    0x1b_ab10_e32d,
+    0x37_06b1_677a,
+    0x64_4d62_6ffd,
+];
+
+fn polymod(checksum: u64, value: u64) -> u64 {
+    let top = checksum >> 35;
+    let mut checksum = ((checksum & 0x07_ffff_ffff) << 5) ^ value;
+    for (i, generator) in GENERATOR.iter().enumerate() {
+        if (top >> i) & 1 == 1 {
+            checksum ^= generator;
+        }
+    }
+    checksum
+}
+
+/// Computes the checksum of the output descriptor as Bitcoin Core does.
+///
+/// Returns `None` if the descriptor contains characters which are not allowed
+/// in the descriptors.
+pub fn descriptor_checksum(descriptor: &str) -> Option<String> {
+    let mut checksum = 1;
+    let mut classes = Vec::with_capacity(3);
+    for c in descriptor.chars() {
+        let position = INPUT_CHARSET.find(c)? as u64;
+        checksum = polymod(checksum, position & 31);
+        classes.push(position >> 5);
+        if classes.len() == 3 {
+            checksum = polymod(checksum, classes[0] * 9 + classes[1] * 3 + classes[2]);
+            classes.clear();
+        }
+    }
+    match classes.as_slice() {
+        [first] => checksum = polymod(checksum, *first),
+        [first, second] => checksum = polymod(checksum, first * 3 + second),
+        _ => {}
+    }
+    for _ in 0..8 {
+        checksum = polymod(checksum, 0);
+    }
+    checksum ^= 1;
+
+    let checksum = (0..8)
+        .map(|i| CHECKSUM_CHARSET[((checksum >> (5 * (7 - i))) & 31) as usize] as char)
+        .collect();
+    Some(checksum)
+}
+
+/// Appends the checksum to the given output descriptor.
+pub fn with_descriptor_checksum(descriptor: &str) -> Option<String> {
+    let checksum = descriptor_checksum(descriptor)?;
+    Some(format!("{}#{}", descriptor, checksum))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{descriptor_checksum, with_descriptor_checksum};
+
+    #[test]
+    fn test_descriptor_checksum() {
+        // Examples from the Bitcoin Core documentation.
+        assert_eq!(
+            with_descriptor_checksum("addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)").unwrap(),
+            "addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)#02wpgw69"
+        );
+        assert_eq!(descriptor_checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        // Non-ASCII characters are not allowed.
+        assert!(descriptor_checksum("raw(dé)").is_none());
+    }
+}
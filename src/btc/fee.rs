@@ -0,0 +1,146 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde_derive::{Deserialize, Serialize};
+
+/// Default `minrelaytxfee` and `incrementalrelayfee` of the Bitcoin Core node
+/// in satoshis per 1000 virtual bytes.
+pub const DEFAULT_RELAY_FEE: u64 = 1000;
+
+/// Computes the fee in satoshis for the transaction of the given virtual size
+/// with the given fee rate in satoshis per 1000 virtual bytes.
+///
+/// The result is rounded in the same way as Bitcoin Core does it, so the fee is never
+/// zero for a non-zero fee rate.
+pub fn fee_for_vsize(fee_rate: u64, vsize: u64) -> u64 {
+    let fee = fee_rate * vsize / 1000;
+    if fee == 0 && fee_rate > 0 && vsize > 0 {
+        1
+    } else {
+        fee
+    }
+}
+
+/// Relay policy of the Bitcoin node, i.e. the minimal fee rates of the transactions,
+/// which the node accepts to its memory pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayFeePolicy {
+    /// Minimal fee rate of the relayed transactions in satoshis per 1000 virtual bytes,
+    /// i.e. `minrelaytxfee` of the node.
+    pub min_relay_fee: u64,
+    /// Minimal increase of the fee rate in satoshis per 1000 virtual bytes required to
+    /// replace a transaction in the memory pool, i.e. `incrementalrelayfee` of the node.
+    pub incremental_relay_fee: u64,
+}
+
+impl Default for RelayFeePolicy {
+    fn default() -> Self {
+        Self {
+            min_relay_fee: DEFAULT_RELAY_FEE,
+            incremental_relay_fee: DEFAULT_RELAY_FEE,
+        }
+    }
+}
+
+impl RelayFeePolicy {
+    /// Returns the minimal fee of the transaction of the given virtual size, which
+    /// the node accepts.
+    pub fn min_fee(&self, vsize: u64) -> u64 {
+        fee_for_vsize(self.min_relay_fee, vsize)
+    }
+
+    /// Returns the minimal fee of the transaction of the given virtual size, which
+    /// replaces a memory pool transaction with the given fee.
+    pub fn min_replacement_fee(&self, replaced_fee: u64, vsize: u64) -> u64 {
+        std::cmp::max(
+            self.min_fee(vsize),
+            replaced_fee + fee_for_vsize(self.incremental_relay_fee, vsize),
+        )
+    }
+
+    /// Checks that this policy accepts all the transactions accepted by the other one.
+    pub fn is_satisfied_by(&self, other: &Self) -> bool {
+        other.min_relay_fee >= self.min_relay_fee
+            && other.incremental_relay_fee >= self.incremental_relay_fee
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fee_for_vsize, RelayFeePolicy, DEFAULT_RELAY_FEE};
+
+    #[test]
+    fn test_fee_for_vsize() {
+        // Vectors are taken from the `CFeeRate::GetFee` tests of Bitcoin Core.
+        let vectors = [
+            (0, 0, 0),
+            (0, 1_000, 0),
+            (1_000, 0, 0),
+            (1_000, 1, 1),
+            (1_000, 121, 121),
+            (1_000, 999, 999),
+            (1_000, 1_000, 1_000),
+            (1_000, 9_000, 9_000),
+            (123, 8, 1),
+            (123, 9, 1),
+            (123, 121, 14),
+            (123, 999, 122),
+            (123, 1_000, 123),
+            (123, 9_000, 1_107),
+            (1, 1, 1),
+            (1, 1_000, 1),
+            (1, 1_999, 1),
+            (1, 2_000, 2),
+        ];
+        for &(fee_rate, vsize, fee) in &vectors {
+            assert_eq!(
+                fee_for_vsize(fee_rate, vsize),
+                fee,
+                "fee_rate={}, vsize={}",
+                fee_rate,
+                vsize
+            );
+        }
+    }
+
+    #[test]
+    fn test_relay_fee_policy() {
+        let default_policy = RelayFeePolicy::default();
+        assert_eq!(default_policy.min_fee(250), 250);
+        assert_eq!(default_policy.min_replacement_fee(1_000, 250), 1_250);
+        // The replacement must pay at least the minimal relay fee.
+        assert_eq!(default_policy.min_replacement_fee(0, 250), 250);
+
+        // Non-standard node with an increased relay fee floor.
+        let high_floor = RelayFeePolicy {
+            min_relay_fee: 5_000,
+            incremental_relay_fee: 2_000,
+        };
+        assert_eq!(high_floor.min_fee(250), 1_250);
+        assert_eq!(high_floor.min_replacement_fee(1_000, 250), 1_500);
+        assert!(high_floor.is_satisfied_by(&high_floor));
+        assert!(!high_floor.is_satisfied_by(&default_policy));
+        assert!(default_policy.is_satisfied_by(&high_floor));
+
+        // Non-standard node with a decreased relay fee floor.
+        let low_floor = RelayFeePolicy {
+            min_relay_fee: 100,
+            incremental_relay_fee: DEFAULT_RELAY_FEE,
+        };
+        assert_eq!(low_floor.min_fee(250), 25);
+        assert_eq!(low_floor.min_fee(1), 1);
+        assert!(low_floor.is_satisfied_by(&default_policy));
+        assert!(!default_policy.is_satisfied_by(&low_floor));
+    }
+}
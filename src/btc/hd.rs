@@ -0,0 +1,120 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bitcoin::{
+    secp256k1::Secp256k1,
+    util::bip32::{ChildNumber, DerivationPath, Error, ExtendedPrivKey, ExtendedPubKey},
+};
+
+use super::{PrivateKey, PublicKey};
+
+/// Derives the Bitcoin key pair from the BIP-32 extended private key by the given
+/// derivation path.
+///
+/// Unlike the randomly generated keys, the derived keys can be restored from the seed
+/// of the extended private key, so only the seed needs to be backed up.
+pub fn derive_keypair(
+    xprv: &ExtendedPrivKey,
+    path: &DerivationPath,
+) -> Result<(PublicKey, PrivateKey), Error> {
+    let secp = Secp256k1::new();
+    let private_key = xprv.derive_priv(&secp, path)?.private_key;
+    let public_key = private_key.public_key(&secp);
+    Ok((PublicKey(public_key), PrivateKey(private_key)))
+}
+
+/// Returns the derivation path of the anchoring key with the given index, i.e.
+/// the non-hardened child of the given account path like `m/84'/1'/0'`.
+///
+/// Rotating the anchoring key is as simple as incrementing the index.
+pub fn anchoring_key_path(account: &DerivationPath, index: u32) -> Result<DerivationPath, Error> {
+    let mut path = account.as_ref().to_vec();
+    path.push(ChildNumber::from_normal_idx(index)?);
+    Ok(path.into())
+}
+
+/// Returns the extended public key of the account with the given derivation path, which
+/// is published in the anchoring configuration together with the derived anchoring keys.
+pub fn account_xpub(
+    xprv: &ExtendedPrivKey,
+    account: &DerivationPath,
+) -> Result<ExtendedPubKey, Error> {
+    let secp = Secp256k1::new();
+    let account_xprv = xprv.derive_priv(&secp, account)?;
+    Ok(ExtendedPubKey::from_private(&secp, &account_xprv))
+}
+
+/// Derives the public anchoring key with the given index from the account extended
+/// public key. The result is the same as the public key of `derive_keypair` with
+/// the corresponding `anchoring_key_path`.
+pub fn derive_public_key(account_xpub: &ExtendedPubKey, index: u32) -> Result<PublicKey, Error> {
+    let secp = Secp256k1::new();
+    let child = account_xpub.ckd_pub(&secp, ChildNumber::from_normal_idx(index)?)?;
+    Ok(PublicKey(child.public_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        secp256k1::Secp256k1,
+        util::bip32::{DerivationPath, ExtendedPrivKey},
+    };
+
+    use super::{account_xpub, anchoring_key_path, derive_keypair, derive_public_key};
+
+    // Test vector 1 from BIP-32.
+    const MASTER_XPRV: &str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvv\
+                               NKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+    const CHILD_XPRV: &str = "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL\
+                              5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7";
+
+    #[test]
+    fn test_derive_keypair() {
+        let master = MASTER_XPRV.parse::<ExtendedPrivKey>().unwrap();
+        let child = CHILD_XPRV.parse::<ExtendedPrivKey>().unwrap();
+
+        let path = "m/0'".parse::<DerivationPath>().unwrap();
+        let (public_key, private_key) = derive_keypair(&master, &path).unwrap();
+        assert_eq!(private_key.0, child.private_key);
+        assert_eq!(
+            public_key.0,
+            child.private_key.public_key(&Secp256k1::new())
+        );
+        // Derivation is deterministic.
+        assert_eq!(derive_keypair(&master, &path).unwrap().0, public_key);
+    }
+
+    #[test]
+    fn test_anchoring_key_path() {
+        let account = "m/84'/1'/0'".parse::<DerivationPath>().unwrap();
+        assert_eq!(
+            anchoring_key_path(&account, 5).unwrap(),
+            "m/84'/1'/0'/5".parse::<DerivationPath>().unwrap()
+        );
+        // Hardened indexes are not allowed for the anchoring keys.
+        assert!(anchoring_key_path(&account, 1 << 31).is_err());
+    }
+
+    #[test]
+    fn test_derive_public_key() {
+        let master = MASTER_XPRV.parse::<ExtendedPrivKey>().unwrap();
+        let account = "m/84'/1'/0'".parse::<DerivationPath>().unwrap();
+        let xpub = account_xpub(&master, &account).unwrap();
+
+        let path = anchoring_key_path(&account, 3).unwrap();
+        let (public_key, _) = derive_keypair(&master, &path).unwrap();
+        assert_eq!(derive_public_key(&xpub, 3).unwrap(), public_key);
+        assert_ne!(derive_public_key(&xpub, 4).unwrap(), public_key);
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use aes_gcm::{
+    aead::{Aead, NewAead},
+    Aes256Gcm, Key, Nonce,
+};
+use rand::Rng;
+use thiserror::Error;
+
+/// Maximum length of the encrypted memo in bytes.
+///
+/// Bitcoin nodes do not relay transactions with `OP_RETURN` outputs larger than 80 bytes,
+/// so the memo can only use the space left after the regular payload.
+pub const MAX_MEMO_LEN: usize = 32;
+/// Length of the AES-GCM nonce, which is stored in the beginning of the encrypted memo.
+const MEMO_NONCE_LEN: usize = 12;
+/// Length of the AES-GCM authentication tag.
+const MEMO_TAG_LEN: usize = 16;
+/// Maximum length of the memo before encryption.
+pub const MAX_MEMO_PLAINTEXT_LEN: usize = MAX_MEMO_LEN - MEMO_NONCE_LEN - MEMO_TAG_LEN;
+
+/// Secret key of the anchoring payload memo.
+///
+/// The key is shared among auditors off-chain, the blockchain contains only encrypted memo.
+#[derive(Clone, PartialEq, Eq)]
+pub struct MemoKey([u8; 32]);
+
+/// Memo encryption errors.
+#[derive(Debug, Copy, Clone, PartialEq, Error)]
+pub enum MemoError {
+    /// Memo is too long to fit into the anchoring payload.
+    #[error(
+        "Memo is too long, its length should be less or equal than {}.",
+        MAX_MEMO_PLAINTEXT_LEN
+    )]
+    TooLong,
+    /// Encrypted memo is malformed or has been encrypted by another key.
+    #[error("Unable to decrypt memo.")]
+    DecryptionFailed,
+}
+
+impl MemoKey {
+    /// Creates a memo key from the given bytes.
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Generates a new random memo key.
+    pub fn random() -> Self {
+        Self(rand::thread_rng().gen())
+    }
+
+    /// Encrypts the given memo. The nonce is randomly generated and prepended to
+    /// the ciphertext.
+    pub fn encrypt(&self, memo: &[u8]) -> Result<Vec<u8>, MemoError> {
+        if memo.len() > MAX_MEMO_PLAINTEXT_LEN {
+            return Err(MemoError::TooLong);
+        }
+
+        let nonce: [u8; MEMO_NONCE_LEN] = rand::thread_rng().gen();
+        let ciphertext = self
+            .cipher()
+            .encrypt(Nonce::from_slice(&nonce), memo)
+            .expect("Unable to encrypt memo");
+
+        let mut encrypted_memo = nonce.to_vec();
+        encrypted_memo.extend_from_slice(&ciphertext);
+        Ok(encrypted_memo)
+    }
+
+    /// Decrypts the given memo.
+    pub fn decrypt(&self, encrypted_memo: &[u8]) -> Result<Vec<u8>, MemoError> {
+        if encrypted_memo.len() < MEMO_NONCE_LEN + MEMO_TAG_LEN {
+            return Err(MemoError::DecryptionFailed);
+        }
+
+        let (nonce, ciphertext) = encrypted_memo.split_at(MEMO_NONCE_LEN);
+        self.cipher()
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| MemoError::DecryptionFailed)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::from_slice(&self.0))
+    }
+}
+
+impl std::fmt::Debug for MemoKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoKey").finish()
+    }
+}
+
+impl hex::FromHex for MemoKey {
+    type Error = hex::FromHexError;
+
+    fn from_hex<T: AsRef<[u8]>>(hex: T) -> Result<Self, Self::Error> {
+        <[u8; 32] as hex::FromHex>::from_hex(hex).map(Self)
+    }
+}
+
+impl std::str::FromStr for MemoKey {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        hex::FromHex::from_hex(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoError, MemoKey, MAX_MEMO_LEN, MAX_MEMO_PLAINTEXT_LEN};
+
+    #[test]
+    fn test_memo_encryption_roundtrip() {
+        let key = MemoKey::random();
+        let memo = b"1234";
+
+        let encrypted_memo = key.encrypt(memo).unwrap();
+        assert_eq!(encrypted_memo.len(), MAX_MEMO_LEN);
+        assert_eq!(key.decrypt(&encrypted_memo).unwrap(), memo);
+        // Nonce is random, so the same memo gives different ciphertexts.
+        assert_ne!(key.encrypt(memo).unwrap(), encrypted_memo);
+    }
+
+    #[test]
+    fn test_memo_decryption_errors() {
+        let key = MemoKey::random();
+        let mut encrypted_memo = key.encrypt(b"42").unwrap();
+
+        assert_eq!(
+            MemoKey::random().decrypt(&encrypted_memo),
+            Err(MemoError::DecryptionFailed)
+        );
+        assert_eq!(
+            key.decrypt(&encrypted_memo[..10]),
+            Err(MemoError::DecryptionFailed)
+        );
+        encrypted_memo[20] ^= 1;
+        assert_eq!(
+            key.decrypt(&encrypted_memo),
+            Err(MemoError::DecryptionFailed)
+        );
+    }
+
+    #[test]
+    fn test_memo_too_long() {
+        let memo = vec![0; MAX_MEMO_PLAINTEXT_LEN + 1];
+        assert_eq!(MemoKey::random().encrypt(&memo), Err(MemoError::TooLong));
+    }
+}
@@ -17,24 +17,44 @@
 pub use btc_transaction_utils::test_data::{secp_gen_keypair, secp_gen_keypair_with_rng};
 
 pub use self::{
+    descriptor::{descriptor_checksum, with_descriptor_checksum},
+    fee::{fee_for_vsize, RelayFeePolicy, DEFAULT_RELAY_FEE},
+    hd::{account_xpub, anchoring_key_path, derive_keypair, derive_public_key},
+    memo::{MemoError, MemoKey, MAX_MEMO_LEN, MAX_MEMO_PLAINTEXT_LEN},
     payload::Payload,
-    transaction::{BtcAnchoringTransactionBuilder, BuilderError, Transaction},
+    spv::{SpvProof, SpvProofError},
+    transaction::{BtcAnchoringTransactionBuilder, BuilderError, ForeignAnchor, Transaction},
 };
 
-use bitcoin::{network::constants::Network, util::address};
+use bitcoin::{
+    network::constants::Network,
+    secp256k1::{self, Message, Secp256k1, Signature},
+    util::address,
+};
 use bitcoin_hashes::sha256d;
+use btc_transaction_utils::{p2wpk, TxInRef};
 use derive_more::{Display, From, FromStr, Into};
 use exonum_merkledb::{BinaryValue, ObjectHash};
 use hex::{self, FromHex, ToHex};
 use rand::Rng;
 use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
 
 #[macro_use]
 mod macros;
 
+pub(crate) mod descriptor;
+pub(crate) mod fee;
+pub(crate) mod hd;
+pub(crate) mod memo;
 pub(crate) mod payload;
+pub(crate) mod spv;
 pub(crate) mod transaction;
 
+/// Minimal value of the P2WSH output in satoshis, which is not considered as dust
+/// by the Bitcoin Core nodes with the default relay policy.
+pub const DUST_THRESHOLD: u64 = 330;
+
 /// Bitcoin ECDSA private key wrapper.
 #[derive(Clone, From, Into, PartialEq, Eq)]
 pub struct PrivateKey(pub bitcoin::PrivateKey);
@@ -51,9 +71,77 @@ pub struct Address(pub address::Address);
 #[derive(Debug, Clone, PartialEq, Into, From)]
 pub struct InputSignature(pub btc_transaction_utils::InputSignature);
 
+/// Signature hash types allowed for the anchoring transaction inputs.
+///
+/// Only the types which commit to all outputs of the transaction are considered safe,
+/// otherwise the signature could be reused to redirect the anchoring funds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SigHashType {
+    /// `SIGHASH_ALL`, the signature commits to all inputs and outputs of the transaction.
+    All,
+    /// `SIGHASH_ALL | SIGHASH_ANYONECANPAY`, the signature commits to the signed input
+    /// and all outputs of the transaction, so other inputs can be added or removed.
+    AllPlusAnyoneCanPay,
+}
+
+impl SigHashType {
+    /// Returns the signature hash type with the given raw value, if it is safe.
+    pub fn from_u32(value: u32) -> Result<Self, SignatureError> {
+        match value {
+            0x01 => Ok(SigHashType::All),
+            0x81 => Ok(SigHashType::AllPlusAnyoneCanPay),
+            other => Err(SignatureError::UnsafeSigHashType(other)),
+        }
+    }
+
+    /// Returns the raw value of the signature hash type.
+    pub fn as_u32(self) -> u32 {
+        match self {
+            SigHashType::All => 0x01,
+            SigHashType::AllPlusAnyoneCanPay => 0x81,
+        }
+    }
+}
+
+impl Default for SigHashType {
+    fn default() -> Self {
+        SigHashType::All
+    }
+}
+
+/// Reasons why an input signature is not in the canonical form.
+#[derive(Debug, Copy, Clone, PartialEq, Error)]
+pub enum SignatureError {
+    /// Signature is made with the sighash type other than the declared one.
+    #[error("Unexpected sighash type: {:#04x}.", _0)]
+    UnexpectedSigHashType(u8),
+    /// Sighash type does not commit to all outputs of the transaction.
+    #[error("Sighash type {:#04x} is not allowed.", _0)]
+    UnsafeSigHashType(u32),
+    /// Signature is not a strict DER encoding.
+    #[error("Signature is not encoded in the strict DER form.")]
+    NonCanonicalEncoding,
+    /// Signature has the `S` value in the upper half of the curve order.
+    #[error("Signature has a high S value.")]
+    HighS,
+}
+
 /// Bitcoin SHA256d hash.
 #[derive(
-    Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Into, From, Serialize, Deserialize, Display,
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Into,
+    From,
+    Serialize,
+    Deserialize,
+    Display,
+    FromStr,
 )]
 pub struct Sha256d(pub sha256d::Hash);
 
@@ -171,6 +259,74 @@ impl BinaryValue for InputSignature {
     }
 }
 
+impl InputSignature {
+    /// Creates a signature of the given signature hash with the specified signature
+    /// hash type.
+    pub fn sign(
+        sighash: &sha256d::Hash,
+        sighash_type: SigHashType,
+        private_key: &PrivateKey,
+    ) -> Self {
+        let message =
+            Message::from_slice(&sighash[..]).expect("Signature hash has a correct length");
+        let mut bytes = Secp256k1::signing_only()
+            .sign(&message, &private_key.0.key)
+            .serialize_der()
+            .to_vec();
+        bytes.push(sighash_type.as_u32() as u8);
+        Self(
+            btc_transaction_utils::InputSignature::from_bytes(bytes)
+                .expect("Signature is encoded correctly"),
+        )
+    }
+
+    /// Verifies the signature of the given signature hash.
+    pub fn verify(
+        &self,
+        sighash: &sha256d::Hash,
+        public_key: &PublicKey,
+    ) -> Result<(), secp256k1::Error> {
+        let bytes = self.0.as_ref();
+        let der = bytes
+            .split_last()
+            .map(|(_, der)| der)
+            .ok_or(secp256k1::Error::InvalidSignature)?;
+        let signature = Signature::from_der(der)?;
+        let message = Message::from_slice(&sighash[..])?;
+        Secp256k1::verification_only().verify(&message, &signature, &public_key.0.key)
+    }
+
+    /// Checks that the signature is a strict DER encoded low-S signature
+    /// with the given signature hash type.
+    ///
+    /// Bitcoin nodes with the default policy do not relay transactions with
+    /// non-canonical signatures, so such signatures must not get into the anchoring
+    /// transactions.
+    pub fn check_canonical(&self, sighash_type: SigHashType) -> Result<(), SignatureError> {
+        let (&signature_type, der) = self
+            .0
+            .as_ref()
+            .split_last()
+            .ok_or(SignatureError::NonCanonicalEncoding)?;
+        if u32::from(signature_type) != sighash_type.as_u32() {
+            return Err(SignatureError::UnexpectedSigHashType(signature_type));
+        }
+
+        let signature =
+            Signature::from_der(der).map_err(|_| SignatureError::NonCanonicalEncoding)?;
+        if signature.serialize_der().as_ref() != der {
+            return Err(SignatureError::NonCanonicalEncoding);
+        }
+
+        let mut normalized = signature;
+        normalized.normalize_s();
+        if normalized != signature {
+            return Err(SignatureError::HighS);
+        }
+        Ok(())
+    }
+}
+
 impl ObjectHash for InputSignature {
     fn object_hash(&self) -> exonum::crypto::Hash {
         exonum::crypto::hash(&self.to_bytes())
@@ -217,6 +373,34 @@ impl_serde_str! { PublicKey }
 impl_serde_str! { Address }
 impl_serde_str! { InputSignature }
 
+/// Verifies the signature of the P2WPKH input with the given index, which spends
+/// the output of the `prev_tx` transaction.
+pub(crate) fn verify_p2wpk_input(
+    transaction: &Transaction,
+    index: usize,
+    prev_tx: &Transaction,
+    public_key: &PublicKey,
+    network: Network,
+    signature: &InputSignature,
+    sighash_type: SigHashType,
+) -> Result<(), secp256k1::Error> {
+    match sighash_type {
+        SigHashType::All => p2wpk::InputSigner::new(public_key.0, network).verify_input(
+            TxInRef::new(transaction.as_ref(), index),
+            prev_tx.as_ref(),
+            &public_key.0,
+            signature.as_ref(),
+        ),
+        _ => {
+            let out_index = transaction.0.input[index].previous_output.vout as usize;
+            let value = prev_tx.0.output[out_index].value;
+            let script_code = address::Address::p2pkh(&public_key.0, network).script_pubkey();
+            let sighash = transaction.signature_hash(index, &script_code, value, sighash_type);
+            signature.verify(&sighash, public_key)
+        }
+    }
+}
+
 /// Generates Bitcoin keypair using the given random number generator.
 pub fn gen_keypair_with_rng<R: Rng + ?Sized>(
     rng: &mut R,
@@ -21,28 +21,38 @@ use bitcoin::blockdata::{
 use byteorder::{ByteOrder, LittleEndian};
 use serde_derive::{Deserialize, Serialize};
 
-use super::Sha256d;
+use super::{
+    memo::{MemoError, MemoKey, MAX_MEMO_LEN},
+    Sha256d,
+};
 
 const PAYLOAD_PREFIX: &[u8] = b"EXONUM";
 const PAYLOAD_HEADER_LEN: usize = 8;
 const PAYLOAD_V1: u8 = 1;
 const PAYLOAD_V1_KIND_REGULAR: u8 = 0;
 const PAYLOAD_V1_KIND_RECOVER: u8 = 1;
+const PAYLOAD_V1_KIND_MEMO: u8 = 2;
+const PAYLOAD_V1_KIND_FINAL: u8 = 3;
 
 /// Anchoring transaction payload.
 ///
 /// Data layout in `OP_RETURN` script for `Payload` v.1:
 ///
-/// | Position in bytes     | Description                                       |
-/// |-----------------------|---------------------------------------------------|
-/// | 0..6                  | ASCII-encoded prefix `EXONUM`                     |
-/// | 6                     | Version byte, currently is 1                      |
-/// | 7                     | Payload kind: (0 is regular, 1 is recover)        |
-/// | 8..16                 | Block height                                      |
-/// | 16..48                | Block hash                                        |
-/// | 48..80 (Optionally)   | Txid of previous tx chain (only for recover kind) |
+/// | Position in bytes     | Description                                            |
+/// |-----------------------|--------------------------------------------------------|
+/// | 0..6                  | ASCII-encoded prefix `EXONUM`                          |
+/// | 6                     | Version byte, currently is 1                           |
+/// | 7                     | Payload kind: (0 is regular, 1 is recover, 2 is memo,  |
+/// |                       | 3 is final)                                            |
+/// | 8..16                 | Block height                                           |
+/// | 16..48                | Block hash                                             |
+/// | 48..80 (Optionally)   | Txid of previous tx chain (only for recover kind)      |
+/// | 49..80 (Optionally)   | Encrypted memo (only for memo and final kinds)         |
 ///
-/// In this way the length of `regular` payload is 48, and for `recover` is 80.
+/// In this way the length of `regular` payload is 48, for `recover` is 80, for `memo`
+/// is from 49 to 80 depending on the memo length, and for `final` is from 48 to 80.
+/// The `final` kind is the tombstone of the anchoring chain, no further anchoring
+/// transactions follow it.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Payload {
     /// Anchored block height.
@@ -51,12 +61,20 @@ pub struct Payload {
     pub block_hash: Hash,
     /// `Txid` of previous transactions chain if it has been lost.
     pub prev_tx_chain: Option<Sha256d>,
+    /// Encrypted memo attached to the anchored block.
+    #[serde(default)]
+    pub memo: Option<Vec<u8>>,
+    /// Whether this is the final anchor of the anchoring chain.
+    #[serde(default)]
+    pub is_final: bool,
 }
 
 #[derive(Debug)]
 enum PayloadV1 {
     Regular(Height, Hash),
     Recover(Height, Hash, Sha256d),
+    Memo(Height, Hash, Vec<u8>),
+    Final(Height, Hash, Option<Vec<u8>>),
 }
 
 #[derive(Debug, Default)]
@@ -64,6 +82,8 @@ pub struct PayloadV1Builder {
     block_hash: Option<Hash>,
     block_height: Option<Height>,
     prev_tx_chain: Option<Sha256d>,
+    memo: Option<Vec<u8>>,
+    tombstone: bool,
 }
 
 pub type PayloadBuilder = PayloadV1Builder;
@@ -93,6 +113,26 @@ impl PayloadV1 {
                 let txid = Sha256d::from_slice(&data[40..72]).unwrap();
                 Some(PayloadV1::Recover(Height(block_height), block_hash, txid))
             }
+            PAYLOAD_V1_KIND_MEMO => {
+                if data.len() <= 40 || data.len() > 40 + MAX_MEMO_LEN {
+                    return None;
+                }
+
+                let block_height = LittleEndian::read_u64(&data[0..8]);
+                let block_hash = Hash::from_slice(&data[8..40]).unwrap();
+                let memo = data[40..].to_vec();
+                Some(PayloadV1::Memo(Height(block_height), block_hash, memo))
+            }
+            PAYLOAD_V1_KIND_FINAL => {
+                if data.len() < 40 || data.len() > 40 + MAX_MEMO_LEN {
+                    return None;
+                }
+
+                let block_height = LittleEndian::read_u64(&data[0..8]);
+                let block_hash = Hash::from_slice(&data[8..40]).unwrap();
+                let memo = Some(data[40..].to_vec()).filter(|memo| !memo.is_empty());
+                Some(PayloadV1::Final(Height(block_height), block_hash, memo))
+            }
             _ => None,
         }
     }
@@ -114,6 +154,18 @@ impl PayloadV1 {
                 buf[8..40].copy_from_slice(hash.as_ref());
                 buf[40..72].copy_from_slice(&txid.0[..]);
             }
+            PayloadV1::Memo(height, hash, ref memo) => {
+                LittleEndian::write_u64(&mut buf[0..8], height.0);
+                buf[8..40].copy_from_slice(hash.as_ref());
+                buf[40..].copy_from_slice(memo);
+            }
+            PayloadV1::Final(height, hash, ref memo) => {
+                LittleEndian::write_u64(&mut buf[0..8], height.0);
+                buf[8..40].copy_from_slice(hash.as_ref());
+                if let Some(memo) = memo {
+                    buf[40..].copy_from_slice(memo);
+                }
+            }
         };
     }
 
@@ -121,6 +173,8 @@ impl PayloadV1 {
         match *self {
             PayloadV1::Regular(..) => 40,
             PayloadV1::Recover(..) => 72,
+            PayloadV1::Memo(_, _, ref memo) => 40 + memo.len(),
+            PayloadV1::Final(_, _, ref memo) => 40 + memo.as_ref().map_or(0, Vec::len),
         }
     }
 
@@ -128,6 +182,8 @@ impl PayloadV1 {
         match *self {
             PayloadV1::Regular(..) => PAYLOAD_V1_KIND_REGULAR,
             PayloadV1::Recover(..) => PAYLOAD_V1_KIND_RECOVER,
+            PayloadV1::Memo(..) => PAYLOAD_V1_KIND_MEMO,
+            PayloadV1::Final(..) => PAYLOAD_V1_KIND_FINAL,
         }
     }
 
@@ -152,6 +208,8 @@ impl PayloadV1Builder {
             block_hash: None,
             block_height: None,
             prev_tx_chain: None,
+            memo: None,
+            tombstone: false,
         }
     }
 
@@ -170,21 +228,64 @@ impl PayloadV1Builder {
         self
     }
 
+    /// Sets the encrypted memo. There is no room for memo in the `recover` payload,
+    /// so it is ignored if the previous tx chain is set.
+    pub fn memo(mut self, memo: Option<Vec<u8>>) -> Self {
+        self.memo = memo;
+        self
+    }
+
+    /// Marks the payload as the tombstone of the anchoring chain. The `recover` payload
+    /// takes precedence over the tombstone.
+    pub fn tombstone(mut self, tombstone: bool) -> Self {
+        self.tombstone = tombstone;
+        self
+    }
+
     pub fn into_script(self) -> Script {
         let block_height = self.block_height.expect("Block height is not set");
         let block_hash = self.block_hash.expect("Block hash is not set");
 
-        let payload = match self.prev_tx_chain {
-            Some(txid) => PayloadV1::Recover(block_height, block_hash, txid),
-            None => PayloadV1::Regular(block_height, block_hash),
+        if let Some(memo) = self.memo.as_ref() {
+            assert!(
+                !memo.is_empty() && memo.len() <= MAX_MEMO_LEN,
+                "Memo length is out of range"
+            );
+        }
+
+        let payload = match (self.prev_tx_chain, self.memo) {
+            (Some(txid), _) => PayloadV1::Recover(block_height, block_hash, txid),
+            (None, memo) if self.tombstone => PayloadV1::Final(block_height, block_hash, memo),
+            (None, Some(memo)) => PayloadV1::Memo(block_height, block_hash, memo),
+            (None, None) => PayloadV1::Regular(block_height, block_hash),
         };
         payload.into_script()
     }
 }
 
 impl Payload {
+    /// Version of the payload format used by the new anchoring transactions.
+    pub const VERSION: u8 = PAYLOAD_V1;
+
+    /// Decrypts the memo attached to this payload with the given key, if any.
+    pub fn decrypt_memo(&self, key: &MemoKey) -> Option<Result<Vec<u8>, MemoError>> {
+        self.memo.as_ref().map(|memo| key.decrypt(memo))
+    }
+
     /// Tries to extract payload from given `Script`.
     pub fn from_script(script: &Script) -> Option<Self> {
+        Self::from_tagged_script(script).and_then(|(tag, payload)| {
+            if tag == PAYLOAD_PREFIX {
+                Some(payload)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Tries to extract payload from given `Script` regardless of its prefix.
+    /// Returns the payload together with the prefix it was tagged by.
+    pub(crate) fn from_tagged_script(script: &Script) -> Option<(&[u8], Self)> {
         let mut instructions = script.iter(true);
         instructions
             .next()
@@ -200,13 +301,11 @@ impl Payload {
                     if bytes.len() < PAYLOAD_HEADER_LEN {
                         return None;
                     }
-                    if &bytes[0..6] != PAYLOAD_PREFIX {
-                        return None;
-                    }
                     // Parse metadata
+                    let tag = &bytes[0..6];
                     let version = bytes[6];
                     match version {
-                        PAYLOAD_V1 => PayloadV1::read(&bytes[7..]).map(Self::from),
+                        PAYLOAD_V1 => PayloadV1::read(&bytes[7..]).map(|v1| (tag, Self::from(v1))),
                         _ => None,
                     }
                 } else {
@@ -223,11 +322,29 @@ impl From<PayloadV1> for Payload {
                 block_height: height,
                 block_hash: hash,
                 prev_tx_chain: None,
+                memo: None,
+                is_final: false,
             },
             PayloadV1::Recover(height, hash, txid) => Self {
                 block_height: height,
                 block_hash: hash,
                 prev_tx_chain: Some(txid),
+                memo: None,
+                is_final: false,
+            },
+            PayloadV1::Memo(height, hash, memo) => Self {
+                block_height: height,
+                block_hash: hash,
+                prev_tx_chain: None,
+                memo: Some(memo),
+                is_final: false,
+            },
+            PayloadV1::Final(height, hash, memo) => Self {
+                block_height: height,
+                block_hash: hash,
+                prev_tx_chain: None,
+                memo,
+                is_final: true,
             },
         }
     }
@@ -241,7 +358,7 @@ mod tests {
     use bitcoin::blockdata::script::Script;
     use hex;
 
-    use crate::btc::Sha256d;
+    use crate::btc::{MemoKey, Sha256d};
 
     use super::{Payload, PayloadBuilder};
 
@@ -326,6 +443,89 @@ mod tests {
         assert_eq!(payload.prev_tx_chain, Some(prev_txid));
     }
 
+    #[test]
+    fn test_payload_memo_roundtrip() {
+        let key = MemoKey::random();
+        let encrypted_memo = key.encrypt(b"0042").unwrap();
+
+        let block_hash = hash(&[]);
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .memo(Some(encrypted_memo.clone()))
+            .into_script();
+        // The whole script should fit into the standard `OP_RETURN` output.
+        assert_eq!(payload_script.len(), 83);
+
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_hash, block_hash);
+        assert_eq!(payload.block_height, Height(1234));
+        assert_eq!(payload.prev_tx_chain, None);
+        assert_eq!(payload.memo, Some(encrypted_memo));
+        assert_eq!(payload.decrypt_memo(&key), Some(Ok(b"0042".to_vec())));
+
+        // Memo is ignored in the recover payload.
+        let prev_txid = Sha256d::from_slice(block_hash.as_ref()).unwrap();
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .prev_tx_chain(Some(prev_txid))
+            .memo(payload.memo)
+            .into_script();
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.prev_tx_chain, Some(prev_txid));
+        assert_eq!(payload.memo, None);
+    }
+
+    #[test]
+    fn test_payload_final_roundtrip() {
+        let block_hash = hash(&[]);
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .tombstone(true)
+            .into_script();
+        assert_eq!(
+            payload_script.to_hex(),
+            "6a3045584f4e554d0103d204000000000000e3b0c44298fc1c149afbf4c8996fb92427ae41e4649\
+             b934ca495991b7852b855"
+        );
+
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_hash, block_hash);
+        assert_eq!(payload.block_height, Height(1234));
+        assert_eq!(payload.memo, None);
+        assert!(payload.is_final);
+
+        // Memo is kept in the final payload.
+        let key = MemoKey::random();
+        let encrypted_memo = key.encrypt(b"0042").unwrap();
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .memo(Some(encrypted_memo.clone()))
+            .tombstone(true)
+            .into_script();
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.memo, Some(encrypted_memo));
+        assert!(payload.is_final);
+    }
+
+    #[test]
+    fn test_payload_foreign_tag_deserialize() {
+        // Regular payload with the `FOREIG` prefix instead of `EXONUM`.
+        let payload_script = Script::from_hex(
+            "6a30464f524549470100d204000000000000e3b0c44298fc1c14\
+             9afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        );
+        assert_eq!(Payload::from_script(&payload_script), None);
+
+        let (tag, payload) = Payload::from_tagged_script(&payload_script).unwrap();
+        assert_eq!(tag, b"FOREIG");
+        assert_eq!(payload.block_hash, hash(&[]));
+        assert_eq!(payload.block_height, Height(1234));
+    }
+
     #[test]
     fn test_payload_incorrect_deserialize() {
         // Payload from old anchoring transaction
@@ -0,0 +1,121 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bitcoin::{hash_types::Txid, util::merkleblock::MerkleBlock};
+use derive_more::{From, Into};
+use thiserror::Error;
+
+use super::Sha256d;
+
+/// Proof of the transaction inclusion in the Bitcoin block, i.e. the header of the block
+/// together with the Merkle branch of the transaction, in the format returned by
+/// the `gettxoutproof` call of the Bitcoin Core.
+///
+/// The proof allows to check that the anchoring transaction has been committed to the
+/// Bitcoin blockchain without trusting the Exonum or the Bitcoin nodes, provided that
+/// the block header belongs to the main chain.
+#[derive(Debug, Clone, From, Into, PartialEq)]
+pub struct SpvProof(pub MerkleBlock);
+
+impl_wrapper_for_bitcoin_type! { SpvProof }
+
+/// Errors of the SPV proof verification.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SpvProofError {
+    /// The Merkle branch is malformed.
+    #[error("Malformed Merkle branch: {0}")]
+    MalformedBranch(String),
+    /// The Merkle branch doesn't lead to the Merkle root of the block header.
+    #[error("Merkle root of the branch doesn't match the block header")]
+    MerkleRootMismatch,
+    /// The transaction is not proven by the Merkle branch.
+    #[error("Transaction {0} is not proven by the Merkle branch")]
+    TransactionNotProven(Sha256d),
+    /// The hash of the block header doesn't meet the target of the header.
+    #[error("Block header has insufficient proof of work")]
+    InsufficientWork,
+}
+
+impl SpvProof {
+    /// Returns the hash of the Bitcoin block containing the transaction.
+    pub fn block_hash(&self) -> Sha256d {
+        Sha256d(self.0.header.block_hash().as_hash())
+    }
+
+    /// Verifies that the proof proves the inclusion of the transaction with the given
+    /// identifier, and returns the hash of the block containing the transaction.
+    pub fn verify(&self, txid: Sha256d) -> Result<Sha256d, SpvProofError> {
+        let mut matches = Vec::new();
+        let mut indexes = Vec::new();
+        let merkle_root = self
+            .0
+            .txn
+            .extract_matches(&mut matches, &mut indexes)
+            .map_err(|e| SpvProofError::MalformedBranch(format!("{:?}", e)))?;
+        if merkle_root != self.0.header.merkle_root {
+            return Err(SpvProofError::MerkleRootMismatch);
+        }
+        if !matches.contains(&Txid::from(txid)) {
+            return Err(SpvProofError::TransactionNotProven(txid));
+        }
+        self.0
+            .header
+            .validate_pow(&self.0.header.target())
+            .map_err(|_| SpvProofError::InsufficientWork)?;
+        Ok(self.block_hash())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        blockdata::constants::genesis_block, network::constants::Network,
+        util::merkleblock::MerkleBlock,
+    };
+    use bitcoin_hashes::Hash;
+
+    use std::collections::HashSet;
+
+    use super::{SpvProof, SpvProofError};
+    use crate::btc::Sha256d;
+
+    #[test]
+    fn spv_proof_verify() {
+        let block = genesis_block(Network::Bitcoin);
+        let coinbase_txid = block.txdata[0].txid();
+        let match_txids = [coinbase_txid].iter().cloned().collect::<HashSet<_>>();
+        let proof = SpvProof(MerkleBlock::from_block(&block, &match_txids));
+
+        let txid = Sha256d(coinbase_txid.as_hash());
+        let block_hash = Sha256d(block.block_hash().as_hash());
+        assert_eq!(proof.verify(txid), Ok(block_hash));
+
+        // The proof survives the round trip through the hex representation.
+        let proof = proof.to_string().parse::<SpvProof>().unwrap();
+        assert_eq!(proof.verify(txid), Ok(block_hash));
+
+        let other_txid = Sha256d::new([1; 32]);
+        assert_eq!(
+            proof.verify(other_txid),
+            Err(SpvProofError::TransactionNotProven(other_txid))
+        );
+
+        let mut tampered = proof.clone();
+        tampered.0.header.merkle_root = Hash::from_inner([2; 32]);
+        assert_eq!(
+            tampered.verify(txid),
+            Err(SpvProofError::MerkleRootMismatch)
+        );
+    }
+}
@@ -9,15 +9,21 @@
 
 use exonum::{crypto::Hash, helpers::Height};
 
-use bitcoin::blockdata::{
-    script::Script,
-    transaction::{self, OutPoint, TxIn, TxOut},
+use bitcoin::{
+    blockdata::{
+        script::Script,
+        transaction::{self, OutPoint, TxIn, TxOut},
+    },
+    consensus::Encodable,
+    network::constants::Network,
 };
+use bitcoin_hashes::{sha256d, Hash as BitcoinHash};
 use btc_transaction_utils::multisig::RedeemScript;
 use derive_more::{From, Into};
+use serde_derive::{Deserialize, Serialize};
 use thiserror::Error;
 
-use super::{payload::PayloadBuilder, Payload, Sha256d};
+use super::{payload::PayloadBuilder, Address, Payload, RelayFeePolicy, Sha256d, SigHashType};
 
 /// Bitcoin transaction wrapper.
 #[derive(Debug, Clone, From, Into, PartialEq)]
@@ -25,6 +31,19 @@ pub struct Transaction(pub transaction::Transaction);
 
 impl_wrapper_for_bitcoin_type! { Transaction }
 
+/// Anchor found in an arbitrary Bitcoin transaction, which may belong to any Exonum chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForeignAnchor {
+    /// ASCII-encoded prefix of the anchoring payload.
+    pub tag: String,
+    /// Anchored block height.
+    pub block_height: Height,
+    /// Anchored block hash.
+    pub block_hash: Hash,
+    /// Address of the anchoring output if it has a standard form.
+    pub address: Option<Address>,
+}
+
 impl AsRef<transaction::Transaction> for Transaction {
     fn as_ref(&self) -> &transaction::Transaction {
         &self.0
@@ -70,8 +89,139 @@ impl Transaction {
     pub fn unspent_value(&self) -> Option<u64> {
         self.0.output.get(0).map(|out| out.value)
     }
+
+    /// Return the fee paid by the transaction, given the outputs spent by its inputs
+    /// in the same order as the inputs.
+    ///
+    /// Returns `None` if the number of the given outputs does not match the number
+    /// of inputs, or if the total output value exceeds the total input value.
+    pub fn fee(&self, prev_outputs: &[TxOut]) -> Option<u64> {
+        if prev_outputs.len() != self.0.input.len() {
+            return None;
+        }
+
+        let input_value = prev_outputs.iter().map(|out| out.value).sum::<u64>();
+        let output_value = self.0.output.iter().map(|out| out.value).sum::<u64>();
+        input_value.checked_sub(output_value)
+    }
+
+    /// Return the virtual size of the transaction in vbytes as defined in BIP-141.
+    pub fn vsize(&self) -> usize {
+        (self.0.get_weight() + 3) / 4
+    }
+
+    /// Computes the BIP-143 signature hash of the input with the given index, which spends
+    /// the output with the given script code and value.
+    ///
+    /// # Panics
+    ///
+    /// If the transaction does not have the input with the given index.
+    pub fn signature_hash(
+        &self,
+        index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_type: SigHashType,
+    ) -> sha256d::Hash {
+        fn encode<T: Encodable>(value: &T, buf: &mut Vec<u8>) {
+            value
+                .consensus_encode(buf)
+                .expect("Writing to a vector never fails");
+        }
+
+        let tx = &self.0;
+        let txin = &tx.input[index];
+
+        let (hash_prevouts, hash_sequence) = match sighash_type {
+            SigHashType::All => {
+                let (mut prevouts, mut sequences) = (Vec::new(), Vec::new());
+                for input in &tx.input {
+                    encode(&input.previous_output, &mut prevouts);
+                    encode(&input.sequence, &mut sequences);
+                }
+                (
+                    sha256d::Hash::hash(&prevouts),
+                    sha256d::Hash::hash(&sequences),
+                )
+            }
+            // Other inputs are not committed by the signature.
+            SigHashType::AllPlusAnyoneCanPay => (
+                sha256d::Hash::from_inner([0; 32]),
+                sha256d::Hash::from_inner([0; 32]),
+            ),
+        };
+        let mut outputs = Vec::new();
+        for output in &tx.output {
+            encode(output, &mut outputs);
+        }
+
+        let mut preimage = Vec::new();
+        encode(&tx.version, &mut preimage);
+        preimage.extend_from_slice(&hash_prevouts[..]);
+        preimage.extend_from_slice(&hash_sequence[..]);
+        encode(&txin.previous_output, &mut preimage);
+        encode(script_code, &mut preimage);
+        encode(&value, &mut preimage);
+        encode(&txin.sequence, &mut preimage);
+        preimage.extend_from_slice(&sha256d::Hash::hash(&outputs)[..]);
+        encode(&tx.lock_time, &mut preimage);
+        encode(&sighash_type.as_u32(), &mut preimage);
+        sha256d::Hash::hash(&preimage)
+    }
+
+    /// Find output number which pays to the given address.
+    pub fn output_to(&self, address: &Address) -> Option<(usize, &TxOut)> {
+        self.find_out(&address.0.script_pubkey())
+    }
+
+    /// Check that the transaction contains a valid anchoring payload.
+    pub fn is_anchor(&self) -> bool {
+        self.anchoring_payload().is_some()
+    }
+
+    /// Try to interpret an arbitrary transaction as the anchoring transaction of
+    /// some Exonum chain.
+    ///
+    /// Unlike [`anchoring_payload`](#method.anchoring_payload), this method does not
+    /// assume the layout of outputs used by this service and looks for the first output
+    /// with a suitable payload. If `expected_tag` is specified, only payloads with
+    /// the given prefix are accepted. This method never panics on malformed input.
+    pub fn try_parse_foreign_anchor(
+        &self,
+        network: Network,
+        expected_tag: Option<&[u8]>,
+    ) -> Option<ForeignAnchor> {
+        let (index, tag, payload) = self.0.output.iter().enumerate().find_map(|(index, out)| {
+            let (tag, payload) = Payload::from_tagged_script(&out.script_pubkey)?;
+            match expected_tag {
+                Some(expected_tag) if expected_tag != tag => None,
+                _ => Some((index, tag, payload)),
+            }
+        })?;
+        let tag = std::str::from_utf8(tag).ok()?.to_owned();
+        // By convention, the anchoring output precedes the payload one.
+        let address = index
+            .checked_sub(1)
+            .and_then(|index| self.0.output.get(index))
+            .and_then(|out| bitcoin::Address::from_script(&out.script_pubkey, network))
+            .map(Address);
+
+        Some(ForeignAnchor {
+            tag,
+            block_height: payload.block_height,
+            block_hash: payload.block_hash,
+            address,
+        })
+    }
 }
 
+/// Maximal length of the DER-encoded signature with the sighash type byte.
+const MAX_SIGNATURE_LEN: u64 = 73;
+/// Estimated weight of the witness spending the P2WPKH output of the fee wallet.
+const P2WPKH_WITNESS_WEIGHT: u64 = 1 + (1 + MAX_SIGNATURE_LEN) + (1 + 33);
+/// Weight of the segwit marker and flag bytes.
+const SEGWIT_FLAG_WEIGHT: u64 = 2;
+
 /// Builder for the anchoring transactions.
 #[derive(Debug)]
 pub struct BtcAnchoringTransactionBuilder {
@@ -80,8 +230,17 @@ pub struct BtcAnchoringTransactionBuilder {
     prev_tx: Option<Transaction>,
     recovery_tx: Option<Sha256d>,
     additional_funds: Vec<(usize, Transaction)>,
+    fee_payer: Option<Script>,
+    fee_payer_funds: Vec<(usize, Transaction)>,
     fee: Option<u64>,
+    /// Virtual size and fee of the unconfirmed parent transaction paid by this one.
+    parent: Option<(u64, u64)>,
+    relay_fee_policy: Option<RelayFeePolicy>,
+    /// Estimated weight of the witness of the anchoring wallet input.
+    input_witness_weight: u64,
     payload: Option<(Height, Hash)>,
+    memo: Option<Vec<u8>>,
+    tombstone: bool,
 }
 
 /// Anchoring transaction builder errors.
@@ -107,6 +266,12 @@ pub enum BuilderError {
     /// Funding transaction doesn't contains outputs to the anchoring address.
     #[error("Funding transaction doesn't contains outputs to the anchoring address.")]
     UnsuitableFundingTx,
+    /// Fee payer transaction doesn't contains outputs to the fee wallet address.
+    #[error("Fee payer transaction doesn't contains outputs to the fee wallet address.")]
+    UnsuitableFeePayerTx,
+    /// Funding transaction has been already spent by a previous anchoring transaction.
+    #[error("Funding transaction has been already spent by a previous anchoring transaction.")]
+    SpentFundingTx,
 }
 
 impl BtcAnchoringTransactionBuilder {
@@ -118,8 +283,15 @@ impl BtcAnchoringTransactionBuilder {
             prev_tx: None,
             recovery_tx: None,
             additional_funds: Vec::default(),
+            fee_payer: None,
+            fee_payer_funds: Vec::default(),
             fee: None,
+            parent: None,
+            relay_fee_policy: None,
+            input_witness_weight: Self::multisig_witness_weight(redeem_script),
             payload: None,
+            memo: None,
+            tombstone: false,
         }
     }
 
@@ -156,79 +328,138 @@ impl BtcAnchoringTransactionBuilder {
         Ok(())
     }
 
+    /// Sets the output script of the fee wallet, which pays the transaction fee instead of
+    /// the anchoring wallet. The rest of the fee wallet funds is returned to it by
+    /// the third output of the anchoring transaction.
+    pub fn fee_payer(&mut self, script: Script) {
+        self.fee_payer = Some(script);
+    }
+
+    /// Adds a transaction which corresponding unspent output to the fee wallet
+    /// will use as an input to pay the fee of the following anchoring transaction.
+    pub fn fee_payer_funds(&mut self, tx: Transaction) -> Result<(), BuilderError> {
+        let script_pubkey = self
+            .fee_payer
+            .as_ref()
+            .ok_or_else(|| BuilderError::UnsuitableFeePayerTx)?;
+        let out = tx
+            .find_out(script_pubkey)
+            .ok_or_else(|| BuilderError::UnsuitableFeePayerTx)?
+            .0;
+        self.fee_payer_funds.push((out, tx));
+        Ok(())
+    }
+
     /// Sets the fee per byte value.
     pub fn fee(&mut self, fee: u64) {
         self.fee = Some(fee);
     }
 
+    /// Makes the anchoring transaction pay for its unconfirmed parent (CPFP). The fee per byte
+    /// value is applied to the total size of the parent with the given virtual size and
+    /// the anchoring transaction, and the fee already paid by the parent is deducted.
+    pub fn pay_for_parent(&mut self, parent_vsize: u64, parent_fee: u64) {
+        self.parent = Some((parent_vsize, parent_fee));
+    }
+
+    /// Sets the relay policy of the Bitcoin node. The total fee of the anchoring
+    /// transaction is increased up to the minimal fee accepted by the node, if the fee
+    /// computed from the fee per byte value is not enough.
+    pub fn relay_fee_policy(&mut self, policy: RelayFeePolicy) {
+        self.relay_fee_policy = Some(policy);
+    }
+
     /// Sets the anchoring transaction payload.
     pub fn payload(&mut self, block_height: Height, block_hash: Hash) {
         self.payload = Some((block_height, block_hash));
     }
 
+    /// Sets the encrypted memo of the anchoring transaction payload.
+    pub fn memo(&mut self, memo: Vec<u8>) {
+        self.memo = Some(memo);
+    }
+
+    /// Marks the anchoring transaction payload as the tombstone of the anchoring chain.
+    pub fn tombstone(&mut self) {
+        self.tombstone = true;
+    }
+
     /// Finalizes the anchoring transaction and returns
     /// it with the list of input transactions.
     pub fn create(mut self) -> Result<(Transaction, Vec<Transaction>), BuilderError> {
         // Creates transaction inputs.
-        let (input, input_transactions, balance) = {
-            let mut input = Vec::new();
-            let mut input_transactions = Vec::new();
-            let mut balance = 0;
-
-            let tx_iter = self
-                .prev_tx
-                .into_iter()
-                .map(|tx| (0, tx))
-                .chain(self.additional_funds.into_iter());
-            for (out_index, tx) in tx_iter {
-                let txin = TxIn {
-                    previous_output: OutPoint {
-                        txid: tx.0.txid(),
-                        vout: out_index as u32,
-                    },
-                    script_sig: Script::default(),
-                    sequence: 0xFFFF_FFFF,
-                    witness: Vec::default(),
-                };
-                balance += tx.0.output[out_index].value;
-                input.push(txin);
-                input_transactions.push(tx);
-            }
-            (input, input_transactions, balance)
-        };
+        let mut input = Vec::new();
+        let mut input_transactions = Vec::new();
+        let mut balance = 0;
+
+        let tx_iter = self
+            .prev_tx
+            .into_iter()
+            .map(|tx| (0, tx))
+            .chain(self.additional_funds.into_iter());
+        for (out_index, tx) in tx_iter {
+            balance += tx.0.output[out_index].value;
+            input.push(Self::input_for(&tx, out_index));
+            input_transactions.push(tx);
+        }
 
         // Check that at least one input exists.
         if input_transactions.is_empty() {
             return Err(BuilderError::NoInputs);
         }
 
+        let anchoring_inputs_count = input.len() as u64;
+        // Inputs from the fee wallet are always placed after the anchoring wallet ones.
+        let mut fee_payer_balance = 0;
+        for (out_index, tx) in self.fee_payer_funds {
+            fee_payer_balance += tx.0.output[out_index].value;
+            input.push(Self::input_for(&tx, out_index));
+            input_transactions.push(tx);
+        }
+
+        let fee_payer_inputs_count = input.len() as u64 - anchoring_inputs_count;
+
         // Compute payload script.
         let (block_height, block_hash) = self.payload.take().expect("Payload isn't set.");
         let payload_script = PayloadBuilder::new()
             .block_hash(block_hash)
             .block_height(block_height)
             .prev_tx_chain(self.recovery_tx)
+            .memo(self.memo)
+            .tombstone(self.tombstone)
             .into_script();
         let output = match self.transit_to {
             Some(script) => script,
             _ => self.script_pubkey,
         };
 
+        let mut output = vec![
+            TxOut {
+                value: balance,
+                script_pubkey: output,
+            },
+            TxOut {
+                value: 0,
+                script_pubkey: payload_script,
+            },
+        ];
+        // Return the rest of the fee wallet funds to it.
+        let fee_payer_index = if let Some(script_pubkey) = self.fee_payer {
+            output.push(TxOut {
+                value: fee_payer_balance,
+                script_pubkey,
+            });
+            2
+        } else {
+            0
+        };
+
         // Create unsigned transaction.
         let mut transaction = Transaction::from(transaction::Transaction {
             version: 2,
             lock_time: 0,
             input,
-            output: vec![
-                TxOut {
-                    value: balance,
-                    script_pubkey: output,
-                },
-                TxOut {
-                    value: 0,
-                    script_pubkey: payload_script,
-                },
-            ],
+            output,
         });
 
         // Compute a total fee value.
@@ -236,14 +467,57 @@ impl BtcAnchoringTransactionBuilder {
             let bytes = ::bitcoin::consensus::serialize(&transaction.0);
             bytes.len() as u64
         };
-        let total_fee = self.fee.expect("Fee per byte isn't set.") * size_in_bytes;
-        if total_fee > balance {
-            return Err(BuilderError::InsufficientFunds { total_fee, balance });
+        let fee = self.fee.expect("Fee per byte isn't set.");
+        let mut total_fee = fee * size_in_bytes;
+        if let Some((parent_vsize, parent_fee)) = self.parent {
+            // The fee of the package should match the fee per byte value, thus the lack
+            // of the parent fee is covered by this transaction.
+            let package_fee = fee * (size_in_bytes + parent_vsize);
+            total_fee = std::cmp::max(total_fee, package_fee.saturating_sub(parent_fee));
+        }
+        if let Some(policy) = self.relay_fee_policy {
+            // The fee per byte is applied to the unsigned transaction, while the relay policy
+            // limits the fee of the signed one.
+            let witness_weight = self.input_witness_weight * anchoring_inputs_count
+                + P2WPKH_WITNESS_WEIGHT * fee_payer_inputs_count
+                + SEGWIT_FLAG_WEIGHT;
+            let vsize = size_in_bytes + (witness_weight + 3) / 4;
+            total_fee = std::cmp::max(total_fee, policy.min_fee(vsize));
         }
         // Set the corresponding fee.
-        transaction.0.output[0].value -= total_fee;
+        let fee_output = &mut transaction.0.output[fee_payer_index];
+        if total_fee > fee_output.value {
+            return Err(BuilderError::InsufficientFunds {
+                total_fee,
+                balance: fee_output.value,
+            });
+        }
+        fee_output.value -= total_fee;
         Ok((transaction, input_transactions))
     }
+
+    /// Estimates the weight of the witness spending the P2WSH output with the given
+    /// multisig redeem script: the items count, the empty item, the signatures and
+    /// the redeem script itself.
+    fn multisig_witness_weight(redeem_script: &RedeemScript) -> u64 {
+        let quorum = redeem_script.content().quorum as u64;
+        let script_len = redeem_script.as_ref().len() as u64;
+        // Length of the redeem script is encoded as a compact size.
+        let script_len_size = if script_len < 0xFD { 1 } else { 3 };
+        1 + 1 + quorum * (1 + MAX_SIGNATURE_LEN) + script_len_size + script_len
+    }
+
+    fn input_for(tx: &Transaction, out_index: usize) -> TxIn {
+        TxIn {
+            previous_output: OutPoint {
+                txid: tx.0.txid(),
+                vout: out_index as u32,
+            },
+            script_sig: Script::default(),
+            sequence: 0xFFFF_FFFF,
+            witness: Vec::default(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -263,13 +537,13 @@ mod tests {
     };
 
     use bitcoin_hashes::{sha256d::Hash as Sha256dHash, Hash as BitcoinHash};
-    use btc_transaction_utils::multisig::RedeemScriptBuilder;
+    use btc_transaction_utils::{multisig::RedeemScriptBuilder, p2wpk, TxInRef};
     use hex::FromHex;
     use proptest::proptest;
 
     use std::borrow::Cow;
 
-    use crate::btc::PublicKey;
+    use crate::btc::{self, PublicKey, SigHashType, SignatureError};
 
     use super::{BtcAnchoringTransactionBuilder, BuilderError, Transaction};
 
@@ -322,6 +596,21 @@ mod tests {
         .unwrap();
         let (script_pubkey, payload) = tx.anchoring_metadata().unwrap();
 
+        let anchor = tx
+            .try_parse_foreign_anchor(Network::Testnet, Some(&b"EXONUM"[..]))
+            .unwrap();
+        assert_eq!(anchor.tag, "EXONUM");
+        assert_eq!(anchor.block_height, payload.block_height);
+        assert_eq!(anchor.block_hash, payload.block_hash);
+        assert_eq!(
+            anchor.address.unwrap().0,
+            Address::p2wsh(script_pubkey, Network::Testnet)
+        );
+        assert_eq!(
+            tx.try_parse_foreign_anchor(Network::Testnet, Some(&b"FOREIG"[..])),
+            None
+        );
+
         assert_eq!(payload.block_height, Height(21000));
         assert_eq!(
             payload.block_hash,
@@ -335,6 +624,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_transaction_inspection_helpers() {
+        let funding_tx: Transaction = Transaction::from_hex(
+            "02000000000101b651818fe3855d0d5d74de1cf72b56503c16f808519440e842b6\
+             dc2dd570c4930100000000feffffff02deaa7b0000000000160014923904449829\
+             cd865cdfb72abdba0806ce9e48911027000000000000220020e9bb049fdff8f8d3\
+             b33b7335978b1dbb268833a32a69906f9e500e4103151bef02483045022100ddc7\
+             eb1193529a8d0e48cf24f536d5fbb5de3b67d2f56c98190ea8585d58a156022075\
+             e33981f1a7d78ce2915402d4b9b38b8d5311e0aef2e3ccf9284d2ce602968d0121\
+             021d0478acd223fb9b2ad7485f06f12914a1b7effc78390a08c50bfe53b3b24815\
+             062c1400",
+        )
+        .unwrap();
+        assert!(!funding_tx.is_anchor());
+        assert!(funding_tx.vsize() < funding_tx.clone().into_bytes().len());
+
+        let keys = vec![
+            "038b782f94d19f34536a96e12e0bad99e6f82c838fa16a4234572f5f132d95ba29",
+            "020ae2216f42575c4196864eda0252c75c61273065f691b32be9a99cb2a3c9b4d1",
+            "02536d5e1464b961562da57207e4a46edb7dade9b92aa29712ca8309c8aba5be5b",
+        ]
+        .iter()
+        .map(|h| PublicKey::from_hex(h).unwrap().0)
+        .collect::<Vec<_>>();
+        let redeem_script = RedeemScriptBuilder::with_public_keys(keys)
+            .to_script()
+            .unwrap();
+        let address: btc::Address =
+            btc_transaction_utils::p2wsh::address(&redeem_script, Network::Testnet).into();
+        let (out_index, out) = funding_tx.output_to(&address).unwrap();
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(1);
+        builder.payload(Height::zero(), funding_tx.object_hash());
+        let (tx, _) = builder.create().unwrap();
+
+        assert!(tx.is_anchor());
+        assert_eq!(out_index, 1);
+        assert_eq!(
+            tx.fee(&[out.clone()]),
+            Some(out.value - tx.unspent_value().unwrap())
+        );
+        assert_eq!(tx.fee(&[]), None);
+    }
+
     proptest! {
         #[test]
         fn test_transaction_exonum_field(
@@ -532,6 +867,111 @@ mod tests {
         assert_eq!(out_1.value, 0);
     }
 
+    #[test]
+    fn test_anchoring_transaction_builder_fee_payer() {
+        let funding_tx: Transaction = Transaction::from_hex(
+            "02000000000101b651818fe3855d0d5d74de1cf72b56503c16f808519440e842b6\
+             dc2dd570c4930100000000feffffff02deaa7b0000000000160014923904449829\
+             cd865cdfb72abdba0806ce9e48911027000000000000220020e9bb049fdff8f8d3\
+             b33b7335978b1dbb268833a32a69906f9e500e4103151bef02483045022100ddc7\
+             eb1193529a8d0e48cf24f536d5fbb5de3b67d2f56c98190ea8585d58a156022075\
+             e33981f1a7d78ce2915402d4b9b38b8d5311e0aef2e3ccf9284d2ce602968d0121\
+             021d0478acd223fb9b2ad7485f06f12914a1b7effc78390a08c50bfe53b3b24815\
+             062c1400",
+        )
+        .unwrap();
+
+        let keys = vec![
+            "038b782f94d19f34536a96e12e0bad99e6f82c838fa16a4234572f5f132d95ba29",
+            "020ae2216f42575c4196864eda0252c75c61273065f691b32be9a99cb2a3c9b4d1",
+            "02536d5e1464b961562da57207e4a46edb7dade9b92aa29712ca8309c8aba5be5b",
+        ]
+        .iter()
+        .map(|h| PublicKey::from_hex(h).unwrap().0)
+        .collect::<Vec<_>>();
+
+        let redeem_script = RedeemScriptBuilder::with_public_keys(keys)
+            .to_script()
+            .unwrap();
+
+        // The first output of the funding transaction is P2WPKH one.
+        let fee_payer_out = funding_tx.0.output[0].clone();
+        let funding_out = funding_tx.0.output[1].clone();
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        assert_eq!(
+            builder.fee_payer_funds(funding_tx.clone()).unwrap_err(),
+            BuilderError::UnsuitableFeePayerTx
+        );
+        builder.fee_payer(fee_payer_out.script_pubkey.clone());
+        builder.fee_payer_funds(funding_tx.clone()).unwrap();
+        builder.fee(1);
+        builder.payload(Height::zero(), funding_tx.object_hash());
+        let (tx, inputs) = builder.create().unwrap();
+
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(tx.0.input[1].previous_output.vout, 0);
+
+        let outputs = &tx.0.output;
+        assert_eq!(outputs.len(), 3);
+        // The anchoring wallet does not pay the fee.
+        assert_eq!(outputs[0].value, funding_out.value);
+        assert_eq!(outputs[1].value, 0);
+        assert_eq!(outputs[2].script_pubkey, fee_payer_out.script_pubkey);
+        assert_eq!(
+            tx.fee(&[funding_out, fee_payer_out.clone()]).unwrap(),
+            fee_payer_out.value - outputs[2].value
+        );
+    }
+
+    #[test]
+    fn test_anchoring_transaction_builder_pay_for_parent() {
+        let funding_tx: Transaction = Transaction::from_hex(
+            "02000000000101b651818fe3855d0d5d74de1cf72b56503c16f808519440e842b6\
+             dc2dd570c4930100000000feffffff02deaa7b0000000000160014923904449829\
+             cd865cdfb72abdba0806ce9e48911027000000000000220020e9bb049fdff8f8d3\
+             b33b7335978b1dbb268833a32a69906f9e500e4103151bef02483045022100ddc7\
+             eb1193529a8d0e48cf24f536d5fbb5de3b67d2f56c98190ea8585d58a156022075\
+             e33981f1a7d78ce2915402d4b9b38b8d5311e0aef2e3ccf9284d2ce602968d0121\
+             021d0478acd223fb9b2ad7485f06f12914a1b7effc78390a08c50bfe53b3b24815\
+             062c1400",
+        )
+        .unwrap();
+
+        let keys = vec![
+            "038b782f94d19f34536a96e12e0bad99e6f82c838fa16a4234572f5f132d95ba29",
+            "020ae2216f42575c4196864eda0252c75c61273065f691b32be9a99cb2a3c9b4d1",
+            "02536d5e1464b961562da57207e4a46edb7dade9b92aa29712ca8309c8aba5be5b",
+        ]
+        .iter()
+        .map(|h| PublicKey::from_hex(h).unwrap().0)
+        .collect::<Vec<_>>();
+
+        let redeem_script = RedeemScriptBuilder::with_public_keys(keys)
+            .to_script()
+            .unwrap();
+        let funding_out = funding_tx.0.output[1].clone();
+        let create_tx = |parent: Option<(u64, u64)>| {
+            let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+            builder.additional_funds(funding_tx.clone()).unwrap();
+            builder.fee(2);
+            if let Some((parent_vsize, parent_fee)) = parent {
+                builder.pay_for_parent(parent_vsize, parent_fee);
+            }
+            builder.payload(Height::zero(), funding_tx.object_hash());
+            builder.create().unwrap().0
+        };
+
+        let own_fee = create_tx(None).fee(&[funding_out.clone()]).unwrap();
+        // The child covers the lack of the parent fee.
+        let tx = create_tx(Some((200, 100)));
+        assert_eq!(tx.fee(&[funding_out.clone()]).unwrap(), own_fee + 300);
+        // The parent which has paid enough doesn't reduce the fee of the child.
+        let tx = create_tx(Some((200, 1_000)));
+        assert_eq!(tx.fee(&[funding_out]).unwrap(), own_fee);
+    }
+
     #[test]
     fn test_anchoring_transaction_builder_incorrect_prev_tx() {
         let funding_tx: Transaction = Transaction::from_hex(
@@ -614,4 +1054,88 @@ mod tests {
             BuilderError::UnsuitableFundingTx
         );
     }
+
+    #[test]
+    fn test_signature_hash_types() {
+        let network = Network::Testnet;
+        let (public_key, private_key) = btc::gen_keypair(network);
+
+        let prev_tx: Transaction = transaction::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: Vec::new(),
+            output: vec![TxOut {
+                value: 100_000,
+                script_pubkey: Address::p2wpkh(&public_key.0, network).script_pubkey(),
+            }],
+        }
+        .into();
+        let txin = |vout| TxIn {
+            previous_output: OutPoint {
+                txid: prev_tx.0.txid(),
+                vout,
+            },
+            script_sig: Script::default(),
+            sequence: 0xFFFF_FFFF,
+            witness: Vec::default(),
+        };
+        let mut tx: Transaction = transaction::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![txin(0)],
+            output: vec![TxOut {
+                value: 90_000,
+                script_pubkey: Script::default(),
+            }],
+        }
+        .into();
+
+        // Signature made by the `btc_transaction_utils` matches the computed signature hash.
+        let script_code = Address::p2pkh(&public_key.0, network).script_pubkey();
+        let signature: btc::InputSignature = p2wpk::InputSigner::new(public_key.0, network)
+            .sign_input(TxInRef::new(&tx.0, 0), &prev_tx.0, &private_key.0.key)
+            .unwrap()
+            .into();
+        let sighash = tx.signature_hash(0, &script_code, 100_000, SigHashType::All);
+        signature.verify(&sighash, &public_key).unwrap();
+        signature.check_canonical(SigHashType::All).unwrap();
+
+        let acp_sighash =
+            tx.signature_hash(0, &script_code, 100_000, SigHashType::AllPlusAnyoneCanPay);
+        let acp_signature =
+            btc::InputSignature::sign(&acp_sighash, SigHashType::AllPlusAnyoneCanPay, &private_key);
+        acp_signature
+            .check_canonical(SigHashType::AllPlusAnyoneCanPay)
+            .unwrap();
+        assert_eq!(
+            acp_signature.check_canonical(SigHashType::All),
+            Err(SignatureError::UnexpectedSigHashType(0x81))
+        );
+        let verify = |tx: &Transaction, signature: &btc::InputSignature, sighash_type| {
+            btc::verify_p2wpk_input(
+                tx,
+                0,
+                &prev_tx,
+                &public_key,
+                network,
+                signature,
+                sighash_type,
+            )
+        };
+        verify(&tx, &acp_signature, SigHashType::AllPlusAnyoneCanPay).unwrap();
+
+        // Only the `SIGHASH_ANYONECANPAY` signature remains valid after adding an input.
+        tx.0.input.push(txin(1));
+        verify(&tx, &acp_signature, SigHashType::AllPlusAnyoneCanPay).unwrap();
+        verify(&tx, &signature, SigHashType::All).unwrap_err();
+
+        assert_eq!(
+            SigHashType::from_u32(0x03),
+            Err(SignatureError::UnsafeSigHashType(0x03))
+        );
+        assert_eq!(
+            SigHashType::from_u32(0x81),
+            Ok(SigHashType::AllPlusAnyoneCanPay)
+        );
+    }
 }
@@ -14,18 +14,22 @@
 
 //! BTC anchoring configuration data types.
 
-pub use crate::proto::{AnchoringKeys, Config};
+pub use crate::proto::{
+    AnchoringKeys, CheckpointFilter, Config, EmergencyBrake, FeeBudget, FeeEstimation, FinalAnchor,
+    KeyDerivation, RecoveryExport,
+};
 
 use anyhow::ensure;
 use bitcoin::network::constants::Network;
 use btc_transaction_utils::{
     multisig::{RedeemScript, RedeemScriptBuilder, RedeemScriptError},
-    p2wsh,
+    p2wpk, p2wsh,
 };
 use exonum::{
     crypto::PublicKey,
     helpers::{Height, ValidateInput},
 };
+use serde_derive::Deserialize;
 
 use crate::btc::{self, Address};
 
@@ -36,20 +40,59 @@ impl Default for Config {
             anchoring_keys: vec![],
             anchoring_interval: 5_000,
             transaction_fee: 10,
+            fee_payer_key: None,
+            encrypted_memo: None,
+            finality_confirmations: Self::DEFAULT_FINALITY_CONFIRMATIONS,
+            final_anchor: None,
+            compact_storage: false,
+            signature_timeout: Self::DEFAULT_SIGNATURE_TIMEOUT,
+            min_relay_fee: btc::DEFAULT_RELAY_FEE,
+            max_funding_tx_weight: Self::DEFAULT_MAX_FUNDING_TX_WEIGHT,
+            max_funding_tx_outputs: Self::DEFAULT_MAX_FUNDING_TX_OUTPUTS,
+            recovery_export: None,
+            emergency_brake: None,
+            fee_estimation: None,
+            max_cpfp_fee: 0,
+            checkpoint_filter: None,
+            quorum: None,
+            fee_budget: None,
         }
     }
 }
 
 impl Config {
     /// Current limit on the number of keys in a redeem script on the Bitcoin network.
-    const MAX_NODES_COUNT: usize = 20;
+    pub const MAX_NODES_COUNT: usize = 20;
     /// Minimal fee in satoshis for Bitcoin transaction.
     const MIN_TOTAL_TX_FEE: u64 = 1000;
     /// Minimal total transaction size according to
     /// https://bitcoin.stackexchange.com/questions/1195/how-to-calculate-transaction-size-before-sending-legacy-non-segwit-p2pkh-p2sh     
     const MIN_TX_LEN: u64 = 10 + 146 + 33 + 81;
     /// Minimal enough transaction fee per byte.
-    const MIN_TX_FEE: u64 = Self::MIN_TOTAL_TX_FEE / Self::MIN_TX_LEN + 1; // Round up.
+    pub const MIN_TX_FEE: u64 = Self::MIN_TOTAL_TX_FEE / Self::MIN_TX_LEN + 1; // Round up.
+
+    /// Default number of Bitcoin confirmations after which the anchoring transaction
+    /// is considered finally confirmed.
+    pub const DEFAULT_FINALITY_CONFIRMATIONS: u32 = 6;
+
+    /// Default number of blocks after which the anchoring proposal without a quorum
+    /// of signatures is considered stalled.
+    pub const DEFAULT_SIGNATURE_TIMEOUT: u64 = 20;
+
+    /// Default maximum weight of the funding transaction, which is equal to the maximum
+    /// weight of the standard transaction in Bitcoin Core.
+    pub const DEFAULT_MAX_FUNDING_TX_WEIGHT: u64 = 400_000;
+
+    /// Default maximum number of outputs of the funding transaction.
+    pub const DEFAULT_MAX_FUNDING_TX_OUTPUTS: u32 = 100;
+
+    /// Maximal confirmation target of the fee estimation, which is supported
+    /// by the `estimatesmartfee` call of Bitcoin Core.
+    pub const MAX_CONFIRMATION_TARGET: u32 = 1008;
+
+    /// Bitcoin networks supported by the anchoring service.
+    pub const SUPPORTED_NETWORKS: [Network; 3] =
+        [Network::Bitcoin, Network::Testnet, Network::Regtest];
 
     /// Creates Bitcoin anchoring config instance with default parameters for the
     /// given Bitcoin network and public keys of participants.
@@ -80,24 +123,114 @@ impl Config {
         })
     }
 
+    /// Returns the weight of the Bitcoin key of the anchoring node with the given identifier.
+    pub fn key_weight(&self, id: u16) -> usize {
+        self.anchoring_keys
+            .get(id as usize)
+            .map_or(0, |keys| keys.weight as usize)
+    }
+
+    /// Returns the total weight of the Bitcoin keys.
+    pub fn total_key_weight(&self) -> usize {
+        self.anchoring_keys
+            .iter()
+            .map(|keys| keys.weight as usize)
+            .sum()
+    }
+
+    /// Returns the total weight of the signatures required to spend the anchoring output.
+    ///
+    /// It is equal to the custom `quorum` if it is specified, otherwise it is the byzantine
+    /// majority of the key weights. For the keys with the default weight the latter is equal
+    /// to the `byzantine_quorum`.
+    pub fn signature_quorum(&self) -> usize {
+        self.quorum.map_or_else(
+            || exonum::helpers::byzantine_quorum(self.total_key_weight()),
+            |quorum| quorum as usize,
+        )
+    }
+
     /// Returns the corresponding Bitcoin address.
     pub fn anchoring_address(&self) -> Address {
         p2wsh::address(&self.redeem_script(), self.network).into()
     }
 
+    /// Derives the anchoring address from the given list of Bitcoin keys and the
+    /// number of required signatures.
+    ///
+    /// This function does not depend on the blockchain state, so anyone can recompute
+    /// the anchoring address from the published list of keys. The keys must be given
+    /// in the same order as in the `anchoring_keys` list of the configuration, each key
+    /// repeated as many times as its weight, and the quorum should be equal to
+    /// the `signature_quorum` of this configuration.
+    pub fn derive_address_with(
+        network: Network,
+        keys: impl IntoIterator<Item = btc::PublicKey>,
+        quorum: usize,
+    ) -> Result<Address, RedeemScriptError> {
+        let redeem_script = RedeemScriptBuilder::with_public_keys(keys.into_iter().map(|x| x.0))
+            .quorum(quorum)
+            .to_script()?;
+        Ok(p2wsh::address(&redeem_script, network).into())
+    }
+
     /// Returns the corresponding redeem script.
     pub fn redeem_script(&self) -> RedeemScript {
-        RedeemScriptBuilder::with_public_keys(self.anchoring_keys.iter().map(|x| x.bitcoin_key.0))
-            .quorum(self.byzantine_quorum())
+        RedeemScriptBuilder::with_public_keys(self.weighted_bitcoin_keys())
+            .quorum(self.signature_quorum())
             .to_script()
             .unwrap()
     }
 
+    /// Returns the output descriptor of the anchoring address with the checksum appended,
+    /// for example `wsh(multi(2,key_1,key_2,key_3))#checksum`.
+    ///
+    /// The descriptor can be imported into the descriptor wallet of the Bitcoin node
+    /// to watch and fund the anchoring address.
+    pub fn anchoring_descriptor(&self) -> String {
+        let keys = self
+            .weighted_bitcoin_keys()
+            .map(|key| key.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let descriptor = format!("wsh(multi({},{}))", self.signature_quorum(), keys);
+        btc::with_descriptor_checksum(&descriptor).expect("Descriptor must be valid")
+    }
+
+    /// Returns the Bitcoin keys of the redeem script, each key repeated as many times
+    /// as its weight.
+    fn weighted_bitcoin_keys(&self) -> impl Iterator<Item = bitcoin::PublicKey> + '_ {
+        self.anchoring_keys
+            .iter()
+            .flat_map(|keys| std::iter::repeat(keys.bitcoin_key.0).take(keys.weight as usize))
+    }
+
     /// Computes the P2WSH output corresponding to the actual redeem script.
     pub fn anchoring_out_script(&self) -> bitcoin::Script {
         self.redeem_script().as_ref().to_v0_p2wsh()
     }
 
+    /// Returns the P2WPKH address of the fee wallet if it is specified.
+    pub fn fee_payer_address(&self) -> Option<Address> {
+        self.fee_payer_key
+            .map(|key| p2wpk::address(&key.0, self.network).into())
+    }
+
+    /// Returns the output descriptor of the fee wallet with the checksum appended
+    /// if it is specified.
+    pub fn fee_payer_descriptor(&self) -> Option<String> {
+        self.fee_payer_key.map(|key| {
+            btc::with_descriptor_checksum(&format!("wpkh({})", key.0))
+                .expect("Descriptor must be valid")
+        })
+    }
+
+    /// Computes the P2WPKH output of the fee wallet if it is specified.
+    pub fn fee_payer_out_script(&self) -> Option<bitcoin::Script> {
+        self.fee_payer_address()
+            .map(|address| address.0.script_pubkey())
+    }
+
     /// Returns the latest height below the given height which must be anchored.
     pub fn previous_anchoring_height(&self, current_height: Height) -> Height {
         Height(current_height.0 - current_height.0 % self.anchoring_interval)
@@ -105,7 +238,73 @@ impl Config {
 
     /// Returns the nearest height above the given height which must be anchored.
     pub fn following_anchoring_height(&self, current_height: Height) -> Height {
-        Height(self.previous_anchoring_height(current_height).0 + self.anchoring_interval)
+        let height =
+            Height(self.previous_anchoring_height(current_height).0 + self.anchoring_interval);
+        self.following_checkpoint(height)
+    }
+
+    /// Returns the nearest height above the given height which must be anchored within
+    /// the anchoring epoch started at the given height.
+    ///
+    /// Anchoring heights are counted from the epoch start, so the change of the anchoring
+    /// interval doesn't mix the old and the new intervals in the schedule.
+    pub fn following_anchoring_height_in_epoch(
+        &self,
+        epoch_start: Height,
+        current_height: Height,
+    ) -> Height {
+        let offset = current_height.0.saturating_sub(epoch_start.0);
+        let height = Height(
+            epoch_start.0 + offset - offset % self.anchoring_interval + self.anchoring_interval,
+        );
+        self.following_checkpoint(height)
+    }
+
+    /// Moves the scheduled anchoring height forward to the nearest checkpoint
+    /// if the checkpoint filter is specified.
+    fn following_checkpoint(&self, height: Height) -> Height {
+        self.checkpoint_filter
+            .map_or(height, |filter| filter.following_checkpoint(height))
+    }
+
+    pub(crate) fn default_finality_confirmations() -> u32 {
+        Self::DEFAULT_FINALITY_CONFIRMATIONS
+    }
+
+    pub(crate) fn default_signature_timeout() -> u64 {
+        Self::DEFAULT_SIGNATURE_TIMEOUT
+    }
+
+    pub(crate) fn default_min_relay_fee() -> u64 {
+        btc::DEFAULT_RELAY_FEE
+    }
+
+    pub(crate) fn default_max_funding_tx_weight() -> u64 {
+        Self::DEFAULT_MAX_FUNDING_TX_WEIGHT
+    }
+
+    pub(crate) fn default_max_funding_tx_outputs() -> u32 {
+        Self::DEFAULT_MAX_FUNDING_TX_OUTPUTS
+    }
+
+    /// Checks that the funding transaction does not exceed the weight and output count
+    /// limits of this configuration.
+    pub fn check_funding_tx_limits(&self, transaction: &btc::Transaction) -> anyhow::Result<()> {
+        let weight = transaction.0.get_weight() as u64;
+        ensure!(
+            weight <= self.max_funding_tx_weight,
+            "Funding transaction weight {} exceeds the limit {}.",
+            weight,
+            self.max_funding_tx_weight
+        );
+        let outputs = transaction.0.output.len();
+        ensure!(
+            outputs <= self.max_funding_tx_outputs as usize,
+            "Funding transaction has {} outputs, which exceeds the limit {}.",
+            outputs,
+            self.max_funding_tx_outputs
+        );
+        Ok(())
     }
 
     /// Returns sufficient number of votes for the given anchoring nodes number.
@@ -127,6 +326,15 @@ impl ValidateInput for Config {
             "Too many anchoring nodes: amount of anchoring nodes should be less or equal than the {}.",
             Self::MAX_NODES_COUNT
         );
+        ensure!(
+            self.anchoring_keys.iter().all(|keys| keys.weight > 0),
+            "Weights of the anchoring keys should be greater than zero."
+        );
+        ensure!(
+            self.total_key_weight() <= Self::MAX_NODES_COUNT,
+            "Total weight of the anchoring keys should be less or equal than the {}.",
+            Self::MAX_NODES_COUNT
+        );
         ensure!(
             self.anchoring_interval > 0,
             "Anchoring interval should be greater than zero."
@@ -136,15 +344,188 @@ impl ValidateInput for Config {
             "Transaction fee should be greater than {}",
             Self::MIN_TX_FEE
         );
+        ensure!(
+            self.finality_confirmations > 0,
+            "Number of finality confirmations should be greater than zero."
+        );
+        ensure!(
+            self.signature_timeout > 0,
+            "Signature timeout should be greater than zero."
+        );
+        ensure!(
+            self.max_funding_tx_weight > 0 && self.max_funding_tx_outputs > 0,
+            "Funding transaction limits should be greater than zero."
+        );
+        if let Some(fee_payer_key) = self.fee_payer_key.as_ref() {
+            ensure!(
+                self.anchoring_keys
+                    .iter()
+                    .all(|keys| &keys.bitcoin_key != fee_payer_key),
+                "Fee payer key must not be one of the anchoring keys."
+            );
+        }
+        if let Some(emergency_brake) = self.emergency_brake.as_ref() {
+            ensure!(
+                emergency_brake.required_approvals > 0,
+                "Number of approvals required by the emergency brake should be greater than zero."
+            );
+        }
+        if let Some(fee_estimation) = self.fee_estimation.as_ref() {
+            ensure!(
+                (1..=Self::MAX_CONFIRMATION_TARGET).contains(&fee_estimation.confirmation_target),
+                "Fee estimation confirmation target should be in range from 1 to {}.",
+                Self::MAX_CONFIRMATION_TARGET
+            );
+            ensure!(
+                fee_estimation.min_fee >= Self::MIN_TX_FEE,
+                "Minimal estimated fee should be greater than {}",
+                Self::MIN_TX_FEE
+            );
+            ensure!(
+                fee_estimation.min_fee <= fee_estimation.max_fee,
+                "Minimal estimated fee should not exceed the maximal one."
+            );
+        }
+        ensure!(
+            self.max_cpfp_fee == 0 || self.max_cpfp_fee >= self.transaction_fee,
+            "Maximal CPFP fee should not be less than the transaction fee."
+        );
+        if let Some(checkpoint_filter) = self.checkpoint_filter.as_ref() {
+            // The anchoring schedule is counted from the epoch start, which is not known
+            // in advance, so the checkpoints are not required to lie on it. The scheduled
+            // height is moved forward to the nearest checkpoint instead, so each checkpoint
+            // is anchored if the period is not shorter than the anchoring interval.
+            ensure!(
+                checkpoint_filter.period >= self.anchoring_interval,
+                "Checkpoint period should not be less than the anchoring interval."
+            );
+            ensure!(
+                checkpoint_filter.offset < checkpoint_filter.period,
+                "Checkpoint offset should be less than the checkpoint period."
+            );
+        }
+        if let Some(fee_budget) = self.fee_budget.as_ref() {
+            ensure!(
+                fee_budget.window > 0 && fee_budget.max_fees > 0,
+                "Fee budget window and maximal fees should be greater than zero."
+            );
+        }
+        if let Some(quorum) = self.quorum {
+            // Two disjoint groups of the anchoring nodes must not be able to spend
+            // the anchoring output independently.
+            let total_weight = self.total_key_weight();
+            ensure!(
+                quorum as usize * 2 > total_weight && quorum as usize <= total_weight,
+                "Signature quorum should be greater than a half of the total weight of \
+                 the anchoring keys and not greater than the total weight {}.",
+                total_weight
+            );
+        }
+        if let Some(encrypted_memo) = self.encrypted_memo.as_ref() {
+            ensure!(
+                !encrypted_memo.is_empty() && encrypted_memo.len() <= btc::MAX_MEMO_LEN,
+                "Encrypted memo length should be in range from 1 to {}.",
+                btc::MAX_MEMO_LEN
+            );
+        }
+        for keys in &self.anchoring_keys {
+            if let Some(derivation) = keys.derivation.as_ref() {
+                // Extended keys only distinguish the main network from the test ones.
+                ensure!(
+                    (derivation.account_xpub.network == Network::Bitcoin)
+                        == (self.network == Network::Bitcoin),
+                    "Extended public key of the Bitcoin key {} is made for another network.",
+                    keys.bitcoin_key
+                );
+                ensure!(
+                    derivation.derive_public_key()? == keys.bitcoin_key,
+                    "Bitcoin key {} is not derived from the specified extended public key.",
+                    keys.bitcoin_key
+                );
+            }
+        }
 
         // Verify that the redeem script is suitable.
-        RedeemScriptBuilder::with_public_keys(self.anchoring_keys.iter().map(|x| x.bitcoin_key.0))
-            .quorum(self.byzantine_quorum())
+        RedeemScriptBuilder::with_public_keys(self.weighted_bitcoin_keys())
+            .quorum(self.signature_quorum())
             .to_script()?;
         Ok(())
     }
 }
 
+/// Anchoring configuration layout of the `0.9` and earlier versions of the service.
+#[derive(Debug, Deserialize)]
+struct LegacyConfig {
+    network: Network,
+    public_keys: Vec<btc::PublicKey>,
+    transaction_fee: u64,
+    #[serde(alias = "frequency")]
+    anchoring_interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct LegacyValidatorKeys {
+    service_key: PublicKey,
+}
+
+#[derive(Debug, Deserialize)]
+struct LegacyGenesisConfig {
+    validator_keys: Vec<LegacyValidatorKeys>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LegacyServicesConfigs {
+    btc_anchoring: LegacyConfig,
+}
+
+#[derive(Debug, Deserialize)]
+struct LegacyNodeConfig {
+    genesis: LegacyGenesisConfig,
+    services_configs: LegacyServicesConfigs,
+}
+
+/// Converts the node configuration of the `0.9` and earlier versions of the service
+/// into the actual anchoring configuration.
+///
+/// The old configuration did not contain service keys of the anchoring nodes, so they are
+/// taken from the `genesis.validator_keys` section in the same order as the anchoring
+/// public keys. Funding transaction from the old configuration is not migrated, it should
+/// be resent via the `management/add-funds` private API endpoint.
+pub fn migrate_from_v1(value: toml::Value) -> anyhow::Result<Config> {
+    let legacy_config: LegacyNodeConfig = value.try_into()?;
+    let anchoring_config = legacy_config.services_configs.btc_anchoring;
+    let validator_keys = legacy_config.genesis.validator_keys;
+
+    ensure!(
+        anchoring_config.public_keys.len() == validator_keys.len(),
+        "Number of anchoring keys ({}) does not match the number of validators ({}).",
+        anchoring_config.public_keys.len(),
+        validator_keys.len()
+    );
+
+    let anchoring_keys = anchoring_config
+        .public_keys
+        .into_iter()
+        .zip(validator_keys)
+        .map(|(bitcoin_key, validator_keys)| AnchoringKeys {
+            bitcoin_key,
+            service_key: validator_keys.service_key,
+            weight: AnchoringKeys::DEFAULT_WEIGHT,
+            derivation: None,
+        })
+        .collect();
+
+    let config = Config {
+        network: anchoring_config.network,
+        anchoring_keys,
+        anchoring_interval: anchoring_config.anchoring_interval,
+        transaction_fee: anchoring_config.transaction_fee,
+        ..Config::default()
+    };
+    config.validate()?;
+    Ok(config)
+}
+
 #[cfg(test)]
 mod tests {
     use exonum::{
@@ -154,16 +535,21 @@ mod tests {
 
     use bitcoin::network::constants::Network;
     use btc_transaction_utils::test_data::secp_gen_keypair;
+    use exonum_proto::ProtobufConvert;
 
-    use crate::proto::AnchoringKeys;
+    use crate::{btc, proto::AnchoringKeys};
 
-    use super::Config;
+    use super::{
+        migrate_from_v1, CheckpointFilter, Config, FeeBudget, FeeEstimation, KeyDerivation,
+    };
 
     fn gen_anchoring_keys(network: bitcoin::Network, count: usize) -> Vec<AnchoringKeys> {
         (0..count)
             .map(|_| AnchoringKeys {
                 bitcoin_key: secp_gen_keypair(network).0.into(),
                 service_key: crypto::gen_keypair().0,
+                weight: AnchoringKeys::DEFAULT_WEIGHT,
+                derivation: None,
             })
             .collect::<Vec<_>>()
     }
@@ -180,6 +566,83 @@ mod tests {
         assert_eq!(config2, config);
     }
 
+    #[test]
+    fn config_derived_keys() {
+        // Test vector 1 from BIP-32.
+        let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvv\
+                    NKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi"
+            .parse()
+            .unwrap();
+        let account = "m/84'/1'/0'".parse().unwrap();
+        let account_xpub = btc::account_xpub(&xprv, &account).unwrap();
+
+        let mut anchoring_keys = gen_anchoring_keys(Network::Bitcoin, 4);
+        anchoring_keys[0].bitcoin_key = btc::derive_public_key(&account_xpub, 1).unwrap();
+        anchoring_keys[0].derivation = Some(KeyDerivation {
+            account_xpub,
+            index: 1,
+        });
+        let mut config = Config::with_public_keys(Network::Bitcoin, anchoring_keys).unwrap();
+        config.validate().unwrap();
+        // Derivation survives the protobuf roundtrip.
+        assert_eq!(Config::from_pb(config.to_pb()).unwrap(), config);
+
+        config.anchoring_keys[0].derivation.as_mut().unwrap().index = 2;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("is not derived from the specified extended public key"));
+
+        config.anchoring_keys[0].derivation.as_mut().unwrap().index = 1;
+        config.network = Network::Testnet;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("is made for another network"));
+    }
+
+    #[test]
+    fn config_weighted_keys() {
+        let mut public_keys = gen_anchoring_keys(Network::Bitcoin, 4);
+        public_keys[0].weight = 3;
+
+        let config = Config::with_public_keys(Network::Bitcoin, public_keys).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.total_key_weight(), 6);
+        assert_eq!(config.signature_quorum(), 5);
+        assert_eq!(config.key_weight(0), 3);
+        assert_eq!(config.key_weight(1), 1);
+
+        let redeem_script = config.redeem_script();
+        assert_eq!(redeem_script.content().quorum, 5);
+        assert_eq!(redeem_script.content().public_keys.len(), 6);
+
+        // The default weights are encoded as before, so such configurations keep their hashes.
+        let mut unweighted = config.clone();
+        unweighted.anchoring_keys[0].weight = AnchoringKeys::DEFAULT_WEIGHT;
+        assert_eq!(unweighted.to_pb().get_anchoring_keys()[0].get_weight(), 0);
+        assert_eq!(Config::from_pb(config.to_pb()).unwrap(), config);
+    }
+
+    #[test]
+    fn config_custom_quorum() {
+        let public_keys = gen_anchoring_keys(Network::Bitcoin, 5);
+        let mut config = Config::with_public_keys(Network::Bitcoin, public_keys).unwrap();
+        assert_eq!(config.signature_quorum(), 4);
+        let byzantine_address = config.anchoring_address();
+
+        config.quorum = Some(3);
+        config.validate().unwrap();
+        assert_eq!(config.signature_quorum(), 3);
+        assert_eq!(config.redeem_script().content().quorum, 3);
+        assert!(config.anchoring_descriptor().starts_with("wsh(multi(3,"));
+        assert_ne!(config.anchoring_address(), byzantine_address);
+        // The consensus of the anchoring nodes keeps its own rules.
+        assert_eq!(config.byzantine_quorum(), 4);
+
+        assert_eq!(Config::from_pb(config.to_pb()).unwrap(), config);
+        // The configurations without quorum are encoded as before.
+        config.quorum = None;
+        assert_eq!(config.to_pb().get_quorum(), 0);
+        assert_eq!(Config::from_pb(config.to_pb()).unwrap(), config);
+    }
+
     #[test]
     fn config_anchoring_height() {
         let public_keys = gen_anchoring_keys(Network::Bitcoin, 4);
@@ -204,6 +667,162 @@ mod tests {
         );
     }
 
+    #[test]
+    fn config_anchoring_height_in_epoch() {
+        let public_keys = gen_anchoring_keys(Network::Bitcoin, 4);
+
+        let mut config = Config::with_public_keys(Network::Bitcoin, public_keys).unwrap();
+        config.anchoring_interval = 7;
+
+        // The epoch started at zero height has the same schedule as the whole chain.
+        for height in 0..30 {
+            assert_eq!(
+                config.following_anchoring_height_in_epoch(Height(0), Height(height)),
+                config.following_anchoring_height(Height(height))
+            );
+        }
+
+        let epoch_start = Height(10);
+        assert_eq!(
+            config.following_anchoring_height_in_epoch(epoch_start, Height(10)),
+            Height(17)
+        );
+        assert_eq!(
+            config.following_anchoring_height_in_epoch(epoch_start, Height(16)),
+            Height(17)
+        );
+        assert_eq!(
+            config.following_anchoring_height_in_epoch(epoch_start, Height(17)),
+            Height(24)
+        );
+    }
+
+    #[test]
+    fn config_anchoring_height_with_checkpoint_filter() {
+        let public_keys = gen_anchoring_keys(Network::Bitcoin, 4);
+
+        let mut config = Config::with_public_keys(Network::Bitcoin, public_keys).unwrap();
+        config.anchoring_interval = 1000;
+        config.checkpoint_filter = Some(CheckpointFilter {
+            period: 10_000,
+            offset: 0,
+        });
+        config.validate().unwrap();
+
+        assert_eq!(config.following_anchoring_height(Height(0)), Height(10_000));
+        assert_eq!(
+            config.following_anchoring_height(Height(9_999)),
+            Height(10_000)
+        );
+        assert_eq!(
+            config.following_anchoring_height(Height(10_000)),
+            Height(20_000)
+        );
+        // Heights of the epochs started between checkpoints are moved forward as well.
+        assert_eq!(
+            config.following_anchoring_height_in_epoch(Height(10_500), Height(10_500)),
+            Height(20_000)
+        );
+
+        config.checkpoint_filter = Some(CheckpointFilter {
+            period: 10_000,
+            offset: 3000,
+        });
+        assert_eq!(config.following_anchoring_height(Height(0)), Height(3000));
+        assert_eq!(
+            config.following_anchoring_height(Height(3000)),
+            Height(13_000)
+        );
+        assert!(config.checkpoint_filter.unwrap().matches(Height(23_000)));
+        assert!(!config.checkpoint_filter.unwrap().matches(Height(20_000)));
+    }
+
+    #[test]
+    fn config_migrate_from_v1() {
+        let anchoring_keys = gen_anchoring_keys(Network::Testnet, 4);
+        let validator_keys = anchoring_keys
+            .iter()
+            .map(|keys| {
+                format!(
+                    "{{ service_key = \"{}\" }}",
+                    hex::encode(keys.service_key.as_ref())
+                )
+            })
+            .collect::<Vec<_>>();
+        let public_keys = anchoring_keys
+            .iter()
+            .map(|keys| format!("\"{}\"", keys.bitcoin_key))
+            .collect::<Vec<_>>();
+        let legacy_config = format!(
+            r#"
+            [genesis]
+            validator_keys = [{}]
+
+            [services_configs.btc_anchoring]
+            network = "testnet"
+            public_keys = [{}]
+            transaction_fee = 10
+            frequency = 500
+            utxo_confirmations = 5
+            "#,
+            validator_keys.join(", "),
+            public_keys.join(", ")
+        );
+        let legacy_config = toml::from_str::<toml::Value>(&legacy_config).unwrap();
+
+        let config = migrate_from_v1(legacy_config).unwrap();
+        assert_eq!(config.network, Network::Testnet);
+        assert_eq!(config.anchoring_keys, anchoring_keys);
+        assert_eq!(config.anchoring_interval, 500);
+        assert_eq!(config.transaction_fee, 10);
+    }
+
+    #[test]
+    fn config_derive_address_with() {
+        let public_keys = gen_anchoring_keys(Network::Testnet, 4);
+        let config = Config::with_public_keys(Network::Testnet, public_keys).unwrap();
+
+        let bitcoin_keys = config.anchoring_keys.iter().map(|keys| keys.bitcoin_key);
+        let address = Config::derive_address_with(
+            config.network,
+            bitcoin_keys.clone(),
+            config.byzantine_quorum(),
+        )
+        .unwrap();
+        assert_eq!(address, config.anchoring_address());
+
+        // Another quorum gives another address.
+        let address = Config::derive_address_with(config.network, bitcoin_keys.clone(), 4).unwrap();
+        assert_ne!(address, config.anchoring_address());
+        // Quorum cannot exceed the number of keys.
+        Config::derive_address_with(config.network, bitcoin_keys, 5).unwrap_err();
+    }
+
+    #[test]
+    fn config_descriptors() {
+        let public_keys = gen_anchoring_keys(Network::Testnet, 4);
+        let mut config = Config::with_public_keys(Network::Testnet, public_keys).unwrap();
+
+        let descriptor = config.anchoring_descriptor();
+        let (body, checksum) = descriptor.split_at(descriptor.len() - 9);
+        assert!(body.starts_with("wsh(multi(3,"));
+        assert_eq!(
+            checksum,
+            format!("#{}", btc::descriptor_checksum(body).unwrap())
+        );
+        for keys in &config.anchoring_keys {
+            assert!(body.contains(&keys.bitcoin_key.to_string()));
+        }
+
+        assert_eq!(config.fee_payer_descriptor(), None);
+        let fee_payer_key = btc::PublicKey(secp_gen_keypair(Network::Testnet).0);
+        config.fee_payer_key = Some(fee_payer_key);
+        assert!(config
+            .fee_payer_descriptor()
+            .unwrap()
+            .starts_with(&format!("wpkh({})#", fee_payer_key)));
+    }
+
     // TODO test validation of the Bitcoin anchoring config
 
     #[test]
@@ -244,6 +863,142 @@ mod tests {
                 },
                 "Transaction fee should be greater than",
             ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    encrypted_memo: Some(vec![0; 33]),
+                    ..Config::default()
+                },
+                "Encrypted memo length should be in range",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    finality_confirmations: 0,
+                    ..Config::default()
+                },
+                "Number of finality confirmations should be greater than zero",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    signature_timeout: 0,
+                    ..Config::default()
+                },
+                "Signature timeout should be greater than zero",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    max_funding_tx_outputs: 0,
+                    ..Config::default()
+                },
+                "Funding transaction limits should be greater than zero",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    fee_estimation: Some(FeeEstimation {
+                        confirmation_target: 0,
+                        min_fee: 5,
+                        max_fee: 50,
+                    }),
+                    ..Config::default()
+                },
+                "Fee estimation confirmation target should be in range",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    fee_estimation: Some(FeeEstimation {
+                        confirmation_target: 6,
+                        min_fee: 50,
+                        max_fee: 5,
+                    }),
+                    ..Config::default()
+                },
+                "Minimal estimated fee should not exceed the maximal one",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    transaction_fee: 10,
+                    max_cpfp_fee: 5,
+                    ..Config::default()
+                },
+                "Maximal CPFP fee should not be less than the transaction fee",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4)
+                        .into_iter()
+                        .map(|keys| AnchoringKeys { weight: 0, ..keys })
+                        .collect(),
+                    ..Config::default()
+                },
+                "Weights of the anchoring keys should be greater than zero",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4)
+                        .into_iter()
+                        .map(|keys| AnchoringKeys { weight: 6, ..keys })
+                        .collect(),
+                    ..Config::default()
+                },
+                "Total weight of the anchoring keys should be less or equal",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    anchoring_interval: 1000,
+                    checkpoint_filter: Some(CheckpointFilter {
+                        period: 500,
+                        offset: 0,
+                    }),
+                    ..Config::default()
+                },
+                "Checkpoint period should not be less than the anchoring interval",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    anchoring_interval: 1000,
+                    checkpoint_filter: Some(CheckpointFilter {
+                        period: 10_000,
+                        offset: 10_000,
+                    }),
+                    ..Config::default()
+                },
+                "Checkpoint offset should be less than the checkpoint period",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    quorum: Some(2),
+                    ..Config::default()
+                },
+                "Signature quorum should be greater than a half of the total weight",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    quorum: Some(5),
+                    ..Config::default()
+                },
+                "Signature quorum should be greater than a half of the total weight",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    fee_budget: Some(FeeBudget {
+                        window: 0,
+                        max_fees: 100_000,
+                    }),
+                    ..Config::default()
+                },
+                "Fee budget window and maximal fees should be greater than zero",
+            ),
         ];
 
         for (config, expected_err) in &test_cases {
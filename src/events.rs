@@ -0,0 +1,290 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stream of the anchoring events.
+//!
+//! The stream is available at the `/api/services/{instance_name}/events` path of the public
+//! API in the [Server-Sent Events] format, so the sync utility and the monitoring tools
+//! can react to the changes of the anchoring chain instead of polling the
+//! `anchoring-proposal` endpoint. Each event is sent as the JSON-encoded [`AnchoringEvent`]
+//! in the `data` field, and the `event` field contains the name of the event.
+//!
+//! The events are not persisted, so the subscriber misses the events which have happened
+//! while it has been disconnected. Right after the subscription the stream reports the
+//! actual anchoring proposal, if any, thus the subscriber can resume from the actual state.
+//!
+//! # Examples
+//!
+//! ```text
+//! $ curl -N http://127.0.0.1:8080/api/services/anchoring/events
+//! event: proposal_created
+//! data: {"type":"proposal_created","txid":"...","anchored_height":1000}
+//!
+//! event: proposal_signed
+//! data: {"type":"proposal_signed","txid":"...","finalized_at":1003}
+//!
+//! event: chain_grown
+//! data: {"type":"chain_grown","length":12}
+//! ```
+//!
+//! [Server-Sent Events]: https://html.spec.whatwg.org/multipage/server-sent-events.html
+//! [`AnchoringEvent`]: enum.AnchoringEvent.html
+
+use actix_web::{
+    http::Method,
+    web::{Bytes, Payload},
+    HttpRequest, HttpResponse,
+};
+use exonum::{blockchain::Blockchain, helpers::Height, merkledb::Snapshot, runtime::SnapshotExt};
+use exonum_rust_runtime::api::{
+    backends::actix::{RawHandler, RequestHandler},
+    ServiceApiBuilder,
+};
+use futures::{
+    future::{FutureExt, LocalBoxFuture},
+    stream, StreamExt,
+};
+use serde_derive::{Deserialize, Serialize};
+use tokio::time::{self, Interval};
+
+use std::{sync::Arc, time::Duration};
+
+use crate::{blockchain::Schema, btc};
+
+/// Interval between the checks of the anchoring schema made for each subscriber.
+pub const EVENTS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Event of the anchoring chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnchoringEvent {
+    /// A new anchoring transaction proposal has appeared.
+    ProposalCreated {
+        /// Identifier of the proposal.
+        txid: btc::Sha256d,
+        /// Height of the block anchored by the proposal.
+        anchored_height: Height,
+    },
+    /// The anchoring transaction proposal has gathered a quorum of signatures and has been
+    /// finalized.
+    ProposalSigned {
+        /// Identifier of the anchoring transaction.
+        txid: btc::Sha256d,
+        /// Height of the block in which the anchoring transaction has been finalized.
+        finalized_at: Height,
+    },
+    /// New anchoring transactions have been appended to the anchoring chain.
+    ChainGrown {
+        /// Length of the anchoring chain.
+        length: u64,
+    },
+}
+
+impl AnchoringEvent {
+    /// Returns the name of the event.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnchoringEvent::ProposalCreated { .. } => "proposal_created",
+            AnchoringEvent::ProposalSigned { .. } => "proposal_signed",
+            AnchoringEvent::ChainGrown { .. } => "chain_grown",
+        }
+    }
+
+    /// Encodes the event as the message of the Server-Sent Events stream.
+    pub fn to_sse_message(&self) -> String {
+        format!(
+            "event: {}\ndata: {}\n\n",
+            self.name(),
+            serde_json::to_string(self).expect("Unable to serialize anchoring event")
+        )
+    }
+}
+
+/// Tracker of the anchoring schema changes, which produces the anchoring events.
+#[derive(Debug, Default)]
+pub struct EventTracker {
+    chain_length: Option<u64>,
+    proposal: Option<btc::Sha256d>,
+}
+
+impl EventTracker {
+    /// Creates a tracker, which has not seen the anchoring schema yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the events which have happened since the previous poll.
+    ///
+    /// The first poll reports only the actual anchoring proposal, if any.
+    pub fn poll(&mut self, snapshot: &dyn Snapshot, instance_name: &str) -> Vec<AnchoringEvent> {
+        let schema = match snapshot.for_service(instance_name) {
+            Some(access) => Schema::new(access),
+            None => return Vec::new(),
+        };
+
+        let mut events = Vec::new();
+        let chain_length = schema.transactions_chain.len();
+        if let Some(previous_length) = self.chain_length {
+            for index in previous_length..chain_length {
+                let txid = schema.transactions_chain.get(index).unwrap().id();
+                if let Some(finalized_at) = schema.finalization_height(&txid) {
+                    events.push(AnchoringEvent::ProposalSigned { txid, finalized_at });
+                }
+            }
+            if chain_length > previous_length {
+                events.push(AnchoringEvent::ChainGrown {
+                    length: chain_length,
+                });
+            }
+        }
+        self.chain_length = Some(chain_length);
+
+        let proposal = schema
+            .actual_proposed_anchoring_transaction(snapshot.for_core())
+            .and_then(Result::ok)
+            .and_then(|(proposal, _)| {
+                let anchored_height = proposal.anchoring_payload()?.block_height;
+                Some((proposal.id(), anchored_height))
+            });
+        let proposal_id = proposal.map(|(txid, _)| txid);
+        if let Some((txid, anchored_height)) = proposal {
+            if self.proposal != Some(txid) {
+                events.push(AnchoringEvent::ProposalCreated {
+                    txid,
+                    anchored_height,
+                });
+            }
+        }
+        self.proposal = proposal_id;
+        events
+    }
+}
+
+/// Subscription of the API client to the anchoring events.
+struct Subscription {
+    blockchain: Blockchain,
+    instance_name: String,
+    interval: Interval,
+    tracker: EventTracker,
+}
+
+impl Subscription {
+    async fn next_events(&mut self) -> Vec<AnchoringEvent> {
+        self.interval.tick().await;
+        let snapshot = self.blockchain.snapshot();
+        self.tracker.poll(&*snapshot, &self.instance_name)
+    }
+}
+
+pub(crate) fn wire(builder: &mut ServiceApiBuilder) {
+    let blockchain = builder.blockchain().clone();
+    let handler: Arc<RawHandler> = Arc::new(move |request: HttpRequest, _payload: Payload| {
+        let response = match crate::api::instance_name(&request) {
+            Some(instance_name) => {
+                let subscription = Subscription {
+                    blockchain: blockchain.clone(),
+                    instance_name: instance_name.to_owned(),
+                    interval: time::interval(EVENTS_POLL_INTERVAL),
+                    tracker: EventTracker::new(),
+                };
+                let events = stream::unfold(subscription, |mut subscription| async move {
+                    let events = subscription.next_events().await;
+                    Some((stream::iter(events), subscription))
+                })
+                .flatten()
+                .map(|event| Ok::<_, actix_web::Error>(Bytes::from(event.to_sse_message())))
+                .boxed_local();
+                HttpResponse::Ok()
+                    .content_type("text/event-stream")
+                    .streaming(events)
+            }
+            None => HttpResponse::NotFound().finish(),
+        };
+        async move { Ok(response) }.boxed_local() as LocalBoxFuture<'static, _>
+    });
+    builder
+        .public_scope()
+        .web_backend()
+        .raw_handler(RequestHandler {
+            name: "events".to_owned(),
+            method: Method::GET,
+            inner: handler,
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::helpers::Height;
+
+    use super::{AnchoringEvent, EventTracker};
+    use crate::test_helpers::{AnchoringTestKit, ANCHORING_INSTANCE_NAME};
+
+    #[test]
+    fn event_tracker() {
+        let mut testkit = AnchoringTestKit::default();
+        let mut tracker = EventTracker::new();
+        let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+
+        let proposal = testkit.anchoring_transaction_proposal().unwrap().0;
+        let events = tracker.poll(&testkit.inner.snapshot(), ANCHORING_INSTANCE_NAME);
+        assert_eq!(
+            events,
+            vec![AnchoringEvent::ProposalCreated {
+                txid: proposal.id(),
+                anchored_height: Height(0),
+            }]
+        );
+        // The proposal is reported only once.
+        assert!(tracker
+            .poll(&testkit.inner.snapshot(), ANCHORING_INSTANCE_NAME)
+            .is_empty());
+
+        testkit
+            .inner
+            .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+        let events = tracker.poll(&testkit.inner.snapshot(), ANCHORING_INSTANCE_NAME);
+        assert_eq!(
+            events,
+            vec![
+                AnchoringEvent::ProposalSigned {
+                    txid: proposal.id(),
+                    finalized_at: Height(1),
+                },
+                AnchoringEvent::ChainGrown { length: 1 },
+            ]
+        );
+
+        testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval));
+        let proposal = testkit.anchoring_transaction_proposal().unwrap().0;
+        let events = tracker.poll(&testkit.inner.snapshot(), ANCHORING_INSTANCE_NAME);
+        assert_eq!(
+            events,
+            vec![AnchoringEvent::ProposalCreated {
+                txid: proposal.id(),
+                anchored_height: Height(anchoring_interval),
+            }]
+        );
+    }
+
+    #[test]
+    fn event_sse_message() {
+        let event = AnchoringEvent::ChainGrown { length: 2 };
+        assert_eq!(
+            event.to_sse_message(),
+            "event: chain_grown\ndata: {\"type\":\"chain_grown\",\"length\":2}\n\n"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Anchoring annotations for the blockchain explorers.
+//!
+//! A block is covered by the first anchoring transaction which anchors this block
+//! or a later one, since the anchored block hash transitively commits to all the previous
+//! blocks. The [`AnchoringExplorerExt`] trait annotates the blocks returned by the Exonum
+//! explorer with their covering anchors, so the explorer frontends can show the anchoring
+//! status of the block without bespoke queries.
+//!
+//! # Examples
+//!
+//! ```
+//! # use exonum::merkledb::Snapshot;
+//! use exonum::helpers::Height;
+//! use exonum_btc_anchoring::explorer::AnchoringExplorerExt;
+//! use exonum_explorer::BlockchainExplorer;
+//!
+//! fn print_anchored_blocks(snapshot: &dyn Snapshot) {
+//!     let explorer = BlockchainExplorer::new(snapshot);
+//!     let blocks = explorer.blocks(Height(0)..);
+//!     for block in snapshot.annotate_blocks("anchoring", blocks) {
+//!         let badge = if block.is_anchored() { "anchored" } else { "pending" };
+//!         println!("Block {}: {}", block.block.height(), badge);
+//!     }
+//! }
+//! ```
+//!
+//! [`AnchoringExplorerExt`]: trait.AnchoringExplorerExt.html
+
+use exonum::{helpers::Height, merkledb::Snapshot, runtime::SnapshotExt};
+use exonum_explorer::BlockInfo;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{api::AnchorConfirmationState, blockchain::Schema, btc};
+
+/// Anchoring transaction covering the block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockAnchor {
+    /// Index of the anchoring transaction in the anchoring chain.
+    pub anchor_index: u64,
+    /// Identifier of the anchoring transaction in the Bitcoin network.
+    pub txid: btc::Sha256d,
+    /// Height of the block anchored by this transaction.
+    pub anchored_height: Height,
+    /// Confirmation state of the anchoring transaction agreed by the anchoring nodes.
+    pub state: AnchorConfirmationState,
+}
+
+impl BlockAnchor {
+    /// Checks that the anchoring transaction is finally confirmed in the Bitcoin blockchain.
+    pub fn is_final(&self) -> bool {
+        if let AnchorConfirmationState::Final { .. } = self.state {
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Explorer block annotated with its covering anchor.
+#[derive(Debug, Serialize)]
+pub struct AnchoredBlock<'a> {
+    /// Block information returned by the explorer.
+    #[serde(flatten)]
+    pub block: BlockInfo<'a>,
+    /// Anchoring transaction covering the block, if any.
+    pub anchor: Option<BlockAnchor>,
+}
+
+impl AnchoredBlock<'_> {
+    /// Checks that the block is covered by the finally confirmed anchoring transaction.
+    pub fn is_anchored(&self) -> bool {
+        self.anchor.as_ref().map_or(false, BlockAnchor::is_final)
+    }
+}
+
+/// Extension of the blockchain snapshot, which annotates the blocks with their
+/// covering anchors.
+pub trait AnchoringExplorerExt {
+    /// Returns the anchoring transaction covering the block with the given height,
+    /// or `None` if the block is not anchored yet or the anchoring service with the given
+    /// instance name doesn't exist.
+    fn block_anchor(&self, instance_name: &str, height: Height) -> Option<BlockAnchor>;
+
+    /// Annotates the explorer blocks with their covering anchors.
+    fn annotate_blocks<'a>(
+        &self,
+        instance_name: &str,
+        blocks: impl IntoIterator<Item = BlockInfo<'a>>,
+    ) -> Vec<AnchoredBlock<'a>>;
+}
+
+impl AnchoringExplorerExt for dyn Snapshot {
+    fn block_anchor(&self, instance_name: &str, height: Height) -> Option<BlockAnchor> {
+        let schema = Schema::new(self.for_service(instance_name)?);
+        let anchor_index = schema.covering_anchor_index(height)?;
+        let tx = schema.transactions_chain.get(anchor_index)?;
+        let anchored_height = tx.anchoring_payload()?.block_height;
+        Some(BlockAnchor {
+            anchor_index,
+            txid: tx.id(),
+            anchored_height,
+            state: AnchorConfirmationState::from_schema(&schema, tx.id()),
+        })
+    }
+
+    fn annotate_blocks<'a>(
+        &self,
+        instance_name: &str,
+        blocks: impl IntoIterator<Item = BlockInfo<'a>>,
+    ) -> Vec<AnchoredBlock<'a>> {
+        blocks
+            .into_iter()
+            .map(|block| {
+                let anchor = self.block_anchor(instance_name, block.height());
+                AnchoredBlock { block, anchor }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,470 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GraphQL endpoint over the anchoring schema.
+//!
+//! The endpoint complements the REST API for the dashboards, which need to fetch
+//! the anchoring chain, configurations, funds and participation of the anchoring nodes
+//! in a single request. It is available at the `/api/services/{instance_name}/graphql`
+//! path of the public API if the crate is built with the `juniper` feature.
+//!
+//! GraphQL `Int` type is limited to 32 bits, so the 64-bit integers, such as block
+//! heights and amounts of satoshis, are represented as decimal strings. The lists are
+//! paginated by the opaque cursors: pass the `endCursor` of the page as the `after`
+//! argument to get the next page.
+//!
+//! # Examples
+//!
+//! ```graphql
+//! {
+//!   chain(first: 10, fromHeight: "1000") {
+//!     totalCount
+//!     nodes { index txid anchoredHeight finalizedAt signers }
+//!     pageInfo { endCursor hasNextPage }
+//!   }
+//!   config { anchoringAddress anchoringInterval }
+//!   funds { unspentFundingTransaction { txid value } }
+//! }
+//! ```
+
+use exonum::{helpers::Height, merkledb::Snapshot, runtime::SnapshotExt};
+use exonum_merkledb::access::Access;
+use exonum_rust_runtime::api::{self, ServiceApiBuilder, ServiceApiState};
+use juniper::{
+    http::GraphQLRequest, EmptyMutation, FieldError, FieldResult, GraphQLObject, RootNode,
+};
+
+use std::collections::BTreeMap;
+
+use crate::{
+    api::{Page, PageQuery},
+    blockchain::{data_layout::TxInputId, Schema},
+    btc,
+    config::Config,
+};
+
+/// Context of the GraphQL queries, which contains the snapshot of the blockchain state.
+pub struct Context {
+    snapshot: Box<dyn Snapshot>,
+    instance_name: String,
+}
+
+impl std::fmt::Debug for Context {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Context")
+            .field("instance_name", &self.instance_name)
+            .finish()
+    }
+}
+
+impl juniper::Context for Context {}
+
+impl Context {
+    fn schema(&self) -> Schema<impl Access + '_> {
+        let service_data = self
+            .snapshot
+            .for_service(self.instance_name.as_str())
+            .expect("Anchoring service data is absent");
+        Schema::new(service_data)
+    }
+}
+
+/// Information about the pagination of the list.
+#[derive(Debug, GraphQLObject)]
+pub struct PageInfo {
+    /// Cursor to pass as the `after` argument to get the next page.
+    pub end_cursor: Option<String>,
+    /// Whether there are more items after this page.
+    pub has_next_page: bool,
+}
+
+impl PageInfo {
+    fn new<T>(page: &Page<T>) -> Self {
+        Self {
+            end_cursor: page.next.map(|next| next.to_string()),
+            has_next_page: page.next.is_some(),
+        }
+    }
+}
+
+/// Entry of the anchoring chain.
+#[derive(Debug, GraphQLObject)]
+pub struct ChainEntry {
+    /// Position of the transaction in the anchoring chain.
+    pub index: String,
+    /// Identifier of the anchoring transaction.
+    pub txid: String,
+    /// Height of the anchored Exonum block.
+    pub anchored_height: String,
+    /// Hash of the anchored Exonum block.
+    pub anchored_block_hash: String,
+    /// Whether this is the final anchor of the anchoring chain.
+    pub is_final: bool,
+    /// Height of the block in which the transaction has been finalized.
+    pub finalized_at: Option<String>,
+    /// Hash of the Bitcoin block containing the transaction, agreed by a quorum
+    /// of the anchoring nodes.
+    pub bitcoin_block_hash: Option<String>,
+    /// Height of the Bitcoin block containing the transaction, agreed by a quorum
+    /// of the anchoring nodes.
+    pub bitcoin_block_height: Option<String>,
+    /// Height of the block in which the transaction has been marked as finally confirmed.
+    pub finally_confirmed_at: Option<String>,
+    /// Indexes of the anchoring nodes which have signed the first input
+    /// of the transaction, in the configuration actual at the moment of signing.
+    pub signers: Vec<i32>,
+    /// Hex-encoded transaction.
+    pub content: String,
+}
+
+impl ChainEntry {
+    fn new<T: Access>(schema: &Schema<T>, index: u64, transaction: btc::Transaction) -> Self {
+        let txid = transaction.id();
+        let payload = transaction
+            .anchoring_payload()
+            .expect("Anchoring transaction without payload");
+        let inclusion = schema.anchor_inclusion(&txid);
+        Self {
+            index: index.to_string(),
+            txid: txid.to_string(),
+            anchored_height: payload.block_height.0.to_string(),
+            anchored_block_hash: payload.block_hash.to_hex(),
+            is_final: payload.is_final,
+            finalized_at: schema
+                .finalization_height(&txid)
+                .map(|height| height.0.to_string()),
+            bitcoin_block_hash: inclusion
+                .as_ref()
+                .map(|inclusion| inclusion.block_hash.to_string()),
+            bitcoin_block_height: inclusion.map(|inclusion| inclusion.block_height.to_string()),
+            finally_confirmed_at: schema
+                .anchor_finality_height(&txid)
+                .map(|height| height.0.to_string()),
+            signers: signers(schema, txid),
+            content: transaction.to_string(),
+        }
+    }
+}
+
+/// Page of the anchoring chain entries.
+#[derive(Debug, GraphQLObject)]
+pub struct ChainConnection {
+    /// Total length of the anchoring chain.
+    pub total_count: String,
+    /// Entries of the page.
+    pub nodes: Vec<ChainEntry>,
+    /// Information about the pagination.
+    pub page_info: PageInfo,
+}
+
+/// Keys of the anchoring node.
+#[derive(Debug, GraphQLObject)]
+pub struct AnchoringKeysEntry {
+    /// Service key of the node.
+    pub service_key: String,
+    /// Bitcoin key of the node.
+    pub bitcoin_key: String,
+    /// Weight of the Bitcoin key in the redeem script.
+    pub weight: i32,
+}
+
+/// Anchoring configuration.
+#[derive(Debug, GraphQLObject)]
+pub struct ConfigEntry {
+    /// Bitcoin network.
+    pub network: String,
+    /// Anchoring address.
+    pub anchoring_address: String,
+    /// Address of the fee wallet, if any.
+    pub fee_payer_address: Option<String>,
+    /// Keys of the anchoring nodes.
+    pub anchoring_keys: Vec<AnchoringKeysEntry>,
+    /// Total weight of the signatures required to spend the anchoring output.
+    pub byzantine_quorum: i32,
+    /// Interval in blocks between the anchored blocks.
+    pub anchoring_interval: String,
+    /// Fee per byte in satoshis.
+    pub transaction_fee: String,
+    /// Number of Bitcoin confirmations after which the anchor is finally confirmed.
+    pub finality_confirmations: i32,
+}
+
+impl From<Config> for ConfigEntry {
+    fn from(config: Config) -> Self {
+        Self {
+            network: config.network.to_string(),
+            anchoring_address: config.anchoring_address().to_string(),
+            fee_payer_address: config
+                .fee_payer_address()
+                .map(|address| address.to_string()),
+            anchoring_keys: config
+                .anchoring_keys
+                .iter()
+                .map(|keys| AnchoringKeysEntry {
+                    service_key: keys.service_key.to_hex(),
+                    bitcoin_key: keys.bitcoin_key.to_string(),
+                    weight: keys.weight as i32,
+                })
+                .collect(),
+            byzantine_quorum: config.signature_quorum() as i32,
+            anchoring_interval: config.anchoring_interval.to_string(),
+            transaction_fee: config.transaction_fee.to_string(),
+            finality_confirmations: config.finality_confirmations as i32,
+        }
+    }
+}
+
+/// Unspent funding transaction.
+#[derive(Debug, GraphQLObject)]
+pub struct FundingEntry {
+    /// Identifier of the funding transaction.
+    pub txid: String,
+    /// Amount of satoshis sent to the wallet.
+    pub value: String,
+}
+
+/// Funds available for the anchoring.
+#[derive(Debug, GraphQLObject)]
+pub struct Funds {
+    /// Unspent funding transaction of the anchoring wallet.
+    pub unspent_funding_transaction: Option<FundingEntry>,
+    /// Unspent funding transaction of the fee wallet.
+    pub unspent_fee_payer_transaction: Option<FundingEntry>,
+    /// Amount of satoshis of the latest anchoring transaction output.
+    pub anchoring_output_value: Option<String>,
+}
+
+/// Participation of the anchoring node in the signing of the anchoring transactions.
+#[derive(Debug, GraphQLObject)]
+pub struct NodeParticipation {
+    /// Index of the anchoring node in the configuration actual at the moment of signing.
+    pub node_index: i32,
+    /// Number of the signed anchoring transactions.
+    pub signed: i32,
+}
+
+/// Participation of the anchoring nodes for a page of the anchoring chain.
+#[derive(Debug, GraphQLObject)]
+pub struct ParticipationStats {
+    /// Number of the anchoring transactions taken into account.
+    pub transactions: i32,
+    /// Participation of the anchoring nodes in order of their indexes.
+    pub nodes: Vec<NodeParticipation>,
+    /// Information about the pagination.
+    pub page_info: PageInfo,
+}
+
+/// Root of the GraphQL queries.
+#[derive(Debug)]
+pub struct Query;
+
+#[juniper::object(Context = Context)]
+impl Query {
+    /// Returns a page of the anchoring chain in order of the anchored heights.
+    fn chain(
+        context: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        from_height: Option<String>,
+        to_height: Option<String>,
+    ) -> FieldResult<ChainConnection> {
+        let schema = context.schema();
+        let query = page_query(first, after, from_height, to_height)?;
+        let page = chain_page(&schema, query)?;
+        Ok(ChainConnection {
+            total_count: schema.transactions_chain.len().to_string(),
+            page_info: PageInfo::new(&page),
+            nodes: page
+                .items
+                .into_iter()
+                .map(|(index, transaction)| ChainEntry::new(&schema, index, transaction))
+                .collect(),
+        })
+    }
+
+    /// Returns the anchoring chain entry with the given index.
+    fn chain_entry(context: &Context, index: String) -> FieldResult<Option<ChainEntry>> {
+        let schema = context.schema();
+        let index = parse_u64("index", &index)?;
+        Ok(schema
+            .anchoring_transaction(index)
+            .map(|transaction| ChainEntry::new(&schema, index, transaction)))
+    }
+
+    /// Returns the actual anchoring configuration.
+    fn config(context: &Context) -> ConfigEntry {
+        context.schema().actual_config().into()
+    }
+
+    /// Returns the following anchoring configuration, if any.
+    fn following_config(context: &Context) -> Option<ConfigEntry> {
+        context.schema().following_config().map(From::from)
+    }
+
+    /// Returns the anchoring configurations in order of their activation.
+    fn config_history(context: &Context) -> Vec<ConfigEntry> {
+        context
+            .schema()
+            .config_history()
+            .into_iter()
+            .map(From::from)
+            .collect()
+    }
+
+    /// Returns the funds available for the anchoring.
+    fn funds(context: &Context) -> Funds {
+        let schema = context.schema();
+        let config = schema.actual_config();
+        let funding_entry = |transaction: btc::Transaction, address: &btc::Address| {
+            let value = transaction
+                .output_to(address)
+                .map_or(0, |(_, output)| output.value);
+            FundingEntry {
+                txid: transaction.id().to_string(),
+                value: value.to_string(),
+            }
+        };
+
+        Funds {
+            unspent_funding_transaction: schema
+                .unspent_funding_transaction()
+                .map(|transaction| funding_entry(transaction, &config.anchoring_address())),
+            unspent_fee_payer_transaction: config.fee_payer_address().and_then(|address| {
+                schema
+                    .unspent_fee_payer_transaction()
+                    .map(|transaction| funding_entry(transaction, &address))
+            }),
+            anchoring_output_value: schema
+                .last_anchoring_transaction()
+                .and_then(|transaction| transaction.unspent_value())
+                .map(|value| value.to_string()),
+        }
+    }
+
+    /// Returns the participation of the anchoring nodes in the signing of a page
+    /// of the anchoring chain.
+    fn participation(
+        context: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        from_height: Option<String>,
+        to_height: Option<String>,
+    ) -> FieldResult<ParticipationStats> {
+        let schema = context.schema();
+        let query = page_query(first, after, from_height, to_height)?;
+        let page = chain_page(&schema, query)?;
+
+        let mut signed = BTreeMap::new();
+        for (_, transaction) in &page.items {
+            for node_index in signers(&schema, transaction.id()) {
+                *signed.entry(node_index).or_insert(0) += 1;
+            }
+        }
+        Ok(ParticipationStats {
+            transactions: page.items.len() as i32,
+            nodes: signed
+                .into_iter()
+                .map(|(node_index, signed)| NodeParticipation { node_index, signed })
+                .collect(),
+            page_info: PageInfo::new(&page),
+        })
+    }
+
+    /// Returns the height of the latest committed block.
+    fn latest_height(context: &Context) -> String {
+        context.snapshot.for_core().height().0.to_string()
+    }
+}
+
+fn parse_u64(name: &str, value: &str) -> FieldResult<u64> {
+    value
+        .parse()
+        .map_err(|e| FieldError::from(format!("Invalid `{}` value: {}", name, e)))
+}
+
+fn page_query(
+    first: Option<i32>,
+    after: Option<String>,
+    from_height: Option<String>,
+    to_height: Option<String>,
+) -> FieldResult<PageQuery> {
+    let limit = first
+        .map(|first| {
+            if first > 0 {
+                Ok(first as u64)
+            } else {
+                Err(FieldError::from("`first` should be positive"))
+            }
+        })
+        .transpose()?;
+    Ok(PageQuery {
+        start: after.map(|after| parse_u64("after", &after)).transpose()?,
+        from_height: from_height
+            .map(|height| parse_u64("fromHeight", &height).map(Height))
+            .transpose()?,
+        to_height: to_height
+            .map(|height| parse_u64("toHeight", &height).map(Height))
+            .transpose()?,
+        limit,
+    })
+}
+
+fn chain_page<T: Access>(
+    schema: &Schema<T>,
+    query: PageQuery,
+) -> FieldResult<Page<(u64, btc::Transaction)>> {
+    let chain = &schema.transactions_chain;
+    query
+        .paginate(
+            chain.len(),
+            |index| (index, chain.get(index).unwrap()),
+            |(_, transaction)| {
+                transaction
+                    .anchoring_payload()
+                    .expect("Anchoring transaction without payload")
+                    .block_height
+            },
+        )
+        .map_err(|e| FieldError::from(e.body.title))
+}
+
+fn signers<T: Access>(schema: &Schema<T>, txid: btc::Sha256d) -> Vec<i32> {
+    schema
+        .input_signatures(&TxInputId::new(txid, 0))
+        .0
+        .keys()
+        .map(|&node_index| i32::from(node_index))
+        .collect()
+}
+
+fn execute(context: &Context, request: &GraphQLRequest) -> api::Result<serde_json::Value> {
+    let root_node = RootNode::new(Query, EmptyMutation::<Context>::new());
+    let response = request.execute(&root_node, context);
+    serde_json::to_value(&response)
+        .map_err(|e| api::Error::internal(e).title("Unable to serialize GraphQL response"))
+}
+
+pub(crate) fn wire(builder: &mut ServiceApiBuilder) {
+    let blockchain = builder.blockchain().clone();
+    builder.public_scope().endpoint_mut(
+        "graphql",
+        move |state: ServiceApiState, request: GraphQLRequest| {
+            let context = Context {
+                snapshot: blockchain.snapshot(),
+                instance_name: state.instance().name.clone(),
+            };
+            async move { execute(&context, &request) }
+        },
+    );
+}
@@ -64,6 +64,12 @@ pub mod api;
 pub mod blockchain;
 pub mod btc;
 pub mod config;
+pub mod events;
+pub mod explorer;
+#[cfg(feature = "juniper")]
+pub mod graphql;
+pub mod psbt;
+pub mod recovery;
 pub mod sync;
 pub mod test_helpers;
 
@@ -17,8 +17,10 @@
 pub use binary_map::BinaryMap;
 
 use anyhow::anyhow;
+use bitcoin::util::bip32::{self, ExtendedPubKey};
 use exonum::{
     crypto::{proto::*, Hash, PublicKey},
+    helpers::Height,
     merkledb::{
         impl_object_hash_for_binary_value, impl_serde_hex_for_binary_value, BinaryKey, BinaryValue,
         ObjectHash,
@@ -37,6 +39,21 @@ mod binary_map;
 
 include!(concat!(env!("OUT_DIR"), "/protobuf_mod.rs"));
 
+/// Protobuf sources of the service data types.
+const PROTO_SOURCES: [&str; 3] = [
+    include_str!("btc_types.proto"),
+    include_str!("internal.proto"),
+    include_str!("service.proto"),
+];
+
+/// Returns the hash of the protobuf sources of the service data types.
+///
+/// Any change in the protobuf sources changes this hash, thus two builds with the same
+/// hash use the same binary format of the transactions and the schema values.
+pub(crate) fn schema_hash() -> Hash {
+    exonum::crypto::hash(PROTO_SOURCES.concat().as_bytes())
+}
+
 impl ProtobufConvert for btc::PublicKey {
     type ProtoStruct = btc_types::PublicKey;
 
@@ -102,16 +119,126 @@ impl ProtobufConvert for btc::Sha256d {
     }
 }
 
+impl ProtobufConvert for btc::SigHashType {
+    type ProtoStruct = u32;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        self.as_u32()
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        // Messages without the explicit signature hash type are signed with `SIGHASH_ALL`.
+        if pb == 0 {
+            return Ok(btc::SigHashType::All);
+        }
+        btc::SigHashType::from_u32(pb).map_err(From::from)
+    }
+}
+
 /// Public keys of an anchoring node.
-#[derive(
-    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
-)]
-#[protobuf_convert(source = "self::service::AnchoringKeys")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, BinaryValue, ObjectHash)]
 pub struct AnchoringKeys {
     /// Service key is used to authorize transactions.
     pub service_key: PublicKey,
     /// The Bitcoin public key is used to calculate the corresponding redeem script.
     pub bitcoin_key: btc::PublicKey,
+    /// Weight of the Bitcoin key in the redeem script.
+    ///
+    /// The key is repeated in the multisig redeem script as many times as its weight,
+    /// and its signature is counted the same number of times. Thus, the anchoring
+    /// transaction requires signatures with the total weight of the Byzantine majority
+    /// of the total weight of the keys.
+    #[serde(default = "AnchoringKeys::default_weight")]
+    pub weight: u32,
+    /// Optional BIP-32 derivation of the Bitcoin key, which allows everyone to verify that
+    /// the key is derived from the published account extended public key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivation: Option<KeyDerivation>,
+}
+
+impl AnchoringKeys {
+    /// Default weight of the Bitcoin key.
+    pub const DEFAULT_WEIGHT: u32 = 1;
+
+    fn default_weight() -> u32 {
+        Self::DEFAULT_WEIGHT
+    }
+}
+
+impl ProtobufConvert for AnchoringKeys {
+    type ProtoStruct = self::service::AnchoringKeys;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_service_key(self.service_key.to_pb());
+        proto_struct.set_bitcoin_key(self.bitcoin_key.to_pb());
+        // Keys with the default weight are encoded as before the weights appeared,
+        // so the hashes of the existing configurations remain the same.
+        if self.weight != Self::DEFAULT_WEIGHT {
+            proto_struct.set_weight(self.weight);
+        }
+        if let Some(derivation) = self.derivation.as_ref() {
+            proto_struct.set_derivation(derivation.to_pb());
+        }
+        proto_struct
+    }
+
+    fn from_pb(mut pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        Ok(Self {
+            service_key: ProtobufConvert::from_pb(pb.take_service_key())?,
+            bitcoin_key: ProtobufConvert::from_pb(pb.take_bitcoin_key())?,
+            // Configurations created before this field appeared have no value in it.
+            weight: match pb.get_weight() {
+                0 => AnchoringKeys::DEFAULT_WEIGHT,
+                value => value,
+            },
+            derivation: if pb.has_derivation() {
+                Some(ProtobufConvert::from_pb(pb.take_derivation())?)
+            } else {
+                None
+            },
+        })
+    }
+}
+
+/// BIP-32 derivation of the anchoring Bitcoin key.
+///
+/// The key is the non-hardened child with the given index of the account extended public
+/// key, so the anchoring node derives a fresh key for each anchoring address change by
+/// incrementing the index, and the other nodes can verify the derivation without any
+/// secrets.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, BinaryValue, ObjectHash)]
+pub struct KeyDerivation {
+    /// Extended public key of the account.
+    #[serde(with = "serde_str")]
+    pub account_xpub: ExtendedPubKey,
+    /// Index of the child key.
+    pub index: u32,
+}
+
+impl KeyDerivation {
+    /// Derives the Bitcoin public key.
+    pub fn derive_public_key(&self) -> Result<btc::PublicKey, bip32::Error> {
+        btc::derive_public_key(&self.account_xpub, self.index)
+    }
+}
+
+impl ProtobufConvert for KeyDerivation {
+    type ProtoStruct = self::service::KeyDerivation;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_account_xpub(self.account_xpub.to_string());
+        proto_struct.set_index(self.index);
+        proto_struct
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        Ok(Self {
+            account_xpub: pb.get_account_xpub().parse()?,
+            index: pb.get_index(),
+        })
+    }
 }
 
 /// Exonum message with a signature for one of the inputs of a new anchoring transaction.
@@ -124,6 +251,8 @@ pub struct SignInput {
     pub input: u32,
     /// Signature content.
     pub input_signature: btc::InputSignature,
+    /// Signature hash type used to create the signature.
+    pub sighash_type: btc::SigHashType,
 }
 
 /// Exonum message with the unspent funding transaction.
@@ -134,6 +263,377 @@ pub struct AddFunds {
     pub transaction: btc::Transaction,
 }
 
+/// Exonum message with the proof of possession of the Bitcoin key by the transaction author.
+#[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
+#[protobuf_convert(source = "self::service::AttestKey")]
+pub struct AttestKey {
+    /// Attested Bitcoin public key.
+    pub bitcoin_key: btc::PublicKey,
+    /// DER-encoded signature of the author's service key made by the attested Bitcoin key.
+    pub signature: Vec<u8>,
+}
+
+/// Exonum message with the location of the confirmed anchoring transaction in the Bitcoin
+/// blockchain.
+#[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
+#[protobuf_convert(source = "self::service::ConfirmAnchor")]
+pub struct ConfirmAnchor {
+    /// Anchoring transaction id.
+    pub txid: Sha256d,
+    /// Hash of the Bitcoin block containing the anchoring transaction.
+    pub block_hash: Sha256d,
+    /// Height of the Bitcoin block containing the anchoring transaction.
+    pub block_height: u64,
+    /// Number of confirmations of the anchoring transaction observed by the node.
+    pub confirmations: u32,
+}
+
+/// Exonum message with the approval of the special anchoring transaction by the validator.
+#[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
+#[protobuf_convert(source = "self::service::ApproveSpecialTx")]
+pub struct ApproveSpecialTx {
+    /// Approved anchoring transaction ID.
+    pub txid: Sha256d,
+}
+
+/// Exonum message with the fee rate estimated by the Bitcoin node of the anchoring node.
+#[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
+#[protobuf_convert(source = "self::service::ReportFeeEstimate")]
+pub struct ReportFeeEstimate {
+    /// Estimated fee per byte in satoshis.
+    pub fee: u64,
+}
+
+/// Exonum message with the request to bump the fee of the latest anchoring transaction,
+/// which is stuck in the Bitcoin memory pool, by the child-pays-for-parent transaction.
+#[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
+#[protobuf_convert(source = "self::service::RequestCpfp")]
+pub struct RequestCpfp {
+    /// Identifier of the stuck anchoring transaction.
+    pub txid: Sha256d,
+    /// Fee per byte in satoshis of the package of the stuck transaction and its child.
+    pub fee: u64,
+}
+
+impl ConfirmAnchor {
+    /// Returns the location of the anchoring transaction reported by this message.
+    pub fn inclusion(&self) -> AnchorInclusion {
+        AnchorInclusion {
+            block_hash: self.block_hash,
+            block_height: self.block_height,
+        }
+    }
+}
+
+/// Divergence detected during the finalization of the anchoring proposal.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::FinalizationIncident")]
+pub struct FinalizationIncident {
+    /// Height of the block in which the divergence has been detected.
+    pub height: u64,
+    /// Identifier of the anchoring proposal rebuilt from the schema.
+    pub expected_txid: btc::Sha256d,
+    /// Identifier of the finalized anchoring transaction.
+    pub actual_txid: btc::Sha256d,
+    /// Description of the divergence.
+    pub description: String,
+}
+
+/// Different signatures of the same anchoring proposal input made by the same Bitcoin key.
+///
+/// Signatures are made with deterministic nonces, so such a conflict should never happen
+/// and is an evidence of the nonce reuse or the compromise of the key.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::SignatureConflict")]
+pub struct SignatureConflict {
+    /// Height of the block in which the conflict has been detected.
+    pub height: u64,
+    /// Identifier of the anchoring proposal.
+    pub txid: btc::Sha256d,
+    /// Index of the signed input.
+    pub input: u32,
+    /// Bitcoin key which has made both signatures.
+    pub public_key: btc::PublicKey,
+    /// Signature recorded earlier.
+    pub recorded_signature: btc::InputSignature,
+    /// Rejected signature, which differs from the recorded one.
+    pub conflicting_signature: btc::InputSignature,
+}
+
+/// Location of the anchoring transaction in the Bitcoin blockchain.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::AnchorInclusion")]
+pub struct AnchorInclusion {
+    /// Hash of the Bitcoin block containing the anchoring transaction.
+    pub block_hash: btc::Sha256d,
+    /// Height of the Bitcoin block containing the anchoring transaction.
+    pub block_height: u64,
+}
+
+/// Range of the Exonum block heights covered by the anchoring transaction.
+///
+/// The anchoring transaction covers the blocks after the block anchored by the previous
+/// anchoring transaction up to its own anchored block inclusive. The range is empty if
+/// the transaction anchors the same block as the previous one.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::AnchorCoverage")]
+pub struct AnchorCoverage {
+    /// The first covered height.
+    pub first_height: u64,
+    /// The last covered height, i.e. the height of the anchored block.
+    pub last_height: u64,
+}
+
+impl AnchorCoverage {
+    /// Returns the range covered by the anchoring transaction, which anchors the block
+    /// with the given height, and follows the transaction with the given coverage.
+    pub fn following(previous: Option<&Self>, anchored_height: Height) -> Self {
+        Self {
+            first_height: previous.map_or(0, |previous| previous.last_height + 1),
+            last_height: anchored_height.0,
+        }
+    }
+
+    /// Checks that the block with the given height is covered.
+    pub fn contains(&self, height: Height) -> bool {
+        self.first_height <= height.0 && height.0 <= self.last_height
+    }
+}
+
+/// Child-pays-for-parent fee bump of the latest anchoring transaction agreed by
+/// the anchoring nodes.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::CpfpAgreement")]
+pub struct CpfpAgreement {
+    /// Identifier of the stuck anchoring transaction.
+    pub txid: btc::Sha256d,
+    /// Fee per byte in satoshis of the package of the stuck transaction and its child.
+    pub fee: u64,
+    /// Height of the block to be anchored by the child transaction.
+    pub anchoring_height: u64,
+}
+
+/// Fee paid by the finalized anchoring transaction.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::FeeSpending")]
+pub struct FeeSpending {
+    /// Height of the block in which the anchoring transaction has been finalized.
+    pub height: u64,
+    /// Fee in satoshis.
+    pub fee: u64,
+}
+
+/// Excess of the fee budget, which pauses the anchoring proposals until the supervisor
+/// applies a new configuration.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::FeeBudgetBreach")]
+pub struct FeeBudgetBreach {
+    /// Height of the block in which the budget has been exceeded.
+    pub height: u64,
+    /// Total fee in satoshis spent within the rolling window of the budget.
+    pub spent_fees: u64,
+    /// Maximal total fee in satoshis allowed by the budget.
+    pub max_fees: u64,
+}
+
+/// Witness of the anchoring transaction input stored separately from the transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, BinaryValue, ObjectHash)]
+pub struct InputWitness {
+    /// Witness items except the witness script.
+    pub items: Vec<Vec<u8>>,
+    /// Hash of the witness script, if the input spends a P2WSH output.
+    ///
+    /// Witness scripts are the same for all anchoring transactions with the same
+    /// anchoring keys, so they are stored only once.
+    pub witness_script_hash: Option<Hash>,
+}
+
+impl ProtobufConvert for InputWitness {
+    type ProtoStruct = self::internal::InputWitness;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_items(self.items.clone().into());
+        if let Some(hash) = self.witness_script_hash.as_ref() {
+            proto_struct.set_witness_script_hash(hash.to_pb());
+        }
+        proto_struct
+    }
+
+    fn from_pb(mut pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        Ok(Self {
+            items: pb.take_items().into_vec(),
+            witness_script_hash: if pb.has_witness_script_hash() {
+                Some(ProtobufConvert::from_pb(pb.take_witness_script_hash())?)
+            } else {
+                None
+            },
+        })
+    }
+}
+
+/// Witnesses of all inputs of the anchoring transaction.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::TransactionWitnesses")]
+pub struct TransactionWitnesses {
+    /// Witnesses of the transaction inputs in the order of the inputs.
+    pub inputs: Vec<InputWitness>,
+}
+
+/// Request to anchor the latest block of the blockchain being decommissioned.
+///
+/// Once the configuration with this request is applied, the latest committed block is
+/// anchored regardless of the anchoring interval, and no further anchoring transactions
+/// are created afterwards.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::FinalAnchor")]
+pub struct FinalAnchor {
+    /// Whether the final anchoring transaction is marked by the tombstone payload,
+    /// so external verifiers know that no further anchors will follow.
+    pub tombstone: bool,
+}
+
+/// Emergency brake, which requires out-of-band approvals of the validators to broadcast
+/// the special anchoring transactions.
+///
+/// The anchoring transaction is special if its fee exceeds the fee threshold, or it
+/// sweeps the funds to the following anchoring address, or it is the final anchoring
+/// transaction. The sync utility doesn't broadcast such transactions until the required
+/// number of validators approve them by the `approve_special_tx` transactions.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::EmergencyBrake")]
+pub struct EmergencyBrake {
+    /// Fee in satoshis above which the anchoring transaction requires approvals,
+    /// zero value stands for the fee check disabled.
+    #[serde(default)]
+    pub fee_threshold: u64,
+    /// Number of approvals from distinct validators required to broadcast the special
+    /// anchoring transaction.
+    pub required_approvals: u32,
+}
+
+/// Estimation of the anchoring transaction fee by the Bitcoin nodes of the anchoring nodes.
+///
+/// Each anchoring node reports the fee rate estimated by its Bitcoin node by the
+/// `report_fee_estimate` transaction. Once an anchoring transaction is finalized, the fee
+/// of the next anchoring proposals is set to the median of the reported estimates, limited
+/// by the `min_fee` and `max_fee` values.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::FeeEstimation")]
+pub struct FeeEstimation {
+    /// Number of blocks within which the anchoring transaction should be confirmed.
+    pub confirmation_target: u32,
+    /// Minimal fee per byte in satoshis.
+    pub min_fee: u64,
+    /// Maximal fee per byte in satoshis.
+    pub max_fee: u64,
+}
+
+impl FeeEstimation {
+    /// Limits the fee by the `min_fee` and `max_fee` values.
+    pub fn clamp(&self, fee: u64) -> u64 {
+        fee.max(self.min_fee).min(self.max_fee)
+    }
+}
+
+/// Filter of the block heights which may be anchored.
+///
+/// The height is a checkpoint if its remainder of the division by the `period` equals
+/// the `offset`. The scheduled anchoring height is moved forward to the nearest checkpoint,
+/// so only the checkpoint blocks are anchored by the regular anchoring transactions.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::CheckpointFilter")]
+pub struct CheckpointFilter {
+    /// Period in blocks between the checkpoint heights.
+    pub period: u64,
+    /// Remainder of the division of the checkpoint heights by the period.
+    #[serde(default)]
+    pub offset: u64,
+}
+
+impl CheckpointFilter {
+    /// Checks that the given height is a checkpoint.
+    pub fn matches(&self, height: Height) -> bool {
+        height.0 % self.period == self.offset
+    }
+
+    /// Returns the nearest checkpoint height which is not less than the given height.
+    pub fn following_checkpoint(&self, height: Height) -> Height {
+        let remainder = height.0 % self.period;
+        if remainder <= self.offset {
+            Height(height.0 - remainder + self.offset)
+        } else {
+            Height(height.0 - remainder + self.period + self.offset)
+        }
+    }
+}
+
+/// Budget of the fees spent by the anchoring transactions within a rolling window of blocks.
+///
+/// If the total fee of the anchoring transactions finalized within the window exceeds
+/// the budget, the anchoring proposals are paused until the supervisor applies a new
+/// configuration. It protects the anchoring wallet from being drained by the fee spikes.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::FeeBudget")]
+pub struct FeeBudget {
+    /// Length of the rolling window in blocks.
+    pub window: u64,
+    /// Maximal total fee in satoshis of the anchoring transactions finalized within the window.
+    pub max_fees: u64,
+}
+
+/// Supervisor authorization to export the disaster recovery bundle.
+///
+/// The authorization is not stored in the configuration. Once the configuration with it
+/// is applied, the export is recorded to the `Schema::recovery_exports` list, and the
+/// anchoring nodes can produce the bundle encrypted by the escrow key with the given hash.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::RecoveryExport")]
+pub struct RecoveryExport {
+    /// Hash of the escrow key which encrypts the exported bundle.
+    pub escrow_key_hash: Hash,
+}
+
+/// Recovery bundle export authorized by the supervisor.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::AuthorizedRecoveryExport")]
+pub struct AuthorizedRecoveryExport {
+    /// Hash of the escrow key which encrypts the exported bundle.
+    pub escrow_key_hash: Hash,
+    /// Height of the block in which the export has been authorized.
+    pub height: u64,
+}
+
 /// Consensus parameters in the BTC anchoring.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, BinaryValue, ObjectHash)]
 pub struct Config {
@@ -145,6 +645,87 @@ pub struct Config {
     pub anchoring_interval: u64,
     /// Fee per byte in satoshis.
     pub transaction_fee: u64,
+    /// Bitcoin public key of the optional fee wallet, which pays anchoring fees
+    /// instead of the multisig anchoring wallet.
+    #[serde(default)]
+    pub fee_payer_key: Option<btc::PublicKey>,
+    /// Optional encrypted memo, which is attached to the anchoring transactions payload.
+    ///
+    /// Memo should be encrypted by the [`MemoKey`] shared among auditors off-chain.
+    ///
+    /// [`MemoKey`]: ../btc/struct.MemoKey.html
+    #[serde(default)]
+    pub encrypted_memo: Option<Vec<u8>>,
+    /// Number of Bitcoin confirmations reported by a quorum of the anchoring nodes
+    /// after which the anchoring transaction is considered finally confirmed.
+    #[serde(default = "Config::default_finality_confirmations")]
+    pub finality_confirmations: u32,
+    /// Optional request to anchor the latest block and shut down the anchoring chain.
+    #[serde(default)]
+    pub final_anchor: Option<FinalAnchor>,
+    /// Whether the anchoring transactions are stored without witnesses.
+    ///
+    /// Witnesses are stored in a separate index, and the full transactions are
+    /// reconstructed on demand.
+    #[serde(default)]
+    pub compact_storage: bool,
+    /// Number of blocks after which the anchoring proposal, which has not collected
+    /// a quorum of signatures, is considered stalled. The service warns about stalled
+    /// proposals, and the sync utility re-submits its signatures for them.
+    #[serde(default = "Config::default_signature_timeout")]
+    pub signature_timeout: u64,
+    /// Minimal fee rate of the anchoring transactions in satoshis per 1000 virtual bytes.
+    ///
+    /// The fee of the anchoring transaction is raised up to this rate if the `transaction_fee`
+    /// is not enough, so the value should be not less than the `minrelaytxfee` of the Bitcoin
+    /// nodes used by the anchoring nodes.
+    #[serde(default = "Config::default_min_relay_fee")]
+    pub min_relay_fee: u64,
+    /// Maximum weight of the funding transaction as defined in BIP-141.
+    ///
+    /// Larger funding transactions are rejected, since they are stored on the blockchain
+    /// and returned in the anchoring proposals.
+    #[serde(default = "Config::default_max_funding_tx_weight")]
+    pub max_funding_tx_weight: u64,
+    /// Maximum number of outputs of the funding transaction.
+    #[serde(default = "Config::default_max_funding_tx_outputs")]
+    pub max_funding_tx_outputs: u32,
+    /// Optional authorization to export the disaster recovery bundle.
+    #[serde(default)]
+    pub recovery_export: Option<RecoveryExport>,
+    /// Optional emergency brake for the special anchoring transactions.
+    #[serde(default)]
+    pub emergency_brake: Option<EmergencyBrake>,
+    /// Optional estimation of the transaction fee by the Bitcoin nodes, which replaces
+    /// the static `transaction_fee`.
+    #[serde(default)]
+    pub fee_estimation: Option<FeeEstimation>,
+    /// Maximal fee per byte in satoshis of the package of the anchoring transaction stuck
+    /// in the Bitcoin memory pool and the child transaction which pays for it.
+    ///
+    /// The anchoring nodes may agree to bump the fee of the stuck transaction by anchoring
+    /// the latest block ahead of the schedule. Zero value stands for the fee bumping disabled.
+    #[serde(default)]
+    pub max_cpfp_fee: u64,
+    /// Optional filter of the block heights which may be anchored.
+    ///
+    /// The filter allows to anchor only the "epoch" blocks, for example, the blocks with
+    /// the heights divisible by 10 000. The transition and final anchoring transactions,
+    /// as well as the child-pays-for-parent proposals, are not restricted by the filter.
+    #[serde(default)]
+    pub checkpoint_filter: Option<CheckpointFilter>,
+    /// Optional total weight of the signatures required to spend the anchoring output.
+    ///
+    /// By default, the anchoring output requires the byzantine majority of the key weights,
+    /// which matches the Exonum consensus. The custom quorum allows another trust model,
+    /// for example, 3-of-5 signatures, and should be greater than a half of the total
+    /// weight of the keys.
+    #[serde(default)]
+    pub quorum: Option<u32>,
+    /// Optional budget of the fees spent by the anchoring transactions within a rolling
+    /// window of blocks.
+    #[serde(default)]
+    pub fee_budget: Option<FeeBudget>,
 }
 
 impl ProtobufConvert for Config {
@@ -157,6 +738,38 @@ impl ProtobufConvert for Config {
         proto_struct.set_anchoring_keys(self.anchoring_keys.to_pb().into());
         proto_struct.set_anchoring_interval(self.anchoring_interval.to_pb());
         proto_struct.set_transaction_fee(self.transaction_fee.to_pb());
+        if let Some(fee_payer_key) = self.fee_payer_key.as_ref() {
+            proto_struct.set_fee_payer_key(fee_payer_key.to_pb());
+        }
+        if let Some(encrypted_memo) = self.encrypted_memo.as_ref() {
+            proto_struct.set_encrypted_memo(encrypted_memo.clone());
+        }
+        proto_struct.set_finality_confirmations(self.finality_confirmations);
+        if let Some(final_anchor) = self.final_anchor.as_ref() {
+            proto_struct.set_final_anchor(final_anchor.to_pb());
+        }
+        proto_struct.set_compact_storage(self.compact_storage);
+        proto_struct.set_signature_timeout(self.signature_timeout);
+        proto_struct.set_min_relay_fee(self.min_relay_fee);
+        proto_struct.set_max_funding_tx_weight(self.max_funding_tx_weight);
+        proto_struct.set_max_funding_tx_outputs(self.max_funding_tx_outputs);
+        if let Some(recovery_export) = self.recovery_export.as_ref() {
+            proto_struct.set_recovery_export(recovery_export.to_pb());
+        }
+        if let Some(emergency_brake) = self.emergency_brake.as_ref() {
+            proto_struct.set_emergency_brake(emergency_brake.to_pb());
+        }
+        if let Some(fee_estimation) = self.fee_estimation.as_ref() {
+            proto_struct.set_fee_estimation(fee_estimation.to_pb());
+        }
+        proto_struct.set_max_cpfp_fee(self.max_cpfp_fee);
+        if let Some(checkpoint_filter) = self.checkpoint_filter.as_ref() {
+            proto_struct.set_checkpoint_filter(checkpoint_filter.to_pb());
+        }
+        proto_struct.set_quorum(self.quorum.unwrap_or_default());
+        if let Some(fee_budget) = self.fee_budget.as_ref() {
+            proto_struct.set_fee_budget(fee_budget.to_pb());
+        }
         proto_struct
     }
 
@@ -169,11 +782,79 @@ impl ProtobufConvert for Config {
             anchoring_keys: ProtobufConvert::from_pb(pb.take_anchoring_keys().into_vec())?,
             anchoring_interval: ProtobufConvert::from_pb(pb.get_anchoring_interval())?,
             transaction_fee: ProtobufConvert::from_pb(pb.get_transaction_fee())?,
+            fee_payer_key: if pb.has_fee_payer_key() {
+                Some(ProtobufConvert::from_pb(pb.take_fee_payer_key())?)
+            } else {
+                None
+            },
+            encrypted_memo: Some(pb.take_encrypted_memo()).filter(|memo| !memo.is_empty()),
+            // Configurations created before this field appeared have no value in it.
+            finality_confirmations: match pb.get_finality_confirmations() {
+                0 => Config::DEFAULT_FINALITY_CONFIRMATIONS,
+                value => value,
+            },
+            final_anchor: if pb.has_final_anchor() {
+                Some(ProtobufConvert::from_pb(pb.take_final_anchor())?)
+            } else {
+                None
+            },
+            compact_storage: pb.get_compact_storage(),
+            // Configurations created before this field appeared have no value in it.
+            signature_timeout: match pb.get_signature_timeout() {
+                0 => Config::DEFAULT_SIGNATURE_TIMEOUT,
+                value => value,
+            },
+            // Configurations created before this field appeared have no value in it.
+            min_relay_fee: match pb.get_min_relay_fee() {
+                0 => btc::DEFAULT_RELAY_FEE,
+                value => value,
+            },
+            // Configurations created before these fields appeared have no values in them.
+            max_funding_tx_weight: match pb.get_max_funding_tx_weight() {
+                0 => Config::DEFAULT_MAX_FUNDING_TX_WEIGHT,
+                value => value,
+            },
+            max_funding_tx_outputs: match pb.get_max_funding_tx_outputs() {
+                0 => Config::DEFAULT_MAX_FUNDING_TX_OUTPUTS,
+                value => value,
+            },
+            recovery_export: if pb.has_recovery_export() {
+                Some(ProtobufConvert::from_pb(pb.take_recovery_export())?)
+            } else {
+                None
+            },
+            emergency_brake: if pb.has_emergency_brake() {
+                Some(ProtobufConvert::from_pb(pb.take_emergency_brake())?)
+            } else {
+                None
+            },
+            fee_estimation: if pb.has_fee_estimation() {
+                Some(ProtobufConvert::from_pb(pb.take_fee_estimation())?)
+            } else {
+                None
+            },
+            max_cpfp_fee: pb.get_max_cpfp_fee(),
+            checkpoint_filter: if pb.has_checkpoint_filter() {
+                Some(ProtobufConvert::from_pb(pb.take_checkpoint_filter())?)
+            } else {
+                None
+            },
+            quorum: Some(pb.get_quorum()).filter(|&quorum| quorum > 0),
+            fee_budget: if pb.has_fee_budget() {
+                Some(ProtobufConvert::from_pb(pb.take_fee_budget())?)
+            } else {
+                None
+            },
         })
     }
 }
 
 impl_serde_hex_for_binary_value! { SignInput }
+impl_serde_hex_for_binary_value! { AttestKey }
+impl_serde_hex_for_binary_value! { ConfirmAnchor }
+impl_serde_hex_for_binary_value! { ApproveSpecialTx }
+impl_serde_hex_for_binary_value! { ReportFeeEstimate }
+impl_serde_hex_for_binary_value! { RequestCpfp }
 
 impl BinaryValue for btc::Sha256d {
     fn to_bytes(&self) -> Vec<u8> {
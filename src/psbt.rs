@@ -0,0 +1,133 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Anchoring proposals in the PSBT (BIP-174) format for the external signers.
+//!
+//! The actual anchoring proposal is exported via the `anchoring-proposal/psbt` private
+//! API endpoint together with the spent outputs and the witness script of the anchoring
+//! wallet, so hardware wallets and other external signers can sign its inputs. The signed
+//! PSBT is submitted via the `signing/psbt` endpoint, which converts the signatures made
+//! by the Bitcoin keys of the node into the `sign_input` transactions.
+
+use anyhow::{anyhow, ensure};
+use bitcoin::{consensus::encode, util::psbt::PartiallySignedTransaction};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    blockchain::{is_fee_payer_input, SignInput},
+    btc,
+    config::Config,
+};
+
+/// Anchoring proposal in the PSBT format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProposalPsbt {
+    /// Identifier of the proposed anchoring transaction.
+    pub txid: btc::Sha256d,
+    /// Hex-encoded PSBT of the proposal.
+    pub psbt: String,
+}
+
+impl ProposalPsbt {
+    /// Creates the PSBT of the given anchoring proposal.
+    ///
+    /// Each input of the PSBT contains the spent output and the `SIGHASH_ALL` type, and
+    /// the inputs of the anchoring wallet also contain its witness script.
+    pub fn new(
+        config: &Config,
+        proposal: &btc::Transaction,
+        inputs: &[btc::Transaction],
+    ) -> anyhow::Result<Self> {
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(proposal.0.clone())?;
+        let redeem_script = config.redeem_script();
+        for (index, (psbt_input, txin)) in psbt.inputs.iter_mut().zip(&proposal.0.input).enumerate()
+        {
+            let spent_output = inputs
+                .get(index)
+                .and_then(|tx| tx.0.output.get(txin.previous_output.vout as usize))
+                .ok_or_else(|| anyhow!("Input {} spends a nonexistent output", index))?;
+            psbt_input.witness_utxo = Some(spent_output.clone());
+            psbt_input.sighash_type = Some(bitcoin::SigHashType::All);
+            // Inputs of the fee wallet are P2WPKH ones, which need no witness script.
+            let is_fee_payer = config.fee_payer_key.is_some()
+                && is_fee_payer_input(config, proposal, inputs, index);
+            if !is_fee_payer {
+                psbt_input.witness_script = Some(redeem_script.as_ref().clone());
+            }
+        }
+
+        Ok(Self::from_psbt(&psbt))
+    }
+
+    /// Encodes the given PSBT.
+    pub fn from_psbt(psbt: &PartiallySignedTransaction) -> Self {
+        Self {
+            txid: btc::Transaction::from(psbt.global.unsigned_tx.clone()).id(),
+            psbt: hex::encode(encode::serialize(psbt)),
+        }
+    }
+
+    /// Decodes the PSBT.
+    pub fn to_psbt(&self) -> anyhow::Result<PartiallySignedTransaction> {
+        encode::deserialize(&hex::decode(&self.psbt)?).map_err(From::from)
+    }
+
+    /// Extracts the signatures of the given anchoring proposal made by the given Bitcoin
+    /// keys. Each input is signed by the first of the keys which has a partial signature
+    /// of it, the inputs without such signatures are skipped.
+    pub fn signatures(
+        &self,
+        proposal: &btc::Transaction,
+        bitcoin_keys: &[btc::PublicKey],
+    ) -> anyhow::Result<Vec<SignInput>> {
+        ensure!(
+            self.txid == proposal.id(),
+            "PSBT is made for the transaction {}, while the actual proposal is {}",
+            self.txid,
+            proposal.id()
+        );
+        let psbt = self.to_psbt()?;
+        ensure!(
+            psbt.global.unsigned_tx == proposal.0,
+            "PSBT transaction differs from the actual proposal {}",
+            self.txid
+        );
+
+        psbt.inputs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, input)| {
+                let signature = bitcoin_keys
+                    .iter()
+                    .find_map(|key| input.partial_sigs.get(&key.0))?;
+                Some((index, signature))
+            })
+            .map(|(index, signature)| {
+                let sighash_type = signature
+                    .last()
+                    .ok_or_else(|| anyhow!("Signature of the input {} is empty", index))
+                    .and_then(|&value| Ok(btc::SigHashType::from_u32(u32::from(value))?))?;
+                let input_signature =
+                    btc_transaction_utils::InputSignature::from_bytes(signature.clone())
+                        .map_err(|e| anyhow!("Invalid signature of the input {}: {}", index, e))?;
+                Ok(SignInput {
+                    txid: self.txid,
+                    input: index as u32,
+                    input_signature: btc::InputSignature(input_signature),
+                    sighash_type,
+                })
+            })
+            .collect()
+    }
+}
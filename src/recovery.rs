@@ -0,0 +1,394 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Disaster recovery bundle of the anchoring wallets.
+//!
+//! The bundle contains the redeem scripts and the configuration history of the anchoring
+//! chain together with the instructions on how to spend the anchoring wallet with the
+//! Bitcoin keys escrowed off-chain. It never contains private keys. The bundle export
+//! should be authorized by the supervisor via the `recovery_export` field of the
+//! configuration proposal, then any anchoring node returns the bundle encrypted by
+//! the escrow key via the `management/recovery-bundle` private API endpoint.
+
+use aes_gcm::{
+    aead::{Aead, NewAead},
+    Aes256Gcm, Key, Nonce,
+};
+use exonum::{
+    crypto::{self, Hash},
+    helpers::Height,
+};
+use exonum_merkledb::access::Access;
+use rand::Rng;
+use serde::{de::Error as _, Deserializer, Serializer};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    blockchain::{AuthorizedRecoveryExport, Schema},
+    btc,
+    config::Config,
+};
+
+/// Length of the AES-GCM nonce, which is stored in the beginning of the encrypted bundle.
+const BUNDLE_NONCE_LEN: usize = 12;
+/// Length of the AES-GCM authentication tag.
+const BUNDLE_TAG_LEN: usize = 16;
+
+/// Secret key which encrypts the disaster recovery bundle.
+///
+/// The key is kept by the escrow agents off-chain, the supervisor authorizes the export
+/// by the hash of this key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RecoveryKey([u8; 32]);
+
+/// Recovery bundle decryption errors.
+#[derive(Debug, Copy, Clone, PartialEq, Error)]
+pub enum RecoveryError {
+    /// Encrypted bundle is malformed or has been encrypted by another key.
+    #[error("Unable to decrypt recovery bundle.")]
+    DecryptionFailed,
+    /// Decrypted bundle has an unexpected format.
+    #[error("Recovery bundle is malformed.")]
+    MalformedBundle,
+}
+
+impl RecoveryKey {
+    /// Creates a recovery key from the given bytes.
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Generates a new random recovery key.
+    pub fn random() -> Self {
+        Self(rand::thread_rng().gen())
+    }
+
+    /// Returns the hash of this key, which is used in the export authorization.
+    pub fn hash(&self) -> Hash {
+        crypto::hash(&self.0)
+    }
+
+    /// Encrypts the given recovery bundle. The nonce is randomly generated and prepended
+    /// to the ciphertext.
+    pub fn encrypt(&self, bundle: &RecoveryBundle) -> EncryptedRecoveryBundle {
+        let plaintext = serde_json::to_vec(bundle).expect("Unable to serialize recovery bundle");
+        let nonce: [u8; BUNDLE_NONCE_LEN] = rand::thread_rng().gen();
+        let ciphertext = self
+            .cipher()
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_ref())
+            .expect("Unable to encrypt recovery bundle");
+
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&ciphertext);
+        EncryptedRecoveryBundle {
+            export_index: bundle.export_index,
+            snapshot_height: bundle.snapshot_height,
+            data: hex::encode(data),
+        }
+    }
+
+    /// Decrypts the given recovery bundle.
+    pub fn decrypt(
+        &self,
+        bundle: &EncryptedRecoveryBundle,
+    ) -> Result<RecoveryBundle, RecoveryError> {
+        let data = hex::decode(&bundle.data).map_err(|_| RecoveryError::DecryptionFailed)?;
+        if data.len() < BUNDLE_NONCE_LEN + BUNDLE_TAG_LEN {
+            return Err(RecoveryError::DecryptionFailed);
+        }
+
+        let (nonce, ciphertext) = data.split_at(BUNDLE_NONCE_LEN);
+        let plaintext = self
+            .cipher()
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| RecoveryError::DecryptionFailed)?;
+        serde_json::from_slice(&plaintext).map_err(|_| RecoveryError::MalformedBundle)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::from_slice(&self.0))
+    }
+}
+
+impl std::fmt::Debug for RecoveryKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecoveryKey").finish()
+    }
+}
+
+impl hex::FromHex for RecoveryKey {
+    type Error = hex::FromHexError;
+
+    fn from_hex<T: AsRef<[u8]>>(hex: T) -> Result<Self, Self::Error> {
+        <[u8; 32] as hex::FromHex>::from_hex(hex).map(Self)
+    }
+}
+
+impl std::str::FromStr for RecoveryKey {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        hex::FromHex::from_hex(s)
+    }
+}
+
+// The key is sent to the anchoring node in the body of the export request, so it
+// is serialized as a hex string.
+impl serde::Serialize for RecoveryKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(self.0))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for RecoveryKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = <String as serde::Deserialize>::deserialize(deserializer)?;
+        hex.parse().map_err(D::Error::custom)
+    }
+}
+
+/// Anchoring wallet which has been used by the anchoring chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryWallet {
+    /// Latest anchoring configuration with this wallet.
+    pub config: Config,
+    /// P2WSH address of the wallet.
+    pub address: btc::Address,
+    /// Hex-encoded redeem script of the wallet.
+    pub redeem_script: String,
+    /// Total weight of the signatures required to spend the wallet outputs.
+    pub quorum: usize,
+}
+
+impl RecoveryWallet {
+    fn new(config: Config) -> Self {
+        Self {
+            address: config.anchoring_address(),
+            redeem_script: hex::encode(config.redeem_script().as_ref().as_bytes()),
+            quorum: config.signature_quorum(),
+            config,
+        }
+    }
+}
+
+/// Disaster recovery bundle of the anchoring wallets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryBundle {
+    /// Index of the export in the `Schema::recovery_exports` list.
+    pub export_index: u64,
+    /// Export authorization.
+    pub export: AuthorizedRecoveryExport,
+    /// Height of the blockchain state from which the bundle has been created.
+    pub snapshot_height: Height,
+    /// Anchoring wallets in order of their activation, including the following one.
+    pub wallets: Vec<RecoveryWallet>,
+    /// Latest anchoring transaction, its first output holds the anchoring funds.
+    pub latest_anchoring_transaction: Option<btc::Transaction>,
+    /// Funding transaction which has not yet been spent by the anchoring chain.
+    pub unspent_funding_transaction: Option<btc::Transaction>,
+    /// Steps to spend the anchoring funds with the escrowed Bitcoin keys.
+    pub instructions: Vec<String>,
+}
+
+impl RecoveryBundle {
+    /// Creates a recovery bundle from the given anchoring schema.
+    pub fn new<T: Access>(
+        schema: &Schema<T>,
+        export_index: u64,
+        export: AuthorizedRecoveryExport,
+        snapshot_height: Height,
+    ) -> Self {
+        Self::from_parts(
+            export_index,
+            export,
+            snapshot_height,
+            schema.config_history(),
+            schema.last_anchoring_transaction(),
+            schema.unspent_funding_transaction(),
+        )
+    }
+
+    /// Creates a recovery bundle from the given configuration history and the unspent
+    /// transactions of the anchoring wallet.
+    ///
+    /// # Panics
+    ///
+    /// - If the configuration history is empty.
+    pub fn from_parts(
+        export_index: u64,
+        export: AuthorizedRecoveryExport,
+        snapshot_height: Height,
+        config_history: impl IntoIterator<Item = Config>,
+        latest_anchoring_transaction: Option<btc::Transaction>,
+        unspent_funding_transaction: Option<btc::Transaction>,
+    ) -> Self {
+        let mut wallets: Vec<RecoveryWallet> = Vec::new();
+        for config in config_history {
+            let wallet = RecoveryWallet::new(config);
+            // Configurations which do not change the anchoring keys share the same wallet.
+            match wallets.last_mut() {
+                Some(last) if last.address == wallet.address => *last = wallet,
+                _ => wallets.push(wallet),
+            }
+        }
+        assert!(
+            !wallets.is_empty(),
+            "Configuration history of the recovery bundle must not be empty"
+        );
+
+        let mut bundle = Self {
+            export_index,
+            export,
+            snapshot_height,
+            wallets,
+            latest_anchoring_transaction,
+            unspent_funding_transaction,
+            instructions: Vec::new(),
+        };
+        bundle.instructions = bundle.create_instructions();
+        bundle
+    }
+
+    fn create_instructions(&self) -> Vec<String> {
+        // During the transition the funds are still held by the actual wallet, thus
+        // the wallet is found by the output of the latest anchoring transaction.
+        let funds_script = self
+            .latest_anchoring_transaction
+            .as_ref()
+            .and_then(|tx| tx.anchoring_metadata())
+            .map(|(script, _)| script.clone());
+        let wallet = self
+            .wallets
+            .iter()
+            .rev()
+            .find(|wallet| Some(wallet.config.anchoring_out_script()) == funds_script)
+            .unwrap_or_else(|| self.wallets.last().unwrap());
+        let mut instructions = vec![format!(
+            "Retrieve the escrowed Bitcoin private keys with the total weight of at least {} \
+             of {} corresponding to the `bitcoin_key` fields of the `anchoring_keys` of \
+             the wallet {}.",
+            wallet.quorum,
+            wallet.config.total_key_weight(),
+            wallet.address
+        )];
+
+        let mut outputs = Vec::new();
+        if let Some(tx) = self.latest_anchoring_transaction.as_ref() {
+            outputs.push(format!(
+                "output 0 of the latest anchoring transaction {}",
+                tx.id()
+            ));
+        }
+        if let Some(tx) = self.unspent_funding_transaction.as_ref() {
+            outputs.push(format!(
+                "outputs to the wallet address of the funding transaction {}",
+                tx.id()
+            ));
+        }
+        if outputs.is_empty() {
+            instructions.push("The wallet has no known unspent outputs.".to_owned());
+        } else {
+            instructions.push(format!("Spendable funds: {}.", outputs.join(", ")));
+        }
+
+        instructions.push(
+            "Spend these outputs with the P2WSH witness consisting of an empty item, the \
+             SIGHASH_ALL signatures in the order of the `anchoring_keys` list and the \
+             `redeem_script` of the wallet."
+                .to_owned(),
+        );
+        if let Some(fee_payer_address) = wallet.config.fee_payer_address() {
+            instructions.push(format!(
+                "The fee wallet {} is a P2WPKH wallet controlled solely by the escrowed \
+                 `fee_payer_key`.",
+                fee_payer_address
+            ));
+        }
+        if self.wallets.len() > 1 {
+            instructions.push(
+                "Other wallets are listed for auditing the transitions of the anchoring chain, \
+                 they hold no funds of the anchoring chain."
+                    .to_owned(),
+            );
+        }
+        instructions
+    }
+}
+
+/// Disaster recovery bundle encrypted by the escrow key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncryptedRecoveryBundle {
+    /// Index of the export in the `Schema::recovery_exports` list.
+    pub export_index: u64,
+    /// Height of the blockchain state from which the bundle has been created.
+    pub snapshot_height: Height,
+    /// Hex-encoded AES-256-GCM nonce and ciphertext of the JSON-serialized bundle.
+    pub data: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::{crypto::Hash, helpers::Height};
+
+    use super::{AuthorizedRecoveryExport, RecoveryBundle, RecoveryError, RecoveryKey};
+
+    fn bundle() -> RecoveryBundle {
+        RecoveryBundle {
+            export_index: 0,
+            export: AuthorizedRecoveryExport {
+                escrow_key_hash: Hash::zero(),
+                height: 1,
+            },
+            snapshot_height: Height(2),
+            wallets: Vec::new(),
+            latest_anchoring_transaction: None,
+            unspent_funding_transaction: None,
+            instructions: vec!["Test".to_owned()],
+        }
+    }
+
+    #[test]
+    fn test_bundle_encryption_roundtrip() {
+        let key = RecoveryKey::random();
+        let encrypted = key.encrypt(&bundle());
+        assert_eq!(encrypted.snapshot_height, Height(2));
+        assert_eq!(key.decrypt(&encrypted).unwrap(), bundle());
+    }
+
+    #[test]
+    fn test_bundle_decryption_errors() {
+        let key = RecoveryKey::random();
+        let mut encrypted = key.encrypt(&bundle());
+        assert_eq!(
+            RecoveryKey::random().decrypt(&encrypted),
+            Err(RecoveryError::DecryptionFailed)
+        );
+
+        encrypted.data.truncate(20);
+        assert_eq!(
+            key.decrypt(&encrypted),
+            Err(RecoveryError::DecryptionFailed)
+        );
+    }
+
+    #[test]
+    fn test_recovery_key_serde() {
+        let key = RecoveryKey::new([7; 32]);
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(json, format!("\"{}\"", hex::encode([7; 32])));
+        assert_eq!(serde_json::from_str::<RecoveryKey>(&json).unwrap(), key);
+        assert_ne!(key.hash(), RecoveryKey::random().hash());
+    }
+}
@@ -15,15 +15,25 @@
 use exonum::{
     helpers::ValidateInput,
     merkledb::BinaryValue,
-    runtime::{CommonError, ExecutionContext, ExecutionError},
+    runtime::{
+        migrations::{InitMigrationError, LinearMigrations, MigrateData, MigrationScript},
+        versioning::Version,
+        CommonError, ExecutionContext, ExecutionError, ExecutionFail,
+    },
 };
 use exonum_derive::{ServiceDispatcher, ServiceFactory};
-use exonum_rust_runtime::{api::ServiceApiBuilder, Service};
+use exonum_rust_runtime::{api::ServiceApiBuilder, Service, ServiceFactory};
 use exonum_supervisor::Configure;
+use log::error;
 
 use crate::{
     api,
-    blockchain::{BtcAnchoringInterface, Schema},
+    blockchain::{
+        errors::Error,
+        migrations,
+        transactions::{check_signing_progress, finalize_anchoring_proposal},
+        BtcAnchoringInterface, Schema,
+    },
     config::Config,
     proto,
 };
@@ -31,9 +41,21 @@ use crate::{
 /// Bitcoin anchoring service implementation for the Exonum blockchain.
 #[derive(ServiceFactory, ServiceDispatcher, Debug, Clone, Copy)]
 #[service_dispatcher(implements("BtcAnchoringInterface", raw = "Configure<Params = Config>"))]
-#[service_factory(proto_sources = "proto")]
+#[service_factory(proto_sources = "proto", with_migrations)]
 pub struct BtcAnchoringService;
 
+impl MigrateData for BtcAnchoringService {
+    fn migration_scripts(
+        &self,
+        start_version: &Version,
+    ) -> Result<Vec<MigrationScript>, InitMigrationError> {
+        LinearMigrations::new(self.artifact_id().version)
+            .set_min_version(Version::new(1, 0, 0))
+            .add_script(Version::new(1, 1, 0), migrations::migrate_from_v1_0)
+            .select(start_version)
+    }
+}
+
 impl Service for BtcAnchoringService {
     fn initialize(
         &self,
@@ -45,9 +67,24 @@ impl Service for BtcAnchoringService {
             .and_then(ValidateInput::into_validated)
             .map_err(CommonError::malformed_arguments)?;
 
-        Schema::new(context.service_data())
-            .actual_config
-            .set(config);
+        let height = context.data().for_core().next_height();
+        Schema::new(context.service_data()).set_actual_config(config, height);
+        Ok(())
+    }
+
+    fn after_transactions(&self, context: ExecutionContext<'_>) -> Result<(), ExecutionError> {
+        finalize_anchoring_proposal(&context);
+        check_signing_progress(&context);
+
+        let mut schema = Schema::new(context.service_data());
+        let height = context.data().for_core().next_height();
+        if let Err(txid) = schema.apply_pending_funding_transactions(height) {
+            error!(
+                "Funding transactions confirmed in the block {} have been rejected, \
+                 since funding transaction {} is unsuitable.",
+                height, txid
+            );
+        }
         Ok(())
     }
 
@@ -69,13 +106,83 @@ impl Configure for BtcAnchoringService {
             .as_supervisor()
             .ok_or(CommonError::UnauthorizedCaller)?;
 
-        params.validate().map_err(CommonError::malformed_arguments)
+        params
+            .validate()
+            .map_err(CommonError::malformed_arguments)?;
+
+        // The anchoring chain can't be reconfigured after the final anchor has been requested.
+        let schema = Schema::new(context.service_data());
+        if schema.final_anchoring_height.exists() {
+            return Err(Error::AnchoringChainShutDown.into());
+        }
+        let actual_config = schema.actual_config();
+        if params.final_anchor.is_some()
+            && (schema.following_config().is_some()
+                || params.anchoring_address() != actual_config.anchoring_address())
+        {
+            return Err(Error::AnchoringChainShutDown.with_description(
+                "The final anchor can't be requested together with the anchoring address change",
+            ));
+        }
+
+        if let Some(emergency_brake) = params.emergency_brake.as_ref() {
+            let validators_count = context
+                .data()
+                .for_core()
+                .consensus_config()
+                .validator_keys
+                .len();
+            if emergency_brake.required_approvals as usize > validators_count {
+                return Err(CommonError::malformed_arguments(format!(
+                    "The emergency brake requires {} approvals, but there are only {} validators",
+                    emergency_brake.required_approvals, validators_count
+                )));
+            }
+        }
+
+        // Make sure that each new Bitcoin key has been attested by its owner.
+        for keys in &params.anchoring_keys {
+            if actual_config.anchoring_keys.contains(keys) {
+                continue;
+            }
+            if schema.attested_bitcoin_key(&keys.service_key) != Some(keys.bitcoin_key) {
+                return Err(Error::MissingKeyAttestation.with_description(format!(
+                    "Bitcoin key {} is not attested by the owner of service key {}",
+                    keys.bitcoin_key, keys.service_key
+                )));
+            }
+        }
+
+        // Derived keys are never reused, so each anchoring address change gets fresh keys.
+        for keys in &params.anchoring_keys {
+            let derivation = match keys.derivation.as_ref() {
+                Some(derivation) => derivation,
+                None => continue,
+            };
+            let previous = actual_config
+                .anchoring_keys
+                .iter()
+                .find(|actual| actual.service_key == keys.service_key)
+                .filter(|actual| actual.bitcoin_key != keys.bitcoin_key)
+                .and_then(|actual| actual.derivation.as_ref())
+                .filter(|previous| previous.account_xpub == derivation.account_xpub);
+            if let Some(previous) = previous {
+                if derivation.index < previous.index {
+                    return Err(CommonError::malformed_arguments(format!(
+                        "Bitcoin key {} is derived with the index {}, which is less than \
+                         the index {} of the actual key",
+                        keys.bitcoin_key, derivation.index, previous.index
+                    )));
+                }
+            }
+        }
+        Ok(())
     }
 
     fn apply_config(
         &self,
         context: ExecutionContext<'_>,
-        params: Self::Params,
+        mut params: Self::Params,
     ) -> Result<(), ExecutionError> {
         context
             .caller()
@@ -83,14 +190,33 @@ impl Configure for BtcAnchoringService {
             .ok_or(CommonError::UnauthorizedCaller)?;
 
         let mut schema = Schema::new(context.service_data());
+        // Applying a new configuration lifts the pause of the anchoring caused by the excess
+        // of the fee budget.
+        schema.lift_fee_budget_breach(context.data().for_core().height());
+        // The recovery bundle export is a one-off action, so the authorization is recorded
+        // separately instead of being kept in the configuration.
+        if let Some(export) = params.recovery_export.take() {
+            let height = context.data().for_core().height();
+            schema.authorize_recovery_export(export, height);
+        }
+        if params.final_anchor.is_some() {
+            // Anchor the latest committed block regardless of the anchoring interval.
+            let height = context.data().for_core().height();
+            schema.final_anchoring_height.set(height.0);
+        }
+
         if schema.actual_config().anchoring_address() == params.anchoring_address() {
             // There are no changes in the anchoring address, so we just apply the config
             // immediately.
-            schema.actual_config.set(params);
+            let height = context.data().for_core().next_height();
+            schema.set_actual_config(params, height);
         } else {
             // Set the config as the next one, which will become an actual after the transition
             // of the anchoring chain to the following address.
+            let height = context.data().for_core().height();
             schema.following_config.set(params);
+            schema.transition_start_height.set(height.0);
+            schema.transition_transaction.remove();
         }
         Ok(())
     }
@@ -0,0 +1,100 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Amounts of bitcoins in the responses of the Bitcoin nodes and Electrum servers.
+
+use bitcoin::util::amount::Denomination;
+use serde::de::{Deserialize, Deserializer, Error as _};
+use serde_derive::Deserialize;
+
+/// Number of satoshis in one bitcoin.
+const SATOSHIS_PER_BTC: f64 = 100_000_000.0;
+
+/// Amount of bitcoins, which is represented in BTC in the responses and stored
+/// in satoshis.
+///
+/// The amounts are converted to satoshis right after the parsing of the response, so
+/// the values are never summed or divided as floating point numbers. The value may be
+/// either a JSON number or a decimal string, which is preferable since it retains
+/// the exact value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct BtcAmount(bitcoin::Amount);
+
+impl BtcAmount {
+    /// Returns the amount in satoshis.
+    pub fn as_sat(self) -> u64 {
+        self.0.as_sat()
+    }
+}
+
+impl<'de> Deserialize<'de> for BtcAmount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(f64),
+            Decimal(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            // The value is rounded to the nearest satoshi, so the float noise doesn't affect
+            // the result. All valid amounts are below 2^53 satoshis, thus they are exact.
+            Repr::Number(btc) if btc.is_finite() && btc >= 0.0 => {
+                let satoshis = (btc * SATOSHIS_PER_BTC).round();
+                Ok(BtcAmount(bitcoin::Amount::from_sat(satoshis as u64)))
+            }
+            Repr::Number(btc) => Err(D::Error::custom(format!("Invalid BTC amount: {}", btc))),
+            Repr::Decimal(btc) => bitcoin::Amount::from_str_in(&btc, Denomination::Bitcoin)
+                .map(BtcAmount)
+                .map_err(D::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::BtcAmount;
+
+    fn parse(value: serde_json::Value) -> Result<u64, serde_json::Error> {
+        serde_json::from_value::<BtcAmount>(value).map(BtcAmount::as_sat)
+    }
+
+    #[test]
+    fn btc_amount_from_number() {
+        assert_eq!(parse(json!(0.00001)).unwrap(), 1_000);
+        assert_eq!(parse(json!(0.1 + 0.2)).unwrap(), 30_000_000);
+        assert_eq!(
+            parse(json!(20_999_999.9769)).unwrap(),
+            2_099_999_997_690_000
+        );
+        assert_eq!(parse(json!(0)).unwrap(), 0);
+    }
+
+    #[test]
+    fn btc_amount_from_decimal_string() {
+        assert_eq!(parse(json!("0.00012345")).unwrap(), 12_345);
+        assert_eq!(parse(json!("21000000")).unwrap(), 2_100_000_000_000_000);
+        // Amounts with the fractions of satoshi are rejected.
+        parse(json!("0.000000001")).unwrap_err();
+    }
+
+    #[test]
+    fn btc_amount_rejects_negative_values() {
+        parse(json!(-0.1)).unwrap_err();
+        parse(json!("-0.1")).unwrap_err();
+        parse(json!("not an amount")).unwrap_err();
+    }
+}
@@ -0,0 +1,544 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Authentication of the requests to the private API of the anchoring node.
+//!
+//! The private API of the Exonum node has no authentication of its own, so it is
+//! protected only by listening on the private address. To run the sync utility on
+//! a different host, the anchoring service verifies the private API requests itself
+//! if the credentials of the API scope are set by the environment variables of the node.
+//! The signing and the management scopes have independent credentials (see
+//! [`PrivateApiScope`]), so the sync utility can't manage the funds with the secret
+//! of the signing scope. The nodes of the older service versions can be placed behind
+//! the authenticating proxy of the sync utility instead.
+//!
+//! The requests are authenticated with the bearer token and/or signed with
+//! the HMAC-SHA256 of the request key over the timestamp, the nonce, the method,
+//! the path with the query and the body separated by dots. The signature is sent in
+//! the `X-Anchoring-Signature` header together with the `X-Anchoring-Timestamp` and
+//! `X-Anchoring-Nonce` ones. The requests with the timestamps too far from the node
+//! clock are rejected, as well as the ones with the nonce which has already been used
+//! within the allowed clock skew, so the signed requests cannot be replayed.
+//!
+//! The mutual TLS authentication is out of scope: the private API is served over
+//! plain HTTP, so the TLS with the client certificates should be terminated by
+//! the reverse proxy in front of the node.
+//!
+//! [`PrivateApiScope`]: enum.PrivateApiScope.html
+
+use anyhow::{anyhow, ensure};
+use bitcoin_hashes::{
+    hmac::{Hmac, HmacEngine},
+    sha256, Hash as _, HashEngine as _,
+};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION},
+    Method, Request,
+};
+use thiserror::Error;
+
+use std::{
+    collections::HashMap,
+    env,
+    fmt::{self, Debug},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::{SIGNATURE_HEADER, TIMESTAMP_HEADER};
+
+/// Header with the random nonce of the private API request.
+pub const NONCE_HEADER: &str = "X-Anchoring-Nonce";
+/// Environment variable with the bearer token required by the signing scope of the private
+/// API of the node.
+pub const SIGNING_API_TOKEN_ENV: &str = "BTC_ANCHORING_SIGNING_API_TOKEN";
+/// Environment variable with the hex-encoded key of the request signatures required
+/// by the signing scope of the private API of the node.
+pub const SIGNING_API_KEY_ENV: &str = "BTC_ANCHORING_SIGNING_API_KEY";
+/// Environment variable with the bearer token required by the management scope of
+/// the private API of the node.
+pub const MANAGEMENT_API_TOKEN_ENV: &str = "BTC_ANCHORING_MANAGEMENT_API_TOKEN";
+/// Environment variable with the hex-encoded key of the request signatures required
+/// by the management scope of the private API of the node.
+pub const MANAGEMENT_API_KEY_ENV: &str = "BTC_ANCHORING_MANAGEMENT_API_KEY";
+
+/// Length of the random request nonce in bytes.
+const NONCE_LEN: usize = 16;
+
+/// Errors of the private API request authentication.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum AuthError {
+    /// The bearer token is missing or doesn't match the expected one.
+    #[error("Invalid or missing bearer token")]
+    InvalidToken,
+    /// The request signature, its timestamp or nonce is missing.
+    #[error("Request signature is missing")]
+    MissingSignature,
+    /// The request timestamp is malformed or too far from the actual time.
+    #[error("Request timestamp is malformed or outdated")]
+    InvalidTimestamp,
+    /// The request signature doesn't match the request.
+    #[error("Request signature is invalid")]
+    InvalidSignature,
+    /// The request with the same nonce has already been accepted.
+    #[error("Request has already been accepted")]
+    ReplayedRequest,
+}
+
+/// Scope of the private API of the anchoring service with its own credentials.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivateApiScope {
+    /// Endpoints used by the sync utility: signing of the anchoring proposals, reports
+    /// of the Bitcoin network state and the read-only endpoints.
+    Signing,
+    /// Endpoints of the funds and configuration management: funding transactions,
+    /// key attestations, recovery bundles and approvals of the special transactions.
+    Management,
+}
+
+impl PrivateApiScope {
+    /// Environment variable with the bearer token required by the scope.
+    pub fn token_env(self) -> &'static str {
+        match self {
+            PrivateApiScope::Signing => SIGNING_API_TOKEN_ENV,
+            PrivateApiScope::Management => MANAGEMENT_API_TOKEN_ENV,
+        }
+    }
+
+    /// Environment variable with the hex-encoded key of the request signatures required
+    /// by the scope.
+    pub fn key_env(self) -> &'static str {
+        match self {
+            PrivateApiScope::Signing => SIGNING_API_KEY_ENV,
+            PrivateApiScope::Management => MANAGEMENT_API_KEY_ENV,
+        }
+    }
+}
+
+impl fmt::Display for PrivateApiScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrivateApiScope::Signing => f.write_str("signing"),
+            PrivateApiScope::Management => f.write_str("management"),
+        }
+    }
+}
+
+/// Shared secret of the private API requests authentication, which is used both by
+/// the API client to authenticate the requests and by the node or proxy to verify them.
+///
+/// The clones share the nonces of the accepted requests.
+#[derive(Clone)]
+pub struct PrivateApiAuth {
+    auth_token: Option<String>,
+    request_key: Option<Vec<u8>>,
+    max_clock_skew: Duration,
+    /// Nonces of the accepted requests along with their timestamps.
+    seen_nonces: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl PrivateApiAuth {
+    /// Default maximum difference between the request timestamp and the actual time.
+    pub const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::from_secs(30);
+
+    /// Creates an authentication without credentials, which accepts any request.
+    pub fn new() -> Self {
+        Self {
+            auth_token: None,
+            request_key: None,
+            max_clock_skew: Self::DEFAULT_MAX_CLOCK_SKEW,
+            seen_nonces: Arc::default(),
+        }
+    }
+
+    /// Reads the authentication required by the given scope of the private API of the node
+    /// from its environment variables. Returns `None` if neither of them is set.
+    pub fn from_env(scope: PrivateApiScope) -> anyhow::Result<Option<Self>> {
+        let auth_token = env_var(scope.token_env())?;
+        let request_key = env_var(scope.key_env())?
+            .map(hex::decode)
+            .transpose()
+            .map_err(|e| anyhow!("Invalid `{}` value: {}", scope.key_env(), e))?;
+        if auth_token.is_none() && request_key.is_none() {
+            return Ok(None);
+        }
+
+        let mut auth = Self::new();
+        auth.auth_token = auth_token;
+        auth.request_key = request_key;
+        Ok(Some(auth))
+    }
+
+    /// Sets the bearer token of the requests.
+    pub fn with_auth_token(mut self, auth_token: impl Into<String>) -> Self {
+        self.auth_token = Some(auth_token.into());
+        self
+    }
+
+    /// Sets the key of the HMAC-SHA256 signatures of the requests.
+    pub fn with_request_key(mut self, request_key: impl Into<Vec<u8>>) -> Self {
+        self.request_key = Some(request_key.into());
+        self
+    }
+
+    /// Sets the maximum difference between the request timestamp and the actual time.
+    pub fn with_max_clock_skew(mut self, max_clock_skew: Duration) -> Self {
+        self.max_clock_skew = max_clock_skew;
+        self
+    }
+
+    /// Adds the authentication headers to the request.
+    pub fn authenticate(&self, request: &mut Request) -> anyhow::Result<()> {
+        let path = path_and_query(request.url().path(), request.url().query());
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .unwrap_or_default()
+            .to_vec();
+        let headers = self.headers(request.method(), &path, &body, unix_time()?)?;
+        request.headers_mut().extend(headers);
+        Ok(())
+    }
+
+    /// Verifies the authentication headers of the request with the given method,
+    /// path with the query, headers and body.
+    pub fn verify(
+        &self,
+        method: &Method,
+        path_and_query: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<(), AuthError> {
+        self.verify_with(
+            method,
+            path_and_query,
+            |name| header_str(headers, name),
+            body,
+        )
+    }
+
+    /// Verifies the authentication headers of the request, which are obtained by their
+    /// names with the given function. This allows to verify the requests of the HTTP
+    /// frameworks with their own header maps.
+    pub fn verify_with<'a>(
+        &self,
+        method: &Method,
+        path_and_query: &str,
+        header: impl Fn(&str) -> Option<&'a str>,
+        body: &[u8],
+    ) -> Result<(), AuthError> {
+        let now = unix_time().map_err(|_| AuthError::InvalidTimestamp)?;
+        self.verify_at(method, path_and_query, &header, body, now)
+    }
+
+    fn headers(
+        &self,
+        method: &Method,
+        path_and_query: &str,
+        body: &[u8],
+        timestamp: u64,
+    ) -> anyhow::Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        if let Some(auth_token) = self.auth_token.as_ref() {
+            let value = HeaderValue::from_str(&format!("Bearer {}", auth_token))?;
+            headers.insert(AUTHORIZATION, value);
+        }
+        if let Some(request_key) = self.request_key.as_ref() {
+            let timestamp = timestamp.to_string();
+            let nonce = hex::encode(rand::random::<[u8; NONCE_LEN]>());
+            let signature = request_signature(
+                request_key,
+                &timestamp,
+                &nonce,
+                method,
+                path_and_query,
+                body,
+            );
+            headers.insert(
+                HeaderName::from_bytes(TIMESTAMP_HEADER.as_bytes())?,
+                HeaderValue::from_str(&timestamp)?,
+            );
+            headers.insert(
+                HeaderName::from_bytes(NONCE_HEADER.as_bytes())?,
+                HeaderValue::from_str(&nonce)?,
+            );
+            headers.insert(
+                HeaderName::from_bytes(SIGNATURE_HEADER.as_bytes())?,
+                HeaderValue::from_str(&signature)?,
+            );
+        }
+        Ok(headers)
+    }
+
+    fn verify_at<'a>(
+        &self,
+        method: &Method,
+        path_and_query: &str,
+        header: &dyn Fn(&str) -> Option<&'a str>,
+        body: &[u8],
+        now: u64,
+    ) -> Result<(), AuthError> {
+        if let Some(auth_token) = self.auth_token.as_ref() {
+            let expected = format!("Bearer {}", auth_token);
+            let actual = header(AUTHORIZATION.as_str()).unwrap_or_default();
+            if !constant_time_eq(actual.as_bytes(), expected.as_bytes()) {
+                return Err(AuthError::InvalidToken);
+            }
+        }
+
+        if let Some(request_key) = self.request_key.as_ref() {
+            let timestamp = header(TIMESTAMP_HEADER).ok_or(AuthError::MissingSignature)?;
+            let nonce = header(NONCE_HEADER).ok_or(AuthError::MissingSignature)?;
+            let signature = header(SIGNATURE_HEADER).ok_or(AuthError::MissingSignature)?;
+            let request_time = timestamp
+                .parse::<u64>()
+                .map_err(|_| AuthError::InvalidTimestamp)?;
+            let skew = if request_time > now {
+                request_time - now
+            } else {
+                now - request_time
+            };
+            if skew > self.max_clock_skew.as_secs() {
+                return Err(AuthError::InvalidTimestamp);
+            }
+
+            let expected =
+                request_signature(request_key, timestamp, nonce, method, path_and_query, body);
+            if !constant_time_eq(signature.as_bytes(), expected.as_bytes()) {
+                return Err(AuthError::InvalidSignature);
+            }
+            self.accept_nonce(nonce, request_time, now)?;
+        }
+        Ok(())
+    }
+
+    /// Remembers the nonce of the request, unless it has already been accepted.
+    fn accept_nonce(&self, nonce: &str, request_time: u64, now: u64) -> Result<(), AuthError> {
+        let max_clock_skew = self.max_clock_skew.as_secs();
+        let mut seen_nonces = self.seen_nonces.lock().unwrap();
+        // The requests outside of the allowed clock skew are rejected by their
+        // timestamps, so there is no need to remember their nonces.
+        seen_nonces.retain(|_, time| time.saturating_add(max_clock_skew) >= now);
+        if seen_nonces.contains_key(nonce) {
+            return Err(AuthError::ReplayedRequest);
+        }
+        seen_nonces.insert(nonce.to_owned(), request_time);
+        Ok(())
+    }
+}
+
+impl Default for PrivateApiAuth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for PrivateApiAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Credentials are intentionally omitted.
+        f.debug_struct("PrivateApiAuth")
+            .field("has_auth_token", &self.auth_token.is_some())
+            .field("has_request_key", &self.request_key.is_some())
+            .field("max_clock_skew", &self.max_clock_skew)
+            .finish()
+    }
+}
+
+fn env_var(name: &str) -> anyhow::Result<Option<String>> {
+    match env::var(name) {
+        Ok(value) => {
+            ensure!(!value.is_empty(), "Empty `{}` value", name);
+            Ok(Some(value))
+        }
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(anyhow!("Invalid `{}` value: {}", name, e)),
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn unix_time() -> anyhow::Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+fn path_and_query(path: &str, query: Option<&str>) -> String {
+    match query {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_owned(),
+    }
+}
+
+/// Computes the hex-encoded HMAC-SHA256 signature of the private API request.
+fn request_signature(
+    request_key: &[u8],
+    timestamp: &str,
+    nonce: &str,
+    method: &Method,
+    path_and_query: &str,
+    body: &[u8],
+) -> String {
+    let mut engine = HmacEngine::<sha256::Hash>::new(request_key);
+    for part in &[timestamp, nonce, method.as_str(), path_and_query] {
+        engine.input(part.as_bytes());
+        engine.input(b".");
+    }
+    engine.input(body);
+    hex::encode(Hmac::<sha256::Hash>::from_engine(engine).into_inner())
+}
+
+/// Compares the byte strings in time independent of their content.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{
+        header::{HeaderMap, HeaderName},
+        Method,
+    };
+
+    use super::{header_str, AuthError, PrivateApiAuth, NONCE_HEADER};
+
+    const NOW: u64 = 1_600_000_000;
+
+    fn verify_at(
+        auth: &PrivateApiAuth,
+        method: &Method,
+        path_and_query: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: u64,
+    ) -> Result<(), AuthError> {
+        let header = |name: &str| header_str(headers, name);
+        auth.verify_at(method, path_and_query, &header, body, now)
+    }
+
+    #[test]
+    fn private_api_auth_accepts_signed_requests() {
+        let auth = PrivateApiAuth::new()
+            .with_auth_token("token")
+            .with_request_key(b"secret".to_vec());
+        let path = "/api/services/anchoring/signing/sign-input";
+        let headers = auth.headers(&Method::POST, path, b"{}", NOW).unwrap();
+
+        assert_eq!(
+            verify_at(&auth, &Method::POST, path, &headers, b"{}", NOW + 10),
+            Ok(())
+        );
+        // Any change of the request invalidates the signature.
+        assert_eq!(
+            verify_at(&auth, &Method::POST, path, &headers, b"[]", NOW),
+            Err(AuthError::InvalidSignature)
+        );
+        assert_eq!(
+            verify_at(&auth, &Method::GET, path, &headers, b"{}", NOW),
+            Err(AuthError::InvalidSignature)
+        );
+        assert_eq!(
+            verify_at(
+                &auth,
+                &Method::POST,
+                "/api/services/anchoring/config",
+                &headers,
+                b"{}",
+                NOW
+            ),
+            Err(AuthError::InvalidSignature)
+        );
+        // Outdated requests are rejected.
+        assert_eq!(
+            verify_at(&auth, &Method::POST, path, &headers, b"{}", NOW + 60),
+            Err(AuthError::InvalidTimestamp)
+        );
+    }
+
+    #[test]
+    fn private_api_auth_rejects_replayed_requests() {
+        let client_auth = PrivateApiAuth::new().with_request_key(b"secret".to_vec());
+        let auth = PrivateApiAuth::new().with_request_key(b"secret".to_vec());
+        let path = "/api/services/anchoring/signing/sign-input";
+
+        let headers = client_auth
+            .headers(&Method::POST, path, b"{}", NOW)
+            .unwrap();
+        assert_eq!(
+            verify_at(&auth, &Method::POST, path, &headers, b"{}", NOW),
+            Ok(())
+        );
+        // The same request is rejected even if it is verified by a clone of the authentication.
+        assert_eq!(
+            verify_at(&auth.clone(), &Method::POST, path, &headers, b"{}", NOW + 1),
+            Err(AuthError::ReplayedRequest)
+        );
+        // The identical request with another nonce is accepted.
+        let other_headers = client_auth
+            .headers(&Method::POST, path, b"{}", NOW)
+            .unwrap();
+        assert_eq!(
+            verify_at(&auth, &Method::POST, path, &other_headers, b"{}", NOW + 1),
+            Ok(())
+        );
+
+        // The nonce cannot be replaced, since it is covered by the signature.
+        let mut forged_headers = headers.clone();
+        forged_headers.insert(
+            HeaderName::from_bytes(NONCE_HEADER.as_bytes()).unwrap(),
+            other_headers[NONCE_HEADER].clone(),
+        );
+        assert_eq!(
+            verify_at(&auth, &Method::POST, path, &forged_headers, b"{}", NOW + 1),
+            Err(AuthError::InvalidSignature)
+        );
+        // The request without the nonce is rejected.
+        let mut headers = headers;
+        headers.remove(NONCE_HEADER);
+        assert_eq!(
+            verify_at(&auth, &Method::POST, path, &headers, b"{}", NOW + 1),
+            Err(AuthError::MissingSignature)
+        );
+    }
+
+    #[test]
+    fn private_api_auth_rejects_unauthenticated_requests() {
+        let auth = PrivateApiAuth::new()
+            .with_auth_token("token")
+            .with_request_key(b"secret".to_vec());
+        let path = "/api/services/anchoring/config";
+        assert_eq!(
+            verify_at(&auth, &Method::GET, path, &HeaderMap::new(), b"", NOW),
+            Err(AuthError::InvalidToken)
+        );
+
+        let other_auth = PrivateApiAuth::new().with_auth_token("other");
+        let headers = other_auth.headers(&Method::GET, path, b"", NOW).unwrap();
+        assert_eq!(
+            verify_at(&auth, &Method::GET, path, &headers, b"", NOW),
+            Err(AuthError::InvalidToken)
+        );
+
+        let token_auth = PrivateApiAuth::new().with_auth_token("token");
+        let headers = token_auth.headers(&Method::GET, path, b"", NOW).unwrap();
+        assert_eq!(
+            verify_at(&auth, &Method::GET, path, &headers, b"", NOW),
+            Err(AuthError::MissingSignature)
+        );
+        assert_eq!(
+            verify_at(&token_auth, &Method::GET, path, &headers, b"", NOW),
+            Ok(())
+        );
+    }
+}
@@ -15,13 +15,41 @@
 //! Collections of helpers for synchronization with the Bitcoin network.
 
 use async_trait::async_trait;
+use bitcoin_hashes::hex::FromHex;
 use bitcoincore_rpc::RpcApi;
 use jsonrpc::Error as JsonRpcError;
+use serde_derive::{Deserialize, Serialize};
+use tokio::task;
 
-use crate::btc;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::{blockchain::AnchorInclusion, btc};
+
+use super::{
+    amount::BtcAmount,
+    rpc_client::{BitcoinRpcClientBuilder, RpcConnection},
+};
+
+/// Bitcoin Core RPC error code of the unspecified failure.
+const RPC_MISC_ERROR: i32 = -1;
+/// Bitcoin Core RPC error code of the rejected transaction.
+const RPC_VERIFY_REJECTED: i32 = -26;
+/// JSON-RPC error code of the unknown method, which isn't supported by the node version.
+const RPC_METHOD_NOT_FOUND: i32 = -32601;
+/// Bitcoin Core RPC error codes of the node, which is not ready to serve requests:
+/// it is not connected to the network, is in the initial block download or is still
+/// warming up.
+const RPC_NODE_NOT_READY: [i32; 3] = [-9, -10, -28];
 
 /// Status of the transaction in the Bitcoin network.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TransactionStatus {
     /// Transaction is unknown in the Bitcoin network.
     Unknown,
@@ -48,6 +76,121 @@ impl TransactionStatus {
     }
 }
 
+/// Approximate virtual size of the Bitcoin block.
+const BLOCK_VSIZE: u64 = 1_000_000;
+
+/// Distribution of the memory pool transactions by their fee rates.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FeeHistogram {
+    /// Pairs of the fee rate in satoshis per virtual byte and the total virtual size
+    /// of the transactions with this fee rate, in descending order of the fee rates.
+    pub buckets: Vec<(u64, u64)>,
+}
+
+impl FeeHistogram {
+    /// Creates a histogram from the fee rates and virtual sizes of the memory
+    /// pool transactions.
+    pub fn from_entries(entries: impl IntoIterator<Item = (u64, u64)>) -> Self {
+        let mut buckets = BTreeMap::new();
+        for (fee_rate, vsize) in entries {
+            *buckets.entry(fee_rate).or_insert(0) += vsize;
+        }
+        Self {
+            buckets: buckets.into_iter().rev().collect(),
+        }
+    }
+
+    /// Estimates the fee rate in satoshis per virtual byte which is required
+    /// for the transaction to get into the next block.
+    ///
+    /// Returns zero if all the memory pool transactions fit into the next block.
+    pub fn next_block_fee_rate(&self) -> u64 {
+        let mut total_vsize = 0;
+        for &(fee_rate, vsize) in &self.buckets {
+            total_vsize += vsize;
+            if total_vsize >= BLOCK_VSIZE {
+                return fee_rate;
+            }
+        }
+        0
+    }
+}
+
+/// Unspent output of the address watched by the Bitcoin node wallet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnspentOutput {
+    /// Identifier of the transaction containing the output.
+    pub txid: btc::Sha256d,
+    /// Index of the output in the transaction.
+    pub vout: u32,
+    /// Value of the output in satoshis.
+    pub value: u64,
+    /// Number of confirmations of the transaction.
+    pub confirmations: u32,
+}
+
+/// Unspent output returned by the `listunspent` call.
+#[derive(Debug, Deserialize)]
+struct ListUnspentEntry {
+    txid: bitcoin::hash_types::Txid,
+    vout: u32,
+    amount: BtcAmount,
+    confirmations: u32,
+}
+
+/// Result of the descriptor import returned by the `importdescriptors` call.
+#[derive(Debug, Deserialize)]
+struct ImportDescriptorResult {
+    success: bool,
+    #[serde(default)]
+    error: Option<jsonrpc::error::RpcError>,
+}
+
+/// Memory pool entry returned by the verbose `getrawmempool` and `getmempoolentry` calls.
+#[derive(Debug, Deserialize)]
+struct MempoolEntry {
+    vsize: u64,
+    fees: MempoolEntryFees,
+}
+
+#[derive(Debug, Deserialize)]
+struct MempoolEntryFees {
+    /// Transaction fee.
+    base: BtcAmount,
+}
+
+/// Transactions spending the outputs of the memory pool entry returned by
+/// the `getmempoolentry` call.
+#[derive(Debug, Deserialize)]
+struct MempoolSpenders {
+    spentby: Vec<bitcoin::hash_types::Txid>,
+}
+
+/// Spender of the output returned by the `gettxspendingprevout` call.
+#[derive(Debug, Deserialize)]
+struct SpendingPrevout {
+    #[serde(default)]
+    spendingtxid: Option<bitcoin::hash_types::Txid>,
+}
+
+/// Relay fees returned by the `getnetworkinfo` call.
+#[derive(Debug, Deserialize)]
+struct NetworkInfoFees {
+    /// Minimal relay fee per 1000 virtual bytes.
+    relayfee: BtcAmount,
+    /// Minimal fee increment for the transaction replacement per 1000 virtual bytes.
+    incrementalfee: BtcAmount,
+}
+
+/// Fee rate estimation returned by the `estimatesmartfee` call.
+#[derive(Debug, Deserialize)]
+struct SmartFeeEstimate {
+    /// Estimated fee rate per 1000 virtual bytes, which is absent if the node
+    /// has not enough data for the estimation.
+    #[serde(default)]
+    feerate: Option<BtcAmount>,
+}
+
 /// Describes communication with the Bitcoin network node.
 #[async_trait]
 pub trait BitcoinRelay {
@@ -60,8 +203,384 @@ pub trait BitcoinRelay {
     ) -> Result<btc::Sha256d, Self::Error>;
     /// Gets status for the transaction with the specified identifier.
     async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error>;
+    /// Checks that the transaction has been rejected by the `send_transaction` method,
+    /// because it conflicts with another transaction in the memory pool.
+    fn is_mempool_conflict(_error: &Self::Error) -> bool {
+        false
+    }
+    /// Checks that the error is caused by the unavailability of the Bitcoin node rather
+    /// than by the request itself, so the same request may succeed with another node.
+    fn is_unavailable(_error: &Self::Error) -> bool {
+        false
+    }
+    /// Finds a transaction in the memory pool, which spends any of the outputs spent
+    /// by the given transaction.
+    async fn find_conflicting_transaction(
+        &self,
+        _transaction: &btc::Transaction,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        Ok(None)
+    }
+    /// Returns the hash and the height of the Bitcoin block containing the transaction
+    /// with the specified identifier, if the transaction is committed.
+    async fn transaction_inclusion(
+        &self,
+        _id: btc::Sha256d,
+    ) -> Result<Option<AnchorInclusion>, Self::Error> {
+        Ok(None)
+    }
+    /// Returns the fee histogram of the memory pool, if the relay supports it.
+    async fn fee_histogram(&self) -> Result<Option<FeeHistogram>, Self::Error> {
+        Ok(None)
+    }
+    /// Returns the relay fee policy of the Bitcoin node, if the relay supports it.
+    async fn relay_fee_policy(&self) -> Result<Option<btc::RelayFeePolicy>, Self::Error> {
+        Ok(None)
+    }
+    /// Returns the fee rate in satoshis per virtual byte sufficient for the transaction
+    /// to be confirmed within the given number of blocks, if the relay supports it
+    /// and has enough data for the estimation.
+    async fn estimate_smart_fee(
+        &self,
+        _confirmation_target: u32,
+    ) -> Result<Option<u64>, Self::Error> {
+        Ok(None)
+    }
+    /// Returns the fee rate in satoshis per virtual byte paid by the transaction with
+    /// the specified identifier, if the transaction is in the memory pool and the relay
+    /// supports it.
+    async fn mempool_fee_rate(&self, _id: btc::Sha256d) -> Result<Option<u64>, Self::Error> {
+        Ok(None)
+    }
+    /// Returns the proof of inclusion of the transaction with the specified identifier
+    /// in the Bitcoin block, if the transaction is committed and the relay supports it.
+    async fn merkle_proof(&self, _id: btc::Sha256d) -> Result<Option<btc::SpvProof>, Self::Error> {
+        Ok(None)
+    }
+}
+
+// Blocking requests to the Bitcoin Core RPC shared by the `bitcoincore_rpc::Client`
+// and `BitcoinRpcClient` relays.
+
+fn rpc_send_transaction(
+    client: &impl RpcApi,
+    transaction: &btc::Transaction,
+) -> Result<btc::Sha256d, bitcoincore_rpc::Error> {
+    client
+        .send_raw_transaction(transaction.to_string())
+        .map(|txid| btc::Sha256d(txid.into()))
+}
+
+fn rpc_transaction_status(
+    client: &impl RpcApi,
+    id: btc::Sha256d,
+) -> Result<TransactionStatus, bitcoincore_rpc::Error> {
+    match client.get_raw_transaction_verbose(&id.into(), None) {
+        Ok(info) => {
+            let status = match info.confirmations {
+                None => TransactionStatus::Mempool,
+                Some(num) => TransactionStatus::Committed(num),
+            };
+            Ok(status)
+        }
+        // TODO Write more graceful error handling. [ECR-3222]
+        Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => {
+            Ok(TransactionStatus::Unknown)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn rpc_is_mempool_conflict(error: &bitcoincore_rpc::Error) -> bool {
+    match error {
+        bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(e)) => {
+            e.code == RPC_VERIFY_REJECTED && e.message.contains("txn-mempool-conflict")
+        }
+        _ => false,
+    }
+}
+
+fn rpc_is_unavailable(error: &bitcoincore_rpc::Error) -> bool {
+    match error {
+        bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(e)) => {
+            RPC_NODE_NOT_READY.contains(&e.code)
+        }
+        bitcoincore_rpc::Error::JsonRpc(_) | bitcoincore_rpc::Error::Io(_) => true,
+        _ => false,
+    }
+}
+
+fn rpc_find_conflicting_transaction(
+    client: &impl RpcApi,
+    transaction: &btc::Transaction,
+) -> Result<Option<btc::Sha256d>, bitcoincore_rpc::Error> {
+    let id = transaction.id();
+    // Outputs of the confirmed transactions spent by unknown memory pool transactions.
+    let mut spent_in_mempool = Vec::new();
+    for txin in &transaction.0.input {
+        let outpoint = txin.previous_output;
+        let parent_id = serde_json::Value::String(outpoint.txid.to_string());
+        match client.call::<MempoolSpenders>("getmempoolentry", &[parent_id]) {
+            // The memory pool entry of the spent transaction lists the transactions spending
+            // any of its outputs.
+            Ok(entry) => {
+                for spender_id in entry.spentby {
+                    if btc::Sha256d(spender_id.into()) == id {
+                        continue;
+                    }
+                    let spender = match client.get_raw_transaction_hex(&spender_id, None) {
+                        Ok(hex) => hex.parse::<btc::Transaction>(),
+                        // The transaction may have left the memory pool in the meantime.
+                        Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => continue,
+                        Err(e) => return Err(e),
+                    };
+                    if let Ok(spender) = spender {
+                        if spends_outputs(&spender, &[outpoint]) {
+                            return Ok(Some(spender.id()));
+                        }
+                    }
+                }
+            }
+            // The spent transaction is confirmed, so its output is spent in the memory pool
+            // if it is unspent in the chain, but not with the memory pool taken into account.
+            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => {
+                if !rpc_is_unspent(client, outpoint, true)?
+                    && rpc_is_unspent(client, outpoint, false)?
+                {
+                    spent_in_mempool.push(outpoint);
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    if spent_in_mempool.is_empty() {
+        Ok(None)
+    } else {
+        rpc_find_mempool_spender(client, &spent_in_mempool, id)
+    }
 }
 
+/// Checks whether the transaction output is unspent using the `gettxout` call.
+fn rpc_is_unspent(
+    client: &impl RpcApi,
+    outpoint: bitcoin::OutPoint,
+    include_mempool: bool,
+) -> Result<bool, bitcoincore_rpc::Error> {
+    let args = [
+        serde_json::Value::String(outpoint.txid.to_string()),
+        outpoint.vout.into(),
+        include_mempool.into(),
+    ];
+    let txout = client.call::<Option<serde_json::Value>>("gettxout", &args)?;
+    Ok(txout.is_some())
+}
+
+/// Finds the memory pool transaction other than the given one, which spends any of
+/// the given outputs of the confirmed transactions.
+fn rpc_find_mempool_spender(
+    client: &impl RpcApi,
+    outpoints: &[bitcoin::OutPoint],
+    id: btc::Sha256d,
+) -> Result<Option<btc::Sha256d>, bitcoincore_rpc::Error> {
+    let prevouts = outpoints
+        .iter()
+        .map(|outpoint| {
+            serde_json::json!({
+                "txid": outpoint.txid.to_string(),
+                "vout": outpoint.vout,
+            })
+        })
+        .collect();
+    match client.call::<Vec<SpendingPrevout>>(
+        "gettxspendingprevout",
+        &[serde_json::Value::Array(prevouts)],
+    ) {
+        Ok(prevouts) => {
+            return Ok(prevouts
+                .into_iter()
+                .filter_map(|prevout| prevout.spendingtxid)
+                .map(|spender_id| btc::Sha256d(spender_id.into()))
+                .find(|spender_id| *spender_id != id))
+        }
+        Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(ref e)))
+            if e.code == RPC_METHOD_NOT_FOUND => {}
+        Err(e) => return Err(e),
+    }
+
+    // Bitcoin Core before 24.0 can't find the spender of the confirmed output, so
+    // the memory pool is scanned in this case.
+    for txid in client.get_raw_mempool()? {
+        let candidate = match client.get_raw_transaction_hex(&txid, None) {
+            Ok(hex) => hex.parse::<btc::Transaction>(),
+            // The transaction may have left the memory pool in the meantime.
+            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => continue,
+            Err(e) => return Err(e),
+        };
+
+        if let Ok(candidate) = candidate {
+            if spends_outputs(&candidate, outpoints) && candidate.id() != id {
+                return Ok(Some(candidate.id()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn spends_outputs(transaction: &btc::Transaction, outpoints: &[bitcoin::OutPoint]) -> bool {
+    transaction
+        .0
+        .input
+        .iter()
+        .any(|txin| outpoints.contains(&txin.previous_output))
+}
+
+fn rpc_transaction_inclusion(
+    client: &impl RpcApi,
+    id: btc::Sha256d,
+) -> Result<Option<AnchorInclusion>, bitcoincore_rpc::Error> {
+    let block_hash = match client.get_raw_transaction_verbose(&id.into(), None) {
+        Ok(info) => match info.blockhash {
+            Some(block_hash) => block_hash,
+            None => return Ok(None),
+        },
+        Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let header = client.get_block_header_verbose(&block_hash)?;
+    Ok(Some(AnchorInclusion {
+        block_hash: btc::Sha256d(block_hash.as_hash()),
+        block_height: header.height as u64,
+    }))
+}
+
+fn rpc_fee_histogram(client: &impl RpcApi) -> Result<Option<FeeHistogram>, bitcoincore_rpc::Error> {
+    let mempool: HashMap<String, MempoolEntry> =
+        client.call("getrawmempool", &[serde_json::Value::Bool(true)])?;
+    let entries = mempool
+        .values()
+        .filter(|entry| entry.vsize > 0)
+        .map(|entry| (entry.fees.base.as_sat() / entry.vsize, entry.vsize));
+    Ok(Some(FeeHistogram::from_entries(entries)))
+}
+
+fn rpc_relay_fee_policy(
+    client: &impl RpcApi,
+) -> Result<Option<btc::RelayFeePolicy>, bitcoincore_rpc::Error> {
+    let fees: NetworkInfoFees = client.call("getnetworkinfo", &[])?;
+    Ok(Some(btc::RelayFeePolicy {
+        min_relay_fee: fees.relayfee.as_sat(),
+        incremental_relay_fee: fees.incrementalfee.as_sat(),
+    }))
+}
+
+fn rpc_estimate_smart_fee(
+    client: &impl RpcApi,
+    confirmation_target: u32,
+) -> Result<Option<u64>, bitcoincore_rpc::Error> {
+    let estimate: SmartFeeEstimate = client.call(
+        "estimatesmartfee",
+        &[serde_json::Value::from(confirmation_target)],
+    )?;
+    // The fee rate is rounded up, so the estimated fee is not less than the node expects.
+    Ok(estimate
+        .feerate
+        .map(|feerate| (feerate.as_sat() + 999) / 1000))
+}
+
+fn rpc_mempool_fee_rate(
+    client: &impl RpcApi,
+    id: btc::Sha256d,
+) -> Result<Option<u64>, bitcoincore_rpc::Error> {
+    let txid = bitcoin::hash_types::Txid::from(id).to_string();
+    match client.call::<MempoolEntry>("getmempoolentry", &[serde_json::Value::String(txid)]) {
+        Ok(entry) if entry.vsize > 0 => Ok(Some(entry.fees.base.as_sat() / entry.vsize)),
+        Ok(_) => Ok(None),
+        // The transaction is not in the memory pool.
+        Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn rpc_merkle_proof(
+    client: &impl RpcApi,
+    id: btc::Sha256d,
+) -> Result<Option<btc::SpvProof>, bitcoincore_rpc::Error> {
+    let txid = bitcoin::hash_types::Txid::from(id).to_string();
+    let proof = match client.call::<String>("gettxoutproof", &[serde_json::json!([txid])]) {
+        Ok(proof) => proof,
+        // The transaction is not committed or is unknown to the node.
+        Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let bytes = Vec::<u8>::from_hex(&proof)?;
+    Ok(Some(btc::SpvProof(bitcoin::consensus::deserialize(
+        &bytes,
+    )?)))
+}
+
+fn rpc_import_descriptor(
+    client: &impl RpcApi,
+    descriptor: &str,
+    label: &str,
+    timestamp: Option<u64>,
+) -> Result<(), bitcoincore_rpc::Error> {
+    // Without the timestamp the wallet doesn't rescan the blockchain and watches
+    // only the new transactions.
+    let timestamp = timestamp.map_or_else(|| serde_json::json!("now"), serde_json::Value::from);
+    let request = serde_json::json!([{
+        "desc": descriptor,
+        "timestamp": timestamp,
+        "label": label,
+    }]);
+    let results: Vec<ImportDescriptorResult> = client.call("importdescriptors", &[request])?;
+    match results.into_iter().next() {
+        Some(ImportDescriptorResult { success: true, .. }) => Ok(()),
+        Some(ImportDescriptorResult {
+            error: Some(error), ..
+        }) => Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(error))),
+        _ => Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(
+            jsonrpc::error::RpcError {
+                code: RPC_MISC_ERROR,
+                message: format!("Unable to import descriptor {}", descriptor),
+                data: None,
+            },
+        ))),
+    }
+}
+
+fn rpc_list_unspent(
+    client: &impl RpcApi,
+    address: &btc::Address,
+    min_confirmations: u32,
+) -> Result<Vec<UnspentOutput>, bitcoincore_rpc::Error> {
+    let entries: Vec<ListUnspentEntry> = client.call(
+        "listunspent",
+        &[
+            serde_json::Value::from(min_confirmations),
+            serde_json::Value::from(9_999_999),
+            serde_json::json!([address.to_string()]),
+        ],
+    )?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| UnspentOutput {
+            txid: btc::Sha256d(entry.txid.into()),
+            vout: entry.vout,
+            value: entry.amount.as_sat(),
+            confirmations: entry.confirmations,
+        })
+        .collect())
+}
+
+/// Plain Bitcoin Core RPC client.
+///
+/// The requests of this relay block the current thread until the Bitcoin node responds,
+/// thus the relay stalls other tasks of the async runtime. Use [`BitcoinRpcClient`]
+/// to sync several anchoring instances concurrently.
+///
+/// [`BitcoinRpcClient`]: struct.BitcoinRpcClient.html
 #[async_trait]
 impl BitcoinRelay for bitcoincore_rpc::Client {
     type Error = bitcoincore_rpc::Error;
@@ -70,24 +589,540 @@ impl BitcoinRelay for bitcoincore_rpc::Client {
         &self,
         transaction: &btc::Transaction,
     ) -> Result<btc::Sha256d, Self::Error> {
-        self.send_raw_transaction(transaction.to_string())
-            .map(|txid| btc::Sha256d(txid.into()))
+        rpc_send_transaction(self, transaction)
     }
 
     async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
-        match self.get_raw_transaction_verbose(&id.into(), None) {
-            Ok(info) => {
-                let status = match info.confirmations {
-                    None => TransactionStatus::Mempool,
-                    Some(num) => TransactionStatus::Committed(num),
-                };
-                Ok(status)
-            }
-            // TODO Write more graceful error handling. [ECR-3222]
-            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => {
-                Ok(TransactionStatus::Unknown)
+        rpc_transaction_status(self, id)
+    }
+
+    fn is_mempool_conflict(error: &Self::Error) -> bool {
+        rpc_is_mempool_conflict(error)
+    }
+
+    fn is_unavailable(error: &Self::Error) -> bool {
+        rpc_is_unavailable(error)
+    }
+
+    async fn find_conflicting_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        rpc_find_conflicting_transaction(self, transaction)
+    }
+
+    async fn transaction_inclusion(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<AnchorInclusion>, Self::Error> {
+        rpc_transaction_inclusion(self, id)
+    }
+
+    async fn fee_histogram(&self) -> Result<Option<FeeHistogram>, Self::Error> {
+        rpc_fee_histogram(self)
+    }
+
+    async fn relay_fee_policy(&self) -> Result<Option<btc::RelayFeePolicy>, Self::Error> {
+        rpc_relay_fee_policy(self)
+    }
+
+    async fn estimate_smart_fee(
+        &self,
+        confirmation_target: u32,
+    ) -> Result<Option<u64>, Self::Error> {
+        rpc_estimate_smart_fee(self, confirmation_target)
+    }
+
+    async fn mempool_fee_rate(&self, id: btc::Sha256d) -> Result<Option<u64>, Self::Error> {
+        rpc_mempool_fee_rate(self, id)
+    }
+
+    async fn merkle_proof(&self, id: btc::Sha256d) -> Result<Option<btc::SpvProof>, Self::Error> {
+        rpc_merkle_proof(self, id)
+    }
+}
+
+/// Bitcoin Core RPC client with a pool of connections to the Bitcoin node.
+///
+/// The client is cheap to clone, and all clones share the same pool of connections.
+/// Thus a single client instance can be used concurrently by the API layer and
+/// the sync tasks. The requests are distributed among the connections in the round-robin
+/// manner, so concurrent requests are not serialized on a single connection.
+///
+/// The requests are executed on the blocking thread pool of the `tokio` runtime,
+/// so waiting for the Bitcoin node doesn't stall other tasks of the runtime, for example,
+/// the sync tasks of other anchoring instances served by the same process.
+#[derive(Clone)]
+pub struct BitcoinRpcClient {
+    inner: Arc<RpcConnectionPool>,
+}
+
+struct RpcConnectionPool {
+    url: String,
+    connections: Vec<RpcConnection>,
+    next: AtomicUsize,
+}
+
+impl BitcoinRpcClient {
+    /// Default number of connections in the pool.
+    pub const DEFAULT_POOL_SIZE: usize = 4;
+
+    /// Creates a client with the default number of connections to the Bitcoin node
+    /// with the given URL.
+    pub fn new(
+        url: impl Into<String>,
+        auth: bitcoincore_rpc::Auth,
+    ) -> Result<Self, bitcoincore_rpc::Error> {
+        Self::builder(url, auth).build()
+    }
+
+    /// Creates a client with the given number of connections to the Bitcoin node
+    /// with the given URL. The pool contains at least one connection.
+    pub fn with_pool_size(
+        url: impl Into<String>,
+        auth: bitcoincore_rpc::Auth,
+        pool_size: usize,
+    ) -> Result<Self, bitcoincore_rpc::Error> {
+        Self::builder(url, auth).pool_size(pool_size).build()
+    }
+
+    /// Returns a builder of the client for the Bitcoin node with the given URL,
+    /// which allows to set the timeouts, TLS options and proxy of the connections.
+    pub fn builder(url: impl Into<String>, auth: bitcoincore_rpc::Auth) -> BitcoinRpcClientBuilder {
+        BitcoinRpcClientBuilder::new(url, auth)
+    }
+
+    pub(super) fn from_connections(url: String, connections: Vec<RpcConnection>) -> Self {
+        Self {
+            inner: Arc::new(RpcConnectionPool {
+                url,
+                connections,
+                next: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Imports the output descriptor into the descriptor wallet of the Bitcoin node,
+    /// so the wallet watches the outputs of this descriptor. For example, it can be
+    /// the descriptor of the anchoring address returned by [`Config::anchoring_descriptor`].
+    ///
+    /// If the timestamp is specified, the wallet rescans the blockchain from the blocks
+    /// with this UNIX time, otherwise only the new transactions are tracked.
+    ///
+    /// [`Config::anchoring_descriptor`]: ../config/struct.Config.html#method.anchoring_descriptor
+    pub async fn import_descriptor(
+        &self,
+        descriptor: impl Into<String>,
+        label: impl Into<String>,
+        timestamp: Option<u64>,
+    ) -> Result<(), bitcoincore_rpc::Error> {
+        let descriptor = descriptor.into();
+        let label = label.into();
+        self.blocking(move |client| rpc_import_descriptor(client, &descriptor, &label, timestamp))
+            .await
+    }
+
+    /// Returns the unspent outputs of the given address with at least the given number
+    /// of confirmations. The address should be watched by the node wallet, for example,
+    /// after the [import](#method.import_descriptor) of its descriptor.
+    pub async fn list_unspent(
+        &self,
+        address: &btc::Address,
+        min_confirmations: u32,
+    ) -> Result<Vec<UnspentOutput>, bitcoincore_rpc::Error> {
+        let address = address.clone();
+        self.blocking(move |client| rpc_list_unspent(client, &address, min_confirmations))
+            .await
+    }
+
+    /// Returns the number of connections in the pool.
+    pub fn pool_size(&self) -> usize {
+        self.inner.connections.len()
+    }
+
+    fn connection(&self) -> &RpcConnection {
+        let connections = &self.inner.connections;
+        let index = self.inner.next.fetch_add(1, Ordering::Relaxed) % connections.len();
+        &connections[index]
+    }
+
+    /// Executes the blocking request on the blocking thread pool with the next connection
+    /// from the pool.
+    async fn blocking<T, F>(&self, request: F) -> Result<T, bitcoincore_rpc::Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&RpcConnection) -> Result<T, bitcoincore_rpc::Error> + Send + 'static,
+    {
+        let client = self.clone();
+        task::spawn_blocking(move || request(client.connection()))
+            .await
+            .expect("Bitcoin RPC request has panicked")
+    }
+}
+
+impl fmt::Debug for BitcoinRpcClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BitcoinRpcClient")
+            .field("url", &self.inner.url)
+            .field("pool_size", &self.pool_size())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl BitcoinRelay for BitcoinRpcClient {
+    type Error = bitcoincore_rpc::Error;
+
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        let transaction = transaction.clone();
+        self.blocking(move |client| rpc_send_transaction(client, &transaction))
+            .await
+    }
+
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        self.blocking(move |client| rpc_transaction_status(client, id))
+            .await
+    }
+
+    fn is_mempool_conflict(error: &Self::Error) -> bool {
+        rpc_is_mempool_conflict(error)
+    }
+
+    fn is_unavailable(error: &Self::Error) -> bool {
+        rpc_is_unavailable(error)
+    }
+
+    async fn find_conflicting_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        let transaction = transaction.clone();
+        self.blocking(move |client| rpc_find_conflicting_transaction(client, &transaction))
+            .await
+    }
+
+    async fn transaction_inclusion(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<AnchorInclusion>, Self::Error> {
+        self.blocking(move |client| rpc_transaction_inclusion(client, id))
+            .await
+    }
+
+    async fn fee_histogram(&self) -> Result<Option<FeeHistogram>, Self::Error> {
+        self.blocking(rpc_fee_histogram).await
+    }
+
+    async fn relay_fee_policy(&self) -> Result<Option<btc::RelayFeePolicy>, Self::Error> {
+        self.blocking(rpc_relay_fee_policy).await
+    }
+
+    async fn estimate_smart_fee(
+        &self,
+        confirmation_target: u32,
+    ) -> Result<Option<u64>, Self::Error> {
+        self.blocking(move |client| rpc_estimate_smart_fee(client, confirmation_target))
+            .await
+    }
+
+    async fn mempool_fee_rate(&self, id: btc::Sha256d) -> Result<Option<u64>, Self::Error> {
+        self.blocking(move |client| rpc_mempool_fee_rate(client, id))
+            .await
+    }
+
+    async fn merkle_proof(&self, id: btc::Sha256d) -> Result<Option<btc::SpvProof>, Self::Error> {
+        self.blocking(move |client| rpc_merkle_proof(client, id))
+            .await
+    }
+}
+
+/// Shared relay handle, which allows to use a single relay instance in several places.
+#[async_trait]
+impl<R> BitcoinRelay for Arc<R>
+where
+    R: BitcoinRelay + Send + Sync,
+{
+    type Error = R::Error;
+
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        self.as_ref().send_transaction(transaction).await
+    }
+
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        self.as_ref().transaction_status(id).await
+    }
+
+    fn is_mempool_conflict(error: &Self::Error) -> bool {
+        R::is_mempool_conflict(error)
+    }
+
+    fn is_unavailable(error: &Self::Error) -> bool {
+        R::is_unavailable(error)
+    }
+
+    async fn find_conflicting_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        self.as_ref()
+            .find_conflicting_transaction(transaction)
+            .await
+    }
+
+    async fn transaction_inclusion(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<AnchorInclusion>, Self::Error> {
+        self.as_ref().transaction_inclusion(id).await
+    }
+
+    async fn fee_histogram(&self) -> Result<Option<FeeHistogram>, Self::Error> {
+        self.as_ref().fee_histogram().await
+    }
+
+    async fn relay_fee_policy(&self) -> Result<Option<btc::RelayFeePolicy>, Self::Error> {
+        self.as_ref().relay_fee_policy().await
+    }
+
+    async fn estimate_smart_fee(
+        &self,
+        confirmation_target: u32,
+    ) -> Result<Option<u64>, Self::Error> {
+        self.as_ref().estimate_smart_fee(confirmation_target).await
+    }
+
+    async fn mempool_fee_rate(&self, id: btc::Sha256d) -> Result<Option<u64>, Self::Error> {
+        self.as_ref().mempool_fee_rate(id).await
+    }
+
+    async fn merkle_proof(&self, id: btc::Sha256d) -> Result<Option<btc::SpvProof>, Self::Error> {
+        self.as_ref().merkle_proof(id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{hash_types::Txid, hashes::Hash, OutPoint, Script, TxIn, TxOut};
+    use bitcoincore_rpc::RpcApi;
+    use jsonrpc::{error::RpcError, Error as JsonRpcError};
+    use serde_json::{json, Value};
+
+    use std::{
+        cell::RefCell,
+        collections::{HashMap, HashSet},
+    };
+
+    use super::{rpc_find_conflicting_transaction, RPC_METHOD_NOT_FOUND};
+    use crate::btc;
+
+    /// Bitcoin Core RPC error code of the unknown transaction.
+    const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+
+    /// Bitcoin node with the memory pool and the unspent outputs of the confirmed
+    /// transactions, which counts the RPC calls.
+    #[derive(Default)]
+    struct FakeNode {
+        mempool: HashMap<Txid, btc::Transaction>,
+        utxos: HashSet<OutPoint>,
+        supports_spending_prevout: bool,
+        calls: RefCell<HashMap<String, usize>>,
+    }
+
+    impl FakeNode {
+        /// Creates the node with the given unspent outputs and the memory pool with
+        /// a thousand of unrelated transactions.
+        fn new(utxos: &[OutPoint]) -> Self {
+            let mut node = Self {
+                utxos: utxos.iter().copied().collect(),
+                supports_spending_prevout: true,
+                ..Self::default()
+            };
+            for i in 0..1_000_u32 {
+                let outpoint = OutPoint::new(Txid::hash(&i.to_le_bytes()), 0);
+                node.add_to_mempool(transaction(&[outpoint], 1_000));
             }
-            Err(e) => Err(e),
+            node
+        }
+
+        fn add_to_mempool(&mut self, transaction: btc::Transaction) -> Txid {
+            let txid = transaction.0.txid();
+            self.mempool.insert(txid, transaction);
+            txid
+        }
+
+        fn mempool_spender(&self, outpoint: &OutPoint) -> Option<Txid> {
+            self.mempool
+                .iter()
+                .find(|(_, tx)| {
+                    tx.0.input
+                        .iter()
+                        .any(|txin| txin.previous_output == *outpoint)
+                })
+                .map(|(txid, _)| *txid)
+        }
+
+        fn calls(&self, method: &str) -> usize {
+            self.calls.borrow().get(method).copied().unwrap_or_default()
+        }
+
+        fn total_calls(&self) -> usize {
+            self.calls.borrow().values().sum()
+        }
+    }
+
+    impl RpcApi for FakeNode {
+        fn call<T: for<'a> serde::de::Deserialize<'a>>(
+            &self,
+            cmd: &str,
+            args: &[Value],
+        ) -> bitcoincore_rpc::Result<T> {
+            *self.calls.borrow_mut().entry(cmd.to_owned()).or_default() += 1;
+            let txid = |value: &Value| value.as_str().unwrap().parse::<Txid>().unwrap();
+            let value = match cmd {
+                "getmempoolentry" => {
+                    let parent_id = txid(&args[0]);
+                    let parent = self
+                        .mempool
+                        .get(&parent_id)
+                        .ok_or_else(|| rpc_error(RPC_INVALID_ADDRESS_OR_KEY))?;
+                    let spentby = (0..parent.0.output.len() as u32)
+                        .filter_map(|vout| self.mempool_spender(&OutPoint::new(parent_id, vout)))
+                        .map(|spender_id| spender_id.to_string())
+                        .collect::<Vec<_>>();
+                    json!({ "spentby": spentby })
+                }
+                "getrawtransaction" => {
+                    let tx = self
+                        .mempool
+                        .get(&txid(&args[0]))
+                        .ok_or_else(|| rpc_error(RPC_INVALID_ADDRESS_OR_KEY))?;
+                    Value::String(tx.to_string())
+                }
+                "gettxout" => {
+                    let outpoint = OutPoint::new(txid(&args[0]), args[1].as_u64().unwrap() as u32);
+                    let is_spent_in_mempool =
+                        args[2].as_bool().unwrap() && self.mempool_spender(&outpoint).is_some();
+                    if self.utxos.contains(&outpoint) && !is_spent_in_mempool {
+                        json!({ "value": 0.001 })
+                    } else {
+                        Value::Null
+                    }
+                }
+                "gettxspendingprevout" if self.supports_spending_prevout => {
+                    let prevouts = args[0].as_array().unwrap().iter().map(|prevout| {
+                        let outpoint = OutPoint::new(
+                            txid(&prevout["txid"]),
+                            prevout["vout"].as_u64().unwrap() as u32,
+                        );
+                        let mut prevout = prevout.clone();
+                        if let Some(spender_id) = self.mempool_spender(&outpoint) {
+                            prevout["spendingtxid"] = Value::String(spender_id.to_string());
+                        }
+                        prevout
+                    });
+                    Value::Array(prevouts.collect())
+                }
+                "getrawmempool" => {
+                    let txids = self.mempool.keys().map(Txid::to_string);
+                    Value::Array(txids.map(Value::String).collect())
+                }
+                _ => return Err(rpc_error(RPC_METHOD_NOT_FOUND)),
+            };
+            Ok(serde_json::from_value(value)?)
         }
     }
+
+    fn rpc_error(code: i32) -> bitcoincore_rpc::Error {
+        bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(RpcError {
+            code,
+            message: String::new(),
+            data: None,
+        }))
+    }
+
+    fn transaction(inputs: &[OutPoint], value: u64) -> btc::Transaction {
+        let input = inputs
+            .iter()
+            .map(|&previous_output| TxIn {
+                previous_output,
+                script_sig: Script::new(),
+                sequence: 0xFFFF_FFFF,
+                witness: Vec::new(),
+            })
+            .collect();
+        btc::Transaction::from(bitcoin::Transaction {
+            version: 2,
+            lock_time: 0,
+            input,
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new(),
+            }],
+        })
+    }
+
+    /// Creates the node and the transaction spending the confirmed output and
+    /// the output of the memory pool transaction.
+    fn node_with_transaction() -> (FakeNode, btc::Transaction) {
+        let confirmed_output = OutPoint::new(Txid::hash(b"funding"), 0);
+        let mut node = FakeNode::new(&[confirmed_output]);
+        let parent_id = node.add_to_mempool(transaction(&[OutPoint::default()], 10_000));
+        let tx = transaction(&[confirmed_output, OutPoint::new(parent_id, 0)], 9_000);
+        (node, tx)
+    }
+
+    #[test]
+    fn find_conflicting_transaction_without_conflicts() {
+        let (node, tx) = node_with_transaction();
+        assert_eq!(rpc_find_conflicting_transaction(&node, &tx).unwrap(), None);
+        // One `getmempoolentry` call per input and one `gettxout` call
+        // for the confirmed output.
+        assert_eq!(node.total_calls(), 3);
+        assert_eq!(node.calls("getrawmempool"), 0);
+    }
+
+    #[test]
+    fn find_conflicting_transaction_spending_mempool_output() {
+        let (mut node, tx) = node_with_transaction();
+        let conflict = transaction(&[tx.0.input[1].previous_output], 8_000);
+        node.add_to_mempool(conflict.clone());
+
+        assert_eq!(
+            rpc_find_conflicting_transaction(&node, &tx).unwrap(),
+            Some(conflict.id())
+        );
+        assert_eq!(node.total_calls(), 4);
+        assert_eq!(node.calls("getrawtransaction"), 1);
+        assert_eq!(node.calls("getrawmempool"), 0);
+    }
+
+    #[test]
+    fn find_conflicting_transaction_spending_confirmed_output() {
+        let (mut node, tx) = node_with_transaction();
+        let conflict = transaction(&[tx.0.input[0].previous_output], 8_000);
+        node.add_to_mempool(conflict.clone());
+
+        assert_eq!(
+            rpc_find_conflicting_transaction(&node, &tx).unwrap(),
+            Some(conflict.id())
+        );
+        assert_eq!(node.total_calls(), 5);
+        assert_eq!(node.calls("gettxspendingprevout"), 1);
+        assert_eq!(node.calls("getrawmempool"), 0);
+
+        // The memory pool is scanned only if the node can't find the spender itself.
+        node.supports_spending_prevout = false;
+        node.calls.borrow_mut().clear();
+        assert_eq!(
+            rpc_find_conflicting_transaction(&node, &tx).unwrap(),
+            Some(conflict.id())
+        );
+        assert_eq!(node.calls("getrawmempool"), 1);
+    }
 }
@@ -0,0 +1,401 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checklist of the preconditions of the first anchoring transaction.
+
+use exonum::{crypto::Hash, helpers::Height};
+
+use std::fmt::{self, Display};
+
+use crate::{
+    api::{AnchoringProposalState, PrivateApi},
+    blockchain::NoProposalReason,
+    btc::{self, BtcAnchoringTransactionBuilder, BuilderError},
+    config::Config,
+};
+
+use super::{BitcoinRelay, KeyPool, TransactionStatus};
+
+/// Outcome of a single item of the bootstrap checklist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    /// The precondition is met.
+    Passed,
+    /// The precondition is met, but the setup looks suspicious.
+    Warning,
+    /// The precondition is not met, so the first anchoring transaction won't appear.
+    Failed,
+}
+
+/// Precondition of the first anchoring transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootstrapItem {
+    /// The anchoring chain already contains transactions, so the bootstrap is complete.
+    AnchoringChain,
+    /// The key pool of the node contains one of the anchoring keys.
+    AnchoringKeys,
+    /// The funding transaction is confirmed by the anchoring nodes and in the Bitcoin
+    /// network, and its value covers the fee of the first anchoring transaction.
+    FundingTransaction,
+    /// The Bitcoin relay is reachable.
+    BitcoinRelay,
+    /// The first anchored block is expected within the anchoring interval.
+    AnchoringInterval,
+}
+
+impl Display for BootstrapItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BootstrapItem::AnchoringChain => "Anchoring chain",
+            BootstrapItem::AnchoringKeys => "Anchoring keys",
+            BootstrapItem::FundingTransaction => "Funding transaction",
+            BootstrapItem::BitcoinRelay => "Bitcoin relay",
+            BootstrapItem::AnchoringInterval => "Anchoring interval",
+        })
+    }
+}
+
+/// Result of the check of a single precondition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BootstrapCheck {
+    /// Checked precondition.
+    pub item: BootstrapItem,
+    /// Outcome of the check.
+    pub status: CheckStatus,
+    /// Human-readable description of the outcome.
+    pub detail: String,
+}
+
+impl BootstrapCheck {
+    fn new(item: BootstrapItem, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            item,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Checklist of the preconditions of the first anchoring transaction.
+///
+/// The checklist is built by the [`check_bootstrap`] function.
+///
+/// [`check_bootstrap`]: fn.check_bootstrap.html
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BootstrapReport {
+    /// Results of the checks in the checklist order.
+    pub checks: Vec<BootstrapCheck>,
+}
+
+impl BootstrapReport {
+    /// Returns `true` if none of the checks has failed.
+    pub fn is_ready(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Failed)
+    }
+
+    /// Returns the result of the check of the given precondition, if it has been checked.
+    pub fn check(&self, item: BootstrapItem) -> Option<&BootstrapCheck> {
+        self.checks.iter().find(|check| check.item == item)
+    }
+}
+
+impl Display for BootstrapReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let status = match check.status {
+                CheckStatus::Passed => "OK",
+                CheckStatus::Warning => "WARN",
+                CheckStatus::Failed => "FAIL",
+            };
+            writeln!(f, "[{:>4}] {}: {}", status, check.item, check.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the preconditions of the first anchoring transaction from the point of view
+/// of the anchoring node with the given key pool and Bitcoin relay.
+///
+/// Errors of the Bitcoin relay are reported in the checklist, while errors of the API
+/// client are returned, since nothing can be checked without the anchoring service.
+pub async fn check_bootstrap<T, R>(
+    api_client: &T,
+    btc_relay: Option<&R>,
+    key_pool: &KeyPool,
+) -> Result<BootstrapReport, T::Error>
+where
+    T: PrivateApi,
+    R: BitcoinRelay,
+    R::Error: Display,
+{
+    let config = api_client.config().await?;
+    let mut report = BootstrapReport::default();
+
+    let chain_len = api_client.transactions_count().await?.value;
+    if chain_len > 0 {
+        report.checks.push(BootstrapCheck::new(
+            BootstrapItem::AnchoringChain,
+            CheckStatus::Passed,
+            format!(
+                "The anchoring chain contains {} transactions, the first anchor is done",
+                chain_len
+            ),
+        ));
+    }
+
+    report.checks.push(check_anchoring_keys(&config, key_pool));
+
+    let funding_tx = api_client.unspent_funding_transaction().await?;
+    let relay_check = match btc_relay {
+        Some(relay) => {
+            // The status request doubles as the connectivity check.
+            let txid = funding_tx
+                .as_ref()
+                .map_or_else(|| btc::Sha256d::new([0; 32]), btc::Transaction::id);
+            match relay.transaction_status(txid).await {
+                Ok(status) => Ok(Some(status)),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+        None => Ok(None),
+    };
+
+    if chain_len == 0 {
+        report.checks.push(check_funding_transaction(
+            &config,
+            funding_tx.as_ref(),
+            relay_check.as_ref().ok().copied().flatten(),
+        ));
+    }
+    report.checks.push(match relay_check {
+        Ok(Some(_)) => BootstrapCheck::new(
+            BootstrapItem::BitcoinRelay,
+            CheckStatus::Passed,
+            "The Bitcoin relay is reachable",
+        ),
+        Ok(None) => BootstrapCheck::new(
+            BootstrapItem::BitcoinRelay,
+            CheckStatus::Warning,
+            "No Bitcoin relay is configured, so this node doesn't broadcast \
+             the anchoring transactions",
+        ),
+        Err(e) => BootstrapCheck::new(
+            BootstrapItem::BitcoinRelay,
+            CheckStatus::Failed,
+            format!("The Bitcoin relay is unreachable: {}", e),
+        ),
+    });
+
+    if chain_len == 0 {
+        let proposal = api_client.anchoring_proposal().await?;
+        report
+            .checks
+            .push(check_anchoring_interval(&config, &proposal));
+    }
+    Ok(report)
+}
+
+fn check_anchoring_keys(config: &Config, key_pool: &KeyPool) -> BootstrapCheck {
+    let own_key = config
+        .anchoring_keys
+        .iter()
+        .map(|keys| keys.bitcoin_key)
+        .find(|bitcoin_key| key_pool.get(bitcoin_key).is_some());
+    match own_key {
+        Some(bitcoin_key) => BootstrapCheck::new(
+            BootstrapItem::AnchoringKeys,
+            CheckStatus::Passed,
+            format!(
+                "The key pool contains the anchoring key {}, signatures with the total weight \
+                 of {} of {} are required",
+                bitcoin_key,
+                config.signature_quorum(),
+                config.total_key_weight()
+            ),
+        ),
+        None => BootstrapCheck::new(
+            BootstrapItem::AnchoringKeys,
+            CheckStatus::Failed,
+            format!(
+                "The key pool contains none of the {} anchoring keys of the actual configuration",
+                config.anchoring_keys.len()
+            ),
+        ),
+    }
+}
+
+fn check_funding_transaction(
+    config: &Config,
+    funding_tx: Option<&btc::Transaction>,
+    status: Option<TransactionStatus>,
+) -> BootstrapCheck {
+    let funding_tx = match funding_tx {
+        Some(funding_tx) => funding_tx,
+        None => {
+            return BootstrapCheck::new(
+                BootstrapItem::FundingTransaction,
+                CheckStatus::Failed,
+                format!(
+                    "There is no funding transaction confirmed by the anchoring nodes. Send \
+                     bitcoins to the address {} and confirm the transaction via \
+                     the `management/add-funds` endpoint by a quorum of the anchoring nodes",
+                    config.anchoring_address()
+                ),
+            )
+        }
+    };
+
+    let txid = funding_tx.id();
+    match status {
+        Some(TransactionStatus::Committed(_)) | None => {}
+        Some(status) => {
+            return BootstrapCheck::new(
+                BootstrapItem::FundingTransaction,
+                CheckStatus::Failed,
+                format!(
+                    "Funding transaction {} is not committed to the Bitcoin blockchain, \
+                     its status is {:?}",
+                    txid, status
+                ),
+            )
+        }
+    }
+
+    // The fee of the anchoring transaction doesn't depend on the anchored block, so
+    // the trial transaction shows whether the funds are sufficient.
+    let fee = config
+        .fee_estimation
+        .map_or(config.transaction_fee, |fee_estimation| {
+            fee_estimation.max_fee.max(config.transaction_fee)
+        });
+    let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+    if let Err(e) = builder.additional_funds(funding_tx.clone()) {
+        return BootstrapCheck::new(
+            BootstrapItem::FundingTransaction,
+            CheckStatus::Failed,
+            format!("Funding transaction {} is unsuitable: {}", txid, e),
+        );
+    }
+    if let Some(memo) = config.encrypted_memo.clone() {
+        builder.memo(memo);
+    }
+    builder.payload(Height::zero(), Hash::zero());
+    builder.fee(fee);
+    builder.relay_fee_policy(btc::RelayFeePolicy {
+        min_relay_fee: config.min_relay_fee,
+        ..btc::RelayFeePolicy::default()
+    });
+
+    let confirmations = status.and_then(TransactionStatus::confirmations);
+    match builder.create() {
+        // The fee wallet pays the fees, so the funding transaction only has to be suitable.
+        Err(BuilderError::InsufficientFunds { .. }) if config.fee_payer_key.is_some() => {
+            BootstrapCheck::new(
+                BootstrapItem::FundingTransaction,
+                CheckStatus::Warning,
+                format!(
+                    "Funding transaction {} is suitable, make sure that the fee wallet {} \
+                     is funded",
+                    txid,
+                    config.fee_payer_address().unwrap()
+                ),
+            )
+        }
+        Err(BuilderError::InsufficientFunds { total_fee, balance }) => BootstrapCheck::new(
+            BootstrapItem::FundingTransaction,
+            CheckStatus::Failed,
+            format!(
+                "Funding transaction {} has {} satoshis, which doesn't cover the fee {} \
+                 of the first anchoring transaction at {} satoshis per byte",
+                txid, balance, total_fee, fee
+            ),
+        ),
+        Err(e) => BootstrapCheck::new(
+            BootstrapItem::FundingTransaction,
+            CheckStatus::Failed,
+            format!("Funding transaction {} is unsuitable: {}", txid, e),
+        ),
+        Ok(_) => match confirmations {
+            Some(confirmations) => BootstrapCheck::new(
+                BootstrapItem::FundingTransaction,
+                CheckStatus::Passed,
+                format!(
+                    "Funding transaction {} has {} confirmations and covers the fee \
+                     of the first anchoring transaction",
+                    txid, confirmations
+                ),
+            ),
+            None => BootstrapCheck::new(
+                BootstrapItem::FundingTransaction,
+                CheckStatus::Warning,
+                format!(
+                    "Funding transaction {} covers the fee of the first anchoring transaction, \
+                     but its status in the Bitcoin network is unknown",
+                    txid
+                ),
+            ),
+        },
+    }
+}
+
+fn check_anchoring_interval(config: &Config, proposal: &AnchoringProposalState) -> BootstrapCheck {
+    let interval = config.anchoring_interval;
+    match proposal {
+        AnchoringProposalState::Available { transaction, .. } => BootstrapCheck::new(
+            BootstrapItem::AnchoringInterval,
+            CheckStatus::Passed,
+            format!(
+                "The first anchoring proposal {} is waiting for the signatures",
+                transaction.id()
+            ),
+        ),
+        AnchoringProposalState::None {
+            reason:
+                NoProposalReason::AwaitingAnchoringHeight {
+                    next_anchoring_height,
+                    latest_height,
+                },
+        } => {
+            let remaining = next_anchoring_height.0.saturating_sub(latest_height.0);
+            let status = if remaining > interval {
+                CheckStatus::Warning
+            } else {
+                CheckStatus::Passed
+            };
+            BootstrapCheck::new(
+                BootstrapItem::AnchoringInterval,
+                status,
+                format!(
+                    "The first anchored block is at height {}, {} blocks to go with \
+                     the anchoring interval of {} blocks",
+                    next_anchoring_height, remaining, interval
+                ),
+            )
+        }
+        AnchoringProposalState::None { reason } => BootstrapCheck::new(
+            BootstrapItem::AnchoringInterval,
+            CheckStatus::Failed,
+            format!("No anchoring proposal is expected: {}", reason),
+        ),
+        // The lack of funds is reported by the funding transaction check.
+        AnchoringProposalState::InsufficientFunds { .. }
+        | AnchoringProposalState::NoInitialFunds => BootstrapCheck::new(
+            BootstrapItem::AnchoringInterval,
+            CheckStatus::Passed,
+            format!("The anchoring interval is {} blocks", interval),
+        ),
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storages for the state of the sync utility, which survives its restarts.
+
+use async_trait::async_trait;
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::btc;
+
+/// Anchoring transaction sent to the Bitcoin network by the sync utility.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BroadcastRecord {
+    /// Index of the transaction in the anchoring chain.
+    pub index: u64,
+    /// Transaction identifier.
+    pub txid: btc::Sha256d,
+}
+
+/// State of the sync utility.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncCheckpoint {
+    /// Index of the latest anchoring transaction known to the Bitcoin network.
+    pub latest_synced_tx_index: Option<u64>,
+    /// Latest anchoring transactions sent to the Bitcoin network, in order of sending.
+    #[serde(default)]
+    pub broadcast_history: Vec<BroadcastRecord>,
+}
+
+impl SyncCheckpoint {
+    /// Maximum number of the records in the broadcast history.
+    pub const MAX_HISTORY_LEN: usize = 100;
+
+    /// Appends the sent transaction to the broadcast history, the oldest records
+    /// are removed if the history is too long.
+    ///
+    /// Returns `false` if the transaction is already the latest record.
+    pub fn record_broadcast(&mut self, index: u64, txid: btc::Sha256d) -> bool {
+        let record = BroadcastRecord { index, txid };
+        if self.broadcast_history.last() == Some(&record) {
+            return false;
+        }
+
+        self.broadcast_history.push(record);
+        let excess = self
+            .broadcast_history
+            .len()
+            .saturating_sub(Self::MAX_HISTORY_LEN);
+        self.broadcast_history.drain(..excess);
+        true
+    }
+}
+
+/// Storage of the sync utility checkpoint.
+///
+/// Besides the local file, the checkpoint can be kept in the external storage, so the sync
+/// utility can run in the environment without a persistent local disk.
+#[async_trait]
+pub trait CheckpointStore {
+    /// Loads the latest saved checkpoint, if any.
+    async fn load(&self) -> anyhow::Result<Option<SyncCheckpoint>>;
+    /// Replaces the saved checkpoint by the given one.
+    async fn save(&self, checkpoint: &SyncCheckpoint) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl<S> CheckpointStore for Box<S>
+where
+    S: CheckpointStore + Send + Sync + ?Sized,
+{
+    async fn load(&self) -> anyhow::Result<Option<SyncCheckpoint>> {
+        (**self).load().await
+    }
+
+    async fn save(&self, checkpoint: &SyncCheckpoint) -> anyhow::Result<()> {
+        (**self).save(checkpoint).await
+    }
+}
+
+/// Keeps the checkpoint in the local JSON file.
+#[derive(Debug, Clone)]
+pub struct FileCheckpointStore {
+    path: PathBuf,
+}
+
+impl FileCheckpointStore {
+    /// Creates the store for the file with the given path.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for FileCheckpointStore {
+    async fn load(&self) -> anyhow::Result<Option<SyncCheckpoint>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read(&self.path)?;
+        Ok(Some(serde_json::from_slice(&content)?))
+    }
+
+    async fn save(&self, checkpoint: &SyncCheckpoint) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Write to the temporary file first, so the checkpoint is never left half-written.
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(checkpoint)?)?;
+        fs::rename(tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Key of the checkpoint in the key-value storages.
+#[cfg(any(feature = "sled", feature = "redis"))]
+const DEFAULT_CHECKPOINT_KEY: &str = "btc_anchoring_sync_checkpoint";
+
+/// Keeps the checkpoint in the [`sled`] embedded database.
+///
+/// Requires the `sled` feature.
+///
+/// [`sled`]: https://docs.rs/sled
+#[cfg(feature = "sled")]
+#[derive(Debug, Clone)]
+pub struct SledCheckpointStore {
+    db: sled::Db,
+}
+
+#[cfg(feature = "sled")]
+impl SledCheckpointStore {
+    /// Opens the database at the given path.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(Self {
+            db: sled::open(path)?,
+        })
+    }
+}
+
+#[cfg(feature = "sled")]
+#[async_trait]
+impl CheckpointStore for SledCheckpointStore {
+    async fn load(&self) -> anyhow::Result<Option<SyncCheckpoint>> {
+        self.db
+            .get(DEFAULT_CHECKPOINT_KEY)?
+            .map(|value| serde_json::from_slice(&value).map_err(From::from))
+            .transpose()
+    }
+
+    async fn save(&self, checkpoint: &SyncCheckpoint) -> anyhow::Result<()> {
+        self.db
+            .insert(DEFAULT_CHECKPOINT_KEY, serde_json::to_vec(checkpoint)?)?;
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+/// Keeps the checkpoint in the Redis server.
+///
+/// Requires the `redis` feature.
+#[cfg(feature = "redis")]
+#[derive(Debug, Clone)]
+pub struct RedisCheckpointStore {
+    client: redis::Client,
+    key: String,
+}
+
+#[cfg(feature = "redis")]
+impl RedisCheckpointStore {
+    /// Creates the store for the Redis server with the given URL, like
+    /// `redis://127.0.0.1/`. Several sync utilities may share the same server
+    /// if they use different keys.
+    pub fn new(url: &str, key: Option<String>) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: redis::Client::open(url)?,
+            key: key.unwrap_or_else(|| DEFAULT_CHECKPOINT_KEY.to_owned()),
+        })
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl CheckpointStore for RedisCheckpointStore {
+    async fn load(&self) -> anyhow::Result<Option<SyncCheckpoint>> {
+        use redis::Commands;
+
+        let mut connection = self.client.get_connection()?;
+        let value: Option<String> = connection.get(&self.key)?;
+        value
+            .map(|value| serde_json::from_str(&value).map_err(From::from))
+            .transpose()
+    }
+
+    async fn save(&self, checkpoint: &SyncCheckpoint) -> anyhow::Result<()> {
+        use redis::Commands;
+
+        let mut connection = self.client.get_connection()?;
+        let _: () = connection.set(&self.key, serde_json::to_string(checkpoint)?)?;
+        Ok(())
+    }
+}
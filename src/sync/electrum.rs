@@ -0,0 +1,472 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bitcoin relay which communicates with the Electrum server.
+
+use async_trait::async_trait;
+use bitcoin_hashes::{hex::FromHex, sha256, sha256d, Hash};
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time::timeout,
+};
+
+use std::{collections::HashSet, time::Duration};
+
+use crate::{blockchain::AnchorInclusion, btc};
+
+use super::{amount::BtcAmount, BitcoinRelay, FeeHistogram, TransactionStatus};
+
+/// Errors that occur in the communication with the Electrum server.
+#[derive(Debug, Error)]
+pub enum ElectrumError {
+    /// Unable to connect to the Electrum server or to exchange messages with it.
+    #[error("Electrum server connection error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The Electrum server didn't respond in time.
+    #[error("Electrum server request timed out")]
+    Timeout,
+    /// The Electrum server rejected the request.
+    #[error("Electrum server error {code}: {message}")]
+    Server {
+        /// Error code.
+        code: i64,
+        /// Error description.
+        message: String,
+    },
+    /// The Electrum server response can't be parsed.
+    #[error("Unexpected Electrum server response: {0}")]
+    InvalidResponse(String),
+}
+
+/// Bitcoin relay which uses the [Electrum protocol] to communicate with the Bitcoin network.
+///
+/// This relay allows to run the anchoring node without a full `bitcoind` node. Each request
+/// uses a separate plain TCP connection, so the relay can be used concurrently. Electrum
+/// servers index the transactions by their output scripts, thus the relay looks up the
+/// transactions by their first output, which is the anchoring output for the anchoring
+/// transactions.
+///
+/// Unlike the Bitcoin Core RPC, the Electrum protocol doesn't provide the wallet methods,
+/// so the relay can't send funds to the anchoring address, use an Electrum wallet for this.
+///
+/// [Electrum protocol]: https://electrumx.readthedocs.io/en/latest/protocol.html
+#[derive(Debug, Clone)]
+pub struct ElectrumRelay {
+    address: String,
+    timeout: Duration,
+}
+
+impl ElectrumRelay {
+    /// Default timeout of the requests to the Electrum server.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// Creates a relay for the Electrum server with the given address in the `host:port`
+    /// format, e.g. `localhost:50001`.
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets the timeout of the requests to the Electrum server.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the address of the Electrum server.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    async fn connect(&self) -> Result<Connection, ElectrumError> {
+        let stream = timeout(self.timeout, TcpStream::connect(self.address.as_str()))
+            .await
+            .map_err(|_| ElectrumError::Timeout)??;
+        Ok(Connection {
+            stream: BufReader::new(stream),
+            timeout: self.timeout,
+            next_id: 0,
+        })
+    }
+}
+
+/// Entry of the transaction history of the output script.
+#[derive(Debug, Deserialize)]
+struct HistoryEntry {
+    tx_hash: String,
+    /// Height of the block with the transaction, zero or negative value stands for
+    /// the memory pool transactions.
+    height: i64,
+}
+
+/// Tip of the Bitcoin blockchain returned by the `blockchain.headers.subscribe` call.
+#[derive(Debug, Deserialize)]
+struct HeadersTip {
+    height: u64,
+}
+
+/// Single connection to the Electrum server, which processes requests sequentially.
+struct Connection {
+    stream: BufReader<TcpStream>,
+    timeout: Duration,
+    next_id: u64,
+}
+
+impl Connection {
+    async fn call<T: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, ElectrumError> {
+        let timeout_duration = self.timeout;
+        timeout(timeout_duration, self.call_inner(method, params))
+            .await
+            .map_err(|_| ElectrumError::Timeout)?
+    }
+
+    async fn call_inner<T: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, ElectrumError> {
+        self.next_id += 1;
+        let id = self.next_id;
+        let mut request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        })
+        .to_string();
+        request.push('\n');
+        self.stream.get_mut().write_all(request.as_bytes()).await?;
+
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                return Err(ElectrumError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+            let mut response: Value = serde_json::from_str(&line)
+                .map_err(|e| ElectrumError::InvalidResponse(e.to_string()))?;
+            // Skip the notifications and responses to other requests.
+            if response.get("id").and_then(Value::as_u64) != Some(id) {
+                continue;
+            }
+
+            if let Some(error) = response.get("error").filter(|error| !error.is_null()) {
+                return Err(server_error(error));
+            }
+            let result = response
+                .get_mut("result")
+                .map(Value::take)
+                .unwrap_or_default();
+            return serde_json::from_value(result)
+                .map_err(|e| ElectrumError::InvalidResponse(e.to_string()));
+        }
+    }
+
+    async fn transaction(
+        &mut self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, ElectrumError> {
+        let hex = match self
+            .call::<String>("blockchain.transaction.get", json!([id.to_string()]))
+            .await
+        {
+            Ok(hex) => hex,
+            // Electrum servers don't have a dedicated error code for unknown transactions.
+            Err(ElectrumError::Server { .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        hex.parse::<btc::Transaction>()
+            .map(Some)
+            .map_err(|e| ElectrumError::InvalidResponse(e.to_string()))
+    }
+
+    /// Returns the height of the block containing the transaction, zero stands for
+    /// the memory pool transactions.
+    async fn transaction_height(&mut self, id: btc::Sha256d) -> Result<Option<u64>, ElectrumError> {
+        let transaction = match self.transaction(id).await? {
+            Some(transaction) => transaction,
+            None => return Ok(None),
+        };
+        let script = match transaction.0.output.first() {
+            Some(output) => &output.script_pubkey,
+            None => return Ok(None),
+        };
+
+        let history: Vec<HistoryEntry> = self
+            .call(
+                "blockchain.scripthash.get_history",
+                json!([script_hash(script)]),
+            )
+            .await?;
+        let txid = id.to_string();
+        Ok(history
+            .into_iter()
+            .find(|entry| entry.tx_hash == txid)
+            .map(|entry| entry.height.max(0) as u64))
+    }
+
+    async fn tip_height(&mut self) -> Result<u64, ElectrumError> {
+        let tip: HeadersTip = self.call("blockchain.headers.subscribe", json!([])).await?;
+        Ok(tip.height)
+    }
+
+    async fn block_hash(&mut self, height: u64) -> Result<btc::Sha256d, ElectrumError> {
+        let header_hex: String = self
+            .call("blockchain.block.header", json!([height]))
+            .await?;
+        let header = hex::decode(&header_hex)
+            .map_err(|e| ElectrumError::InvalidResponse(e.to_string()))
+            .and_then(|bytes| {
+                bitcoin::consensus::deserialize::<bitcoin::BlockHeader>(&bytes)
+                    .map_err(|e| ElectrumError::InvalidResponse(e.to_string()))
+            })?;
+        Ok(btc::Sha256d(header.block_hash().as_hash()))
+    }
+}
+
+fn server_error(error: &Value) -> ElectrumError {
+    let code = error
+        .get("code")
+        .and_then(Value::as_i64)
+        .unwrap_or_default();
+    let message = match error.get("message").and_then(Value::as_str) {
+        Some(message) => message.to_owned(),
+        None => error
+            .as_str()
+            .map_or_else(|| error.to_string(), ToOwned::to_owned),
+    };
+    ElectrumError::Server { code, message }
+}
+
+fn parse_txid(hex: &str) -> Result<btc::Sha256d, ElectrumError> {
+    sha256d::Hash::from_hex(hex)
+        .map(btc::Sha256d)
+        .map_err(|e| ElectrumError::InvalidResponse(e.to_string()))
+}
+
+/// Computes the Electrum script hash, i.e. the reversed SHA256 digest of the output script.
+fn script_hash(script: &bitcoin::Script) -> String {
+    let mut hash = sha256::Hash::hash(script.as_bytes()).into_inner();
+    hash.reverse();
+    hex::encode(hash)
+}
+
+#[async_trait]
+impl BitcoinRelay for ElectrumRelay {
+    type Error = ElectrumError;
+
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        let mut connection = self.connect().await?;
+        let txid: String = connection
+            .call(
+                "blockchain.transaction.broadcast",
+                json!([transaction.to_string()]),
+            )
+            .await?;
+        parse_txid(&txid)
+    }
+
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        let mut connection = self.connect().await?;
+        let status = match connection.transaction_height(id).await? {
+            None => TransactionStatus::Unknown,
+            Some(0) => TransactionStatus::Mempool,
+            Some(height) => {
+                let tip_height = connection.tip_height().await?;
+                let confirmations = tip_height.saturating_sub(height) + 1;
+                TransactionStatus::Committed(confirmations as u32)
+            }
+        };
+        Ok(status)
+    }
+
+    fn is_mempool_conflict(error: &Self::Error) -> bool {
+        match error {
+            ElectrumError::Server { message, .. } => message.contains("txn-mempool-conflict"),
+            _ => false,
+        }
+    }
+
+    fn is_unavailable(error: &Self::Error) -> bool {
+        if let ElectrumError::Server { .. } = error {
+            false
+        } else {
+            true
+        }
+    }
+
+    async fn find_conflicting_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        let spent_outputs = transaction
+            .0
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<HashSet<_>>();
+
+        let mut connection = self.connect().await?;
+        let mut visited = HashSet::new();
+        for txin in &transaction.0.input {
+            let prev_tx = match connection
+                .transaction(btc::Sha256d(txin.previous_output.txid.as_hash()))
+                .await?
+            {
+                Some(prev_tx) => prev_tx,
+                None => continue,
+            };
+            let script = match prev_tx.0.output.get(txin.previous_output.vout as usize) {
+                Some(output) => &output.script_pubkey,
+                None => continue,
+            };
+
+            // The conflicting transaction spends the same output, thus it is presented
+            // in the memory pool history of the spent output script.
+            let mempool: Vec<HistoryEntry> = connection
+                .call(
+                    "blockchain.scripthash.get_mempool",
+                    json!([script_hash(script)]),
+                )
+                .await?;
+            for entry in mempool {
+                let txid = parse_txid(&entry.tx_hash)?;
+                if txid == transaction.id() || !visited.insert(txid) {
+                    continue;
+                }
+                // The transaction may have left the memory pool in the meantime.
+                if let Some(candidate) = connection.transaction(txid).await? {
+                    let is_conflicting = candidate
+                        .0
+                        .input
+                        .iter()
+                        .any(|txin| spent_outputs.contains(&txin.previous_output));
+                    if is_conflicting {
+                        return Ok(Some(txid));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    async fn transaction_inclusion(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<AnchorInclusion>, Self::Error> {
+        let mut connection = self.connect().await?;
+        let block_height = match connection.transaction_height(id).await? {
+            None | Some(0) => return Ok(None),
+            Some(height) => height,
+        };
+        let block_hash = connection.block_hash(block_height).await?;
+        Ok(Some(AnchorInclusion {
+            block_hash,
+            block_height,
+        }))
+    }
+
+    async fn fee_histogram(&self) -> Result<Option<FeeHistogram>, Self::Error> {
+        let mut connection = self.connect().await?;
+        let buckets: Vec<(f64, u64)> = connection
+            .call("mempool.get_fee_histogram", json!([]))
+            .await?;
+        let entries = buckets
+            .into_iter()
+            .map(|(fee_rate, vsize)| (fee_rate as u64, vsize));
+        Ok(Some(FeeHistogram::from_entries(entries)))
+    }
+
+    async fn relay_fee_policy(&self) -> Result<Option<btc::RelayFeePolicy>, Self::Error> {
+        let mut connection = self.connect().await?;
+        let relay_fee: BtcAmount = connection.call("blockchain.relayfee", json!([])).await?;
+        // The Electrum protocol doesn't expose the incremental relay fee, so the default
+        // value of the Bitcoin Core is used.
+        Ok(Some(btc::RelayFeePolicy {
+            min_relay_fee: relay_fee.as_sat(),
+            incremental_relay_fee: btc::DEFAULT_RELAY_FEE,
+        }))
+    }
+
+    async fn estimate_smart_fee(
+        &self,
+        confirmation_target: u32,
+    ) -> Result<Option<u64>, Self::Error> {
+        let mut connection = self.connect().await?;
+        let fee_rate: Value = connection
+            .call("blockchain.estimatefee", json!([confirmation_target]))
+            .await?;
+        // The server responds with `-1` if the Bitcoin node can't estimate the fee.
+        if fee_rate.as_f64().map_or(false, |fee_rate| fee_rate < 0.0) {
+            return Ok(None);
+        }
+        let fee_rate: BtcAmount = serde_json::from_value(fee_rate)
+            .map_err(|e| ElectrumError::InvalidResponse(e.to_string()))?;
+        Ok(Some((fee_rate.as_sat() + 999) / 1000))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{script_hash, server_error, ElectrumError};
+
+    #[test]
+    fn script_hash_matches_electrum_protocol() {
+        // Example from the Electrum protocol documentation.
+        let address = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"
+            .parse::<bitcoin::Address>()
+            .unwrap();
+        assert_eq!(
+            script_hash(&address.script_pubkey()),
+            "8b01df4e368ea28f8dc0423bcf7a4923e3a12d307c875e47a0cfbf90b5c39161"
+        );
+    }
+
+    #[test]
+    fn server_errors_are_parsed() {
+        let error = server_error(&json!({ "code": 1, "message": "txn-mempool-conflict" }));
+        match error {
+            ElectrumError::Server { code, message } => {
+                assert_eq!(code, 1);
+                assert_eq!(message, "txn-mempool-conflict");
+            }
+            other => panic!("Unexpected error: {:?}", other),
+        }
+
+        // Legacy servers return errors as plain strings.
+        let error = server_error(&json!("missing transaction"));
+        match error {
+            ElectrumError::Server { code, message } => {
+                assert_eq!(code, 0);
+                assert_eq!(message, "missing transaction");
+            }
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+}
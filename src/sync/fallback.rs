@@ -0,0 +1,233 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bitcoin relay which fails over between several backends.
+
+use async_trait::async_trait;
+
+use std::{
+    fmt::Display,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{blockchain::AnchorInclusion, btc};
+
+use super::{BitcoinRelay, FeeHistogram, TransactionStatus};
+
+/// Health of the backend of the [`FallbackRelay`].
+///
+/// [`FallbackRelay`]: struct.FallbackRelay.html
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RelayHealth {
+    /// Number of the failed requests since the latest successful one.
+    pub consecutive_failures: u32,
+    /// Total number of the failed requests.
+    pub total_failures: u64,
+    /// Time of the latest failed request.
+    pub last_failure: Option<Instant>,
+    /// Description of the latest error which made the backend unavailable.
+    pub last_error: Option<String>,
+}
+
+impl RelayHealth {
+    /// Checks that the latest request to the backend has succeeded.
+    pub fn is_healthy(&self) -> bool {
+        self.consecutive_failures == 0
+    }
+
+    fn is_suspended(&self, retry_interval: Duration) -> bool {
+        !self.is_healthy()
+            && self
+                .last_failure
+                .map_or(false, |time| time.elapsed() < retry_interval)
+    }
+
+    fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+    }
+
+    fn record_failure(&mut self, error: &impl Display) {
+        self.consecutive_failures += 1;
+        self.total_failures += 1;
+        self.last_failure = Some(Instant::now());
+        self.last_error = Some(error.to_string());
+    }
+}
+
+/// Bitcoin relay which wraps an ordered list of backends and fails over to the next
+/// backend if the current one is unavailable.
+///
+/// Each request is sent to the first backend which has not failed recently. If the backend
+/// returns an error, for which [`BitcoinRelay::is_unavailable`] returns `true`, the request
+/// is repeated with the next backend. Other errors, for example, the rejection of the sent
+/// transaction, are returned as is. A failed backend is skipped during the retry interval,
+/// then it gets requests again, so the relay returns to the preferred backends once they
+/// recover. If all the backends are unavailable, the error of the last one is returned.
+///
+/// The relay is cheap to clone, and all clones share the health of the backends.
+///
+/// [`BitcoinRelay::is_unavailable`]: trait.BitcoinRelay.html#method.is_unavailable
+#[derive(Debug, Clone)]
+pub struct FallbackRelay<R> {
+    backends: Arc<Vec<R>>,
+    health: Arc<Mutex<Vec<RelayHealth>>>,
+    retry_interval: Duration,
+}
+
+impl<R> FallbackRelay<R> {
+    /// Default interval during which the failed backend is skipped.
+    pub const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Creates a relay with the given backends in order of preference.
+    ///
+    /// # Panics
+    ///
+    /// If the list of backends is empty.
+    pub fn new(backends: impl IntoIterator<Item = R>) -> Self {
+        let backends = backends.into_iter().collect::<Vec<_>>();
+        assert!(
+            !backends.is_empty(),
+            "Fallback relay requires at least one backend"
+        );
+        let health = vec![RelayHealth::default(); backends.len()];
+        Self {
+            backends: Arc::new(backends),
+            health: Arc::new(Mutex::new(health)),
+            retry_interval: Self::DEFAULT_RETRY_INTERVAL,
+        }
+    }
+
+    /// Sets the interval during which the failed backend is skipped.
+    pub fn with_retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    /// Returns the backends in order of preference.
+    pub fn backends(&self) -> &[R] {
+        &self.backends
+    }
+
+    /// Returns the health of the backends in order of preference.
+    pub fn health(&self) -> Vec<RelayHealth> {
+        self.health.lock().unwrap().clone()
+    }
+
+    /// Returns the backend indices in order of the request attempts: the backends
+    /// which have not failed recently go first.
+    fn attempts_order(&self) -> Vec<usize> {
+        let health = self.health.lock().unwrap();
+        let (active, suspended): (Vec<_>, Vec<_>) = (0..self.backends.len())
+            .partition(|&index| !health[index].is_suspended(self.retry_interval));
+        active.into_iter().chain(suspended).collect()
+    }
+}
+
+impl<R> FallbackRelay<R>
+where
+    R: BitcoinRelay,
+    R::Error: Display,
+{
+    async fn request<'a, T, F, Fut>(&'a self, request: F) -> Result<T, R::Error>
+    where
+        F: Fn(&'a R) -> Fut,
+        Fut: Future<Output = Result<T, R::Error>>,
+    {
+        let mut last_error = None;
+        for index in self.attempts_order() {
+            match request(&self.backends[index]).await {
+                Err(e) if R::is_unavailable(&e) => {
+                    log::warn!("Bitcoin relay backend #{} is unavailable. {}", index, e);
+                    self.health.lock().unwrap()[index].record_failure(&e);
+                    last_error = Some(e);
+                }
+                // The backend has responded, so it is available even if the request failed.
+                response => {
+                    self.health.lock().unwrap()[index].record_success();
+                    return response;
+                }
+            }
+        }
+        Err(last_error.expect("Fallback relay has no backends"))
+    }
+}
+
+#[async_trait]
+impl<R> BitcoinRelay for FallbackRelay<R>
+where
+    R: BitcoinRelay + Send + Sync,
+    R::Error: Display + Send,
+{
+    type Error = R::Error;
+
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        self.request(|relay| relay.send_transaction(transaction))
+            .await
+    }
+
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        self.request(|relay| relay.transaction_status(id)).await
+    }
+
+    fn is_mempool_conflict(error: &Self::Error) -> bool {
+        R::is_mempool_conflict(error)
+    }
+
+    fn is_unavailable(error: &Self::Error) -> bool {
+        R::is_unavailable(error)
+    }
+
+    async fn find_conflicting_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        self.request(|relay| relay.find_conflicting_transaction(transaction))
+            .await
+    }
+
+    async fn transaction_inclusion(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<AnchorInclusion>, Self::Error> {
+        self.request(|relay| relay.transaction_inclusion(id)).await
+    }
+
+    async fn fee_histogram(&self) -> Result<Option<FeeHistogram>, Self::Error> {
+        self.request(|relay| relay.fee_histogram()).await
+    }
+
+    async fn relay_fee_policy(&self) -> Result<Option<btc::RelayFeePolicy>, Self::Error> {
+        self.request(|relay| relay.relay_fee_policy()).await
+    }
+
+    async fn estimate_smart_fee(
+        &self,
+        confirmation_target: u32,
+    ) -> Result<Option<u64>, Self::Error> {
+        self.request(|relay| relay.estimate_smart_fee(confirmation_target))
+            .await
+    }
+    async fn mempool_fee_rate(&self, id: btc::Sha256d) -> Result<Option<u64>, Self::Error> {
+        self.request(|relay| relay.mempool_fee_rate(id)).await
+    }
+
+    async fn merkle_proof(&self, id: btc::Sha256d) -> Result<Option<btc::SpvProof>, Self::Error> {
+        self.request(|relay| relay.merkle_proof(id)).await
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pool of the Bitcoin keys used by the sync tasks to sign anchoring proposals.
+
+use thiserror::Error;
+
+use std::sync::Arc;
+
+use crate::btc;
+
+/// Error that occurs when the same Bitcoin key is added to the key pool more than once.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("Bitcoin key {0} is specified more than once in the key pool")]
+pub struct DuplicateKeyError(pub btc::PublicKey);
+
+/// Ordered pool of the Bitcoin keypairs.
+///
+/// The order of the keys defines their priority: if several keys of the pool are
+/// suitable for signing, the key added first is selected. Thus the behavior of the sync
+/// tasks with several keys doesn't depend on the hashing of the keys and is the same
+/// across runs. The pool is cheap to clone.
+#[derive(Debug, Clone, Default)]
+pub struct KeyPool {
+    keys: Arc<Vec<(btc::PublicKey, btc::PrivateKey)>>,
+}
+
+impl KeyPool {
+    /// Creates a pool with the given keypairs in order of their priority.
+    ///
+    /// Returns an error if the same public key occurs more than once.
+    pub fn new(
+        keys: impl IntoIterator<Item = (btc::PublicKey, btc::PrivateKey)>,
+    ) -> Result<Self, DuplicateKeyError> {
+        let mut pool = Vec::<(btc::PublicKey, btc::PrivateKey)>::new();
+        for (public_key, private_key) in keys {
+            if pool.iter().any(|(key, _)| *key == public_key) {
+                return Err(DuplicateKeyError(public_key));
+            }
+            pool.push((public_key, private_key));
+        }
+        Ok(Self {
+            keys: Arc::new(pool),
+        })
+    }
+
+    /// Returns the number of keys in the pool.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Checks that the pool contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the public keys in order of their priority.
+    pub fn public_keys(&self) -> impl Iterator<Item = btc::PublicKey> + '_ {
+        self.keys.iter().map(|(public_key, _)| *public_key)
+    }
+
+    /// Returns the private key corresponding to the given public key, if it is in the pool.
+    pub fn get(&self, public_key: &btc::PublicKey) -> Option<&btc::PrivateKey> {
+        self.keys
+            .iter()
+            .find(|(key, _)| key == public_key)
+            .map(|(_, private_key)| private_key)
+    }
+
+    /// Selects the keypair with the highest priority among the given public keys.
+    pub fn select(
+        &self,
+        public_keys: impl IntoIterator<Item = btc::PublicKey>,
+    ) -> Option<(btc::PublicKey, btc::PrivateKey)> {
+        let public_keys = public_keys.into_iter().collect::<Vec<_>>();
+        self.keys
+            .iter()
+            .find(|(key, _)| public_keys.contains(key))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Network;
+
+    use super::{DuplicateKeyError, KeyPool};
+    use crate::btc;
+
+    #[test]
+    fn key_pool_priority() {
+        let keypairs = (0..3)
+            .map(|_| btc::gen_keypair(Network::Testnet))
+            .collect::<Vec<_>>();
+        let pool = KeyPool::new(keypairs.clone()).unwrap();
+        assert_eq!(pool.len(), 3);
+        assert_eq!(
+            pool.public_keys().collect::<Vec<_>>(),
+            keypairs.iter().map(|(key, _)| *key).collect::<Vec<_>>()
+        );
+
+        // The first key of the pool wins regardless of the order of the requested keys.
+        let selected = pool.select(vec![keypairs[2].0, keypairs[1].0]).unwrap();
+        assert_eq!(selected.0, keypairs[1].0);
+        assert_eq!(pool.select(vec![keypairs[2].0]).unwrap().0, keypairs[2].0);
+
+        let unknown_key = btc::gen_keypair(Network::Testnet).0;
+        assert!(pool.select(vec![unknown_key]).is_none());
+        assert!(pool.get(&unknown_key).is_none());
+    }
+
+    #[test]
+    fn key_pool_rejects_duplicates() {
+        let keypair = btc::gen_keypair(Network::Testnet);
+        let other = btc::gen_keypair(Network::Testnet);
+        let e = KeyPool::new(vec![keypair.clone(), other, keypair.clone()]).unwrap_err();
+        assert_eq!(e, DuplicateKeyError(keypair.0));
+    }
+}
@@ -0,0 +1,352 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Encrypted on-disk storage of the Bitcoin keys used by the sync utility.
+//!
+//! The keystore is a JSON file with the AES-256-GCM encrypted secrets, the encryption
+//! key is derived from the passphrase by the scrypt KDF with a random salt. The
+//! passphrase is taken from the `BTC_ANCHORING_KEYSTORE_PASSPHRASE` environment variable
+//! or is prompted from the terminal.
+
+use aes_gcm::{
+    aead::{Aead, NewAead},
+    Aes256Gcm, Key, Nonce,
+};
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::btc;
+
+/// Environment variable with the keystore passphrase.
+pub const KEYSTORE_PASSPHRASE_ENV: &str = "BTC_ANCHORING_KEYSTORE_PASSPHRASE";
+
+/// Current version of the keystore file format.
+const KEYSTORE_VERSION: u8 = 1;
+/// Length of the random salt of the KDF.
+const SALT_LEN: usize = 16;
+/// Length of the AES-GCM nonce, which is stored in the beginning of the encrypted data.
+const NONCE_LEN: usize = 12;
+/// Length of the AES-GCM authentication tag.
+const TAG_LEN: usize = 16;
+
+/// Keystore decryption errors.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum KeystoreError {
+    /// Keystore file has a newer format than the one supported by this version.
+    #[error("Unsupported keystore version {0}.")]
+    UnsupportedVersion(u8),
+    /// KDF parameters of the keystore are invalid.
+    #[error("Invalid KDF parameters of the keystore.")]
+    InvalidKdfParams,
+    /// Keystore is malformed or the passphrase is wrong.
+    #[error("Unable to decrypt keystore, perhaps the passphrase is wrong.")]
+    DecryptionFailed,
+    /// Decrypted keystore has an unexpected format.
+    #[error("Keystore is malformed.")]
+    MalformedKeystore,
+}
+
+/// Secrets stored in the keystore.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Keystore {
+    /// Bitcoin keypairs in order of their priority.
+    pub bitcoin_keys: Vec<(btc::PublicKey, btc::PrivateKey)>,
+    /// BIP-32 extended private key in the Base58 encoding from which the Bitcoin keys
+    /// are derived.
+    #[serde(default)]
+    pub xprv: Option<String>,
+}
+
+impl fmt::Debug for Keystore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keystore")
+            .field(
+                "bitcoin_keys",
+                &self
+                    .bitcoin_keys
+                    .iter()
+                    .map(|(public_key, _)| public_key)
+                    .collect::<Vec<_>>(),
+            )
+            .field("has_xprv", &self.xprv.is_some())
+            .finish()
+    }
+}
+
+/// Parameters of the scrypt KDF.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScryptParams {
+    /// Base-2 logarithm of the CPU/memory cost.
+    pub log_n: u8,
+    /// Block size.
+    pub r: u32,
+    /// Parallelization.
+    pub p: u32,
+    /// Hex-encoded random salt.
+    pub salt: String,
+}
+
+impl ScryptParams {
+    /// Creates parameters recommended for the interactive logins with a random salt.
+    pub fn random() -> Self {
+        let salt: [u8; SALT_LEN] = rand::thread_rng().gen();
+        Self {
+            log_n: 15,
+            r: 8,
+            p: 1,
+            salt: hex::encode(salt),
+        }
+    }
+
+    fn derive_key(&self, passphrase: &str) -> Result<[u8; 32], KeystoreError> {
+        let salt = hex::decode(&self.salt).map_err(|_| KeystoreError::InvalidKdfParams)?;
+        let params = scrypt::ScryptParams::new(self.log_n, self.r, self.p)
+            .map_err(|_| KeystoreError::InvalidKdfParams)?;
+        let mut key = [0_u8; 32];
+        scrypt::scrypt(passphrase.as_bytes(), &salt, &params, &mut key)
+            .map_err(|_| KeystoreError::InvalidKdfParams)?;
+        Ok(key)
+    }
+}
+
+/// Keystore file with the encrypted secrets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncryptedKeystore {
+    /// Version of the keystore format.
+    pub version: u8,
+    /// Parameters of the KDF which derives the encryption key from the passphrase.
+    pub kdf: ScryptParams,
+    /// Hex-encoded AES-256-GCM nonce and ciphertext of the JSON-serialized keystore.
+    pub data: String,
+}
+
+impl Keystore {
+    /// Encrypts the keystore by the given passphrase. The KDF salt and the nonce are
+    /// randomly generated.
+    pub fn encrypt(&self, passphrase: &str) -> EncryptedKeystore {
+        self.encrypt_with(passphrase, ScryptParams::random())
+            .expect("Default KDF parameters are valid")
+    }
+
+    /// Encrypts the keystore by the given passphrase with the specified KDF parameters.
+    pub fn encrypt_with(
+        &self,
+        passphrase: &str,
+        kdf: ScryptParams,
+    ) -> Result<EncryptedKeystore, KeystoreError> {
+        let key = kdf.derive_key(passphrase)?;
+        let plaintext = serde_json::to_vec(self).expect("Unable to serialize keystore");
+        let nonce: [u8; NONCE_LEN] = rand::thread_rng().gen();
+        let ciphertext = cipher(&key)
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_ref())
+            .expect("Unable to encrypt keystore");
+
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&ciphertext);
+        Ok(EncryptedKeystore {
+            version: KEYSTORE_VERSION,
+            kdf,
+            data: hex::encode(data),
+        })
+    }
+
+    /// Loads and decrypts the keystore file.
+    pub fn load(path: impl AsRef<Path>, passphrase: &str) -> anyhow::Result<Self> {
+        let encrypted: EncryptedKeystore = serde_json::from_slice(&fs::read(path)?)?;
+        encrypted.decrypt(passphrase).map_err(From::from)
+    }
+
+    /// Encrypts the keystore and atomically replaces the keystore file with it.
+    /// On Unix, the file is readable only by its owner.
+    pub fn save(&self, path: impl AsRef<Path>, passphrase: &str) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let tmp_path = path.with_extension("tmp");
+        fs::write(
+            &tmp_path,
+            serde_json::to_vec_pretty(&self.encrypt(passphrase))?,
+        )?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&tmp_path, fs::Permissions::from_mode(0o600))?;
+        }
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+}
+
+impl EncryptedKeystore {
+    /// Decrypts the keystore by the given passphrase.
+    pub fn decrypt(&self, passphrase: &str) -> Result<Keystore, KeystoreError> {
+        if self.version > KEYSTORE_VERSION {
+            return Err(KeystoreError::UnsupportedVersion(self.version));
+        }
+
+        let data = hex::decode(&self.data).map_err(|_| KeystoreError::DecryptionFailed)?;
+        if data.len() < NONCE_LEN + TAG_LEN {
+            return Err(KeystoreError::DecryptionFailed);
+        }
+
+        let key = self.kdf.derive_key(passphrase)?;
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = cipher(&key)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| KeystoreError::DecryptionFailed)?;
+        serde_json::from_slice(&plaintext).map_err(|_| KeystoreError::MalformedKeystore)
+    }
+}
+
+fn cipher(key: &[u8; 32]) -> Aes256Gcm {
+    Aes256Gcm::new(Key::from_slice(key))
+}
+
+/// Returns the keystore passphrase from the `BTC_ANCHORING_KEYSTORE_PASSPHRASE`
+/// environment variable or prompts it from the terminal.
+pub fn read_passphrase(keystore_path: &Path) -> anyhow::Result<String> {
+    if let Ok(passphrase) = std::env::var(KEYSTORE_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let prompt = format!("Passphrase of the keystore {}: ", keystore_path.display());
+    rpassword::read_password_from_tty(Some(&prompt)).map_err(From::from)
+}
+
+/// Prompts the passphrase of the new keystore twice from the terminal, unless it is
+/// specified by the `BTC_ANCHORING_KEYSTORE_PASSPHRASE` environment variable.
+pub fn read_new_passphrase(keystore_path: &Path) -> anyhow::Result<String> {
+    if let Ok(passphrase) = std::env::var(KEYSTORE_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let passphrase = read_passphrase(keystore_path)?;
+    let confirmation = rpassword::read_password_from_tty(Some("Repeat the passphrase: "))?;
+    anyhow::ensure!(passphrase == confirmation, "Passphrases don't match");
+    Ok(passphrase)
+}
+
+/// Keystore unlocked by the passphrase, which is kept to re-encrypt the updated secrets.
+#[derive(Clone)]
+pub struct UnlockedKeystore {
+    path: PathBuf,
+    passphrase: String,
+    /// Decrypted secrets.
+    pub keystore: Keystore,
+}
+
+impl fmt::Debug for UnlockedKeystore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnlockedKeystore")
+            .field("path", &self.path)
+            .field("keystore", &self.keystore)
+            .finish()
+    }
+}
+
+impl UnlockedKeystore {
+    /// Creates an empty keystore which will be saved to the given path.
+    pub fn create(path: impl Into<PathBuf>, passphrase: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            passphrase: passphrase.into(),
+            keystore: Keystore::default(),
+        }
+    }
+
+    /// Unlocks the keystore file by the given passphrase.
+    pub fn open(path: impl Into<PathBuf>, passphrase: impl Into<String>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let passphrase = passphrase.into();
+        let keystore = Keystore::load(&path, &passphrase)?;
+        Ok(Self {
+            path,
+            passphrase,
+            keystore,
+        })
+    }
+
+    /// Returns the path to the keystore file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Re-encrypts the secrets and saves them to the keystore file.
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.keystore.save(&self.path, &self.passphrase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Network;
+
+    use super::{Keystore, KeystoreError, ScryptParams, UnlockedKeystore};
+    use crate::btc;
+
+    // Cheap KDF parameters to keep the tests fast.
+    fn test_params() -> ScryptParams {
+        ScryptParams {
+            log_n: 4,
+            ..ScryptParams::random()
+        }
+    }
+
+    #[test]
+    fn keystore_roundtrip() {
+        let keystore = Keystore {
+            bitcoin_keys: vec![btc::gen_keypair(Network::Testnet)],
+            xprv: Some("tprv".to_owned()),
+        };
+        let encrypted = keystore.encrypt_with("passphrase", test_params()).unwrap();
+        assert_eq!(encrypted.decrypt("passphrase").unwrap(), keystore);
+        assert_eq!(
+            encrypted.decrypt("wrong passphrase").unwrap_err(),
+            KeystoreError::DecryptionFailed
+        );
+
+        let mut newer = encrypted;
+        newer.version += 1;
+        assert_eq!(
+            newer.decrypt("passphrase").unwrap_err(),
+            KeystoreError::UnsupportedVersion(newer.version)
+        );
+    }
+
+    #[test]
+    fn keystore_file() {
+        let path = std::env::temp_dir().join(format!("keystore-{}.json", rand::random::<u64>()));
+        let mut unlocked = UnlockedKeystore::create(&path, "passphrase");
+        unlocked
+            .keystore
+            .bitcoin_keys
+            .push(btc::gen_keypair(Network::Testnet));
+        unlocked.save().unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let private_key = unlocked.keystore.bitcoin_keys[0].1.to_string();
+        assert!(!content.contains(&private_key));
+
+        let reopened = UnlockedKeystore::open(&path, "passphrase").unwrap();
+        assert_eq!(reopened.keystore, unlocked.keystore);
+        assert!(UnlockedKeystore::open(&path, "wrong passphrase").is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -13,28 +13,106 @@
 // limitations under the License.
 
 //! Building blocks of the anchoring sync utility.
+//!
+//! The sync tasks are driven by the async `process` methods, which never block
+//! the current thread, provided that the API client and the Bitcoin relay are
+//! non-blocking as well, like the `BitcoinRpcClient` and `ElectrumRelay` relays.
+//! The task futures are `Send`, so the tasks of several anchoring instances can be
+//! spawned on a single `tokio` runtime and synced concurrently from one process.
 
-pub use self::bitcoin_relay::{BitcoinRelay, TransactionStatus};
+#[cfg(feature = "redis")]
+pub use self::checkpoint::RedisCheckpointStore;
+#[cfg(feature = "sled")]
+pub use self::checkpoint::SledCheckpointStore;
+#[cfg(feature = "zmq")]
+pub use self::notifications::{BitcoinEvent, ZmqNotifications};
+pub use self::{
+    auth::{
+        AuthError, PrivateApiAuth, PrivateApiScope, MANAGEMENT_API_KEY_ENV,
+        MANAGEMENT_API_TOKEN_ENV, NONCE_HEADER, SIGNING_API_KEY_ENV, SIGNING_API_TOKEN_ENV,
+    },
+    bitcoin_relay::{
+        BitcoinRelay, BitcoinRpcClient, FeeHistogram, TransactionStatus, UnspentOutput,
+    },
+    bootstrap::{check_bootstrap, BootstrapCheck, BootstrapItem, BootstrapReport, CheckStatus},
+    checkpoint::{BroadcastRecord, CheckpointStore, FileCheckpointStore, SyncCheckpoint},
+    electrum::{ElectrumError, ElectrumRelay},
+    fallback::{FallbackRelay, RelayHealth},
+    key_pool::{DuplicateKeyError, KeyPool},
+    keystore::{
+        read_new_passphrase, read_passphrase, EncryptedKeystore, Keystore, KeystoreError,
+        ScryptParams, UnlockedKeystore, KEYSTORE_PASSPHRASE_ENV,
+    },
+    offline::{InputSighash, OfflineSignature, OfflineSignatures, SighashExport},
+    recording::{
+        RecordedError, RecordingRelay, RelayRecord, RelayRequest, ReplayError, ReplayRelay,
+    },
+    review::{QueuedProposal, ReviewDecision, ReviewPolicy, ReviewQueue},
+    rpc_client::BitcoinRpcClientBuilder,
+    runner::{MultiSyncRunner, SyncRunner, SyncRunnerStatus, SyncTaskStatus},
+    signer::{
+        HwiKey, HwiSigner, RemoteSignRequest, RemoteSigner, Signer, AUDIT_LOG_TARGET,
+        SIGNATURE_HEADER, TIMESTAMP_HEADER,
+    },
+};
 
 use anyhow::anyhow;
-use btc_transaction_utils::{p2wsh, TxInRef};
+use futures::lock::Mutex as AsyncMutex;
+use serde_derive::{Deserialize, Serialize};
+use tokio::time::delay_for;
 
-use std::{collections::HashMap, fmt::Display, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 use crate::{
-    api::{AnchoringProposalState, PrivateApi},
-    blockchain::SignInput,
+    api::{
+        AnchorConfirmationState, AnchoringProposalState, FundingTransactionState, PrivateApi,
+        ServiceVersion, SpecialTxApprovalState,
+    },
+    blockchain::{ConfirmAnchor, ReportFeeEstimate, RequestCpfp, SignInput},
     btc,
     config::Config,
+    psbt::ProposalPsbt,
 };
 
+use self::review::ReviewStatus;
+
+mod amount;
+mod auth;
 mod bitcoin_relay;
+mod bootstrap;
+mod checkpoint;
+mod electrum;
+mod fallback;
+mod key_pool;
+mod keystore;
+#[cfg(feature = "zmq")]
+mod notifications;
+mod offline;
+mod recording;
+mod review;
+mod rpc_client;
+mod runner;
+mod signer;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+/// Maximum exponent of the backoff multiplier, which prevents the overflow of the delay.
+const MAX_BACKOFF_EXPONENT: u32 = 16;
+/// Interval of the shutdown checks while waiting for the retry.
+const SHUTDOWN_CHECK_INTERVAL: Duration = Duration::from_millis(200);
 
 /// Anchoring transaction with its index in the anchoring chain.
 pub type TransactionWithIndex = (btc::Transaction, u64);
 
-type KeyPool = Arc<HashMap<btc::PublicKey, btc::PrivateKey>>;
-
 /// Errors that occur when updating the anchoring chain.
 #[derive(Debug)]
 pub enum ChainUpdateError<C: Display> {
@@ -49,10 +127,160 @@ pub enum ChainUpdateError<C: Display> {
     },
     /// Initial funding transaction is absent.
     NoInitialFunds,
+    /// Anchoring proposal has been queued for the human review and is not signed
+    /// until it is approved or the review window expires.
+    AwaitingReview {
+        /// Identifier of the proposed anchoring transaction.
+        txid: btc::Sha256d,
+        /// Time left until the end of the review window.
+        remaining: Duration,
+    },
+    /// Anchoring proposal has been denied by the reviewer.
+    ReviewDenied {
+        /// Identifier of the proposed anchoring transaction.
+        txid: btc::Sha256d,
+    },
     /// Internal error.
     Internal(anyhow::Error),
 }
 
+impl<C: Display> ChainUpdateError<C> {
+    /// Returns `true` if the error is transient, i.e. the failed attempt may succeed
+    /// if repeated. Errors of the private API client are considered transient.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ChainUpdateError::Client(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Policy which is used to verify anchoring proposals in the verify-only mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VerificationPolicy {
+    /// Maximum total fee of the anchoring transaction in satoshis.
+    pub max_fee: Option<u64>,
+}
+
+impl VerificationPolicy {
+    /// Checks the anchoring proposal against this policy and the given configuration.
+    /// Returns the list of found violations.
+    pub fn check_proposal(
+        &self,
+        config: &Config,
+        proposal: &btc::Transaction,
+        inputs: &[btc::Transaction],
+    ) -> Vec<VerificationAlert> {
+        let txid = proposal.id();
+        let mismatch = |reason: String| VerificationAlert::ProposalMismatch { txid, reason };
+
+        if proposal.anchoring_payload().is_none() {
+            return vec![mismatch("Proposal has no anchoring payload".to_owned())];
+        }
+        if proposal.as_ref().input.len() != inputs.len() {
+            return vec![mismatch(format!(
+                "Proposal has {} inputs, but {} input transactions are given",
+                proposal.as_ref().input.len(),
+                inputs.len()
+            ))];
+        }
+
+        let anchoring_script = config.anchoring_out_script();
+        let fee_payer_script = config.fee_payer_out_script();
+        let mut alerts = Vec::new();
+        let mut prev_outputs = Vec::with_capacity(inputs.len());
+        for (index, (txin, input)) in proposal.as_ref().input.iter().zip(inputs).enumerate() {
+            let outpoint = txin.previous_output;
+            let prev_output = if outpoint.txid == input.as_ref().txid() {
+                input.as_ref().output.get(outpoint.vout as usize)
+            } else {
+                None
+            };
+            let prev_output = match prev_output {
+                Some(prev_output) => prev_output,
+                None => {
+                    return vec![mismatch(format!(
+                        "Input {} doesn't spend the given transaction {}",
+                        index,
+                        input.id()
+                    ))]
+                }
+            };
+
+            if prev_output.script_pubkey != anchoring_script
+                && Some(&prev_output.script_pubkey) != fee_payer_script.as_ref()
+            {
+                alerts.push(mismatch(format!(
+                    "Input {} spends funds of an unknown wallet",
+                    index
+                )));
+            }
+            prev_outputs.push(prev_output.clone());
+        }
+
+        match proposal.fee(&prev_outputs) {
+            None => alerts.push(mismatch("Proposal spends more than its inputs".to_owned())),
+            Some(fee) => match self.max_fee {
+                Some(max_fee) if fee > max_fee => {
+                    alerts.push(VerificationAlert::ExcessiveFee { txid, fee, max_fee })
+                }
+                _ => {}
+            },
+        }
+        alerts
+    }
+}
+
+/// Inconsistencies found by the sync tasks in the verify-only mode.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationAlert {
+    /// Anchoring proposal doesn't match the verification policy.
+    ProposalMismatch {
+        /// Identifier of the proposed transaction.
+        txid: btc::Sha256d,
+        /// Description of the mismatch.
+        reason: String,
+    },
+    /// Anchoring proposal pays a greater fee than the verification policy allows.
+    ExcessiveFee {
+        /// Identifier of the proposed transaction.
+        txid: btc::Sha256d,
+        /// Total fee of the proposed transaction.
+        fee: u64,
+        /// Maximum fee allowed by the policy.
+        max_fee: u64,
+    },
+    /// Anchoring transaction is unknown to the Bitcoin network, although it is spent
+    /// by the following anchoring transaction, which is known.
+    MissingOnChain {
+        /// Index of the transaction in the anchoring chain.
+        index: u64,
+        /// Identifier of the transaction.
+        txid: btc::Sha256d,
+    },
+}
+
+impl Display for VerificationAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationAlert::ProposalMismatch { txid, reason } => {
+                write!(f, "Anchoring proposal {} is unexpected: {}", txid, reason)
+            }
+            VerificationAlert::ExcessiveFee { txid, fee, max_fee } => write!(
+                f,
+                "Anchoring proposal {} pays fee {}, which exceeds the limit {}",
+                txid, fee, max_fee
+            ),
+            VerificationAlert::MissingOnChain { index, txid } => write!(
+                f,
+                "Anchoring transaction {} with index {} is absent in the Bitcoin network, \
+                 but the following anchoring transaction is known",
+                txid, index
+            ),
+        }
+    }
+}
+
 /// Signs the inputs of the anchoring transaction proposal by the corresponding
 /// Bitcoin private keys.
 #[derive(Debug)]
@@ -60,8 +288,17 @@ pub struct AnchoringChainUpdateTask<T>
 where
     T: PrivateApi + 'static,
 {
-    key_pool: KeyPool,
+    signer: Arc<dyn Signer>,
     api_client: T,
+    /// Identifier of the latest proposal signed by this task.
+    signed_proposal: Mutex<Option<btc::Sha256d>>,
+    /// Bitcoin key selected to sign the latest proposal.
+    signing_key: Mutex<Option<btc::PublicKey>>,
+    review_queue: Option<ReviewQueue>,
+    retry_policy: Option<RetryPolicy>,
+    /// Held while processing, so the shutdown waits for the in-flight signing.
+    processing: AsyncMutex<()>,
+    is_shut_down: AtomicBool,
 }
 
 impl<T> AnchoringChainUpdateTask<T>
@@ -69,24 +306,113 @@ where
     T: PrivateApi + 'static,
     T::Error: Display,
 {
-    /// Creates a new anchoring chain updater instance.
-    pub fn new(
-        keys: impl IntoIterator<Item = (btc::PublicKey, btc::PrivateKey)>,
-        api_client: T,
-    ) -> Self {
+    /// Creates a new anchoring chain updater instance with the given pool of the Bitcoin keys.
+    pub fn new(key_pool: KeyPool, api_client: T) -> Self {
+        Self::with_signer(key_pool, api_client)
+    }
+
+    /// Creates a new anchoring chain updater instance with the given signer, for example,
+    /// the `HwiSigner` which keeps the Bitcoin keys on the hardware wallet.
+    pub fn with_signer(signer: impl Signer + 'static, api_client: T) -> Self {
         Self {
-            key_pool: Arc::new(keys.into_iter().collect()),
+            signer: Arc::new(signer),
             api_client,
+            signed_proposal: Mutex::default(),
+            signing_key: Mutex::default(),
+            review_queue: None,
+            retry_policy: None,
+            processing: AsyncMutex::new(()),
+            is_shut_down: AtomicBool::new(false),
         }
     }
 
+    /// Sets the queue of the anchoring proposals waiting for the human review.
+    /// The proposals which require review according to the policy of the queue
+    /// are not signed until they are approved or the review window expires.
+    pub fn with_review_queue(mut self, review_queue: ReviewQueue) -> Self {
+        self.review_queue = Some(review_queue);
+        self
+    }
+
+    /// Sets the policy of retrying the attempts failed due to transient errors.
+    /// Without the policy the errors are returned to the caller immediately.
+    ///
+    /// The policy is not used by the `SyncRunner`, which retries the failed rounds
+    /// with its own backoff.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Returns the Bitcoin key selected from the signer keys to sign the latest anchoring
+    /// proposal, if any.
+    pub fn signing_key(&self) -> Option<btc::PublicKey> {
+        *self.signing_key.lock().unwrap()
+    }
+
     /// Returns an actual anchoring configuration.
     pub async fn anchoring_config(&self) -> Result<Config, T::Error> {
         self.api_client.config().await
     }
 
-    /// Performs one attempt to sign an anchoring proposal, if any.
+    /// Checks that the anchoring service of the node is compatible with this build of
+    /// the sync utility. It should be called before processing, otherwise the sync utility
+    /// may silently misinterpret the node responses after the partial upgrade.
+    pub async fn check_compatibility(&self) -> Result<ServiceVersion, ChainUpdateError<T::Error>> {
+        let version = self
+            .api_client
+            .version()
+            .await
+            .map_err(ChainUpdateError::Client)?;
+        ServiceVersion::current()
+            .check_compatibility(&version)
+            .map_err(|e| {
+                ChainUpdateError::Internal(
+                    e.context("Anchoring service of the node is incompatible"),
+                )
+            })?;
+        Ok(version)
+    }
+
+    /// Stops the task. Waits for the in-flight `process` call, so the signatures which
+    /// have been already made are sent to the node. The in-flight call doesn't start
+    /// signing of a new proposal, and the subsequent `process` calls return immediately
+    /// without doing anything.
+    pub async fn shutdown(&self) {
+        self.is_shut_down.store(true, Ordering::SeqCst);
+        let _processing = self.processing.lock().await;
+        log::info!("Anchoring chain update task has been shut down");
+    }
+
+    /// Returns `true` if the task has been shut down.
+    pub fn is_shut_down(&self) -> bool {
+        self.is_shut_down.load(Ordering::SeqCst)
+    }
+
+    /// Performs one attempt to sign an anchoring proposal, if any. The attempt is retried
+    /// according to the retry policy, if it fails due to a transient error.
     pub async fn process(&self) -> Result<(), ChainUpdateError<T::Error>> {
+        RetryPolicy::retry(
+            self.retry_policy.as_ref(),
+            ChainUpdateError::is_retryable,
+            || self.is_shut_down(),
+            || self.process_once(),
+        )
+        .await
+    }
+
+    /// Performs one attempt to sign an anchoring proposal without retries. The `SyncRunner`
+    /// uses it, since it retries the failed rounds with its own backoff.
+    pub(crate) async fn process_once(&self) -> Result<(), ChainUpdateError<T::Error>> {
+        // The lock is released between the retries, so the shutdown doesn't wait for them.
+        let _processing = self.processing.lock().await;
+        if self.is_shut_down() {
+            return Ok(());
+        }
+        self.update_chain().await
+    }
+
+    async fn update_chain(&self) -> Result<(), ChainUpdateError<T::Error>> {
         log::trace!("Perform an anchoring chain update");
 
         match self
@@ -95,7 +421,10 @@ where
             .await
             .map_err(ChainUpdateError::Client)?
         {
-            AnchoringProposalState::None => Ok(()),
+            AnchoringProposalState::None { reason } => {
+                log::trace!("No anchoring proposal: {}", reason);
+                Ok(())
+            }
             AnchoringProposalState::Available {
                 transaction,
                 inputs,
@@ -113,6 +442,110 @@ where
         }
     }
 
+    /// Checks the anchoring proposal, if any, against the given policy without signing it.
+    /// Returns the list of found violations.
+    pub async fn verify_proposal(
+        &self,
+        policy: &VerificationPolicy,
+    ) -> Result<Vec<VerificationAlert>, ChainUpdateError<T::Error>> {
+        log::trace!("Verify an anchoring proposal");
+
+        match self
+            .api_client
+            .anchoring_proposal()
+            .await
+            .map_err(ChainUpdateError::Client)?
+        {
+            AnchoringProposalState::None { .. } => Ok(Vec::new()),
+            AnchoringProposalState::Available {
+                transaction,
+                inputs,
+            } => {
+                let config = self
+                    .anchoring_config()
+                    .await
+                    .map_err(ChainUpdateError::Client)?;
+                Ok(policy.check_proposal(&config, &transaction, &inputs))
+            }
+            AnchoringProposalState::InsufficientFunds { balance, total_fee } => {
+                Err(ChainUpdateError::InsufficientFunds { balance, total_fee })
+            }
+            AnchoringProposalState::NoInitialFunds => Err(ChainUpdateError::NoInitialFunds),
+        }
+    }
+
+    /// Exports the signature hashes of the anchoring proposal for the offline signing.
+    pub async fn export_sighashes(&self) -> Result<SighashExport, ChainUpdateError<T::Error>> {
+        let (config, proposal, inputs) = self.actual_proposal().await?;
+        SighashExport::new(&config, &proposal, &inputs).map_err(ChainUpdateError::Internal)
+    }
+
+    /// Verifies the signatures made offline against the actual anchoring proposal
+    /// and submits them to the anchoring service. Returns the number of submitted
+    /// signatures.
+    pub async fn import_signatures(
+        &self,
+        signatures: &OfflineSignatures,
+    ) -> Result<usize, ChainUpdateError<T::Error>> {
+        let (config, proposal, inputs) = self.actual_proposal().await?;
+        SighashExport::new(&config, &proposal, &inputs)
+            .and_then(|export| export.verify(signatures))
+            .map_err(ChainUpdateError::Internal)?;
+
+        let sign_inputs = signatures
+            .signatures
+            .iter()
+            .map(|signature| SignInput {
+                txid: signatures.txid,
+                input: signature.input,
+                input_signature: signature.signature.clone(),
+                sighash_type: btc::SigHashType::All,
+            })
+            .collect::<Vec<_>>();
+        if !sign_inputs.is_empty() {
+            self.api_client
+                .sign_inputs(sign_inputs)
+                .await
+                .map_err(ChainUpdateError::Client)?;
+        }
+        log::info!(
+            "Submitted {} offline signatures for the anchoring transaction proposal {}",
+            signatures.signatures.len(),
+            signatures.txid
+        );
+        Ok(signatures.signatures.len())
+    }
+
+    async fn actual_proposal(
+        &self,
+    ) -> Result<(Config, btc::Transaction, Vec<btc::Transaction>), ChainUpdateError<T::Error>> {
+        match self
+            .api_client
+            .anchoring_proposal()
+            .await
+            .map_err(ChainUpdateError::Client)?
+        {
+            AnchoringProposalState::None { reason } => Err(ChainUpdateError::Internal(anyhow!(
+                "No anchoring proposal: {}",
+                reason
+            ))),
+            AnchoringProposalState::Available {
+                transaction,
+                inputs,
+            } => {
+                let config = self
+                    .anchoring_config()
+                    .await
+                    .map_err(ChainUpdateError::Client)?;
+                Ok((config, transaction, inputs))
+            }
+            AnchoringProposalState::InsufficientFunds { balance, total_fee } => {
+                Err(ChainUpdateError::InsufficientFunds { balance, total_fee })
+            }
+            AnchoringProposalState::NoInitialFunds => Err(ChainUpdateError::NoInitialFunds),
+        }
+    }
+
     async fn handle_proposal(
         &self,
         config: Config,
@@ -120,17 +553,20 @@ where
         inputs: Vec<btc::Transaction>,
     ) -> Result<(), ChainUpdateError<T::Error>> {
         log::trace!("Got an anchoring proposal: {:?}", proposal);
-        // Find among the keys one from which we have a private part.
-        // TODO What we have to do if we find more than one key? [ECR-3222]
-        let keypair = if let Some(keypair) =
-            self.find_private_key(config.anchoring_keys.iter().map(|x| x.bitcoin_key))
-        {
-            keypair
-        } else {
+        // Find among the keys one which the signer has. If there are several such keys,
+        // the one with the highest priority is selected.
+        let signer_keys = self.signer.signing_keys();
+        let signing_key = signer_keys.iter().copied().find(|key| {
+            config
+                .anchoring_keys
+                .iter()
+                .any(|keys| keys.bitcoin_key == *key)
+        });
+        // Find the key of the fee wallet if it is specified.
+        let fee_payer_key = config.fee_payer_key.filter(|key| signer_keys.contains(key));
+        if signing_key.is_none() && fee_payer_key.is_none() {
             return Ok(());
-        };
-        // Create `SignInput` transactions.
-        let redeem_script = config.redeem_script();
+        }
         let block_height = match proposal.anchoring_payload() {
             Some(payload) => payload.block_height,
             None => {
@@ -141,51 +577,86 @@ where
             }
         };
 
-        log::info!(
-            "Found a new unfinished anchoring transaction proposal for height: {}",
-            block_height
-        );
+        let txid = proposal.id();
+        if let Some(review_queue) = self.review_queue.as_ref() {
+            let spent_value = review::spent_value(&config, &proposal, &inputs);
+            match review_queue.check(txid, block_height, spent_value) {
+                ReviewStatus::Approved => {}
+                ReviewStatus::Pending(remaining) => {
+                    return Err(ChainUpdateError::AwaitingReview { txid, remaining })
+                }
+                ReviewStatus::Denied => return Err(ChainUpdateError::ReviewDenied { txid }),
+            }
+        }
 
-        let mut signer = p2wsh::InputSigner::new(redeem_script);
-        let sign_input_messages = inputs
-            .iter()
-            .enumerate()
-            .map(|(index, proposal_input)| {
-                let signature = signer.sign_input(
-                    TxInRef::new(proposal.as_ref(), index),
-                    proposal_input.as_ref(),
-                    &(keypair.1).0.key,
-                )?;
-
-                Ok(SignInput {
-                    input: index as u32,
-                    input_signature: signature.into(),
-                    txid: proposal.id(),
-                })
-            })
-            .collect::<anyhow::Result<Vec<_>>>()
+        // The signatures for this proposal have been already sent, so they are re-sent
+        // only if the proposal has stalled, for example, because the previous `sign_input`
+        // transactions have been dropped from the memory pool.
+        // The lock is released before awaiting, so the task future remains `Send`.
+        let is_signed = *self.signed_proposal.lock().unwrap() == Some(txid);
+        if is_signed {
+            match self
+                .api_client
+                .signing_progress()
+                .await
+                .map_err(ChainUpdateError::Client)?
+            {
+                Some(progress) if progress.txid == txid && progress.is_stalled => log::warn!(
+                    "Anchoring transaction proposal {} for height {} has been waiting for \
+                     a quorum of signatures for {} blocks, re-submitting signatures",
+                    txid,
+                    block_height,
+                    progress.waiting_blocks
+                ),
+                _ => return Ok(()),
+            }
+        } else {
+            log::info!(
+                "Found a new unfinished anchoring transaction proposal for height: {}",
+                block_height
+            );
+        }
+        // Don't start signing after the shutdown, since the signer may be slow to respond.
+        if self.is_shut_down() {
+            return Ok(());
+        }
+        if let Some(public_key) = signing_key {
+            log::info!(
+                "Signing anchoring transaction proposal {} with the Bitcoin key {}",
+                txid,
+                public_key
+            );
+        }
+
+        // Create `SignInput` transactions.
+        let mut psbt = ProposalPsbt::new(&config, &proposal, &inputs)
+            .and_then(|psbt| psbt.to_psbt())
+            .map_err(ChainUpdateError::Internal)?;
+        let keys = signing_key
+            .into_iter()
+            .chain(fee_payer_key)
+            .collect::<Vec<_>>();
+        for &key in &keys {
+            psbt = self
+                .signer
+                .sign_psbt(psbt, key)
+                .await
+                .map_err(ChainUpdateError::Internal)?;
+        }
+        let sign_input_messages = ProposalPsbt::from_psbt(&psbt)
+            .signatures(&proposal, &keys)
             .map_err(ChainUpdateError::Internal)?;
-        // Send sign input transactions to the Exonum node.
-        for sign_input in sign_input_messages {
+        // Send sign input transactions to the Exonum node at once.
+        if !sign_input_messages.is_empty() {
             self.api_client
-                .sign_input(sign_input)
+                .sign_inputs(sign_input_messages)
                 .await
                 .map_err(ChainUpdateError::Client)?;
         }
+        *self.signed_proposal.lock().unwrap() = Some(txid);
+        *self.signing_key.lock().unwrap() = signing_key;
         Ok(())
     }
-
-    fn find_private_key(
-        &self,
-        anchoring_keys: impl IntoIterator<Item = btc::PublicKey>,
-    ) -> Option<(btc::PublicKey, btc::PrivateKey)> {
-        anchoring_keys.into_iter().find_map(|public_key| {
-            self.key_pool
-                .get(&public_key)
-                .cloned()
-                .map(|private_key| (public_key, private_key))
-        })
-    }
 }
 
 /// Errors that occur when updating the sync with Bitcoin task.
@@ -199,6 +670,174 @@ pub enum SyncWithBitcoinError<C: Display, R: Display> {
     Internal(anyhow::Error),
     /// Initial funding transaction is unconfirmed.
     UnconfirmedFundingTransaction(btc::Sha256d),
+    /// Anchoring transaction has been rejected, because another transaction in the memory
+    /// pool spends the same outputs. For example, a different variant of the anchoring
+    /// transaction has been already broadcast.
+    MempoolConflict {
+        /// Identifier of the rejected anchoring transaction.
+        txid: btc::Sha256d,
+        /// Identifier of the conflicting transaction, if it has been found.
+        conflicting_txid: Option<btc::Sha256d>,
+    },
+    /// Anchoring transaction has been rejected, and the minimal fee rate in the anchoring
+    /// configuration is lower than the minimal relay fee rate of the Bitcoin node.
+    RelayFeeTooLow {
+        /// Identifier of the rejected anchoring transaction.
+        txid: btc::Sha256d,
+        /// The `min_relay_fee` value of the anchoring configuration.
+        min_relay_fee: u64,
+        /// The `minrelaytxfee` value of the Bitcoin node.
+        node_min_relay_fee: u64,
+    },
+    /// Anchoring transaction requires approvals of the validators according to
+    /// the emergency brake of the anchoring configuration, and has not collected
+    /// enough of them yet.
+    AwaitingApprovals {
+        /// Identifier of the anchoring transaction.
+        txid: btc::Sha256d,
+        /// Number of the recorded approvals.
+        approvals: u64,
+        /// Number of the required approvals.
+        required: u64,
+    },
+}
+
+impl<C: Display, R: Display> SyncWithBitcoinError<C, R> {
+    /// Returns `true` if the error is transient, i.e. the failed attempt may succeed
+    /// if repeated. Errors of the private API client and the Bitcoin relay are
+    /// considered transient.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            SyncWithBitcoinError::Client(_) | SyncWithBitcoinError::Relay(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Policy of retrying the attempts of the sync tasks failed due to transient errors.
+///
+/// The delay before the retry grows exponentially from the initial delay up to
+/// the maximum delay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of retries of the failed attempt.
+    pub max_retries: u32,
+    /// Delay before the first retry.
+    pub initial_delay: Duration,
+    /// Maximum delay between the retries.
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the retry with the given zero-based number.
+    pub fn delay(&self, retry: u32) -> Duration {
+        backoff_delay(self.initial_delay, self.max_delay, retry)
+    }
+
+    /// Performs the attempt and retries it while it fails due to transient errors
+    /// and the number of retries doesn't exceed the maximum, or the `should_stop`
+    /// check returns `true`.
+    async fn retry<T, E, F, Fut>(
+        policy: Option<&Self>,
+        is_retryable: impl Fn(&E) -> bool,
+        should_stop: impl Fn() -> bool,
+        mut attempt: F,
+    ) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut retry = 0;
+        loop {
+            let res = attempt().await;
+            let policy = match (policy, &res) {
+                (Some(policy), Err(e)) if is_retryable(e) => policy,
+                _ => return res,
+            };
+            if retry >= policy.max_retries || should_stop() {
+                return res;
+            }
+            let delay = policy.delay(retry);
+            log::warn!(
+                "Sync attempt has failed due to a transient error, retrying in {} ms",
+                delay.as_millis()
+            );
+            delay_unless_shut_down(delay, &should_stop).await;
+            retry += 1;
+        }
+    }
+}
+
+/// Returns the delay which grows exponentially with the given exponent from the initial
+/// delay up to the maximum delay.
+pub(crate) fn backoff_delay(
+    initial_delay: Duration,
+    max_delay: Duration,
+    exponent: u32,
+) -> Duration {
+    initial_delay
+        .checked_mul(2_u32.pow(exponent.min(MAX_BACKOFF_EXPONENT)))
+        .map_or(max_delay, |delay| delay.min(max_delay))
+}
+
+/// Waits for the given delay in short steps, so the shutdown isn't delayed by the long
+/// backoff.
+pub(crate) async fn delay_unless_shut_down(delay: Duration, is_shut_down: impl Fn() -> bool) {
+    let deadline = Instant::now() + delay;
+    while !is_shut_down() {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        delay_for((deadline - now).min(SHUTDOWN_CHECK_INTERVAL)).await;
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Policy which is used to delay broadcasting of the anchoring transactions while
+/// the Bitcoin memory pool is congested.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BroadcastPolicy {
+    /// Fee rate in satoshis per virtual byte. Broadcasting is delayed while the estimated
+    /// fee rate required to get into the next block exceeds this value.
+    pub target_fee_rate: u64,
+    /// Maximum delay of broadcasting of the anchoring transaction. After this delay
+    /// the transaction is broadcast regardless of the estimated fee rate.
+    pub max_delay: Duration,
+}
+
+/// Proof that the anchoring transaction has been committed to the Bitcoin blockchain,
+/// which third parties can verify without trusting the Exonum node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchorSpvProof {
+    /// Index of the anchoring transaction in the anchoring chain.
+    pub index: u64,
+    /// Anchoring transaction.
+    pub transaction: btc::Transaction,
+    /// Header of the Bitcoin block containing the anchoring transaction together with
+    /// the Merkle branch of the transaction.
+    pub proof: btc::SpvProof,
+}
+
+impl AnchorSpvProof {
+    /// Verifies the proof and returns the hash of the Bitcoin block containing
+    /// the anchoring transaction.
+    ///
+    /// The verifier should check separately that the block belongs to the main chain
+    /// of the Bitcoin network, and that the anchoring payload of the transaction matches
+    /// the Exonum block.
+    pub fn verify(&self) -> Result<btc::Sha256d, btc::SpvProofError> {
+        self.proof.verify(self.transaction.id())
+    }
 }
 
 /// Pushes anchoring transactions to the Bitcoin blockchain.
@@ -210,6 +849,36 @@ where
 {
     btc_relay: R,
     api_client: T,
+    /// Conflicting transactions found in the memory pool for the rejected anchoring
+    /// transactions.
+    mempool_conflicts: Mutex<HashMap<btc::Sha256d, btc::Sha256d>>,
+    broadcast_policy: Option<BroadcastPolicy>,
+    retry_policy: Option<RetryPolicy>,
+    /// Moments when broadcasting of the anchoring transactions has been delayed for
+    /// the first time.
+    delayed_since: Mutex<HashMap<btc::Sha256d, Instant>>,
+    /// Index and identifier of the latest anchoring transaction sent to the Bitcoin network.
+    last_broadcast: Mutex<Option<(u64, btc::Sha256d)>>,
+    /// Latest fee estimate reported to the anchoring service.
+    last_fee_estimate: Mutex<Option<u64>>,
+    /// Identifier of the latest anchoring transaction for which the fee bumping
+    /// has been requested.
+    last_cpfp_request: Mutex<Option<btc::Sha256d>>,
+    /// State of the task saved to the checkpoint store.
+    checkpoint: Mutex<SyncCheckpoint>,
+    checkpoint_store: Option<BoxedCheckpointStore>,
+    /// Held while processing, so the shutdown waits for the in-flight broadcast.
+    processing: AsyncMutex<()>,
+    is_shut_down: AtomicBool,
+}
+
+/// Checkpoint store used by the sync with Bitcoin task.
+struct BoxedCheckpointStore(Box<dyn CheckpointStore + Send + Sync>);
+
+impl fmt::Debug for BoxedCheckpointStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("BoxedCheckpointStore").finish()
+    }
 }
 
 impl<T, R> SyncWithBitcoinTask<T, R>
@@ -224,14 +893,175 @@ where
         Self {
             api_client,
             btc_relay,
+            mempool_conflicts: Mutex::default(),
+            broadcast_policy: None,
+            retry_policy: None,
+            delayed_since: Mutex::default(),
+            last_broadcast: Mutex::default(),
+            last_fee_estimate: Mutex::default(),
+            last_cpfp_request: Mutex::default(),
+            checkpoint: Mutex::default(),
+            checkpoint_store: None,
+            processing: AsyncMutex::new(()),
+            is_shut_down: AtomicBool::new(false),
+        }
+    }
+
+    /// Sets the policy of delaying the anchoring transactions broadcasting.
+    pub fn with_broadcast_policy(mut self, policy: BroadcastPolicy) -> Self {
+        self.broadcast_policy = Some(policy);
+        self
+    }
+
+    /// Sets the policy of retrying the attempts failed due to transient errors.
+    /// Without the policy the errors are returned to the caller immediately.
+    ///
+    /// The policy is not used by the `SyncRunner`, which retries the failed rounds
+    /// with its own backoff.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Sets the store to which the checkpoint of the task is saved after each change,
+    /// starting with the given checkpoint loaded from the store.
+    pub fn with_checkpoint_store(
+        mut self,
+        store: impl CheckpointStore + Send + Sync + 'static,
+        checkpoint: SyncCheckpoint,
+    ) -> Self {
+        *self.last_broadcast.get_mut().unwrap() = checkpoint
+            .broadcast_history
+            .last()
+            .map(|record| (record.index, record.txid));
+        self.checkpoint_store = Some(BoxedCheckpointStore(Box::new(store)));
+        *self.checkpoint.get_mut().unwrap() = checkpoint;
+        self
+    }
+
+    /// Sets the store to which the checkpoint of the task is saved after each change,
+    /// starting with the checkpoint loaded from the store, if any. Thus the restarted
+    /// task resumes from the `checkpoint().latest_synced_tx_index` instead of rescanning
+    /// the whole anchoring chain against the Bitcoin relay.
+    pub async fn with_restored_checkpoint(
+        self,
+        store: impl CheckpointStore + Send + Sync + 'static,
+    ) -> anyhow::Result<Self> {
+        let checkpoint = store.load().await?;
+        if let Some(checkpoint) = checkpoint.as_ref() {
+            log::info!(
+                "Restored the sync checkpoint, the latest synced transaction index is {:?}",
+                checkpoint.latest_synced_tx_index
+            );
+        }
+        Ok(self.with_checkpoint_store(store, checkpoint.unwrap_or_default()))
+    }
+
+    /// Returns the actual checkpoint of the task.
+    pub fn checkpoint(&self) -> SyncCheckpoint {
+        self.checkpoint.lock().unwrap().clone()
+    }
+
+    /// Stops the task. Waits for the in-flight `process` call, including the broadcast
+    /// of the anchoring transaction, and saves the checkpoint, so the broadcast
+    /// transaction is never lost from the checkpoint. The subsequent `process` calls
+    /// return immediately without doing anything.
+    pub async fn shutdown(&self) -> anyhow::Result<()> {
+        self.is_shut_down.store(true, Ordering::SeqCst);
+        let _processing = self.processing.lock().await;
+        if let Some(store) = self.checkpoint_store.as_ref() {
+            let checkpoint = self.checkpoint();
+            store.0.save(&checkpoint).await?;
         }
+        log::info!("Sync with Bitcoin task has been shut down");
+        Ok(())
+    }
+
+    /// Returns `true` if the task has been shut down.
+    pub fn is_shut_down(&self) -> bool {
+        self.is_shut_down.load(Ordering::SeqCst)
+    }
+
+    /// Returns the conflicting transactions found in the Bitcoin memory pool, indexed
+    /// by the identifiers of the rejected anchoring transactions.
+    pub fn mempool_conflicts(&self) -> HashMap<btc::Sha256d, btc::Sha256d> {
+        self.mempool_conflicts.lock().unwrap().clone()
+    }
+
+    /// Returns the index and the identifier of the latest anchoring transaction sent
+    /// to the Bitcoin network by this task, if any.
+    pub fn last_broadcast(&self) -> Option<(u64, btc::Sha256d)> {
+        *self.last_broadcast.lock().unwrap()
     }
 
     /// Performs one attempt to send the first uncommitted anchoring transaction into the Bitcoin network, if any.
     /// sign an anchoring proposal, if any. Returns an index of the last committed transaction.
+    ///
+    /// The attempt is retried according to the retry policy, if it fails due to
+    /// a transient error. The checkpoint of the task is updated and saved to
+    /// the checkpoint store, if any, before returning. If the attempt fails, the checkpoint
+    /// keeps the given index, so the caller can resume from `checkpoint()`.
     pub async fn process(
         &self,
         latest_committed_tx_index: Option<u64>,
+    ) -> Result<Option<u64>, SyncWithBitcoinError<T::Error, R::Error>> {
+        RetryPolicy::retry(
+            self.retry_policy.as_ref(),
+            SyncWithBitcoinError::is_retryable,
+            || self.is_shut_down(),
+            || self.process_once(latest_committed_tx_index),
+        )
+        .await
+    }
+
+    /// Performs one attempt to send the first uncommitted anchoring transaction without
+    /// retries. The `SyncRunner` uses it, since it retries the failed rounds with its
+    /// own backoff.
+    pub(crate) async fn process_once(
+        &self,
+        latest_committed_tx_index: Option<u64>,
+    ) -> Result<Option<u64>, SyncWithBitcoinError<T::Error, R::Error>> {
+        // The lock is released between the retries, so the shutdown doesn't wait for them.
+        let _processing = self.processing.lock().await;
+        if self.is_shut_down() {
+            return Ok(latest_committed_tx_index);
+        }
+
+        let res = self.sync(latest_committed_tx_index).await;
+        let index = res
+            .as_ref()
+            .map_or(latest_committed_tx_index, |index| *index);
+        self.update_checkpoint(index).await;
+        res
+    }
+
+    /// Records the index of the latest synced transaction and the latest broadcast
+    /// to the checkpoint, and saves it if it has changed.
+    async fn update_checkpoint(&self, latest_synced_tx_index: Option<u64>) {
+        let checkpoint = {
+            let mut checkpoint = self.checkpoint.lock().unwrap();
+            let mut is_changed = checkpoint.latest_synced_tx_index != latest_synced_tx_index;
+            checkpoint.latest_synced_tx_index = latest_synced_tx_index;
+            if let Some((index, txid)) = self.last_broadcast() {
+                is_changed |= checkpoint.record_broadcast(index, txid);
+            }
+            if !is_changed {
+                return;
+            }
+            checkpoint.clone()
+        };
+
+        if let Some(store) = self.checkpoint_store.as_ref() {
+            // The checkpoint is saved once again on shutdown.
+            if let Err(e) = store.0.save(&checkpoint).await {
+                log::error!("Unable to save the sync checkpoint. {}", e)
+            }
+        }
+    }
+
+    async fn sync(
+        &self,
+        latest_committed_tx_index: Option<u64>,
     ) -> Result<Option<u64>, SyncWithBitcoinError<T::Error, R::Error>> {
         log::trace!("Perform syncing with the Bitcoin network");
         // Try to find a suitable transaction for sending to the Bitcoin network.
@@ -265,12 +1095,42 @@ where
             return Ok(None);
         };
 
-        // Send an actual uncommitted transaction into the Bitcoin network.
-        self.btc_relay
-            .send_transaction(&transaction)
+        let approval_state = self
+            .api_client
+            .special_tx_approval_state(transaction.id())
             .await
-            .map_err(SyncWithBitcoinError::Relay)?;
+            .map_err(SyncWithBitcoinError::Client)?;
+        if let SpecialTxApprovalState::Pending {
+            approvers,
+            required,
+        } = approval_state
+        {
+            return Err(SyncWithBitcoinError::AwaitingApprovals {
+                txid: transaction.id(),
+                approvals: approvers.len() as u64,
+                required,
+            });
+        }
+
+        if self.should_delay_broadcast(&transaction).await {
+            return Ok(latest_committed_tx_index);
+        }
+        // Don't start a new broadcast after the shutdown.
+        if self.is_shut_down() {
+            return Ok(latest_committed_tx_index);
+        }
+
+        // Send an actual uncommitted transaction into the Bitcoin network.
+        if let Err(e) = self.btc_relay.send_transaction(&transaction).await {
+            return Err(if R::is_mempool_conflict(&e) {
+                self.mempool_conflict_error(&transaction).await
+            } else {
+                self.rejected_transaction_error(&transaction, e).await?
+            });
+        }
 
+        self.delayed_since.lock().unwrap().remove(&transaction.id());
+        *self.last_broadcast.lock().unwrap() = Some((index, transaction.id()));
         log::info!(
             "Sent transaction to the Bitcoin network: {}",
             transaction.id()
@@ -279,6 +1139,112 @@ where
         Ok(Some(index))
     }
 
+    /// Checks whether broadcasting of the anchoring transaction should be delayed according
+    /// to the broadcast policy and the fee histogram of the Bitcoin memory pool.
+    async fn should_delay_broadcast(&self, transaction: &btc::Transaction) -> bool {
+        let policy = match self.broadcast_policy {
+            Some(policy) => policy,
+            None => return false,
+        };
+
+        let txid = transaction.id();
+        let delayed_since = *self
+            .delayed_since
+            .lock()
+            .unwrap()
+            .entry(txid)
+            .or_insert_with(Instant::now);
+        if delayed_since.elapsed() >= policy.max_delay {
+            return false;
+        }
+
+        let fee_rate = match self.btc_relay.fee_histogram().await {
+            Ok(Some(histogram)) => histogram.next_block_fee_rate(),
+            Ok(None) => return false,
+            Err(e) => {
+                log::warn!("Unable to get the memory pool fee histogram: {}", e);
+                return false;
+            }
+        };
+        if fee_rate <= policy.target_fee_rate {
+            return false;
+        }
+
+        log::info!(
+            "Delaying broadcast of transaction {}: estimated fee rate {} sat/vB \
+             exceeds the target {} sat/vB",
+            txid,
+            fee_rate,
+            policy.target_fee_rate
+        );
+        true
+    }
+
+    /// Looks up the transaction which conflicts with the rejected anchoring transaction
+    /// and remembers it.
+    async fn mempool_conflict_error(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> SyncWithBitcoinError<T::Error, R::Error> {
+        let txid = transaction.id();
+        let conflicting_txid = self
+            .btc_relay
+            .find_conflicting_transaction(transaction)
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!(
+                    "Unable to find transaction conflicting with {}: {}",
+                    txid,
+                    e
+                );
+                None
+            });
+        if let Some(conflicting_txid) = conflicting_txid {
+            self.mempool_conflicts
+                .lock()
+                .unwrap()
+                .insert(txid, conflicting_txid);
+        }
+        SyncWithBitcoinError::MempoolConflict {
+            txid,
+            conflicting_txid,
+        }
+    }
+
+    /// Checks whether the rejected anchoring transaction could violate the relay policy
+    /// of the Bitcoin node, because the anchoring configuration allows lower fees than
+    /// the node accepts.
+    async fn rejected_transaction_error(
+        &self,
+        transaction: &btc::Transaction,
+        error: R::Error,
+    ) -> Result<SyncWithBitcoinError<T::Error, R::Error>, SyncWithBitcoinError<T::Error, R::Error>>
+    {
+        let policy = match self.btc_relay.relay_fee_policy().await {
+            Ok(Some(policy)) => policy,
+            Ok(None) => return Ok(SyncWithBitcoinError::Relay(error)),
+            Err(e) => {
+                log::warn!("Unable to get the relay fee policy: {}", e);
+                return Ok(SyncWithBitcoinError::Relay(error));
+            }
+        };
+        let config = self
+            .api_client
+            .config()
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+
+        Ok(if config.min_relay_fee < policy.min_relay_fee {
+            SyncWithBitcoinError::RelayFeeTooLow {
+                txid: transaction.id(),
+                min_relay_fee: config.min_relay_fee,
+                node_min_relay_fee: policy.min_relay_fee,
+            }
+        } else {
+            SyncWithBitcoinError::Relay(error)
+        })
+    }
+
     /// Finds the first anchoring transaction and its index, which was not committed into
     /// the Bitcoin blockchain.
     pub async fn find_first_uncommitted_transaction(
@@ -347,6 +1313,257 @@ where
         }
     }
 
+    /// Checks the anchoring transactions starting from the given index against the Bitcoin
+    /// network without sending anything to it.
+    ///
+    /// Returns the index from which the next check should start, i.e. the index
+    /// of the first transaction that is not committed to the Bitcoin blockchain yet,
+    /// and the list of found inconsistencies.
+    pub async fn verify_chain(
+        &self,
+        from_index: u64,
+    ) -> Result<(u64, Vec<VerificationAlert>), SyncWithBitcoinError<T::Error, R::Error>> {
+        log::trace!("Verify the anchoring chain from index {}", from_index);
+
+        let chain_len = self
+            .api_client
+            .transactions_count()
+            .await
+            .map_err(SyncWithBitcoinError::Client)?
+            .value;
+
+        let mut next_index = from_index;
+        let mut alerts = Vec::new();
+        // Transactions unknown to the Bitcoin network with their indices.
+        let mut unknown: Vec<(u64, btc::Transaction)> = Vec::new();
+        for index in from_index..chain_len {
+            let transaction = self.get_transaction(index).await?;
+            let status = self.transaction_status(transaction.id()).await?;
+            if !status.is_known() {
+                unknown.push((index, transaction));
+                continue;
+            }
+
+            // All of the unknown transactions spent by the known one
+            // should be known as well.
+            let mut spent_txid = transaction.prev_tx_id();
+            let mut missing = Vec::new();
+            while let Some((index, unknown_tx)) = unknown.pop() {
+                if unknown_tx.id() != spent_txid {
+                    break;
+                }
+                spent_txid = unknown_tx.prev_tx_id();
+                missing.push(VerificationAlert::MissingOnChain {
+                    index,
+                    txid: unknown_tx.id(),
+                });
+            }
+            alerts.extend(missing.into_iter().rev());
+            unknown.clear();
+
+            if status.confirmations().is_some() && next_index == index {
+                next_index = index + 1;
+            }
+        }
+        Ok((next_index, alerts))
+    }
+
+    /// Reports the location of the anchoring transaction with the given index in the Bitcoin
+    /// blockchain and the number of its confirmations to the anchoring service.
+    ///
+    /// Returns `false` if the transaction has not been committed yet. Transactions that
+    /// have been already finally confirmed by a quorum of the anchoring nodes are not
+    /// reported again, regardless of the local Bitcoin node state.
+    pub async fn confirm_anchor(
+        &self,
+        index: u64,
+    ) -> Result<bool, SyncWithBitcoinError<T::Error, R::Error>> {
+        let txid = self.get_transaction(index).await?.id();
+        let state = self
+            .api_client
+            .anchor_confirmation_state(txid)
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+        if let AnchorConfirmationState::Final { .. } = state {
+            return Ok(true);
+        }
+
+        let inclusion = self
+            .btc_relay
+            .transaction_inclusion(txid)
+            .await
+            .map_err(SyncWithBitcoinError::Relay)?;
+        let confirmations = self.transaction_status(txid).await?.confirmations();
+
+        if let (Some(inclusion), Some(confirmations)) = (inclusion, confirmations) {
+            self.api_client
+                .confirm_anchor(ConfirmAnchor {
+                    txid,
+                    block_hash: inclusion.block_hash,
+                    block_height: inclusion.block_height,
+                    confirmations,
+                })
+                .await
+                .map_err(SyncWithBitcoinError::Client)?;
+            log::info!(
+                "Confirmed anchoring transaction {} in the Bitcoin block {} at height {}, \
+                 confirmations: {}",
+                txid,
+                inclusion.block_hash,
+                inclusion.block_height,
+                confirmations
+            );
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Returns the proof of inclusion of the anchoring transaction with the given index
+    /// in the Bitcoin blockchain.
+    ///
+    /// Returns `None` if the transaction has not been committed yet or the Bitcoin relay
+    /// doesn't support the proofs. The Bitcoin Core relays use the `gettxoutproof` call,
+    /// which requires the `-txindex` option of the node.
+    pub async fn spv_proof(
+        &self,
+        index: u64,
+    ) -> Result<Option<AnchorSpvProof>, SyncWithBitcoinError<T::Error, R::Error>> {
+        let transaction = self.get_transaction(index).await?;
+        let proof = self
+            .btc_relay
+            .merkle_proof(transaction.id())
+            .await
+            .map_err(SyncWithBitcoinError::Relay)?;
+        Ok(proof.map(|proof| AnchorSpvProof {
+            index,
+            transaction,
+            proof,
+        }))
+    }
+
+    /// Reports the fee rate estimated by the Bitcoin node to the anchoring service
+    /// if the fee estimation is enabled in the anchoring configuration.
+    ///
+    /// The estimate is limited by the caps of the configuration and is reported only if it
+    /// differs from the previously reported one. Returns the reported estimate, if any.
+    pub async fn report_fee_estimate(
+        &self,
+    ) -> Result<Option<u64>, SyncWithBitcoinError<T::Error, R::Error>> {
+        let config = self
+            .api_client
+            .config()
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+        let fee_estimation = match config.fee_estimation {
+            Some(fee_estimation) => fee_estimation,
+            None => return Ok(None),
+        };
+
+        let fee = self
+            .btc_relay
+            .estimate_smart_fee(fee_estimation.confirmation_target)
+            .await
+            .map_err(SyncWithBitcoinError::Relay)?
+            .map(|fee| fee_estimation.clamp(fee));
+        let fee = match fee {
+            Some(fee) if *self.last_fee_estimate.lock().unwrap() != Some(fee) => fee,
+            _ => return Ok(None),
+        };
+
+        self.api_client
+            .report_fee_estimate(ReportFeeEstimate { fee })
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+        *self.last_fee_estimate.lock().unwrap() = Some(fee);
+        log::info!("Reported fee estimate: {} satoshis per byte", fee);
+        Ok(Some(fee))
+    }
+
+    /// Requests the fee bumping of the latest anchoring transaction by the child-pays-for-parent
+    /// transaction if it is stuck in the memory pool of the Bitcoin node.
+    ///
+    /// The transaction is considered stuck if its fee rate is lower than the rate required
+    /// to get into the next block. The requested fee rate of the package is limited by
+    /// the `max_cpfp_fee` of the anchoring configuration, and the fee bumping is requested
+    /// only once for each anchoring transaction. Returns the requested fee rate, if any.
+    pub async fn request_cpfp(
+        &self,
+    ) -> Result<Option<u64>, SyncWithBitcoinError<T::Error, R::Error>> {
+        let config = self
+            .api_client
+            .config()
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+        if config.max_cpfp_fee == 0 {
+            return Ok(None);
+        }
+
+        let count = self
+            .api_client
+            .transactions_count()
+            .await
+            .map_err(SyncWithBitcoinError::Client)?
+            .value;
+        let txid = match count.checked_sub(1) {
+            Some(index) => self.get_transaction(index).await?.id(),
+            None => return Ok(None),
+        };
+        if *self.last_cpfp_request.lock().unwrap() == Some(txid)
+            || self.transaction_status(txid).await? != TransactionStatus::Mempool
+        {
+            return Ok(None);
+        }
+
+        let fee_rate = self
+            .btc_relay
+            .mempool_fee_rate(txid)
+            .await
+            .map_err(SyncWithBitcoinError::Relay)?;
+        let next_block_fee_rate = self
+            .btc_relay
+            .fee_histogram()
+            .await
+            .map_err(SyncWithBitcoinError::Relay)?
+            .map(|histogram| histogram.next_block_fee_rate());
+        let fee = match (fee_rate, next_block_fee_rate) {
+            (Some(fee_rate), Some(next_block_fee_rate)) if fee_rate < next_block_fee_rate => {
+                next_block_fee_rate.min(config.max_cpfp_fee)
+            }
+            _ => return Ok(None),
+        };
+
+        self.api_client
+            .request_cpfp(RequestCpfp { txid, fee })
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+        *self.last_cpfp_request.lock().unwrap() = Some(txid);
+        log::info!(
+            "Requested CPFP fee bumping of the anchoring transaction {}: {} satoshis per byte",
+            txid,
+            fee
+        );
+        Ok(Some(fee))
+    }
+
+    /// Returns the state of the funding transaction in the anchoring service together with
+    /// its status in the Bitcoin network.
+    pub async fn funding_transaction_status(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<
+        (FundingTransactionState, TransactionStatus),
+        SyncWithBitcoinError<T::Error, R::Error>,
+    > {
+        let state = self
+            .api_client
+            .funding_transaction_state(txid)
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+        let status = self.transaction_status(txid).await?;
+        Ok((state, status))
+    }
+
     async fn get_transaction(
         &self,
         index: u64,
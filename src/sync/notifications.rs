@@ -0,0 +1,186 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Notifications of the Bitcoin node delivered via ZeroMQ.
+
+use bitcoin_hashes::{sha256d, Hash};
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    StreamExt,
+};
+use tokio::time::{delay_until, timeout_at, Instant};
+
+use std::{thread, time::Duration};
+
+use crate::btc;
+
+/// Size of the serialized Bitcoin block header.
+const BLOCK_HEADER_LEN: usize = 80;
+/// Timeout of the receive calls, after which the subscriber thread checks that
+/// the notifications are still listened to.
+const RECEIVE_TIMEOUT_MS: i32 = 1_000;
+
+/// Event in the Bitcoin network reported by the Bitcoin node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitcoinEvent {
+    /// A new block with the given hash has been connected to the blockchain.
+    Block(btc::Sha256d),
+    /// The transaction with the given identifier has been accepted to the memory pool
+    /// or committed to the block.
+    Transaction(btc::Sha256d),
+}
+
+/// Subscription to the `rawblock` and `hashtx` ZeroMQ notifications of the Bitcoin node.
+///
+/// The notifications allow the sync utility to check confirmations and to rebroadcast
+/// anchoring transactions as soon as something happens in the Bitcoin network instead of
+/// waiting for the next polling round. To enable the notifications, start `bitcoind` with
+/// the `-zmqpubrawblock` and `-zmqpubhashtx` options. ZeroMQ doesn't guarantee delivery,
+/// so the notifications should complement polling rather than replace it completely.
+#[derive(Debug)]
+pub struct ZmqNotifications {
+    receiver: UnboundedReceiver<BitcoinEvent>,
+}
+
+impl ZmqNotifications {
+    /// Subscribes to the notifications published by the Bitcoin node at the given address,
+    /// for example, `tcp://127.0.0.1:28332`.
+    pub fn subscribe(address: &str) -> Result<Self, zmq::Error> {
+        let context = zmq::Context::new();
+        let socket = context.socket(zmq::SUB)?;
+        socket.set_rcvtimeo(RECEIVE_TIMEOUT_MS)?;
+        socket.connect(address)?;
+        socket.set_subscribe(b"rawblock")?;
+        socket.set_subscribe(b"hashtx")?;
+
+        let (sender, receiver) = mpsc::unbounded();
+        let address = address.to_owned();
+        thread::spawn(move || receive_notifications(&socket, &sender, &address));
+        Ok(Self { receiver })
+    }
+
+    /// Waits for the next event which satisfies the given filter, but no longer than
+    /// the given timeout. Returns `None` if the timeout has expired.
+    pub async fn wait_for(
+        &mut self,
+        timeout: Duration,
+        filter: impl Fn(&BitcoinEvent) -> bool,
+    ) -> Option<BitcoinEvent> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match timeout_at(deadline, self.receiver.next()).await {
+                Ok(Some(event)) if filter(&event) => return Some(event),
+                Ok(Some(_)) => continue,
+                // The subscriber thread has stopped, so only the timeout is left.
+                Ok(None) => {
+                    delay_until(deadline).await;
+                    return None;
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+fn receive_notifications(
+    socket: &zmq::Socket,
+    sender: &UnboundedSender<BitcoinEvent>,
+    address: &str,
+) {
+    while !sender.is_closed() {
+        let parts = match socket.recv_multipart(0) {
+            Ok(parts) => parts,
+            Err(zmq::Error::EAGAIN) => continue,
+            Err(e) => {
+                log::error!(
+                    "Unable to receive notifications of the Bitcoin node at {}. {}",
+                    address,
+                    e
+                );
+                return;
+            }
+        };
+
+        // Each notification consists of the topic, the body and the sequence number.
+        if let [topic, body, ..] = parts.as_slice() {
+            if let Some(event) = parse_notification(topic, body) {
+                log::trace!("Received notification of the Bitcoin node: {:?}", event);
+                if sender.unbounded_send(event).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn parse_notification(topic: &[u8], body: &[u8]) -> Option<BitcoinEvent> {
+    match topic {
+        b"rawblock" => {
+            let header = body.get(..BLOCK_HEADER_LEN)?;
+            let block_hash = sha256d::Hash::hash(header);
+            Some(BitcoinEvent::Block(btc::Sha256d(block_hash)))
+        }
+        // The transaction identifier is published in the reversed byte order.
+        b"hashtx" => {
+            let mut txid = body.to_vec();
+            txid.reverse();
+            btc::Sha256d::from_slice(&txid).map(BitcoinEvent::Transaction)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_notification, BitcoinEvent};
+
+    #[test]
+    fn parse_block_notification() {
+        // Header of the genesis block of the Bitcoin mainnet.
+        let header = hex::decode(
+            "0100000000000000000000000000000000000000000000000000000000000000000000003ba3ed\
+             fd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c",
+        )
+        .unwrap();
+        let mut block = header.clone();
+        // The block body is ignored.
+        block.extend_from_slice(&[0; 16]);
+
+        let event = parse_notification(b"rawblock", &block).unwrap();
+        if let BitcoinEvent::Block(block_hash) = event {
+            assert_eq!(
+                block_hash.to_string(),
+                "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+            );
+        } else {
+            panic!("Unexpected event: {:?}", event);
+        }
+
+        assert_eq!(parse_notification(b"rawblock", &header[..40]), None);
+    }
+
+    #[test]
+    fn parse_transaction_notification() {
+        let txid = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+        let event = parse_notification(b"hashtx", &hex::decode(txid).unwrap()).unwrap();
+        if let BitcoinEvent::Transaction(id) = event {
+            assert_eq!(id.to_string(), txid);
+        } else {
+            panic!("Unexpected event: {:?}", event);
+        }
+
+        assert_eq!(parse_notification(b"hashtx", &[0; 16]), None);
+        assert_eq!(parse_notification(b"sequence", &[0; 32]), None);
+    }
+}
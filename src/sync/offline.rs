@@ -0,0 +1,189 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Offline signing of the anchoring proposals on the air-gapped machines.
+//!
+//! The signature hashes of the proposal are exported to a file, which is carried
+//! to the air-gapped machine holding the Bitcoin keys. The signatures made there
+//! are carried back and imported, the import verifies them against the actual
+//! proposal before submitting.
+
+use anyhow::{anyhow, ensure};
+use bitcoin::util::address::Address;
+use exonum::helpers::Height;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{blockchain::is_fee_payer_input, btc, config::Config};
+
+use super::KeyPool;
+
+/// Signature hashes of the anchoring proposal exported for the offline signing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SighashExport {
+    /// Identifier of the proposed anchoring transaction.
+    pub txid: btc::Sha256d,
+    /// Height of the block anchored by the proposal.
+    pub anchored_height: Height,
+    /// Signature hashes of the proposal inputs.
+    pub inputs: Vec<InputSighash>,
+}
+
+/// Signature hash of the anchoring proposal input.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputSighash {
+    /// Index of the input.
+    pub input: u32,
+    /// BIP-143 signature hash of the input with the `SIGHASH_ALL` type.
+    pub sighash: btc::Sha256d,
+    /// Bitcoin keys any of which can sign the input: the anchoring keys for the inputs
+    /// of the anchoring wallet and the fee payer key for the inputs of the fee wallet.
+    pub signers: Vec<btc::PublicKey>,
+}
+
+/// Signatures of the anchoring proposal made on the air-gapped machine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OfflineSignatures {
+    /// Identifier of the signed anchoring transaction.
+    pub txid: btc::Sha256d,
+    /// Signatures of the proposal inputs.
+    pub signatures: Vec<OfflineSignature>,
+}
+
+/// Signature of the anchoring proposal input.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OfflineSignature {
+    /// Index of the signed input.
+    pub input: u32,
+    /// Bitcoin key which has made the signature.
+    pub public_key: btc::PublicKey,
+    /// Signature of the input.
+    pub signature: btc::InputSignature,
+}
+
+impl SighashExport {
+    /// Computes the signature hashes of the given anchoring proposal.
+    pub fn new(
+        config: &Config,
+        proposal: &btc::Transaction,
+        inputs: &[btc::Transaction],
+    ) -> anyhow::Result<Self> {
+        let anchored_height = proposal
+            .anchoring_payload()
+            .ok_or_else(|| anyhow!("Incorrect anchoring proposal found: {:?}", proposal))?
+            .block_height;
+        let redeem_script = config.redeem_script();
+        let anchoring_keys = config
+            .anchoring_keys
+            .iter()
+            .map(|keys| keys.bitcoin_key)
+            .collect::<Vec<_>>();
+
+        let input_sighashes = proposal
+            .0
+            .input
+            .iter()
+            .zip(inputs)
+            .enumerate()
+            .map(|(index, (txin, input_tx))| {
+                let value = input_tx
+                    .0
+                    .output
+                    .get(txin.previous_output.vout as usize)
+                    .ok_or_else(|| anyhow!("Input {} spends a nonexistent output", index))?
+                    .value;
+                let (script_code, signers) = match config
+                    .fee_payer_key
+                    .filter(|_| is_fee_payer_input(config, proposal, inputs, index))
+                {
+                    Some(key) => (
+                        Address::p2pkh(&key.0, config.network).script_pubkey(),
+                        vec![key],
+                    ),
+                    None => (redeem_script.as_ref().clone(), anchoring_keys.clone()),
+                };
+                let sighash =
+                    proposal.signature_hash(index, &script_code, value, btc::SigHashType::All);
+                Ok(InputSighash {
+                    input: index as u32,
+                    sighash: sighash.into(),
+                    signers,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            txid: proposal.id(),
+            anchored_height,
+            inputs: input_sighashes,
+        })
+    }
+
+    /// Signs the exported signature hashes by the keys of the given key pool. Each input
+    /// is signed by the key with the highest priority among its signers, the inputs
+    /// without such keys are skipped.
+    pub fn sign(&self, key_pool: &KeyPool) -> OfflineSignatures {
+        let signatures = self
+            .inputs
+            .iter()
+            .filter_map(|input| {
+                let (public_key, private_key) = key_pool.select(input.signers.iter().copied())?;
+                let signature = btc::InputSignature::sign(
+                    input.sighash.as_ref(),
+                    btc::SigHashType::All,
+                    &private_key,
+                );
+                Some(OfflineSignature {
+                    input: input.input,
+                    public_key,
+                    signature,
+                })
+            })
+            .collect();
+
+        OfflineSignatures {
+            txid: self.txid,
+            signatures,
+        }
+    }
+
+    /// Verifies the offline signatures against the signature hashes of this export.
+    pub fn verify(&self, signatures: &OfflineSignatures) -> anyhow::Result<()> {
+        ensure!(
+            signatures.txid == self.txid,
+            "Signatures are made for the transaction {}, while the actual proposal is {}",
+            signatures.txid,
+            self.txid
+        );
+
+        for signature in &signatures.signatures {
+            let input = self
+                .inputs
+                .iter()
+                .find(|input| input.input == signature.input)
+                .ok_or_else(|| anyhow!("Proposal has no input {}", signature.input))?;
+            ensure!(
+                input.signers.contains(&signature.public_key),
+                "Bitcoin key {} can't sign the input {}",
+                signature.public_key,
+                signature.input
+            );
+            signature.signature.check_canonical(btc::SigHashType::All)?;
+            signature
+                .signature
+                .verify(input.sighash.as_ref(), &signature.public_key)
+                .map_err(|e| anyhow!("Invalid signature of the input {}: {}", input.input, e))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Human review of the anchoring proposals before signing.
+
+use exonum::helpers::Height;
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{btc, config::Config};
+
+/// Policy of the human review of the anchoring proposals.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReviewPolicy {
+    /// Proposals which spend more than this amount of satoshis from the anchoring
+    /// wallets require review. The spent amount includes the transaction fee and
+    /// the funds sent to other addresses, for example, during the transition
+    /// to a new anchoring address.
+    pub spend_threshold: u64,
+    /// Duration of the review. The proposal which has been neither approved
+    /// nor denied during this window is signed automatically.
+    pub review_window: Duration,
+}
+
+/// Decision of the reviewer on the anchoring proposal.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ReviewDecision {
+    /// The proposal is waiting for the decision until the end of the review window.
+    Pending,
+    /// The proposal has been approved and can be signed.
+    Approved,
+    /// The proposal has been denied and is never signed by this node.
+    Denied,
+}
+
+/// Anchoring proposal queued for review.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedProposal {
+    /// Identifier of the proposed anchoring transaction.
+    pub txid: btc::Sha256d,
+    /// Height of the block anchored by the proposal.
+    pub anchored_height: Height,
+    /// Amount of satoshis spent from the anchoring wallets by the proposal.
+    pub spent_value: u64,
+    /// Number of seconds the proposal has been in the queue.
+    pub waiting_secs: u64,
+    /// Number of seconds left until the end of the review window.
+    pub remaining_secs: u64,
+    /// Decision of the reviewer.
+    pub decision: ReviewDecision,
+}
+
+/// Outcome of the review for the proposal to be signed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ReviewStatus {
+    /// The proposal can be signed.
+    Approved,
+    /// The proposal is waiting for the decision during the given time.
+    Pending(Duration),
+    /// The proposal has been denied.
+    Denied,
+}
+
+#[derive(Debug)]
+struct ReviewEntry {
+    anchored_height: Height,
+    spent_value: u64,
+    queued_at: Instant,
+    decision: ReviewDecision,
+}
+
+/// Queue of the anchoring proposals waiting for the human review.
+///
+/// The anchoring chain update task puts each new proposal which spends more than
+/// the threshold of the [`ReviewPolicy`] into the queue and doesn't sign it until
+/// the proposal is approved or the review window expires. The queue is cheap to clone,
+/// and all clones share the same state, so the decisions can be made from another
+/// task, for example, from the handler of a local HTTP endpoint.
+///
+/// [`ReviewPolicy`]: struct.ReviewPolicy.html
+#[derive(Debug, Clone)]
+pub struct ReviewQueue {
+    policy: ReviewPolicy,
+    entries: Arc<Mutex<HashMap<btc::Sha256d, ReviewEntry>>>,
+}
+
+impl ReviewQueue {
+    /// Creates an empty queue with the given review policy.
+    pub fn new(policy: ReviewPolicy) -> Self {
+        Self {
+            policy,
+            entries: Arc::default(),
+        }
+    }
+
+    /// Returns the review policy.
+    pub fn policy(&self) -> ReviewPolicy {
+        self.policy
+    }
+
+    /// Approves the queued proposal with the given identifier. Returns `false`
+    /// if there is no such proposal in the queue.
+    pub fn approve(&self, txid: btc::Sha256d) -> bool {
+        self.decide(txid, ReviewDecision::Approved)
+    }
+
+    /// Denies the queued proposal with the given identifier. Returns `false`
+    /// if there is no such proposal in the queue.
+    pub fn deny(&self, txid: btc::Sha256d) -> bool {
+        self.decide(txid, ReviewDecision::Denied)
+    }
+
+    /// Returns the queued proposals in order of their anchored heights.
+    pub fn proposals(&self) -> Vec<QueuedProposal> {
+        let entries = self.entries.lock().unwrap();
+        let mut proposals = entries
+            .iter()
+            .map(|(&txid, entry)| {
+                let waiting_time = entry.queued_at.elapsed();
+                QueuedProposal {
+                    txid,
+                    anchored_height: entry.anchored_height,
+                    spent_value: entry.spent_value,
+                    waiting_secs: waiting_time.as_secs(),
+                    remaining_secs: self
+                        .policy
+                        .review_window
+                        .checked_sub(waiting_time)
+                        .unwrap_or_default()
+                        .as_secs(),
+                    decision: entry.decision,
+                }
+            })
+            .collect::<Vec<_>>();
+        proposals.sort_by_key(|proposal| (proposal.anchored_height, proposal.txid));
+        proposals
+    }
+
+    fn decide(&self, txid: btc::Sha256d, decision: ReviewDecision) -> bool {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(&txid) {
+            log::info!("Anchoring proposal {} has been {:?}", txid, decision);
+            entry.decision = decision;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Checks whether the proposal can be signed, and queues it for review if needed.
+    pub(crate) fn check(
+        &self,
+        txid: btc::Sha256d,
+        anchored_height: Height,
+        spent_value: u64,
+    ) -> ReviewStatus {
+        if spent_value <= self.policy.spend_threshold {
+            return ReviewStatus::Approved;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        // Proposals for the previous heights will never be signed.
+        entries.retain(|_, entry| entry.anchored_height >= anchored_height);
+        let entry = entries.entry(txid).or_insert_with(|| {
+            log::warn!(
+                "Anchoring proposal {} spends {} satoshis and has been queued for review",
+                txid,
+                spent_value
+            );
+            ReviewEntry {
+                anchored_height,
+                spent_value,
+                queued_at: Instant::now(),
+                decision: ReviewDecision::Pending,
+            }
+        });
+
+        match entry.decision {
+            ReviewDecision::Approved => ReviewStatus::Approved,
+            ReviewDecision::Denied => ReviewStatus::Denied,
+            ReviewDecision::Pending => {
+                match self
+                    .policy
+                    .review_window
+                    .checked_sub(entry.queued_at.elapsed())
+                {
+                    Some(remaining) if remaining > Duration::from_secs(0) => {
+                        ReviewStatus::Pending(remaining)
+                    }
+                    _ => ReviewStatus::Approved,
+                }
+            }
+        }
+    }
+}
+
+/// Computes the amount of satoshis spent by the proposal from the anchoring wallets
+/// of the given configuration, i.e. the inputs value without the change returned
+/// to the anchoring and the fee wallets.
+pub(crate) fn spent_value(
+    config: &Config,
+    proposal: &btc::Transaction,
+    inputs: &[btc::Transaction],
+) -> u64 {
+    let inputs_value = proposal
+        .0
+        .input
+        .iter()
+        .zip(inputs)
+        .filter_map(|(txin, input)| input.0.output.get(txin.previous_output.vout as usize))
+        .map(|output| output.value)
+        .sum::<u64>();
+
+    let anchoring_script = config.anchoring_out_script();
+    let fee_payer_script = config.fee_payer_out_script();
+    let change_value = proposal
+        .0
+        .output
+        .iter()
+        .filter(|output| {
+            output.script_pubkey == anchoring_script
+                || Some(&output.script_pubkey) == fee_payer_script.as_ref()
+        })
+        .map(|output| output.value)
+        .sum::<u64>();
+    inputs_value.saturating_sub(change_value)
+}
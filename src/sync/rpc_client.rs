@@ -0,0 +1,261 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! HTTP transport of the Bitcoin Core JSON-RPC client.
+
+use bitcoincore_rpc::RpcApi;
+use jsonrpc::Error as JsonRpcError;
+use serde_json::{json, Value};
+
+use std::{
+    fmt, io,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use super::BitcoinRpcClient;
+
+/// Builder of the [`BitcoinRpcClient`] with custom HTTP settings: timeouts, TLS options
+/// and proxy.
+///
+/// # Examples
+///
+/// ```no_run
+/// use exonum_btc_anchoring::sync::BitcoinRpcClient;
+///
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), bitcoincore_rpc::Error> {
+/// let ca_certificate = std::fs::read("bitcoind-ca.pem")?;
+/// let client = BitcoinRpcClient::builder(
+///     "https://bitcoind.example.com:8332",
+///     bitcoincore_rpc::Auth::UserPass("user".to_owned(), "password".to_owned()),
+/// )
+/// .connect_timeout(Duration::from_secs(5))
+/// .request_timeout(Duration::from_secs(30))
+/// .add_root_certificate(ca_certificate)
+/// .build()?;
+/// # drop(client);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`BitcoinRpcClient`]: struct.BitcoinRpcClient.html
+pub struct BitcoinRpcClientBuilder {
+    url: String,
+    auth: bitcoincore_rpc::Auth,
+    pool_size: usize,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    root_certificates: Vec<Vec<u8>>,
+    accept_invalid_certs: bool,
+    proxy: Option<String>,
+}
+
+impl BitcoinRpcClientBuilder {
+    pub(super) fn new(url: impl Into<String>, auth: bitcoincore_rpc::Auth) -> Self {
+        Self {
+            url: url.into(),
+            auth,
+            pool_size: BitcoinRpcClient::DEFAULT_POOL_SIZE,
+            connect_timeout: None,
+            request_timeout: None,
+            root_certificates: Vec::new(),
+            accept_invalid_certs: false,
+            proxy: None,
+        }
+    }
+
+    /// Sets the number of connections to the Bitcoin node. The pool contains
+    /// at least one connection.
+    pub fn pool_size(mut self, pool_size: usize) -> Self {
+        self.pool_size = pool_size;
+        self
+    }
+
+    /// Sets the timeout of the connection establishment, including the TLS handshake.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout of the whole request, including reading of the response.
+    /// By default, the requests have no timeout.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Adds a trusted root certificate in the PEM format, for example, a custom CA
+    /// certificate of the TLS proxy in front of the Bitcoin node.
+    pub fn add_root_certificate(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Disables the validation of the TLS certificates of the Bitcoin node.
+    ///
+    /// This makes the connection vulnerable to the man-in-the-middle attacks,
+    /// so it should only be used in test environments.
+    pub fn danger_accept_invalid_certs(mut self, accept_invalid_certs: bool) -> Self {
+        self.accept_invalid_certs = accept_invalid_certs;
+        self
+    }
+
+    /// Sends all requests through the proxy with the given URL, for example,
+    /// `socks5://127.0.0.1:9050` or `http://proxy.example.com:3128`.
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Creates the client with the specified settings.
+    ///
+    /// Returns an error if the certificates or the proxy URL are invalid, or the
+    /// credentials can't be read from the cookie file.
+    pub fn build(self) -> Result<BitcoinRpcClient, bitcoincore_rpc::Error> {
+        let (user, password) = self.auth.clone().get_user_pass()?;
+        let root_certificates = self
+            .root_certificates
+            .iter()
+            .map(|pem| reqwest::Certificate::from_pem(pem))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(transport_error)?;
+        let proxy = self
+            .proxy
+            .as_ref()
+            .map(reqwest::Proxy::all)
+            .transpose()
+            .map_err(transport_error)?;
+
+        let connections = (0..self.pool_size.max(1))
+            .map(|_| {
+                // Each connection keeps at most one idle HTTP connection to the node,
+                // thus the number of the HTTP connections is bounded by the pool size.
+                let mut builder = reqwest::Client::builder()
+                    .danger_accept_invalid_certs(self.accept_invalid_certs)
+                    .pool_max_idle_per_host(1);
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                if let Some(timeout) = self.request_timeout {
+                    builder = builder.timeout(timeout);
+                }
+                for certificate in &root_certificates {
+                    builder = builder.add_root_certificate(certificate.clone());
+                }
+                if let Some(proxy) = &proxy {
+                    builder = builder.proxy(proxy.clone());
+                }
+
+                Ok(RpcConnection {
+                    client: builder.build().map_err(transport_error)?,
+                    url: self.url.clone(),
+                    user: user.clone(),
+                    password: password.clone(),
+                    nonce: AtomicUsize::new(0),
+                })
+            })
+            .collect::<Result<Vec<_>, bitcoincore_rpc::Error>>()?;
+        Ok(BitcoinRpcClient::from_connections(self.url, connections))
+    }
+}
+
+impl fmt::Debug for BitcoinRpcClientBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Credentials are intentionally omitted.
+        f.debug_struct("BitcoinRpcClientBuilder")
+            .field("url", &self.url)
+            .field("pool_size", &self.pool_size)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("request_timeout", &self.request_timeout)
+            .field("root_certificates", &self.root_certificates.len())
+            .field("accept_invalid_certs", &self.accept_invalid_certs)
+            .field("proxy", &self.proxy)
+            .finish()
+    }
+}
+
+/// Single connection to the Bitcoin node, which sends JSON-RPC requests over HTTP(S).
+///
+/// The requests block the current thread until the response is received, so they must be
+/// executed on the blocking thread pool of the `tokio` runtime.
+pub(super) struct RpcConnection {
+    client: reqwest::Client,
+    url: String,
+    user: Option<String>,
+    password: Option<String>,
+    nonce: AtomicUsize,
+}
+
+impl RpcConnection {
+    async fn request(
+        &self,
+        method: &str,
+        params: &[Value],
+    ) -> Result<jsonrpc::Response, bitcoincore_rpc::Error> {
+        let id = json!(self.nonce.fetch_add(1, Ordering::Relaxed));
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+
+        let mut request = self.client.post(&self.url).json(&body);
+        if let Some(user) = &self.user {
+            request = request.basic_auth(user, self.password.as_ref());
+        }
+        let response = request.send().await.map_err(transport_error)?;
+        let status = response.status();
+        let bytes = response.bytes().await.map_err(transport_error)?;
+
+        // Bitcoin Core responds to the failed requests with the error HTTP status
+        // and the JSON-RPC error in the body, so the body is parsed regardless of the status.
+        let response = serde_json::from_slice::<jsonrpc::Response>(&bytes).map_err(|e| {
+            if status.is_success() {
+                bitcoincore_rpc::Error::Json(e)
+            } else {
+                bitcoincore_rpc::Error::Io(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("Bitcoin node responded with the {} status", status),
+                ))
+            }
+        })?;
+        if response.id != id {
+            return Err(JsonRpcError::NonceMismatch.into());
+        }
+        Ok(response)
+    }
+}
+
+impl RpcApi for RpcConnection {
+    fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[Value],
+    ) -> bitcoincore_rpc::Result<T> {
+        let response = futures::executor::block_on(self.request(cmd, args))?;
+        Ok(response.into_result()?)
+    }
+}
+
+fn transport_error(e: reqwest::Error) -> bitcoincore_rpc::Error {
+    let kind = if e.is_timeout() {
+        io::ErrorKind::TimedOut
+    } else {
+        io::ErrorKind::Other
+    };
+    bitcoincore_rpc::Error::Io(io::Error::new(kind, e))
+}
@@ -0,0 +1,594 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Built-in scheduler of the sync tasks.
+//!
+//! The runner drives the anchoring chain update task and the sync with Bitcoin task
+//! on their own intervals, so the sync utility doesn't need an external loop. A random
+//! jitter is added to each delay, so the sync utilities of different validators don't
+//! hit the nodes at the same moment. Transient errors of the API client and the Bitcoin
+//! relay are retried with the exponential backoff, while internal errors stop the runner.
+//!
+//! The runner stops gracefully after both tasks have been shut down, for example,
+//! on the termination signal.
+//!
+//! Several anchoring instances, for example, of different Exonum chains, can be synced
+//! from a single process by the `MultiSyncRunner`, which runs the runners of these
+//! instances concurrently and reports their statuses.
+
+use anyhow::{anyhow, bail};
+use futures::future;
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use super::{
+    backoff_delay, delay_unless_shut_down, AnchoringChainUpdateTask, BitcoinRelay,
+    ChainUpdateError, SyncWithBitcoinError, SyncWithBitcoinTask,
+};
+use crate::api::PrivateApi;
+
+/// Outcome of the single round of the sync task.
+#[derive(Debug)]
+enum RoundOutcome {
+    /// The round has been completed, the next one is performed after the regular interval.
+    Completed,
+    /// The round has failed due to a transient error, it is retried with the backoff.
+    Failed(String),
+}
+
+/// Status of the sync task driven by the runner.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncTaskStatus {
+    /// Number of the completed rounds.
+    pub completed_rounds: u64,
+    /// Number of the consecutive rounds failed due to transient errors.
+    pub consecutive_failures: u32,
+    /// Description of the latest error, if any.
+    pub last_error: Option<String>,
+    /// Whether the task has been stopped due to a fatal error or the shutdown.
+    pub is_stopped: bool,
+}
+
+impl SyncTaskStatus {
+    fn record_outcome(&mut self, outcome: &anyhow::Result<RoundOutcome>) {
+        match outcome {
+            Ok(RoundOutcome::Completed) => {
+                self.completed_rounds += 1;
+                self.consecutive_failures = 0;
+            }
+            Ok(RoundOutcome::Failed(e)) => {
+                self.consecutive_failures += 1;
+                self.last_error = Some(e.clone());
+            }
+            Err(e) => {
+                self.last_error = Some(e.to_string());
+                self.is_stopped = true;
+            }
+        }
+    }
+}
+
+/// Status of the sync tasks driven by the runner.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncRunnerStatus {
+    /// Status of the anchoring chain update task.
+    pub chain_update: SyncTaskStatus,
+    /// Status of the sync with Bitcoin task, if any.
+    pub bitcoin_sync: Option<SyncTaskStatus>,
+}
+
+/// Scheduler which runs the sync tasks on configurable intervals.
+#[derive(Debug)]
+pub struct SyncRunner<T, R>
+where
+    T: PrivateApi + 'static,
+    R: BitcoinRelay + 'static,
+{
+    chain_updater: Arc<AnchoringChainUpdateTask<T>>,
+    sync_with_bitcoin: Option<Arc<SyncWithBitcoinTask<T, R>>>,
+    chain_update_interval: Duration,
+    bitcoin_sync_interval: Duration,
+    jitter: Duration,
+    max_backoff: Duration,
+    chain_update_status: Mutex<SyncTaskStatus>,
+    bitcoin_sync_status: Mutex<SyncTaskStatus>,
+    bitcoin_sync_wake_up: AtomicBool,
+}
+
+impl<T, R> SyncRunner<T, R>
+where
+    T: PrivateApi + 'static,
+    R: BitcoinRelay + 'static,
+    T::Error: Display,
+    R::Error: Display,
+{
+    /// Default interval between the rounds of the sync tasks.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+    /// Default maximum random delay added to the intervals.
+    pub const DEFAULT_JITTER: Duration = Duration::from_secs(1);
+    /// Default maximum delay before the retry of the failed round.
+    pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+    /// Creates a runner of the given anchoring chain update task without the sync
+    /// with Bitcoin task.
+    pub fn new(chain_updater: AnchoringChainUpdateTask<T>) -> Self {
+        Self {
+            chain_updater: Arc::new(chain_updater),
+            sync_with_bitcoin: None,
+            chain_update_interval: Self::DEFAULT_INTERVAL,
+            bitcoin_sync_interval: Self::DEFAULT_INTERVAL,
+            jitter: Self::DEFAULT_JITTER,
+            max_backoff: Self::DEFAULT_MAX_BACKOFF,
+            chain_update_status: Mutex::default(),
+            bitcoin_sync_status: Mutex::default(),
+            bitcoin_sync_wake_up: AtomicBool::new(false),
+        }
+    }
+
+    /// Sets the sync with Bitcoin task run alongside the anchoring chain update task.
+    /// The task is shared, so its owner can shut it down while the runner is running.
+    pub fn with_bitcoin_task(mut self, task: Arc<SyncWithBitcoinTask<T, R>>) -> Self {
+        self.sync_with_bitcoin = Some(task);
+        self
+    }
+
+    /// Sets the interval between the rounds of the anchoring chain update task.
+    pub fn with_chain_update_interval(mut self, interval: Duration) -> Self {
+        self.chain_update_interval = interval;
+        self
+    }
+
+    /// Sets the interval between the rounds of the sync with Bitcoin task.
+    pub fn with_bitcoin_sync_interval(mut self, interval: Duration) -> Self {
+        self.bitcoin_sync_interval = interval;
+        self
+    }
+
+    /// Sets the maximum random delay added to the intervals.
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the maximum delay before the retry of the round failed due to a transient error.
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Returns the anchoring chain update task, which can be used to shut it down while
+    /// the runner is running.
+    pub fn chain_updater(&self) -> &Arc<AnchoringChainUpdateTask<T>> {
+        &self.chain_updater
+    }
+
+    /// Returns the sync with Bitcoin task, if any.
+    pub fn bitcoin_task(&self) -> Option<&Arc<SyncWithBitcoinTask<T, R>>> {
+        self.sync_with_bitcoin.as_ref()
+    }
+
+    /// Returns the actual status of the sync tasks.
+    pub fn status(&self) -> SyncRunnerStatus {
+        SyncRunnerStatus {
+            chain_update: self.chain_update_status.lock().unwrap().clone(),
+            bitcoin_sync: self
+                .sync_with_bitcoin
+                .as_ref()
+                .map(|_| self.bitcoin_sync_status.lock().unwrap().clone()),
+        }
+    }
+
+    /// Starts the next round of the sync with Bitcoin task without waiting for the rest
+    /// of the interval, for example, when the Bitcoin node notifies about a new block.
+    pub fn wake_up_bitcoin_sync(&self) {
+        self.bitcoin_sync_wake_up.store(true, Ordering::SeqCst);
+    }
+
+    /// Shuts down both sync tasks, so the `run` future stops after the in-flight rounds.
+    pub async fn shutdown(&self) -> anyhow::Result<()> {
+        self.chain_updater.shutdown().await;
+        if let Some(task) = self.sync_with_bitcoin.as_ref() {
+            task.shutdown().await?;
+        }
+        Ok(())
+    }
+
+    /// Runs the sync tasks until an internal error occurs or the tasks are shut down.
+    ///
+    /// Errors of the API client and the Bitcoin relay are considered transient, so
+    /// they are logged and the failed round is retried with the exponential backoff.
+    /// The internal errors and the unconfirmed initial funding transaction stop
+    /// the runner, and the corresponding error is returned.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let chain_updates = run_task(
+            self.chain_update_interval,
+            self.jitter,
+            self.max_backoff,
+            &self.chain_update_status,
+            || self.chain_updater.is_shut_down(),
+            || false,
+            || self.chain_update_round(),
+        );
+        match self.sync_with_bitcoin.as_ref() {
+            Some(task) => {
+                let bitcoin_syncs = run_task(
+                    self.bitcoin_sync_interval,
+                    self.jitter,
+                    self.max_backoff,
+                    &self.bitcoin_sync_status,
+                    || task.is_shut_down(),
+                    || self.bitcoin_sync_wake_up.swap(false, Ordering::SeqCst),
+                    || bitcoin_sync_round(task),
+                );
+                future::try_join(chain_updates, bitcoin_syncs).await?;
+            }
+            None => chain_updates.await?,
+        }
+        Ok(())
+    }
+
+    async fn chain_update_round(&self) -> anyhow::Result<RoundOutcome> {
+        match self.chain_updater.process_once().await {
+            Ok(_) => {}
+            Err(ChainUpdateError::Client(e)) => {
+                log::error!("An error in the anchoring API client occurred. {}", e);
+                return Ok(RoundOutcome::Failed(e.to_string()));
+            }
+            Err(ChainUpdateError::InsufficientFunds { total_fee, balance }) => log::warn!(
+                "Insufficient funds to construct a new anchoring transaction, \
+                 total fee is {}, total balance is {}",
+                total_fee,
+                balance
+            ),
+            Err(ChainUpdateError::NoInitialFunds) => {
+                let address = match self.chain_updater.anchoring_config().await {
+                    Ok(config) => config.anchoring_address(),
+                    Err(e) => {
+                        log::error!("An error in the anchoring API client occurred. {}", e);
+                        return Ok(RoundOutcome::Failed(e.to_string()));
+                    }
+                };
+                log::warn!(
+                    "Initial funding transaction is absent, you should send some \
+                     Bitcoins to the address {}",
+                    address
+                );
+                log::warn!(
+                    "And then confirm this transaction using the private \
+                     `management/add-funds` API method."
+                );
+            }
+            Err(ChainUpdateError::AwaitingReview { txid, remaining }) => log::info!(
+                "Anchoring proposal {} is waiting for review, it will be signed \
+                 in {} seconds unless it is denied",
+                txid,
+                remaining.as_secs()
+            ),
+            Err(ChainUpdateError::ReviewDenied { txid }) => log::warn!(
+                "Anchoring proposal {} has been denied by the reviewer and is not signed",
+                txid
+            ),
+            Err(ChainUpdateError::Internal(e)) => return Err(e),
+        }
+        Ok(RoundOutcome::Completed)
+    }
+}
+
+/// Runner of the sync tasks of several anchoring instances.
+///
+/// Each instance is identified by its name and has its own runner with the API client
+/// of the corresponding node, the signer and the Bitcoin relay.
+#[derive(Debug)]
+pub struct MultiSyncRunner<T, R>
+where
+    T: PrivateApi + 'static,
+    R: BitcoinRelay + 'static,
+{
+    runners: BTreeMap<String, SyncRunner<T, R>>,
+}
+
+impl<T, R> MultiSyncRunner<T, R>
+where
+    T: PrivateApi + 'static,
+    R: BitcoinRelay + 'static,
+    T::Error: Display,
+    R::Error: Display,
+{
+    /// Creates a runner without anchoring instances.
+    pub fn new() -> Self {
+        Self {
+            runners: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the runner of the sync tasks of the anchoring instance with the given name.
+    /// Returns an error if the instance with the same name has been already added.
+    pub fn with_instance(
+        mut self,
+        instance_name: impl Into<String>,
+        runner: SyncRunner<T, R>,
+    ) -> anyhow::Result<Self> {
+        let instance_name = instance_name.into();
+        if self.runners.contains_key(&instance_name) {
+            bail!(
+                "Anchoring instance {} has been already added",
+                instance_name
+            );
+        }
+        self.runners.insert(instance_name, runner);
+        Ok(self)
+    }
+
+    /// Returns the runner of the anchoring instance with the given name, if any.
+    pub fn instance(&self, instance_name: &str) -> Option<&SyncRunner<T, R>> {
+        self.runners.get(instance_name)
+    }
+
+    /// Returns the runners of all instances together with the instance names.
+    pub fn instances(&self) -> impl Iterator<Item = (&str, &SyncRunner<T, R>)> {
+        self.runners
+            .iter()
+            .map(|(instance_name, runner)| (instance_name.as_str(), runner))
+    }
+
+    /// Starts the next round of the sync with Bitcoin tasks of all instances without
+    /// waiting for the rest of the interval.
+    pub fn wake_up_bitcoin_sync(&self) {
+        self.runners
+            .values()
+            .for_each(SyncRunner::wake_up_bitcoin_sync);
+    }
+
+    /// Returns the actual statuses of the sync tasks indexed by the instance names.
+    pub fn status(&self) -> BTreeMap<String, SyncRunnerStatus> {
+        self.runners
+            .iter()
+            .map(|(instance_name, runner)| (instance_name.clone(), runner.status()))
+            .collect()
+    }
+
+    /// Shuts down the sync tasks of all instances.
+    pub async fn shutdown(&self) -> anyhow::Result<()> {
+        let results = future::join_all(self.runners.values().map(SyncRunner::shutdown)).await;
+        results.into_iter().collect()
+    }
+
+    /// Runs the sync tasks of all instances concurrently until all of them are stopped.
+    ///
+    /// A fatal error of one instance doesn't stop the other ones. If some instances have
+    /// been stopped due to fatal errors, the error listing them is returned.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let runs = self
+            .runners
+            .iter()
+            .map(|(instance_name, runner)| async move {
+                let res = runner.run().await;
+                if let Err(e) = res.as_ref() {
+                    log::error!(
+                        "Sync of the anchoring instance {} has stopped. {}",
+                        instance_name,
+                        e
+                    );
+                }
+                res.err().map(|_| instance_name.as_str())
+            });
+        let failed_instances = future::join_all(runs)
+            .await
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if failed_instances.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Sync of the anchoring instances {} has stopped due to errors",
+                failed_instances.join(", ")
+            ))
+        }
+    }
+}
+
+impl<T, R> Default for MultiSyncRunner<T, R>
+where
+    T: PrivateApi + 'static,
+    R: BitcoinRelay + 'static,
+    T::Error: Display,
+    R::Error: Display,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn bitcoin_sync_round<T, R>(task: &SyncWithBitcoinTask<T, R>) -> anyhow::Result<RoundOutcome>
+where
+    T: PrivateApi + 'static,
+    R: BitcoinRelay + 'static,
+    T::Error: Display,
+    R::Error: Display,
+{
+    match task
+        .process_once(task.checkpoint().latest_synced_tx_index)
+        .await
+    {
+        Ok(_) => {}
+        Err(SyncWithBitcoinError::Client(e)) => {
+            log::error!("An error in the anchoring API client occurred. {}", e);
+            return Ok(RoundOutcome::Failed(e.to_string()));
+        }
+        Err(SyncWithBitcoinError::Relay(e)) => {
+            log::error!("An error in the Bitcoin relay occurred. {}", e);
+            return Ok(RoundOutcome::Failed(e.to_string()));
+        }
+        Err(SyncWithBitcoinError::UnconfirmedFundingTransaction(txid)) => {
+            return Err(anyhow!(
+                "Funding transaction with id {} is unconfirmed by Bitcoin network. \
+                 This is a serious mistake that can break anchoring process.",
+                txid
+            ))
+        }
+        Err(SyncWithBitcoinError::MempoolConflict {
+            txid,
+            conflicting_txid,
+        }) => log::error!(
+            "Anchoring transaction {} conflicts with the transaction {:?} in the Bitcoin \
+             memory pool, which spends the same outputs.",
+            txid,
+            conflicting_txid
+        ),
+        Err(SyncWithBitcoinError::RelayFeeTooLow {
+            txid,
+            min_relay_fee,
+            node_min_relay_fee,
+        }) => log::error!(
+            "Anchoring transaction {} has been rejected by the Bitcoin node. The minimal relay \
+             fee rate of the node is {} sat/kvB, while the anchoring configuration allows {} \
+             sat/kvB.",
+            txid,
+            node_min_relay_fee,
+            min_relay_fee
+        ),
+        Err(SyncWithBitcoinError::AwaitingApprovals {
+            txid,
+            approvals,
+            required,
+        }) => log::warn!(
+            "Broadcasting of the anchoring transaction {} is held by the emergency brake, \
+             {} of {} required approvals have been recorded.",
+            txid,
+            approvals,
+            required
+        ),
+        Err(SyncWithBitcoinError::Internal(e)) => return Err(e),
+    }
+
+    match task.report_fee_estimate().await {
+        Err(SyncWithBitcoinError::Client(e)) => {
+            log::error!("Unable to report the fee estimate. {}", e)
+        }
+        Err(SyncWithBitcoinError::Relay(e)) => {
+            log::error!("Unable to estimate the anchoring fee. {}", e)
+        }
+        _ => {}
+    }
+    match task.request_cpfp().await {
+        Err(SyncWithBitcoinError::Client(e)) => {
+            log::error!("Unable to request the CPFP fee bumping. {}", e)
+        }
+        Err(SyncWithBitcoinError::Relay(e)) => {
+            log::error!("Unable to check the anchoring transaction fee. {}", e)
+        }
+        _ => {}
+    }
+    Ok(RoundOutcome::Completed)
+}
+
+/// Runs the rounds of the sync task until one of them returns an error or the task
+/// is shut down. The delay between the rounds is cut short once `is_woken_up` returns
+/// `true`.
+async fn run_task<F, Fut>(
+    interval: Duration,
+    jitter: Duration,
+    max_backoff: Duration,
+    status: &Mutex<SyncTaskStatus>,
+    is_shut_down: impl Fn() -> bool,
+    is_woken_up: impl Fn() -> bool,
+    mut round: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<RoundOutcome>>,
+{
+    while !is_shut_down() {
+        let outcome = round().await;
+        let failures = {
+            let mut status = status.lock().unwrap();
+            status.record_outcome(&outcome);
+            status.consecutive_failures
+        };
+        outcome?;
+
+        let delay = retry_delay(interval, max_backoff, failures) + random_jitter(jitter);
+        delay_unless_shut_down(delay, || is_shut_down() || is_woken_up()).await;
+    }
+    status.lock().unwrap().is_stopped = true;
+    Ok(())
+}
+
+/// Returns the delay before the next round after the given number of consecutive failures.
+fn retry_delay(interval: Duration, max_backoff: Duration, failures: u32) -> Duration {
+    if failures == 0 {
+        return interval;
+    }
+    backoff_delay(interval, max_backoff.max(interval), failures)
+}
+
+fn random_jitter(jitter: Duration) -> Duration {
+    let millis = jitter.as_millis() as u64;
+    if millis == 0 {
+        return Duration::default();
+    }
+    Duration::from_millis(rand::thread_rng().gen_range(0, millis + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{random_jitter, retry_delay};
+
+    #[test]
+    fn sync_runner_retry_delay() {
+        let interval = Duration::from_secs(5);
+        let max_backoff = Duration::from_secs(60);
+
+        assert_eq!(retry_delay(interval, max_backoff, 0), interval);
+        assert_eq!(
+            retry_delay(interval, max_backoff, 1),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            retry_delay(interval, max_backoff, 3),
+            Duration::from_secs(40)
+        );
+        // The delay doesn't exceed the maximum backoff.
+        assert_eq!(retry_delay(interval, max_backoff, 4), max_backoff);
+        assert_eq!(
+            retry_delay(interval, max_backoff, u32::max_value()),
+            max_backoff
+        );
+        // The maximum backoff never shortens the regular interval.
+        assert_eq!(retry_delay(interval, Duration::from_secs(1), 2), interval);
+    }
+
+    #[test]
+    fn sync_runner_random_jitter() {
+        assert_eq!(random_jitter(Duration::default()), Duration::default());
+        let jitter = Duration::from_millis(100);
+        for _ in 0..100 {
+            assert!(random_jitter(jitter) <= jitter);
+        }
+    }
+}
@@ -0,0 +1,529 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signers of the anchoring proposals used by the anchoring chain update task.
+//!
+//! The proposals are passed to the signers in the PSBT (BIP-174) format, so the private
+//! Bitcoin keys may be kept outside of the sync utility, for example, on the hardware
+//! wallets accessed via the [HWI] tool, or in the HSM behind a remote signing service.
+//!
+//! [HWI]: https://github.com/bitcoin-core/HWI
+
+use anyhow::{anyhow, ensure};
+use async_trait::async_trait;
+use bitcoin::{
+    consensus::encode,
+    util::{
+        address::Address,
+        bip32::{DerivationPath, Fingerprint},
+        psbt::{self, PartiallySignedTransaction},
+    },
+    Network, Script,
+};
+use bitcoin_hashes::{
+    hmac::{Hmac, HmacEngine},
+    sha256, Hash as _, HashEngine as _,
+};
+use reqwest::header::CONTENT_TYPE;
+use serde_derive::{Deserialize, Serialize};
+use tokio::task;
+
+use std::{
+    fmt::{self, Debug},
+    path::PathBuf,
+    process::{Command, Output},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{btc, psbt::ProposalPsbt};
+
+use super::KeyPool;
+
+/// Signer of the anchoring proposals.
+#[async_trait]
+pub trait Signer: Debug + Send + Sync {
+    /// Returns the Bitcoin keys available to the signer in order of their priority.
+    fn signing_keys(&self) -> Vec<btc::PublicKey>;
+
+    /// Signs the PSBT inputs which can be signed by the given Bitcoin key and returns
+    /// the PSBT with the added partial signatures. The inputs which can't be signed by
+    /// the key are left untouched.
+    async fn sign_psbt(
+        &self,
+        psbt: PartiallySignedTransaction,
+        public_key: btc::PublicKey,
+    ) -> anyhow::Result<PartiallySignedTransaction>;
+}
+
+#[async_trait]
+impl Signer for KeyPool {
+    fn signing_keys(&self) -> Vec<btc::PublicKey> {
+        self.public_keys().collect()
+    }
+
+    async fn sign_psbt(
+        &self,
+        mut psbt: PartiallySignedTransaction,
+        public_key: btc::PublicKey,
+    ) -> anyhow::Result<PartiallySignedTransaction> {
+        let private_key = self
+            .get(&public_key)
+            .ok_or_else(|| anyhow!("Bitcoin key {} is not in the key pool", public_key))?;
+        let transaction = btc::Transaction::from(psbt.global.unsigned_tx.clone());
+        for (index, input) in psbt.inputs.iter_mut().enumerate() {
+            let (script_code, value) = match signed_script_code(input, &public_key) {
+                Some(script_code) => script_code,
+                None => continue,
+            };
+            let sighash =
+                transaction.signature_hash(index, &script_code, value, btc::SigHashType::All);
+            let signature = btc::InputSignature::sign(&sighash, btc::SigHashType::All, private_key);
+            input
+                .partial_sigs
+                .insert(public_key.0, signature.0.as_ref().to_vec());
+        }
+        Ok(psbt)
+    }
+}
+
+/// Returns the script code and the value of the spent output of the PSBT input if
+/// the input can be signed by the given key.
+fn signed_script_code(input: &psbt::Input, public_key: &btc::PublicKey) -> Option<(Script, u64)> {
+    let spent_output = input.witness_utxo.as_ref()?;
+    let script_code = match input.witness_script.as_ref() {
+        Some(witness_script) => {
+            let key_bytes = public_key.0.to_bytes();
+            let has_key = witness_script
+                .as_bytes()
+                .windows(key_bytes.len())
+                .any(|window| window == key_bytes.as_slice());
+            if !has_key || witness_script.to_v0_p2wsh() != spent_output.script_pubkey {
+                return None;
+            }
+            witness_script.clone()
+        }
+        None => {
+            // The network doesn't affect the scripts.
+            let script_pubkey = Address::p2wpkh(&public_key.0, Network::Bitcoin).script_pubkey();
+            if script_pubkey != spent_output.script_pubkey {
+                return None;
+            }
+            Address::p2pkh(&public_key.0, Network::Bitcoin).script_pubkey()
+        }
+    };
+    Some((script_code, spent_output.value))
+}
+
+/// Bitcoin key stored on the hardware wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HwiKey {
+    /// Public part of the key.
+    pub public_key: btc::PublicKey,
+    /// BIP-32 derivation path of the key from the master key of the wallet.
+    pub derivation_path: DerivationPath,
+}
+
+/// Signer which uses the hardware wallets, such as Ledger or Trezor, via the [HWI] tool.
+///
+/// Each signing request must be confirmed on the device, so the signer is suitable
+/// for the nodes with the rare anchoring or the manual review of the proposals.
+///
+/// [HWI]: https://github.com/bitcoin-core/HWI
+#[derive(Debug, Clone)]
+pub struct HwiSigner {
+    executable: PathBuf,
+    device_type: String,
+    fingerprint: Fingerprint,
+    network: Network,
+    keys: Vec<HwiKey>,
+}
+
+impl HwiSigner {
+    /// Creates a signer which uses the device of the given type, for example, `ledger`
+    /// or `trezor`, with the given fingerprint of the master key.
+    pub fn new(device_type: impl Into<String>, fingerprint: Fingerprint, network: Network) -> Self {
+        Self {
+            executable: PathBuf::from("hwi"),
+            device_type: device_type.into(),
+            fingerprint,
+            network,
+            keys: Vec::new(),
+        }
+    }
+
+    /// Sets the path to the HWI executable. By default, `hwi` is looked up in the `PATH`.
+    pub fn with_executable(mut self, executable: impl Into<PathBuf>) -> Self {
+        self.executable = executable.into();
+        self
+    }
+
+    /// Adds the Bitcoin key of the device. Keys are prioritized in order of addition.
+    pub fn with_key(mut self, public_key: btc::PublicKey, derivation_path: DerivationPath) -> Self {
+        self.keys.push(HwiKey {
+            public_key,
+            derivation_path,
+        });
+        self
+    }
+
+    fn command(&self, psbt: &PartiallySignedTransaction) -> Command {
+        let mut command = Command::new(&self.executable);
+        command
+            .arg("--device-type")
+            .arg(&self.device_type)
+            .arg("--fingerprint")
+            .arg(self.fingerprint.to_string());
+        if self.network != Network::Bitcoin {
+            command.arg("--testnet");
+        }
+        command
+            .arg("signtx")
+            .arg(base64::encode(encode::serialize(psbt)));
+        command
+    }
+}
+
+/// Parses the signed PSBT from the output of the HWI `signtx` command.
+fn parse_hwi_output(output: &Output) -> anyhow::Result<PartiallySignedTransaction> {
+    let response: serde_json::Value = serde_json::from_slice(&output.stdout).map_err(|e| {
+        anyhow!(
+            "Unexpected HWI output ({}): {}",
+            e,
+            String::from_utf8_lossy(&output.stderr)
+        )
+    })?;
+    if let Some(error) = response.get("error") {
+        return Err(anyhow!("HWI has failed to sign the proposal: {}", error));
+    }
+    let psbt = response
+        .get("psbt")
+        .and_then(serde_json::Value::as_str)
+        .ok_or_else(|| anyhow!("HWI output has no signed PSBT"))?;
+    Ok(encode::deserialize(&base64::decode(psbt)?)?)
+}
+
+#[async_trait]
+impl Signer for HwiSigner {
+    fn signing_keys(&self) -> Vec<btc::PublicKey> {
+        self.keys.iter().map(|key| key.public_key).collect()
+    }
+
+    async fn sign_psbt(
+        &self,
+        mut psbt: PartiallySignedTransaction,
+        public_key: btc::PublicKey,
+    ) -> anyhow::Result<PartiallySignedTransaction> {
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.public_key == public_key)
+            .ok_or_else(|| anyhow!("Bitcoin key {} is not on the device", public_key))?;
+        // The device signs only the inputs with the derivation paths of its keys.
+        let mut has_inputs = false;
+        for input in &mut psbt.inputs {
+            if signed_script_code(input, &public_key).is_some() {
+                input.hd_keypaths.insert(
+                    public_key.0,
+                    (self.fingerprint, key.derivation_path.clone()),
+                );
+                has_inputs = true;
+            }
+        }
+        if !has_inputs {
+            return Ok(psbt);
+        }
+
+        let mut command = self.command(&psbt);
+        let output = task::spawn_blocking(move || command.output())
+            .await
+            .expect("HWI command has panicked")?;
+        let signed_psbt = parse_hwi_output(&output)?;
+        merge_signatures(&mut psbt, signed_psbt, public_key)?;
+        Ok(psbt)
+    }
+}
+
+/// Copies the partial signatures made by the given key from the PSBT signed by
+/// the external signer. Returns the number of the signed inputs.
+fn merge_signatures(
+    psbt: &mut PartiallySignedTransaction,
+    signed_psbt: PartiallySignedTransaction,
+    public_key: btc::PublicKey,
+) -> anyhow::Result<usize> {
+    ensure!(
+        signed_psbt.global.unsigned_tx == psbt.global.unsigned_tx,
+        "External signer has signed a different transaction"
+    );
+
+    // Only the signatures made by the requested key are taken from the signer.
+    let mut signed_inputs = 0;
+    for (input, signed_input) in psbt.inputs.iter_mut().zip(signed_psbt.inputs) {
+        if let Some(signature) = signed_input.partial_sigs.get(&public_key.0) {
+            input.partial_sigs.insert(public_key.0, signature.clone());
+            signed_inputs += 1;
+        }
+    }
+    Ok(signed_inputs)
+}
+
+/// Target of the audit log records of the remote signer.
+pub const AUDIT_LOG_TARGET: &str = "btc_anchoring::audit";
+/// Header with the Unix time of the request to the remote signing service.
+pub const TIMESTAMP_HEADER: &str = "X-Anchoring-Timestamp";
+/// Header with the hex-encoded HMAC-SHA256 of the request to the remote signing service.
+pub const SIGNATURE_HEADER: &str = "X-Anchoring-Signature";
+
+/// Request to the remote signing service.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteSignRequest {
+    /// Bitcoin key which should sign the proposal.
+    pub public_key: btc::PublicKey,
+    /// Anchoring proposal to sign, the service responds with the same proposal
+    /// with the added partial signatures.
+    pub proposal: ProposalPsbt,
+}
+
+/// Signer which delegates signing to the remote signing service, for example,
+/// a daemon in front of the HSM.
+///
+/// The proposals are sent as the JSON-encoded `RemoteSignRequest` to the service URL
+/// with the `POST` method. The requests are authenticated with the bearer token, and
+/// signed with the HMAC-SHA256 of the request key over the timestamp and the body
+/// separated by a dot, which is sent in the `X-Anchoring-Signature` header together with
+/// the `X-Anchoring-Timestamp` one. Each request and its outcome are logged with
+/// the `btc_anchoring::audit` target.
+#[derive(Clone)]
+pub struct RemoteSigner {
+    url: String,
+    client: reqwest::Client,
+    auth_token: Option<String>,
+    request_key: Option<Vec<u8>>,
+    keys: Vec<btc::PublicKey>,
+}
+
+impl RemoteSigner {
+    /// Creates a signer which uses the service with the given URL to sign by the given
+    /// Bitcoin keys in order of their priority.
+    pub fn new(url: impl Into<String>, keys: impl IntoIterator<Item = btc::PublicKey>) -> Self {
+        Self {
+            url: url.into(),
+            client: reqwest::Client::new(),
+            auth_token: None,
+            request_key: None,
+            keys: keys.into_iter().collect(),
+        }
+    }
+
+    /// Sets the bearer token of the requests.
+    pub fn with_auth_token(mut self, auth_token: impl Into<String>) -> Self {
+        self.auth_token = Some(auth_token.into());
+        self
+    }
+
+    /// Sets the key of the HMAC-SHA256 signatures of the requests.
+    pub fn with_request_key(mut self, request_key: impl Into<Vec<u8>>) -> Self {
+        self.request_key = Some(request_key.into());
+        self
+    }
+
+    /// Sets the HTTP client, for example, with the custom TLS certificates or timeouts.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    async fn request(&self, body: Vec<u8>) -> anyhow::Result<ProposalPsbt> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json");
+        if let Some(auth_token) = self.auth_token.as_ref() {
+            request = request.bearer_auth(auth_token);
+        }
+        if let Some(request_key) = self.request_key.as_ref() {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)?
+                .as_secs()
+                .to_string();
+            let signature = request_signature(request_key, &timestamp, &body);
+            request = request
+                .header(TIMESTAMP_HEADER, timestamp)
+                .header(SIGNATURE_HEADER, signature);
+        }
+
+        let response = request.body(body).send().await?.error_for_status()?;
+        Ok(response.json().await?)
+    }
+}
+
+impl Debug for RemoteSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Credentials are intentionally omitted.
+        f.debug_struct("RemoteSigner")
+            .field("url", &self.url)
+            .field("keys", &self.keys)
+            .finish()
+    }
+}
+
+/// Computes the hex-encoded HMAC-SHA256 signature of the request to the remote
+/// signing service.
+fn request_signature(request_key: &[u8], timestamp: &str, body: &[u8]) -> String {
+    let mut engine = HmacEngine::<sha256::Hash>::new(request_key);
+    engine.input(timestamp.as_bytes());
+    engine.input(b".");
+    engine.input(body);
+    hex::encode(Hmac::<sha256::Hash>::from_engine(engine).into_inner())
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    fn signing_keys(&self) -> Vec<btc::PublicKey> {
+        self.keys.clone()
+    }
+
+    async fn sign_psbt(
+        &self,
+        mut psbt: PartiallySignedTransaction,
+        public_key: btc::PublicKey,
+    ) -> anyhow::Result<PartiallySignedTransaction> {
+        ensure!(
+            self.keys.contains(&public_key),
+            "Bitcoin key {} is not served by the remote signer",
+            public_key
+        );
+        let proposal = ProposalPsbt::from_psbt(&psbt);
+        let txid = proposal.txid;
+        let body = serde_json::to_vec(&RemoteSignRequest {
+            public_key,
+            proposal,
+        })?;
+
+        log::info!(
+            target: AUDIT_LOG_TARGET,
+            "Requesting {} to sign the anchoring proposal {} with the Bitcoin key {}",
+            self.url,
+            txid,
+            public_key
+        );
+        let signed_inputs = match self.request(body).await.and_then(|signed_proposal| {
+            let signed_psbt = signed_proposal.to_psbt()?;
+            merge_signatures(&mut psbt, signed_psbt, public_key)
+        }) {
+            Ok(signed_inputs) => signed_inputs,
+            Err(e) => {
+                log::warn!(
+                    target: AUDIT_LOG_TARGET,
+                    "{} has failed to sign the anchoring proposal {} with the Bitcoin key {}: {}",
+                    self.url,
+                    txid,
+                    public_key,
+                    e
+                );
+                return Err(e);
+            }
+        };
+        log::info!(
+            target: AUDIT_LOG_TARGET,
+            "{} has signed {} inputs of the anchoring proposal {} with the Bitcoin key {}",
+            self.url,
+            signed_inputs,
+            txid,
+            public_key
+        );
+        Ok(psbt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Network;
+    use exonum::{crypto, helpers::Height};
+    use exonum_merkledb::ObjectHash;
+
+    use super::{request_signature, Signer};
+    use crate::{
+        btc::{self, BtcAnchoringTransactionBuilder},
+        config::Config,
+        proto::AnchoringKeys,
+        psbt::ProposalPsbt,
+        sync::KeyPool,
+        test_helpers::create_fake_funding_transaction,
+    };
+
+    #[tokio::test]
+    async fn key_pool_signs_psbt() {
+        let keypairs = (0..3)
+            .map(|_| btc::gen_keypair(Network::Testnet))
+            .collect::<Vec<_>>();
+        let config = Config::with_public_keys(
+            Network::Testnet,
+            keypairs.iter().map(|(bitcoin_key, _)| AnchoringKeys {
+                bitcoin_key: *bitcoin_key,
+                service_key: crypto::gen_keypair().0,
+                weight: AnchoringKeys::DEFAULT_WEIGHT,
+                derivation: None,
+            }),
+        )
+        .unwrap();
+        let redeem_script = config.redeem_script();
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+        let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(1);
+        builder.payload(Height::zero(), funding_tx.object_hash());
+        let (proposal, inputs) = builder.create().unwrap();
+        let psbt = ProposalPsbt::new(&config, &proposal, &inputs)
+            .unwrap()
+            .to_psbt()
+            .unwrap();
+
+        let public_key = keypairs[1].0;
+        let key_pool = KeyPool::new(vec![keypairs[1].clone()]).unwrap();
+        assert_eq!(key_pool.signing_keys(), vec![public_key]);
+        let signed_psbt = key_pool.sign_psbt(psbt.clone(), public_key).await.unwrap();
+        let signatures = ProposalPsbt::from_psbt(&signed_psbt)
+            .signatures(&proposal, &[public_key])
+            .unwrap();
+        assert_eq!(signatures.len(), 1);
+        let (_, spent_output) = funding_tx
+            .find_out(&config.anchoring_address().script_pubkey())
+            .unwrap();
+        let sighash = proposal.signature_hash(
+            0,
+            redeem_script.as_ref(),
+            spent_output.value,
+            btc::SigHashType::All,
+        );
+        signatures[0]
+            .input_signature
+            .verify(&sighash, &public_key)
+            .unwrap();
+
+        // The key which is absent in the witness script signs nothing.
+        let other_keypair = btc::gen_keypair(Network::Testnet);
+        let other_pool = KeyPool::new(vec![other_keypair.clone()]).unwrap();
+        let unsigned_psbt = other_pool.sign_psbt(psbt, other_keypair.0).await.unwrap();
+        assert!(unsigned_psbt.inputs[0].partial_sigs.is_empty());
+    }
+
+    #[test]
+    fn remote_request_signature() {
+        let signature = request_signature(b"secret", "1600000000", b"{}");
+        assert_eq!(
+            signature,
+            "1e56a11da123b137c26fa37b7c222060bdf22988aa9b3248c31244f8b2ef4a28"
+        );
+    }
+}
@@ -0,0 +1,2326 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In-memory implementations of the sync building blocks for the unit tests.
+//!
+//! Unlike the testkit-based helpers these fakes don't require a running blockchain
+//! or an HTTP server, their state is fully controlled by the test.
+
+use async_trait::async_trait;
+use bitcoin_hashes::{sha256d, Hash as BitcoinHash};
+use exonum::{crypto::Hash, helpers::Height};
+use exonum_merkledb::ObjectHash;
+use thiserror::Error;
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    api::{
+        AnchorConfirmationState, AnchoringChainLength, AnchoringProposalState,
+        FundingTransactionState, ManagementApi, PrivateApi, RecoveryBundleQuery, ServiceVersion,
+        SpecialTxApprovalState,
+    },
+    blockchain::{
+        AddFunds, AnchorInclusion, ApproveSpecialTx, AttestKey, AuthorizedRecoveryExport,
+        ConfirmAnchor, NoProposalReason, ReportFeeEstimate, RequestCpfp, SignInput,
+        SigningProgress,
+    },
+    btc,
+    config::Config,
+    psbt::ProposalPsbt,
+    recovery::{EncryptedRecoveryBundle, RecoveryBundle},
+};
+
+use super::{BitcoinRelay, FeeHistogram, TransactionStatus};
+
+/// Bitcoin relay which keeps transactions in memory.
+///
+/// Sent transactions are placed to the memory pool, use the [`set_status`] method
+/// to emulate their confirmations and the [`set_inclusion`] method to specify the blocks
+/// in which they have been committed. Like the Bitcoin nodes, the relay rejects transactions
+/// which spend the same outputs as the transactions in the memory pool. Use the
+/// [`set_unavailable`] method to emulate the outage of the Bitcoin node.
+///
+/// The relay also emulates the Bitcoin blockchain: the [`mine_blocks`] method commits
+/// the memory pool transactions into new blocks, and the [`reorg`] and [`invalidate`]
+/// methods emulate the reorganizations of the blockchain.
+///
+/// [`set_status`]: #method.set_status
+/// [`set_inclusion`]: #method.set_inclusion
+/// [`set_unavailable`]: #method.set_unavailable
+/// [`mine_blocks`]: #method.mine_blocks
+/// [`reorg`]: #method.reorg
+/// [`invalidate`]: #method.invalidate
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryRelay {
+    transactions: Arc<Mutex<HashMap<btc::Sha256d, (btc::Transaction, TransactionStatus)>>>,
+    inclusions: Arc<Mutex<HashMap<btc::Sha256d, AnchorInclusion>>>,
+    fee_histogram: Arc<Mutex<Option<FeeHistogram>>>,
+    relay_fee_policy: Arc<Mutex<Option<btc::RelayFeePolicy>>>,
+    smart_fee: Arc<Mutex<Option<u64>>>,
+    unavailable: Arc<Mutex<bool>>,
+    chain: Arc<Mutex<EmulatedChain>>,
+}
+
+/// Blocks of the Bitcoin blockchain emulated by the `InMemoryRelay`.
+#[derive(Debug, Default)]
+struct EmulatedChain {
+    /// Hashes of the blocks in order of their heights, starting from the height 1.
+    blocks: Vec<btc::Sha256d>,
+    /// Total number of the mined blocks including the disconnected ones, which makes
+    /// the hashes of the replacing blocks unique.
+    mined_count: u64,
+}
+
+impl EmulatedChain {
+    fn push_block(&mut self) -> AnchorInclusion {
+        self.mined_count += 1;
+        let mut data = self
+            .blocks
+            .last()
+            .map_or_else(Vec::new, |prev_hash| prev_hash.0[..].to_vec());
+        data.extend_from_slice(&self.mined_count.to_le_bytes());
+
+        let block_hash = btc::Sha256d(sha256d::Hash::hash(&data));
+        self.blocks.push(block_hash);
+        AnchorInclusion {
+            block_hash,
+            block_height: self.blocks.len() as u64,
+        }
+    }
+}
+
+impl InMemoryRelay {
+    /// Creates an empty relay.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a transaction with the given status, for example, a funding transaction
+    /// which has been already committed to the Bitcoin blockchain.
+    pub fn add_transaction(&self, transaction: btc::Transaction, status: TransactionStatus) {
+        self.transactions
+            .lock()
+            .unwrap()
+            .insert(transaction.id(), (transaction, status));
+    }
+
+    /// Changes the status of the transaction with the given identifier, if it is known.
+    pub fn set_status(&self, txid: btc::Sha256d, status: TransactionStatus) {
+        if let Some(entry) = self.transactions.lock().unwrap().get_mut(&txid) {
+            entry.1 = status;
+        }
+    }
+
+    /// Sets the block in which the transaction with the given identifier has been committed.
+    pub fn set_inclusion(&self, txid: btc::Sha256d, inclusion: AnchorInclusion) {
+        self.inclusions.lock().unwrap().insert(txid, inclusion);
+    }
+
+    /// Sets the fee histogram of the memory pool.
+    pub fn set_fee_histogram(&self, histogram: FeeHistogram) {
+        *self.fee_histogram.lock().unwrap() = Some(histogram);
+    }
+
+    /// Sets the relay fee policy of the emulated Bitcoin node.
+    pub fn set_relay_fee_policy(&self, policy: btc::RelayFeePolicy) {
+        *self.relay_fee_policy.lock().unwrap() = Some(policy);
+    }
+
+    /// Sets the fee rate in satoshis per virtual byte estimated by the emulated
+    /// Bitcoin node for any confirmation target.
+    pub fn set_smart_fee(&self, fee: u64) {
+        *self.smart_fee.lock().unwrap() = Some(fee);
+    }
+
+    /// Makes the relay reject all the requests with the `Unavailable` error
+    /// until it is made available again.
+    pub fn set_unavailable(&self, unavailable: bool) {
+        *self.unavailable.lock().unwrap() = unavailable;
+    }
+
+    /// Mines the given number of blocks. The first block contains all the memory pool
+    /// transactions, and all the committed transactions get a confirmation per block.
+    /// Returns hashes of the mined blocks.
+    pub fn mine_blocks(&self, count: u32) -> Vec<btc::Sha256d> {
+        let mut chain = self.chain.lock().unwrap();
+        let mut transactions = self.transactions.lock().unwrap();
+        let mut inclusions = self.inclusions.lock().unwrap();
+        (0..count)
+            .map(|_| {
+                let inclusion = chain.push_block();
+                for (txid, (_, status)) in transactions.iter_mut() {
+                    *status = match *status {
+                        TransactionStatus::Mempool => {
+                            inclusions.insert(*txid, inclusion.clone());
+                            TransactionStatus::Committed(1)
+                        }
+                        TransactionStatus::Committed(confirmations) => {
+                            TransactionStatus::Committed(confirmations + 1)
+                        }
+                        TransactionStatus::Unknown => TransactionStatus::Unknown,
+                    };
+                }
+                inclusion.block_hash
+            })
+            .collect()
+    }
+
+    /// Emulates the reorganization of the latest `depth` blocks. The disconnected blocks
+    /// are replaced by the same number of empty blocks, so the transactions committed
+    /// in them return to the memory pool, while the other transactions keep
+    /// their confirmations. Returns identifiers of the transactions returned
+    /// to the memory pool in ascending order.
+    pub fn reorg(&self, depth: u32) -> Vec<btc::Sha256d> {
+        let mut chain = self.chain.lock().unwrap();
+        let mut transactions = self.transactions.lock().unwrap();
+        let mut inclusions = self.inclusions.lock().unwrap();
+
+        let fork_height = chain.blocks.len().saturating_sub(depth as usize);
+        chain.blocks.truncate(fork_height);
+        for _ in 0..depth {
+            chain.push_block();
+        }
+
+        let mut disconnected = Vec::new();
+        for (txid, (_, status)) in transactions.iter_mut() {
+            if let TransactionStatus::Committed(confirmations) = *status {
+                if confirmations <= depth {
+                    *status = TransactionStatus::Mempool;
+                    inclusions.remove(txid);
+                    disconnected.push(*txid);
+                }
+            }
+        }
+        disconnected.sort();
+        disconnected
+    }
+
+    /// Removes the transaction with the given identifier and all the transactions
+    /// spending its outputs, as if it was double spent in the reorganized blockchain.
+    /// Returns identifiers of the removed transactions in ascending order.
+    pub fn invalidate(&self, txid: btc::Sha256d) -> Vec<btc::Sha256d> {
+        let mut transactions = self.transactions.lock().unwrap();
+        let mut inclusions = self.inclusions.lock().unwrap();
+
+        let mut removed = Vec::new();
+        let mut queue = vec![txid];
+        while let Some(txid) = queue.pop() {
+            if transactions.remove(&txid).is_none() {
+                continue;
+            }
+            inclusions.remove(&txid);
+            removed.push(txid);
+
+            let descendants = transactions.values().filter(|(transaction, _)| {
+                transaction
+                    .0
+                    .input
+                    .iter()
+                    .any(|txin| btc::Sha256d::from(txin.previous_output.txid) == txid)
+            });
+            queue.extend(descendants.map(|(transaction, _)| transaction.id()));
+        }
+        removed.sort();
+        removed
+    }
+
+    /// Returns the height of the latest mined block, or zero if there are no blocks.
+    pub fn tip_height(&self) -> u64 {
+        self.chain.lock().unwrap().blocks.len() as u64
+    }
+
+    /// Returns the status of the transaction with the given identifier.
+    pub fn status(&self, txid: btc::Sha256d) -> TransactionStatus {
+        self.transactions
+            .lock()
+            .unwrap()
+            .get(&txid)
+            .map_or(TransactionStatus::Unknown, |entry| entry.1)
+    }
+
+    /// Returns the block in which the transaction with the given identifier has been
+    /// committed, if it is known.
+    pub fn inclusion(&self, txid: btc::Sha256d) -> Option<AnchorInclusion> {
+        self.inclusions.lock().unwrap().get(&txid).cloned()
+    }
+
+    /// Asserts that the transaction with the given identifier has the expected status.
+    ///
+    /// # Panics
+    ///
+    /// If the actual status of the transaction differs from the expected one.
+    pub fn assert_status(&self, txid: btc::Sha256d, expected: TransactionStatus) {
+        let actual = self.status(txid);
+        assert_eq!(
+            actual, expected,
+            "Unexpected status of the transaction {}: {:?}, expected {:?}",
+            txid, actual, expected
+        );
+    }
+
+    /// Returns the transaction with the given identifier, if it is known.
+    pub fn transaction(&self, txid: btc::Sha256d) -> Option<btc::Transaction> {
+        self.transactions
+            .lock()
+            .unwrap()
+            .get(&txid)
+            .map(|entry| entry.0.clone())
+    }
+}
+
+/// Errors of the in-memory Bitcoin relay.
+#[derive(Debug, Clone, Copy, PartialEq, Error)]
+pub enum InMemoryRelayError {
+    /// Transaction spends the same outputs as another transaction in the memory pool.
+    #[error("txn-mempool-conflict")]
+    MempoolConflict,
+    /// Transaction fee is lower than the minimal relay fee.
+    #[error("min relay fee not met")]
+    MinRelayFeeNotMet,
+    /// The emulated Bitcoin node is unavailable.
+    #[error("Bitcoin node is unavailable")]
+    Unavailable,
+}
+
+impl InMemoryRelay {
+    fn check_available(&self) -> Result<(), InMemoryRelayError> {
+        if *self.unavailable.lock().unwrap() {
+            Err(InMemoryRelayError::Unavailable)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn find_conflict(&self, transaction: &btc::Transaction) -> Option<btc::Sha256d> {
+        let txid = transaction.id();
+        self.transactions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(other, status)| *status == TransactionStatus::Mempool && other.id() != txid)
+            .find(|(other, _)| {
+                other.0.input.iter().any(|other_txin| {
+                    transaction
+                        .0
+                        .input
+                        .iter()
+                        .any(|txin| txin.previous_output == other_txin.previous_output)
+                })
+            })
+            .map(|(other, _)| other.id())
+    }
+
+    fn transaction_fee(&self, transaction: &btc::Transaction) -> Option<u64> {
+        let transactions = self.transactions.lock().unwrap();
+        let prev_outputs = transaction
+            .0
+            .input
+            .iter()
+            .map(|txin| {
+                let txid: btc::Sha256d = txin.previous_output.txid.into();
+                let (prev_tx, _) = transactions.get(&txid)?;
+                prev_tx
+                    .0
+                    .output
+                    .get(txin.previous_output.vout as usize)
+                    .cloned()
+            })
+            .collect::<Option<Vec<_>>>()?;
+        transaction.fee(&prev_outputs)
+    }
+}
+
+#[async_trait]
+impl BitcoinRelay for InMemoryRelay {
+    type Error = InMemoryRelayError;
+
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        self.check_available()?;
+        if self.find_conflict(transaction).is_some() {
+            return Err(InMemoryRelayError::MempoolConflict);
+        }
+        let relay_fee_policy = *self.relay_fee_policy.lock().unwrap();
+        if let Some(policy) = relay_fee_policy {
+            let min_fee = policy.min_fee(transaction.vsize() as u64);
+            if self
+                .transaction_fee(transaction)
+                .map_or(false, |fee| fee < min_fee)
+            {
+                return Err(InMemoryRelayError::MinRelayFeeNotMet);
+            }
+        }
+
+        let txid = transaction.id();
+        self.transactions
+            .lock()
+            .unwrap()
+            .entry(txid)
+            .or_insert_with(|| (transaction.clone(), TransactionStatus::Mempool));
+        Ok(txid)
+    }
+
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        self.check_available()?;
+        Ok(self.status(id))
+    }
+
+    fn is_mempool_conflict(error: &Self::Error) -> bool {
+        *error == InMemoryRelayError::MempoolConflict
+    }
+
+    fn is_unavailable(error: &Self::Error) -> bool {
+        *error == InMemoryRelayError::Unavailable
+    }
+
+    async fn find_conflicting_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        self.check_available()?;
+        Ok(self.find_conflict(transaction))
+    }
+
+    async fn transaction_inclusion(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<AnchorInclusion>, Self::Error> {
+        self.check_available()?;
+        Ok(self.inclusion(id))
+    }
+
+    async fn fee_histogram(&self) -> Result<Option<FeeHistogram>, Self::Error> {
+        self.check_available()?;
+        Ok(self.fee_histogram.lock().unwrap().clone())
+    }
+
+    async fn relay_fee_policy(&self) -> Result<Option<btc::RelayFeePolicy>, Self::Error> {
+        self.check_available()?;
+        Ok(*self.relay_fee_policy.lock().unwrap())
+    }
+
+    async fn estimate_smart_fee(
+        &self,
+        _confirmation_target: u32,
+    ) -> Result<Option<u64>, Self::Error> {
+        self.check_available()?;
+        Ok(*self.smart_fee.lock().unwrap())
+    }
+    async fn mempool_fee_rate(&self, id: btc::Sha256d) -> Result<Option<u64>, Self::Error> {
+        self.check_available()?;
+        if self.status(id) != TransactionStatus::Mempool {
+            return Ok(None);
+        }
+        let transaction = self.transactions.lock().unwrap()[&id].0.clone();
+        Ok(self
+            .transaction_fee(&transaction)
+            .map(|fee| fee / transaction.vsize() as u64))
+    }
+}
+
+#[derive(Debug)]
+struct InMemoryApiState {
+    config: Config,
+    version: ServiceVersion,
+    proposal: AnchoringProposalState,
+    signing_progress: Option<SigningProgress>,
+    transactions: Vec<btc::Transaction>,
+    unspent_funding_transaction: Option<btc::Transaction>,
+    funding_states: HashMap<btc::Sha256d, FundingTransactionState>,
+    anchor_states: HashMap<btc::Sha256d, AnchorConfirmationState>,
+    special_tx_states: HashMap<btc::Sha256d, SpecialTxApprovalState>,
+    sign_inputs: Vec<SignInput>,
+    anchor_confirmations: Vec<ConfirmAnchor>,
+    added_funds: Vec<btc::Transaction>,
+    attestations: Vec<AttestKey>,
+    special_tx_approvals: Vec<btc::Sha256d>,
+    fee_estimates: Vec<u64>,
+    cpfp_requests: Vec<RequestCpfp>,
+}
+
+/// Private API client which keeps the anchoring service state in memory.
+///
+/// The client doesn't execute received transactions, it only records them, so a test
+/// can check what has been sent by the sync tasks.
+#[derive(Debug, Clone)]
+pub struct InMemoryPrivateApi {
+    state: Arc<Mutex<InMemoryApiState>>,
+}
+
+impl InMemoryPrivateApi {
+    /// Creates a client with the given anchoring configuration and
+    /// empty anchoring chain.
+    pub fn new(config: Config) -> Self {
+        let state = InMemoryApiState {
+            config,
+            version: ServiceVersion::current(),
+            proposal: AnchoringProposalState::None {
+                reason: NoProposalReason::AwaitingAnchoringHeight {
+                    next_anchoring_height: Height::zero(),
+                    latest_height: Height::zero(),
+                },
+            },
+            signing_progress: None,
+            transactions: Vec::new(),
+            unspent_funding_transaction: None,
+            funding_states: HashMap::new(),
+            anchor_states: HashMap::new(),
+            special_tx_states: HashMap::new(),
+            sign_inputs: Vec::new(),
+            anchor_confirmations: Vec::new(),
+            added_funds: Vec::new(),
+            attestations: Vec::new(),
+            special_tx_approvals: Vec::new(),
+            fee_estimates: Vec::new(),
+            cpfp_requests: Vec::new(),
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Changes the anchoring configuration.
+    pub fn set_config(&self, config: Config) {
+        self.state.lock().unwrap().config = config;
+    }
+
+    /// Changes the version metadata of the anchoring service.
+    pub fn set_version(&self, version: ServiceVersion) {
+        self.state.lock().unwrap().version = version;
+    }
+
+    /// Changes the anchoring transaction proposal.
+    pub fn set_proposal(&self, proposal: AnchoringProposalState) {
+        self.state.lock().unwrap().proposal = proposal;
+    }
+
+    /// Changes the progress of collecting signatures for the anchoring transaction proposal.
+    pub fn set_signing_progress(&self, progress: Option<SigningProgress>) {
+        self.state.lock().unwrap().signing_progress = progress;
+    }
+
+    /// Appends the transaction to the anchoring chain.
+    pub fn push_transaction(&self, transaction: btc::Transaction) {
+        self.state.lock().unwrap().transactions.push(transaction);
+    }
+
+    /// Sets the unspent funding transaction of the actual configuration.
+    pub fn set_unspent_funding_transaction(&self, transaction: Option<btc::Transaction>) {
+        self.state.lock().unwrap().unspent_funding_transaction = transaction;
+    }
+
+    /// Changes the state of the funding transaction with the given identifier.
+    pub fn set_funding_transaction_state(
+        &self,
+        txid: btc::Sha256d,
+        state: FundingTransactionState,
+    ) {
+        self.state
+            .lock()
+            .unwrap()
+            .funding_states
+            .insert(txid, state);
+    }
+
+    /// Changes the confirmation state of the anchoring transaction with the given identifier.
+    pub fn set_anchor_confirmation_state(
+        &self,
+        txid: btc::Sha256d,
+        state: AnchorConfirmationState,
+    ) {
+        self.state.lock().unwrap().anchor_states.insert(txid, state);
+    }
+
+    /// Changes the approval state of the anchoring transaction with the given identifier.
+    pub fn set_special_tx_approval_state(&self, txid: btc::Sha256d, state: SpecialTxApprovalState) {
+        self.state
+            .lock()
+            .unwrap()
+            .special_tx_states
+            .insert(txid, state);
+    }
+
+    /// Returns all received `SignInput` messages in order of arrival.
+    pub fn sign_inputs(&self) -> Vec<SignInput> {
+        self.state.lock().unwrap().sign_inputs.clone()
+    }
+
+    /// Returns all received `ConfirmAnchor` messages in order of arrival.
+    pub fn anchor_confirmations(&self) -> Vec<ConfirmAnchor> {
+        self.state.lock().unwrap().anchor_confirmations.clone()
+    }
+
+    /// Returns all received funding transactions in order of arrival.
+    pub fn added_funds(&self) -> Vec<btc::Transaction> {
+        self.state.lock().unwrap().added_funds.clone()
+    }
+
+    /// Returns all received key attestations in order of arrival.
+    pub fn attestations(&self) -> Vec<AttestKey> {
+        self.state.lock().unwrap().attestations.clone()
+    }
+
+    /// Returns identifiers of the approved special anchoring transactions in order
+    /// of arrival.
+    pub fn special_tx_approvals(&self) -> Vec<btc::Sha256d> {
+        self.state.lock().unwrap().special_tx_approvals.clone()
+    }
+
+    /// Returns all reported fee estimates in order of arrival.
+    pub fn fee_estimates(&self) -> Vec<u64> {
+        self.state.lock().unwrap().fee_estimates.clone()
+    }
+
+    /// Returns all CPFP requests in order of arrival.
+    pub fn cpfp_requests(&self) -> Vec<RequestCpfp> {
+        self.state.lock().unwrap().cpfp_requests.clone()
+    }
+}
+
+#[async_trait]
+impl PrivateApi for InMemoryPrivateApi {
+    type Error = Infallible;
+
+    async fn sign_input(&self, sign_input: SignInput) -> Result<Hash, Self::Error> {
+        let hash = sign_input.object_hash();
+        self.state.lock().unwrap().sign_inputs.push(sign_input);
+        Ok(hash)
+    }
+
+    async fn sign_inputs(&self, sign_inputs: Vec<SignInput>) -> Result<Vec<Hash>, Self::Error> {
+        let hashes = sign_inputs.iter().map(ObjectHash::object_hash).collect();
+        self.state.lock().unwrap().sign_inputs.extend(sign_inputs);
+        Ok(hashes)
+    }
+
+    async fn confirm_anchor(&self, confirmation: ConfirmAnchor) -> Result<Hash, Self::Error> {
+        let hash = confirmation.object_hash();
+        self.state
+            .lock()
+            .unwrap()
+            .anchor_confirmations
+            .push(confirmation);
+        Ok(hash)
+    }
+
+    async fn report_fee_estimate(&self, estimate: ReportFeeEstimate) -> Result<Hash, Self::Error> {
+        let hash = estimate.object_hash();
+        self.state.lock().unwrap().fee_estimates.push(estimate.fee);
+        Ok(hash)
+    }
+
+    async fn request_cpfp(&self, request: RequestCpfp) -> Result<Hash, Self::Error> {
+        let hash = request.object_hash();
+        self.state.lock().unwrap().cpfp_requests.push(request);
+        Ok(hash)
+    }
+
+    async fn anchoring_proposal(&self) -> Result<AnchoringProposalState, Self::Error> {
+        Ok(self.state.lock().unwrap().proposal.clone())
+    }
+
+    async fn anchoring_proposal_psbt(&self) -> Result<Option<ProposalPsbt>, Self::Error> {
+        let state = self.state.lock().unwrap();
+        Ok(match &state.proposal {
+            AnchoringProposalState::Available {
+                transaction,
+                inputs,
+            } => ProposalPsbt::new(&state.config, transaction, inputs).ok(),
+            _ => None,
+        })
+    }
+
+    async fn submit_psbt(&self, psbt: ProposalPsbt) -> Result<Vec<Hash>, Self::Error> {
+        let mut state = self.state.lock().unwrap();
+        let sign_inputs = match &state.proposal {
+            AnchoringProposalState::Available { transaction, .. } => {
+                // The fake accepts the signatures made by any key of the configuration.
+                let bitcoin_keys = state
+                    .config
+                    .anchoring_keys
+                    .iter()
+                    .map(|keys| keys.bitcoin_key)
+                    .chain(state.config.fee_payer_key)
+                    .collect::<Vec<_>>();
+                psbt.signatures(transaction, &bitcoin_keys)
+                    .unwrap_or_default()
+            }
+            _ => Vec::new(),
+        };
+        let hashes = sign_inputs.iter().map(ObjectHash::object_hash).collect();
+        state.sign_inputs.extend(sign_inputs);
+        Ok(hashes)
+    }
+
+    async fn config(&self) -> Result<Config, Self::Error> {
+        Ok(self.state.lock().unwrap().config.clone())
+    }
+
+    async fn transaction_with_index(
+        &self,
+        index: u64,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .transactions
+            .get(index as usize)
+            .cloned())
+    }
+
+    async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error> {
+        let count = self.state.lock().unwrap().transactions.len() as u64;
+        Ok(count.into())
+    }
+
+    async fn unspent_funding_transaction(&self) -> Result<Option<btc::Transaction>, Self::Error> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .unspent_funding_transaction
+            .clone())
+    }
+
+    async fn funding_transaction_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<FundingTransactionState, Self::Error> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .funding_states
+            .get(&txid)
+            .copied()
+            .unwrap_or(FundingTransactionState::Unknown))
+    }
+
+    async fn anchor_confirmation_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<AnchorConfirmationState, Self::Error> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .anchor_states
+            .get(&txid)
+            .cloned()
+            .unwrap_or(AnchorConfirmationState::Unknown))
+    }
+
+    async fn version(&self) -> Result<ServiceVersion, Self::Error> {
+        Ok(self.state.lock().unwrap().version.clone())
+    }
+
+    async fn signing_progress(&self) -> Result<Option<SigningProgress>, Self::Error> {
+        Ok(self.state.lock().unwrap().signing_progress.clone())
+    }
+
+    async fn special_tx_approval_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<SpecialTxApprovalState, Self::Error> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .special_tx_states
+            .get(&txid)
+            .cloned()
+            .unwrap_or(SpecialTxApprovalState::NotRequired))
+    }
+}
+
+#[async_trait]
+impl ManagementApi for InMemoryPrivateApi {
+    type Error = Infallible;
+
+    async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error> {
+        let hash = AddFunds {
+            transaction: transaction.clone(),
+        }
+        .object_hash();
+        self.state.lock().unwrap().added_funds.push(transaction);
+        Ok(hash)
+    }
+
+    async fn attest_key(&self, attestation: AttestKey) -> Result<Hash, Self::Error> {
+        let hash = attestation.object_hash();
+        self.state.lock().unwrap().attestations.push(attestation);
+        Ok(hash)
+    }
+
+    async fn recovery_bundle(
+        &self,
+        query: RecoveryBundleQuery,
+    ) -> Result<EncryptedRecoveryBundle, Self::Error> {
+        // Any export is considered authorized by the given key at the genesis block.
+        let export = AuthorizedRecoveryExport {
+            escrow_key_hash: query.key.hash(),
+            height: 0,
+        };
+        let state = self.state.lock().unwrap();
+        let bundle = RecoveryBundle::from_parts(
+            query.export_index,
+            export,
+            Height(0),
+            Some(state.config.clone()),
+            state.transactions.last().cloned(),
+            None,
+        );
+        Ok(query.key.encrypt(&bundle))
+    }
+
+    async fn approve_special_tx(&self, txid: btc::Sha256d) -> Result<Hash, Self::Error> {
+        let hash = ApproveSpecialTx { txid }.object_hash();
+        self.state.lock().unwrap().special_tx_approvals.push(txid);
+        Ok(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Network;
+    use exonum::{
+        crypto::{Hash, KeyPair},
+        helpers::Height,
+    };
+
+    use crate::{
+        api::{ServiceVersion, SpecialTxApprovalState},
+        blockchain::{RequestCpfp, SigningProgress},
+        btc::{self, BtcAnchoringTransactionBuilder},
+        config::{AnchoringKeys, Config, FeeEstimation},
+        sync::{
+            check_bootstrap, AnchoringChainUpdateTask, BitcoinRelay, BitcoinRpcClient,
+            BootstrapItem, BroadcastPolicy, ChainUpdateError, CheckStatus, CheckpointStore,
+            FallbackRelay, FeeHistogram, FileCheckpointStore, KeyPool, MultiSyncRunner,
+            RecordingRelay, RelayRecord, RelayRequest, ReplayError, ReplayRelay, RetryPolicy,
+            ReviewDecision, ReviewPolicy, ReviewQueue, SyncCheckpoint, SyncRunner,
+            SyncWithBitcoinError, SyncWithBitcoinTask, TransactionStatus, VerificationAlert,
+            VerificationPolicy,
+        },
+        test_helpers::create_fake_funding_transaction,
+    };
+
+    use std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use super::{
+        AnchorConfirmationState, AnchorInclusion, AnchoringProposalState, InMemoryPrivateApi,
+        InMemoryRelay, InMemoryRelayError,
+    };
+
+    fn anchoring_keypairs(count: usize) -> Vec<(btc::PublicKey, btc::PrivateKey)> {
+        (0..count)
+            .map(|_| btc::gen_keypair(Network::Testnet))
+            .collect()
+    }
+
+    fn anchoring_config(keypairs: &[(btc::PublicKey, btc::PrivateKey)]) -> Config {
+        let anchoring_keys = keypairs.iter().map(|(bitcoin_key, _)| AnchoringKeys {
+            bitcoin_key: *bitcoin_key,
+            service_key: KeyPair::random().public_key(),
+            weight: AnchoringKeys::DEFAULT_WEIGHT,
+            derivation: None,
+        });
+        Config::with_public_keys(Network::Testnet, anchoring_keys).unwrap()
+    }
+
+    #[tokio::test]
+    async fn chain_update_task_signs_proposal() {
+        let keypairs = anchoring_keypairs(4);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, inputs) = builder.create().unwrap();
+
+        let api = InMemoryPrivateApi::new(config);
+        api.set_proposal(AnchoringProposalState::Available {
+            transaction: transaction.clone(),
+            inputs,
+        });
+
+        AnchoringChainUpdateTask::new(KeyPool::new(keypairs[..1].to_vec()).unwrap(), api.clone())
+            .process()
+            .await
+            .unwrap();
+        let sign_inputs = api.sign_inputs();
+        assert_eq!(sign_inputs.len(), 1);
+        assert_eq!(sign_inputs[0].txid, transaction.id());
+    }
+
+    #[tokio::test]
+    async fn chain_update_task_shutdown() {
+        let keypairs = anchoring_keypairs(4);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, inputs) = builder.create().unwrap();
+
+        let api = InMemoryPrivateApi::new(config);
+        api.set_proposal(AnchoringProposalState::Available {
+            transaction,
+            inputs,
+        });
+
+        let task = AnchoringChainUpdateTask::new(
+            KeyPool::new(keypairs[..1].to_vec()).unwrap(),
+            api.clone(),
+        );
+        assert!(!task.is_shut_down());
+        task.shutdown().await;
+        assert!(task.is_shut_down());
+        // The task doesn't sign proposals after the shutdown.
+        task.process().await.unwrap();
+        assert!(api.sign_inputs().is_empty());
+    }
+
+    #[tokio::test]
+    async fn chain_update_task_resubmits_stalled_signatures() {
+        let keypairs = anchoring_keypairs(4);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, inputs) = builder.create().unwrap();
+
+        let api = InMemoryPrivateApi::new(config);
+        api.set_proposal(AnchoringProposalState::Available {
+            transaction: transaction.clone(),
+            inputs,
+        });
+        let mut progress = SigningProgress {
+            txid: transaction.id(),
+            anchored_height: Height::zero(),
+            waiting_blocks: 1,
+            input_signatures: vec![1],
+            is_stalled: false,
+        };
+        api.set_signing_progress(Some(progress.clone()));
+
+        let key_pool = KeyPool::new(keypairs[..1].to_vec()).unwrap();
+        let task = AnchoringChainUpdateTask::new(key_pool, api.clone());
+        task.process().await.unwrap();
+        assert_eq!(api.sign_inputs().len(), 1);
+        // Signatures for the same proposal are not sent again while it is not stalled.
+        task.process().await.unwrap();
+        assert_eq!(api.sign_inputs().len(), 1);
+
+        progress.waiting_blocks = 20;
+        progress.is_stalled = true;
+        api.set_signing_progress(Some(progress));
+        task.process().await.unwrap();
+        let sign_inputs = api.sign_inputs();
+        assert_eq!(sign_inputs.len(), 2);
+        assert_eq!(sign_inputs[0], sign_inputs[1]);
+    }
+
+    #[tokio::test]
+    async fn chain_update_task_imports_offline_signatures() {
+        let keypairs = anchoring_keypairs(4);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, inputs) = builder.create().unwrap();
+        let proposal = AnchoringProposalState::Available {
+            transaction: transaction.clone(),
+            inputs,
+        };
+
+        let api = InMemoryPrivateApi::new(config.clone());
+        api.set_proposal(proposal.clone());
+        let online_api = InMemoryPrivateApi::new(config);
+        online_api.set_proposal(proposal);
+        let key_pool = KeyPool::new(keypairs[..1].to_vec()).unwrap();
+        AnchoringChainUpdateTask::new(key_pool.clone(), online_api.clone())
+            .process()
+            .await
+            .unwrap();
+
+        // The node doesn't hold the Bitcoin keys.
+        let task = AnchoringChainUpdateTask::new(KeyPool::default(), api.clone());
+        let export = task.export_sighashes().await.unwrap();
+        assert_eq!(export.txid, transaction.id());
+        assert_eq!(export.inputs.len(), 1);
+
+        // Signatures made by the keys which can't sign the inputs are rejected.
+        let foreign_keys = KeyPool::new(anchoring_keypairs(1)).unwrap();
+        let mut signatures = export.sign(&foreign_keys);
+        assert!(signatures.signatures.is_empty());
+        signatures.signatures = export.sign(&key_pool).signatures;
+        signatures.signatures[0].public_key = keypairs[1].0;
+        task.import_signatures(&signatures).await.unwrap_err();
+        assert!(api.sign_inputs().is_empty());
+
+        // Offline signatures are the same as the ones made by the online node.
+        let signatures = export.sign(&key_pool);
+        assert_eq!(task.import_signatures(&signatures).await.unwrap(), 1);
+        assert_eq!(api.sign_inputs(), online_api.sign_inputs());
+    }
+
+    #[tokio::test]
+    async fn chain_update_task_awaits_review() {
+        let keypairs = anchoring_keypairs(4);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, inputs) = builder.create().unwrap();
+        let txid = transaction.id();
+
+        let proposal = AnchoringProposalState::Available {
+            transaction,
+            inputs,
+        };
+        let new_api = || {
+            let api = InMemoryPrivateApi::new(config.clone());
+            api.set_proposal(proposal.clone());
+            api
+        };
+        let key_pool = KeyPool::new(keypairs[..1].to_vec()).unwrap();
+
+        // The proposal spends only the fee, which doesn't exceed the threshold.
+        let review_queue = ReviewQueue::new(ReviewPolicy {
+            spend_threshold: 10_000,
+            review_window: Duration::from_secs(3600),
+        });
+        let api = new_api();
+        let task = AnchoringChainUpdateTask::new(key_pool.clone(), api.clone())
+            .with_review_queue(review_queue.clone());
+        task.process().await.unwrap();
+        assert_eq!(api.sign_inputs().len(), 1);
+        assert!(review_queue.proposals().is_empty());
+
+        let api = new_api();
+        let review_queue = ReviewQueue::new(ReviewPolicy {
+            spend_threshold: 0,
+            review_window: Duration::from_secs(3600),
+        });
+        let task = AnchoringChainUpdateTask::new(key_pool.clone(), api.clone())
+            .with_review_queue(review_queue.clone());
+        match task.process().await {
+            Err(ChainUpdateError::AwaitingReview { txid: id, .. }) => assert_eq!(id, txid),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        let proposals = review_queue.proposals();
+        assert_eq!(proposals.len(), 1);
+        assert_eq!(proposals[0].txid, txid);
+        assert_eq!(proposals[0].decision, ReviewDecision::Pending);
+        assert!(proposals[0].spent_value > 0);
+        assert!(api.sign_inputs().is_empty());
+
+        assert!(review_queue.deny(txid));
+        match task.process().await {
+            Err(ChainUpdateError::ReviewDenied { txid: id }) => assert_eq!(id, txid),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert!(api.sign_inputs().is_empty());
+
+        assert!(review_queue.approve(txid));
+        task.process().await.unwrap();
+        assert_eq!(api.sign_inputs().len(), 1);
+
+        // The proposal is signed automatically once the review window expires.
+        let api = new_api();
+        let review_queue = ReviewQueue::new(ReviewPolicy {
+            spend_threshold: 0,
+            review_window: Duration::from_secs(0),
+        });
+        AnchoringChainUpdateTask::new(key_pool, api.clone())
+            .with_review_queue(review_queue)
+            .process()
+            .await
+            .unwrap();
+        assert_eq!(api.sign_inputs().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn chain_update_task_checks_compatibility() {
+        let keypairs = anchoring_keypairs(1);
+        let api = InMemoryPrivateApi::new(anchoring_config(&keypairs));
+        let task = AnchoringChainUpdateTask::new(KeyPool::new(keypairs).unwrap(), api.clone());
+        assert_eq!(
+            task.check_compatibility().await.unwrap(),
+            ServiceVersion::current()
+        );
+
+        // The node doesn't support one of the features.
+        let mut version = ServiceVersion::current();
+        version.features.pop();
+        api.set_version(version);
+        match task.check_compatibility().await {
+            Err(ChainUpdateError::Internal(e)) => {
+                assert!(format!("{:#}", e).contains("Features are not supported"))
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        // The node uses another protobuf schema.
+        let mut version = ServiceVersion::current();
+        version.schema_hash = Hash::zero();
+        api.set_version(version);
+        match task.check_compatibility().await {
+            Err(ChainUpdateError::Internal(e)) => {
+                assert!(format!("{:#}", e).contains("Protobuf schema mismatch"))
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_task_sends_transactions() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction.clone());
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(funding_tx, TransactionStatus::Committed(1));
+
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api);
+        assert_eq!(sync.process(None).await.unwrap(), Some(0));
+        assert_eq!(relay.transaction(transaction.id()), Some(transaction));
+        // The transaction is already known, so there is nothing to send.
+        assert_eq!(sync.process(Some(0)).await.unwrap(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn sync_tasks_share_relay() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction.clone());
+        let relay = Arc::new(InMemoryRelay::new());
+        relay.add_transaction(funding_tx, TransactionStatus::Committed(1));
+
+        let first = SyncWithBitcoinTask::new(Arc::clone(&relay), api.clone());
+        let second = SyncWithBitcoinTask::new(Arc::clone(&relay), api);
+        assert_eq!(first.process(None).await.unwrap(), Some(0));
+        assert_eq!(relay.transaction(transaction.id()), Some(transaction));
+        // The transaction has been sent via the same relay instance, so it is already known.
+        assert_eq!(second.process(None).await.unwrap(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn sync_tasks_run_concurrently() {
+        let relay = InMemoryRelay::new();
+        let mut txids = Vec::new();
+        let mut handles = Vec::new();
+        // Each anchoring instance has its own sync task, all of them are spawned
+        // on the same runtime.
+        for _ in 0..3 {
+            let keypairs = anchoring_keypairs(1);
+            let config = anchoring_config(&keypairs);
+            let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+            let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+            builder.additional_funds(funding_tx.clone()).unwrap();
+            builder.fee(config.transaction_fee);
+            builder.payload(Height::zero(), Hash::zero());
+            let (transaction, _) = builder.create().unwrap();
+
+            let api = InMemoryPrivateApi::new(config);
+            api.push_transaction(transaction.clone());
+            relay.add_transaction(funding_tx, TransactionStatus::Committed(1));
+            txids.push(transaction.id());
+
+            let sync = SyncWithBitcoinTask::new(relay.clone(), api);
+            handles.push(tokio::spawn(async move { sync.process(None).await }));
+        }
+
+        for handle in handles {
+            assert_eq!(handle.await.unwrap().unwrap(), Some(0));
+        }
+        for txid in txids {
+            relay.assert_status(txid, TransactionStatus::Mempool);
+        }
+    }
+
+    #[test]
+    fn bitcoin_rpc_client_pool() {
+        let client = BitcoinRpcClient::with_pool_size(
+            "http://localhost:18332",
+            bitcoincore_rpc::Auth::None,
+            0,
+        )
+        .unwrap();
+        // The pool contains at least one connection.
+        assert_eq!(client.pool_size(), 1);
+
+        let client =
+            BitcoinRpcClient::new("http://localhost:18332", bitcoincore_rpc::Auth::None).unwrap();
+        assert_eq!(client.pool_size(), BitcoinRpcClient::DEFAULT_POOL_SIZE);
+        // Clones share the same pool.
+        assert_eq!(client.clone().pool_size(), client.pool_size());
+    }
+
+    /// Serves JSON-RPC requests on the loopback interface with the given response body
+    /// and status after the given delay.
+    fn serve_rpc_response(
+        status: u16,
+        body: &'static str,
+        delay: Duration,
+    ) -> std::net::SocketAddr {
+        use hyper::{
+            service::{make_service_fn, service_fn},
+            Body, Request, Response, Server,
+        };
+        use std::convert::Infallible;
+
+        let make_service = make_service_fn(move |_: Request<Body>| async move {
+            Ok::<_, Infallible>(service_fn(move |_| async move {
+                tokio::time::delay_for(delay).await;
+                let mut response = Response::new(Body::from(body));
+                *response.status_mut() = hyper::StatusCode::from_u16(status).unwrap();
+                Ok::<_, Infallible>(response)
+            }))
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let address = server.local_addr();
+        tokio::spawn(server);
+        address
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn bitcoin_rpc_client_errors() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let transaction = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        // Bitcoin Core responds with the 500 status to the rejected transactions.
+        let address = serve_rpc_response(
+            500,
+            r#"{"result":null,"error":{"code":-26,"message":"txn-mempool-conflict"},"id":0}"#,
+            Duration::from_secs(0),
+        );
+        let client = BitcoinRpcClient::builder(
+            format!("http://{}", address),
+            bitcoincore_rpc::Auth::UserPass("user".to_owned(), "password".to_owned()),
+        )
+        .pool_size(1)
+        .build()
+        .unwrap();
+        let e = client.send_transaction(&transaction).await.unwrap_err();
+        assert!(BitcoinRpcClient::is_mempool_conflict(&e));
+        assert!(!BitcoinRpcClient::is_unavailable(&e));
+
+        // Slow nodes are treated as unavailable.
+        let address = serve_rpc_response(
+            200,
+            r#"{"result":null,"error":null,"id":0}"#,
+            Duration::from_secs(5),
+        );
+        let client =
+            BitcoinRpcClient::builder(format!("http://{}", address), bitcoincore_rpc::Auth::None)
+                .request_timeout(Duration::from_millis(100))
+                .build()
+                .unwrap();
+        let e = client.send_transaction(&transaction).await.unwrap_err();
+        assert!(BitcoinRpcClient::is_unavailable(&e));
+    }
+
+    #[test]
+    fn bitcoin_rpc_client_builder_rejects_invalid_settings() {
+        let builder =
+            || BitcoinRpcClient::builder("https://localhost:8332", bitcoincore_rpc::Auth::None);
+        assert!(builder()
+            .add_root_certificate("not a certificate")
+            .build()
+            .is_err());
+        assert!(builder().proxy("not a proxy url").build().is_err());
+
+        let client = builder()
+            .connect_timeout(Duration::from_secs(1))
+            .request_timeout(Duration::from_secs(5))
+            .danger_accept_invalid_certs(true)
+            .proxy("socks5://127.0.0.1:9050")
+            .build()
+            .unwrap();
+        assert_eq!(client.pool_size(), BitcoinRpcClient::DEFAULT_POOL_SIZE);
+    }
+
+    #[test]
+    fn fee_histogram_next_block_fee_rate() {
+        let histogram = FeeHistogram::from_entries(vec![(5, 400_000), (20, 300_000), (5, 200_000)]);
+        assert_eq!(histogram.buckets, vec![(20, 300_000), (5, 600_000)]);
+        // All transactions fit into the next block.
+        assert_eq!(histogram.next_block_fee_rate(), 0);
+
+        let histogram = FeeHistogram::from_entries(vec![(5, 400_000), (20, 700_000), (1, 100)]);
+        assert_eq!(histogram.next_block_fee_rate(), 5);
+    }
+
+    #[tokio::test]
+    async fn sync_task_delays_broadcast() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction.clone());
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(funding_tx, TransactionStatus::Committed(1));
+        relay.set_fee_histogram(FeeHistogram::from_entries(vec![(50, 2_000_000)]));
+
+        let policy = BroadcastPolicy {
+            target_fee_rate: 10,
+            max_delay: Duration::from_secs(3600),
+        };
+        let sync =
+            SyncWithBitcoinTask::new(relay.clone(), api.clone()).with_broadcast_policy(policy);
+        // The memory pool is congested, so the transaction is not sent.
+        assert_eq!(sync.process(None).await.unwrap(), None);
+        assert_eq!(relay.transaction(transaction.id()), None);
+
+        relay.set_fee_histogram(FeeHistogram::from_entries(vec![(5, 2_000_000)]));
+        assert_eq!(sync.process(None).await.unwrap(), Some(0));
+        assert_eq!(
+            relay.transaction(transaction.id()),
+            Some(transaction.clone())
+        );
+
+        // The transaction is sent regardless of the fee rate after the maximum delay.
+        let relay = InMemoryRelay::new();
+        relay.set_fee_histogram(FeeHistogram::from_entries(vec![(50, 2_000_000)]));
+        let policy = BroadcastPolicy {
+            max_delay: Duration::from_secs(0),
+            ..policy
+        };
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api).with_broadcast_policy(policy);
+        assert_eq!(sync.process(Some(0)).await.unwrap(), Some(0));
+        assert_eq!(relay.transaction(transaction.id()), Some(transaction));
+    }
+
+    #[tokio::test]
+    async fn sync_task_confirms_anchor() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction.clone());
+        let relay = InMemoryRelay::new();
+
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api.clone());
+        // The transaction is not committed yet, so there is nothing to confirm.
+        assert!(!sync.confirm_anchor(0).await.unwrap());
+        assert!(api.anchor_confirmations().is_empty());
+
+        let inclusion = AnchorInclusion {
+            block_hash: btc::Sha256d::new([1; 32]),
+            block_height: 100,
+        };
+        relay.set_inclusion(transaction.id(), inclusion.clone());
+        relay.add_transaction(transaction.clone(), TransactionStatus::Committed(3));
+        assert!(sync.confirm_anchor(0).await.unwrap());
+
+        let confirmations = api.anchor_confirmations();
+        assert_eq!(confirmations.len(), 1);
+        assert_eq!(confirmations[0].txid, transaction.id());
+        assert_eq!(confirmations[0].inclusion(), inclusion);
+        assert_eq!(confirmations[0].confirmations, 3);
+
+        // Finally confirmed transactions are not reported anymore.
+        api.set_anchor_confirmation_state(
+            transaction.id(),
+            AnchorConfirmationState::Final {
+                inclusion,
+                confirmed_at: Height(1),
+            },
+        );
+        assert!(sync.confirm_anchor(0).await.unwrap());
+        assert_eq!(api.anchor_confirmations().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sync_task_reports_fee_estimate() {
+        let keypairs = anchoring_keypairs(1);
+        let mut config = anchoring_config(&keypairs);
+        let api = InMemoryPrivateApi::new(config.clone());
+        let relay = InMemoryRelay::new();
+        relay.set_smart_fee(20);
+
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api.clone());
+        // The fee estimation is disabled.
+        assert_eq!(sync.report_fee_estimate().await.unwrap(), None);
+
+        config.fee_estimation = Some(FeeEstimation {
+            confirmation_target: 6,
+            min_fee: 5,
+            max_fee: 50,
+        });
+        api.set_config(config);
+        assert_eq!(sync.report_fee_estimate().await.unwrap(), Some(20));
+        // The same estimate is not reported twice.
+        assert_eq!(sync.report_fee_estimate().await.unwrap(), None);
+        // The estimate is limited by the caps of the configuration.
+        relay.set_smart_fee(1_000);
+        assert_eq!(sync.report_fee_estimate().await.unwrap(), Some(50));
+        assert_eq!(api.fee_estimates(), vec![20, 50]);
+    }
+
+    #[tokio::test]
+    async fn sync_task_requests_cpfp() {
+        let keypairs = anchoring_keypairs(1);
+        let mut config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let api = InMemoryPrivateApi::new(config.clone());
+        api.push_transaction(transaction.clone());
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(funding_tx, TransactionStatus::Committed(1));
+        relay.add_transaction(transaction.clone(), TransactionStatus::Mempool);
+        relay.set_fee_histogram(FeeHistogram::from_entries(vec![(50, 2_000_000)]));
+
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api.clone());
+        // The fee bumping is disabled.
+        assert_eq!(sync.request_cpfp().await.unwrap(), None);
+
+        config.max_cpfp_fee = 30;
+        api.set_config(config);
+        // The requested fee rate is limited by the configuration.
+        assert_eq!(sync.request_cpfp().await.unwrap(), Some(30));
+        // The fee bumping is requested only once.
+        assert_eq!(sync.request_cpfp().await.unwrap(), None);
+        assert_eq!(
+            api.cpfp_requests(),
+            vec![RequestCpfp {
+                txid: transaction.id(),
+                fee: 30
+            }]
+        );
+
+        // The transaction with a sufficient fee is not bumped.
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api.clone());
+        relay.set_fee_histogram(FeeHistogram::from_entries(vec![(5, 2_000_000)]));
+        assert_eq!(sync.request_cpfp().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn check_bootstrap_reports_checklist() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let key_pool = KeyPool::new(keypairs).unwrap();
+        let api = InMemoryPrivateApi::new(config.clone());
+        let relay = InMemoryRelay::new();
+
+        // There are neither funds nor a relay.
+        let report = check_bootstrap(&api, None::<&InMemoryRelay>, &key_pool)
+            .await
+            .unwrap();
+        assert!(!report.is_ready());
+        assert_eq!(
+            report.check(BootstrapItem::AnchoringKeys).unwrap().status,
+            CheckStatus::Passed
+        );
+        assert_eq!(
+            report
+                .check(BootstrapItem::FundingTransaction)
+                .unwrap()
+                .status,
+            CheckStatus::Failed
+        );
+        assert_eq!(
+            report.check(BootstrapItem::BitcoinRelay).unwrap().status,
+            CheckStatus::Warning
+        );
+
+        // The funding transaction doesn't cover the fee.
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 100);
+        relay.add_transaction(funding_tx.clone(), TransactionStatus::Committed(6));
+        api.set_unspent_funding_transaction(Some(funding_tx));
+        let report = check_bootstrap(&api, Some(&relay), &key_pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            report
+                .check(BootstrapItem::FundingTransaction)
+                .unwrap()
+                .status,
+            CheckStatus::Failed
+        );
+
+        // The funding transaction is not committed yet.
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 100_000);
+        relay.add_transaction(funding_tx.clone(), TransactionStatus::Mempool);
+        api.set_unspent_funding_transaction(Some(funding_tx.clone()));
+        let report = check_bootstrap(&api, Some(&relay), &key_pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            report
+                .check(BootstrapItem::FundingTransaction)
+                .unwrap()
+                .status,
+            CheckStatus::Failed
+        );
+
+        relay.set_status(funding_tx.id(), TransactionStatus::Committed(1));
+        let report = check_bootstrap(&api, Some(&relay), &key_pool)
+            .await
+            .unwrap();
+        assert!(report.is_ready(), "{}", report);
+        assert!(report.check(BootstrapItem::AnchoringChain).is_none());
+
+        // The relay is unreachable, and the node has no anchoring key.
+        relay.set_unavailable(true);
+        let other_keys = KeyPool::new(anchoring_keypairs(1)).unwrap();
+        let report = check_bootstrap(&api, Some(&relay), &other_keys)
+            .await
+            .unwrap();
+        assert!(!report.is_ready());
+        assert_eq!(
+            report.check(BootstrapItem::AnchoringKeys).unwrap().status,
+            CheckStatus::Failed
+        );
+        assert_eq!(
+            report.check(BootstrapItem::BitcoinRelay).unwrap().status,
+            CheckStatus::Failed
+        );
+    }
+
+    #[tokio::test]
+    async fn verify_proposal_fee_limit() {
+        let keypairs = anchoring_keypairs(4);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, inputs) = builder.create().unwrap();
+        let fee = 10_000 - transaction.unspent_value().unwrap();
+
+        let api = InMemoryPrivateApi::new(config);
+        api.set_proposal(AnchoringProposalState::Available {
+            transaction: transaction.clone(),
+            inputs,
+        });
+
+        let task = AnchoringChainUpdateTask::new(KeyPool::default(), api.clone());
+        let policy = VerificationPolicy { max_fee: Some(fee) };
+        assert_eq!(task.verify_proposal(&policy).await.unwrap(), vec![]);
+
+        let policy = VerificationPolicy {
+            max_fee: Some(fee - 1),
+        };
+        assert_eq!(
+            task.verify_proposal(&policy).await.unwrap(),
+            vec![VerificationAlert::ExcessiveFee {
+                txid: transaction.id(),
+                fee,
+                max_fee: fee - 1,
+            }]
+        );
+        // Proposal is not signed in the verify-only mode.
+        assert!(api.sign_inputs().is_empty());
+    }
+
+    #[tokio::test]
+    async fn verify_chain_missing_transaction() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (tx0, _) = builder.create().unwrap();
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.prev_tx(tx0.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height(10), Hash::zero());
+        let (tx1, _) = builder.create().unwrap();
+
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(tx0.clone());
+        api.push_transaction(tx1.clone());
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(funding_tx, TransactionStatus::Committed(2));
+
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api);
+        // Transactions have not been sent yet, which is normal.
+        assert_eq!(sync.verify_chain(0).await.unwrap(), (0, vec![]));
+
+        // The second transaction is known, while the first one is not.
+        relay.add_transaction(tx1, TransactionStatus::Mempool);
+        assert_eq!(
+            sync.verify_chain(0).await.unwrap(),
+            (
+                0,
+                vec![VerificationAlert::MissingOnChain {
+                    index: 0,
+                    txid: tx0.id(),
+                }]
+            )
+        );
+
+        relay.add_transaction(tx0, TransactionStatus::Committed(1));
+        assert_eq!(sync.verify_chain(0).await.unwrap(), (1, vec![]));
+    }
+
+    #[tokio::test]
+    async fn sync_task_reports_mempool_conflict() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let create_tx = |height| {
+            let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+            builder.additional_funds(funding_tx.clone()).unwrap();
+            builder.fee(config.transaction_fee);
+            builder.payload(height, Hash::zero());
+            builder.create().unwrap().0
+        };
+        let transaction = create_tx(Height::zero());
+        // Another variant of the anchoring transaction spending the same funds.
+        let variant = create_tx(Height(1));
+
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction.clone());
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(funding_tx, TransactionStatus::Committed(1));
+        relay.add_transaction(variant.clone(), TransactionStatus::Mempool);
+
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api);
+        match sync.process(None).await.unwrap_err() {
+            SyncWithBitcoinError::MempoolConflict {
+                txid,
+                conflicting_txid,
+            } => {
+                assert_eq!(txid, transaction.id());
+                assert_eq!(conflicting_txid, Some(variant.id()));
+            }
+            e => panic!("Unexpected error occurred: {:?}", e),
+        }
+        assert_eq!(relay.transaction(transaction.id()), None);
+        assert_eq!(
+            sync.mempool_conflicts().get(&transaction.id()),
+            Some(&variant.id())
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_task_reports_low_relay_fee() {
+        let keypairs = anchoring_keypairs(1);
+        let mut config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 100_000);
+        // Bitcoin node with the increased relay fee floor.
+        let node_policy = btc::RelayFeePolicy {
+            min_relay_fee: 20_000,
+            ..btc::RelayFeePolicy::default()
+        };
+
+        let create_tx = |config: &Config| {
+            let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+            builder.additional_funds(funding_tx.clone()).unwrap();
+            builder.fee(config.transaction_fee);
+            builder.relay_fee_policy(btc::RelayFeePolicy {
+                min_relay_fee: config.min_relay_fee,
+                ..btc::RelayFeePolicy::default()
+            });
+            builder.payload(Height::zero(), Hash::zero());
+            builder.create().unwrap().0
+        };
+        let transaction = create_tx(&config);
+
+        let api = InMemoryPrivateApi::new(config.clone());
+        api.push_transaction(transaction.clone());
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(funding_tx.clone(), TransactionStatus::Committed(1));
+        relay.set_relay_fee_policy(node_policy);
+
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api);
+        match sync.process(None).await.unwrap_err() {
+            SyncWithBitcoinError::RelayFeeTooLow {
+                txid,
+                min_relay_fee,
+                node_min_relay_fee,
+            } => {
+                assert_eq!(txid, transaction.id());
+                assert_eq!(min_relay_fee, btc::DEFAULT_RELAY_FEE);
+                assert_eq!(node_min_relay_fee, node_policy.min_relay_fee);
+            }
+            e => panic!("Unexpected error occurred: {:?}", e),
+        }
+        assert_eq!(relay.transaction(transaction.id()), None);
+
+        // Raised minimal fee rate makes the anchoring transaction acceptable by the node.
+        config.min_relay_fee = node_policy.min_relay_fee;
+        let transaction = create_tx(&config);
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction.clone());
+
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api);
+        assert_eq!(sync.process(None).await.unwrap(), Some(0));
+        assert_eq!(relay.transaction(transaction.id()), Some(transaction));
+    }
+
+    #[tokio::test]
+    async fn sync_task_awaits_special_tx_approvals() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 100_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let transaction = builder.create().unwrap().0;
+
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction.clone());
+        let approver = KeyPair::random().public_key();
+        api.set_special_tx_approval_state(
+            transaction.id(),
+            SpecialTxApprovalState::Pending {
+                approvers: vec![approver],
+                required: 2,
+            },
+        );
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(funding_tx, TransactionStatus::Committed(1));
+
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api.clone());
+        match sync.process(None).await.unwrap_err() {
+            SyncWithBitcoinError::AwaitingApprovals {
+                txid,
+                approvals,
+                required,
+            } => {
+                assert_eq!(txid, transaction.id());
+                assert_eq!(approvals, 1);
+                assert_eq!(required, 2);
+            }
+            e => panic!("Unexpected error occurred: {:?}", e),
+        }
+        assert_eq!(relay.transaction(transaction.id()), None);
+
+        // Once enough approvals are recorded, the transaction is broadcast.
+        api.set_special_tx_approval_state(
+            transaction.id(),
+            SpecialTxApprovalState::Approved {
+                approvers: vec![approver, KeyPair::random().public_key()],
+            },
+        );
+        assert_eq!(sync.process(None).await.unwrap(), Some(0));
+        assert_eq!(relay.transaction(transaction.id()), Some(transaction));
+    }
+
+    #[tokio::test]
+    async fn fallback_relay_fails_over() {
+        let config = anchoring_config(&anchoring_keypairs(1));
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 100_000);
+        let primary = InMemoryRelay::new();
+        primary.add_transaction(funding_tx.clone(), TransactionStatus::Committed(1));
+        let secondary = InMemoryRelay::new();
+        let relay = FallbackRelay::new(vec![primary.clone(), secondary.clone()]);
+
+        let txid = funding_tx.id();
+        assert_eq!(
+            relay.transaction_status(txid).await.unwrap(),
+            TransactionStatus::Committed(1)
+        );
+
+        // The request is repeated with the secondary backend, which doesn't know
+        // the transaction.
+        primary.set_unavailable(true);
+        assert_eq!(
+            relay.transaction_status(txid).await.unwrap(),
+            TransactionStatus::Unknown
+        );
+        let health = relay.health();
+        assert!(!health[0].is_healthy());
+        assert_eq!(
+            health[0].last_error,
+            Some(InMemoryRelayError::Unavailable.to_string())
+        );
+        assert!(health[1].is_healthy());
+
+        // The failed backend is skipped during the retry interval.
+        primary.set_unavailable(false);
+        assert_eq!(
+            relay.transaction_status(txid).await.unwrap(),
+            TransactionStatus::Unknown
+        );
+        assert_eq!(relay.health()[0].total_failures, 1);
+
+        // But it is used if the other backends are unavailable.
+        secondary.set_unavailable(true);
+        assert_eq!(
+            relay.transaction_status(txid).await.unwrap(),
+            TransactionStatus::Committed(1)
+        );
+        assert!(relay.health()[0].is_healthy());
+
+        primary.set_unavailable(true);
+        assert_eq!(
+            relay.transaction_status(txid).await.unwrap_err(),
+            InMemoryRelayError::Unavailable
+        );
+    }
+
+    #[tokio::test]
+    async fn fallback_relay_returns_rejections() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 100_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let transaction = builder.create().unwrap().0;
+
+        let primary = InMemoryRelay::new();
+        primary.add_transaction(funding_tx.clone(), TransactionStatus::Committed(1));
+        primary.set_relay_fee_policy(btc::RelayFeePolicy {
+            min_relay_fee: 1_000_000,
+            ..btc::RelayFeePolicy::default()
+        });
+        let secondary = InMemoryRelay::new();
+        let relay = FallbackRelay::new(vec![primary, secondary.clone()]);
+
+        // The rejection of the transaction is not a reason to fail over.
+        assert_eq!(
+            relay.send_transaction(&transaction).await.unwrap_err(),
+            InMemoryRelayError::MinRelayFeeNotMet
+        );
+        assert_eq!(secondary.transaction(transaction.id()), None);
+        assert!(relay.health().iter().all(|health| health.is_healthy()));
+    }
+
+    #[test]
+    fn relay_reorg_returns_transactions_to_mempool() {
+        let config = anchoring_config(&anchoring_keypairs(1));
+        for depth in 1..=6 {
+            let relay = InMemoryRelay::new();
+            // Transactions are committed to the blocks on heights from 1 to 7.
+            let transactions = (0..7)
+                .map(|i| {
+                    let tx =
+                        create_fake_funding_transaction(&config.anchoring_address(), 10_000 + i);
+                    relay.add_transaction(tx.clone(), TransactionStatus::Mempool);
+                    relay.mine_blocks(1);
+                    tx
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(relay.tip_height(), 7);
+            let old_tip = relay.inclusion(transactions[6].id()).unwrap();
+
+            let disconnected = relay.reorg(depth);
+            assert_eq!(disconnected.len(), depth as usize);
+            assert_eq!(relay.tip_height(), 7);
+            for (i, tx) in transactions.iter().enumerate() {
+                let confirmations = 7 - i as u32;
+                if confirmations <= depth {
+                    assert!(disconnected.contains(&tx.id()));
+                    relay.assert_status(tx.id(), TransactionStatus::Mempool);
+                    assert_eq!(relay.inclusion(tx.id()), None);
+                } else {
+                    relay.assert_status(tx.id(), TransactionStatus::Committed(confirmations));
+                    assert!(relay.inclusion(tx.id()).is_some());
+                }
+            }
+
+            // Disconnected transactions are committed to the new block.
+            let new_tip = relay.mine_blocks(1)[0];
+            assert_ne!(new_tip, old_tip.block_hash);
+            let inclusion = relay.inclusion(transactions[6].id()).unwrap();
+            assert_eq!(inclusion.block_hash, new_tip);
+            assert_eq!(inclusion.block_height, 8);
+            relay.assert_status(transactions[6].id(), TransactionStatus::Committed(1));
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_task_rebroadcasts_invalidated_transaction() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction.clone());
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(funding_tx.clone(), TransactionStatus::Mempool);
+        relay.mine_blocks(1);
+
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api);
+        assert_eq!(sync.process(None).await.unwrap(), Some(0));
+        relay.mine_blocks(3);
+        relay.assert_status(transaction.id(), TransactionStatus::Committed(3));
+
+        // The reorganization returns the anchoring transaction to the memory pool,
+        // so it is still known and is not sent again.
+        assert_eq!(relay.reorg(3), vec![transaction.id()]);
+        assert_eq!(sync.process(Some(0)).await.unwrap(), Some(0));
+        relay.assert_status(transaction.id(), TransactionStatus::Mempool);
+
+        // The anchoring transaction is evicted from the reorganized blockchain,
+        // so it is sent again.
+        assert_eq!(relay.invalidate(transaction.id()), vec![transaction.id()]);
+        relay.assert_status(transaction.id(), TransactionStatus::Unknown);
+        assert_eq!(sync.process(Some(0)).await.unwrap(), Some(0));
+        relay.assert_status(transaction.id(), TransactionStatus::Mempool);
+
+        // Invalidation of the funding transaction evicts the anchoring transaction as well.
+        let mut evicted = vec![funding_tx.id(), transaction.id()];
+        evicted.sort();
+        assert_eq!(relay.invalidate(funding_tx.id()), evicted);
+        relay.assert_status(transaction.id(), TransactionStatus::Unknown);
+    }
+
+    #[tokio::test]
+    async fn sync_task_confirms_anchor_after_reorg() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction.clone());
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(transaction.clone(), TransactionStatus::Mempool);
+        relay.mine_blocks(2);
+
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api.clone());
+        assert!(sync.confirm_anchor(0).await.unwrap());
+        let old_inclusion = relay.inclusion(transaction.id()).unwrap();
+        assert_eq!(api.anchor_confirmations()[0].inclusion(), old_inclusion);
+
+        // The anchoring transaction is not committed after the reorganization,
+        // so there is nothing to confirm.
+        relay.reorg(2);
+        assert!(!sync.confirm_anchor(0).await.unwrap());
+        assert_eq!(api.anchor_confirmations().len(), 1);
+
+        // The new location of the anchoring transaction is reported.
+        relay.mine_blocks(1);
+        assert!(sync.confirm_anchor(0).await.unwrap());
+        let confirmations = api.anchor_confirmations();
+        assert_eq!(confirmations.len(), 2);
+        assert_ne!(confirmations[1].inclusion(), old_inclusion);
+        assert_eq!(
+            confirmations[1].inclusion(),
+            relay.inclusion(transaction.id()).unwrap()
+        );
+        assert_eq!(confirmations[1].confirmations, 1);
+    }
+
+    #[test]
+    fn sync_checkpoint_broadcast_history() {
+        let mut checkpoint = SyncCheckpoint::default();
+        let txid = |n: u8| btc::Sha256d::from_slice(&[n; 32]).unwrap();
+
+        assert!(checkpoint.record_broadcast(0, txid(0)));
+        // Repeated broadcasts of the same transaction are not recorded.
+        assert!(!checkpoint.record_broadcast(0, txid(0)));
+        assert_eq!(checkpoint.broadcast_history.len(), 1);
+
+        for n in 1..=SyncCheckpoint::MAX_HISTORY_LEN as u8 {
+            assert!(checkpoint.record_broadcast(u64::from(n), txid(n)));
+        }
+        assert_eq!(
+            checkpoint.broadcast_history.len(),
+            SyncCheckpoint::MAX_HISTORY_LEN
+        );
+        // The oldest record has been removed.
+        assert_eq!(checkpoint.broadcast_history[0].txid, txid(1));
+    }
+
+    #[tokio::test]
+    async fn file_checkpoint_store() {
+        let path = std::env::temp_dir()
+            .join(format!(
+                "btc_anchoring_checkpoint_{}",
+                rand::random::<u64>()
+            ))
+            .join("checkpoint.json");
+        let store = FileCheckpointStore::new(&path);
+        assert_eq!(store.load().await.unwrap(), None);
+
+        let mut checkpoint = SyncCheckpoint {
+            latest_synced_tx_index: Some(5),
+            ..SyncCheckpoint::default()
+        };
+        checkpoint.record_broadcast(5, btc::Sha256d::from_slice(&[5; 32]).unwrap());
+        store.save(&checkpoint).await.unwrap();
+        assert_eq!(store.load().await.unwrap(), Some(checkpoint.clone()));
+
+        // The saved checkpoint is replaced.
+        checkpoint.latest_synced_tx_index = Some(6);
+        store.save(&checkpoint).await.unwrap();
+        assert_eq!(store.load().await.unwrap(), Some(checkpoint));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn recorded_relay_is_replayed() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let path = std::env::temp_dir()
+            .join(format!("btc_anchoring_relay_{}", rand::random::<u64>()))
+            .join("relay.jsonl");
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(funding_tx, TransactionStatus::Committed(1));
+        let recording = RecordingRelay::new(relay.clone(), &path).unwrap();
+
+        let api = InMemoryPrivateApi::new(config.clone());
+        api.push_transaction(transaction.clone());
+        let sync = SyncWithBitcoinTask::new(recording.clone(), api);
+        assert_eq!(sync.process(None).await.unwrap(), Some(0));
+        let records = recording.records();
+        assert!(records.contains(&RelayRecord {
+            request: RelayRequest::SendTransaction {
+                transaction: transaction.clone(),
+            },
+            response: Ok(serde_json::to_value(transaction.id()).unwrap()),
+        }));
+
+        // The sync task gets the same responses from the replayed relay.
+        let replay = ReplayRelay::load(&path).unwrap();
+        assert_eq!(replay.remaining(), records.len());
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction.clone());
+        let sync = SyncWithBitcoinTask::new(replay, api);
+        assert_eq!(sync.process(None).await.unwrap(), Some(0));
+        // Requests which differ from the recorded ones are rejected.
+        let replay = ReplayRelay::load(&path).unwrap();
+        let unknown_txid = btc::Sha256d::from_slice(&[1; 32]).unwrap();
+        match replay.transaction_status(unknown_txid).await.unwrap_err() {
+            ReplayError::UnexpectedRequest { expected, .. } => {
+                assert_eq!(expected, records[0].request)
+            }
+            e => panic!("Unexpected error: {}", e),
+        }
+        assert_eq!(replay.remaining(), records.len());
+
+        // Errors are recorded together with their kind, the oldest records are removed.
+        let recording = RecordingRelay::new(relay.clone(), &path)
+            .unwrap()
+            .with_capacity(1);
+        relay.set_unavailable(true);
+        recording
+            .transaction_status(transaction.id())
+            .await
+            .unwrap_err();
+        let replay = ReplayRelay::load(&path).unwrap();
+        assert_eq!(replay.remaining(), 1);
+        let e = replay
+            .transaction_status(transaction.id())
+            .await
+            .unwrap_err();
+        assert!(ReplayRelay::is_unavailable(&e));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sync_task_reports_last_broadcast() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction.clone());
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(funding_tx, TransactionStatus::Committed(1));
+
+        let sync = SyncWithBitcoinTask::new(relay, api);
+        assert_eq!(sync.last_broadcast(), None);
+        assert_eq!(sync.process(None).await.unwrap(), Some(0));
+        assert_eq!(sync.last_broadcast(), Some((0, transaction.id())));
+    }
+
+    #[tokio::test]
+    async fn sync_task_shutdown_saves_checkpoint() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let path = std::env::temp_dir()
+            .join(format!(
+                "btc_anchoring_checkpoint_{}",
+                rand::random::<u64>()
+            ))
+            .join("checkpoint.json");
+        let store = FileCheckpointStore::new(&path);
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction.clone());
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(funding_tx, TransactionStatus::Committed(1));
+
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api)
+            .with_checkpoint_store(store.clone(), SyncCheckpoint::default());
+        assert_eq!(sync.process(None).await.unwrap(), Some(0));
+        // The broadcast transaction is recorded before `process` returns.
+        let checkpoint = store.load().await.unwrap().unwrap();
+        assert_eq!(checkpoint, sync.checkpoint());
+        assert_eq!(checkpoint.latest_synced_tx_index, Some(0));
+        assert_eq!(checkpoint.broadcast_history[0].txid, transaction.id());
+
+        std::fs::remove_file(&path).unwrap();
+        sync.shutdown().await.unwrap();
+        assert_eq!(store.load().await.unwrap(), Some(checkpoint));
+
+        // The task does nothing after the shutdown.
+        relay.set_unavailable(true);
+        assert_eq!(sync.process(None).await.unwrap(), None);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sync_task_restores_checkpoint() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let path = std::env::temp_dir()
+            .join(format!(
+                "btc_anchoring_checkpoint_{}",
+                rand::random::<u64>()
+            ))
+            .join("checkpoint.json");
+        let store = FileCheckpointStore::new(&path);
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction.clone());
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(funding_tx, TransactionStatus::Committed(1));
+
+        // The task starts from scratch without the saved checkpoint.
+        let sync = SyncWithBitcoinTask::new(relay.clone(), api.clone())
+            .with_restored_checkpoint(store.clone())
+            .await
+            .unwrap();
+        assert_eq!(sync.checkpoint(), SyncCheckpoint::default());
+        assert_eq!(sync.process(None).await.unwrap(), Some(0));
+        sync.shutdown().await.unwrap();
+
+        // The restarted task resumes from the saved checkpoint.
+        let sync = SyncWithBitcoinTask::new(relay, api)
+            .with_restored_checkpoint(store)
+            .await
+            .unwrap();
+        assert_eq!(sync.checkpoint().latest_synced_tx_index, Some(0));
+        assert_eq!(sync.last_broadcast(), Some((0, transaction.id())));
+        let latest_synced_tx_index = sync.checkpoint().latest_synced_tx_index;
+        assert_eq!(sync.process(latest_synced_tx_index).await.unwrap(), Some(0));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sync_task_shutdown_interrupts_retries() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let api = InMemoryPrivateApi::new(config);
+        api.push_transaction(transaction);
+        let relay = InMemoryRelay::new();
+        relay.set_unavailable(true);
+
+        let retry_policy = RetryPolicy {
+            max_retries: 10,
+            initial_delay: Duration::from_secs(60),
+            max_delay: Duration::from_secs(60),
+        };
+        let sync = SyncWithBitcoinTask::new(relay, api).with_retry_policy(retry_policy);
+        let start = Instant::now();
+        let (res, shutdown) = futures::join!(sync.process(None), async {
+            tokio::time::delay_for(Duration::from_millis(50)).await;
+            sync.shutdown().await
+        });
+        shutdown.unwrap();
+        // Neither the shutdown nor the in-flight call wait for the retries.
+        assert_eq!(res.unwrap(), None);
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn multi_sync_runner_reports_status() {
+        let keypairs = anchoring_keypairs(1);
+        let config = anchoring_config(&keypairs);
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let runner = |relay: InMemoryRelay| {
+            let api = InMemoryPrivateApi::new(config.clone());
+            api.push_transaction(transaction.clone());
+            let key_pool = KeyPool::new(keypairs.clone()).unwrap();
+            SyncRunner::new(AnchoringChainUpdateTask::new(key_pool, api.clone()))
+                .with_bitcoin_task(Arc::new(SyncWithBitcoinTask::new(relay, api)))
+                .with_chain_update_interval(Duration::from_millis(10))
+                .with_bitcoin_sync_interval(Duration::from_millis(10))
+                .with_jitter(Duration::default())
+                .with_max_backoff(Duration::from_millis(20))
+        };
+        let relay = InMemoryRelay::new();
+        relay.add_transaction(funding_tx, TransactionStatus::Committed(1));
+        let unavailable_relay = InMemoryRelay::new();
+        unavailable_relay.set_unavailable(true);
+        // Instance names are unique.
+        let duplicate = MultiSyncRunner::new()
+            .with_instance("first", runner(InMemoryRelay::new()))
+            .unwrap()
+            .with_instance("first", runner(InMemoryRelay::new()));
+        assert!(duplicate.is_err());
+
+        let runner = MultiSyncRunner::new()
+            .with_instance("first", runner(relay.clone()))
+            .unwrap()
+            .with_instance("second", runner(unavailable_relay))
+            .unwrap();
+
+        let check = async {
+            tokio::time::delay_for(Duration::from_millis(200)).await;
+            let status = runner.status();
+            let first = status["first"].bitcoin_sync.clone().unwrap();
+            assert!(first.completed_rounds > 0);
+            assert_eq!(first.consecutive_failures, 0);
+            // The relay errors of the second instance don't affect the first one.
+            let second = status["second"].bitcoin_sync.clone().unwrap();
+            assert_eq!(second.completed_rounds, 0);
+            assert!(second.consecutive_failures > 0);
+            assert!(second.last_error.is_some());
+            assert!(status["second"].chain_update.completed_rounds > 0);
+
+            runner.shutdown().await.unwrap();
+        };
+        let (res, _) = futures::join!(runner.run(), check);
+        res.unwrap();
+
+        let status = runner.status();
+        assert!(status.values().all(|status| status.chain_update.is_stopped
+            && status.bitcoin_sync.as_ref().unwrap().is_stopped));
+        relay.assert_status(transaction.id(), TransactionStatus::Mempool);
+    }
+}
@@ -26,7 +26,7 @@ use exonum::{
     runtime::{InstanceId, SnapshotExt, SUPERVISOR_INSTANCE_ID},
 };
 use exonum_merkledb::{access::Access, Snapshot};
-use exonum_rust_runtime::api;
+use exonum_rust_runtime::{api, RustRuntimeBuilder};
 use exonum_supervisor::{ConfigPropose, Supervisor, SupervisorInterface};
 use exonum_testkit::{ApiKind, Spec, TestKit, TestKitApiClient, TestKitBuilder, TestNode};
 use rand::{thread_rng, Rng};
@@ -35,13 +35,25 @@ use std::collections::BTreeMap;
 
 use crate::{
     api::{
-        AnchoringChainLength, AnchoringProposalState, FindTransactionQuery, IndexQuery, PrivateApi,
-        PublicApi, TransactionProof,
+        AnchorConfirmationState, AnchorFinalityProof, AnchorStatusQuery, AnchoredPayload,
+        AnchoringChainDigest, AnchoringChainLength, AnchoringProposalState, AnchoringSchedule,
+        AnchoringStatus, BlockCoverage, ChainDigestQuery, ConfigDiff, CoverageQuery,
+        FindTransactionQuery, FundingTransactionQuery, FundingTransactionState, HistoricalConfig,
+        IndexQuery, LatencyQuery, ManagementApi, Page, PageQuery, PrivateApi, ProofToVerify,
+        ProofVerdict, PublicApi, RecoveryBundleQuery, RejectedSignature, RejectedSignaturesQuery,
+        SchemaHashes, ServiceLimits, ServiceVersion, SpecialTxApprovalState, SpecialTxQuery,
+        TransactionProof, TransactionsPage, TransactionsQuery, TxidQuery,
+    },
+    blockchain::{
+        AddFunds, AnchoringLatency, ApproveSpecialTx, AttestKey, BtcAnchoringInterface,
+        ConfirmAnchor, FinalizationIncident, ReportFeeEstimate, RequestCpfp, Schema, SignInput,
+        SignatureConflict, SigningProgress, TransitionStatus,
     },
-    blockchain::{AddFunds, BtcAnchoringInterface, Schema, SignInput},
     btc,
     config::Config,
     proto::AnchoringKeys,
+    psbt::ProposalPsbt,
+    recovery::EncryptedRecoveryBundle,
     BtcAnchoringService,
 };
 
@@ -111,6 +123,8 @@ impl AnchoringNodes {
             .map(|(&service_key, &bitcoin_key)| AnchoringKeys {
                 bitcoin_key,
                 service_key,
+                weight: AnchoringKeys::DEFAULT_WEIGHT,
+                derivation: None,
             })
             .collect()
     }
@@ -145,12 +159,17 @@ impl AnchoringTestKit {
     /// Creates an anchoring testkit instance for the specified number of anchoring nodes,
     /// and interval between anchors.
     pub fn new(nodes_num: u16, anchoring_interval: u64) -> Self {
+        Self::with_network(nodes_num, anchoring_interval, Network::Testnet)
+    }
+
+    /// Creates an anchoring testkit instance for the specified number of anchoring nodes,
+    /// interval between anchors and Bitcoin network.
+    pub fn with_network(nodes_num: u16, anchoring_interval: u64, network: Network) -> Self {
         let validator_keys = (0..nodes_num)
             .map(|_| gen_validator_keys())
             .collect::<Vec<_>>();
 
-        let network = Network::Testnet;
-        let anchoring_nodes = AnchoringNodes::from_keys(Network::Testnet, &validator_keys);
+        let anchoring_nodes = AnchoringNodes::from_keys(network, &validator_keys);
 
         let anchoring_config = Config {
             network,
@@ -175,6 +194,18 @@ impl AnchoringTestKit {
         }
     }
 
+    /// Restarts the testkit node. The service instances are created anew and recover
+    /// their state from the storage.
+    pub fn restart(self) -> Self {
+        let rust_runtime = RustRuntimeBuilder::new()
+            .with_factory(Supervisor)
+            .with_migrating_factory(BtcAnchoringService);
+        Self {
+            inner: self.inner.stop().resume(rust_runtime),
+            anchoring_nodes: self.anchoring_nodes,
+        }
+    }
+
     /// Returns the actual anchoring configuration.
     pub fn actual_anchoring_config(&self) -> Config {
         get_anchoring_schema(&self.inner.snapshot()).actual_config()
@@ -182,9 +213,7 @@ impl AnchoringTestKit {
 
     /// Returns the latest anchoring transaction.
     pub fn last_anchoring_tx(&self) -> Option<btc::Transaction> {
-        get_anchoring_schema(&self.inner.snapshot())
-            .transactions_chain
-            .last()
+        get_anchoring_schema(&self.inner.snapshot()).last_anchoring_transaction()
     }
 
     /// Returns the proposal of the next anchoring transaction for the actual anchoring state.
@@ -202,41 +231,14 @@ impl AnchoringTestKit {
         &self,
         node: &TestNode,
     ) -> Result<Vec<Verified<AnyTx>>, btc::BuilderError> {
-        let service_keypair = node.service_keypair();
         let snapshot = self.inner.snapshot();
         let schema = get_anchoring_schema(&snapshot);
 
         let mut signatures = Vec::new();
         if let Some(p) = schema.actual_proposed_anchoring_transaction(snapshot.for_core()) {
             let (proposal, proposal_inputs) = p?;
-
             let actual_config = schema.actual_state().actual_config().clone();
-            let bitcoin_key = actual_config
-                .find_bitcoin_key(&service_keypair.public_key())
-                .unwrap()
-                .1;
-            let btc_private_key = self.anchoring_nodes.private_key(&bitcoin_key);
-
-            let redeem_script = actual_config.redeem_script();
-            let mut signer = p2wsh::InputSigner::new(redeem_script);
-            for (index, proposal_input) in proposal_inputs.iter().enumerate() {
-                let signature = signer
-                    .sign_input(
-                        TxInRef::new(proposal.as_ref(), index),
-                        proposal_input.as_ref(),
-                        &btc_private_key.0.key,
-                    )
-                    .unwrap();
-
-                signatures.push(service_keypair.sign_input(
-                    ANCHORING_INSTANCE_ID,
-                    SignInput {
-                        input: index as u32,
-                        input_signature: signature.into(),
-                        txid: proposal.id(),
-                    },
-                ));
-            }
+            signatures = self.sign_proposal(node, &actual_config, &proposal, &proposal_inputs);
         }
         Ok(signatures)
     }
@@ -244,9 +246,22 @@ impl AnchoringTestKit {
     /// Creates signatures for each input of the proposed anchoring transaction signed by all of
     /// anchoring nodes.
     pub fn create_signature_txs(&self) -> Vec<Vec<Verified<AnyTx>>> {
+        self.create_signature_txs_withholding(&[])
+    }
+
+    /// Creates signatures for each input of the proposed anchoring transaction signed by all of
+    /// anchoring nodes except the nodes with the given Bitcoin keys, which withhold their
+    /// signatures.
+    pub fn create_signature_txs_withholding(
+        &self,
+        withheld_keys: &[btc::PublicKey],
+    ) -> Vec<Vec<Verified<AnyTx>>> {
         let mut signatures = Vec::new();
 
         for anchoring_keys in self.actual_anchoring_config().anchoring_keys {
+            if withheld_keys.contains(&anchoring_keys.bitcoin_key) {
+                continue;
+            }
             let node = self
                 .find_node_by_service_key(anchoring_keys.service_key)
                 .unwrap();
@@ -256,6 +271,100 @@ impl AnchoringTestKit {
         signatures
     }
 
+    /// Returns the forged version of the actual anchoring proposal, which sends the anchoring
+    /// funds to the address of an attacker instead of the anchoring address.
+    pub fn forged_anchoring_proposal(&self) -> Option<(btc::Transaction, Vec<btc::Transaction>)> {
+        let (mut proposal, inputs) = self.anchoring_transaction_proposal()?;
+        let network = self.actual_anchoring_config().network;
+        let attacker_key = btc::gen_keypair(network).0;
+        proposal.0.output[0].script_pubkey =
+            bitcoin::Address::p2wpkh(&attacker_key.0, network).script_pubkey();
+        Some((proposal, inputs))
+    }
+
+    /// Creates signatures for each input of the forged anchoring proposal signed by the first
+    /// `nodes_count` anchoring nodes of the actual configuration, as if these nodes have
+    /// colluded to steal the anchoring funds. See `forged_anchoring_proposal`.
+    ///
+    /// The service rejects such signatures with the `UnexpectedProposalTxId` error.
+    pub fn create_forged_signature_txs(&self, nodes_count: usize) -> Vec<Vec<Verified<AnyTx>>> {
+        let (proposal, inputs) = match self.forged_anchoring_proposal() {
+            Some(forged) => forged,
+            None => return Vec::new(),
+        };
+        let actual_config = self.actual_anchoring_config();
+        actual_config
+            .anchoring_keys
+            .iter()
+            .take(nodes_count)
+            .map(|anchoring_keys| {
+                let node = self
+                    .find_node_by_service_key(anchoring_keys.service_key)
+                    .unwrap();
+                self.sign_proposal(node, &actual_config, &proposal, &inputs)
+            })
+            .collect()
+    }
+
+    /// Creates signatures for each input of the proposed anchoring transaction signed by the
+    /// specified node with its Bitcoin key and the redeem script of the given outdated
+    /// configuration, as if the node has missed the configuration change.
+    ///
+    /// The service rejects such signatures with the `InputVerificationFailed` error, or with
+    /// the `UnauthorizedAnchoringKey` one if the node has been removed from the anchoring nodes.
+    pub fn create_signature_tx_with_config(
+        &self,
+        node: &TestNode,
+        config: &Config,
+    ) -> Vec<Verified<AnyTx>> {
+        self.anchoring_transaction_proposal()
+            .map(|(proposal, inputs)| self.sign_proposal(node, config, &proposal, &inputs))
+            .unwrap_or_default()
+    }
+
+    /// Signs each input of the given transaction by the Bitcoin key of the node from
+    /// the given configuration.
+    fn sign_proposal(
+        &self,
+        node: &TestNode,
+        config: &Config,
+        proposal: &btc::Transaction,
+        proposal_inputs: &[btc::Transaction],
+    ) -> Vec<Verified<AnyTx>> {
+        let service_keypair = node.service_keypair();
+        let bitcoin_key = config
+            .find_bitcoin_key(&service_keypair.public_key())
+            .unwrap()
+            .1;
+        let btc_private_key = self.anchoring_nodes.private_key(&bitcoin_key);
+
+        let redeem_script = config.redeem_script();
+        let mut signer = p2wsh::InputSigner::new(redeem_script);
+        proposal_inputs
+            .iter()
+            .enumerate()
+            .map(|(index, proposal_input)| {
+                let signature = signer
+                    .sign_input(
+                        TxInRef::new(proposal.as_ref(), index),
+                        proposal_input.as_ref(),
+                        &btc_private_key.0.key,
+                    )
+                    .unwrap();
+
+                service_keypair.sign_input(
+                    ANCHORING_INSTANCE_ID,
+                    SignInput {
+                        input: index as u32,
+                        input_signature: signature.into(),
+                        txid: proposal.id(),
+                        sighash_type: btc::SigHashType::All,
+                    },
+                )
+            })
+            .collect()
+    }
+
     /// Creates the confirmation transactions with a funding transaction to the current address
     /// with a given amount of Satoshi.
     pub fn create_funding_confirmation_txs(
@@ -292,6 +401,86 @@ impl AnchoringTestKit {
             .collect()
     }
 
+    /// Creates the transactions reporting the given anchoring transaction location
+    /// from each anchoring node.
+    pub fn create_anchor_confirmation_txs(
+        &self,
+        confirmation: ConfirmAnchor,
+    ) -> Vec<Verified<AnyTx>> {
+        self.actual_anchoring_config()
+            .anchoring_keys
+            .into_iter()
+            .map(move |anchoring_keys| {
+                let node_keypair = self
+                    .find_node_by_service_key(anchoring_keys.service_key)
+                    .expect("Unable to find node by service key")
+                    .service_keypair();
+
+                node_keypair.confirm_anchor(ANCHORING_INSTANCE_ID, confirmation.clone())
+            })
+            .collect()
+    }
+
+    /// Creates the transactions approving broadcasting of the given special anchoring
+    /// transaction from each anchoring node.
+    pub fn create_special_tx_approval_txs(&self, txid: btc::Sha256d) -> Vec<Verified<AnyTx>> {
+        self.actual_anchoring_config()
+            .anchoring_keys
+            .into_iter()
+            .map(move |anchoring_keys| {
+                let node_keypair = self
+                    .find_node_by_service_key(anchoring_keys.service_key)
+                    .expect("Unable to find node by service key")
+                    .service_keypair();
+
+                node_keypair.approve_special_tx(ANCHORING_INSTANCE_ID, ApproveSpecialTx { txid })
+            })
+            .collect()
+    }
+
+    /// Creates the transactions reporting the given fee estimates from the anchoring nodes,
+    /// one estimate per node in order of the anchoring keys.
+    pub fn create_fee_estimate_txs(
+        &self,
+        fees: impl IntoIterator<Item = u64>,
+    ) -> Vec<Verified<AnyTx>> {
+        self.actual_anchoring_config()
+            .anchoring_keys
+            .into_iter()
+            .zip(fees)
+            .map(|(anchoring_keys, fee)| {
+                let node_keypair = self
+                    .find_node_by_service_key(anchoring_keys.service_key)
+                    .expect("Unable to find node by service key")
+                    .service_keypair();
+
+                node_keypair.report_fee_estimate(ANCHORING_INSTANCE_ID, ReportFeeEstimate { fee })
+            })
+            .collect()
+    }
+
+    /// Creates the transactions requesting the fee bumping of the given anchoring transaction
+    /// from the anchoring nodes, one fee rate per node in order of the anchoring keys.
+    pub fn create_cpfp_request_txs(
+        &self,
+        txid: btc::Sha256d,
+        fees: impl IntoIterator<Item = u64>,
+    ) -> Vec<Verified<AnyTx>> {
+        self.actual_anchoring_config()
+            .anchoring_keys
+            .into_iter()
+            .zip(fees)
+            .map(|(anchoring_keys, fee)| {
+                let node_keypair = self
+                    .find_node_by_service_key(anchoring_keys.service_key)
+                    .expect("Unable to find node by service key")
+                    .service_keypair();
+
+                node_keypair.request_cpfp(ANCHORING_INSTANCE_ID, RequestCpfp { txid, fee })
+            })
+            .collect()
+    }
+
     /// Creates configuration change transaction for simple supervisor.
     pub fn create_config_change_tx(&self, proposal: ConfigPropose) -> Verified<AnyTx> {
         let initiator_id = self.inner.network().us().validator_id().unwrap();
@@ -299,6 +488,30 @@ impl AnchoringTestKit {
         keypair.propose_config_change(SUPERVISOR_INSTANCE_ID, proposal)
     }
 
+    /// Creates attestation transactions for the Bitcoin keys of the given configuration
+    /// which are absent in the actual one.
+    pub fn create_key_attestation_txs(&self, config: &Config) -> Vec<Verified<AnyTx>> {
+        let actual_config = self.actual_anchoring_config();
+        config
+            .anchoring_keys
+            .iter()
+            .filter(|keys| !actual_config.anchoring_keys.contains(keys))
+            .map(|keys| {
+                let node_keypair = self
+                    .find_node_by_service_key(keys.service_key)
+                    .expect("Unable to find node by service key")
+                    .service_keypair();
+                let attestation = AttestKey::new(
+                    &keys.service_key,
+                    keys.bitcoin_key,
+                    &self.node_private_key(&keys.bitcoin_key),
+                );
+
+                node_keypair.attest_key(ANCHORING_INSTANCE_ID, attestation)
+            })
+            .collect()
+    }
+
     /// Adds a new auditor node to the testkit network and create Bitcoin keypair for it.
     pub fn add_node(&mut self) -> AnchoringKeys {
         let service_key = self
@@ -314,6 +527,8 @@ impl AnchoringTestKit {
         AnchoringKeys {
             bitcoin_key,
             service_key,
+            weight: AnchoringKeys::DEFAULT_WEIGHT,
+            derivation: None,
         }
     }
 
@@ -412,6 +627,153 @@ impl PublicApi for TestKitApiClient {
             .get("config")
             .await
     }
+
+    async fn funding_transaction_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> api::Result<FundingTransactionState> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&FundingTransactionQuery { txid })
+            .get("funds/status")
+            .await
+    }
+
+    async fn anchor_confirmation_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> api::Result<AnchorConfirmationState> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&AnchorStatusQuery { txid })
+            .get("anchor/status")
+            .await
+    }
+
+    async fn anchor_finality_proof(&self, txid: btc::Sha256d) -> api::Result<AnchorFinalityProof> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&AnchorStatusQuery { txid })
+            .get("anchor/finality-proof")
+            .await
+    }
+
+    async fn limits(&self) -> api::Result<ServiceLimits> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("limits")
+            .await
+    }
+
+    async fn state_hashes(&self) -> api::Result<SchemaHashes> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("debug/state-hashes")
+            .await
+    }
+
+    async fn finalization_incidents(
+        &self,
+        query: PageQuery,
+    ) -> api::Result<Page<FinalizationIncident>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&query)
+            .get("finalization-incidents")
+            .await
+    }
+
+    async fn rejected_signatures(
+        &self,
+        query: RejectedSignaturesQuery,
+    ) -> api::Result<Vec<RejectedSignature>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&query)
+            .get("rejected-signatures")
+            .await
+    }
+
+    async fn signature_conflicts(&self, query: PageQuery) -> api::Result<Page<SignatureConflict>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&query)
+            .get("signature-conflicts")
+            .await
+    }
+
+    async fn version(&self) -> api::Result<ServiceVersion> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("version")
+            .await
+    }
+
+    async fn anchoring_latency(
+        &self,
+        query: LatencyQuery,
+    ) -> api::Result<Option<AnchoringLatency>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&query)
+            .get("anchoring-latency")
+            .await
+    }
+
+    async fn transition_status(&self) -> api::Result<Option<TransitionStatus>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("transition-status")
+            .await
+    }
+
+    async fn config_diff(&self) -> api::Result<Option<ConfigDiff>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("config/diff")
+            .await
+    }
+
+    async fn config_history(&self) -> api::Result<Vec<HistoricalConfig>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("config/history")
+            .await
+    }
+
+    async fn chain_digest(&self, query: ChainDigestQuery) -> api::Result<AnchoringChainDigest> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&query)
+            .get("chain/digest")
+            .await
+    }
+
+    async fn transactions(&self, query: TransactionsQuery) -> api::Result<TransactionsPage> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&query)
+            .get("transactions")
+            .await
+    }
+
+    async fn coverage(&self, query: CoverageQuery) -> api::Result<Option<BlockCoverage>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&query)
+            .get("coverage")
+            .await
+    }
+
+    async fn status(&self) -> api::Result<AnchoringStatus> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("status")
+            .await
+    }
+
+    async fn schedule(&self) -> api::Result<Option<AnchoringSchedule>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("schedule")
+            .await
+    }
+
+    async fn verify_proof(&self, proof: ProofToVerify) -> api::Result<ProofVerdict> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&proof)
+            .post("verify-proof")
+            .await
+    }
+
+    async fn transaction_by_txid(&self, query: TxidQuery) -> api::Result<Option<AnchoredPayload>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&query)
+            .get("transaction-by-txid")
+            .await
+    }
 }
 
 #[async_trait]
@@ -421,14 +783,35 @@ impl PrivateApi for TestKitApiClient {
     async fn sign_input(&self, sign_input: SignInput) -> api::Result<Hash> {
         self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
             .query(&sign_input)
-            .post("sign-input")
+            .post("signing/sign-input")
             .await
     }
 
-    async fn add_funds(&self, transaction: btc::Transaction) -> api::Result<Hash> {
+    async fn sign_inputs(&self, sign_inputs: Vec<SignInput>) -> api::Result<Vec<Hash>> {
         self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
-            .query(&transaction)
-            .post("add-funds")
+            .query(&sign_inputs)
+            .post("signing/sign-inputs")
+            .await
+    }
+
+    async fn confirm_anchor(&self, confirmation: ConfirmAnchor) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&confirmation)
+            .post("signing/confirm-anchor")
+            .await
+    }
+
+    async fn report_fee_estimate(&self, estimate: ReportFeeEstimate) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&estimate)
+            .post("signing/report-fee-estimate")
+            .await
+    }
+
+    async fn request_cpfp(&self, request: RequestCpfp) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&request)
+            .post("signing/request-cpfp")
             .await
     }
 
@@ -438,6 +821,19 @@ impl PrivateApi for TestKitApiClient {
             .await
     }
 
+    async fn anchoring_proposal_psbt(&self) -> api::Result<Option<ProposalPsbt>> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("anchoring-proposal/psbt")
+            .await
+    }
+
+    async fn submit_psbt(&self, psbt: ProposalPsbt) -> api::Result<Vec<Hash>> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&psbt)
+            .post("signing/psbt")
+            .await
+    }
+
     async fn config(&self) -> api::Result<Config> {
         self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
             .get("config")
@@ -456,6 +852,90 @@ impl PrivateApi for TestKitApiClient {
             .get("transactions-count")
             .await
     }
+
+    async fn unspent_funding_transaction(&self) -> api::Result<Option<btc::Transaction>> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("funds/unspent")
+            .await
+    }
+
+    async fn funding_transaction_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> api::Result<FundingTransactionState> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&FundingTransactionQuery { txid })
+            .get("funds/status")
+            .await
+    }
+
+    async fn anchor_confirmation_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> api::Result<AnchorConfirmationState> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&AnchorStatusQuery { txid })
+            .get("anchor/status")
+            .await
+    }
+
+    async fn version(&self) -> api::Result<ServiceVersion> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("version")
+            .await
+    }
+
+    async fn signing_progress(&self) -> api::Result<Option<SigningProgress>> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("signing-progress")
+            .await
+    }
+
+    async fn special_tx_approval_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> api::Result<SpecialTxApprovalState> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&SpecialTxQuery { txid })
+            .get("special-tx/status")
+            .await
+    }
+}
+
+#[async_trait]
+impl ManagementApi for TestKitApiClient {
+    type Error = api::Error;
+
+    async fn add_funds(&self, transaction: btc::Transaction) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&transaction)
+            .post("management/add-funds")
+            .await
+    }
+
+    async fn attest_key(&self, attestation: AttestKey) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&attestation)
+            .post("management/attest-key")
+            .await
+    }
+
+    async fn recovery_bundle(
+        &self,
+        query: RecoveryBundleQuery,
+    ) -> api::Result<EncryptedRecoveryBundle> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&query)
+            .post("management/recovery-bundle")
+            .await
+    }
+
+    async fn approve_special_tx(&self, txid: btc::Sha256d) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&SpecialTxQuery { txid })
+            .post("management/approve-special-tx")
+            .await
+    }
 }
 
 /// Proof validation extension.
@@ -482,3 +962,27 @@ impl ValidateProof for TransactionProof {
         Ok(entry)
     }
 }
+
+impl ValidateProof for AnchorFinalityProof {
+    type Output = Option<(btc::Sha256d, Height)>;
+
+    fn validate(self, validator_keys: &[PublicKey]) -> anyhow::Result<Self::Output> {
+        let (_, index_hash) = self.index_proof.verify(validator_keys)?;
+        let checked_proof = self.finality_proof.check_against_hash(index_hash)?;
+        let entry = checked_proof
+            .entries()
+            .next()
+            .map(|(txid, height)| (*txid, Height(*height)));
+        Ok(entry)
+    }
+}
+
+impl ValidateProof for TransactionsPage {
+    type Output = Vec<(u64, btc::Transaction)>;
+
+    fn validate(self, validator_keys: &[PublicKey]) -> anyhow::Result<Self::Output> {
+        let (_, index_hash) = self.index_proof.verify(validator_keys)?;
+        let checked_proof = self.transactions_proof.check_against_hash(index_hash)?;
+        Ok(checked_proof.entries().to_vec())
+    }
+}
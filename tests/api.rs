@@ -11,19 +11,34 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use bitcoin::{consensus::encode, util::psbt::PartiallySignedTransaction};
 use btc_transaction_utils::{p2wsh, TxInRef};
-use exonum::helpers::Height;
+use exonum::{helpers::Height, runtime::ErrorMatch};
 use exonum_btc_anchoring::{
-    api::{AnchoringProposalState, PrivateApi, PublicApi},
-    blockchain::SignInput,
+    api::{
+        AnchorConfirmationState, AnchoringAbsence, AnchoringChainDigest, AnchoringProposalState, AnchoringSchedule, AnchoringStateKind,
+        ChainDigestQuery, CoverageQuery, FundingTransactionState, HeightRange, IndexQuery, LatencyQuery, ManagementApi, PageQuery,
+        PrivateApi, ProofToVerify, ProofVerdict, PublicApi, RecoveryBundleQuery, RejectedSignaturesQuery, SchemaHashes, ServiceVersion,
+        SpecialTxApprovalState, TransactionsQuery, TxidQuery, ValueChange,
+    },
+    blockchain::{
+        errors::Error, AnchoringLatency, ConfirmAnchor, NoProposalReason, ReportFeeEstimate, RequestCpfp,
+        SignInput, TransitionStep,
+    },
     btc,
+    config::{EmergencyBrake, FeeEstimation, RecoveryExport},
+    explorer::AnchoringExplorerExt,
+    psbt::ProposalPsbt,
+    recovery::RecoveryKey,
     test_helpers::{
         create_fake_funding_transaction, get_anchoring_schema, AnchoringTestKit, ValidateProof,
-        ANCHORING_INSTANCE_ID,
+        ANCHORING_INSTANCE_ID, ANCHORING_INSTANCE_NAME,
     },
 };
+use exonum_explorer::BlockchainExplorer;
+use exonum_merkledb::ObjectHash;
 use exonum_supervisor::ConfigPropose;
-use exonum_testkit::TestKitApi;
+use exonum_testkit::{ApiKind, TestKitApi};
 
 fn init_testkit() -> (AnchoringTestKit, TestKitApi) {
     let mut testkit = AnchoringTestKit::default();
@@ -56,6 +71,15 @@ async fn transaction_with_index(api: &TestKitApi, index: u64) -> Option<btc::Tra
     api.client().transaction_with_index(index).await.unwrap()
 }
 
+async fn funding_transaction_state(
+    api: &TestKitApi,
+    txid: btc::Sha256d,
+) -> FundingTransactionState {
+    PublicApi::funding_transaction_state(api.client(), txid)
+        .await
+        .unwrap()
+}
+
 #[tokio::test]
 async fn actual_address() {
     let (mut anchoring_testkit, anchoring_api) = init_testkit();
@@ -110,12 +134,14 @@ async fn following_address() {
     let following_address = new_cfg.anchoring_address();
 
     // Commit configuration with without last anchoring node.
-    anchoring_testkit.inner.create_block_with_transaction(
-        anchoring_testkit.create_config_change_tx(
-            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
-                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
-        ),
+    let attestation_txs = anchoring_testkit.create_key_attestation_txs(&new_cfg);
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg),
     );
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(attestation_txs.into_iter().chain(Some(config_change_tx)));
     anchoring_testkit.inner.create_block();
 
     assert_eq!(
@@ -167,9 +193,13 @@ async fn find_transaction_regular() {
         tx_chain.get(1).unwrap()
     );
     assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(1000)))
-            .await
-            .unwrap(),
+        find_transaction(
+            &anchoring_testkit,
+            &anchoring_api,
+            Some(anchoring_testkit.inner.height())
+        )
+        .await
+        .unwrap(),
         tx_chain.get(4).unwrap()
     );
     assert_eq!(
@@ -187,6 +217,334 @@ async fn find_transaction_regular() {
     }
 }
 
+#[tokio::test]
+async fn find_transaction_proof_of_absence() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit.inner.create_block();
+    let tip = anchoring_testkit.inner.height();
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+
+    // The latest block is not anchored yet, so the latest anchoring transaction is returned.
+    let proof = anchoring_api
+        .client()
+        .find_transaction(Some(tip))
+        .await
+        .unwrap();
+    assert_eq!(
+        proof
+            .verify_absence(ANCHORING_INSTANCE_NAME, &validator_keys, tip)
+            .unwrap(),
+        AnchoringAbsence {
+            block_height: tip,
+            chain_length: 1,
+            latest_anchored_height: Some(Height(0)),
+        }
+    );
+    // The anchored block is covered by the transaction.
+    assert!(proof
+        .verify_absence(ANCHORING_INSTANCE_NAME, &validator_keys, Height(0))
+        .is_err());
+    // The proof is bound to the anchoring instance.
+    assert!(proof
+        .verify_absence("other_anchoring", &validator_keys, tip)
+        .is_err());
+}
+
+#[tokio::test]
+async fn find_transaction_out_of_range() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let tip = anchoring_testkit.inner.height();
+    let e = anchoring_api
+        .client()
+        .find_transaction(Some(tip.next()))
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 422);
+    assert_eq!(
+        HeightRange::from_error(&e),
+        Some(HeightRange {
+            from: Height(0),
+            to: tip
+        })
+    );
+
+    // The latest committed block is still available.
+    assert!(anchoring_api
+        .client()
+        .find_transaction(Some(tip))
+        .await
+        .is_ok());
+}
+
+#[test]
+fn explorer_block_anchors() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    for i in 1..=2 {
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+    }
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let tx_chain = get_anchoring_schema(&snapshot).transactions_chain;
+    assert_eq!(tx_chain.len(), 2);
+
+    let anchor = snapshot
+        .block_anchor(ANCHORING_INSTANCE_NAME, Height(0))
+        .unwrap();
+    assert_eq!(anchor.anchor_index, 0);
+    assert_eq!(anchor.txid, tx_chain.get(0).unwrap().id());
+    assert_eq!(anchor.anchored_height, Height(0));
+    // No anchoring node has reported the confirmations yet.
+    assert!(!anchor.is_final());
+
+    let anchor = snapshot
+        .block_anchor(ANCHORING_INSTANCE_NAME, Height(1))
+        .unwrap();
+    assert_eq!(anchor.anchor_index, 1);
+    assert_eq!(anchor.anchored_height, Height(anchoring_interval));
+    // Blocks after the latest anchored one are not covered yet.
+    assert_eq!(
+        snapshot.block_anchor(ANCHORING_INSTANCE_NAME, Height(anchoring_interval + 1)),
+        None
+    );
+
+    let explorer = BlockchainExplorer::new(snapshot.as_ref());
+    let blocks = snapshot.annotate_blocks(ANCHORING_INSTANCE_NAME, explorer.blocks(Height(0)..));
+    assert_eq!(
+        blocks.len() as u64,
+        anchoring_testkit.inner.height().next().0
+    );
+    assert!(blocks.iter().all(
+        |block| block.anchor.is_some() == (block.block.height() <= Height(anchoring_interval))
+    ));
+}
+
+#[tokio::test]
+async fn find_transaction_finalized_at() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    let proof = anchoring_api.client().find_transaction(None).await.unwrap();
+    assert_eq!(proof.finalized_at, None);
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let finalization_height = anchoring_testkit.inner.height();
+    anchoring_testkit.inner.create_blocks_until(Height(10));
+
+    let proof = anchoring_api.client().find_transaction(None).await.unwrap();
+    assert_eq!(proof.finalized_at, Some(finalization_height));
+}
+
+#[tokio::test]
+async fn immutable_transaction() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let query = IndexQuery { index: 0 };
+
+    let e = anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&query)
+        .get::<btc::Transaction>("transactions/immutable")
+        .await
+        .expect_err("Anchoring transaction should be absent");
+    assert_eq!(e.http_code.as_u16(), 404);
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tx = anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&query)
+        .get::<btc::Transaction>("transactions/immutable")
+        .await
+        .unwrap();
+    assert_eq!(Some(tx), transaction_with_index(&anchoring_api, 0).await);
+}
+
+#[tokio::test]
+async fn find_transaction_inclusion() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let proof = anchoring_api.client().find_transaction(None).await.unwrap();
+    assert_eq!(proof.inclusion, None);
+
+    let confirmation = ConfirmAnchor {
+        txid: tx.id(),
+        block_hash: btc::Sha256d::new([1; 32]),
+        block_height: 100,
+        confirmations: 1,
+    };
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_anchor_confirmation_txs(confirmation.clone()),
+    );
+
+    let proof = anchoring_api.client().find_transaction(None).await.unwrap();
+    assert_eq!(proof.inclusion, Some(confirmation.inclusion()));
+}
+
+#[tokio::test]
+async fn anchor_confirmation_state() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    let config = anchoring_testkit.actual_anchoring_config();
+
+    let state = PublicApi::anchor_confirmation_state(anchoring_api.client(), tx.id())
+        .await
+        .unwrap();
+    assert_eq!(
+        state,
+        AnchorConfirmationState::Pending {
+            inclusion: None,
+            votes: 0,
+            required: config.byzantine_quorum() as u64,
+        }
+    );
+
+    let confirmation = ConfirmAnchor {
+        txid: tx.id(),
+        block_hash: btc::Sha256d::new([1; 32]),
+        block_height: 100,
+        confirmations: config.finality_confirmations,
+    };
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_anchor_confirmation_txs(confirmation.clone()),
+    );
+    let confirmed_at = anchoring_testkit.inner.height();
+
+    let state = PublicApi::anchor_confirmation_state(anchoring_api.client(), tx.id())
+        .await
+        .unwrap();
+    assert_eq!(
+        state,
+        AnchorConfirmationState::Final {
+            inclusion: confirmation.inclusion(),
+            confirmed_at,
+        }
+    );
+    let proof = anchoring_api.client().find_transaction(None).await.unwrap();
+    assert_eq!(proof.finally_confirmed_at, Some(confirmed_at));
+
+    let state =
+        PublicApi::anchor_confirmation_state(anchoring_api.client(), btc::Sha256d::new([1; 32]))
+            .await
+            .unwrap();
+    assert_eq!(state, AnchorConfirmationState::Unknown);
+}
+
+#[tokio::test]
+async fn anchor_finality_proof() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    let config = anchoring_testkit.actual_anchoring_config();
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+
+    // The proof of absence is returned for the transaction which is not finally confirmed.
+    let proof = anchoring_api
+        .client()
+        .anchor_finality_proof(tx.id())
+        .await
+        .unwrap();
+    assert_eq!(proof.validate(&validator_keys).unwrap(), None);
+
+    let confirmation = ConfirmAnchor {
+        txid: tx.id(),
+        block_hash: btc::Sha256d::new([1; 32]),
+        block_height: 100,
+        confirmations: config.finality_confirmations,
+    };
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_anchor_confirmation_txs(confirmation),
+    );
+    let confirmed_at = anchoring_testkit.inner.height();
+
+    let proof = anchoring_api
+        .client()
+        .anchor_finality_proof(tx.id())
+        .await
+        .unwrap();
+    assert_eq!(
+        proof.validate(&validator_keys).unwrap(),
+        Some((tx.id(), confirmed_at))
+    );
+}
+
+#[tokio::test]
+async fn confirm_anchor_err_unknown_transaction() {
+    let (_, anchoring_api) = init_testkit();
+
+    let e = anchoring_api
+        .client()
+        .confirm_anchor(ConfirmAnchor {
+            txid: btc::Sha256d::new([1; 32]),
+            block_hash: btc::Sha256d::new([2; 32]),
+            block_height: 100,
+            confirmations: 1,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 400);
+}
+
 // Check come edge cases in the find_transaction api method.
 #[tokio::test]
 async fn find_transaction_configuration_change() {
@@ -214,12 +572,14 @@ async fn find_transaction_configuration_change() {
     new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
 
     // Commit configuration with without last anchoring node.
-    anchoring_testkit.inner.create_block_with_transaction(
-        anchoring_testkit.create_config_change_tx(
-            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
-                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
-        ),
+    let attestation_txs = anchoring_testkit.create_key_attestation_txs(&new_cfg);
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg),
     );
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(attestation_txs.into_iter().chain(Some(config_change_tx)));
     anchoring_testkit.inner.create_block();
 
     // Transit to the new address.
@@ -319,54 +679,415 @@ async fn actual_config() {
 }
 
 #[tokio::test]
-async fn anchoring_proposal_ok() {
+async fn service_limits() {
     let (anchoring_testkit, anchoring_api) = init_testkit();
-    let proposal = anchoring_testkit.anchoring_transaction_proposal().unwrap();
 
+    let cfg = anchoring_testkit.actual_anchoring_config();
+    let limits = anchoring_api.client().limits().await.unwrap();
+    assert_eq!(limits.payload_version, 1);
+    assert_eq!(limits.max_anchoring_nodes, 20);
+    assert_eq!(limits.anchoring_nodes, cfg.anchoring_keys.len());
+    assert_eq!(limits.quorum, cfg.byzantine_quorum());
+    assert!(limits.min_transaction_fee <= cfg.transaction_fee);
+    assert!(limits.supported_networks.contains(&cfg.network));
+    assert_eq!(limits.max_funding_tx_weight, cfg.max_funding_tx_weight);
+    assert_eq!(limits.max_funding_tx_outputs, cfg.max_funding_tx_outputs);
+}
+
+#[tokio::test]
+async fn state_hashes() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    let hashes = anchoring_api.client().state_hashes().await.unwrap();
     assert_eq!(
-        anchoring_api.client().anchoring_proposal().await.unwrap(),
-        AnchoringProposalState::Available {
-            transaction: proposal.0,
-            inputs: proposal.1,
-        }
+        hashes.transactions_chain,
+        get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+            .transactions_chain
+            .object_hash()
+    );
+    assert!(hashes.actual_config.is_some());
+    assert_eq!(hashes.following_config, None);
+
+    // Signatures change only the corresponding index.
+    let signatures = anchoring_testkit.create_signature_txs();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures[0].clone());
+    let new_hashes = anchoring_api.client().state_hashes().await.unwrap();
+    assert_ne!(
+        new_hashes.transaction_signatures,
+        hashes.transaction_signatures
+    );
+    assert_eq!(
+        SchemaHashes {
+            transaction_signatures: hashes.transaction_signatures,
+            ..new_hashes.clone()
+        },
+        hashes
+    );
+
+    // Finalization changes the anchoring chain.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures.into_iter().skip(1).flatten());
+    let last_hashes = anchoring_api.client().state_hashes().await.unwrap();
+    assert_ne!(
+        last_hashes.transactions_chain,
+        new_hashes.transactions_chain
     );
 }
 
 #[tokio::test]
-async fn anchoring_proposal_none() {
+async fn anchoring_latency() {
     let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let config = anchoring_testkit.actual_anchoring_config();
+    let latency = anchoring_api
+        .client()
+        .anchoring_latency(LatencyQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(latency, None);
 
-    // Establish anchoring transactions chain.
     anchoring_testkit.inner.create_block_with_transactions(
         anchoring_testkit
             .create_signature_txs()
             .into_iter()
             .flatten(),
     );
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    let anchored_height = tx.anchoring_payload().unwrap().block_height;
+    anchoring_testkit.inner.create_blocks_until(Height(10));
 
-    assert_eq!(
-        anchoring_api.client().anchoring_proposal().await.unwrap(),
-        AnchoringProposalState::None
+    let confirmation = ConfirmAnchor {
+        txid: tx.id(),
+        block_hash: btc::Sha256d::new([1; 32]),
+        block_height: 100,
+        confirmations: config.finality_confirmations,
+    };
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_anchor_confirmation_txs(confirmation),
     );
-}
+    let expected = anchoring_testkit.inner.height().0 - anchored_height.0;
 
-#[tokio::test]
-async fn anchoring_proposal_err_without_initial_funds() {
-    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+    let latency = anchoring_api
+        .client()
+        .anchoring_latency(LatencyQuery::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        latency,
+        AnchoringLatency {
+            samples: 1,
+            p50: expected,
+            p90: expected,
+            p99: expected,
+            max: expected,
+        }
+    );
 
-    let api = anchoring_testkit.inner.api();
-    let state = api.client().anchoring_proposal().await.unwrap();
-    assert_eq!(state, AnchoringProposalState::NoInitialFunds);
+    let e = anchoring_api
+        .client()
+        .anchoring_latency(LatencyQuery { window: Some(0) })
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 400);
 }
 
 #[tokio::test]
-async fn anchoring_proposal_err_insufficient_funds() {
-    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
-
-    // Add an initial funding transaction to enable anchoring.
-    anchoring_testkit
-        .inner
-        .create_block_with_transactions(anchoring_testkit.create_funding_confirmation_txs(20).0);
+async fn transition_status() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let config = anchoring_testkit.actual_anchoring_config();
+
+    // Finally confirm the first anchor, so the duration of the transition can be estimated.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    anchoring_testkit.inner.create_blocks_until(Height(10));
+    let confirmation = ConfirmAnchor {
+        txid: tx.id(),
+        block_hash: btc::Sha256d::new([1; 32]),
+        block_height: 100,
+        confirmations: config.finality_confirmations,
+    };
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_anchor_confirmation_txs(confirmation),
+    );
+    let latency = anchoring_api
+        .client()
+        .anchoring_latency(LatencyQuery::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        anchoring_api.client().transition_status().await.unwrap(),
+        None
+    );
+
+    // Add an anchoring node, which changes the anchoring address.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    let following_address = new_cfg.anchoring_address();
+    let attestation_txs = anchoring_testkit.create_key_attestation_txs(&new_cfg);
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(attestation_txs.into_iter().chain(Some(config_change_tx)));
+    anchoring_testkit.inner.create_block();
+
+    let status = anchoring_api
+        .client()
+        .transition_status()
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal = anchoring_testkit
+        .anchoring_transaction_proposal()
+        .unwrap()
+        .0;
+    assert_eq!(status.following_address, following_address);
+    assert_eq!(
+        status.step,
+        TransitionStep::AwaitingSignatures {
+            txid: proposal.id(),
+            signatures: 0,
+            required: config.byzantine_quorum() as u64,
+        }
+    );
+    let elapsed = anchoring_testkit.inner.height().0 - status.started_at.0;
+    assert_eq!(
+        status.estimated_blocks_remaining,
+        Some(latency.p50.saturating_sub(elapsed))
+    );
+
+    // Finalize the transition transaction.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let transition_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(transition_tx.id(), proposal.id());
+    let new_config = anchoring_testkit.actual_anchoring_config();
+    let step = anchoring_api
+        .client()
+        .transition_status()
+        .await
+        .unwrap()
+        .unwrap()
+        .step;
+    assert_eq!(
+        step,
+        TransitionStep::AwaitingConfirmations {
+            txid: transition_tx.id(),
+            votes: 0,
+            required: new_config.byzantine_quorum() as u64,
+        }
+    );
+
+    // The transition is over once its transaction is finally confirmed.
+    let confirmation = ConfirmAnchor {
+        txid: transition_tx.id(),
+        block_hash: btc::Sha256d::new([2; 32]),
+        block_height: 101,
+        confirmations: new_config.finality_confirmations,
+    };
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_anchor_confirmation_txs(confirmation),
+    );
+    assert_eq!(
+        anchoring_api.client().transition_status().await.unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn config_diff() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    assert_eq!(anchoring_api.client().config_diff().await.unwrap(), None);
+
+    // Add an anchoring node and change the anchoring interval.
+    let config = anchoring_testkit.actual_anchoring_config();
+    let mut new_cfg = config.clone();
+    let new_keys = anchoring_testkit.add_node();
+    new_cfg.anchoring_keys.push(new_keys.clone());
+    new_cfg.anchoring_interval = config.anchoring_interval * 2;
+    let attestation_txs = anchoring_testkit.create_key_attestation_txs(&new_cfg);
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(attestation_txs.into_iter().chain(Some(config_change_tx)));
+
+    let diff = anchoring_api
+        .client()
+        .config_diff()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(diff.added_keys, vec![new_keys]);
+    assert!(diff.removed_keys.is_empty());
+    assert_eq!(
+        diff.signature_quorum,
+        Some(ValueChange {
+            actual: config.signature_quorum(),
+            following: new_cfg.signature_quorum(),
+        })
+    );
+    assert_eq!(
+        diff.anchoring_interval,
+        Some(ValueChange {
+            actual: config.anchoring_interval,
+            following: new_cfg.anchoring_interval,
+        })
+    );
+    assert_eq!(diff.transaction_fee, None);
+    assert_eq!(diff.fee_payer_address, None);
+    assert_eq!(
+        diff.anchoring_address,
+        Some(ValueChange {
+            actual: config.anchoring_address(),
+            following: new_cfg.anchoring_address(),
+        })
+    );
+
+    // The diff disappears once the following configuration becomes actual.
+    anchoring_testkit.inner.create_block();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    assert_eq!(anchoring_api.client().config_diff().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn signing_progress() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let config = anchoring_testkit.actual_anchoring_config();
+    let proposal = anchoring_testkit
+        .anchoring_transaction_proposal()
+        .unwrap()
+        .0;
+
+    // Only one of the anchoring nodes signs the proposal.
+    let signatures = anchoring_testkit.create_signature_txs();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures[0].clone());
+    let progress = anchoring_api
+        .client()
+        .signing_progress()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(progress.txid, proposal.id());
+    assert_eq!(progress.anchored_height, Height(0));
+    assert_eq!(progress.waiting_blocks, anchoring_testkit.inner.height().0);
+    assert_eq!(progress.input_signatures, vec![1; proposal.0.input.len()]);
+    assert!(!progress.is_stalled);
+
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(config.signature_timeout));
+    let progress = anchoring_api
+        .client()
+        .signing_progress()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(progress.waiting_blocks, config.signature_timeout);
+    assert!(progress.is_stalled);
+
+    // Once the proposal is finalized, the progress relates to the next one.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures.into_iter().skip(1).flatten());
+    let progress = anchoring_api
+        .client()
+        .signing_progress()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_ne!(progress.txid, proposal.id());
+    assert_eq!(progress.anchored_height, Height(config.anchoring_interval));
+    assert_eq!(progress.input_signatures, vec![0]);
+}
+
+#[tokio::test]
+async fn anchoring_proposal_ok() {
+    let (anchoring_testkit, anchoring_api) = init_testkit();
+    let proposal = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+
+    assert_eq!(
+        anchoring_api.client().anchoring_proposal().await.unwrap(),
+        AnchoringProposalState::Available {
+            transaction: proposal.0,
+            inputs: proposal.1,
+        }
+    );
+}
+
+#[tokio::test]
+async fn anchoring_proposal_none() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    // Establish anchoring transactions chain.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    assert_eq!(
+        anchoring_api.client().anchoring_proposal().await.unwrap(),
+        AnchoringProposalState::None {
+            reason: NoProposalReason::AwaitingAnchoringHeight {
+                next_anchoring_height: Height(anchoring_interval),
+                latest_height: anchoring_testkit.inner.height(),
+            }
+        }
+    );
+}
+
+#[tokio::test]
+async fn anchoring_proposal_err_without_initial_funds() {
+    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+
+    let api = anchoring_testkit.inner.api();
+    let state = api.client().anchoring_proposal().await.unwrap();
+    assert_eq!(state, AnchoringProposalState::NoInitialFunds);
+}
+
+#[tokio::test]
+async fn anchoring_proposal_err_insufficient_funds() {
+    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+
+    // Add an initial funding transaction to enable anchoring.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(anchoring_testkit.create_funding_confirmation_txs(20).0);
 
     let api = anchoring_testkit.inner.api();
     let state = api.client().anchoring_proposal().await.unwrap();
@@ -418,6 +1139,7 @@ async fn sign_input() {
             input: 0,
             input_signature: signature.into(),
             txid: proposal.id(),
+            sighash_type: btc::SigHashType::All,
         })
         .await
         .unwrap();
@@ -429,6 +1151,132 @@ async fn sign_input() {
         .expect("Transaction should be successful");
 }
 
+#[tokio::test]
+async fn sign_inputs() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    let config = anchoring_testkit.actual_anchoring_config();
+    let bitcoin_public_key = config
+        .find_bitcoin_key(&anchoring_testkit.inner.us().service_keypair().public_key())
+        .unwrap()
+        .1;
+    let bitcoin_private_key = anchoring_testkit.node_private_key(&bitcoin_public_key);
+    let (proposal, proposal_inputs) = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+    let signature = p2wsh::InputSigner::new(config.redeem_script())
+        .sign_input(
+            TxInRef::new(proposal.as_ref(), 0),
+            proposal_inputs[0].as_ref(),
+            &bitcoin_private_key.0.key,
+        )
+        .unwrap();
+    let sign_input = SignInput {
+        input: 0,
+        input_signature: signature.into(),
+        txid: proposal.id(),
+        sighash_type: btc::SigHashType::All,
+    };
+
+    // The whole batch is rejected if any of the signatures is incorrect.
+    let invalid_sign_input = SignInput {
+        input: 10,
+        ..sign_input.clone()
+    };
+    anchoring_api
+        .client()
+        .sign_inputs(vec![sign_input.clone(), invalid_sign_input])
+        .await
+        .unwrap_err();
+    anchoring_api
+        .client()
+        .sign_inputs(Vec::new())
+        .await
+        .unwrap_err();
+
+    let tx_hashes = anchoring_api
+        .client()
+        .sign_inputs(vec![sign_input])
+        .await
+        .unwrap();
+    assert_eq!(tx_hashes.len(), 1);
+    anchoring_testkit
+        .inner
+        .create_block_with_tx_hashes(&tx_hashes)[0]
+        .status()
+        .expect("Transaction should be successful");
+}
+
+#[tokio::test]
+async fn proposal_psbt() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    let config = anchoring_testkit.actual_anchoring_config();
+    let bitcoin_public_key = config
+        .find_bitcoin_key(&anchoring_testkit.inner.us().service_keypair().public_key())
+        .unwrap()
+        .1;
+    let bitcoin_private_key = anchoring_testkit.node_private_key(&bitcoin_public_key);
+    let (proposal, proposal_inputs) = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+
+    let proposal_psbt = anchoring_api
+        .client()
+        .anchoring_proposal_psbt()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(proposal_psbt.txid, proposal.id());
+
+    // The PSBT without signatures is rejected.
+    let e = anchoring_api
+        .client()
+        .submit_psbt(proposal_psbt.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 400);
+
+    // Sign the PSBT inputs like an external signer does.
+    let mut psbt: PartiallySignedTransaction =
+        encode::deserialize(&hex::decode(&proposal_psbt.psbt).unwrap()).unwrap();
+    let mut signer = p2wsh::InputSigner::new(config.redeem_script());
+    for (index, input) in psbt.inputs.iter_mut().enumerate() {
+        assert_eq!(
+            input.witness_script.as_ref(),
+            Some(config.redeem_script().as_ref())
+        );
+        let spent_output = input.witness_utxo.as_ref().unwrap();
+        assert_eq!(
+            spent_output,
+            &proposal_inputs[index].0.output[proposal.0.input[index].previous_output.vout as usize]
+        );
+
+        let signature = signer
+            .sign_input(
+                TxInRef::new(proposal.as_ref(), index),
+                proposal_inputs[index].as_ref(),
+                &bitcoin_private_key.0.key,
+            )
+            .unwrap();
+        input
+            .partial_sigs
+            .insert(bitcoin_public_key.0, signature.into());
+    }
+
+    let tx_hashes = anchoring_api
+        .client()
+        .submit_psbt(ProposalPsbt {
+            txid: proposal.id(),
+            psbt: hex::encode(encode::serialize(&psbt)),
+        })
+        .await
+        .unwrap();
+    assert_eq!(tx_hashes.len(), proposal_inputs.len());
+    for tx in anchoring_testkit
+        .inner
+        .create_block_with_tx_hashes(&tx_hashes)
+    {
+        tx.status().expect("Transaction should be successful");
+    }
+}
+
 #[tokio::test]
 async fn add_funds_ok() {
     let anchoring_interval = 5;
@@ -451,6 +1299,29 @@ async fn add_funds_ok() {
         .expect("Transaction should be successful");
 }
 
+#[tokio::test]
+async fn add_funds_deprecated_path() {
+    let mut anchoring_testkit = AnchoringTestKit::new(1, 5);
+    let anchoring_api = anchoring_testkit.inner.api();
+
+    let config = anchoring_testkit.actual_anchoring_config();
+    let funding_transaction = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+    // The endpoint is available at the path used by the sync utilities of the previous versions.
+    let tx_hash = anchoring_api
+        .private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&funding_transaction)
+        .post::<exonum::crypto::Hash>("add-funds")
+        .await
+        .unwrap();
+
+    anchoring_testkit
+        .inner
+        .create_block_with_tx_hashes(&[tx_hash])[0]
+        .status()
+        .expect("Transaction should be successful");
+}
+
 #[tokio::test]
 async fn add_funds_err_already_used() {
     let anchoring_interval = 5;
@@ -493,3 +1364,951 @@ async fn add_funds_err_unsuitable() {
         .await
         .expect_err("Add funds must fail");
 }
+
+#[tokio::test]
+async fn funding_transaction_state_changes() {
+    let anchoring_interval = 5;
+    let mut anchoring_testkit = AnchoringTestKit::new(4, anchoring_interval);
+    let anchoring_api = anchoring_testkit.inner.api();
+
+    let (txs, funding_transaction) = anchoring_testkit.create_funding_confirmation_txs(2000);
+    let txid = funding_transaction.id();
+
+    assert_eq!(
+        funding_transaction_state(&anchoring_api, txid).await,
+        FundingTransactionState::Unknown
+    );
+    // Confirm funding transaction by the one of anchoring nodes.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(txs[0..1].to_vec());
+    assert_eq!(
+        funding_transaction_state(&anchoring_api, txid).await,
+        FundingTransactionState::Pending {
+            confirmations: 1,
+            required: 3,
+        }
+    );
+    // Confirm funding transaction by the rest of anchoring nodes.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(txs[1..].to_vec());
+    assert_eq!(
+        funding_transaction_state(&anchoring_api, txid).await,
+        FundingTransactionState::Spendable
+    );
+    // Spend funding transaction by the anchoring transaction.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    assert_eq!(
+        funding_transaction_state(&anchoring_api, txid).await,
+        FundingTransactionState::Spent
+    );
+}
+
+#[tokio::test]
+async fn funding_transaction_state_rejected() {
+    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+    let anchoring_api = anchoring_testkit.inner.api();
+
+    // Confirm two funding transactions of the same wallet in one block.
+    let (first_txs, first_funding_transaction) =
+        anchoring_testkit.create_funding_confirmation_txs(2000);
+    let (second_txs, second_funding_transaction) =
+        anchoring_testkit.create_funding_confirmation_txs(2400);
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(first_txs.iter().cloned().chain(second_txs));
+    let height = anchoring_testkit.inner.height().0;
+    for txid in &[first_funding_transaction.id(), second_funding_transaction.id()] {
+        assert_eq!(
+            funding_transaction_state(&anchoring_api, *txid).await,
+            FundingTransactionState::Rejected { height }
+        );
+    }
+
+    // Apply the first transaction by the confirmation of a single node.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(first_txs.into_iter().take(1));
+    assert_eq!(
+        funding_transaction_state(&anchoring_api, first_funding_transaction.id()).await,
+        FundingTransactionState::Spendable
+    );
+    assert_eq!(
+        funding_transaction_state(&anchoring_api, second_funding_transaction.id()).await,
+        FundingTransactionState::Rejected { height }
+    );
+}
+
+#[tokio::test]
+async fn finalization_incidents() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    // Regular finalization doesn't produce incidents.
+    let page = anchoring_api
+        .client()
+        .finalization_incidents(PageQuery::default())
+        .await
+        .unwrap();
+    assert!(page.items.is_empty());
+    assert_eq!(page.next, None);
+
+    let e = anchoring_api
+        .client()
+        .finalization_incidents(PageQuery {
+            limit: Some(0),
+            ..PageQuery::default()
+        })
+        .await
+        .expect_err("Page request with zero limit should fail");
+    assert_eq!(e.http_code.as_u16(), 400);
+}
+
+#[tokio::test]
+async fn rejected_signatures() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let config = anchoring_testkit.actual_anchoring_config();
+    let forged_txid = anchoring_testkit.forged_anchoring_proposal().unwrap().0.id();
+    let forged_signatures = anchoring_testkit
+        .create_forged_signature_txs(1)
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(forged_signatures.clone());
+    let rejected_at = anchoring_testkit.inner.height();
+    // The following blocks are scanned too.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let signatures = anchoring_api
+        .client()
+        .rejected_signatures(RejectedSignaturesQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(signatures.len(), forged_signatures.len());
+    for (signature, tx) in signatures.iter().zip(&forged_signatures) {
+        assert_eq!(signature.height, rejected_at);
+        assert_eq!(signature.tx_hash, tx.object_hash());
+        assert_eq!(signature.author, config.anchoring_keys[0].service_key);
+        assert_eq!(signature.txid, forged_txid);
+        assert_eq!(
+            signature.error,
+            ErrorMatch::from_fail(&Error::UnexpectedProposalTxId)
+                .for_service(ANCHORING_INSTANCE_ID)
+        );
+    }
+
+    // The blocks after the given height are not scanned.
+    let signatures = anchoring_api
+        .client()
+        .rejected_signatures(RejectedSignaturesQuery {
+            to_height: Some(rejected_at.previous()),
+            blocks: None,
+        })
+        .await
+        .unwrap();
+    assert!(signatures.is_empty());
+
+    let e = anchoring_api
+        .client()
+        .rejected_signatures(RejectedSignaturesQuery {
+            to_height: None,
+            blocks: Some(RejectedSignaturesQuery::MAX_BLOCKS + 1),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 400);
+}
+
+#[tokio::test]
+async fn signature_conflicts() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    assert!(anchoring_testkit.last_anchoring_tx().is_some());
+
+    // Regular signing doesn't produce conflicts.
+    let page = anchoring_api
+        .client()
+        .signature_conflicts(PageQuery::default())
+        .await
+        .unwrap();
+    assert!(page.items.is_empty());
+    assert_eq!(page.next, None);
+}
+
+#[tokio::test]
+async fn chain_digest() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let digest = anchoring_api
+        .client()
+        .chain_digest(ChainDigestQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(digest, AnchoringChainDigest::default());
+
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    for i in 1..=2 {
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+    }
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let transactions = get_anchoring_schema(&snapshot)
+        .transactions_chain
+        .iter()
+        .collect::<Vec<_>>();
+    assert_eq!(transactions.len(), 2);
+
+    let digest = anchoring_api
+        .client()
+        .chain_digest(ChainDigestQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(digest.length, 2);
+    assert_eq!(digest, AnchoringChainDigest::new(&transactions));
+    // Digest of the chain prefix is the same as the one of the shorter chain.
+    let prefix_digest = anchoring_api
+        .client()
+        .chain_digest(ChainDigestQuery { index: Some(0) })
+        .await
+        .unwrap();
+    assert_eq!(prefix_digest, AnchoringChainDigest::new(&transactions[..1]));
+    assert_eq!(prefix_digest.append(&transactions[1]), digest);
+
+    let e = anchoring_api
+        .client()
+        .chain_digest(ChainDigestQuery { index: Some(2) })
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 404);
+}
+
+#[tokio::test]
+async fn transactions_pages() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    for i in 1..=3 {
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+    }
+    let transactions = get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+        .transactions_chain
+        .iter()
+        .enumerate()
+        .map(|(index, tx)| (index as u64, tx))
+        .collect::<Vec<_>>();
+    assert_eq!(transactions.len(), 3);
+
+    // Walk through the whole chain page by page.
+    let mut query = TransactionsQuery {
+        from: None,
+        count: Some(2),
+    };
+    let page = anchoring_api.client().transactions(query).await.unwrap();
+    assert_eq!(page.next, Some(2));
+    assert_eq!(page.validate(&validator_keys).unwrap(), transactions[..2]);
+
+    query.from = Some(2);
+    let page = anchoring_api.client().transactions(query).await.unwrap();
+    assert_eq!(page.next, None);
+    assert_eq!(page.validate(&validator_keys).unwrap(), transactions[2..]);
+
+    // The page beyond the end of the chain is empty.
+    query.from = Some(10);
+    let page = anchoring_api.client().transactions(query).await.unwrap();
+    assert_eq!(page.next, None);
+    assert!(page.validate(&validator_keys).unwrap().is_empty());
+
+    // Invalid number of transactions.
+    let e = anchoring_api
+        .client()
+        .transactions(TransactionsQuery {
+            from: None,
+            count: Some(0),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 400);
+}
+
+#[tokio::test]
+async fn block_coverage() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let mut query = CoverageQuery {
+        height: Height(1),
+        bitcoin_height: Some(105),
+    };
+    let coverage = anchoring_api.client().coverage(query).await.unwrap().unwrap();
+    assert_eq!(coverage.anchor_index, 1);
+    assert_eq!(coverage.txid, tx.id());
+    assert_eq!(coverage.first_height, Height(1));
+    assert_eq!(coverage.last_height, Height(anchoring_interval));
+    // The location of the anchoring transaction is unknown yet.
+    assert_eq!(coverage.confirmations, None);
+
+    let confirmation = ConfirmAnchor {
+        txid: tx.id(),
+        block_hash: btc::Sha256d::new([1; 32]),
+        block_height: 100,
+        confirmations: 1,
+    };
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_anchor_confirmation_txs(confirmation.clone()),
+    );
+    let coverage = anchoring_api.client().coverage(query).await.unwrap().unwrap();
+    assert_eq!(coverage.confirmations, Some(6));
+
+    // The genesis block is covered by the first anchor.
+    query.height = Height(0);
+    let coverage = anchoring_api.client().coverage(query).await.unwrap().unwrap();
+    assert_eq!(coverage.anchor_index, 0);
+    assert_eq!(coverage.first_height, Height(0));
+    assert_eq!(coverage.last_height, Height(0));
+
+    // The block has not been anchored yet.
+    query.height = Height(anchoring_interval + 1);
+    assert_eq!(anchoring_api.client().coverage(query).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn status() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let config = anchoring_testkit.actual_anchoring_config();
+    let funding_value = get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+        .unspent_funding_transaction()
+        .unwrap()
+        .find_out(&config.anchoring_out_script())
+        .unwrap()
+        .1
+        .value;
+
+    let status = anchoring_api.client().status().await.unwrap();
+    assert_eq!(status.state, AnchoringStateKind::Regular);
+    assert_eq!(status.next_anchoring_height, Height(0));
+    assert_eq!(status.latest_anchored_height, None);
+    assert_eq!(status.available_funds, funding_value);
+    let progress = status.signing_progress.unwrap();
+    assert_eq!(progress.anchored_height, Height(0));
+    assert!(progress.input_signatures.iter().all(|&count| count == 0));
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let status = anchoring_api.client().status().await.unwrap();
+    assert_eq!(status.state, AnchoringStateKind::Regular);
+    assert_eq!(
+        status.next_anchoring_height,
+        Height(config.anchoring_interval)
+    );
+    assert_eq!(status.latest_anchored_height, Some(Height(0)));
+    assert_eq!(status.available_funds, tx.0.output[0].value);
+    // The next proposal is not available until the anchoring height.
+    assert_eq!(status.signing_progress, None);
+}
+
+#[tokio::test]
+async fn schedule() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // The genesis block is already committed, so it is anchored right away.
+    let schedule = anchoring_api.client().schedule().await.unwrap().unwrap();
+    assert_eq!(
+        schedule,
+        AnchoringSchedule {
+            state: AnchoringStateKind::Regular,
+            next_anchoring_height: Height(0),
+            latest_height: anchoring_testkit.inner.height(),
+            blocks_remaining: 0,
+        }
+    );
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tip = anchoring_testkit.inner.height();
+    let schedule = anchoring_api.client().schedule().await.unwrap().unwrap();
+    assert_eq!(schedule.next_anchoring_height, Height(anchoring_interval));
+    assert_eq!(schedule.latest_height, tip);
+    assert_eq!(schedule.blocks_remaining, anchoring_interval - tip.0);
+}
+
+#[tokio::test]
+async fn verify_proof() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let first_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    let first_proof = anchoring_api.client().find_transaction(None).await.unwrap();
+    let first_height = first_proof.index_proof.block_proof.block.height;
+    let stale_transaction_proof = first_proof.transaction_proof.clone();
+
+    let verdict = anchoring_api
+        .client()
+        .verify_proof(ProofToVerify::Transaction(first_proof))
+        .await
+        .unwrap();
+    match verdict {
+        ProofVerdict::Valid {
+            block_height,
+            anchor_index,
+            txid,
+            ..
+        } => {
+            assert_eq!(block_height, first_height);
+            assert_eq!(anchor_index, Some(0));
+            assert_eq!(txid, Some(first_tx.id()));
+        }
+        other => panic!("Unexpected verdict {:?}", other),
+    }
+
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    // The block proof is verified as well.
+    let proof = anchoring_api.client().find_transaction(None).await.unwrap();
+    let verdict = anchoring_api
+        .client()
+        .verify_proof(ProofToVerify::Block(proof.index_proof.block_proof))
+        .await
+        .unwrap();
+    match verdict {
+        ProofVerdict::Valid {
+            block_height,
+            anchor_index,
+            ..
+        } => {
+            assert_eq!(block_height, anchoring_testkit.inner.height());
+            assert_eq!(anchor_index, None);
+        }
+        other => panic!("Unexpected verdict {:?}", other),
+    }
+
+    // The transaction proof doesn't match the anchoring chain at the later block.
+    let mut proof = anchoring_api.client().find_transaction(None).await.unwrap();
+    proof.transaction_proof = stale_transaction_proof;
+    let verdict = anchoring_api
+        .client()
+        .verify_proof(ProofToVerify::Transaction(proof))
+        .await
+        .unwrap();
+    match verdict {
+        ProofVerdict::Invalid { .. } => {}
+        other => panic!("Unexpected verdict {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn config_history() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let config = anchoring_testkit.actual_anchoring_config();
+
+    let history = anchoring_api.client().config_history().await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].config, config);
+    assert_eq!(history[0].actual_from, Some(Height(0)));
+
+    // The configuration with the same anchoring address becomes actual immediately.
+    let mut new_cfg = config.clone();
+    new_cfg.transaction_fee = config.transaction_fee * 2;
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(config_change_tx);
+    let applied_at = anchoring_testkit.inner.height();
+
+    let history = anchoring_api.client().config_history().await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].config, config);
+    assert_eq!(history[1].config, new_cfg);
+    assert_eq!(history[1].actual_from, Some(applied_at));
+}
+
+#[tokio::test]
+async fn transaction_by_txid() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let anchored = anchoring_api
+        .client()
+        .transaction_by_txid(TxidQuery { txid: tx.id() })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(anchored.anchor_index, 1);
+    assert_eq!(anchored.payload, tx.anchoring_payload().unwrap());
+    assert_eq!(anchored.payload.block_height, Height(anchoring_interval));
+    assert_eq!(
+        anchored.proof.transaction_proof.check().unwrap().entries(),
+        &[(1, tx.clone())]
+    );
+
+    // Unknown transactions are not found.
+    let unknown = anchoring_api
+        .client()
+        .transaction_by_txid(TxidQuery {
+            txid: btc::Sha256d::new([1; 32]),
+        })
+        .await
+        .unwrap();
+    assert!(unknown.is_none());
+}
+
+#[tokio::test]
+async fn version() {
+    let (_, anchoring_api) = init_testkit();
+
+    let public_version = PublicApi::version(anchoring_api.client()).await.unwrap();
+    let private_version = PrivateApi::version(anchoring_api.client()).await.unwrap();
+    assert_eq!(public_version, ServiceVersion::current());
+    assert_eq!(private_version, public_version);
+    assert_eq!(public_version.crate_version, env!("CARGO_PKG_VERSION"));
+    ServiceVersion::current()
+        .check_compatibility(&public_version)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn recovery_bundle() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let key = RecoveryKey::random();
+    let query = RecoveryBundleQuery {
+        export_index: 0,
+        key: key.clone(),
+    };
+
+    // The export is not authorized yet.
+    let e = anchoring_api
+        .client()
+        .recovery_bundle(query.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 404);
+
+    // Establish anchoring transactions chain.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    // Authorize the export by the supervisor.
+    let cfg = anchoring_testkit.actual_anchoring_config();
+    let mut new_cfg = cfg.clone();
+    new_cfg.recovery_export = Some(RecoveryExport {
+        escrow_key_hash: key.hash(),
+    });
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(config_change_tx);
+    anchoring_testkit.inner.create_block();
+    // The authorization is not kept in the configuration.
+    assert_eq!(anchoring_testkit.actual_anchoring_config(), cfg);
+
+    let encrypted_bundle = anchoring_api.client().recovery_bundle(query).await.unwrap();
+    let bundle = key.decrypt(&encrypted_bundle).unwrap();
+    assert_eq!(bundle.export.escrow_key_hash, key.hash());
+    assert_eq!(bundle.wallets.len(), 1);
+    assert_eq!(bundle.wallets[0].config, cfg);
+    assert_eq!(bundle.wallets[0].address, cfg.anchoring_address());
+    assert_eq!(bundle.wallets[0].quorum, cfg.byzantine_quorum());
+    assert_eq!(
+        bundle.latest_anchoring_transaction,
+        anchoring_testkit.last_anchoring_tx()
+    );
+    assert!(!bundle.instructions.is_empty());
+
+    // Another key is not authorized to export the bundle.
+    let e = anchoring_api
+        .client()
+        .recovery_bundle(RecoveryBundleQuery {
+            export_index: 0,
+            key: RecoveryKey::random(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 400);
+}
+
+#[tokio::test]
+async fn special_tx_approvals() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    // Enable the emergency brake for any anchoring transaction with a non-zero fee.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.emergency_brake = Some(EmergencyBrake {
+        fee_threshold: 1,
+        required_approvals: 2,
+    });
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(config_change_tx);
+    anchoring_testkit.inner.create_block();
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let txid = anchoring_testkit.last_anchoring_tx().unwrap().id();
+    assert_eq!(
+        anchoring_api
+            .client()
+            .special_tx_approval_state(txid)
+            .await
+            .unwrap(),
+        SpecialTxApprovalState::Pending {
+            approvers: vec![],
+            required: 2,
+        }
+    );
+
+    // Only the known special transactions can be approved.
+    let e = anchoring_api
+        .client()
+        .approve_special_tx(btc::Sha256d::new([1; 32]))
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 400);
+
+    let approvals = anchoring_testkit.create_special_tx_approval_txs(txid);
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(approvals[0].clone());
+    let state = anchoring_api
+        .client()
+        .special_tx_approval_state(txid)
+        .await
+        .unwrap();
+    assert!(!state.is_broadcast_allowed());
+
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(approvals[1].clone());
+    let state = anchoring_api
+        .client()
+        .special_tx_approval_state(txid)
+        .await
+        .unwrap();
+    assert!(state.is_broadcast_allowed());
+    if let SpecialTxApprovalState::Approved { approvers } = state {
+        assert_eq!(approvers.len(), 2);
+    } else {
+        panic!("Unexpected approval state: {:?}", state);
+    }
+}
+
+#[tokio::test]
+async fn fee_estimation() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    // Estimates can't be reported while the fee estimation is disabled.
+    let e = anchoring_api
+        .client()
+        .report_fee_estimate(ReportFeeEstimate { fee: 20 })
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 400);
+
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.fee_estimation = Some(FeeEstimation {
+        confirmation_target: 6,
+        min_fee: 5,
+        max_fee: 50,
+    });
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(config_change_tx);
+    anchoring_testkit.inner.create_block();
+
+    let e = anchoring_api
+        .client()
+        .report_fee_estimate(ReportFeeEstimate { fee: 0 })
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 400);
+
+    // The outlier estimate doesn't affect the median.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_fee_estimate_txs(vec![20, 30, 40, 1_000]),
+    );
+    let schema_fee = |testkit: &AnchoringTestKit| {
+        let snapshot = testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        (
+            schema.estimated_fee(&new_cfg),
+            schema.anchoring_fee(&new_cfg),
+        )
+    };
+    // The fee is not changed until the anchoring transaction is finalized.
+    assert_eq!(
+        schema_fee(&anchoring_testkit),
+        (Some(40), new_cfg.transaction_fee)
+    );
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    assert_eq!(schema_fee(&anchoring_testkit), (Some(40), 40));
+
+    // Estimates are limited by the caps of the configuration.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_fee_estimate_txs(vec![100, 200, 300, 400]),
+    );
+    assert_eq!(schema_fee(&anchoring_testkit), (Some(50), 40));
+}
+
+#[tokio::test]
+async fn cpfp() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    // The fee bumping can't be requested while it is disabled.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    let txid = create_fake_funding_transaction(&new_cfg.anchoring_address(), 10_000).id();
+    let e = anchoring_api
+        .client()
+        .request_cpfp(RequestCpfp { txid, fee: 20 })
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 400);
+
+    new_cfg.max_cpfp_fee = 100;
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(config_change_tx);
+    anchoring_testkit.inner.create_block();
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let parent = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    // A quorum of the anchoring nodes requests the fee bumping.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_cpfp_request_txs(parent.id(), vec![20, 40, 60]),
+    );
+    let agreement = {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        get_anchoring_schema(&snapshot).cpfp_agreement().unwrap()
+    };
+    assert_eq!(agreement.txid, parent.id());
+    assert_eq!(agreement.fee, 40);
+
+    // The next proposal spends the parent immediately and pays for it.
+    let proposal = anchoring_api.client().anchoring_proposal().await.unwrap();
+    let child = match proposal {
+        AnchoringProposalState::Available { transaction, .. } => transaction,
+        other => panic!("Unexpected proposal state: {:?}", other),
+    };
+    assert_eq!(child.prev_tx_id(), parent.id());
+    assert_eq!(
+        child.anchoring_payload().unwrap().block_height,
+        Height(agreement.anchoring_height)
+    );
+    let child_fee = parent.0.output[0].value - child.0.output[0].value;
+    assert!(child_fee > agreement.fee * child.vsize() as u64);
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.transactions_chain.len(), 2);
+    assert_eq!(schema.cpfp_agreement(), None);
+}
+
+#[cfg(feature = "juniper")]
+#[tokio::test]
+async fn graphql_chain_and_participation() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let query = r#"{
+        chain(first: 1) {
+            totalCount
+            nodes { index txid finalizedAt signers }
+            pageInfo { endCursor hasNextPage }
+        }
+        participation { transactions nodes { nodeIndex signed } }
+        config { byzantineQuorum }
+    }"#;
+    let response = anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&serde_json::json!({ "query": query }))
+        .post::<serde_json::Value>("graphql")
+        .await
+        .unwrap();
+    assert!(response.get("errors").is_none(), "{}", response);
+
+    let data = &response["data"];
+    assert_eq!(data["chain"]["totalCount"], "1");
+    assert_eq!(data["chain"]["pageInfo"]["hasNextPage"], false);
+    let entry = &data["chain"]["nodes"][0];
+    assert_eq!(entry["index"], "0");
+    assert_eq!(entry["txid"], tx.id().to_string());
+    assert!(entry["finalizedAt"].is_string());
+
+    let signers = entry["signers"].as_array().unwrap();
+    let quorum = data["config"]["byzantineQuorum"].as_u64().unwrap();
+    assert!(signers.len() as u64 >= quorum);
+    assert_eq!(data["participation"]["transactions"], 1);
+    let nodes = data["participation"]["nodes"].as_array().unwrap();
+    assert_eq!(nodes.len(), signers.len());
+    assert!(nodes.iter().all(|node| node["signed"] == 1));
+
+    // Invalid cursors are reported as GraphQL errors.
+    let response = anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&serde_json::json!({ "query": r#"{ chain(after: "x") { totalCount } }"# }))
+        .post::<serde_json::Value>("graphql")
+        .await
+        .unwrap();
+    assert!(response["errors"].is_array());
+}
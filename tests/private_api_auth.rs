@@ -0,0 +1,70 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use exonum_btc_anchoring::{
+    api::{check_api_env, ManagementApi, PrivateApi, PublicApi},
+    sync::{MANAGEMENT_API_KEY_ENV, SIGNING_API_KEY_ENV, SIGNING_API_TOKEN_ENV},
+    test_helpers::{create_fake_funding_transaction, AnchoringTestKit},
+};
+
+use std::env;
+
+// The authentication is configured by the environment variables of the node process, so this
+// test is kept in a separate binary.
+#[tokio::test]
+async fn private_api_rejects_unauthenticated_requests() {
+    env::set_var(SIGNING_API_TOKEN_ENV, "token");
+    env::set_var(SIGNING_API_KEY_ENV, hex::encode(b"secret"));
+    // The management scope has invalid credentials.
+    env::set_var(MANAGEMENT_API_KEY_ENV, "not a hex string");
+    assert!(check_api_env().is_err());
+
+    // The node is started anyway, but the management scope rejects all requests.
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_api = anchoring_testkit.inner.api();
+
+    let e = PrivateApi::transactions_count(anchoring_api.client())
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 401);
+    let e = anchoring_api
+        .client()
+        .sign_inputs(Vec::new())
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 401);
+
+    let funding_transaction = create_fake_funding_transaction(
+        &anchoring_testkit
+            .actual_anchoring_config()
+            .anchoring_address(),
+        10_000,
+    );
+    let e = anchoring_api
+        .client()
+        .add_funds(funding_transaction)
+        .await
+        .unwrap_err();
+    assert_eq!(e.http_code.as_u16(), 500);
+
+    // Public API is not affected.
+    assert_eq!(
+        PublicApi::actual_address(anchoring_api.client())
+            .await
+            .unwrap(),
+        anchoring_testkit
+            .actual_anchoring_config()
+            .anchoring_address()
+    );
+}
@@ -20,13 +20,20 @@ use exonum::{
     messages::{AnyTx, Verified},
 };
 use exonum_btc_anchoring::{
-    api::{AnchoringChainLength, AnchoringProposalState, PrivateApi},
-    blockchain::{AddFunds, BtcAnchoringInterface, SignInput},
+    api::{
+        AnchorConfirmationState, AnchoringChainLength, AnchoringProposalState,
+        FundingTransactionState, PrivateApi, ServiceVersion, SpecialTxApprovalState,
+    },
+    blockchain::{
+        BtcAnchoringInterface, ConfirmAnchor, ReportFeeEstimate, RequestCpfp, SignInput,
+        SigningProgress,
+    },
     btc,
     config::Config,
+    psbt::ProposalPsbt,
     sync::{
-        AnchoringChainUpdateTask, BitcoinRelay, ChainUpdateError, SyncWithBitcoinError,
-        SyncWithBitcoinTask, TransactionStatus,
+        AnchoringChainUpdateTask, BitcoinRelay, ChainUpdateError, KeyPool, RetryPolicy,
+        SyncWithBitcoinError, SyncWithBitcoinTask, TransactionStatus,
     },
     test_helpers::{get_anchoring_schema, AnchoringTestKit, ANCHORING_INSTANCE_ID},
 };
@@ -36,6 +43,7 @@ use exonum_testkit::TestKitApiClient;
 use std::{
     collections::VecDeque,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
 #[derive(Debug, Clone)]
@@ -48,6 +56,8 @@ enum FakeRelayRequest {
         request: btc::Sha256d,
         response: TransactionStatus,
     },
+    /// Transient failure of any request.
+    Error,
 }
 
 impl FakeRelayRequest {
@@ -84,12 +94,17 @@ impl FakeBitcoinRelay {
         self.requests.lock().unwrap().extend(requests)
     }
 
-    fn dequeue_request(&self) -> FakeRelayRequest {
-        self.requests
+    fn dequeue_request(&self) -> anyhow::Result<FakeRelayRequest> {
+        let request = self
+            .requests
             .lock()
             .unwrap()
             .pop_front()
-            .expect("Expected relay request")
+            .expect("Expected relay request");
+        if let FakeRelayRequest::Error = request {
+            anyhow::bail!("Bitcoin node is unavailable");
+        }
+        Ok(request)
     }
 }
 
@@ -113,13 +128,13 @@ impl BitcoinRelay for FakeBitcoinRelay {
         &self,
         transaction: &btc::Transaction,
     ) -> Result<btc::Sha256d, Self::Error> {
-        let (expected_request, response) = self.dequeue_request().into_send_transaction();
+        let (expected_request, response) = self.dequeue_request()?.into_send_transaction();
         assert_eq!(&expected_request, transaction, "Unexpected data in request");
         Ok(response)
     }
 
     async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
-        let (expected_request, response) = self.dequeue_request().into_transaction_status();
+        let (expected_request, response) = self.dequeue_request()?.into_transaction_status();
         assert_eq!(expected_request, id, "Unexpected data in request");
         Ok(response)
     }
@@ -175,10 +190,36 @@ impl PrivateApi for FakePrivateApi {
         Ok(hash)
     }
 
-    async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error> {
+    async fn sign_inputs(&self, sign_inputs: Vec<SignInput>) -> Result<Vec<Hash>, Self::Error> {
+        let mut hashes = Vec::with_capacity(sign_inputs.len());
+        for sign_input in sign_inputs {
+            hashes.push(self.sign_input(sign_input).await?);
+        }
+        Ok(hashes)
+    }
+
+    async fn confirm_anchor(&self, confirmation: ConfirmAnchor) -> Result<Hash, Self::Error> {
+        let signed_tx = self
+            .service_keypair
+            .confirm_anchor(ANCHORING_INSTANCE_ID, confirmation);
+        let hash = signed_tx.object_hash();
+        self.send(signed_tx).await;
+        Ok(hash)
+    }
+
+    async fn report_fee_estimate(&self, estimate: ReportFeeEstimate) -> Result<Hash, Self::Error> {
+        let signed_tx = self
+            .service_keypair
+            .report_fee_estimate(ANCHORING_INSTANCE_ID, estimate);
+        let hash = signed_tx.object_hash();
+        self.send(signed_tx).await;
+        Ok(hash)
+    }
+
+    async fn request_cpfp(&self, request: RequestCpfp) -> Result<Hash, Self::Error> {
         let signed_tx = self
             .service_keypair
-            .add_funds(ANCHORING_INSTANCE_ID, AddFunds { transaction });
+            .request_cpfp(ANCHORING_INSTANCE_ID, request);
         let hash = signed_tx.object_hash();
         self.send(signed_tx).await;
         Ok(hash)
@@ -188,6 +229,14 @@ impl PrivateApi for FakePrivateApi {
         self.client.anchoring_proposal().await
     }
 
+    async fn anchoring_proposal_psbt(&self) -> Result<Option<ProposalPsbt>, Self::Error> {
+        self.client.anchoring_proposal_psbt().await
+    }
+
+    async fn submit_psbt(&self, psbt: ProposalPsbt) -> Result<Vec<Hash>, Self::Error> {
+        self.client.submit_psbt(psbt).await
+    }
+
     async fn config(&self) -> Result<Config, Self::Error> {
         self.client.config().await
     }
@@ -202,6 +251,43 @@ impl PrivateApi for FakePrivateApi {
     async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error> {
         self.client.transactions_count().await
     }
+
+    async fn unspent_funding_transaction(&self) -> Result<Option<btc::Transaction>, Self::Error> {
+        self.client.unspent_funding_transaction().await
+    }
+
+    async fn funding_transaction_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<FundingTransactionState, Self::Error> {
+        PrivateApi::funding_transaction_state(&self.client, txid).await
+    }
+
+    async fn anchor_confirmation_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<AnchorConfirmationState, Self::Error> {
+        PrivateApi::anchor_confirmation_state(&self.client, txid).await
+    }
+
+    async fn version(&self) -> Result<ServiceVersion, Self::Error> {
+        PrivateApi::version(&self.client).await
+    }
+
+    async fn signing_progress(&self) -> Result<Option<SigningProgress>, Self::Error> {
+        self.client.signing_progress().await
+    }
+
+    async fn special_tx_approval_state(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<SpecialTxApprovalState, Self::Error> {
+        self.client.special_tx_approval_state(txid).await
+    }
+}
+
+fn key_pool(testkit: &AnchoringTestKit) -> KeyPool {
+    KeyPool::new(testkit.anchoring_keypairs()).unwrap()
 }
 
 fn anchoring_transaction_payload(testkit: &AnchoringTestKit, index: u64) -> Option<btc::Payload> {
@@ -227,7 +313,7 @@ async fn chain_updater_normal() {
             let private_api =
                 FakePrivateApi::for_anchoring_node(&testkit, api.client().clone(), &keypair.0);
 
-            AnchoringChainUpdateTask::new(vec![keypair], private_api)
+            AnchoringChainUpdateTask::new(KeyPool::new(vec![keypair]).unwrap(), private_api)
                 .process()
                 .await
                 .unwrap();
@@ -253,7 +339,7 @@ async fn chain_updater_no_initial_funds() {
         .create_blocks_until(Height(anchoring_interval));
     // Try to perform anchoring chain update.
     let api = testkit.inner.api();
-    let e = AnchoringChainUpdateTask::new(testkit.anchoring_keypairs(), api.client().clone())
+    let e = AnchoringChainUpdateTask::new(key_pool(&testkit), api.client().clone())
         .process()
         .await
         .unwrap_err();
@@ -280,7 +366,7 @@ async fn chain_updater_insufficient_funds() {
         .create_blocks_until(Height(anchoring_interval));
     // Try to perform anchoring chain update.
     let api = testkit.inner.api();
-    let e = AnchoringChainUpdateTask::new(testkit.anchoring_keypairs(), api.client().clone())
+    let e = AnchoringChainUpdateTask::new(key_pool(&testkit), api.client().clone())
         .process()
         .await
         .unwrap_err();
@@ -421,3 +507,49 @@ async fn sync_with_bitcoin_err_unconfirmed_funding_tx() {
         e => panic!("Unexpected error occurred: {:?}", e),
     }
 }
+
+#[tokio::test]
+async fn sync_with_bitcoin_retry_transient_errors() {
+    let mut testkit = AnchoringTestKit::default();
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let snapshot = testkit.inner.snapshot();
+    let tx_chain = get_anchoring_schema(&snapshot).transactions_chain;
+
+    let fake_relay = FakeBitcoinRelay::default();
+    let api = testkit.inner.api();
+    // Without the retry policy the transient error is returned to the caller.
+    let sync = SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone());
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::Error]);
+    let e = sync.process(Some(0)).await.unwrap_err();
+    assert!(e.is_retryable());
+    // The checkpoint keeps the given index.
+    assert_eq!(sync.checkpoint().latest_synced_tx_index, Some(0));
+
+    let retry_policy = RetryPolicy {
+        max_retries: 2,
+        initial_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(10),
+    };
+    let sync = SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone())
+        .with_retry_policy(retry_policy);
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::Error,
+        FakeRelayRequest::Error,
+        FakeRelayRequest::TransactionStatus {
+            request: tx_chain.get(0).unwrap().id(),
+            response: TransactionStatus::Unknown,
+        },
+        FakeRelayRequest::SendTransaction {
+            request: tx_chain.get(0).unwrap(),
+            response: tx_chain.get(0).unwrap().id(),
+        },
+    ]);
+    assert_eq!(sync.process(Some(0)).await.unwrap(), Some(0));
+
+    // The number of retries is limited.
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::Error; 3]);
+    let e = sync.process(Some(0)).await.unwrap_err();
+    assert!(e.is_retryable());
+}
@@ -12,15 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use bitcoin::secp256k1::Signature;
 use exonum::helpers::Height;
 use exonum::{
     messages::{AnyTx, Verified},
     runtime::{ErrorMatch, SnapshotExt},
 };
 use exonum_btc_anchoring::{
-    blockchain::{errors::Error, BtcAnchoringInterface, SignInput},
+    blockchain::{
+        data_layout::TxOutputId, errors::Error, AnchorInclusion, BtcAnchoringInterface,
+        ConfirmAnchor, NoProposalReason, SignInput,
+    },
     btc::{self, BuilderError},
-    config::Config,
+    config::{CheckpointFilter, Config, FeeBudget, FinalAnchor},
     test_helpers::{
         create_fake_funding_transaction, get_anchoring_schema, AnchoringTestKit,
         ANCHORING_INSTANCE_ID,
@@ -28,6 +32,7 @@ use exonum_btc_anchoring::{
 };
 use exonum_crypto::KeyPair;
 use exonum_explorer::CommittedTransaction;
+use exonum_merkledb::BinaryValue;
 use exonum_supervisor::ConfigPropose;
 
 fn assert_tx_error(tx: &CommittedTransaction, e: ErrorMatch) {
@@ -79,12 +84,14 @@ where
     config_change_predicate(&mut anchoring_testkit, &mut new_cfg);
 
     // Commit configuration with without last anchoring node.
-    anchoring_testkit.inner.create_block_with_transaction(
-        anchoring_testkit.create_config_change_tx(
-            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
-                .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
-        ),
+    let attestation_txs = anchoring_testkit.create_key_attestation_txs(&new_cfg);
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
     );
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(attestation_txs.into_iter().chain(Some(config_change_tx)));
 
     // Extract a previous anchoring transaction from the proposal.
     let (anchoring_tx_proposal, previous_anchoring_tx) = anchoring_testkit
@@ -206,6 +213,41 @@ fn simple() {
     assert!(tx1_meta.1.block_height == Height(anchoring_interval));
 }
 
+#[test]
+fn finalization_is_deterministic() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let signatures = anchoring_testkit
+        .create_signature_txs()
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    // Finalize the proposal with the signatures in the original order.
+    anchoring_testkit.inner.checkpoint();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures.clone());
+    let block_height = anchoring_testkit.inner.height();
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let finalized_at = get_anchoring_schema(&snapshot).finalization_height(&tx.id());
+    assert_eq!(finalized_at, Some(block_height));
+    anchoring_testkit.inner.rollback();
+
+    // Finalize the same proposal with the signatures in the reverse order.
+    assert!(anchoring_testkit.last_anchoring_tx().is_none());
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures.into_iter().rev());
+    let other_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    let snapshot = anchoring_testkit.inner.snapshot();
+    assert_eq!(tx.to_string(), other_tx.to_string());
+    assert_eq!(
+        get_anchoring_schema(&snapshot).finalization_height(&other_tx.id()),
+        finalized_at
+    );
+}
+
 #[test]
 fn additional_funding() {
     let mut anchoring_testkit = AnchoringTestKit::default();
@@ -304,6 +346,49 @@ fn err_spent_funding() {
     );
 }
 
+#[test]
+fn err_anchoring_transaction_as_funding() {
+    let anchoring_interval = 5;
+    let mut anchoring_testkit = AnchoringTestKit::new(4, anchoring_interval);
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let first_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let second_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(
+        get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+            .spending_transaction(&TxOutputId::new(first_tx.id(), 0)),
+        Some(second_tx.id())
+    );
+
+    // Neither the spent output of the anchoring chain nor the unspent one can be reused
+    // as the funds.
+    for tx in vec![first_tx, second_tx] {
+        let block = anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_funding_confirmation_txs_with(tx)
+                .into_iter()
+                .take(1),
+        );
+        assert_tx_error(
+            &block[0],
+            ErrorMatch::from_fail(&Error::AlreadyUsedFundingTx),
+        );
+    }
+}
+
 #[test]
 fn insufficient_funds() {
     let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
@@ -373,7 +458,7 @@ fn no_anchoring_proposal() {
         .create_block_with_transactions(leftover_signatures);
     assert_tx_error(
         &block[0],
-        ErrorMatch::from_fail(&Error::UnexpectedProposalTxId),
+        ErrorMatch::from_fail(&Error::NoAnchoringProposal),
     );
 }
 
@@ -420,6 +505,72 @@ fn unexpected_anchoring_proposal() {
     );
 }
 
+#[test]
+fn late_signature_for_transition_transaction() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // Establish anchoring transactions chain.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+
+    // Remove the last anchoring node to change the anchoring address.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.anchoring_keys.pop();
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(config_change_tx);
+
+    // Finalize the transition transaction without the signatures of the first node.
+    let mut signatures = anchoring_testkit.create_signature_txs();
+    let leftover_signatures = signatures.remove(0);
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures.into_iter().flatten());
+    assert_eq!(anchoring_testkit.actual_anchoring_config(), new_cfg);
+
+    let transition_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    let proposal = anchoring_testkit
+        .anchoring_transaction_proposal()
+        .unwrap()
+        .0;
+    assert_eq!(proposal.prev_tx_id(), transition_tx.id());
+
+    // The late signatures for the transition transaction should be accepted,
+    // but they should not affect the anchoring chain.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(leftover_signatures)
+        .transactions
+        .iter()
+        .try_for_each(|tx| tx.status())
+        .expect("Each transaction should be successful.");
+    assert_eq!(
+        anchoring_testkit.last_anchoring_tx().unwrap(),
+        transition_tx
+    );
+    assert_eq!(
+        anchoring_testkit
+            .anchoring_transaction_proposal()
+            .unwrap()
+            .0,
+        proposal
+    );
+}
+
 #[test]
 fn add_anchoring_node() {
     test_anchoring_config_change(|anchoring_testkit, cfg| {
@@ -441,6 +592,87 @@ fn change_anchoring_node_without_funds() {
     });
 }
 
+#[test]
+fn byzantine_nodes_sign_forged_proposal() {
+    let mut testkit = AnchoringTestKit::default();
+    let forged_signatures = testkit.create_forged_signature_txs(3);
+    assert_eq!(forged_signatures.len(), 3);
+
+    let block = testkit
+        .inner
+        .create_block_with_transactions(forged_signatures.into_iter().flatten());
+    for tx in block.transactions.iter() {
+        assert_tx_error(tx, ErrorMatch::from_fail(&Error::UnexpectedProposalTxId));
+    }
+    assert!(testkit.last_anchoring_tx().is_none());
+
+    // Honest signatures are still accepted.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    assert!(testkit.last_anchoring_tx().is_some());
+}
+
+#[test]
+fn byzantine_nodes_withhold_signatures() {
+    let mut testkit = AnchoringTestKit::default();
+    let withheld_keys = testkit
+        .actual_anchoring_config()
+        .anchoring_keys
+        .iter()
+        .take(2)
+        .map(|keys| keys.bitcoin_key)
+        .collect::<Vec<_>>();
+
+    // Two of four nodes are not enough for the quorum.
+    testkit.inner.create_block_with_transactions(
+        testkit
+            .create_signature_txs_withholding(&withheld_keys)
+            .into_iter()
+            .flatten(),
+    );
+    assert!(testkit.last_anchoring_tx().is_none());
+    assert!(testkit.anchoring_transaction_proposal().is_some());
+
+    // One of the nodes stops withholding its signatures.
+    testkit.inner.create_block_with_transactions(
+        testkit
+            .create_signature_txs_withholding(&withheld_keys[1..])
+            .into_iter()
+            .flatten(),
+    );
+    assert!(testkit.last_anchoring_tx().is_some());
+}
+
+#[test]
+fn node_signs_with_outdated_config() {
+    let mut old_cfg = None;
+    let mut testkit = test_anchoring_config_change(|anchoring_testkit, cfg| {
+        old_cfg = Some(cfg.clone());
+        cfg.anchoring_keys[0].bitcoin_key = anchoring_testkit.gen_bitcoin_key();
+    });
+    let old_cfg = old_cfg.unwrap();
+
+    // The node has missed the change of its Bitcoin key.
+    let service_key = old_cfg.anchoring_keys[0].service_key;
+    let node = testkit
+        .inner
+        .network()
+        .nodes()
+        .iter()
+        .find(|node| node.service_keypair().public_key() == service_key)
+        .unwrap()
+        .clone();
+    let outdated_signatures = testkit.create_signature_tx_with_config(&node, &old_cfg);
+    assert!(!outdated_signatures.is_empty());
+    let block = testkit
+        .inner
+        .create_block_with_transactions(outdated_signatures);
+    for tx in block.transactions.iter() {
+        assert_tx_error(tx, ErrorMatch::from_fail(&Error::InputVerificationFailed));
+    }
+}
+
 #[test]
 fn add_anchoring_node_insufficient_funds() {
     let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
@@ -472,12 +704,14 @@ fn add_anchoring_node_insufficient_funds() {
     new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
 
     // Commit configuration with without last anchoring node.
-    anchoring_testkit.inner.create_block_with_transaction(
-        anchoring_testkit.create_config_change_tx(
-            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
-                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
-        ),
+    let attestation_txs = anchoring_testkit.create_key_attestation_txs(&new_cfg);
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg),
     );
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(attestation_txs.into_iter().chain(Some(config_change_tx)));
     anchoring_testkit.inner.create_block();
 
     // Ensure that the anchoring transaction proposal is unsuitable.
@@ -528,6 +762,26 @@ fn funding_tx_err_unsuitable() {
     );
 }
 
+#[test]
+fn funding_tx_err_oversized() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let config = anchoring_testkit.actual_anchoring_config();
+
+    // Create funding transaction with too many outputs.
+    let mut funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+    let extra_output = funding_tx.0.output[0].clone();
+    funding_tx
+        .0
+        .output
+        .extend(std::iter::repeat(extra_output).take(config.max_funding_tx_outputs as usize));
+
+    // Check that it did not pass.
+    let block = anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs_with(funding_tx),
+    );
+    assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::OversizedFundingTx));
+}
+
 #[test]
 fn funding_tx_override() {
     // Actually, we can override the funding transaction by another one.
@@ -551,6 +805,211 @@ fn funding_tx_override() {
     );
 }
 
+#[test]
+fn funding_txs_batch_in_single_block() {
+    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+
+    // Add an initial funding transaction to enable anchoring.
+    let (txs, initial_funding_transaction) =
+        anchoring_testkit.create_funding_confirmation_txs(1000);
+    anchoring_testkit.inner.create_block_with_transactions(txs);
+
+    // Confirm two funding transactions of the same wallet in one block.
+    let (first_txs, first_funding_transaction) =
+        anchoring_testkit.create_funding_confirmation_txs(2000);
+    let (second_txs, _) = anchoring_testkit.create_funding_confirmation_txs(2400);
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(first_txs.iter().cloned().chain(second_txs))
+        .transactions
+        .iter()
+        .try_for_each(|tx| tx.status())
+        .expect("Each transaction should be successful.");
+    // None of them is applied, since only one of them could be spent.
+    assert_eq!(
+        unspent_funding_transaction(&anchoring_testkit).unwrap(),
+        initial_funding_transaction
+    );
+
+    // The rejected transaction keeps its confirmations, so it is applied after
+    // a single confirmation in the next block.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(first_txs.into_iter().take(1))
+        .transactions[0]
+        .status()
+        .expect("Transaction should be successful.");
+    assert_eq!(
+        unspent_funding_transaction(&anchoring_testkit).unwrap(),
+        first_funding_transaction
+    );
+}
+
+#[test]
+fn rejected_funding_tx_applied_after_extra_confirmation() {
+    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+    let quorum = anchoring_testkit
+        .actual_anchoring_config()
+        .byzantine_quorum();
+
+    // Add an initial funding transaction to enable anchoring.
+    let (txs, initial_funding_transaction) =
+        anchoring_testkit.create_funding_confirmation_txs(1000);
+    anchoring_testkit.inner.create_block_with_transactions(txs);
+
+    // Confirm two funding transactions of the same wallet by the quorum of nodes
+    // in one block, so both of them are rejected.
+    let (first_txs, first_funding_transaction) =
+        anchoring_testkit.create_funding_confirmation_txs(2000);
+    let (second_txs, _) = anchoring_testkit.create_funding_confirmation_txs(2400);
+    anchoring_testkit.inner.create_block_with_transactions(
+        first_txs
+            .iter()
+            .take(quorum)
+            .cloned()
+            .chain(second_txs.into_iter().take(quorum)),
+    );
+    assert_eq!(
+        unspent_funding_transaction(&anchoring_testkit).unwrap(),
+        initial_funding_transaction
+    );
+
+    // The confirmation of another node exceeds the quorum, and the transaction is applied.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(first_txs.into_iter().skip(quorum))
+        .transactions[0]
+        .status()
+        .expect("Transaction should be successful.");
+    assert_eq!(
+        unspent_funding_transaction(&anchoring_testkit).unwrap(),
+        first_funding_transaction
+    );
+}
+
+#[test]
+fn confirm_anchor_with_quorum() {
+    let mut testkit = AnchoringTestKit::default();
+    let signatures = testkit.create_signature_txs().into_iter().flatten();
+    testkit.inner.create_block_with_transactions(signatures);
+    let tx = testkit.last_anchoring_tx().unwrap();
+
+    let inclusion = AnchorInclusion {
+        block_hash: btc::Sha256d::new([1; 32]),
+        block_height: 100,
+    };
+    let confirmation = ConfirmAnchor {
+        txid: tx.id(),
+        block_hash: inclusion.block_hash,
+        block_height: inclusion.block_height,
+        confirmations: 1,
+    };
+    let mut confirmations = testkit.create_anchor_confirmation_txs(confirmation.clone());
+    let quorum = testkit.actual_anchoring_config().byzantine_quorum();
+    let mut rest = confirmations.split_off(quorum - 1);
+
+    // A divergent report from the last node doesn't count towards the quorum.
+    let divergent_tx = testkit
+        .create_anchor_confirmation_txs(ConfirmAnchor {
+            block_height: 101,
+            ..confirmation
+        })
+        .pop()
+        .unwrap();
+    testkit
+        .inner
+        .create_block_with_transactions(confirmations.into_iter().chain(Some(divergent_tx)))
+        .transactions
+        .iter()
+        .try_for_each(|tx| tx.status())
+        .expect("Each transaction should be successful.");
+    let snapshot = testkit.inner.snapshot();
+    assert_eq!(
+        get_anchoring_schema(&snapshot).anchor_inclusion(&tx.id()),
+        None
+    );
+
+    testkit.inner.create_block_with_transaction(rest.remove(0));
+    let snapshot = testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.anchor_inclusion(&tx.id()), Some(inclusion));
+    // There are not enough confirmations to consider the anchor final.
+    assert_eq!(schema.anchor_finality_height(&tx.id()), None);
+}
+
+#[test]
+fn confirm_anchor_finality_with_quorum() {
+    let mut testkit = AnchoringTestKit::default();
+    let signatures = testkit.create_signature_txs().into_iter().flatten();
+    testkit.inner.create_block_with_transactions(signatures);
+    let tx = testkit.last_anchoring_tx().unwrap();
+
+    let config = testkit.actual_anchoring_config();
+    let confirmation = ConfirmAnchor {
+        txid: tx.id(),
+        block_hash: btc::Sha256d::new([1; 32]),
+        block_height: 100,
+        confirmations: config.finality_confirmations,
+    };
+    let mut confirmations = testkit.create_anchor_confirmation_txs(confirmation.clone());
+    let mut rest = confirmations.split_off(config.byzantine_quorum() - 1);
+
+    // A report with insufficient confirmations from the last node doesn't count
+    // towards the quorum.
+    let shallow_tx = testkit
+        .create_anchor_confirmation_txs(ConfirmAnchor {
+            confirmations: config.finality_confirmations - 1,
+            ..confirmation.clone()
+        })
+        .pop()
+        .unwrap();
+    testkit
+        .inner
+        .create_block_with_transactions(confirmations.into_iter().chain(Some(shallow_tx)));
+    let snapshot = testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(
+        schema.anchor_inclusion(&tx.id()),
+        Some(confirmation.inclusion())
+    );
+    assert_eq!(schema.anchor_finality_height(&tx.id()), None);
+
+    testkit.inner.create_block_with_transaction(rest.remove(0));
+    let finality_height = testkit.inner.height();
+    let snapshot = testkit.inner.snapshot();
+    assert_eq!(
+        get_anchoring_schema(&snapshot).anchor_finality_height(&tx.id()),
+        Some(finality_height)
+    );
+
+    // Late reports are ignored.
+    testkit.inner.create_block_with_transaction(rest.remove(0));
+    let snapshot = testkit.inner.snapshot();
+    assert_eq!(
+        get_anchoring_schema(&snapshot).anchor_finality_height(&tx.id()),
+        Some(finality_height)
+    );
+}
+
+#[test]
+fn confirm_anchor_err_unknown_transaction() {
+    let mut testkit = AnchoringTestKit::default();
+    let tx = testkit
+        .create_anchor_confirmation_txs(ConfirmAnchor {
+            txid: btc::Sha256d::new([1; 32]),
+            block_hash: btc::Sha256d::new([2; 32]),
+            block_height: 100,
+            confirmations: 1,
+        })
+        .remove(0);
+
+    let block = testkit.inner.create_block_with_transaction(tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnknownAnchoringTransaction),
+    );
+}
+
 #[test]
 fn sign_input_err_unauthorized() {
     let mut testkit = AnchoringTestKit::default();
@@ -622,4 +1081,690 @@ fn sign_input_err_input_verification_failed() {
     );
 }
 
+/// Replaces the `S` value of the signature by `n - S`, which gives another valid,
+/// but non-canonical signature.
+fn make_high_s_signature(signature: btc::InputSignature) -> btc::InputSignature {
+    // Order of the secp256k1 curve.
+    const CURVE_ORDER: [u8; 32] = [
+        0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        0xFE, 0xBA, 0xAE, 0xDC, 0xE6, 0xAF, 0x48, 0xA0, 0x3B, 0xBF, 0xD2, 0x5E, 0x8C, 0xD0, 0x36,
+        0x41, 0x41,
+    ];
+
+    let bytes: Vec<u8> = signature.into();
+    let (sighash_type, der) = bytes.split_last().unwrap();
+    let mut compact = Signature::from_der(der).unwrap().serialize_compact();
+    let mut borrow = 0_i16;
+    for i in (0..32).rev() {
+        let mut digit = i16::from(CURVE_ORDER[i]) - i16::from(compact[32 + i]) - borrow;
+        borrow = if digit < 0 { 1 } else { 0 };
+        digit += borrow * 256;
+        compact[32 + i] = digit as u8;
+    }
+
+    let mut bytes = Signature::from_compact(&compact)
+        .unwrap()
+        .serialize_der()
+        .to_vec();
+    bytes.push(*sighash_type);
+    btc::InputSignature::from_bytes(bytes.into()).unwrap()
+}
+
+#[test]
+fn sign_input_err_non_canonical_signature() {
+    let mut testkit = AnchoringTestKit::default();
+    let us = testkit.inner.us();
+    // Create sign_input transaction for the anchoring node.
+    let tx = testkit.create_signature_tx_for_node(&us).unwrap()[0]
+        .payload()
+        .parse::<SignInput>()
+        .unwrap();
+
+    // Signature with the high S value.
+    let high_s_tx = us.service_keypair().sign_input(
+        ANCHORING_INSTANCE_ID,
+        SignInput {
+            input_signature: make_high_s_signature(tx.input_signature.clone()),
+            ..tx.clone()
+        },
+    );
+    // Signature with the unexpected sighash type.
+    let mut bytes: Vec<u8> = tx.input_signature.clone().into();
+    *bytes.last_mut().unwrap() = 0x81;
+    let sighash_tx = us.service_keypair().sign_input(
+        ANCHORING_INSTANCE_ID,
+        SignInput {
+            input_signature: btc::InputSignature::from_bytes(bytes.into()).unwrap(),
+            ..tx
+        },
+    );
+
+    let block = testkit
+        .inner
+        .create_block_with_transactions(vec![high_s_tx, sighash_tx]);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::NonCanonicalSignature)
+            .with_description_containing("high S value"),
+    );
+    assert_tx_error(
+        &block[1],
+        ErrorMatch::from_fail(&Error::NonCanonicalSignature)
+            .with_description_containing("Unexpected sighash type: 0x81"),
+    );
+}
+
+#[test]
+fn sign_input_err_unsupported_sighash_type() {
+    let mut testkit = AnchoringTestKit::default();
+    let us = testkit.inner.us();
+    // Create sign_input transaction for the anchoring node.
+    let tx = testkit.create_signature_tx_for_node(&us).unwrap()[0]
+        .payload()
+        .parse::<SignInput>()
+        .unwrap();
+    // Re-sign the multisig input with `SIGHASH_ALL | SIGHASH_ANYONECANPAY`.
+    let mut bytes: Vec<u8> = tx.input_signature.clone().into();
+    *bytes.last_mut().unwrap() = 0x81;
+    let malformed_tx = us.service_keypair().sign_input(
+        ANCHORING_INSTANCE_ID,
+        SignInput {
+            input_signature: btc::InputSignature::from_bytes(bytes.into()).unwrap(),
+            sighash_type: btc::SigHashType::AllPlusAnyoneCanPay,
+            ..tx
+        },
+    );
+    let block = testkit.inner.create_block_with_transaction(malformed_tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnsupportedSigHashType),
+    );
+}
+
+#[test]
+fn attest_key_err_invalid_attestation() {
+    let mut testkit = AnchoringTestKit::default();
+    let mut new_cfg = testkit.actual_anchoring_config();
+    new_cfg.anchoring_keys.push(testkit.add_node());
+    // Create attestation transaction for the new anchoring node.
+    let tx = testkit.create_key_attestation_txs(&new_cfg)[0].clone();
+    // Re-sign this transaction by the other keypair.
+    let malformed_tx = change_tx_signature(tx, &KeyPair::random());
+    // Commit this transaction and check status.
+    let block = testkit.inner.create_block_with_transaction(malformed_tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::InvalidKeyAttestation)
+            .with_description_containing("secp: signature failed verification"),
+    );
+}
+
+#[test]
+fn add_anchoring_node_without_attestation() {
+    let mut testkit = AnchoringTestKit::default();
+    let mut new_cfg = testkit.actual_anchoring_config();
+    new_cfg.anchoring_keys.push(testkit.add_node());
+
+    // Try to commit configuration with the unattested Bitcoin key.
+    let block = testkit.inner.create_block_with_transaction(
+        testkit.create_config_change_tx(
+            ConfigPropose::new(0, testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
+    );
+    assert!(block[0].status().is_err());
+
+    testkit.inner.create_block();
+    let snapshot = testkit.inner.snapshot();
+    assert_eq!(get_anchoring_schema(&snapshot).following_config(), None);
+}
+
+#[test]
+fn anchoring_interval_change() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // Anchor the zero and the first anchoring heights.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let latest_anchored_height = anchoring_testkit
+        .last_anchoring_tx()
+        .unwrap()
+        .anchoring_payload()
+        .unwrap()
+        .block_height;
+    assert_eq!(latest_anchored_height, Height(anchoring_interval));
+
+    // Change the anchoring interval in the middle of the anchoring interval.
+    let new_interval = anchoring_interval + 2;
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.anchoring_interval = new_interval;
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(config_change_tx);
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(schema.anchoring_epoch_start(), latest_anchored_height);
+        assert_eq!(
+            schema
+                .recover_runtime_state(snapshot.for_core())
+                .next_anchoring_height,
+            Height(anchoring_interval + new_interval)
+        );
+    }
+
+    // The next anchored block is exactly one new interval after the latest anchored one,
+    // and the heights of the new interval are not aligned to the zero height.
+    assert!(anchoring_testkit.anchoring_transaction_proposal().is_none());
+    for expected_height in &[
+        anchoring_interval + new_interval,
+        anchoring_interval + new_interval * 2,
+    ] {
+        let expected_height = Height(*expected_height);
+        anchoring_testkit
+            .inner
+            .create_blocks_until(expected_height.previous());
+        assert!(anchoring_testkit.anchoring_transaction_proposal().is_none());
+        anchoring_testkit.inner.create_block();
+
+        let proposal = anchoring_testkit
+            .anchoring_transaction_proposal()
+            .unwrap()
+            .0;
+        assert_eq!(
+            proposal.anchoring_payload().unwrap().block_height,
+            expected_height
+        );
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+        assert_eq!(
+            anchoring_testkit.last_anchoring_tx().unwrap().id(),
+            proposal.id()
+        );
+    }
+}
+
+#[test]
+fn checkpoint_filter_after_anchoring_interval_change() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // Anchor the zero and the first anchoring heights.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let latest_anchored_height = Height(anchoring_interval);
+
+    // Change the anchoring interval along with the checkpoint filter, whose checkpoints
+    // lie neither on the schedule started at the zero height nor on the new epoch one.
+    let new_interval = anchoring_interval + 2;
+    let checkpoint_filter = CheckpointFilter {
+        period: new_interval + 3,
+        offset: 3,
+    };
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.anchoring_interval = new_interval;
+    new_cfg.checkpoint_filter = Some(checkpoint_filter);
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(config_change_tx);
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(schema.anchoring_epoch_start(), latest_anchored_height);
+        assert_eq!(
+            schema.actual_config().checkpoint_filter,
+            Some(checkpoint_filter)
+        );
+    }
+
+    // Each checkpoint after the epoch start is anchored.
+    let mut expected_height = checkpoint_filter.following_checkpoint(latest_anchored_height.next());
+    for _ in 0..3 {
+        assert!(checkpoint_filter.matches(expected_height));
+        anchoring_testkit
+            .inner
+            .create_blocks_until(expected_height.previous());
+        assert!(anchoring_testkit.anchoring_transaction_proposal().is_none());
+        anchoring_testkit.inner.create_block();
+
+        let proposal = anchoring_testkit
+            .anchoring_transaction_proposal()
+            .unwrap()
+            .0;
+        assert_eq!(
+            proposal.anchoring_payload().unwrap().block_height,
+            expected_height
+        );
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+        assert_eq!(
+            anchoring_testkit.last_anchoring_tx().unwrap().id(),
+            proposal.id()
+        );
+        expected_height = Height(expected_height.0 + checkpoint_filter.period);
+    }
+}
+
+#[test]
+fn compact_storage() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.compact_storage = true;
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(config_change_tx)
+        .transactions[0]
+        .status()
+        .expect("Config proposal should be successful.");
+
+    // Anchor the zero and the first anchoring heights with the compact storage.
+    let mut proposals = Vec::new();
+    for height in &[Height(0), Height(anchoring_interval)] {
+        if anchoring_testkit.inner.height() < *height {
+            anchoring_testkit.inner.create_blocks_until(*height);
+        }
+        let proposal = anchoring_testkit
+            .anchoring_transaction_proposal()
+            .unwrap()
+            .0;
+        assert_eq!(proposal.anchoring_payload().unwrap().block_height, *height);
+        proposals.push(proposal);
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+    }
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.transactions_chain.len(), 2);
+    let mut witness_scripts = Vec::new();
+    for (index, proposal) in proposals.iter().enumerate() {
+        let stored = schema.transactions_chain.get(index as u64).unwrap();
+        assert!(stored.0.input.iter().all(|input| input.witness.is_empty()));
+        assert_eq!(stored.id(), proposal.id());
+
+        // Full transactions are restored with the same identifiers.
+        let restored = schema.anchoring_transaction(index as u64).unwrap();
+        assert_eq!(restored.id(), proposal.id());
+        assert_eq!(restored.0.input.len(), stored.0.input.len());
+        for input in &restored.0.input {
+            assert!(input.witness.len() > 2);
+            witness_scripts.push(input.witness.last().unwrap().clone());
+        }
+    }
+    // All inputs are signed by the same anchoring keys.
+    assert!(witness_scripts.windows(2).all(|pair| pair[0] == pair[1]));
+    assert_eq!(
+        anchoring_testkit.last_anchoring_tx(),
+        schema.anchoring_transaction(1)
+    );
+}
+
+#[test]
+fn fee_budget_pauses_anchoring() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // Set the fee budget, which is exceeded by the first anchoring transaction.
+    let fee_budget = FeeBudget {
+        window: anchoring_interval * 10,
+        max_fees: 1,
+    };
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.fee_budget = Some(fee_budget);
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(config_change_tx)
+        .transactions[0]
+        .status()
+        .expect("Config proposal should be successful.");
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let breach = {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        let breach = schema.fee_budget_breach().unwrap();
+        assert_eq!(breach.height, anchoring_testkit.inner.height().0);
+        assert_eq!(breach.max_fees, fee_budget.max_fees);
+        assert_eq!(
+            breach.spent_fees,
+            schema.spent_fees(fee_budget.window, Height(breach.height))
+        );
+        assert!(breach.spent_fees > fee_budget.max_fees);
+        breach
+    };
+
+    // The anchoring is paused even at the next anchoring height.
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    assert!(anchoring_testkit.anchoring_transaction_proposal().is_none());
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(
+            schema.no_proposal_reason(snapshot.for_core()),
+            NoProposalReason::FeeBudgetExceeded {
+                breach_height: Height(breach.height),
+                spent_fees: breach.spent_fees,
+                max_fees: breach.max_fees,
+            }
+        );
+    }
+
+    // The supervisor lifts the pause by applying a new configuration with the raised budget.
+    new_cfg.fee_budget = Some(FeeBudget {
+        max_fees: breach.spent_fees * 10,
+        ..fee_budget
+    });
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(config_change_tx)
+        .transactions[0]
+        .status()
+        .expect("Config proposal should be successful.");
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(schema.fee_budget_breach(), None);
+        // The fees spent before the lift are not counted anymore.
+        assert_eq!(
+            schema.spent_fees(fee_budget.window, anchoring_testkit.inner.height()),
+            0
+        );
+    }
+
+    let proposal = anchoring_testkit
+        .anchoring_transaction_proposal()
+        .unwrap()
+        .0;
+    assert_eq!(
+        proposal.anchoring_payload().unwrap().block_height,
+        Height(anchoring_interval)
+    );
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    assert_eq!(
+        anchoring_testkit.last_anchoring_tx().unwrap().id(),
+        proposal.id()
+    );
+}
+
+#[test]
+fn final_anchor_shuts_down_chain() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // Establish anchoring transactions chain.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit.inner.create_blocks_until(Height(3));
+
+    // Request the final anchor in the middle of the anchoring interval.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.final_anchor = Some(FinalAnchor { tombstone: true });
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+    );
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(config_change_tx)
+        .transactions[0]
+        .status()
+        .expect("Config proposal should be successful.");
+
+    // The latest block at the moment of the request is anchored regardless of the interval.
+    let proposal = anchoring_testkit
+        .anchoring_transaction_proposal()
+        .unwrap()
+        .0;
+    let payload = proposal.anchoring_payload().unwrap();
+    assert_eq!(payload.block_height, Height(3));
+    assert!(payload.is_final);
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let final_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(final_tx.id(), proposal.id());
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(schema.final_anchoring_height(), Some(Height(3)));
+        assert_eq!(
+            schema.completion_height(),
+            Some(anchoring_testkit.inner.height())
+        );
+    }
+
+    // No further anchoring transactions are created.
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval * 2));
+    assert!(anchoring_testkit.anchoring_transaction_proposal().is_none());
+    assert_eq!(anchoring_testkit.last_anchoring_tx().unwrap(), final_tx);
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(
+            schema.no_proposal_reason(snapshot.for_core()),
+            NoProposalReason::Completed {
+                completion_height: schema.completion_height().unwrap()
+            }
+        );
+    }
+
+    // The anchoring chain can't be reconfigured anymore.
+    new_cfg.final_anchor = None;
+    let block = anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
+    );
+    assert!(block[0].status().is_err());
+}
+
+#[test]
+fn final_anchor_err_address_change() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    new_cfg.final_anchor = Some(FinalAnchor { tombstone: false });
+
+    let attestation_txs = anchoring_testkit.create_key_attestation_txs(&new_cfg);
+    let config_change_tx = anchoring_testkit.create_config_change_tx(
+        ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+            .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+    );
+    let block = anchoring_testkit
+        .inner
+        .create_block_with_transactions(attestation_txs.into_iter().chain(Some(config_change_tx)));
+    assert!(block.transactions.last().unwrap().status().is_err());
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    assert_eq!(
+        get_anchoring_schema(&snapshot).final_anchoring_height(),
+        None
+    );
+}
+
+#[test]
+fn recover_runtime_state() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let state = get_anchoring_schema(&snapshot).recover_runtime_state(snapshot.for_core());
+    assert!(state.anchoring_state.is_regular());
+    assert_eq!(state.latest_anchored_height, None);
+    assert_eq!(state.next_anchoring_height, Height(0));
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+
+    // The state recovered from a fresh snapshot should match the actual one.
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let state = get_anchoring_schema(&snapshot).recover_runtime_state(snapshot.for_core());
+    assert_eq!(state.latest_anchored_height, Some(Height(0)));
+    assert_eq!(state.next_anchoring_height, Height(anchoring_interval));
+    assert_eq!(
+        state.proposal.unwrap().unwrap().0,
+        anchoring_testkit
+            .anchoring_transaction_proposal()
+            .unwrap()
+            .0
+    );
+}
+
+#[test]
+fn recover_runtime_state_after_restart() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+
+    // Only the first anchoring node signs the proposal before the restart.
+    let proposal = anchoring_testkit
+        .anchoring_transaction_proposal()
+        .unwrap()
+        .0;
+    let mut signatures = anchoring_testkit.create_signature_txs().into_iter();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures.next().unwrap());
+
+    let mut anchoring_testkit = anchoring_testkit.restart();
+    // The service keeps no state in memory, so the restarted node derives the same state
+    // from the schema.
+    anchoring_testkit.inner.create_block();
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    let state = schema.recover_runtime_state(snapshot.for_core());
+    assert_eq!(state.next_anchoring_height, Height(anchoring_interval));
+    assert_eq!(state.proposal.unwrap().unwrap().0, proposal);
+    // The recovered proposal matches the one built from scratch.
+    let rebuilt_proposal = schema
+        .proposed_anchoring_transaction(snapshot.for_core(), &schema.actual_state())
+        .unwrap()
+        .unwrap()
+        .0;
+    assert_eq!(rebuilt_proposal, proposal);
+
+    // Signatures collected before the restart are taken into account.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures.flatten());
+    assert_eq!(
+        anchoring_testkit.last_anchoring_tx().unwrap().id(),
+        proposal.id()
+    );
+}
+
 // TODO Implement tests for anchoring recovery [ECR-3581]